@@ -0,0 +1,232 @@
+//! 证书信息
+
+use super::conv;
+use serde_json::{json, Value};
+use std::{io, mem};
+use windows::Win32::{
+    Foundation::FILETIME, Security::Cryptography::{
+        CertCloseStore, CertCreateCertificateContext, CertFindCertificateInStore, CertFreeCertificateContext, CertGetCertificateContextProperty, CertNameToStrW, CertOpenStore, CERT_CONTEXT, CERT_FIND_SHA1_HASH, CERT_KEY_PROV_INFO_PROP_ID, CERT_OPEN_STORE_FLAGS, CERT_SHA256_HASH_PROP_ID, CERT_STORE_OPEN_EXISTING_FLAG, CERT_STORE_PROV_SYSTEM_W, CERT_STORE_READONLY_FLAG, CERT_SYSTEM_STORE_CURRENT_USER_ID, CERT_SYSTEM_STORE_LOCAL_MACHINE_ID, CERT_SYSTEM_STORE_LOCATION_SHIFT, CERT_X500_NAME_STR, CRYPT_INTEGER_BLOB, HCERTSTORE, HCRYPTPROV_LEGACY, PKCS_7_ASN_ENCODING, X509_ASN_ENCODING
+    }
+};
+
+/// `1601-01-01`至`1970-01-01`的秒数
+const FILETIME_UNIX_EPOCH: i64 = 11_644_473_600;
+
+/// 证书信息
+pub struct CertInfo {
+    pub subject: String,
+    pub issuer: String,
+    /// 生效时间(`UTC`)
+    pub not_before: String,
+    /// 过期时间(`UTC`)
+    pub not_after: String,
+    /// `SHA-256`指纹(小写十六进制)
+    pub sha256: String
+}
+
+impl CertInfo {
+    /// 解析`DER`编码的证书
+    pub fn from_der(der: &[u8]) -> Option<CertInfo> {
+        unsafe {
+            let ctx = CertCreateCertificateContext(X509_ASN_ENCODING | PKCS_7_ASN_ENCODING, der);
+            if ctx.is_null() {
+                return None;
+            }
+            let info = &*(*ctx).pCertInfo;
+            let rv = CertInfo {
+                subject: name_to_string(&info.Subject),
+                issuer: name_to_string(&info.Issuer),
+                not_before: format_filetime(&info.NotBefore),
+                not_after: format_filetime(&info.NotAfter),
+                sha256: sha256(ctx)
+            };
+            CertFreeCertificateContext(Some(ctx));
+            Some(rv)
+        }
+    }
+
+    /// 转换为`JSON`
+    ///
+    /// 包含`subject`、`issuer`、`not_before`、`not_after`以及`sha256`
+    pub fn to_json(&self) -> Value {
+        json!({
+            "subject": self.subject,
+            "issuer": self.issuer,
+            "not_before": self.not_before,
+            "not_after": self.not_after,
+            "sha256": self.sha256
+        })
+    }
+}
+
+/// 系统证书存储中的证书
+///
+/// # Description
+///
+/// 直接使用证书关联的私钥(`CNG`/`CSP`)，私钥不需要允许导出
+pub struct StoreCert(*const CERT_CONTEXT);
+
+//证书上下文可在线程间传递
+unsafe impl Send for StoreCert {}
+
+impl StoreCert {
+    /// 在系统证书存储中查找证书
+    ///
+    /// # Parameters
+    ///
+    /// - `store_name` 存储名称，默认为当前用户的存储，`LocalMachine\`前缀表示本地计算机的存储，如`My`、`LocalMachine\My`
+    /// - `thumbprint` `SHA-1`指纹(十六进制，忽略空格与`:`)
+    ///
+    /// # Returns
+    ///
+    /// 证书没有关联私钥时失败
+    pub fn find(store_name: &str, thumbprint: &str) -> io::Result<StoreCert> {
+        let (location, name) = match store_name.split_once('\\') {
+            Some((location, name)) if location.eq_ignore_ascii_case("LocalMachine") => {
+                (CERT_SYSTEM_STORE_LOCAL_MACHINE_ID, name)
+            },
+            Some((location, name)) if location.eq_ignore_ascii_case("CurrentUser") => {
+                (CERT_SYSTEM_STORE_CURRENT_USER_ID, name)
+            },
+            Some(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid store: {store_name}")
+                ));
+            },
+            None => (CERT_SYSTEM_STORE_CURRENT_USER_ID, store_name)
+        };
+        //从证书管理器复制的指纹可能包含`U+200E`
+        let mut hash = conv::hex_decode(&thumbprint.replace('\u{200e}', ""))
+            .filter(|hash| hash.len() == 20)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("invalid thumbprint: {thumbprint}"))
+            })?;
+        let name = to_wide(name);
+        unsafe {
+            //证书上下文持有存储的引用，关闭后仍然有效
+            let store = Store(
+                CertOpenStore(
+                    CERT_STORE_PROV_SYSTEM_W,
+                    X509_ASN_ENCODING | PKCS_7_ASN_ENCODING,
+                    HCRYPTPROV_LEGACY::default(),
+                    CERT_OPEN_STORE_FLAGS(
+                        (location << CERT_SYSTEM_STORE_LOCATION_SHIFT) |
+                            CERT_STORE_OPEN_EXISTING_FLAG.0 |
+                            CERT_STORE_READONLY_FLAG.0
+                    ),
+                    Some(name.as_ptr() as _)
+                )
+                .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?
+            );
+            let blob = CRYPT_INTEGER_BLOB {
+                cbData: hash.len() as u32,
+                pbData: hash.as_mut_ptr()
+            };
+            let ctx = CertFindCertificateInStore(
+                store.0,
+                X509_ASN_ENCODING | PKCS_7_ASN_ENCODING,
+                0,
+                CERT_FIND_SHA1_HASH,
+                Some(&blob as *const _ as _),
+                None
+            );
+            if ctx.is_null() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("certificate not found: {thumbprint}")
+                ));
+            }
+            let cert = StoreCert(ctx);
+            //握手时通过私钥的提供程序信息获取私钥
+            let mut len = 0;
+            if CertGetCertificateContextProperty(cert.0, CERT_KEY_PROV_INFO_PROP_ID, None, &mut len) == false
+            {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "certificate has no private key"));
+            }
+            Ok(cert)
+        }
+    }
+
+    /// 转换为`native-tls`的客户端证书
+    ///
+    /// NOTE `native-tls`没有提供从证书上下文创建客户端证书的接口，`Windows`下其内部只有一个证书上下文(`schannel::CertContext`)，
+    /// 依赖该内存布局转换(`Cargo.toml`中固定了版本)，升级`native-tls`时需要确认
+    pub fn into_identity(self) -> native_tls::Identity {
+        const _: () =
+            assert!(mem::size_of::<native_tls::Identity>() == mem::size_of::<*const CERT_CONTEXT>());
+        let ctx = self.0;
+        //所有权转移到`Identity`，由其释放
+        mem::forget(self);
+        unsafe { mem::transmute::<*const CERT_CONTEXT, native_tls::Identity>(ctx) }
+    }
+}
+
+impl Drop for StoreCert {
+    fn drop(&mut self) {
+        unsafe {
+            CertFreeCertificateContext(Some(self.0));
+        }
+    }
+}
+
+/// 证书存储句柄，释放时关闭
+struct Store(HCERTSTORE);
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CertCloseStore(self.0, 0);
+        }
+    }
+}
+
+fn to_wide(s: &str) -> Vec<u16> { s.encode_utf16().chain(Some(0)).collect() }
+
+unsafe fn name_to_string(name: &CRYPT_INTEGER_BLOB) -> String {
+    let len = CertNameToStrW(X509_ASN_ENCODING, name, CERT_X500_NAME_STR, None);
+    if len <= 1 {
+        return String::new();
+    }
+    let mut buf = vec![0u16; len as usize];
+    let len = CertNameToStrW(X509_ASN_ENCODING, name, CERT_X500_NAME_STR, Some(&mut buf));
+    //去掉结尾的`\0`
+    String::from_utf16_lossy(&buf[..(len as usize).saturating_sub(1)])
+}
+
+unsafe fn sha256(ctx: *const CERT_CONTEXT) -> String {
+    let mut hash = [0u8; 32];
+    let mut len = hash.len() as u32;
+    if CertGetCertificateContextProperty(
+        ctx,
+        CERT_SHA256_HASH_PROP_ID,
+        Some(hash.as_mut_ptr() as _),
+        &mut len
+    ) == false
+    {
+        return String::new();
+    }
+    hash[..len as usize].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 格式化为`yyyy-mm-dd hh:mm:ss`
+fn format_filetime(ft: &FILETIME) -> String {
+    let ticks = ((ft.dwHighDateTime as u64) << 32) | ft.dwLowDateTime as u64;
+    let secs = (ticks / 10_000_000) as i64 - FILETIME_UNIX_EPOCH;
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    //公历日期(`days_from_civil`的逆运算)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 {
+        mp + 3
+    } else {
+        mp - 9
+    };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
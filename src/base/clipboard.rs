@@ -0,0 +1,262 @@
+//! `Windows`剪贴板
+
+use std::{io, mem, ptr, slice, thread, time::Duration};
+use windows::{
+    core::HSTRING, Win32::{
+        Foundation::{HANDLE, HGLOBAL, HWND}, Graphics::{
+            Gdi::{BITMAPINFOHEADER, BI_RGB}, Imaging::{
+                CLSID_WICImagingFactory, GUID_WICPixelFormat32bppBGRA, IWICImagingFactory, WICConvertBitmapSource, WICDecodeMetadataCacheOnDemand
+            }
+        }, System::{
+            Com::{
+                CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED
+            }, DataExchange::{
+                CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard, RegisterClipboardFormatW, SetClipboardData
+            }, Memory::{GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE}, Ole::{CF_DIB, CF_UNICODETEXT}
+        }, UI::WindowsAndMessaging::{
+            CreateWindowExW, DestroyWindow, HWND_MESSAGE, WINDOW_EX_STYLE, WINDOW_STYLE
+        }
+    }
+};
+
+/// 打开剪贴板的重试次数(剪贴板可能正被其它程序占用)
+const OPEN_RETRIES: u32 = 20;
+/// 打开剪贴板的重试间隔
+const OPEN_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+/// `PNG`文件签名
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// 剪贴板内容
+pub enum Content {
+    /// 文本
+    Text(String),
+    /// 图片文件数据(`PNG`、`JPEG`、`GIF`、`BMP`、`TIFF`等)
+    Image(Vec<u8>),
+    /// 自定义格式
+    Data {
+        format: String,
+        data: Vec<u8>
+    }
+}
+
+/// 写入剪贴板，替换原有的内容
+///
+/// # Description
+///
+/// 图片通过`WIC`解码后以`CF_DIB`格式写入，源数据为`PNG`时同时写入`PNG`格式以保留透明度
+pub fn set(content: &Content) -> io::Result<()> {
+    //先在打开剪贴板前准备好数据，减少占用剪贴板的时间
+    let mut items = Vec::new();
+    match content {
+        Content::Text(text) => {
+            let wide: Vec<u16> = text.encode_utf16().chain(Some(0)).collect();
+            let bytes = unsafe { slice::from_raw_parts(wide.as_ptr() as *const u8, wide.len() * 2) };
+            items.push((CF_UNICODETEXT.0 as u32, bytes.to_vec()));
+        },
+        Content::Image(data) => {
+            items.push((CF_DIB.0 as u32, decode_dib(data)?));
+            if data.starts_with(PNG_SIGNATURE) {
+                items.push((register_format("PNG")?, data.clone()));
+            }
+        },
+        Content::Data {
+            format,
+            data
+        } => items.push((register_format(format)?, data.clone()))
+    }
+    let _clipboard = Clipboard::open()?;
+    unsafe {
+        if EmptyClipboard() == false {
+            return Err(io::Error::last_os_error());
+        }
+        for (format, data) in items {
+            let hmem = alloc_global(&data)?;
+            if let Err(e) = SetClipboardData(format, HANDLE(hmem.0)) {
+                //写入失败时内存仍归调用方所有
+                let _ = GlobalFree(hmem);
+                return Err(e.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 读取剪贴板中的文本，没有文本时返回`None`
+pub fn get_text() -> io::Result<Option<String>> {
+    let data = match get_data(CF_UNICODETEXT.0 as u32)? {
+        Some(data) => data,
+        None => return Ok(None)
+    };
+    let wide: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    let len = wide.iter().position(|c| *c == 0).unwrap_or(wide.len());
+    Ok(Some(String::from_utf16_lossy(&wide[..len])))
+}
+
+/// 读取剪贴板中的指定格式的数据，不存在时返回`None`
+pub fn get_data(format: u32) -> io::Result<Option<Vec<u8>>> {
+    if unsafe { IsClipboardFormatAvailable(format) } == false {
+        return Ok(None);
+    }
+    let _clipboard = Clipboard::open()?;
+    unsafe {
+        let handle = match GetClipboardData(format) {
+            Ok(handle) => handle,
+            Err(_) => return Ok(None)
+        };
+        let hmem = HGLOBAL(handle.0);
+        let ptr = GlobalLock(hmem);
+        if ptr.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let data = slice::from_raw_parts(ptr as *const u8, GlobalSize(hmem)).to_vec();
+        let _ = GlobalUnlock(hmem);
+        Ok(Some(data))
+    }
+}
+
+/// 剪贴板中是否有指定格式的数据
+///
+/// # Parameters
+///
+/// - `format` 格式名称，`text`和`image`分别表示文本与位图，其它为自定义格式
+pub fn has_format(format: &str) -> bool {
+    let format = match format.to_ascii_lowercase().as_str() {
+        "text" => CF_UNICODETEXT.0 as u32,
+        "image" => CF_DIB.0 as u32,
+        _ => {
+            match register_format(format) {
+                Ok(format) => format,
+                Err(_) => return false
+            }
+        },
+    };
+    unsafe { IsClipboardFormatAvailable(format) != false }
+}
+
+/// 注册(或获取已注册的)自定义剪贴板格式
+pub fn register_format(name: &str) -> io::Result<u32> {
+    match unsafe { RegisterClipboardFormatW(&HSTRING::from(name)) } {
+        0 => Err(io::Error::last_os_error()),
+        format => Ok(format)
+    }
+}
+
+/// 打开的剪贴板
+///
+/// 使用仅消息窗口作为剪贴板所有者(`OpenClipboard(NULL)`时`SetClipboardData`会失败)，释放时关闭剪贴板并销毁窗口，
+/// 必须在同一线程中使用
+struct Clipboard {
+    hwnd: HWND
+}
+
+impl Clipboard {
+    fn open() -> io::Result<Clipboard> {
+        let hwnd = unsafe {
+            CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                &HSTRING::from("STATIC"),
+                None,
+                WINDOW_STYLE(0),
+                0,
+                0,
+                0,
+                0,
+                HWND_MESSAGE,
+                None,
+                None,
+                None
+            )
+        };
+        if hwnd.0 == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut retries = 0;
+        loop {
+            if unsafe { OpenClipboard(hwnd) } != false {
+                return Ok(Clipboard {
+                    hwnd
+                });
+            }
+            retries += 1;
+            if retries >= OPEN_RETRIES {
+                let err = io::Error::last_os_error();
+                unsafe {
+                    DestroyWindow(hwnd);
+                }
+                return Err(err);
+            }
+            thread::sleep(OPEN_RETRY_INTERVAL);
+        }
+    }
+}
+
+impl Drop for Clipboard {
+    fn drop(&mut self) {
+        unsafe {
+            CloseClipboard();
+            DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+/// 复制数据到可移动的全局内存
+unsafe fn alloc_global(data: &[u8]) -> io::Result<HGLOBAL> {
+    let hmem = GlobalAlloc(GMEM_MOVEABLE, data.len().max(1))?;
+    let ptr = GlobalLock(hmem);
+    if ptr.is_null() {
+        let err = io::Error::last_os_error();
+        let _ = GlobalFree(hmem);
+        return Err(err);
+    }
+    ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut u8, data.len());
+    let _ = GlobalUnlock(hmem);
+    Ok(hmem)
+}
+
+/// 使用`WIC`解码图片并转换为`32`位`CF_DIB`格式(`BITMAPINFOHEADER` + 自下而上的`BGRA`像素)
+fn decode_dib(data: &[u8]) -> io::Result<Vec<u8>> {
+    unsafe {
+        //线程已按其它模式初始化时直接使用
+        let com_init = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+        let rv = (|| -> io::Result<Vec<u8>> {
+            let factory: IWICImagingFactory =
+                CoCreateInstance(&CLSID_WICImagingFactory, None, CLSCTX_INPROC_SERVER)?;
+            let stream = factory.CreateStream()?;
+            stream.InitializeFromMemory(data)?;
+            let decoder =
+                factory.CreateDecoderFromStream(&stream, ptr::null(), WICDecodeMetadataCacheOnDemand)?;
+            let frame = decoder.GetFrame(0)?;
+            let bitmap = WICConvertBitmapSource(&GUID_WICPixelFormat32bppBGRA, &frame)?;
+            let (mut width, mut height) = (0u32, 0u32);
+            bitmap.GetSize(&mut width, &mut height)?;
+            let stride = width as usize * 4;
+            let mut pixels = vec![0u8; stride * height as usize];
+            bitmap.CopyPixels(ptr::null(), stride as u32, &mut pixels)?;
+
+            let header = BITMAPINFOHEADER {
+                biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width as i32,
+                biHeight: height as i32,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0 as u32,
+                biSizeImage: pixels.len() as u32,
+                ..Default::default()
+            };
+            let mut dib = Vec::with_capacity(mem::size_of::<BITMAPINFOHEADER>() + pixels.len());
+            dib.extend_from_slice(slice::from_raw_parts(
+                &header as *const BITMAPINFOHEADER as *const u8,
+                mem::size_of::<BITMAPINFOHEADER>()
+            ));
+            //`DIB`的行顺序为自下而上
+            for row in pixels.chunks_exact(stride.max(1)).rev() {
+                dib.extend_from_slice(row);
+            }
+            Ok(dib)
+        })();
+        if com_init {
+            CoUninitialize();
+        }
+        rv
+    }
+}
@@ -0,0 +1,229 @@
+//! 全局默认配置
+//!
+//! 通过`pfwxLoadConfig`从`JSON`或`INI`文件加载，作为新建的`nx_httpconfig`、`nx_mqttconfig`的初始配置
+
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap, fs, io, path::Path, sync::{Arc, RwLock}, time::Duration
+};
+
+static CONFIG: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+
+/// 全局默认配置
+#[derive(Debug, Default)]
+pub struct Config {
+    pub http: HttpDefaults,
+    pub mqtt: MqttDefaults,
+    pub log: LogDefaults
+}
+
+/// `HTTP`默认配置(`[http]`)
+#[derive(Debug, Default)]
+pub struct HttpDefaults {
+    /// `timeout` 请求超时(秒)
+    pub timeout: Option<Duration>,
+    /// `connect_timeout` 连接超时(秒)
+    pub connect_timeout: Option<Duration>,
+    /// `proxy` 代理地址
+    pub proxy: Option<String>,
+    /// `proxy_user` 代理用户名
+    pub proxy_user: Option<String>,
+    /// `proxy_password` 代理密码
+    pub proxy_password: Option<String>,
+    /// `user_agent`
+    pub user_agent: Option<String>,
+    /// `https_only` 只允许`HTTPS`
+    pub https_only: Option<bool>,
+    /// `accept_invalid_cert` 接受无效的证书
+    pub accept_invalid_cert: Option<bool>,
+    /// `accept_invalid_host` 接受与证书不匹配的主机名
+    pub accept_invalid_host: Option<bool>,
+    /// `sys_root_certificate` 使用系统根证书
+    pub sys_root_certificate: Option<bool>,
+    /// `concurrency` 异步请求最大并发数
+    pub concurrency: Option<u32>,
+    /// `retry` 失败重试次数
+    pub retry: Option<u32>,
+    /// `retry_delay` 首次重试的等待时间(秒)
+    pub retry_delay: Option<Duration>
+}
+
+/// `MQTT`默认配置(`[mqtt]`)
+#[derive(Debug, Default)]
+pub struct MqttDefaults {
+    /// `auto_reconnect` 自动重连
+    pub auto_reconnect: Option<bool>,
+    /// `reconnect_min` 重连最小间隔(秒)
+    pub reconnect_min: Option<Duration>,
+    /// `reconnect_max` 重连最大间隔(秒)
+    pub reconnect_max: Option<Duration>,
+    /// `timeout` 连接超时(秒)
+    pub timeout: Option<Duration>,
+    /// `keep_alive` 心跳间隔(秒)
+    pub keep_alive: Option<Duration>,
+    /// `clean_session` 清除会话
+    pub clean_session: Option<bool>
+}
+
+/// 运行时日志配置(`[log]`)
+#[derive(Debug, Default)]
+pub struct LogDefaults {
+    /// `path` 日志文件路径
+    pub path: Option<String>,
+    /// `level` 日志级别
+    pub level: Option<String>,
+    /// `rolling` 日志文件大小上限(MB)
+    pub rolling: Option<u32>
+}
+
+/// 当前的全局配置
+pub fn current() -> Arc<Config> { CONFIG.read().unwrap().clone().unwrap_or_default() }
+
+/// 是否已加载全局配置
+pub fn is_loaded() -> bool { CONFIG.read().unwrap().is_some() }
+
+/// 替换全局配置
+pub fn set(cfg: Config) { *CONFIG.write().unwrap() = Some(Arc::new(cfg)); }
+
+/// 从文件加载配置
+///
+/// # Description
+///
+/// 扩展名为`.json`或内容以`{`开头时按`JSON`解析(`{"http":{"timeout":30}}`)，否则按`INI`解析(`[http]`下的`timeout=30`)，
+/// 忽略未知的节和键
+pub fn load(path: impl AsRef<Path>) -> io::Result<Config> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    let text = text.trim_start_matches('\u{feff}');
+    let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) ||
+        text.trim_start().starts_with('{');
+    let sections = if is_json {
+        parse_json(text)?
+    } else {
+        parse_ini(text)
+    };
+    let empty = HashMap::new();
+    let http = Section(sections.get("http").unwrap_or(&empty));
+    let mqtt = Section(sections.get("mqtt").unwrap_or(&empty));
+    let log = Section(sections.get("log").unwrap_or(&empty));
+    Ok(Config {
+        http: HttpDefaults {
+            timeout: http.secs("timeout")?,
+            connect_timeout: http.secs("connect_timeout")?,
+            proxy: http.string("proxy"),
+            proxy_user: http.string("proxy_user"),
+            proxy_password: http.string("proxy_password"),
+            user_agent: http.string("user_agent"),
+            https_only: http.bool("https_only")?,
+            accept_invalid_cert: http.bool("accept_invalid_cert")?,
+            accept_invalid_host: http.bool("accept_invalid_host")?,
+            sys_root_certificate: http.bool("sys_root_certificate")?,
+            concurrency: http.uint("concurrency")?,
+            retry: http.uint("retry")?,
+            retry_delay: http.secs("retry_delay")?
+        },
+        mqtt: MqttDefaults {
+            auto_reconnect: mqtt.bool("auto_reconnect")?,
+            reconnect_min: mqtt.secs("reconnect_min")?,
+            reconnect_max: mqtt.secs("reconnect_max")?,
+            timeout: mqtt.secs("timeout")?,
+            keep_alive: mqtt.secs("keep_alive")?,
+            clean_session: mqtt.bool("clean_session")?
+        },
+        log: LogDefaults {
+            path: log.string("path"),
+            level: log.string("level"),
+            rolling: log.uint("rolling")?
+        }
+    })
+}
+
+/// 节名 -> (键 -> 值)，名称均为小写
+type Sections = HashMap<String, HashMap<String, String>>;
+
+fn parse_json(text: &str) -> io::Result<Sections> {
+    let root: Map<String, Value> =
+        serde_json::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut sections = Sections::new();
+    for (name, section) in root {
+        let Value::Object(section) = section else {
+            continue;
+        };
+        let entries = sections.entry(name.to_ascii_lowercase()).or_default();
+        for (key, value) in section {
+            let value = match value {
+                Value::Null => continue,
+                Value::String(value) => value,
+                value => value.to_string()
+            };
+            entries.insert(key.to_ascii_lowercase(), value);
+        }
+    }
+    Ok(sections)
+}
+
+fn parse_ini(text: &str) -> Sections {
+    let mut sections = Sections::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            current = name.trim().to_ascii_lowercase();
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim();
+            //去除引号
+            let value = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value);
+            sections
+                .entry(current.clone())
+                .or_default()
+                .insert(key.trim().to_ascii_lowercase(), value.to_owned());
+        }
+    }
+    sections
+}
+
+/// 配置节
+struct Section<'a>(&'a HashMap<String, String>);
+
+impl Section<'_> {
+    fn string(&self, key: &str) -> Option<String> {
+        self.0.get(key).filter(|value| !value.is_empty()).cloned()
+    }
+
+    fn bool(&self, key: &str) -> io::Result<Option<bool>> {
+        let Some(value) = self.string(key) else {
+            return Ok(None);
+        };
+        match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(Some(true)),
+            "false" | "no" | "off" | "0" => Ok(Some(false)),
+            _ => Err(invalid_value(key, &value))
+        }
+    }
+
+    fn uint(&self, key: &str) -> io::Result<Option<u32>> {
+        let Some(value) = self.string(key) else {
+            return Ok(None);
+        };
+        value.parse().map(Some).map_err(|_| invalid_value(key, &value))
+    }
+
+    fn secs(&self, key: &str) -> io::Result<Option<Duration>> {
+        let Some(value) = self.string(key) else {
+            return Ok(None);
+        };
+        match value.parse::<f64>() {
+            Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(Some(Duration::from_secs_f64(secs))),
+            _ => Err(invalid_value(key, &value))
+        }
+    }
+}
+
+fn invalid_value(key: &str, value: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid value of '{key}': {value}"))
+}
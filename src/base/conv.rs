@@ -1,5 +1,7 @@
 use pbni::primitive::pblong;
-use std::borrow::Cow;
+use std::{
+    borrow::Cow, io::{self, Read}
+};
 
 pub const ENCODING_UNKNOWN: pblong = 0;
 pub const ENCODING_UTF8: pblong = 1;
@@ -19,6 +21,24 @@ pub const ENCODING_ISO88593: pblong = 10;
 pub const ENCODING_LATIN3: pblong = 10;
 pub const ENCODING_ISO2022JP: pblong = 11;
 pub const ENCODING_ISO2022KR: pblong = 12;
+pub const ENCODING_SHIFTJIS: pblong = 13;
+pub const ENCODING_EUCKR: pblong = 14;
+pub const ENCODING_WINDOWS1250: pblong = 15;
+pub const ENCODING_WINDOWS1251: pblong = 16;
+pub const ENCODING_WINDOWS1252: pblong = 17;
+pub const ENCODING_WINDOWS1253: pblong = 18;
+pub const ENCODING_WINDOWS1254: pblong = 19;
+pub const ENCODING_WINDOWS1255: pblong = 20;
+pub const ENCODING_WINDOWS1256: pblong = 21;
+pub const ENCODING_WINDOWS1257: pblong = 22;
+pub const ENCODING_WINDOWS1258: pblong = 23;
+pub const ENCODING_UTF32: pblong = 24;
+pub const ENCODING_UTF32LE: pblong = 24;
+pub const ENCODING_UTF32BE: pblong = 25;
+
+/// 编码检测的采样长度
+#[cfg(feature = "encoding")]
+const DETECT_SAMPLE_SIZE: usize = 64 * 1024;
 
 fn codepage(encoding: pblong) -> usize {
     match encoding {
@@ -34,6 +54,11 @@ fn codepage(encoding: pblong) -> usize {
         ENCODING_ISO88593 => 28593,
         ENCODING_ISO2022JP => 50220,
         ENCODING_ISO2022KR => 50225,
+        ENCODING_SHIFTJIS => 932,
+        ENCODING_EUCKR => 949,
+        ENCODING_WINDOWS1250..=ENCODING_WINDOWS1258 => 1250 + (encoding - ENCODING_WINDOWS1250) as usize,
+        ENCODING_UTF32LE => 12000,
+        ENCODING_UTF32BE => 12001,
         _ => 0
     }
 }
@@ -43,6 +68,11 @@ fn codepage(encoding: pblong) -> usize {
 /// NOTE 默认`utf-8`
 #[cfg(feature = "encoding")]
 pub fn encode(data: &str, encoding: pblong) -> Cow<[u8]> {
+    match encoding {
+        ENCODING_UTF32LE => return Cow::Owned(data.chars().flat_map(|c| (c as u32).to_le_bytes()).collect()),
+        ENCODING_UTF32BE => return Cow::Owned(data.chars().flat_map(|c| (c as u32).to_be_bytes()).collect()),
+        _ => {}
+    }
     let codec =
         encoding::label::encoding_from_windows_code_page(codepage(encoding)).unwrap_or(encoding::all::UTF_8);
     if codec.name() == "utf-8" {
@@ -57,6 +87,11 @@ pub fn encode(data: &str, encoding: pblong) -> Cow<[u8]> {
 /// NOTE 默认`utf-8`
 #[cfg(feature = "encoding")]
 pub fn decode(data: &[u8], encoding: pblong) -> Cow<str> {
+    match encoding {
+        ENCODING_UTF32LE => return Cow::Owned(decode_utf32(data, u32::from_le_bytes)),
+        ENCODING_UTF32BE => return Cow::Owned(decode_utf32(data, u32::from_be_bytes)),
+        _ => {}
+    }
     let codec =
         encoding::label::encoding_from_windows_code_page(codepage(encoding)).unwrap_or(encoding::all::UTF_8);
     if codec.name() == "utf-8" {
@@ -78,3 +113,268 @@ pub fn decode_by_charset<'a>(data: &'a [u8], charset: &str) -> Cow<'a, str> {
         codec.decode(&data, encoding::DecoderTrap::Replace).map(Cow::from).unwrap_or_default()
     }
 }
+
+/// 自动检测编码并解码
+///
+/// NOTE 去除`BOM`
+#[cfg(feature = "encoding")]
+pub fn decode_auto(data: &[u8]) -> Cow<str> {
+    match detect_bom(data) {
+        Some((encoding, len)) => decode(&data[len..], encoding),
+        None => decode(data, detect_encoding(data))
+    }
+}
+
+/// 通过`BOM`检测编码
+///
+/// # Returns
+///
+/// 编码及`BOM`的长度
+pub fn detect_bom(data: &[u8]) -> Option<(pblong, usize)> {
+    //UTF-32LE的BOM以UTF-16LE的BOM开头，需要优先检测
+    if data.starts_with(b"\xFF\xFE\x00\x00") {
+        Some((ENCODING_UTF32LE, 4))
+    } else if data.starts_with(b"\x00\x00\xFE\xFF") {
+        Some((ENCODING_UTF32BE, 4))
+    } else if data.starts_with(b"\xEF\xBB\xBF") {
+        Some((ENCODING_UTF8, 3))
+    } else if data.starts_with(b"\xFF\xFE") {
+        Some((ENCODING_UTF16LE, 2))
+    } else if data.starts_with(b"\xFE\xFF") {
+        Some((ENCODING_UTF16BE, 2))
+    } else {
+        None
+    }
+}
+
+/// 检测数据的编码
+///
+/// # Description
+///
+/// 1. `BOM`
+/// 2. 无`BOM`的`UTF-32`/`UTF-16`(根据零字节的分布)
+/// 3. `UTF-8`(纯ASCII也视为`UTF-8`)
+/// 4. 依次尝试`GBK`/`Shift-JIS`/`EUC-KR`/`BIG5`，选择字符分布最合理的编码
+/// 5. 以上均不符合时返回`ENCODING_ANSI`
+///
+/// NOTE 仅采样前64KB数据
+#[cfg(feature = "encoding")]
+pub fn detect_encoding(data: &[u8]) -> pblong {
+    if let Some((encoding, _)) = detect_bom(data) {
+        return encoding;
+    }
+    let sample = &data[..data.len().min(DETECT_SAMPLE_SIZE)];
+    if let Some(encoding) = detect_wide(sample) {
+        return encoding;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return ENCODING_UTF8,
+        //采样截断了末尾的多字节字符
+        Err(e) if e.error_len().is_none() && sample.len() < data.len() => return ENCODING_UTF8,
+        Err(_) => {}
+    }
+    let mut rv = (ENCODING_ANSI, 0);
+    for encoding in [ENCODING_GBK, ENCODING_SHIFTJIS, ENCODING_EUCKR, ENCODING_BIG5] {
+        if let Some(score) = score_legacy(sample, encoding, sample.len() < data.len()) {
+            if score > rv.1 {
+                rv = (encoding, score);
+            }
+        }
+    }
+    rv.0
+}
+
+/// 检测无`BOM`的`UTF-32`/`UTF-16`
+///
+/// ASCII字符的高位字节为零
+#[cfg(feature = "encoding")]
+fn detect_wide(sample: &[u8]) -> Option<pblong> {
+    if sample.len() >= 4 && sample.len() % 4 == 0 {
+        let chunks = || sample.chunks_exact(4);
+        let count = sample.len() / 4;
+        if chunks().all(|c| c[2] == 0 && c[3] == 0) && chunks().filter(|c| c[1] == 0).count() * 2 > count {
+            return Some(ENCODING_UTF32LE);
+        }
+        if chunks().all(|c| c[0] == 0 && c[1] == 0) && chunks().filter(|c| c[2] == 0).count() * 2 > count {
+            return Some(ENCODING_UTF32BE);
+        }
+    }
+    if sample.len() >= 2 && sample.len() % 2 == 0 {
+        let zero_odd = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+        let zero_even = sample.iter().step_by(2).filter(|b| **b == 0).count();
+        let half = sample.len() / 2;
+        if zero_odd * 2 > half && zero_even == 0 {
+            return Some(ENCODING_UTF16LE);
+        }
+        if zero_even * 2 > half && zero_odd == 0 {
+            return Some(ENCODING_UTF16BE);
+        }
+    }
+    None
+}
+
+/// 评估数据符合指定多字节编码的程度
+///
+/// # Returns
+///
+/// 解码失败返回`None`，否则返回该语言常用字符数减去罕见字符数
+#[cfg(feature = "encoding")]
+fn score_legacy(sample: &[u8], encoding: pblong, truncated: bool) -> Option<isize> {
+    let codec = encoding::label::encoding_from_windows_code_page(codepage(encoding))?;
+    //采样截断时忽略末尾可能不完整的字符
+    let sample = if truncated {
+        &sample[..sample.len().saturating_sub(1)]
+    } else {
+        sample
+    };
+    let text = codec.decode(sample, encoding::DecoderTrap::Strict).ok()?;
+    let mut score = 0isize;
+    for c in text.chars().filter(|c| !c.is_ascii()) {
+        score += match (encoding, c as u32) {
+            //平假名/片假名
+            (ENCODING_SHIFTJIS, 0x3040..=0x30FF) => 2,
+            //韩文音节
+            (ENCODING_EUCKR, 0xAC00..=0xD7A3) => 2,
+            //CJK统一汉字
+            (_, 0x4E00..=0x9FFF) => 1,
+            //全角标点
+            (_, 0x3000..=0x303F | 0xFF01..=0xFF5E) => 1,
+            //私用区/半角片假名等误判时常见的字符
+            (_, 0xE000..=0xF8FF | 0xFF61..=0xFF9F) => -2,
+            _ => -1
+        };
+    }
+    Some(score)
+}
+
+/// `UTF-32`解码
+#[cfg(feature = "encoding")]
+fn decode_utf32(data: &[u8], from_bytes: fn([u8; 4]) -> u32) -> String {
+    let chunks = data.chunks_exact(4);
+    let partial = !chunks.remainder().is_empty();
+    let mut rv: String = chunks
+        .map(|c| char::from_u32(from_bytes([c[0], c[1], c[2], c[3]])).unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    if partial {
+        rv.push(char::REPLACEMENT_CHARACTER);
+    }
+    rv
+}
+
+/// 创建流式解码读取器，将指定编码的数据转换为`UTF-8`
+///
+/// NOTE `UTF-32`需要一次性读取全部数据
+#[cfg(feature = "encoding")]
+pub fn decode_reader<'a, R: Read + 'a>(mut reader: R, encoding: pblong) -> io::Result<Box<dyn Read + 'a>> {
+    if let ENCODING_UTF32LE | ENCODING_UTF32BE = encoding {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        return Ok(Box::new(io::Cursor::new(decode(&data, encoding).into_owned().into_bytes())));
+    }
+    match encoding::label::encoding_from_windows_code_page(codepage(encoding)) {
+        Some(codec) if codec.name() != "utf-8" => {
+            Ok(Box::new(DecodeReader {
+                inner: reader,
+                decoder: codec.raw_decoder(),
+                input: vec![0; 64 * 1024],
+                output: Vec::new(),
+                pos: 0,
+                eof: false
+            }))
+        },
+        _ => Ok(Box::new(reader))
+    }
+}
+
+/// 十六进制解码，忽略空白字符、`0x`前缀及常用分隔符
+pub fn hex_decode(data: &str) -> Option<Vec<u8>> {
+    let data = data.as_bytes();
+    let mut rv = Vec::with_capacity(data.len() / 2);
+    let mut high: Option<u8> = None;
+    let mut idx = 0;
+    while idx < data.len() {
+        let c = data[idx];
+        idx += 1;
+        let val = match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            b'A'..=b'F' => c - b'A' + 10,
+            //分隔符不能出现在字节中间
+            b':' | b'-' | b',' if high.is_none() => continue,
+            c if c.is_ascii_whitespace() && high.is_none() => continue,
+            _ => return None
+        };
+        //`0x`前缀
+        if c == b'0' && high.is_none() && matches!(data.get(idx), Some(b'x' | b'X')) {
+            idx += 1;
+            continue;
+        }
+        match high.take() {
+            Some(high) => rv.push((high << 4) | val),
+            None => high = Some(val)
+        }
+    }
+    if high.is_some() {
+        return None;
+    }
+    Some(rv)
+}
+
+/// 流式解码读取器
+#[cfg(feature = "encoding")]
+struct DecodeReader<R> {
+    inner: R,
+    decoder: Box<dyn encoding::RawDecoder>,
+    input: Vec<u8>,
+    /// 已解码未读取的`UTF-8`数据
+    output: Vec<u8>,
+    pos: usize,
+    eof: bool
+}
+
+#[cfg(feature = "encoding")]
+impl<R: Read> DecodeReader<R> {
+    /// 读取并解码下一块数据
+    fn fill(&mut self) -> io::Result<()> {
+        let mut text = String::new();
+        while text.is_empty() && !self.eof {
+            let len = self.inner.read(&mut self.input)?;
+            if len == 0 {
+                self.eof = true;
+                if self.decoder.raw_finish(&mut text).is_some() {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                }
+                break;
+            }
+            let mut offset = 0;
+            while offset < len {
+                let (_, err) = self.decoder.raw_feed(&self.input[offset..len], &mut text);
+                match err {
+                    //无效字节替换为`U+FFFD`后继续解码
+                    Some(err) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        //`upto`为负数时无效字节位于上一块数据中，从当前位置继续
+                        offset = (offset as isize + err.upto).max(offset as isize) as usize;
+                    },
+                    None => break
+                }
+            }
+        }
+        self.output = text.into_bytes();
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "encoding")]
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.output.len() {
+            self.fill()?;
+        }
+        let len = buf.len().min(self.output.len() - self.pos);
+        buf[..len].copy_from_slice(&self.output[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
@@ -0,0 +1,73 @@
+//! 诊断统计
+//!
+//! 记录存活的对象数量与各子系统缓存数据的估算内存，由`pfwxGetDiagnostics`输出
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+static OBJECTS: Mutex<BTreeMap<&'static str, usize>> = Mutex::new(BTreeMap::new());
+static MEMORY: Mutex<BTreeMap<&'static str, usize>> = Mutex::new(BTreeMap::new());
+
+/// 存活对象计数
+///
+/// 作为对象的字段，创建时加一，释放时减一
+pub struct LiveObject(&'static str);
+
+impl LiveObject {
+    pub fn new(class: &'static str) -> LiveObject {
+        *OBJECTS.lock().unwrap().entry(class).or_default() += 1;
+        LiveObject(class)
+    }
+}
+
+impl Drop for LiveObject {
+    fn drop(&mut self) {
+        if let Some(count) = OBJECTS.lock().unwrap().get_mut(self.0) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+/// 缓存数据的内存计数
+///
+/// 作为持有数据的对象的字段，数据变化时通过`set`更新，释放时扣除
+pub struct MemoryUsage {
+    subsystem: &'static str,
+    bytes: usize
+}
+
+impl MemoryUsage {
+    pub fn new(subsystem: &'static str) -> MemoryUsage {
+        MemoryUsage {
+            subsystem,
+            bytes: 0
+        }
+    }
+
+    /// 占用的字节数
+    pub fn bytes(&self) -> usize { self.bytes }
+
+    /// 更新占用的字节数
+    pub fn set(&mut self, bytes: usize) {
+        if bytes == self.bytes {
+            return;
+        }
+        let mut memory = MEMORY.lock().unwrap();
+        let total = memory.entry(self.subsystem).or_default();
+        *total = total.saturating_sub(self.bytes) + bytes;
+        self.bytes = bytes;
+    }
+}
+
+impl Drop for MemoryUsage {
+    fn drop(&mut self) { self.set(0); }
+}
+
+/// 各类对象的存活数量
+pub fn objects() -> Vec<(&'static str, usize)> {
+    OBJECTS.lock().unwrap().iter().map(|(class, count)| (*class, *count)).collect()
+}
+
+/// 各子系统的估算内存(字节)
+pub fn memory() -> Vec<(&'static str, usize)> {
+    MEMORY.lock().unwrap().iter().map(|(subsystem, bytes)| (*subsystem, *bytes)).collect()
+}
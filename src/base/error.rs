@@ -0,0 +1,67 @@
+//! 对象的最近一次错误
+
+use super::retcode::RetCode;
+use std::{fmt::Display, io};
+
+/// 最近一次失败的错误码与描述
+///
+/// 由对象的`GetLastError`/`GetLastErrorText`返回
+#[derive(Debug, Clone)]
+pub struct LastError {
+    code: RetCode,
+    text: String
+}
+
+impl Default for LastError {
+    fn default() -> Self {
+        LastError {
+            code: RetCode::OK,
+            text: String::new()
+        }
+    }
+}
+
+impl LastError {
+    /// 记录错误并返回错误码
+    pub fn set(&mut self, code: RetCode, text: impl Display) -> RetCode {
+        self.code = code;
+        self.text = text.to_string();
+        code
+    }
+
+    /// 清除错误
+    pub fn clear(&mut self) {
+        self.code = RetCode::OK;
+        self.text.clear();
+    }
+
+    /// 记录`IO`错误并返回对应的错误码
+    pub fn set_io(&mut self, e: io::Error) -> RetCode { self.set(io_retcode(&e), e) }
+
+    /// 记录`IO`操作的结果，成功时清除错误
+    pub fn set_io_result(&mut self, rv: io::Result<()>) -> RetCode {
+        match rv {
+            Ok(()) => {
+                self.clear();
+                RetCode::OK
+            },
+            Err(e) => self.set_io(e)
+        }
+    }
+
+    /// 错误码，没有错误时为`OK`
+    pub fn code(&self) -> RetCode { self.code }
+
+    /// 错误描述，没有错误时为空字符串
+    pub fn text(&self) -> &str { &self.text }
+}
+
+/// `IO`错误对应的错误码
+pub fn io_retcode(e: &io::Error) -> RetCode {
+    match e.kind() {
+        io::ErrorKind::NotFound => RetCode::E_FILE_NOT_FOUND,
+        io::ErrorKind::Interrupted => RetCode::CANCELLED,
+        io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput => RetCode::E_INVALID_DATA,
+        _ => RetCode::E_IO_ERROR
+    }
+}
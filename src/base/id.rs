@@ -0,0 +1,100 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering}, Mutex
+    }, thread, time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+/// 默认纪元(`2020-01-01 00:00:00 UTC`)
+pub const DEFAULT_EPOCH: u64 = 1_577_836_800_000;
+/// 节点ID位数
+const NODE_BITS: u32 = 10;
+/// 序列号位数
+const SEQUENCE_BITS: u32 = 12;
+/// 最大节点ID
+pub const MAX_NODE_ID: u64 = (1 << NODE_BITS) - 1;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+
+static GENERATOR: Mutex<Snowflake> = Mutex::new(Snowflake {
+    node_id: 0,
+    epoch: DEFAULT_EPOCH,
+    last_ts: 0,
+    sequence: 0
+});
+static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+
+/// 雪花算法ID生成器
+///
+/// 格式为`1位符号(0) + 41位时间戳(毫秒) + 10位节点ID + 12位序列号`
+struct Snowflake {
+    node_id: u64,
+    epoch: u64,
+    last_ts: u64,
+    sequence: u64
+}
+
+impl Snowflake {
+    fn next(&mut self) -> u64 {
+        let mut ts = now_millis().saturating_sub(self.epoch);
+        //时钟回拨时沿用上次的时间戳
+        if ts < self.last_ts {
+            ts = self.last_ts;
+        }
+        if ts == self.last_ts {
+            self.sequence = (self.sequence + 1) & MAX_SEQUENCE;
+            //当前毫秒的序列号已用完，等待下一毫秒
+            if self.sequence == 0 {
+                while ts <= self.last_ts {
+                    thread::sleep(Duration::from_micros(100));
+                    ts = now_millis().saturating_sub(self.epoch);
+                }
+            }
+        } else {
+            self.sequence = 0;
+        }
+        self.last_ts = ts;
+        (ts << (NODE_BITS + SEQUENCE_BITS)) | (self.node_id << SEQUENCE_BITS) | self.sequence
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|dur| dur.as_millis() as u64).unwrap_or_default()
+}
+
+/// 配置ID生成器
+///
+/// # Parameters
+///
+/// - `node_id` 节点ID，`0`-`MAX_NODE_ID`
+/// - `epoch` 纪元(`UNIX`时间戳，毫秒)，不能晚于当前时间
+///
+/// # Returns
+///
+/// 参数无效时返回`false`
+pub fn configure(node_id: u64, epoch: u64) -> bool {
+    if node_id > MAX_NODE_ID || epoch > now_millis() {
+        return false;
+    }
+    let mut generator = GENERATOR.lock().unwrap();
+    if generator.epoch != epoch {
+        generator.last_ts = 0;
+        generator.sequence = 0;
+    }
+    generator.node_id = node_id;
+    generator.epoch = epoch;
+    true
+}
+
+/// 生成雪花算法ID
+///
+/// 同一节点内单调递增
+pub fn next_id() -> u64 { GENERATOR.lock().unwrap().next() }
+
+/// 生成进程内递增的序号(从1开始，溢出后回绕并跳过0)
+pub fn next_sequence() -> u32 {
+    loop {
+        let seq = SEQUENCE.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+        if seq != 0 {
+            break seq;
+        }
+    }
+}
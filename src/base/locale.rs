@@ -0,0 +1,56 @@
+//! 错误信息的语言
+//!
+//! 通过`pfwxSetLocale`切换，影响本库生成的事件参数、错误信息与异常文本，第三方库的原始错误信息保持不变
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static LOCALE: AtomicU8 = AtomicU8::new(Locale::En as u8);
+
+/// 语言
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    /// 英文(默认)
+    En = 0,
+    /// 简体中文
+    ZhCN = 1
+}
+
+impl Locale {
+    /// 解析语言名称，如`en`、`en-US`、`zh`、`zh-CN`，不区分大小写
+    pub fn parse(name: &str) -> Option<Locale> {
+        let name = name.trim().to_ascii_lowercase();
+        match name.split(['-', '_']).next() {
+            Some("en") => Some(Locale::En),
+            Some("zh") => Some(Locale::ZhCN),
+            _ => None
+        }
+    }
+
+    /// 语言名称
+    pub fn name(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::ZhCN => "zh-CN"
+        }
+    }
+}
+
+/// 当前语言
+pub fn current() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::ZhCN,
+        _ => Locale::En
+    }
+}
+
+/// 切换语言
+pub fn set(locale: Locale) { LOCALE.store(locale as u8, Ordering::Relaxed); }
+
+/// 按当前语言选择文本
+pub fn tr(en: &'static str, zh: &'static str) -> &'static str {
+    match current() {
+        Locale::En => en,
+        Locale::ZhCN => zh
+    }
+}
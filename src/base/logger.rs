@@ -0,0 +1,212 @@
+//! 运行时日志
+//!
+use super::fs::create_file_dir_all;
+use chrono::{DateTime, Datelike, Local, Timelike};
+use std::{
+    fs::{self, File, OpenOptions}, io::{self, Write}, path::PathBuf, sync::Mutex
+};
+use tracing::level_filters::LevelFilter;
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::{filter, fmt, fmt::format::FmtSpan, prelude::*, reload, Registry};
+
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+/// 日志配置
+pub struct LogConfig {
+    /// 日志文件路径
+    pub path: PathBuf,
+    /// 日志级别
+    pub level: LevelFilter,
+    /// 日志文件大小上限(字节)，超过后滚动到新文件，`0`表示不滚动
+    pub rolling: u64
+}
+
+/// 设置运行时日志
+///
+/// # Description
+///
+/// 首次调用时安装全局日志订阅器，之后的调用替换日志文件和级别，`LevelFilter::OFF`关闭日志
+///
+/// # Returns
+///
+/// 已经安装了其它全局订阅器(如`trace`调试模式)时返回`false`
+pub fn set_log(cfg: LogConfig) -> io::Result<bool> {
+    let mut logger = LOGGER.lock().unwrap();
+    if logger.is_none() {
+        let (filter, handle) = reload::Layer::new(targets(LevelFilter::OFF));
+        let layer = fmt::layer()
+            .with_ansi(false)
+            .with_span_events(FmtSpan::NONE)
+            .with_line_number(true)
+            .with_thread_names(true)
+            .with_thread_ids(true)
+            .with_writer(LogWriter::current)
+            .with_filter(filter);
+        if tracing_subscriber::registry().with(layer).try_init().is_err() {
+            return Ok(false);
+        }
+        *logger = Some(Logger {
+            filter: handle,
+            writer: None
+        });
+    }
+    let logger = logger.as_mut().unwrap();
+
+    //先关闭旧文件，确保缓冲的日志写入完成
+    logger.writer = None;
+    if cfg.level != LevelFilter::OFF {
+        let file = RollingFile::open(cfg.path, cfg.rolling, Rotation::Never, default::ROLLING_FILES)?;
+        let (writer, guard) =
+            tracing_appender::non_blocking::NonBlockingBuilder::default().lossy(true).finish(file);
+        logger.writer = Some((writer, guard));
+    }
+    let _ = logger.filter.reload(targets(cfg.level));
+    Ok(true)
+}
+
+/// 关闭运行时日志并写入缓冲的日志
+pub fn close_log() {
+    let mut logger = LOGGER.lock().unwrap();
+    if let Some(logger) = logger.as_mut() {
+        let _ = logger.filter.reload(targets(LevelFilter::OFF));
+        logger.writer = None;
+    }
+}
+
+/// 只记录本模块的日志
+fn targets(level: LevelFilter) -> filter::Targets {
+    filter::Targets::default().with_default(LevelFilter::OFF).with_target(env!("CARGO_PKG_NAME"), level)
+}
+
+/// 日志订阅器状态
+struct Logger {
+    filter: reload::Handle<filter::Targets, Registry>,
+    writer: Option<(NonBlocking, WorkerGuard)>
+}
+
+/// 写入当前的日志文件
+struct LogWriter(Option<NonBlocking>);
+
+impl LogWriter {
+    fn current() -> Self {
+        let logger = LOGGER.lock().unwrap();
+        LogWriter(logger.as_ref().and_then(|logger| logger.writer.as_ref()).map(|(writer, _)| writer.clone()))
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.as_mut() {
+            Some(writer) => writer.write(buf),
+            None => Ok(buf.len())
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(())
+        }
+    }
+}
+
+/// 按时间滚动的周期
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Never,
+    Hourly,
+    Daily
+}
+
+impl Rotation {
+    /// 时间所在的周期序号
+    fn period(self, time: DateTime<Local>) -> i64 {
+        match self {
+            Rotation::Never => 0,
+            Rotation::Hourly => time.num_days_from_ce() as i64 * 24 + time.hour() as i64,
+            Rotation::Daily => time.num_days_from_ce() as i64
+        }
+    }
+}
+
+/// 按大小或时间滚动的日志文件
+///
+/// # Description
+///
+/// 超过大小上限或进入新的周期时依次重命名为`xxx.log.1`、`xxx.log.2`...，最多保留`max_files`个旧文件
+pub struct RollingFile {
+    path: PathBuf,
+    max_size: u64,
+    rotation: Rotation,
+    max_files: usize,
+    file: File,
+    size: u64,
+    period: i64
+}
+
+impl RollingFile {
+    pub fn open(path: PathBuf, max_size: u64, rotation: Rotation, max_files: usize) -> io::Result<Self> {
+        create_file_dir_all(&path)?;
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let meta = file.metadata()?;
+        //已有的文件按最后修改时间确定周期，跨周期重新打开时立即滚动
+        let period = rotation.period(meta.modified().map(DateTime::from).unwrap_or_else(|_| Local::now()));
+        Ok(RollingFile {
+            path,
+            max_size,
+            rotation,
+            max_files,
+            file,
+            size: meta.len(),
+            period
+        })
+    }
+
+    /// 滚动到新文件
+    fn roll(&mut self) -> io::Result<()> {
+        let backup = |idx: usize| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{}", idx));
+            PathBuf::from(path)
+        };
+        self.file.flush()?;
+        let _ = fs::remove_file(backup(self.max_files));
+        for idx in (1..self.max_files).rev() {
+            let _ = fs::rename(backup(idx), backup(idx + 1));
+        }
+        //不保留旧文件时清空，重命名失败(如文件被占用)时继续写入原文件
+        if self.max_files == 0 {
+            self.file.set_len(0)?;
+        } else if fs::rename(&self.path, backup(1)).is_ok() {
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.rotation != Rotation::Never {
+            let period = self.rotation.period(Local::now());
+            if period != self.period {
+                self.period = period;
+                if self.size != 0 {
+                    self.roll()?;
+                }
+            }
+        }
+        if self.max_size != 0 && self.size != 0 && self.size + buf.len() as u64 > self.max_size {
+            self.roll()?;
+        }
+        let len = self.file.write(buf)?;
+        self.size += len as u64;
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> { self.file.flush() }
+}
+
+/// 默认配置
+pub mod default {
+    /// 保留的旧日志文件数量
+    pub const ROLLING_FILES: usize = 5;
+}
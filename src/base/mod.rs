@@ -1,4 +1,18 @@
 pub mod retcode;
+pub mod error;
+pub mod locale;
+pub mod diag;
 pub mod pfw;
 pub mod conv;
 pub mod fs;
+pub mod id;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "secrets")]
+pub mod secret;
+#[cfg(any(feature = "http", feature = "mqtt"))]
+pub mod cert;
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
+#[cfg(feature = "log")]
+pub mod logger;
@@ -2,15 +2,21 @@ use pbni::pbx::{pbobject, pbsession, Object, Session};
 use std::slice;
 
 lazy_static::lazy_static! {
-static ref API: &'static Api = unsafe { Api::load() };
+static ref API: Option<&'static Api> = unsafe { Api::load() };
 }
 
+/// `pfw.dll`是否可用
+pub fn is_available() -> bool { API.is_some() }
+
 /// 解析`pfw::n_json`对象
-pub fn json_parse<'a>(session: Session, data: &str) -> Object<'a> {
+///
+/// NOTE `pfw.dll`不可用时返回`None`
+pub fn json_parse<'a>(session: Session, data: &str) -> Option<Object<'a>> {
+    let api = (*API)?;
     unsafe {
-        let obj = (API.JsonParseUTF8)(session.as_raw(), data.as_ptr(), data.len());
+        let obj = (api.JsonParseUTF8)(session.as_raw(), data.as_ptr(), data.len());
         if let Some(obj) = obj {
-            Object::from_raw(obj, session)
+            Some(Object::from_raw(obj, session))
         } else {
             panic!("Cannot create object 'n_json'")
         }
@@ -18,14 +24,19 @@ pub fn json_parse<'a>(session: Session, data: &str) -> Object<'a> {
 }
 
 /// 序列化`pfw::n_json`对象
+///
+/// NOTE `pfw.dll`不可用时返回空字符串
 pub fn json_serialize(obj: &Object) -> String {
+    let Some(api) = *API else {
+        return "".to_owned();
+    };
     unsafe {
         let mut len = 0;
-        let buf = (API.JsonSerializeUTF8)(obj.get_session().as_raw(), obj.as_raw(), &mut len as _);
+        let buf = (api.JsonSerializeUTF8)(obj.get_session().as_raw(), obj.as_raw(), &mut len as _);
         if !buf.is_null() {
             let buf_slice = slice::from_raw_parts(buf, len);
             let rv = String::from(std::str::from_utf8_unchecked(buf_slice));
-            (API.Free)(buf);
+            (api.Free)(buf);
             rv
         } else {
             "".to_owned()
@@ -34,11 +45,14 @@ pub fn json_serialize(obj: &Object) -> String {
 }
 
 /// 解析`pfw::n_xmldoc`对象
-pub fn xml_parse<'a>(session: Session, data: &str) -> Object<'a> {
+///
+/// NOTE `pfw.dll`不可用时返回`None`
+pub fn xml_parse<'a>(session: Session, data: &str) -> Option<Object<'a>> {
+    let api = (*API)?;
     unsafe {
-        let obj = (API.XmlParseUTF8)(session.as_raw(), data.as_ptr(), data.len());
+        let obj = (api.XmlParseUTF8)(session.as_raw(), data.as_ptr(), data.len());
         if let Some(obj) = obj {
-            Object::from_raw(obj, session)
+            Some(Object::from_raw(obj, session))
         } else {
             panic!("Cannot create object 'n_xmldoc'")
         }
@@ -46,14 +60,19 @@ pub fn xml_parse<'a>(session: Session, data: &str) -> Object<'a> {
 }
 
 /// 序列化`pfw::n_xmldoc`对象
+///
+/// NOTE `pfw.dll`不可用时返回空字符串
 pub fn xml_serialize(obj: &Object) -> String {
+    let Some(api) = *API else {
+        return "".to_owned();
+    };
     unsafe {
         let mut len = 0;
-        let buf = (API.XmlSerializeUTF8)(obj.get_session().as_raw(), obj.as_raw(), &mut len as _);
+        let buf = (api.XmlSerializeUTF8)(obj.get_session().as_raw(), obj.as_raw(), &mut len as _);
         if !buf.is_null() {
             let buf_slice = slice::from_raw_parts(buf, len);
             let rv = String::from(std::str::from_utf8_unchecked(buf_slice));
-            (API.Free)(buf);
+            (api.Free)(buf);
             rv
         } else {
             "".to_owned()
@@ -74,19 +93,20 @@ struct Api {
 }
 
 impl Api {
-    unsafe fn load() -> &'static Api {
+    /// 加载`pfw.dll`，失败时返回`None`
+    unsafe fn load() -> Option<&'static Api> {
         static mut LIB: Option<libloading::Library> = None;
 
         type FnGetApi = extern "system" fn() -> *const Api;
 
         if LIB.is_none() {
             unsafe {
-                LIB = Some(libloading::Library::new("pfw.dll").expect("Cannot load module pfw.dll"));
+                LIB = Some(libloading::Library::new("pfw.dll").ok()?);
             }
         }
 
         let lib = LIB.as_ref().unwrap();
-        let api_fn = lib.get::<FnGetApi>(b"pfwAPI").expect("Cannot find entry symbol 'pfwAPI' at pfw.dll");
-        &*api_fn()
+        let api_fn = lib.get::<FnGetApi>(b"pfwAPI").ok()?;
+        api_fn().as_ref()
     }
 }
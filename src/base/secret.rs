@@ -0,0 +1,207 @@
+//! `DPAPI`数据保护与`Windows`凭据管理器
+
+use std::{io, ptr, slice};
+use windows::{
+    core::{HSTRING, PWSTR}, Win32::{
+        Foundation::{ERROR_NOT_FOUND, HLOCAL}, Security::{
+            Credentials::{
+                CredDeleteW, CredEnumerateW, CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_ENUMERATE_FLAGS, CRED_PERSIST_LOCAL_MACHINE, CRED_TYPE_GENERIC
+            }, Cryptography::{
+                CryptProtectData, CryptUnprotectData, CRYPTPROTECT_LOCAL_MACHINE, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB
+            }
+        }, System::Memory::LocalFree
+    }
+};
+
+/// 凭据
+pub struct Credential {
+    pub user_name: String,
+    pub secret: Vec<u8>
+}
+
+impl Credential {
+    /// 以`UTF-16`保存的密码(通过控制面板添加的凭据)转换为字符串，否则按`UTF-8`解析
+    pub fn secret_string(&self) -> String {
+        if self.secret.len() % 2 == 0 && self.secret.iter().skip(1).step_by(2).any(|b| *b == 0) {
+            let wide: Vec<u16> =
+                self.secret.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&wide)
+        } else {
+            String::from_utf8_lossy(&self.secret).into_owned()
+        }
+    }
+}
+
+/// 使用`DPAPI`加密
+///
+/// # Parameters
+///
+/// - `entropy` 附加熵，解密时必须相同
+/// - `machine` 使用计算机范围(本机所有用户都可以解密)，否则只有当前用户可以解密
+pub fn protect(data: &[u8], entropy: &[u8], machine: bool) -> io::Result<Vec<u8>> {
+    let mut flags = CRYPTPROTECT_UI_FORBIDDEN;
+    if machine {
+        flags |= CRYPTPROTECT_LOCAL_MACHINE;
+    }
+    let data_in = blob(data);
+    let entropy = blob(entropy);
+    let mut data_out = CRYPT_INTEGER_BLOB::default();
+    unsafe {
+        if CryptProtectData(
+            &data_in,
+            None,
+            (!entropy.pbData.is_null()).then_some(&entropy as *const _),
+            None,
+            None,
+            flags,
+            &mut data_out
+        ) == false
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(take_blob(data_out))
+    }
+}
+
+/// 使用`DPAPI`解密
+pub fn unprotect(data: &[u8], entropy: &[u8]) -> io::Result<Vec<u8>> {
+    let data_in = blob(data);
+    let entropy = blob(entropy);
+    let mut data_out = CRYPT_INTEGER_BLOB::default();
+    unsafe {
+        if CryptUnprotectData(
+            &data_in,
+            None,
+            (!entropy.pbData.is_null()).then_some(&entropy as *const _),
+            None,
+            None,
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut data_out
+        ) == false
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(take_blob(data_out))
+    }
+}
+
+/// 读取普通凭据，不存在时返回`None`
+pub fn read_credential(target: &str) -> io::Result<Option<Credential>> {
+    let mut cred: *mut CREDENTIALW = ptr::null_mut();
+    unsafe {
+        if CredReadW(&HSTRING::from(target), CRED_TYPE_GENERIC.0, 0, &mut cred) == false {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(ERROR_NOT_FOUND.0 as i32) {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+        let rv = {
+            let cred = &*cred;
+            Credential {
+                user_name: from_pwstr(cred.UserName),
+                secret: if cred.CredentialBlob.is_null() {
+                    Vec::new()
+                } else {
+                    slice::from_raw_parts(cred.CredentialBlob, cred.CredentialBlobSize as usize).to_vec()
+                }
+            }
+        };
+        CredFree(cred as _);
+        Ok(Some(rv))
+    }
+}
+
+/// 写入普通凭据(本机持久保存)，已存在时覆盖
+pub fn write_credential(target: &str, user_name: &str, secret: &[u8]) -> io::Result<()> {
+    let mut target: Vec<u16> = target.encode_utf16().chain(Some(0)).collect();
+    let mut user_name: Vec<u16> = user_name.encode_utf16().chain(Some(0)).collect();
+    let cred = CREDENTIALW {
+        Type: CRED_TYPE_GENERIC,
+        TargetName: PWSTR(target.as_mut_ptr()),
+        CredentialBlobSize: secret.len() as u32,
+        CredentialBlob: secret.as_ptr() as *mut u8,
+        Persist: CRED_PERSIST_LOCAL_MACHINE,
+        UserName: PWSTR(user_name.as_mut_ptr()),
+        ..Default::default()
+    };
+    unsafe {
+        if CredWriteW(&cred, 0) == false {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// 删除普通凭据
+///
+/// # Returns
+///
+/// 凭据是否存在
+pub fn delete_credential(target: &str) -> io::Result<bool> {
+    unsafe {
+        if CredDeleteW(&HSTRING::from(target), CRED_TYPE_GENERIC.0, 0) == false {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(ERROR_NOT_FOUND.0 as i32) {
+                return Ok(false);
+            }
+            return Err(err);
+        }
+    }
+    Ok(true)
+}
+
+/// 枚举凭据名称
+///
+/// # Parameters
+///
+/// - `filter` 名称过滤，支持以`*`结尾的前缀匹配
+pub fn enum_credentials(filter: &str) -> io::Result<Vec<String>> {
+    let mut count = 0u32;
+    let mut creds: *mut *mut CREDENTIALW = ptr::null_mut();
+    unsafe {
+        if CredEnumerateW(&HSTRING::from(filter), CRED_ENUMERATE_FLAGS(0), &mut count, &mut creds) == false {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(ERROR_NOT_FOUND.0 as i32) {
+                return Ok(Vec::new());
+            }
+            return Err(err);
+        }
+        let names = slice::from_raw_parts(creds, count as usize)
+            .iter()
+            .filter(|cred| (***cred).Type == CRED_TYPE_GENERIC)
+            .map(|cred| from_pwstr((**cred).TargetName))
+            .collect();
+        CredFree(creds as _);
+        Ok(names)
+    }
+}
+
+fn blob(data: &[u8]) -> CRYPT_INTEGER_BLOB {
+    CRYPT_INTEGER_BLOB {
+        cbData: data.len() as u32,
+        pbData: if data.is_empty() {
+            ptr::null_mut()
+        } else {
+            data.as_ptr() as *mut u8
+        }
+    }
+}
+
+/// 复制并释放`DPAPI`分配的数据
+unsafe fn take_blob(blob: CRYPT_INTEGER_BLOB) -> Vec<u8> {
+    if blob.pbData.is_null() {
+        return Vec::new();
+    }
+    let data = slice::from_raw_parts(blob.pbData, blob.cbData as usize).to_vec();
+    let _ = LocalFree(HLOCAL(blob.pbData as isize));
+    data
+}
+
+unsafe fn from_pwstr(s: PWSTR) -> String {
+    if s.is_null() {
+        String::new()
+    } else {
+        String::from_utf16_lossy(s.as_wide())
+    }
+}
@@ -2,7 +2,7 @@
 #![allow(dead_code)]
 #![feature(try_trait_v2)]
 
-#[cfg(feature = "trace")]
+#[cfg(feature = "log")]
 #[macro_use]
 extern crate tracing;
 
@@ -12,7 +12,7 @@ mod pbx;
 mod reactor;
 
 mod prelude {
-    pub(crate) use super::base::retcode::RetCode;
+    pub(crate) use super::base::{error::LastError, retcode::RetCode};
     #[cfg(feature = "reactor")]
     pub(crate) use super::reactor;
 }
@@ -0,0 +1,457 @@
+use crate::{base::fs, prelude::*};
+use pbni::pbx::*;
+use qrcode::{Color, EcLevel, QrCode};
+
+/// `Code128`符号的条空宽度(条、空交替)，索引为符号值
+const CODE128_PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212", "221213",
+    "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221", "223211", "221132",
+    "221231", "213212", "223112", "312131", "311222", "321122", "321221", "312212", "322112", "322211",
+    "212123", "212321", "232121", "111323", "131123", "131321", "112313", "132113", "132311", "211313",
+    "231113", "231311", "112133", "112331", "132131", "113123", "113321", "133121", "313121", "211331",
+    "231131", "213113", "213311", "213131", "311123", "311321", "331121", "312113", "312311", "332111",
+    "314111", "221411", "431111", "111224", "111422", "121124", "121421", "141122", "141221", "112214",
+    "112412", "122114", "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111",
+    "111242", "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311", "113141",
+    "114131", "311141", "411131", "211412", "211214", "211232", "2331112"
+];
+const CODE128_CODE_C: usize = 99;
+const CODE128_CODE_B: usize = 100;
+const CODE128_CODE_A: usize = 101;
+const CODE128_START_A: usize = 103;
+const CODE128_START_B: usize = 104;
+const CODE128_START_C: usize = 105;
+const CODE128_STOP: usize = 106;
+
+/// `EAN-13`左侧奇校验(`L`)编码，`R`编码为其取反，`G`编码为`R`编码的逆序
+const EAN_L_CODES: [u8; 10] = [
+    0b0001101, 0b0011001, 0b0010011, 0b0111101, 0b0100011, 0b0110001, 0b0101111, 0b0111011, 0b0110111,
+    0b0001011
+];
+/// `EAN-13`首位数字决定的左侧6位数字的编码方式(`1`表示`G`编码)
+const EAN_PARITY: [u8; 10] =
+    [0b000000, 0b001011, 0b001101, 0b001110, 0b010011, 0b011001, 0b011100, 0b010101, 0b010110, 0b011010];
+
+/// 条码类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symbology {
+    Qr,
+    Code128,
+    Ean13
+}
+
+impl Symbology {
+    /// 通过名称解析，不区分大小写
+    fn new(name: &str) -> Option<Symbology> {
+        Some(match name.replace('-', "").to_ascii_lowercase().as_str() {
+            "qr" | "qrcode" => Symbology::Qr,
+            "code128" => Symbology::Code128,
+            "ean13" => Symbology::Ean13,
+            _ => return None
+        })
+    }
+}
+
+/// 模块矩阵(`true`为深色)
+struct Matrix {
+    width: usize,
+    height: usize,
+    modules: Vec<bool>
+}
+
+struct Barcode {
+    /// 每个模块的像素数
+    module_size: u32,
+    /// 一维码的条高度(像素)
+    bar_height: u32,
+    /// 静区宽度(模块数)，`None`时使用各类型的默认值
+    quiet_zone: Option<u32>,
+    ec_level: EcLevel,
+    /// 前景色(`PB`颜色值)
+    foreground: pblong,
+    /// 背景色(`PB`颜色值)
+    background: pblong,
+    /// 返回给`PB`的二进制数据
+    buf: Vec<u8>,
+    error: LastError
+}
+
+impl Default for Barcode {
+    fn default() -> Self {
+        Barcode {
+            module_size: 4,
+            bar_height: 80,
+            quiet_zone: None,
+            ec_level: EcLevel::M,
+            foreground: 0,
+            background: 0xffffff,
+            buf: Vec::new(),
+            error: LastError::default()
+        }
+    }
+}
+
+#[nonvisualobject(name = "nx_barcode")]
+impl Barcode {
+    /// 设置模块(最窄的条或二维码的点)的像素数，默认`4`
+    #[method(name = "SetModuleSize")]
+    fn set_module_size(&mut self, size: pbulong) -> RetCode {
+        if size == 0 {
+            return RetCode::E_INVALID_ARGUMENT;
+        }
+        self.module_size = size;
+        RetCode::OK
+    }
+
+    /// 设置一维码的条高度(像素)，默认`80`
+    #[method(name = "SetHeight")]
+    fn set_height(&mut self, height: pbulong) -> RetCode {
+        if height == 0 {
+            return RetCode::E_INVALID_ARGUMENT;
+        }
+        self.bar_height = height;
+        RetCode::OK
+    }
+
+    /// 设置四周的空白宽度(模块数)，默认二维码为`4`，一维码为`10`
+    #[method(name = "SetQuietZone")]
+    fn set_quiet_zone(&mut self, modules: pbulong) -> RetCode {
+        self.quiet_zone = Some(modules);
+        RetCode::OK
+    }
+
+    /// 设置二维码的纠错级别
+    ///
+    /// # Parameters
+    ///
+    /// - `level` `L`(7%)、`M`(15%，默认)、`Q`(25%)、`H`(30%)
+    #[method(name = "SetErrorCorrection")]
+    fn set_error_correction(&mut self, level: String) -> RetCode {
+        self.ec_level = match level.to_ascii_uppercase().as_str() {
+            "L" => EcLevel::L,
+            "M" => EcLevel::M,
+            "Q" => EcLevel::Q,
+            "H" => EcLevel::H,
+            _ => return RetCode::E_INVALID_ARGUMENT
+        };
+        RetCode::OK
+    }
+
+    /// 设置颜色
+    ///
+    /// # Parameters
+    ///
+    /// - `foreground` 前景色，默认黑色
+    /// - `background` 背景色，默认白色
+    #[method(name = "SetColors")]
+    fn set_colors(&mut self, foreground: pblong, background: pblong) -> RetCode {
+        self.foreground = foreground;
+        self.background = background;
+        RetCode::OK
+    }
+
+    /// 生成`PNG`图片
+    ///
+    /// # Parameters
+    ///
+    /// - `symbology` 类型：`QR`、`Code128`、`EAN13`
+    /// - `data` 内容，`QR`使用`UTF-8`编码，`Code128`仅支持`ASCII`字符，`EAN13`为12位数字(自动计算校验位)或13位数字
+    ///
+    /// # Returns
+    ///
+    /// 失败时返回空`Blob`，通过`GetLastErrorText`获取错误信息
+    ///
+    /// # Description
+    ///
+    /// 一维码不包含下方的可读文字
+    #[method(name = "Generate")]
+    fn generate(&mut self, symbology: String, data: String) -> &[u8] {
+        self.buf = match self.render(&symbology, &data) {
+            Ok(png) => {
+                self.error.clear();
+                png
+            },
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_DATA, e);
+                Vec::new()
+            }
+        };
+        &self.buf
+    }
+
+    /// 生成`PNG`图片并保存到文件
+    ///
+    /// 参数同`Generate`
+    #[method(name = "SaveFile")]
+    fn save_file(&mut self, symbology: String, data: String, path: String) -> RetCode {
+        let png = match self.render(&symbology, &data) {
+            Ok(png) => png,
+            Err(e) => return self.error.set(RetCode::E_INVALID_DATA, e)
+        };
+        match fs::create_file_dir_all(&path).and_then(|_| std::fs::write(&path, png)) {
+            Ok(_) => {
+                self.error.clear();
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_IO_ERROR, e)
+        }
+    }
+
+    /// 计算`EAN-13`的校验位
+    ///
+    /// # Returns
+    ///
+    /// 参数不是12位数字时返回`-1`
+    #[method(name = "EAN13CheckDigit")]
+    fn ean13_check_digit(&self, data: String) -> pbint {
+        match digits(&data) {
+            Some(digits) if digits.len() == 12 => ean13_check(&digits) as pbint,
+            _ => -1
+        }
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Barcode {
+    fn render(&self, symbology: &str, data: &str) -> Result<Vec<u8>, String> {
+        let symbology =
+            Symbology::new(symbology).ok_or_else(|| format!("unsupported symbology '{symbology}'"))?;
+        let (matrix, quiet_zone) = match symbology {
+            Symbology::Qr => {
+                let code = QrCode::with_error_correction_level(data.as_bytes(), self.ec_level)
+                    .map_err(|e| e.to_string())?;
+                let width = code.width();
+                let modules = code.to_colors().into_iter().map(|color| color == Color::Dark).collect();
+                (
+                    Matrix {
+                        width,
+                        height: width,
+                        modules
+                    },
+                    4
+                )
+            },
+            Symbology::Code128 => (linear(code128(data)?), 10),
+            Symbology::Ean13 => (linear(ean13(data)?), 10)
+        };
+        let quiet_zone = self.quiet_zone.unwrap_or(quiet_zone);
+        encode_png(&matrix, self.module_size, self.bar_height, quiet_zone, self.foreground, self.background)
+    }
+}
+
+/// 一维码转换为单行矩阵
+fn linear(bars: Vec<bool>) -> Matrix {
+    Matrix {
+        width: bars.len(),
+        height: 1,
+        modules: bars
+    }
+}
+
+/// 编码`Code128`，自动在`A`/`B`/`C`字符集之间切换
+fn code128(data: &str) -> Result<Vec<bool>, String> {
+    if data.is_empty() {
+        return Err("empty data".to_owned());
+    }
+    if let Some(c) = data.chars().find(|c| !c.is_ascii()) {
+        return Err(format!("unsupported character '{c}'"));
+    }
+    let bytes = data.as_bytes();
+    let mut values = Vec::new();
+    //当前字符集：`A`、`B`、`C`
+    let mut set: Option<u8> = None;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let digit_run = bytes[idx..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if set == Some(b'C') && digit_run >= 2 {
+            values.push(((bytes[idx] - b'0') * 10 + (bytes[idx + 1] - b'0')) as usize);
+            idx += 2;
+            continue;
+        }
+        //4位以上的连续数字使用`C`字符集，奇数位时第一位仍使用当前字符集
+        if digit_run >= 4 && (digit_run % 2 == 0 || set.is_some()) {
+            if digit_run % 2 == 1 {
+                values.push(code128_value(set.unwrap(), bytes[idx]));
+                idx += 1;
+            }
+            values.push(if set.is_none() {
+                CODE128_START_C
+            } else {
+                CODE128_CODE_C
+            });
+            set = Some(b'C');
+            continue;
+        }
+        //控制字符需要`A`字符集，其它使用`B`字符集
+        let needed = if bytes[idx] < 32 || (set == Some(b'A') && bytes[idx] < 96) {
+            b'A'
+        } else {
+            b'B'
+        };
+        if set != Some(needed) {
+            values.push(match (set, needed) {
+                (None, b'A') => CODE128_START_A,
+                (None, _) => CODE128_START_B,
+                (_, b'A') => CODE128_CODE_A,
+                _ => CODE128_CODE_B
+            });
+            set = Some(needed);
+        }
+        values.push(code128_value(needed, bytes[idx]));
+        idx += 1;
+    }
+    let checksum = values.iter().enumerate().map(|(pos, value)| pos.max(1) * value).sum::<usize>() % 103;
+    values.push(checksum);
+    values.push(CODE128_STOP);
+
+    let mut bars = Vec::new();
+    for value in values {
+        for (pos, width) in CODE128_PATTERNS[value].bytes().enumerate() {
+            bars.extend(std::iter::repeat(pos % 2 == 0).take((width - b'0') as usize));
+        }
+    }
+    Ok(bars)
+}
+
+/// 字符在`A`/`B`字符集中的符号值
+fn code128_value(set: u8, b: u8) -> usize {
+    match (set, b) {
+        (b'A', 0..=31) => b as usize + 64,
+        _ => b as usize - 32
+    }
+}
+
+/// 编码`EAN-13`
+fn ean13(data: &str) -> Result<Vec<bool>, String> {
+    let mut digits = digits(data).ok_or_else(|| "EAN-13 requires digits only".to_owned())?;
+    match digits.len() {
+        12 => digits.push(ean13_check(&digits)),
+        13 => {
+            if ean13_check(&digits[..12]) != digits[12] {
+                return Err("invalid EAN-13 check digit".to_owned());
+            }
+        },
+        _ => return Err("EAN-13 requires 12 or 13 digits".to_owned())
+    }
+    let mut bars = Vec::with_capacity(95);
+    let mut push = |code: u8, len: usize| {
+        for bit in (0..len).rev() {
+            bars.push((code >> bit) & 1 == 1);
+        }
+    };
+    push(0b101, 3);
+    let parity = EAN_PARITY[digits[0] as usize];
+    for (pos, digit) in digits[1..7].iter().enumerate() {
+        let l = EAN_L_CODES[*digit as usize];
+        if (parity >> (5 - pos)) & 1 == 1 {
+            //`G`编码：`R`编码(取反)的逆序
+            let r = !l & 0x7f;
+            push(r.reverse_bits() >> 1, 7);
+        } else {
+            push(l, 7);
+        }
+    }
+    push(0b01010, 5);
+    for digit in &digits[7..13] {
+        push(!EAN_L_CODES[*digit as usize] & 0x7f, 7);
+    }
+    push(0b101, 3);
+    Ok(bars)
+}
+
+/// `EAN-13`校验位
+fn ean13_check(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(pos, d)| {
+            *d as u32 *
+                if pos % 2 == 0 {
+                    1
+                } else {
+                    3
+                }
+        })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+fn digits(data: &str) -> Option<Vec<u8>> {
+    let data = data.trim();
+    if data.is_empty() || !data.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(data.bytes().map(|b| b - b'0').collect())
+}
+
+/// `PB`颜色值(`0x00BBGGRR`)转换为`RGB`
+fn pb_rgb(color: pblong) -> [u8; 3] {
+    [(color & 0xff) as u8, ((color >> 8) & 0xff) as u8, ((color >> 16) & 0xff) as u8]
+}
+
+/// 将模块矩阵缩放后编码为`PNG`
+///
+/// # Parameters
+///
+/// - `bar_height` 单行矩阵(一维码)的像素高度
+fn encode_png(
+    matrix: &Matrix,
+    module_size: u32,
+    bar_height: u32,
+    quiet_zone: u32,
+    foreground: pblong,
+    background: pblong
+) -> Result<Vec<u8>, String> {
+    let module_size = module_size as usize;
+    let quiet = quiet_zone as usize * module_size;
+    let width = matrix.width * module_size + quiet * 2;
+    let (row_height, height) = if matrix.height == 1 {
+        (bar_height as usize, bar_height as usize + quiet * 2)
+    } else {
+        (module_size, matrix.height * module_size + quiet * 2)
+    };
+    let (fg, bg) = (pb_rgb(foreground), pb_rgb(background));
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    let blank_row: Vec<u8> = bg.iter().copied().cycle().take(width * 3).collect();
+    for _ in 0..quiet {
+        pixels.extend_from_slice(&blank_row);
+    }
+    for y in 0..matrix.height {
+        let mut row = Vec::with_capacity(width * 3);
+        row.extend_from_slice(&blank_row[..quiet * 3]);
+        for x in 0..matrix.width {
+            let color = if matrix.modules[y * matrix.width + x] {
+                fg
+            } else {
+                bg
+            };
+            for _ in 0..module_size {
+                row.extend_from_slice(&color);
+            }
+        }
+        row.extend_from_slice(&blank_row[..quiet * 3]);
+        for _ in 0..row_height {
+            pixels.extend_from_slice(&row);
+        }
+    }
+    for _ in 0..quiet {
+        pixels.extend_from_slice(&blank_row);
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&pixels).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(png)
+}
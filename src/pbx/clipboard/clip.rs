@@ -0,0 +1,173 @@
+use crate::{
+    base::clipboard::{self, Content}, prelude::*
+};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::io;
+
+struct Clipboard {
+    state: HandlerState,
+    running: Option<CancelHandle>,
+    job_id: u64,
+    /// 返回给`PB`的二进制数据
+    buf: Vec<u8>,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_clipboard")]
+impl Clipboard {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Clipboard {
+            state: HandlerState::new(session),
+            running: None,
+            job_id: 0,
+            buf: Vec::new(),
+            error: LastError::default()
+        }
+    }
+
+    /// 异步复制文本到剪贴板
+    ///
+    /// 完成后触发`OnSetComplete`
+    #[method(name = "AsyncSetText")]
+    fn async_set_text(&mut self, text: String) -> RetCode { self.async_set(Content::Text(text)) }
+
+    /// 异步复制图片到剪贴板
+    ///
+    /// # Parameters
+    ///
+    /// - `data` 图片文件数据，支持`PNG`、`JPEG`、`GIF`、`BMP`、`TIFF`等，如`nx_httpresponse.GetData()`
+    ///
+    /// # Description
+    ///
+    /// 在后台线程解码为位图后写入，`PNG`同时以`PNG`格式写入以保留透明度，完成后触发`OnSetComplete`
+    #[method(name = "AsyncSetImage")]
+    fn async_set_image(&mut self, data: &[u8]) -> RetCode {
+        if data.is_empty() {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "image data is empty");
+        }
+        self.async_set(Content::Image(data.to_vec()))
+    }
+
+    /// 异步复制图片文件到剪贴板
+    ///
+    /// 同`AsyncSetImage`，在后台线程读取文件
+    #[method(name = "AsyncSetImageFile")]
+    fn async_set_image_file(&mut self, path: String) -> RetCode {
+        if self.running.is_some() {
+            return RetCode::E_BUSY;
+        }
+        match std::fs::metadata(&path) {
+            Ok(meta) if meta.is_file() => {},
+            Ok(_) => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("not a file: {path}")),
+            Err(e) => return self.error.set_io(e)
+        }
+        self.spawn_set(move || clipboard::set(&Content::Image(std::fs::read(path)?)))
+    }
+
+    /// 异步复制自定义格式的二进制数据到剪贴板
+    ///
+    /// # Parameters
+    ///
+    /// - `format` 格式名称，如`HTML Format`、`Rich Text Format`，不存在时自动注册
+    /// - `data` 数据
+    #[method(name = "AsyncSetData")]
+    fn async_set_data(&mut self, format: String, data: &[u8]) -> RetCode {
+        if format.is_empty() {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "format is empty");
+        }
+        self.async_set(Content::Data {
+            format,
+            data: data.to_vec()
+        })
+    }
+
+    /// 是否正在执行异步复制
+    #[method(name = "IsBusy")]
+    fn is_busy(&self) -> bool { self.running.is_some() }
+
+    /// 读取剪贴板中的文本，没有文本时返回空字符串
+    #[method(name = "GetText")]
+    fn get_text(&self) -> String { clipboard::get_text().ok().flatten().unwrap_or_default() }
+
+    /// 读取剪贴板中自定义格式的数据，不存在时返回空`Blob`
+    #[method(name = "GetData")]
+    fn get_data(&mut self, format: String) -> &[u8] {
+        self.buf = match clipboard::register_format(&format).and_then(clipboard::get_data) {
+            Ok(data) => {
+                self.error.clear();
+                data.unwrap_or_default()
+            },
+            Err(e) => {
+                self.error.set_io(e);
+                Vec::new()
+            }
+        };
+        &self.buf
+    }
+
+    /// 剪贴板中是否有指定格式的数据
+    ///
+    /// # Parameters
+    ///
+    /// - `format` 格式名称，`text`表示文本，`image`表示位图，其它为自定义格式
+    #[method(name = "HasFormat")]
+    fn has_format(&self, format: String) -> bool { clipboard::has_format(&format) }
+
+    /// 异步复制完成
+    ///
+    /// 成功时`error`为空字符串
+    #[event(name = "OnSetComplete")]
+    fn on_set_complete(&mut self, error: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Clipboard {
+    fn async_set(&mut self, content: Content) -> RetCode {
+        if self.running.is_some() {
+            return RetCode::E_BUSY;
+        }
+        self.spawn_set(move || clipboard::set(&content))
+    }
+
+    fn spawn_set(&mut self, f: impl FnOnce() -> io::Result<()> + Send + 'static) -> RetCode {
+        self.job_id += 1;
+        let job_id = self.job_id;
+        let cancel_hdl = self.spawn(
+            async move {
+                match tokio::task::spawn_blocking(f).await {
+                    Ok(rv) => rv,
+                    Err(e) => Err(io::Error::new(io::ErrorKind::Other, e))
+                }
+            },
+            move |this, rv| {
+                if this.job_id == job_id {
+                    this.running = None;
+                }
+                match rv {
+                    Ok(()) => this.on_set_complete("".to_owned()),
+                    Err(e) => {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_set_complete(error);
+                    }
+                }
+            }
+        );
+        self.running = Some(cancel_hdl);
+        RetCode::OK
+    }
+}
+
+impl Handler for Clipboard {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
@@ -0,0 +1,344 @@
+use crate::{base::conv, prelude::*};
+use base64::{
+    alphabet, engine::{
+        general_purpose::{GeneralPurpose, GeneralPurposeConfig}, DecodePaddingMode
+    }, write::{EncoderStringWriter, EncoderWriter}, Engine
+};
+use pbni::pbx::*;
+use std::{
+    fs::File, io::{self, BufReader, BufWriter, Write}
+};
+
+/// 标准`Base64`，解码时不要求填充
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent)
+);
+
+/// `Base64URL`，编码时不填充，解码时不要求填充
+const BASE64_URL: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+);
+
+#[derive(Default)]
+struct Codec {
+    /// 返回给`PB`的二进制数据
+    buf: Vec<u8>,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_codec")]
+impl Codec {
+    /// `Base64`编码
+    #[method(name = "Base64Encode")]
+    fn base64_encode(&self, data: &[u8]) -> String { BASE64.encode(data) }
+
+    /// `Base64`编码字符串
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    #[method(name = "Base64EncodeString", overload = 1)]
+    fn base64_encode_string(&self, data: String, encoding: Option<pblong>) -> String {
+        BASE64.encode(conv::encode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)))
+    }
+
+    /// `Base64`解码
+    ///
+    /// 忽略空白字符，数据无效时返回空`Blob`
+    #[method(name = "Base64Decode")]
+    fn base64_decode(&mut self, data: String) -> &[u8] {
+        self.buf = self.decode_or_default(decode(&BASE64, &data), "invalid base64 data");
+        &self.buf
+    }
+
+    /// `Base64`解码为字符串
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    #[method(name = "Base64DecodeString", overload = 1)]
+    fn base64_decode_string(&self, data: String, encoding: Option<pblong>) -> String {
+        let data = decode(&BASE64, &data).unwrap_or_default();
+        conv::decode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)).into_owned()
+    }
+
+    /// `Base64URL`编码(无填充)
+    #[method(name = "Base64UrlEncode")]
+    fn base64_url_encode(&self, data: &[u8]) -> String { BASE64_URL.encode(data) }
+
+    /// `Base64URL`编码字符串(无填充)
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    #[method(name = "Base64UrlEncodeString", overload = 1)]
+    fn base64_url_encode_string(&self, data: String, encoding: Option<pblong>) -> String {
+        BASE64_URL.encode(conv::encode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)))
+    }
+
+    /// `Base64URL`解码
+    ///
+    /// 忽略空白字符，数据无效时返回空`Blob`
+    #[method(name = "Base64UrlDecode")]
+    fn base64_url_decode(&mut self, data: String) -> &[u8] {
+        self.buf = self.decode_or_default(decode(&BASE64_URL, &data), "invalid base64url data");
+        &self.buf
+    }
+
+    /// `Base64URL`解码为字符串
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    #[method(name = "Base64UrlDecodeString", overload = 1)]
+    fn base64_url_decode_string(&self, data: String, encoding: Option<pblong>) -> String {
+        let data = decode(&BASE64_URL, &data).unwrap_or_default();
+        conv::decode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)).into_owned()
+    }
+
+    /// 读取文件并进行`Base64`编码
+    ///
+    /// # Description
+    ///
+    /// 分块读取文件，不需要将文件内容整体加载到`PB`中，失败时返回空字符串
+    #[method(name = "Base64EncodeFile")]
+    fn base64_encode_file(&self, path: String) -> String {
+        let encode = || -> io::Result<String> {
+            let mut reader = BufReader::new(File::open(&path)?);
+            let mut writer = EncoderStringWriter::new(&BASE64);
+            io::copy(&mut reader, &mut writer)?;
+            Ok(writer.into_inner())
+        };
+        encode().unwrap_or_default()
+    }
+
+    /// 读取文件进行`Base64`编码并写入另一个文件
+    ///
+    /// # Parameters
+    ///
+    /// - `src` 源文件
+    /// - `dest` 目标文件
+    #[method(name = "Base64EncodeFileTo")]
+    fn base64_encode_file_to(&mut self, src: String, dest: String) -> RetCode {
+        let mut reader = match File::open(&src) {
+            Ok(file) => BufReader::new(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return self.error.set(RetCode::E_FILE_NOT_FOUND, e);
+            },
+            Err(e) => return self.error.set(RetCode::E_IO_ERROR, e)
+        };
+        let mut encode = || -> io::Result<()> {
+            let mut writer = EncoderWriter::new(BufWriter::new(File::create(&dest)?), &BASE64);
+            io::copy(&mut reader, &mut writer)?;
+            writer.finish()?.flush()
+        };
+        match encode() {
+            Ok(()) => {
+                self.error.clear();
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_IO_ERROR, e)
+        }
+    }
+
+    /// `Base64`解码并写入文件
+    #[method(name = "Base64DecodeToFile")]
+    fn base64_decode_to_file(&mut self, data: String, dest: String) -> RetCode {
+        let data = match decode(&BASE64, &data) {
+            Some(data) => data,
+            None => return self.error.set(RetCode::E_INVALID_DATA, "invalid base64 data")
+        };
+        match std::fs::write(&dest, data) {
+            Ok(()) => {
+                self.error.clear();
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_IO_ERROR, e)
+        }
+    }
+
+    /// 编码为`Data URI`(`data:<MIME类型>;base64,<数据>`)
+    ///
+    /// # Parameters
+    ///
+    /// - `mime_type` `MIME`类型，默认根据数据内容识别常见的图片与`PDF`格式，无法识别时为`application/octet-stream`
+    #[method(name = "DataUriEncode", overload = 1)]
+    fn data_uri_encode(&self, data: &[u8], mime_type: Option<String>) -> String {
+        let mime_type = mime_type.filter(|mime_type| !mime_type.is_empty());
+        let mime_type = mime_type.as_deref().unwrap_or_else(|| sniff_mime_type(data));
+        format!("data:{mime_type};base64,{}", BASE64.encode(data))
+    }
+
+    /// 解码`Data URI`
+    ///
+    /// 支持`Base64`与百分号编码的数据，数据无效时返回空`Blob`
+    #[method(name = "DataUriDecode")]
+    fn data_uri_decode(&mut self, uri: String) -> &[u8] {
+        self.buf = self.decode_or_default(parse_data_uri(&uri).map(|(_, data)| data), "invalid data uri");
+        &self.buf
+    }
+
+    /// 获取`Data URI`的`MIME`类型(不含参数)
+    ///
+    /// 未指定时为`text/plain`，不是有效的`Data URI`时返回空字符串
+    #[method(name = "DataUriGetMimeType")]
+    fn data_uri_get_mime_type(&self, uri: String) -> String {
+        parse_data_uri(&uri).map(|(mime_type, _)| mime_type).unwrap_or_default()
+    }
+
+    /// 十六进制编码
+    ///
+    /// # Parameters
+    ///
+    /// - `upper` 是否使用大写字母，默认`true`
+    /// - `separator` 字节之间的分隔符，默认无
+    #[method(name = "HexEncode", overload = 2)]
+    fn hex_encode(&self, data: &[u8], upper: Option<bool>, separator: Option<String>) -> String {
+        hex_encode(data, upper.unwrap_or(true), separator.as_deref().unwrap_or_default())
+    }
+
+    /// 十六进制编码字符串
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    /// - `upper` 是否使用大写字母，默认`true`
+    /// - `separator` 字节之间的分隔符，默认无
+    #[method(name = "HexEncodeString", overload = 3)]
+    fn hex_encode_string(
+        &self,
+        data: String,
+        encoding: Option<pblong>,
+        upper: Option<bool>,
+        separator: Option<String>
+    ) -> String {
+        hex_encode(
+            &conv::encode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)),
+            upper.unwrap_or(true),
+            separator.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// 十六进制解码
+    ///
+    /// 忽略大小写、空白字符、`0x`前缀以及`:` `-` `,`分隔符，数据无效时返回空`Blob`
+    #[method(name = "HexDecode")]
+    fn hex_decode(&mut self, data: String) -> &[u8] {
+        self.buf = self.decode_or_default(conv::hex_decode(&data), "invalid hex data");
+        &self.buf
+    }
+
+    /// 十六进制解码为字符串
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    #[method(name = "HexDecodeString", overload = 1)]
+    fn hex_decode_string(&self, data: String, encoding: Option<pblong>) -> String {
+        let data = conv::hex_decode(&data).unwrap_or_default();
+        conv::decode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)).into_owned()
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+/// 解码`Base64`，忽略空白字符
+impl Codec {
+    /// 记录解码结果，失败时返回空数据
+    fn decode_or_default(&mut self, data: Option<Vec<u8>>, err: &str) -> Vec<u8> {
+        match data {
+            Some(data) => {
+                self.error.clear();
+                data
+            },
+            None => {
+                self.error.set(RetCode::E_INVALID_DATA, err);
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn decode(engine: &GeneralPurpose, data: &str) -> Option<Vec<u8>> {
+    if data.bytes().any(|c| c.is_ascii_whitespace()) {
+        let data: Vec<u8> = data.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+        engine.decode(data).ok()
+    } else {
+        engine.decode(data).ok()
+    }
+}
+
+/// 根据文件签名识别`MIME`类型
+fn sniff_mime_type(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        "image/jpeg"
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        "image/gif"
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        "image/webp"
+    } else if data.starts_with(b"BM") {
+        "image/bmp"
+    } else if data.starts_with(b"%PDF-") {
+        "application/pdf"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// 解析`Data URI`
+///
+/// # Returns
+///
+/// (`MIME`类型，数据)
+fn parse_data_uri(uri: &str) -> Option<(String, Vec<u8>)> {
+    let uri = uri.trim();
+    if !uri.get(..5)?.eq_ignore_ascii_case("data:") {
+        return None;
+    }
+    let (meta, data) = uri[5..].split_once(',')?;
+    let mut params = meta.split(';');
+    let mime_type = params.next().unwrap_or_default().trim();
+    let is_base64 = params.any(|param| param.trim().eq_ignore_ascii_case("base64"));
+    let mime_type = if mime_type.is_empty() {
+        "text/plain".to_owned()
+    } else {
+        mime_type.to_ascii_lowercase()
+    };
+    let data = if is_base64 {
+        //`Base64`数据中可能含有百分号编码的字符
+        decode(&BASE64, &percent_encoding::percent_decode_str(data).decode_utf8().ok()?)?
+    } else {
+        percent_encoding::percent_decode_str(data).collect()
+    };
+    Some((mime_type, data))
+}
+
+/// 十六进制编码
+fn hex_encode(data: &[u8], upper: bool, separator: &str) -> String {
+    let digits = if upper {
+        b"0123456789ABCDEF"
+    } else {
+        b"0123456789abcdef"
+    };
+    let mut rv = String::with_capacity(data.len() * (2 + separator.len()));
+    for (idx, byte) in data.iter().enumerate() {
+        if idx > 0 {
+            rv.push_str(separator);
+        }
+        rv.push(digits[(byte >> 4) as usize] as char);
+        rv.push(digits[(byte & 0x0F) as usize] as char);
+    }
+    rv
+}
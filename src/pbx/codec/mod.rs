@@ -0,0 +1,4 @@
+mod convert;
+mod uri;
+#[cfg(feature = "protobuf")]
+mod protobuf;
@@ -0,0 +1,202 @@
+use crate::prelude::*;
+use pbni::pbx::*;
+use prost::Message;
+use prost_reflect::{
+    DescriptorPool, DeserializeOptions, DynamicMessage, MessageDescriptor, SerializeOptions
+};
+
+/// `gRPC`消息帧头长度(1字节压缩标志 + 4字节长度)
+const GRPC_HEADER_LEN: usize = 5;
+
+#[derive(Default)]
+struct Protobuf {
+    pool: DescriptorPool,
+    /// 序列化`JSON`时输出默认值字段
+    include_defaults: bool,
+    /// 序列化`JSON`时使用`.proto`中的字段名而不是`lowerCamelCase`
+    proto_field_names: bool,
+    /// 解析`JSON`时忽略未知字段
+    ignore_unknown: bool,
+    /// 返回给`PB`的二进制数据
+    buf: Vec<u8>,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_protobuf")]
+impl Protobuf {
+    /// 加载`protoc --descriptor_set_out`生成的描述符集合
+    ///
+    /// # Description
+    ///
+    /// 可多次加载，依赖的文件需要先加载或包含在同一集合中(`--include_imports`)
+    #[method(name = "LoadDescriptorSet")]
+    fn load_descriptor_set(&mut self, data: &[u8]) -> RetCode {
+        match self.pool.decode_file_descriptor_set(data) {
+            Ok(_) => {
+                self.error.clear();
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_INVALID_DATA, e)
+        }
+    }
+
+    /// 从文件加载描述符集合
+    #[method(name = "LoadDescriptorSetFile")]
+    fn load_descriptor_set_file(&mut self, path: String) -> RetCode {
+        match std::fs::read(&path) {
+            Ok(data) => self.load_descriptor_set(&data),
+            Err(e) => {
+                let code = if e.kind() == std::io::ErrorKind::NotFound {
+                    RetCode::E_FILE_NOT_FOUND
+                } else {
+                    RetCode::E_IO_ERROR
+                };
+                self.error.set(code, e)
+            }
+        }
+    }
+
+    /// 是否已加载指定的消息类型
+    ///
+    /// # Parameters
+    ///
+    /// - `type_name` 包含包名的完整名称，如`package.MyMessage`
+    #[method(name = "HasType")]
+    fn has_type(&self, type_name: String) -> bool { self.pool.get_message_by_name(&type_name).is_some() }
+
+    /// 已加载的所有消息类型名称
+    #[method(name = "GetTypeNames")]
+    fn type_names(&self) -> Vec<String> {
+        self.pool.all_messages().map(|msg| msg.full_name().to_owned()).collect()
+    }
+
+    /// 设置序列化`JSON`时是否输出默认值字段，默认`false`
+    #[method(name = "SetIncludeDefaults")]
+    fn set_include_defaults(&mut self, enabled: bool) -> RetCode {
+        self.include_defaults = enabled;
+        RetCode::OK
+    }
+
+    /// 设置序列化`JSON`时是否使用`.proto`中的字段名，默认`false`(使用`lowerCamelCase`)
+    #[method(name = "SetProtoFieldNames")]
+    fn set_proto_field_names(&mut self, enabled: bool) -> RetCode {
+        self.proto_field_names = enabled;
+        RetCode::OK
+    }
+
+    /// 设置解析`JSON`时是否忽略未知字段，默认`false`
+    #[method(name = "SetIgnoreUnknownFields")]
+    fn set_ignore_unknown_fields(&mut self, enabled: bool) -> RetCode {
+        self.ignore_unknown = enabled;
+        RetCode::OK
+    }
+
+    /// 将`JSON`转换为二进制消息
+    ///
+    /// # Parameters
+    ///
+    /// - `type_name` 包含包名的完整消息类型名称
+    /// - `json` 符合`proto3 JSON`映射的数据
+    /// - `grpc_frame` 是否添加`gRPC`消息帧头，默认`false`
+    ///
+    /// # Returns
+    ///
+    /// 失败时返回空`Blob`，通过`GetLastErrorText`获取错误信息
+    #[method(name = "Encode", overload = 1)]
+    fn encode(&mut self, type_name: String, json: String, grpc_frame: Option<bool>) -> &[u8] {
+        self.buf = match self.json_to_message(&type_name, &json) {
+            Ok(msg) => {
+                self.error.clear();
+                let data = msg.encode_to_vec();
+                if grpc_frame.unwrap_or_default() {
+                    let mut buf = Vec::with_capacity(GRPC_HEADER_LEN + data.len());
+                    buf.push(0);
+                    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+                    buf.extend_from_slice(&data);
+                    buf
+                } else {
+                    data
+                }
+            },
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_DATA, e);
+                Vec::new()
+            }
+        };
+        &self.buf
+    }
+
+    /// 将二进制消息转换为`JSON`
+    ///
+    /// # Parameters
+    ///
+    /// - `type_name` 包含包名的完整消息类型名称
+    /// - `grpc_frame` 数据是否包含`gRPC`消息帧头，默认`false`
+    ///
+    /// # Returns
+    ///
+    /// 失败时返回空字符串，通过`GetLastErrorText`获取错误信息
+    #[method(name = "Decode", overload = 1)]
+    fn decode(&mut self, type_name: String, data: &[u8], grpc_frame: Option<bool>) -> String {
+        match self.message_to_json(&type_name, data, grpc_frame.unwrap_or_default()) {
+            Ok(json) => {
+                self.error.clear();
+                json
+            },
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_DATA, e);
+                "".to_owned()
+            }
+        }
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Protobuf {
+    fn descriptor(&self, type_name: &str) -> Result<MessageDescriptor, String> {
+        self.pool
+            .get_message_by_name(type_name)
+            .ok_or_else(|| format!("message type '{type_name}' not found"))
+    }
+
+    fn json_to_message(&self, type_name: &str, json: &str) -> Result<DynamicMessage, String> {
+        let desc = self.descriptor(type_name)?;
+        let options = DeserializeOptions::new().deny_unknown_fields(!self.ignore_unknown);
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let msg = DynamicMessage::deserialize_with_options(desc, &mut deserializer, &options)
+            .map_err(|e| e.to_string())?;
+        deserializer.end().map_err(|e| e.to_string())?;
+        Ok(msg)
+    }
+
+    fn message_to_json(&self, type_name: &str, data: &[u8], grpc_frame: bool) -> Result<String, String> {
+        let desc = self.descriptor(type_name)?;
+        let data = if grpc_frame {
+            if data.len() < GRPC_HEADER_LEN {
+                return Err("incomplete gRPC frame header".to_owned());
+            }
+            let (header, body) = data.split_at(GRPC_HEADER_LEN);
+            if header[0] != 0 {
+                return Err("compressed gRPC message is not supported".to_owned());
+            }
+            let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+            body.get(..len).ok_or_else(|| "incomplete gRPC message".to_owned())?
+        } else {
+            data
+        };
+        let msg = DynamicMessage::decode(desc, data).map_err(|e| e.to_string())?;
+        let options = SerializeOptions::new()
+            .skip_default_fields(!self.include_defaults)
+            .use_proto_field_name(self.proto_field_names);
+        let mut serializer = serde_json::Serializer::new(Vec::new());
+        msg.serialize_with_options(&mut serializer, &options).map_err(|e| e.to_string())?;
+        String::from_utf8(serializer.into_inner()).map_err(|e| e.to_string())
+    }
+}
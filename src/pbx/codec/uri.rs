@@ -0,0 +1,347 @@
+use crate::{base::conv, prelude::*};
+use pbni::pbx::*;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use url::form_urlencoded;
+
+/// `URL`组件编码字符集(保留`RFC 3986`非保留字符)
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+#[derive(Default)]
+struct Url {
+    inner: Option<url::Url>,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_url")]
+impl Url {
+    /// 解析`URL`
+    ///
+    /// # Returns
+    ///
+    /// 格式无效时返回`E_INVALID_DATA`并清除原有数据，通过`GetLastErrorText`获取错误信息
+    #[method(name = "Parse")]
+    fn parse(&mut self, url: String) -> RetCode {
+        match url::Url::parse(url.trim()) {
+            Ok(url) => {
+                self.inner = Some(url);
+                self.error.clear();
+                RetCode::OK
+            },
+            Err(e) => {
+                self.inner = None;
+                self.error.set(RetCode::E_INVALID_DATA, e)
+            }
+        }
+    }
+
+    /// 是否已解析有效的`URL`
+    #[method(name = "IsValid")]
+    fn is_valid(&self) -> bool { self.inner.is_some() }
+
+    /// 完整的`URL`
+    #[method(name = "ToString")]
+    fn href(&self) -> String { self.inner.as_ref().map(|url| url.to_string()).unwrap_or_default() }
+
+    #[method(name = "GetScheme")]
+    fn scheme(&self) -> String { self.inner.as_ref().map(|url| url.scheme().to_owned()).unwrap_or_default() }
+
+    #[method(name = "GetUserName")]
+    fn username(&self) -> String {
+        self.inner.as_ref().map(|url| url.username().to_owned()).unwrap_or_default()
+    }
+
+    #[method(name = "GetPassword")]
+    fn password(&self) -> String {
+        self.inner.as_ref().and_then(|url| url.password()).unwrap_or_default().to_owned()
+    }
+
+    #[method(name = "GetHost")]
+    fn host(&self) -> String {
+        self.inner.as_ref().and_then(|url| url.host_str()).unwrap_or_default().to_owned()
+    }
+
+    /// 端口
+    ///
+    /// 未指定时返回协议的默认端口，未知时返回`-1`
+    #[method(name = "GetPort")]
+    fn port(&self) -> pblong {
+        self.inner.as_ref().and_then(|url| url.port_or_known_default()).map_or(-1, |port| port as pblong)
+    }
+
+    #[method(name = "GetPath")]
+    fn path(&self) -> String { self.inner.as_ref().map(|url| url.path().to_owned()).unwrap_or_default() }
+
+    /// 查询字符串(不包含`?`)
+    #[method(name = "GetQuery")]
+    fn query(&self) -> String {
+        self.inner.as_ref().and_then(|url| url.query()).unwrap_or_default().to_owned()
+    }
+
+    /// 片段(不包含`#`)
+    #[method(name = "GetFragment")]
+    fn fragment(&self) -> String {
+        self.inner.as_ref().and_then(|url| url.fragment()).unwrap_or_default().to_owned()
+    }
+
+    #[method(name = "SetScheme")]
+    fn set_scheme(&mut self, scheme: String) -> RetCode {
+        match self.inner.as_mut() {
+            Some(url) if url.set_scheme(&scheme).is_ok() => RetCode::OK,
+            Some(_) => self.error.set(RetCode::E_INVALID_ARGUMENT, "cannot be set for this url"),
+            None => self.error.set(RetCode::E_INVALID_OBJECT, "url not parsed")
+        }
+    }
+
+    #[method(name = "SetUserName")]
+    fn set_username(&mut self, username: String) -> RetCode {
+        match self.inner.as_mut() {
+            Some(url) if url.set_username(&username).is_ok() => RetCode::OK,
+            Some(_) => self.error.set(RetCode::E_INVALID_ARGUMENT, "cannot be set for this url"),
+            None => self.error.set(RetCode::E_INVALID_OBJECT, "url not parsed")
+        }
+    }
+
+    #[method(name = "SetPassword")]
+    fn set_password(&mut self, password: String) -> RetCode {
+        match self.inner.as_mut() {
+            Some(url) if url.set_password((!password.is_empty()).then_some(password.as_str())).is_ok() => {
+                RetCode::OK
+            },
+            Some(_) => self.error.set(RetCode::E_INVALID_ARGUMENT, "cannot be set for this url"),
+            None => self.error.set(RetCode::E_INVALID_OBJECT, "url not parsed")
+        }
+    }
+
+    #[method(name = "SetHost")]
+    fn set_host(&mut self, host: String) -> RetCode {
+        match self.inner.as_mut() {
+            Some(url) if url.set_host(Some(&host)).is_ok() => RetCode::OK,
+            Some(_) => self.error.set(RetCode::E_INVALID_ARGUMENT, "cannot be set for this url"),
+            None => self.error.set(RetCode::E_INVALID_OBJECT, "url not parsed")
+        }
+    }
+
+    /// 设置端口
+    ///
+    /// 小于等于0时使用协议的默认端口
+    #[method(name = "SetPort")]
+    fn set_port(&mut self, port: pblong) -> RetCode {
+        let port = match port {
+            port if port <= 0 => None,
+            port if port <= u16::MAX as pblong => Some(port as u16),
+            _ => return self.error.set(RetCode::E_OUT_OF_RANGE, format!("invalid port: {port}"))
+        };
+        match self.inner.as_mut() {
+            Some(url) if url.set_port(port).is_ok() => RetCode::OK,
+            Some(_) => self.error.set(RetCode::E_INVALID_ARGUMENT, "cannot be set for this url"),
+            None => self.error.set(RetCode::E_INVALID_OBJECT, "url not parsed")
+        }
+    }
+
+    #[method(name = "SetPath")]
+    fn set_path(&mut self, path: String) -> RetCode {
+        match self.inner.as_mut() {
+            Some(url) => {
+                url.set_path(&path);
+                RetCode::OK
+            },
+            None => self.error.set(RetCode::E_INVALID_OBJECT, "url not parsed")
+        }
+    }
+
+    /// 设置查询字符串(不包含`?`)，为空时删除
+    #[method(name = "SetQuery")]
+    fn set_query(&mut self, query: String) -> RetCode {
+        match self.inner.as_mut() {
+            Some(url) => {
+                url.set_query((!query.is_empty()).then_some(query.as_str()));
+                RetCode::OK
+            },
+            None => self.error.set(RetCode::E_INVALID_OBJECT, "url not parsed")
+        }
+    }
+
+    /// 设置片段(不包含`#`)，为空时删除
+    #[method(name = "SetFragment")]
+    fn set_fragment(&mut self, fragment: String) -> RetCode {
+        match self.inner.as_mut() {
+            Some(url) => {
+                url.set_fragment((!fragment.is_empty()).then_some(fragment.as_str()));
+                RetCode::OK
+            },
+            None => self.error.set(RetCode::E_INVALID_OBJECT, "url not parsed")
+        }
+    }
+
+    /// 获取查询参数(已解码)
+    ///
+    /// 存在多个同名参数时返回第一个，不存在时返回空字符串
+    #[method(name = "GetQueryParam")]
+    fn query_param(&self, name: String) -> String {
+        self.query_pairs().into_iter().find(|(key, _)| key == &name).map(|(_, val)| val).unwrap_or_default()
+    }
+
+    /// 获取所有同名的查询参数(已解码)
+    #[method(name = "GetQueryParams")]
+    fn query_params(&self, name: String) -> Vec<String> {
+        self.query_pairs().into_iter().filter(|(key, _)| key == &name).map(|(_, val)| val).collect()
+    }
+
+    #[method(name = "HasQueryParam")]
+    fn has_query_param(&self, name: String) -> bool { self.query_pairs().iter().any(|(key, _)| key == &name) }
+
+    /// 设置查询参数(自动编码)
+    ///
+    /// 替换第一个同名参数并删除其它同名参数，不存在时追加
+    #[method(name = "SetQueryParam")]
+    fn set_query_param(&mut self, name: String, value: String) -> RetCode {
+        let mut pairs = self.query_pairs();
+        let mut found = false;
+        pairs.retain_mut(|(key, val)| {
+            if key != &name {
+                return true;
+            }
+            if found {
+                return false;
+            }
+            found = true;
+            *val = value.clone();
+            true
+        });
+        if !found {
+            pairs.push((name, value));
+        }
+        self.set_query_pairs(pairs)
+    }
+
+    /// 追加查询参数(自动编码)，允许同名参数
+    #[method(name = "AddQueryParam")]
+    fn add_query_param(&mut self, name: String, value: String) -> RetCode {
+        let mut pairs = self.query_pairs();
+        pairs.push((name, value));
+        self.set_query_pairs(pairs)
+    }
+
+    /// 删除所有同名的查询参数
+    #[method(name = "RemoveQueryParam")]
+    fn remove_query_param(&mut self, name: String) -> RetCode {
+        let mut pairs = self.query_pairs();
+        let len = pairs.len();
+        pairs.retain(|(key, _)| key != &name);
+        if pairs.len() == len {
+            return self.error.set(RetCode::E_DATA_NOT_FOUND, format!("query param not found: {name}"));
+        }
+        self.set_query_pairs(pairs)
+    }
+
+    /// 基于当前`URL`解析相对地址
+    ///
+    /// # Returns
+    ///
+    /// 解析后的绝对`URL`，失败时返回空字符串
+    #[method(name = "Join")]
+    fn join(&self, relative: String) -> String {
+        self.inner
+            .as_ref()
+            .and_then(|url| url.join(relative.trim()).ok())
+            .map(|url| url.to_string())
+            .unwrap_or_default()
+    }
+
+    /// 基于`base`解析相对地址
+    ///
+    /// # Returns
+    ///
+    /// 解析后的绝对`URL`，失败时返回空字符串
+    #[method(name = "Resolve")]
+    fn resolve(&self, base: String, relative: String) -> String {
+        url::Url::parse(base.trim())
+            .and_then(|url| url.join(relative.trim()))
+            .map(|url| url.to_string())
+            .unwrap_or_default()
+    }
+
+    /// 百分号编码(`RFC 3986`组件)
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    ///
+    /// # Description
+    ///
+    /// 除`A-Z a-z 0-9 - . _ ~`以外的字符均被编码，空格编码为`%20`
+    #[method(name = "PercentEncode", overload = 1)]
+    fn percent_encode(&self, text: String, encoding: Option<pblong>) -> String {
+        match encoding.unwrap_or(conv::ENCODING_UTF8) {
+            conv::ENCODING_UTF8 => utf8_percent_encode(&text, COMPONENT).to_string(),
+            encoding => {
+                percent_encoding::percent_encode(&conv::encode(&text, encoding), COMPONENT).to_string()
+            },
+        }
+    }
+
+    /// 百分号解码
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    ///
+    /// # Description
+    ///
+    /// `+`不会被解码为空格，表单数据使用`FormDecode`
+    #[method(name = "PercentDecode", overload = 1)]
+    fn percent_decode(&self, text: String, encoding: Option<pblong>) -> String {
+        let data: Vec<u8> = percent_decode_str(&text).collect();
+        conv::decode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)).into_owned()
+    }
+
+    /// `application/x-www-form-urlencoded`编码
+    ///
+    /// 空格编码为`+`
+    #[method(name = "FormEncode")]
+    fn form_encode(&self, text: String) -> String {
+        form_urlencoded::byte_serialize(text.as_bytes()).collect()
+    }
+
+    /// `application/x-www-form-urlencoded`解码
+    ///
+    /// `+`解码为空格
+    #[method(name = "FormDecode")]
+    fn form_decode(&self, text: String) -> String {
+        let text = text.replace('+', " ");
+        percent_decode_str(&text).decode_utf8_lossy().into_owned()
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Url {
+    /// 已解码的查询参数
+    fn query_pairs(&self) -> Vec<(String, String)> {
+        match &self.inner {
+            Some(url) => url.query_pairs().map(|(key, val)| (key.into_owned(), val.into_owned())).collect(),
+            None => Vec::new()
+        }
+    }
+
+    /// 重新生成查询字符串
+    fn set_query_pairs(&mut self, pairs: Vec<(String, String)>) -> RetCode {
+        match self.inner.as_mut() {
+            Some(url) => {
+                if pairs.is_empty() {
+                    url.set_query(None);
+                } else {
+                    url.query_pairs_mut().clear().extend_pairs(pairs);
+                }
+                RetCode::OK
+            },
+            None => self.error.set(RetCode::E_INVALID_OBJECT, "url not parsed")
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use crate::{base::locale::tr, prelude::*};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::{
+    fs::{self, File}, io::{self, BufReader, BufWriter, Read, Write}, path::{Path, PathBuf}
+};
+use zip::{
+    result::ZipError, write::SimpleFileOptions, AesMode, CompressionMethod, ZipArchive, ZipWriter, ZIP64_BYTES_THR
+};
+
+/// 文件读写块大小
+const CHUNK_SIZE: usize = 256 * 1024;
+/// 默认压缩级别
+const DEFAULT_LEVEL: i64 = 6;
+
+/// 待添加的源
+enum Source {
+    File {
+        path: PathBuf,
+        name: String
+    },
+    Dir {
+        path: PathBuf,
+        name: String
+    }
+}
+
+/// 展开后的条目
+struct Entry {
+    path: PathBuf,
+    /// 压缩包内的名称(`/`分隔)
+    name: String,
+    is_dir: bool
+}
+
+/// 条目进度回调，参数为条目名称、序号(从1开始)、条目总数、条目大小和已处理的大小，返回`false`中止
+type Progress<'a> = dyn FnMut(&str, usize, usize, u64, u64) -> bool + 'a;
+
+struct Zip {
+    state: HandlerState,
+    sources: Vec<Source>,
+    password: Option<String>,
+    level: i64,
+    job: JobSlot,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_zip")]
+impl Zip {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Zip {
+            state: HandlerState::new(session),
+            sources: Vec::new(),
+            password: None,
+            level: DEFAULT_LEVEL,
+            job: JobSlot::default(),
+            error: LastError::default()
+        }
+    }
+
+    /// 添加文件
+    ///
+    /// # Parameters
+    ///
+    /// - `name` 压缩包内的路径，默认为文件名
+    #[method(name = "AddFile", overload = 1)]
+    fn add_file(&mut self, path: String, name: Option<String>) -> RetCode {
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            return self.error.set(RetCode::E_FILE_NOT_FOUND, format!("file not found: {}", path.display()));
+        }
+        let name = entry_name(name, &path);
+        if name.is_empty() {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "entry name is empty");
+        }
+        self.sources.push(Source::File {
+            path,
+            name
+        });
+        RetCode::OK
+    }
+
+    /// 添加目录(包含所有子目录和文件)
+    ///
+    /// # Parameters
+    ///
+    /// - `name` 压缩包内的路径，默认为目录名，为空字符串时添加到根目录
+    #[method(name = "AddDirectory", overload = 1)]
+    fn add_directory(&mut self, path: String, name: Option<String>) -> RetCode {
+        let path = PathBuf::from(path);
+        if !path.is_dir() {
+            return self
+                .error
+                .set(RetCode::E_FILE_NOT_FOUND, format!("directory not found: {}", path.display()));
+        }
+        let name = entry_name(name, &path);
+        self.sources.push(Source::Dir {
+            path,
+            name
+        });
+        RetCode::OK
+    }
+
+    /// 清除已添加的文件和目录
+    #[method(name = "Clear")]
+    fn clear(&mut self) -> RetCode {
+        self.sources.clear();
+        RetCode::OK
+    }
+
+    /// 已添加的文件和目录数量
+    #[method(name = "GetCount")]
+    fn count(&self) -> pbulong { self.sources.len() as pbulong }
+
+    /// 设置密码
+    ///
+    /// # Description
+    ///
+    /// 创建时使用`AES-256`加密所有条目，解压时用于解密(同时支持`AES`和`ZipCrypto`)，为空时不加密
+    #[method(name = "SetPassword")]
+    fn set_password(&mut self, password: String) -> RetCode {
+        self.password = (!password.is_empty()).then_some(password);
+        RetCode::OK
+    }
+
+    /// 设置压缩级别
+    ///
+    /// # Parameters
+    ///
+    /// - `level` `0-9`，0为仅存储，默认6
+    #[method(name = "SetCompressionLevel")]
+    fn set_compression_level(&mut self, level: pblong) -> RetCode {
+        if !(0..=9).contains(&level) {
+            return self.error.set(RetCode::E_OUT_OF_RANGE, format!("invalid compression level: {level}"));
+        }
+        self.level = level as i64;
+        RetCode::OK
+    }
+
+    /// 异步创建压缩包
+    ///
+    /// # Description
+    ///
+    /// 在后台线程处理已添加的文件和目录，通过`OnProgress`通知每个条目的进度，完成后触发`OnComplete`，
+    /// 开始执行后清除已添加的文件和目录
+    #[method(name = "CreateAsync")]
+    fn create_async(&mut self, dest: String) -> RetCode {
+        if self.job.is_running() {
+            return RetCode::E_BUSY;
+        }
+        if self.sources.is_empty() {
+            return self.error.set(RetCode::E_DATA_NOT_FOUND, "no files or directories added");
+        }
+        let sources = std::mem::take(&mut self.sources);
+        let (password, level) = (self.password.clone(), self.level);
+        self.spawn_task(move |progress| create_archive(&dest, sources, password.as_deref(), level, progress))
+    }
+
+    /// 异步解压
+    ///
+    /// # Parameters
+    ///
+    /// - `dest_dir` 目标目录，不存在时自动创建，同名文件将被覆盖
+    ///
+    /// # Description
+    ///
+    /// 跳过路径指向目标目录以外的条目
+    #[method(name = "ExtractAsync")]
+    fn extract_async(&mut self, src: String, dest_dir: String) -> RetCode {
+        if self.job.is_running() {
+            return RetCode::E_BUSY;
+        }
+        if !Path::new(&src).is_file() {
+            return self.error.set(RetCode::E_FILE_NOT_FOUND, format!("file not found: {src}"));
+        }
+        let password = self.password.clone();
+        self.spawn_task(move |progress| extract_archive(&src, &dest_dir, password.as_deref(), progress))
+    }
+
+    /// 压缩包内的条目名称列表
+    #[method(name = "GetEntries")]
+    fn entries(&self, src: String) -> Vec<String> {
+        File::open(src)
+            .map_err(ZipError::from)
+            .and_then(|file| ZipArchive::new(BufReader::new(file)))
+            .map(|archive| archive.file_names().map(ToOwned::to_owned).collect())
+            .unwrap_or_default()
+    }
+
+    /// 取消异步任务
+    #[method(name = "Cancel")]
+    fn cancel(&mut self) -> RetCode {
+        if self.job.cancel() {
+            RetCode::OK
+        } else {
+            RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.job.is_running() }
+
+    /// 条目处理进度
+    ///
+    /// # Parameters
+    ///
+    /// - `name` 条目名称
+    /// - `index` 条目序号(从1开始)
+    /// - `count` 条目总数
+    /// - `total` 条目大小
+    /// - `processed` 条目已处理的大小
+    ///
+    /// 返回`PREVENT`取消任务
+    #[event(name = "OnProgress")]
+    fn on_progress(
+        &mut self,
+        name: String,
+        index: pbulong,
+        count: pbulong,
+        total: pbulong,
+        processed: pbulong
+    ) -> RetCode {
+    }
+
+    /// 异步任务完成
+    ///
+    /// 成功时`error`为空字符串，创建失败时删除目标文件
+    #[event(name = "OnComplete")]
+    fn on_complete(&mut self, error: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Zip {
+    fn spawn_task<F>(&mut self, task: F) -> RetCode
+    where
+        F: FnOnce(&mut Progress) -> io::Result<()> + Send + 'static
+    {
+        spawn_file_job(
+            self,
+            |this| &mut this.job,
+            move |ctx| {
+                //条目结束时总是通知
+                task(&mut |name, index, count, total, processed| {
+                    ctx.progress_with(
+                        total,
+                        processed,
+                        (name.to_owned(), index, count, total, processed),
+                        |this, (name, index, count, total, processed)| {
+                            this.on_progress(
+                                name,
+                                index as pbulong,
+                                count as pbulong,
+                                total as pbulong,
+                                processed as pbulong
+                            )
+                        }
+                    )
+                })
+            },
+            |this, rv| {
+                match rv {
+                    Ok(_) => this.on_complete("".to_owned()),
+                    Err(e) => {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_complete(error);
+                    }
+                }
+            }
+        )
+    }
+}
+
+impl Handler for Zip {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+/// 规范化压缩包内的名称
+fn entry_name(name: Option<String>, path: &Path) -> String {
+    let name = match name {
+        Some(name) => name,
+        None => path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+    };
+    name.replace('\\', "/").trim_matches('/').to_owned()
+}
+
+fn join_name(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{parent}/{name}")
+    }
+}
+
+/// 展开目录
+fn expand(sources: Vec<Source>) -> io::Result<Vec<Entry>> {
+    fn walk(path: &Path, name: &str, entries: &mut Vec<Entry>) -> io::Result<()> {
+        if !name.is_empty() {
+            entries.push(Entry {
+                path: path.to_owned(),
+                name: name.to_owned(),
+                is_dir: true
+            });
+        }
+        let mut items = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+        items.sort_by_key(|item| item.file_name());
+        for item in items {
+            let item_name = join_name(name, &item.file_name().to_string_lossy());
+            if item.file_type()?.is_dir() {
+                walk(&item.path(), &item_name, entries)?;
+            } else {
+                entries.push(Entry {
+                    path: item.path(),
+                    name: item_name,
+                    is_dir: false
+                });
+            }
+        }
+        Ok(())
+    }
+    let mut entries = Vec::new();
+    for source in sources {
+        match source {
+            Source::File {
+                path,
+                name
+            } => {
+                entries.push(Entry {
+                    path,
+                    name,
+                    is_dir: false
+                })
+            },
+            Source::Dir {
+                path,
+                name
+            } => walk(&path, &name, &mut entries)?
+        }
+    }
+    Ok(entries)
+}
+
+/// 文件的修改时间
+fn modified_time(path: &Path) -> Option<zip::DateTime> {
+    let mtime = fs::metadata(path).and_then(|meta| meta.modified()).ok()?;
+    zip::DateTime::try_from(chrono::DateTime::<chrono::Local>::from(mtime).naive_local()).ok()
+}
+
+/// 创建压缩包，失败时删除目标文件
+fn create_archive(
+    dest: &str,
+    sources: Vec<Source>,
+    password: Option<&str>,
+    level: i64,
+    progress: &mut Progress
+) -> io::Result<()> {
+    let entries = expand(sources)?;
+    let mut writer = ZipWriter::new(BufWriter::new(File::create(dest)?));
+    let rv = (|| {
+        let count = entries.len();
+        let mut buf = vec![0; CHUNK_SIZE];
+        for (idx, entry) in entries.iter().enumerate() {
+            let mut options = SimpleFileOptions::default().compression_method(if level == 0 {
+                CompressionMethod::Stored
+            } else {
+                CompressionMethod::Deflated
+            });
+            if level > 0 {
+                options = options.compression_level(Some(level));
+            }
+            if let Some(mtime) = modified_time(&entry.path) {
+                options = options.last_modified_time(mtime);
+            }
+            if entry.is_dir {
+                writer.add_directory(entry.name.as_str(), options)?;
+                cancel_if(!progress(&entry.name, idx + 1, count, 0, 0))?;
+                continue;
+            }
+            let mut file = File::open(&entry.path)?;
+            let total = file.metadata()?.len();
+            let options = options.large_file(total >= ZIP64_BYTES_THR);
+            match password {
+                Some(password) => {
+                    writer.start_file(
+                        entry.name.as_str(),
+                        options.with_aes_encryption(AesMode::Aes256, password)
+                    )?
+                },
+                None => writer.start_file(entry.name.as_str(), options)?
+            }
+            let mut processed = 0;
+            loop {
+                let len = file.read(&mut buf)?;
+                if len == 0 {
+                    break;
+                }
+                writer.write_all(&buf[..len])?;
+                processed += len as u64;
+                cancel_if(!progress(&entry.name, idx + 1, count, total, processed))?;
+            }
+            if total == 0 {
+                cancel_if(!progress(&entry.name, idx + 1, count, 0, 0))?;
+            }
+        }
+        writer.finish()?.flush()
+    })();
+    if rv.is_err() {
+        let _ = fs::remove_file(dest);
+    }
+    rv
+}
+
+/// 解压
+fn extract_archive(
+    src: &str,
+    dest_dir: &str,
+    password: Option<&str>,
+    progress: &mut Progress
+) -> io::Result<()> {
+    let mut archive = ZipArchive::new(BufReader::new(File::open(src)?))?;
+    let dest_dir = Path::new(dest_dir);
+    fs::create_dir_all(dest_dir)?;
+    let count = archive.len();
+    let mut buf = vec![0; CHUNK_SIZE];
+    for idx in 0..count {
+        let mut file = match password {
+            Some(password) => archive.by_index_decrypt(idx, password.as_bytes())?,
+            None => archive.by_index(idx)?
+        };
+        let name = file.name().to_owned();
+        //跳过不安全的路径
+        let path = match file.enclosed_name() {
+            Some(path) => dest_dir.join(path),
+            None => continue
+        };
+        if file.is_dir() {
+            fs::create_dir_all(&path)?;
+            cancel_if(!progress(&name, idx + 1, count, 0, 0))?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let total = file.size();
+        let mut writer = BufWriter::new(File::create(&path)?);
+        let mut processed = 0;
+        loop {
+            let len = file.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            writer.write_all(&buf[..len])?;
+            processed += len as u64;
+            cancel_if(!progress(&name, idx + 1, count, total, processed))?;
+        }
+        writer.flush()?;
+        if total == 0 {
+            cancel_if(!progress(&name, idx + 1, count, 0, 0))?;
+        }
+    }
+    Ok(())
+}
+
+fn cancel_if(cancelled: bool) -> io::Result<()> {
+    if cancelled {
+        Err(io::Error::new(io::ErrorKind::Interrupted, tr("cancelled", "已取消")))
+    } else {
+        Ok(())
+    }
+}
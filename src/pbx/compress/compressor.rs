@@ -0,0 +1,448 @@
+use crate::{base::locale::tr, prelude::*};
+use flate2::{
+    bufread::{DeflateDecoder, MultiGzDecoder, ZlibDecoder}, write::{DeflateEncoder, GzEncoder, ZlibEncoder}, Compression
+};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::{
+    cell::Cell, fs::{self, File}, io::{self, BufRead, BufReader, BufWriter, Read, Write}, rc::Rc
+};
+
+/// 文件读取块大小
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// 压缩格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Gzip,
+    /// 原始`DEFLATE`流(`RFC 1951`)
+    Deflate,
+    /// 带`zlib`头的`DEFLATE`流(`RFC 1950`，对应`HTTP`的`Content-Encoding: deflate`)
+    Zlib,
+    Zstd
+}
+
+impl Format {
+    /// 通过名称解析，不区分大小写
+    fn new(name: &str) -> Option<Format> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "gzip" | "gz" => Format::Gzip,
+            "deflate" => Format::Deflate,
+            "zlib" => Format::Zlib,
+            "zstd" | "zst" => Format::Zstd,
+            _ => return None
+        })
+    }
+
+    /// 通过数据头识别格式，无法识别时视为原始`DEFLATE`流
+    fn detect(head: &[u8]) -> Format {
+        match head {
+            [0x1f, 0x8b, ..] => Format::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Format::Zstd,
+            [cmf, flg, ..] if cmf & 0x0f == 8 && (((*cmf as u16) << 8) | *flg as u16) % 31 == 0 => {
+                Format::Zlib
+            },
+            _ => Format::Deflate
+        }
+    }
+}
+
+/// 压缩器
+enum Encoder<W: Write> {
+    Gzip(GzEncoder<W>),
+    Deflate(DeflateEncoder<W>),
+    Zlib(ZlibEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>)
+}
+
+impl<W: Write> Encoder<W> {
+    /// # Parameters
+    ///
+    /// - `level` 压缩级别，`gzip`/`deflate`/`zlib`为`0-9`(默认6)，`zstd`为`1-22`(默认3)
+    fn new(format: Format, level: Option<pblong>, writer: W) -> io::Result<Self> {
+        let flate_level = || Compression::new(level.map_or(6, |level| level.clamp(0, 9) as u32));
+        Ok(match format {
+            Format::Gzip => Encoder::Gzip(GzEncoder::new(writer, flate_level())),
+            Format::Deflate => Encoder::Deflate(DeflateEncoder::new(writer, flate_level())),
+            Format::Zlib => Encoder::Zlib(ZlibEncoder::new(writer, flate_level())),
+            Format::Zstd => {
+                Encoder::Zstd(zstd::Encoder::new(writer, level.map_or(3, |level| level.clamp(1, 22)))?)
+            },
+        })
+    }
+
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Deflate(encoder) => encoder.finish(),
+            Encoder::Zlib(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish()
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Deflate(encoder) => encoder.write(buf),
+            Encoder::Zlib(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Deflate(encoder) => encoder.flush(),
+            Encoder::Zlib(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush()
+        }
+    }
+}
+
+/// 创建解压器
+///
+/// # Parameters
+///
+/// - `format` 为`None`时自动识别
+fn decoder<'a, R: BufRead + 'a>(format: Option<Format>, mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    let format = match format {
+        Some(format) => format,
+        None => Format::detect(reader.fill_buf()?)
+    };
+    Ok(match format {
+        Format::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Format::Deflate => Box::new(DeflateDecoder::new(reader)),
+        Format::Zlib => Box::new(ZlibDecoder::new(reader)),
+        Format::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?)
+    })
+}
+
+/// 解析格式参数，`auto`或空字符串表示自动识别
+fn parse_format(name: &str) -> Result<Option<Format>, RetCode> {
+    if name.is_empty() || name.eq_ignore_ascii_case("auto") {
+        return Ok(None);
+    }
+    Format::new(name).map(Some).ok_or(RetCode::E_INVALID_ARGUMENT)
+}
+
+struct Compressor {
+    state: HandlerState,
+    /// 返回给`PB`的二进制数据
+    buf: Vec<u8>,
+    job: JobSlot,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_compress")]
+impl Compressor {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Compressor {
+            state: HandlerState::new(session),
+            buf: Vec::new(),
+            job: JobSlot::default(),
+            error: LastError::default()
+        }
+    }
+
+    /// 压缩
+    ///
+    /// # Parameters
+    ///
+    /// - `format` 格式：`gzip`、`deflate`(原始流)、`zlib`、`zstd`
+    /// - `level` 压缩级别，`gzip`/`deflate`/`zlib`为`0-9`(默认6)，`zstd`为`1-22`(默认3)
+    ///
+    /// # Returns
+    ///
+    /// 格式无效或压缩失败时返回空`Blob`
+    #[method(name = "Compress", overload = 1)]
+    fn compress(&mut self, data: &[u8], format: String, level: Option<pblong>) -> &[u8] {
+        self.buf = match Format::new(&format) {
+            Some(format) => {
+                let rv = compress(data, format, level);
+                self.take_result(rv)
+            },
+            None => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid format: {format}"));
+                Vec::new()
+            }
+        };
+        &self.buf
+    }
+
+    /// 解压
+    ///
+    /// # Parameters
+    ///
+    /// - `format` 格式：`gzip`、`deflate`(原始流)、`zlib`、`zstd`，默认`auto`自动识别
+    ///
+    /// # Returns
+    ///
+    /// 格式无效或数据损坏时返回空`Blob`
+    #[method(name = "Decompress", overload = 1)]
+    fn decompress(&mut self, data: &[u8], format: Option<String>) -> &[u8] {
+        let format = format.unwrap_or_default();
+        self.buf = match parse_format(&format) {
+            Ok(format) => {
+                let rv = decompress(data, format);
+                self.take_result(rv)
+            },
+            Err(rv) => {
+                self.error.set(rv, format!("invalid format: {format}"));
+                Vec::new()
+            }
+        };
+        &self.buf
+    }
+
+    /// 压缩文件
+    ///
+    /// # Description
+    ///
+    /// 同步执行，大文件使用`CompressFileAsync`
+    #[method(name = "CompressFile", overload = 1)]
+    fn compress_file(&mut self, src: String, dest: String, format: String, level: Option<pblong>) -> RetCode {
+        let format = match Format::new(&format) {
+            Some(format) => format,
+            None => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid format: {format}"))
+        };
+        let rv = compress_file(&src, &dest, format, level, &mut |_, _| true);
+        self.error.set_io_result(rv)
+    }
+
+    /// 解压文件
+    #[method(name = "DecompressFile", overload = 1)]
+    fn decompress_file(&mut self, src: String, dest: String, format: Option<String>) -> RetCode {
+        let format = format.unwrap_or_default();
+        let format = match parse_format(&format) {
+            Ok(format) => format,
+            Err(rv) => return self.error.set(rv, format!("invalid format: {format}"))
+        };
+        let rv = decompress_file(&src, &dest, format, &mut |_, _| true);
+        self.error.set_io_result(rv)
+    }
+
+    /// 异步压缩文件
+    ///
+    /// # Description
+    ///
+    /// 在后台线程处理，通过`OnProgress`通知进度，完成后触发`OnComplete`
+    #[method(name = "CompressFileAsync", overload = 1)]
+    fn compress_file_async(
+        &mut self,
+        src: String,
+        dest: String,
+        format: String,
+        level: Option<pblong>
+    ) -> RetCode {
+        let format = match Format::new(&format) {
+            Some(format) => format,
+            None => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid format: {format}"))
+        };
+        self.spawn_file_task(move |progress| compress_file(&src, &dest, format, level, progress))
+    }
+
+    /// 异步解压文件
+    #[method(name = "DecompressFileAsync", overload = 1)]
+    fn decompress_file_async(&mut self, src: String, dest: String, format: Option<String>) -> RetCode {
+        let format = format.unwrap_or_default();
+        let format = match parse_format(&format) {
+            Ok(format) => format,
+            Err(rv) => return self.error.set(rv, format!("invalid format: {format}"))
+        };
+        self.spawn_file_task(move |progress| decompress_file(&src, &dest, format, progress))
+    }
+
+    /// 取消异步任务
+    #[method(name = "Cancel")]
+    fn cancel(&mut self) -> RetCode {
+        if self.job.cancel() {
+            RetCode::OK
+        } else {
+            RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.job.is_running() }
+
+    /// 异步任务进度
+    ///
+    /// `total`为源文件大小，`processed`为已读取的源文件大小，返回`PREVENT`取消任务
+    #[event(name = "OnProgress")]
+    fn on_progress(&mut self, total: pbulong, processed: pbulong) -> RetCode {}
+
+    /// 异步任务完成
+    ///
+    /// 成功时`error`为空字符串，失败时删除目标文件
+    #[event(name = "OnComplete")]
+    fn on_complete(&mut self, error: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Compressor {
+    /// 记录压缩与解压的结果，失败时返回空数据
+    fn take_result(&mut self, rv: io::Result<Vec<u8>>) -> Vec<u8> {
+        match rv {
+            Ok(data) => {
+                self.error.clear();
+                data
+            },
+            Err(e) => {
+                self.error.set_io(e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn spawn_file_task<F>(&mut self, task: F) -> RetCode
+    where
+        F: FnOnce(&mut dyn FnMut(u64, u64) -> bool) -> io::Result<()> + Send + 'static
+    {
+        spawn_file_job(
+            self,
+            |this| &mut this.job,
+            move |ctx| {
+                task(&mut |total, processed| {
+                    ctx.progress(total, processed, |this, (total, processed)| {
+                        this.on_progress(total as pbulong, processed as pbulong)
+                    })
+                })
+            },
+            |this, rv| {
+                match rv {
+                    Ok(_) => this.on_complete("".to_owned()),
+                    Err(e) => {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_complete(error);
+                    }
+                }
+            }
+        )
+    }
+}
+
+impl Handler for Compressor {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+fn compress(data: &[u8], format: Format, level: Option<pblong>) -> io::Result<Vec<u8>> {
+    let mut encoder = Encoder::new(format, level, Vec::new())?;
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn decompress(data: &[u8], format: Option<Format>) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    decoder(format, data)?.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// 统计已读取字节数
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count.set(self.count.get() + len as u64);
+        Ok(len)
+    }
+}
+
+/// 处理文件，失败时删除目标文件
+fn process_file(dest: &str, f: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(dest)?);
+    let rv = f(&mut writer).and_then(|_| writer.flush());
+    if rv.is_err() {
+        drop(writer);
+        let _ = fs::remove_file(dest);
+    }
+    rv
+}
+
+fn check_progress(progress: &mut dyn FnMut(u64, u64) -> bool, total: u64, processed: u64) -> io::Result<()> {
+    if progress(total, processed) {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::Interrupted, tr("cancelled", "已取消")))
+    }
+}
+
+/// 压缩文件
+///
+/// # Parameters
+///
+/// - `progress` 进度回调，参数为源文件大小和已读取的大小，返回`false`中止
+fn compress_file(
+    src: &str,
+    dest: &str,
+    format: Format,
+    level: Option<pblong>,
+    progress: &mut dyn FnMut(u64, u64) -> bool
+) -> io::Result<()> {
+    let mut reader = File::open(src)?;
+    let total = reader.metadata()?.len();
+    process_file(dest, |writer| {
+        let mut encoder = Encoder::new(format, level, writer)?;
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut processed = 0;
+        loop {
+            let len = reader.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            encoder.write_all(&buf[..len])?;
+            processed += len as u64;
+            check_progress(progress, total, processed)?;
+        }
+        encoder.finish()?;
+        Ok(())
+    })
+}
+
+/// 解压文件
+///
+/// # Parameters
+///
+/// - `progress` 进度回调，参数为源文件大小和已读取的大小，返回`false`中止
+fn decompress_file(
+    src: &str,
+    dest: &str,
+    format: Option<Format>,
+    progress: &mut dyn FnMut(u64, u64) -> bool
+) -> io::Result<()> {
+    let file = File::open(src)?;
+    let total = file.metadata()?.len();
+    let count = Rc::new(Cell::new(0));
+    let reader = BufReader::new(CountingReader {
+        inner: file,
+        count: count.clone()
+    });
+    process_file(dest, |writer| {
+        let mut decoder = decoder(format, reader)?;
+        let mut buf = vec![0; CHUNK_SIZE];
+        loop {
+            let len = decoder.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            writer.write_all(&buf[..len])?;
+            check_progress(progress, total, count.get())?;
+        }
+        check_progress(progress, total, total)
+    })
+}
@@ -0,0 +1,2 @@
+mod compressor;
+mod archive;
@@ -0,0 +1,642 @@
+use crate::{
+    base::{conv, locale::tr}, prelude::*
+};
+use aes_gcm::{
+    aead::{consts::U12, generic_array::GenericArray, rand_core::RngCore, Aead, OsRng, Payload}, aes::{
+        cipher::{
+            block_padding::Pkcs7, consts::U16, BlockCipher, BlockDecrypt, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, BlockSizeUser, KeyInit, KeyIvInit
+        }, Aes128, Aes256
+    }, AesGcm, Nonce
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::{
+    fs::{self, File}, io::{self, BufWriter, Read, Write}
+};
+
+/// 文件加解密的块大小(`AES`块大小的整数倍)
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// `GCM`随机数长度
+const NONCE_SIZE: usize = 12;
+/// `GCM`认证标签长度
+const TAG_SIZE: usize = 16;
+/// `CBC`初始向量长度
+const IV_SIZE: usize = 16;
+/// `PBKDF2`默认迭代次数
+const DEFAULT_PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// 加密模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Gcm,
+    Cbc
+}
+
+/// `AES-128`/`AES-256`
+trait AesCipher: BlockCipher + BlockSizeUser<BlockSize = U16> + BlockEncrypt + BlockDecrypt + KeyInit {}
+
+impl<T> AesCipher for T where
+    T: BlockCipher + BlockSizeUser<BlockSize = U16> + BlockEncrypt + BlockDecrypt + KeyInit
+{
+}
+
+/// 按密钥长度选择算法
+macro_rules! dispatch {
+    ($key:expr, $func:ident($($arg:expr),*)) => {
+        match $key.len() {
+            16 => $func::<Aes128>($key, $($arg),*),
+            _ => $func::<Aes256>($key, $($arg),*)
+        }
+    };
+}
+
+struct Cipher {
+    state: HandlerState,
+    /// 密钥，为空时未设置
+    key: Vec<u8>,
+    mode: Mode,
+    /// 返回给`PB`的二进制数据
+    buf: Vec<u8>,
+    job: JobSlot,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_cipher")]
+impl Cipher {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Cipher {
+            state: HandlerState::new(session),
+            key: Vec::new(),
+            mode: Mode::Gcm,
+            buf: Vec::new(),
+            job: JobSlot::default(),
+            error: LastError::default()
+        }
+    }
+
+    /// 设置密钥
+    ///
+    /// # Parameters
+    ///
+    /// - `key` 16字节(`AES-128`)或32字节(`AES-256`)
+    #[method(name = "SetKey")]
+    fn set_key(&mut self, key: &[u8]) -> RetCode {
+        if key.len() != 16 && key.len() != 32 {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "key must be 16 or 32 bytes");
+        }
+        self.key = key.to_vec();
+        RetCode::OK
+    }
+
+    /// 通过`PBKDF2-HMAC-SHA256`派生密钥
+    ///
+    /// # Parameters
+    ///
+    /// - `salt` 盐
+    /// - `iterations` 迭代次数，默认`100000`
+    /// - `key_bits` 密钥长度：`128`或`256`(默认)
+    #[method(name = "DeriveKeyPBKDF2", overload = 2)]
+    fn derive_key_pbkdf2(
+        &mut self,
+        password: String,
+        salt: &[u8],
+        iterations: Option<pblong>,
+        key_bits: Option<pblong>
+    ) -> RetCode {
+        let mut key = match new_key(key_bits) {
+            Some(key) => key,
+            None => return self.error.set(RetCode::E_INVALID_ARGUMENT, "key bits must be 128 or 256")
+        };
+        let iterations = match iterations {
+            Some(iterations) if iterations <= 0 => {
+                return self.error.set(RetCode::E_INVALID_ARGUMENT, "iterations must be greater than 0");
+            },
+            Some(iterations) => iterations as u32,
+            None => DEFAULT_PBKDF2_ITERATIONS
+        };
+        pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password.as_bytes(), salt, iterations, &mut key);
+        self.key = key;
+        RetCode::OK
+    }
+
+    /// 通过`Argon2id`派生密钥
+    ///
+    /// # Parameters
+    ///
+    /// - `salt` 盐，至少8字节
+    /// - `key_bits` 密钥长度：`128`或`256`(默认)
+    ///
+    /// # Description
+    ///
+    /// 使用`Argon2`的默认参数(19MB内存，2次迭代，1个并行度)
+    #[method(name = "DeriveKeyArgon2", overload = 1)]
+    fn derive_key_argon2(&mut self, password: String, salt: &[u8], key_bits: Option<pblong>) -> RetCode {
+        let mut key = match new_key(key_bits) {
+            Some(key) => key,
+            None => return self.error.set(RetCode::E_INVALID_ARGUMENT, "key bits must be 128 or 256")
+        };
+        if let Err(e) = argon2::Argon2::default().hash_password_into(password.as_bytes(), salt, &mut key) {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+        }
+        self.key = key;
+        RetCode::OK
+    }
+
+    /// 生成随机字节(用于盐或密钥)
+    ///
+    /// # Parameters
+    ///
+    /// - `len` 长度，默认16
+    #[method(name = "GenerateRandom", overload = 1)]
+    fn generate_random(&mut self, len: Option<pblong>) -> &[u8] {
+        self.buf = vec![0; len.unwrap_or(16).max(0) as usize];
+        OsRng.fill_bytes(&mut self.buf);
+        &self.buf
+    }
+
+    /// 设置加密模式
+    ///
+    /// # Parameters
+    ///
+    /// - `mode` `GCM`(默认)或`CBC`(`PKCS7`填充)
+    #[method(name = "SetMode")]
+    fn set_mode(&mut self, mode: String) -> RetCode {
+        self.mode = match mode.to_ascii_uppercase().as_str() {
+            "GCM" => Mode::Gcm,
+            "CBC" => Mode::Cbc,
+            _ => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid mode: {mode}"))
+        };
+        RetCode::OK
+    }
+
+    #[method(name = "GetMode")]
+    fn get_mode(&self) -> String {
+        match self.mode {
+            Mode::Gcm => "GCM".to_owned(),
+            Mode::Cbc => "CBC".to_owned()
+        }
+    }
+
+    /// 加密
+    ///
+    /// # Returns
+    ///
+    /// `GCM`模式为`随机数(12字节)+密文+标签(16字节)`，`CBC`模式为`IV(16字节)+密文`，
+    /// 未设置密钥时返回空`Blob`
+    #[method(name = "Encrypt")]
+    fn encrypt(&mut self, data: &[u8]) -> &[u8] {
+        let rv = self.encrypt_data(data);
+        self.buf = self.take_result(rv, RetCode::E_INVALID_OBJECT, "key not set");
+        &self.buf
+    }
+
+    /// 解密
+    ///
+    /// 未设置密钥、数据无效或认证失败时返回空`Blob`
+    #[method(name = "Decrypt")]
+    fn decrypt(&mut self, data: &[u8]) -> &[u8] {
+        let rv = self.decrypt_data(data);
+        self.buf = self.take_result(
+            rv,
+            RetCode::E_INVALID_DATA,
+            "key not set, invalid data or authentication failed"
+        );
+        &self.buf
+    }
+
+    /// 加密字符串
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    ///
+    /// # Returns
+    ///
+    /// `Base64`格式的密文
+    #[method(name = "EncryptString", overload = 1)]
+    fn encrypt_string(&self, data: String, encoding: Option<pblong>) -> String {
+        let data = conv::encode(&data, encoding.unwrap_or(conv::ENCODING_UTF8));
+        self.encrypt_data(&data).map(|data| BASE64.encode(data)).unwrap_or_default()
+    }
+
+    /// 解密`Base64`格式的密文为字符串
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    #[method(name = "DecryptString", overload = 1)]
+    fn decrypt_string(&self, data: String, encoding: Option<pblong>) -> String {
+        let data = match BASE64.decode(data.trim()) {
+            Ok(data) => data,
+            Err(_) => return "".to_owned()
+        };
+        match self.decrypt_data(&data) {
+            Some(data) => conv::decode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)).into_owned(),
+            None => "".to_owned()
+        }
+    }
+
+    /// 加密文件
+    ///
+    /// # Description
+    ///
+    /// 同步执行，大文件使用`EncryptFileAsync`；`GCM`模式按1MB分块加密，格式与`Encrypt`不同
+    #[method(name = "EncryptFile")]
+    fn encrypt_file(&mut self, src: String, dest: String) -> RetCode {
+        if self.key.is_empty() {
+            return self.error.set(RetCode::E_INVALID_OBJECT, "key not set");
+        }
+        let rv = process_file(&self.key, self.mode, true, &src, &dest, |_, _| true);
+        self.error.set_io_result(rv)
+    }
+
+    /// 解密文件
+    #[method(name = "DecryptFile")]
+    fn decrypt_file(&mut self, src: String, dest: String) -> RetCode {
+        if self.key.is_empty() {
+            return self.error.set(RetCode::E_INVALID_OBJECT, "key not set");
+        }
+        let rv = process_file(&self.key, self.mode, false, &src, &dest, |_, _| true);
+        self.error.set_io_result(rv)
+    }
+
+    /// 异步加密文件
+    ///
+    /// # Description
+    ///
+    /// 在后台线程处理，通过`OnProgress`通知进度，完成后触发`OnComplete`
+    #[method(name = "EncryptFileAsync")]
+    fn encrypt_file_async(&mut self, src: String, dest: String) -> RetCode {
+        self.process_file_async(true, src, dest)
+    }
+
+    /// 异步解密文件
+    #[method(name = "DecryptFileAsync")]
+    fn decrypt_file_async(&mut self, src: String, dest: String) -> RetCode {
+        self.process_file_async(false, src, dest)
+    }
+
+    /// 取消异步任务
+    #[method(name = "Cancel")]
+    fn cancel(&mut self) -> RetCode {
+        if self.job.cancel() {
+            RetCode::OK
+        } else {
+            RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.job.is_running() }
+
+    /// 异步任务进度
+    ///
+    /// 返回`PREVENT`取消任务
+    #[event(name = "OnProgress")]
+    fn on_progress(&mut self, total: pbulong, processed: pbulong) -> RetCode {}
+
+    /// 异步任务完成
+    ///
+    /// 成功时`error`为空字符串，失败时删除目标文件
+    #[event(name = "OnComplete")]
+    fn on_complete(&mut self, error: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Cipher {
+    /// 记录加解密的结果，失败时返回空数据
+    fn take_result(&mut self, rv: Option<Vec<u8>>, code: RetCode, err: &str) -> Vec<u8> {
+        match rv {
+            Some(data) => {
+                self.error.clear();
+                data
+            },
+            None => {
+                self.error.set(code, err);
+                Vec::new()
+            }
+        }
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if self.key.is_empty() {
+            return None;
+        }
+        match self.mode {
+            Mode::Gcm => dispatch!(&self.key, gcm_encrypt(data)),
+            Mode::Cbc => dispatch!(&self.key, cbc_encrypt(data))
+        }
+    }
+
+    fn decrypt_data(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if self.key.is_empty() {
+            return None;
+        }
+        match self.mode {
+            Mode::Gcm => dispatch!(&self.key, gcm_decrypt(data)),
+            Mode::Cbc => dispatch!(&self.key, cbc_decrypt(data))
+        }
+    }
+
+    fn process_file_async(&mut self, encrypt: bool, src: String, dest: String) -> RetCode {
+        if self.key.is_empty() {
+            return self.error.set(RetCode::E_INVALID_OBJECT, "key not set");
+        }
+        let (key, mode) = (self.key.clone(), self.mode);
+        spawn_file_job(
+            self,
+            |this| &mut this.job,
+            move |ctx| {
+                process_file(&key, mode, encrypt, &src, &dest, |total, processed| {
+                    ctx.progress(total, processed, |this, (total, processed)| {
+                        this.on_progress(total as pbulong, processed as pbulong)
+                    })
+                })
+            },
+            |this, rv| {
+                match rv {
+                    Ok(_) => this.on_complete("".to_owned()),
+                    Err(e) => {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_complete(error);
+                    }
+                }
+            }
+        )
+    }
+}
+
+impl Handler for Cipher {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+/// 创建指定长度的密钥缓冲区
+fn new_key(key_bits: Option<pblong>) -> Option<Vec<u8>> {
+    match key_bits.unwrap_or(256) {
+        128 => Some(vec![0; 16]),
+        256 => Some(vec![0; 32]),
+        _ => None
+    }
+}
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut buf = [0; N];
+    OsRng.fill_bytes(&mut buf);
+    buf
+}
+
+fn invalid_data(msg: &str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg) }
+
+fn gcm_encrypt<C: AesCipher>(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    let cipher = AesGcm::<C, U12>::new_from_slice(key).ok()?;
+    let nonce = random::<NONCE_SIZE>();
+    let mut out = nonce.to_vec();
+    out.extend(cipher.encrypt(Nonce::from_slice(&nonce), data).ok()?);
+    Some(out)
+}
+
+fn gcm_decrypt<C: AesCipher>(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < NONCE_SIZE + TAG_SIZE {
+        return None;
+    }
+    let cipher = AesGcm::<C, U12>::new_from_slice(key).ok()?;
+    let (nonce, data) = data.split_at(NONCE_SIZE);
+    cipher.decrypt(Nonce::from_slice(nonce), data).ok()
+}
+
+fn cbc_encrypt<C: AesCipher>(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    let iv = random::<IV_SIZE>();
+    let encryptor = cbc::Encryptor::<C>::new_from_slices(key, &iv).ok()?;
+    let mut out = iv.to_vec();
+    out.extend(encryptor.encrypt_padded_vec_mut::<Pkcs7>(data));
+    Some(out)
+}
+
+fn cbc_decrypt<C: AesCipher>(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < IV_SIZE * 2 {
+        return None;
+    }
+    let (iv, data) = data.split_at(IV_SIZE);
+    let decryptor = cbc::Decryptor::<C>::new_from_slices(key, iv).ok()?;
+    decryptor.decrypt_padded_vec_mut::<Pkcs7>(data).ok()
+}
+
+/// 加解密文件，失败时删除目标文件
+///
+/// # Parameters
+///
+/// - `progress` 进度回调，参数为源文件大小和已处理的大小，返回`false`中止
+fn process_file(
+    key: &[u8],
+    mode: Mode,
+    encrypt: bool,
+    src: &str,
+    dest: &str,
+    progress: impl FnMut(u64, u64) -> bool
+) -> io::Result<()> {
+    let mut reader = File::open(src)?;
+    let total = reader.metadata()?.len();
+    let mut writer = BufWriter::new(File::create(dest)?);
+    let rv = match (mode, encrypt) {
+        (Mode::Gcm, true) => dispatch!(key, gcm_encrypt_stream(&mut reader, &mut writer, total, progress)),
+        (Mode::Gcm, false) => dispatch!(key, gcm_decrypt_stream(&mut reader, &mut writer, total, progress)),
+        (Mode::Cbc, true) => dispatch!(key, cbc_encrypt_stream(&mut reader, &mut writer, total, progress)),
+        (Mode::Cbc, false) => dispatch!(key, cbc_decrypt_stream(&mut reader, &mut writer, total, progress))
+    }
+    .and_then(|_| writer.flush());
+    if rv.is_err() {
+        drop(writer);
+        let _ = fs::remove_file(dest);
+    }
+    rv
+}
+
+/// 读满缓冲区，只有到达文件末尾时才返回较小的长度
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(len)
+}
+
+fn check_progress(progress: &mut impl FnMut(u64, u64) -> bool, total: u64, processed: u64) -> io::Result<()> {
+    if progress(total, processed) {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::Interrupted, tr("cancelled", "已取消")))
+    }
+}
+
+/// 分块的随机数，基础随机数的末尾4字节与块序号异或
+fn chunk_nonce(nonce: &[u8; NONCE_SIZE], counter: u32) -> [u8; NONCE_SIZE] {
+    let mut chunk_nonce = *nonce;
+    for (b, c) in chunk_nonce[NONCE_SIZE - 4..].iter_mut().zip(counter.to_be_bytes()) {
+        *b ^= c;
+    }
+    chunk_nonce
+}
+
+/// `GCM`分块加密
+///
+/// # Description
+///
+/// 格式为`随机数(12字节)+N个分块`，每块为`密文+标签(16字节)`，明文块大小为`CHUNK_SIZE`，
+/// 最后一块小于`CHUNK_SIZE`(可以为空)，附加数据标记是否为最后一块以检测截断
+fn gcm_encrypt_stream<C: AesCipher>(
+    key: &[u8],
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    total: u64,
+    mut progress: impl FnMut(u64, u64) -> bool
+) -> io::Result<()> {
+    let cipher = AesGcm::<C, U12>::new_from_slice(key).map_err(|_| invalid_data("invalid key"))?;
+    let nonce = random::<NONCE_SIZE>();
+    writer.write_all(&nonce)?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut processed = 0;
+    let mut counter: u32 = 0;
+    loop {
+        let len = read_full(reader, &mut buf)?;
+        let last = len < CHUNK_SIZE;
+        let payload = Payload {
+            msg: &buf[..len],
+            aad: &[last as u8]
+        };
+        let data = cipher
+            .encrypt(Nonce::from_slice(&chunk_nonce(&nonce, counter)), payload)
+            .map_err(|_| invalid_data("encryption failed"))?;
+        writer.write_all(&data)?;
+        processed += len as u64;
+        check_progress(&mut progress, total, processed)?;
+        if last {
+            break Ok(());
+        }
+        counter = counter.checked_add(1).ok_or_else(|| invalid_data("file too large"))?;
+    }
+}
+
+/// `GCM`分块解密
+fn gcm_decrypt_stream<C: AesCipher>(
+    key: &[u8],
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    total: u64,
+    mut progress: impl FnMut(u64, u64) -> bool
+) -> io::Result<()> {
+    let cipher = AesGcm::<C, U12>::new_from_slice(key).map_err(|_| invalid_data("invalid key"))?;
+    let mut nonce = [0; NONCE_SIZE];
+    if read_full(reader, &mut nonce)? < NONCE_SIZE {
+        return Err(invalid_data("invalid data"));
+    }
+    let mut buf = vec![0; CHUNK_SIZE + TAG_SIZE];
+    let mut processed = NONCE_SIZE as u64;
+    let mut counter: u32 = 0;
+    loop {
+        let len = read_full(reader, &mut buf)?;
+        let last = len < buf.len();
+        let payload = Payload {
+            msg: &buf[..len],
+            aad: &[last as u8]
+        };
+        let data = cipher
+            .decrypt(Nonce::from_slice(&chunk_nonce(&nonce, counter)), payload)
+            .map_err(|_| invalid_data("authentication failed"))?;
+        writer.write_all(&data)?;
+        processed += len as u64;
+        check_progress(&mut progress, total, processed)?;
+        if last {
+            break Ok(());
+        }
+        counter = counter.checked_add(1).ok_or_else(|| invalid_data("invalid data"))?;
+    }
+}
+
+/// `CBC`流式加密，格式与`Encrypt`相同
+fn cbc_encrypt_stream<C: AesCipher>(
+    key: &[u8],
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    total: u64,
+    mut progress: impl FnMut(u64, u64) -> bool
+) -> io::Result<()> {
+    let iv = random::<IV_SIZE>();
+    let mut encryptor =
+        cbc::Encryptor::<C>::new_from_slices(key, &iv).map_err(|_| invalid_data("invalid key"))?;
+    writer.write_all(&iv)?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut processed = 0;
+    let tail = loop {
+        let len = read_full(reader, &mut buf)?;
+        if len < CHUNK_SIZE {
+            break len;
+        }
+        for block in buf.chunks_exact_mut(IV_SIZE) {
+            encryptor.encrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+        writer.write_all(&buf)?;
+        processed += len as u64;
+        check_progress(&mut progress, total, processed)?;
+    };
+    writer.write_all(&encryptor.encrypt_padded_vec_mut::<Pkcs7>(&buf[..tail]))?;
+    check_progress(&mut progress, total, processed + tail as u64)
+}
+
+/// `CBC`流式解密
+fn cbc_decrypt_stream<C: AesCipher>(
+    key: &[u8],
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    total: u64,
+    mut progress: impl FnMut(u64, u64) -> bool
+) -> io::Result<()> {
+    let mut iv = [0; IV_SIZE];
+    if read_full(reader, &mut iv)? < IV_SIZE {
+        return Err(invalid_data("invalid data"));
+    }
+    let mut decryptor =
+        cbc::Decryptor::<C>::new_from_slices(key, &iv).map_err(|_| invalid_data("invalid key"))?;
+    let mut processed = IV_SIZE as u64;
+    //预读下一块以确定最后一块(包含填充)
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut next = vec![0; CHUNK_SIZE];
+    let mut len = read_full(reader, &mut buf)?;
+    loop {
+        let next_len = read_full(reader, &mut next)?;
+        if next_len == 0 {
+            break;
+        }
+        for block in buf.chunks_exact_mut(IV_SIZE) {
+            decryptor.decrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+        writer.write_all(&buf)?;
+        processed += len as u64;
+        check_progress(&mut progress, total, processed)?;
+        std::mem::swap(&mut buf, &mut next);
+        len = next_len;
+    }
+    if len == 0 || len % IV_SIZE != 0 {
+        return Err(invalid_data("invalid data"));
+    }
+    let data = decryptor
+        .decrypt_padded_vec_mut::<Pkcs7>(&buf[..len])
+        .map_err(|_| invalid_data("invalid padding"))?;
+    writer.write_all(&data)?;
+    check_progress(&mut progress, total, processed + len as u64)
+}
@@ -0,0 +1,432 @@
+use crate::{base::conv, prelude::*};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use p256::{ecdsa, SecretKey};
+use pbni::pbx::*;
+use rsa::{
+    pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey}, pkcs1v15, pkcs8::{DecodePrivateKey, DecodePublicKey}, pss, rand_core::OsRng, signature::{RandomizedSigner, SignatureEncoding, Signer, Verifier}, RsaPrivateKey, RsaPublicKey
+};
+use sha1::Sha1;
+use sha2::{
+    digest::{const_oid::AssociatedOid, Digest, FixedOutputReset}, Sha256, Sha384, Sha512
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Default)]
+struct Crypto {
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_crypto")]
+impl Crypto {
+    /// 计算`HMAC`
+    ///
+    /// # Parameters
+    ///
+    /// - `algorithm` 摘要算法：`MD5`、`SHA1`、`SHA256`、`SHA384`、`SHA512`
+    /// - `key` 密钥(`UTF-8`)
+    /// - `data` 数据(`UTF-8`)
+    /// - `format` 输出格式：`hex`(默认，小写)或`base64`
+    ///
+    /// # Returns
+    ///
+    /// 算法无效时返回空字符串，通过`GetLastErrorText`获取错误信息
+    #[method(name = "Hmac", overload = 1)]
+    fn hmac(&mut self, algorithm: String, key: String, data: String, format: Option<String>) -> String {
+        match hmac(&algorithm, key.as_bytes(), data.as_bytes()) {
+            Some(mac) => {
+                self.error.clear();
+                encode_output(&mac, format.as_deref())
+            },
+            None => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid algorithm: {algorithm}"));
+                "".to_owned()
+            }
+        }
+    }
+
+    /// 计算`HMAC-SHA256`
+    ///
+    /// # Parameters
+    ///
+    /// - `format` 输出格式：`hex`(默认，小写)或`base64`
+    #[method(name = "HmacSHA256", overload = 1)]
+    fn hmac_sha256(&mut self, key: String, data: String, format: Option<String>) -> String {
+        self.hmac("SHA256".to_owned(), key, data, format)
+    }
+
+    /// `RSASSA-PKCS1-v1_5`签名
+    ///
+    /// # Parameters
+    ///
+    /// - `pem_key` `PEM`格式的私钥(`PKCS#8`或`PKCS#1`)
+    /// - `data` 数据(`UTF-8`)
+    /// - `hash` 摘要算法：`SHA1`、`SHA256`(默认)、`SHA384`、`SHA512`
+    ///
+    /// # Returns
+    ///
+    /// `Base64`格式的签名，失败时返回空字符串
+    #[method(name = "RsaSignPKCS1", overload = 1)]
+    fn rsa_sign_pkcs1(&mut self, pem_key: String, data: String, hash: Option<String>) -> String {
+        let sig = rsa_sign(&pem_key, data.as_bytes(), hash.as_deref(), false);
+        self.encode_signature(sig, "invalid private key or hash algorithm")
+    }
+
+    /// `RSASSA-PSS`签名
+    ///
+    /// # Parameters
+    ///
+    /// - `pem_key` `PEM`格式的私钥(`PKCS#8`或`PKCS#1`)
+    /// - `data` 数据(`UTF-8`)
+    /// - `hash` 摘要算法：`SHA1`、`SHA256`(默认)、`SHA384`、`SHA512`，盐长度与摘要长度相同
+    ///
+    /// # Returns
+    ///
+    /// `Base64`格式的签名，失败时返回空字符串
+    #[method(name = "RsaSignPSS", overload = 1)]
+    fn rsa_sign_pss(&mut self, pem_key: String, data: String, hash: Option<String>) -> String {
+        let sig = rsa_sign(&pem_key, data.as_bytes(), hash.as_deref(), true);
+        self.encode_signature(sig, "invalid private key or hash algorithm")
+    }
+
+    /// 校验`RSASSA-PKCS1-v1_5`签名
+    ///
+    /// # Parameters
+    ///
+    /// - `pem_key` `PEM`格式的公钥(`SPKI`或`PKCS#1`)
+    /// - `data` 数据(`UTF-8`)
+    /// - `signature` `Base64`格式的签名
+    /// - `hash` 摘要算法：`SHA1`、`SHA256`(默认)、`SHA384`、`SHA512`
+    #[method(name = "RsaVerifyPKCS1", overload = 1)]
+    fn rsa_verify_pkcs1(
+        &self,
+        pem_key: String,
+        data: String,
+        signature: String,
+        hash: Option<String>
+    ) -> bool {
+        rsa_verify(&pem_key, data.as_bytes(), &signature, hash.as_deref(), false)
+    }
+
+    /// 校验`RSASSA-PSS`签名
+    ///
+    /// # Parameters
+    ///
+    /// - `pem_key` `PEM`格式的公钥(`SPKI`或`PKCS#1`)
+    /// - `data` 数据(`UTF-8`)
+    /// - `signature` `Base64`格式的签名
+    /// - `hash` 摘要算法：`SHA1`、`SHA256`(默认)、`SHA384`、`SHA512`
+    #[method(name = "RsaVerifyPSS", overload = 1)]
+    fn rsa_verify_pss(&self, pem_key: String, data: String, signature: String, hash: Option<String>) -> bool {
+        rsa_verify(&pem_key, data.as_bytes(), &signature, hash.as_deref(), true)
+    }
+
+    /// `ECDSA P-256 SHA256`签名
+    ///
+    /// # Parameters
+    ///
+    /// - `pem_key` `PEM`格式的私钥(`PKCS#8`或`SEC1`)
+    /// - `data` 数据(`UTF-8`)
+    ///
+    /// # Returns
+    ///
+    /// `Base64`格式的`DER`编码签名，失败时返回空字符串
+    #[method(name = "EcdsaSign")]
+    fn ecdsa_sign(&mut self, pem_key: String, data: String) -> String {
+        let pem_key = pem_key.trim();
+        let sig = ecdsa::SigningKey::from_pkcs8_pem(pem_key)
+            .ok()
+            .or_else(|| SecretKey::from_sec1_pem(pem_key).ok().map(ecdsa::SigningKey::from))
+            .and_then(|key| Signer::<ecdsa::Signature>::try_sign(&key, data.as_bytes()).ok())
+            .map(|sig| sig.to_der().as_bytes().to_vec());
+        self.encode_signature(sig, "invalid private key")
+    }
+
+    /// 校验`ECDSA P-256 SHA256`签名
+    ///
+    /// # Parameters
+    ///
+    /// - `pem_key` `PEM`格式的公钥(`SPKI`)
+    /// - `data` 数据(`UTF-8`)
+    /// - `signature` `Base64`格式的签名，支持`DER`编码或`r||s`格式
+    #[method(name = "EcdsaVerify")]
+    fn ecdsa_verify(&mut self, pem_key: String, data: String, signature: String) -> bool {
+        let key = match ecdsa::VerifyingKey::from_public_key_pem(pem_key.trim()) {
+            Ok(key) => key,
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid public key: {e}"));
+                return false;
+            }
+        };
+        let sig = match BASE64.decode(signature.trim()) {
+            Ok(sig) => sig,
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_DATA, format!("invalid signature: {e}"));
+                return false;
+            }
+        };
+        match ecdsa::Signature::from_der(&sig).or_else(|_| ecdsa::Signature::from_slice(&sig)) {
+            Ok(sig) => key.verify(data.as_bytes(), &sig).is_ok(),
+            Err(_) => false
+        }
+    }
+
+    /// 校验`Webhook`回调的签名
+    ///
+    /// # Parameters
+    ///
+    /// - `scheme` 签名方案：
+    ///   - `github` `X-Hub-Signature-256: sha256=<hex>`，签名内容为`body`
+    ///   - `stripe` `Stripe-Signature: t=<时间戳>,v1=<hex>`，签名内容为`<时间戳>.<body>`
+    ///   - `slack` `X-Slack-Signature: v0=<hex>`与`X-Slack-Request-Timestamp`，签名内容为`v0:<时间戳>:<body>`
+    ///   - `standard` (`Standard Webhooks`) `webhook-id`、`webhook-timestamp`与`webhook-signature: v1,<base64>`，
+    ///     签名内容为`<id>.<时间戳>.<body>`，`whsec_`开头的密钥按`Base64`解码
+    /// - `secret` 签名密钥
+    /// - `headers_json` 请求头(`JSON`对象，名称不区分大小写)
+    /// - `body` 原始请求内容(`UTF-8`)
+    /// - `tolerance` 时间戳允许的偏差(秒)，默认`300`，`0`表示不检查
+    ///
+    /// # Description
+    ///
+    /// 均使用`HMAC-SHA256`，多个签名时任一匹配即通过
+    #[method(name = "VerifyWebhookSignature", overload = 1)]
+    fn verify_webhook_signature(
+        &self,
+        scheme: String,
+        secret: String,
+        headers_json: String,
+        body: String,
+        tolerance: Option<pbulong>
+    ) -> bool {
+        verify_webhook(&scheme, &secret, &headers_json, body.as_bytes(), tolerance.unwrap_or(300) as u64)
+    }
+
+    /// 校验`Webhook`回调的签名
+    ///
+    /// `body`为原始请求内容的二进制数据，其它参数同`VerifyWebhookSignature(string,...)`
+    #[method(name = "VerifyWebhookSignature", overload = 1)]
+    fn verify_webhook_signature_binary(
+        &self,
+        scheme: String,
+        secret: String,
+        headers_json: String,
+        body: &[u8],
+        tolerance: Option<pbulong>
+    ) -> bool {
+        verify_webhook(&scheme, &secret, &headers_json, body, tolerance.unwrap_or(300) as u64)
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+/// 校验`Webhook`签名
+impl Crypto {
+    /// 签名编码为`Base64`，失败时记录错误并返回空字符串
+    fn encode_signature(&mut self, sig: Option<Vec<u8>>, err: &str) -> String {
+        match sig {
+            Some(sig) => {
+                self.error.clear();
+                BASE64.encode(sig)
+            },
+            None => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, err);
+                "".to_owned()
+            }
+        }
+    }
+}
+
+fn verify_webhook(scheme: &str, secret: &str, headers_json: &str, body: &[u8], tolerance: u64) -> bool {
+    let Ok(serde_json::Value::Object(headers)) = serde_json::from_str::<serde_json::Value>(headers_json)
+    else {
+        return false;
+    };
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .and_then(|(_, val)| val.as_str())
+            .map(str::trim)
+    };
+    let within_tolerance = |ts: &str| {
+        let Ok(ts) = ts.parse::<u64>() else {
+            return false;
+        };
+        if tolerance == 0 {
+            return true;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|dur| dur.as_secs()).unwrap_or_default();
+        now.abs_diff(ts) <= tolerance
+    };
+    let verify = |key: &[u8], payload: &[&[u8]], sig: &[u8]| {
+        let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(key) else {
+            return false;
+        };
+        for part in payload {
+            mac.update(part);
+        }
+        //常量时间比较
+        mac.verify_slice(sig).is_ok()
+    };
+    match scheme.to_ascii_lowercase().as_str() {
+        "github" => {
+            let Some(sig) = header("X-Hub-Signature-256").and_then(|sig| sig.strip_prefix("sha256=")) else {
+                return false;
+            };
+            conv::hex_decode(sig).map_or(false, |sig| verify(secret.as_bytes(), &[body], &sig))
+        },
+        "stripe" => {
+            let Some(value) = header("Stripe-Signature") else {
+                return false;
+            };
+            let mut ts = None;
+            let mut sigs = Vec::new();
+            for item in value.split(',') {
+                match item.trim().split_once('=') {
+                    Some(("t", val)) => ts = Some(val),
+                    Some(("v1", val)) => sigs.extend(conv::hex_decode(val)),
+                    _ => {}
+                }
+            }
+            let Some(ts) = ts.filter(|ts| within_tolerance(ts)) else {
+                return false;
+            };
+            sigs.iter().any(|sig| verify(secret.as_bytes(), &[ts.as_bytes(), b".", body], sig))
+        },
+        "slack" => {
+            let (Some(sig), Some(ts)) = (
+                header("X-Slack-Signature").and_then(|sig| sig.strip_prefix("v0=")),
+                header("X-Slack-Request-Timestamp")
+            ) else {
+                return false;
+            };
+            within_tolerance(ts) &&
+                conv::hex_decode(sig).map_or(false, |sig| {
+                    verify(secret.as_bytes(), &[b"v0:", ts.as_bytes(), b":", body], &sig)
+                })
+        },
+        "standard" => {
+            let (Some(id), Some(ts), Some(value)) =
+                (header("webhook-id"), header("webhook-timestamp"), header("webhook-signature"))
+            else {
+                return false;
+            };
+            if !within_tolerance(ts) {
+                return false;
+            }
+            let key = match secret.strip_prefix("whsec_") {
+                Some(secret) => {
+                    match BASE64.decode(secret) {
+                        Ok(key) => key,
+                        Err(_) => return false
+                    }
+                },
+                None => secret.as_bytes().to_vec()
+            };
+            value.split_whitespace().filter_map(|sig| sig.strip_prefix("v1,")).any(|sig| {
+                BASE64.decode(sig).map_or(false, |sig| {
+                    verify(&key, &[id.as_bytes(), b".", ts.as_bytes(), b".", body], &sig)
+                })
+            })
+        },
+        _ => false
+    }
+}
+
+/// 按格式输出二进制数据
+fn encode_output(data: &[u8], format: Option<&str>) -> String {
+    if format.map_or(false, |format| format.eq_ignore_ascii_case("base64")) {
+        BASE64.encode(data)
+    } else {
+        data.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
+
+fn hmac(algorithm: &str, key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    macro_rules! mac {
+        ($digest:ty) => {{
+            let mut mac = Hmac::<$digest>::new_from_slice(key).ok()?;
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }};
+    }
+    Some(match algorithm.replace('-', "").to_ascii_uppercase().as_str() {
+        "MD5" => mac!(md5::Md5),
+        "SHA1" => mac!(Sha1),
+        "SHA256" => mac!(Sha256),
+        "SHA384" => mac!(Sha384),
+        "SHA512" => mac!(Sha512),
+        _ => return None
+    })
+}
+
+fn rsa_private_key(pem: &str) -> Option<RsaPrivateKey> {
+    let pem = pem.trim();
+    RsaPrivateKey::from_pkcs8_pem(pem).ok().or_else(|| RsaPrivateKey::from_pkcs1_pem(pem).ok())
+}
+
+fn rsa_public_key(pem: &str) -> Option<RsaPublicKey> {
+    let pem = pem.trim();
+    RsaPublicKey::from_public_key_pem(pem).ok().or_else(|| RsaPublicKey::from_pkcs1_pem(pem).ok())
+}
+
+/// `RSA`签名
+fn rsa_sign(pem_key: &str, data: &[u8], hash: Option<&str>, pss: bool) -> Option<Vec<u8>> {
+    fn sign<D: Digest + AssociatedOid + FixedOutputReset>(
+        key: RsaPrivateKey,
+        data: &[u8],
+        pss: bool
+    ) -> Option<Vec<u8>> {
+        if pss {
+            pss::BlindedSigningKey::<D>::new(key)
+                .try_sign_with_rng(&mut OsRng, data)
+                .ok()
+                .map(|sig| sig.to_vec())
+        } else {
+            pkcs1v15::SigningKey::<D>::new(key).try_sign(data).ok().map(|sig| sig.to_vec())
+        }
+    }
+    let key = rsa_private_key(pem_key)?;
+    match hash.unwrap_or("SHA256").replace('-', "").to_ascii_uppercase().as_str() {
+        "SHA1" => sign::<Sha1>(key, data, pss),
+        "SHA256" => sign::<Sha256>(key, data, pss),
+        "SHA384" => sign::<Sha384>(key, data, pss),
+        "SHA512" => sign::<Sha512>(key, data, pss),
+        _ => None
+    }
+}
+
+/// 校验`RSA`签名
+fn rsa_verify(pem_key: &str, data: &[u8], signature: &str, hash: Option<&str>, pss: bool) -> bool {
+    fn verify<D: Digest + AssociatedOid + FixedOutputReset>(
+        key: RsaPublicKey,
+        data: &[u8],
+        sig: &[u8],
+        pss: bool
+    ) -> bool {
+        if pss {
+            pss::Signature::try_from(sig)
+                .map_or(false, |sig| pss::VerifyingKey::<D>::new(key).verify(data, &sig).is_ok())
+        } else {
+            pkcs1v15::Signature::try_from(sig)
+                .map_or(false, |sig| pkcs1v15::VerifyingKey::<D>::new(key).verify(data, &sig).is_ok())
+        }
+    }
+    let (key, sig) = match (rsa_public_key(pem_key), BASE64.decode(signature.trim())) {
+        (Some(key), Ok(sig)) => (key, sig),
+        _ => return false
+    };
+    match hash.unwrap_or("SHA256").replace('-', "").to_ascii_uppercase().as_str() {
+        "SHA1" => verify::<Sha1>(key, data, &sig, pss),
+        "SHA256" => verify::<Sha256>(key, data, &sig, pss),
+        "SHA384" => verify::<Sha384>(key, data, &sig, pss),
+        "SHA512" => verify::<Sha512>(key, data, &sig, pss),
+        _ => false
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    base::{conv, locale::tr}, prelude::*
+};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use sha2::digest::DynDigest;
+use std::{
+    fs::File, io::{self, Read}
+};
+
+/// 文件读取块大小
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// 摘要算法
+pub(crate) enum Hasher {
+    Digest(Box<dyn DynDigest + Send>),
+    Crc32(crc32fast::Hasher)
+}
+
+impl Hasher {
+    /// 通过算法名称创建
+    ///
+    /// 支持`MD5`、`SHA1`、`SHA256`、`SHA384`、`SHA512`、`CRC32`(不区分大小写，可以包含`-`)
+    pub(crate) fn new(algorithm: &str) -> Option<Hasher> {
+        let algorithm = algorithm.replace('-', "").to_ascii_uppercase();
+        Some(match algorithm.as_str() {
+            "MD5" => Hasher::Digest(Box::new(md5::Md5::default())),
+            "SHA1" => Hasher::Digest(Box::new(sha1::Sha1::default())),
+            "SHA256" => Hasher::Digest(Box::new(sha2::Sha256::default())),
+            "SHA384" => Hasher::Digest(Box::new(sha2::Sha384::default())),
+            "SHA512" => Hasher::Digest(Box::new(sha2::Sha512::default())),
+            "CRC32" => Hasher::Crc32(crc32fast::Hasher::new()),
+            _ => return None
+        })
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Digest(hasher) => hasher.update(data),
+            Hasher::Crc32(hasher) => hasher.update(data)
+        }
+    }
+
+    /// 十六进制(小写)格式的摘要
+    pub(crate) fn finalize(self) -> String {
+        let digest = match self {
+            Hasher::Digest(hasher) => hasher.finalize().into_vec(),
+            Hasher::Crc32(hasher) => hasher.finalize().to_be_bytes().to_vec()
+        };
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
+
+struct Hash {
+    state: HandlerState,
+    job: JobSlot,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_hash")]
+impl Hash {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Hash {
+            state: HandlerState::new(session),
+            job: JobSlot::default(),
+            error: LastError::default()
+        }
+    }
+
+    /// 计算二进制数据的摘要
+    ///
+    /// # Parameters
+    ///
+    /// - `algorithm` 算法：`MD5`、`SHA1`、`SHA256`、`SHA384`、`SHA512`、`CRC32`
+    ///
+    /// # Returns
+    ///
+    /// 十六进制(小写)格式的摘要，算法无效时返回空字符串，通过`GetLastErrorText`获取错误信息
+    #[method(name = "HashBlob")]
+    fn hash_blob(&mut self, algorithm: String, data: &[u8]) -> String {
+        match Hasher::new(&algorithm) {
+            Some(mut hasher) => {
+                self.error.clear();
+                hasher.update(data);
+                hasher.finalize()
+            },
+            None => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid algorithm: {algorithm}"));
+                "".to_owned()
+            }
+        }
+    }
+
+    /// 计算字符串的摘要
+    ///
+    /// # Parameters
+    ///
+    /// - `algorithm` 算法：`MD5`、`SHA1`、`SHA256`、`SHA384`、`SHA512`、`CRC32`
+    /// - `encoding` 字符串编码，默认`UTF-8`
+    #[method(name = "HashString", overload = 1)]
+    fn hash_string(&mut self, algorithm: String, data: String, encoding: Option<pblong>) -> String {
+        self.hash_blob(algorithm, &conv::encode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)))
+    }
+
+    /// 计算文件的摘要
+    ///
+    /// # Description
+    ///
+    /// 同步执行，大文件使用`HashFileAsync`，失败时返回空字符串
+    #[method(name = "HashFile")]
+    fn hash_file(&mut self, algorithm: String, path: String) -> String {
+        let Some(hasher) = Hasher::new(&algorithm) else {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid algorithm: {algorithm}"));
+            return "".to_owned();
+        };
+        match hash_file(hasher, &path, |_, _| true) {
+            Ok(digest) => {
+                self.error.clear();
+                digest
+            },
+            Err(e) => {
+                self.error.set_io(e);
+                "".to_owned()
+            }
+        }
+    }
+
+    /// 异步计算文件的摘要
+    ///
+    /// # Description
+    ///
+    /// 在后台线程读取文件，通过`OnProgress`通知进度，完成后触发`OnComplete`
+    #[method(name = "HashFileAsync")]
+    fn hash_file_async(&mut self, algorithm: String, path: String) -> RetCode {
+        let hasher = match Hasher::new(&algorithm) {
+            Some(hasher) => hasher,
+            None => {
+                return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid algorithm: {algorithm}"))
+            },
+        };
+        spawn_file_job(
+            self,
+            |this| &mut this.job,
+            move |ctx| {
+                hash_file(hasher, &path, |total, processed| {
+                    ctx.progress(total, processed, |this, (total, processed)| {
+                        this.on_progress(total as pbulong, processed as pbulong)
+                    })
+                })
+            },
+            |this, rv| {
+                match rv {
+                    Ok(digest) => this.on_complete(digest, "".to_owned()),
+                    Err(e) => {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_complete("".to_owned(), error);
+                    }
+                }
+            }
+        )
+    }
+
+    /// 取消异步计算
+    #[method(name = "Cancel")]
+    fn cancel(&mut self) -> RetCode {
+        if self.job.cancel() {
+            RetCode::OK
+        } else {
+            RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.job.is_running() }
+
+    /// 异步计算进度
+    ///
+    /// 返回`PREVENT`取消计算
+    #[event(name = "OnProgress")]
+    fn on_progress(&mut self, total: pbulong, processed: pbulong) -> RetCode {}
+
+    /// 异步计算完成
+    ///
+    /// 成功时`error`为空字符串
+    #[event(name = "OnComplete")]
+    fn on_complete(&mut self, digest: String, error: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Handler for Hash {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+/// 分块读取文件并计算摘要
+///
+/// # Parameters
+///
+/// - `progress` 进度回调，参数为文件大小和已处理的大小，返回`false`中止
+pub(crate) fn hash_file(
+    mut hasher: Hasher,
+    path: &str,
+    mut progress: impl FnMut(u64, u64) -> bool
+) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let total = file.metadata()?.len();
+    let mut processed = 0;
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+        processed += len as u64;
+        if !progress(total, processed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, tr("cancelled", "已取消")));
+        }
+    }
+    Ok(hasher.finalize())
+}
@@ -0,0 +1,298 @@
+use crate::{base::pfw, pbx::json::Json, prelude::*};
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use pbni::pbx::*;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+#[derive(Default)]
+pub struct Jwt {
+    alg: Algorithm,
+    enc_key: Option<EncodingKey>,
+    dec_key: Option<DecodingKey>,
+    key_id: Option<String>,
+    claims: Map<String, Value>,
+    /// 时间校验的容差(秒)
+    leeway: u64,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_jwt")]
+impl Jwt {
+    /// 设置签名算法
+    ///
+    /// # Parameters
+    ///
+    /// - `alg` `HS256`(默认)、`HS384`、`HS512`、`RS256`、`RS384`、`RS512`
+    ///
+    /// # Description
+    ///
+    /// 切换`HS`/`RS`算法族时清除已设置的密钥
+    #[method(name = "SetAlgorithm")]
+    fn set_algorithm(&mut self, alg: String) -> RetCode {
+        let alg = match Algorithm::from_str(&alg.to_ascii_uppercase()) {
+            Ok(alg) if is_hmac(alg) || is_rsa(alg) => alg,
+            _ => return RetCode::E_INVALID_ARGUMENT
+        };
+        if is_hmac(alg) != is_hmac(self.alg) {
+            self.enc_key = None;
+            self.dec_key = None;
+        }
+        self.alg = alg;
+        RetCode::OK
+    }
+
+    /// 设置`HS`算法的密钥
+    #[method(name = "SetSecret")]
+    fn set_secret(&mut self, secret: String) -> RetCode {
+        if !is_hmac(self.alg) {
+            return RetCode::E_INVALID_ARGUMENT;
+        }
+        self.enc_key = Some(EncodingKey::from_secret(secret.as_bytes()));
+        self.dec_key = Some(DecodingKey::from_secret(secret.as_bytes()));
+        RetCode::OK
+    }
+
+    /// 设置`RS`算法的私钥(`PEM`格式)，用于签名
+    #[method(name = "SetPrivateKey")]
+    fn set_private_key(&mut self, pem_key: String) -> RetCode {
+        if !is_rsa(self.alg) {
+            return RetCode::E_INVALID_ARGUMENT;
+        }
+        match EncodingKey::from_rsa_pem(pem_key.trim().as_bytes()) {
+            Ok(key) => {
+                self.enc_key = Some(key);
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_INVALID_DATA, e)
+        }
+    }
+
+    /// 设置`RS`算法的公钥(`PEM`格式)，用于校验
+    #[method(name = "SetPublicKey")]
+    fn set_public_key(&mut self, pem_key: String) -> RetCode {
+        if !is_rsa(self.alg) {
+            return RetCode::E_INVALID_ARGUMENT;
+        }
+        match DecodingKey::from_rsa_pem(pem_key.trim().as_bytes()) {
+            Ok(key) => {
+                self.dec_key = Some(key);
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_INVALID_DATA, e)
+        }
+    }
+
+    /// 设置头部的`kid`
+    #[method(name = "SetKeyId")]
+    fn set_key_id(&mut self, kid: String) -> RetCode {
+        self.key_id = (!kid.is_empty()).then_some(kid);
+        RetCode::OK
+    }
+
+    /// 设置时间校验(`exp`/`nbf`)的容差
+    #[method(name = "SetLeeway")]
+    fn set_leeway(&mut self, secs: pbulong) -> RetCode {
+        self.leeway = secs as u64;
+        RetCode::OK
+    }
+
+    /// 设置声明(替换已有的声明)
+    ///
+    /// # Parameters
+    ///
+    /// - `claims` `JSON`对象字符串
+    #[method(name = "SetClaims")]
+    fn set_claims(&mut self, claims: String) -> RetCode {
+        match serde_json::from_str(&claims) {
+            Ok(Value::Object(claims)) => {
+                self.claims = claims;
+                RetCode::OK
+            },
+            _ => RetCode::E_INVALID_DATA
+        }
+    }
+
+    /// 设置声明(替换已有的声明)
+    ///
+    /// # Parameters
+    ///
+    /// - `claims` `n_json`对象
+    #[method(name = "SetClaims")]
+    fn set_claims_object(&mut self, claims: Object) -> RetCode {
+        if claims.get_class_name() != "n_json" {
+            return RetCode::E_INVALID_ARGUMENT;
+        }
+        self.set_claims(pfw::json_serialize(&claims))
+    }
+
+    /// 设置声明(替换已有的声明)
+    ///
+    /// # Parameters
+    ///
+    /// - `claims` `nx_json`对象
+    #[method(name = "SetClaims")]
+    fn set_claims_json(&mut self, claims: &mut Json) -> RetCode {
+        match claims.root() {
+            Value::Object(claims) => {
+                self.claims = claims.clone();
+                RetCode::OK
+            },
+            _ => RetCode::E_INVALID_DATA
+        }
+    }
+
+    /// 设置字符串声明
+    #[method(name = "SetClaim")]
+    fn set_claim(&mut self, name: String, value: String) -> RetCode {
+        self.claims.insert(name, Value::String(value));
+        RetCode::OK
+    }
+
+    /// 设置有效期
+    ///
+    /// # Description
+    ///
+    /// 设置`iat`为当前时间，`exp`为当前时间加`secs`秒
+    #[method(name = "SetExpiresIn")]
+    fn set_expires_in(&mut self, secs: pbulong) -> RetCode {
+        let now = jsonwebtoken::get_current_timestamp();
+        self.claims.insert("iat".to_owned(), now.into());
+        self.claims.insert("exp".to_owned(), (now + secs as u64).into());
+        RetCode::OK
+    }
+
+    /// 删除所有声明
+    #[method(name = "ClearClaims")]
+    fn clear_claims(&mut self) -> RetCode {
+        self.claims.clear();
+        RetCode::OK
+    }
+
+    /// 生成签名的令牌
+    ///
+    /// 未设置密钥或签名失败时返回空字符串，通过`GetLastErrorText`获取错误信息
+    #[method(name = "Sign")]
+    fn sign(&mut self) -> String { self.encode().unwrap_or_default() }
+
+    /// 校验并解码令牌
+    ///
+    /// # Parameters
+    ///
+    /// - `audience` 期望的`aud`，不指定时不校验
+    ///
+    /// # Returns
+    ///
+    /// - `E_INVALID_OBJECT` 未设置校验密钥
+    /// - `E_INVALID_DATA` 格式无效、算法不匹配或签名错误
+    /// - `E_TIME_OUT` 已过期(`exp`)或未生效(`nbf`)
+    /// - `E_ACCESS_DENIED` `aud`不匹配
+    ///
+    /// # Description
+    ///
+    /// 令牌包含`exp`时校验有效期，成功后通过`GetClaims`获取声明
+    #[method(name = "Verify", overload = 1)]
+    fn verify(&mut self, token: String, audience: Option<String>) -> RetCode {
+        let key = match &self.dec_key {
+            Some(key) => key,
+            None => return RetCode::E_INVALID_OBJECT
+        };
+        let mut validation = Validation::new(self.alg);
+        validation.required_spec_claims.clear();
+        validation.leeway = self.leeway;
+        validation.validate_nbf = true;
+        match &audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false
+        }
+        match jsonwebtoken::decode::<Map<String, Value>>(token.trim(), key, &validation) {
+            Ok(data) => {
+                self.claims = data.claims;
+                self.error.clear();
+                RetCode::OK
+            },
+            Err(e) => {
+                let code = match e.kind() {
+                    ErrorKind::ExpiredSignature | ErrorKind::ImmatureSignature => RetCode::E_TIME_OUT,
+                    ErrorKind::InvalidAudience => RetCode::E_ACCESS_DENIED,
+                    _ => RetCode::E_INVALID_DATA
+                };
+                self.error.set(code, e)
+            }
+        }
+    }
+
+    /// 解码令牌(不校验签名和有效期)
+    #[method(name = "Decode")]
+    fn decode(&mut self, token: String) -> RetCode {
+        let mut validation = Validation::default();
+        validation.insecure_disable_signature_validation();
+        validation.required_spec_claims.clear();
+        validation.validate_exp = false;
+        validation.validate_aud = false;
+        match jsonwebtoken::decode::<Map<String, Value>>(
+            token.trim(),
+            &DecodingKey::from_secret(&[]),
+            &validation
+        ) {
+            Ok(data) => {
+                self.claims = data.claims;
+                self.error.clear();
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_INVALID_DATA, e)
+        }
+    }
+
+    /// 获取所有声明(`JSON`对象字符串)
+    #[method(name = "GetClaims")]
+    fn get_claims(&self) -> String { Value::Object(self.claims.clone()).to_string() }
+
+    /// 获取声明
+    ///
+    /// 字符串声明返回原值，其它类型返回`JSON`文本，不存在时返回空字符串
+    #[method(name = "GetClaim")]
+    fn get_claim(&self, name: String) -> String {
+        match self.claims.get(&name) {
+            Some(Value::String(val)) => val.clone(),
+            Some(val) => val.to_string(),
+            None => "".to_owned()
+        }
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Jwt {
+    /// 生成签名的令牌
+    pub fn encode(&mut self) -> Option<String> {
+        let key = match &self.enc_key {
+            Some(key) => key,
+            None => {
+                self.error.set(RetCode::E_INVALID_OBJECT, "signing key is not set");
+                return None;
+            }
+        };
+        let mut header = Header::new(self.alg);
+        header.kid = self.key_id.clone();
+        match jsonwebtoken::encode(&header, &self.claims, key) {
+            Ok(token) => {
+                self.error.clear();
+                Some(token)
+            },
+            Err(e) => {
+                self.error.set(RetCode::FAILED, e);
+                None
+            }
+        }
+    }
+}
+
+fn is_hmac(alg: Algorithm) -> bool { matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) }
+
+fn is_rsa(alg: Algorithm) -> bool { matches!(alg, Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512) }
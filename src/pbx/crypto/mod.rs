@@ -0,0 +1,7 @@
+mod hash;
+mod crypt;
+mod cipher;
+mod jwt;
+
+pub(crate) use hash::{hash_file, Hasher};
+pub use jwt::Jwt;
@@ -0,0 +1 @@
+mod win32;
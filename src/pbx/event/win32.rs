@@ -0,0 +1,118 @@
+use crate::prelude::*;
+use pbni::pbx::*;
+use reactor::Win32Event;
+use std::time::Duration;
+
+#[derive(Default)]
+struct Event {
+    event: Option<Win32Event>,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_event")]
+impl Event {
+    /// 创建事件
+    ///
+    /// # Parameters
+    ///
+    /// - `manual` 手动重置(信号保持到调用`Reset`)，否则等待成功后自动重置，默认`true`
+    ///
+    /// # Description
+    ///
+    /// 替换并关闭已创建的事件，已通过`GetHandle`传递给`Send`/`AsyncSend`的句柄使用副本等待，不受影响
+    #[method(name = "Create", overload = 1)]
+    fn create(&mut self, manual: Option<bool>) -> RetCode {
+        self.event = Some(if manual.unwrap_or(true) {
+            Win32Event::manual()
+        } else {
+            Win32Event::auto()
+        });
+        RetCode::OK
+    }
+
+    #[method(name = "IsValid")]
+    fn is_valid(&self) -> bool { self.event.is_some() }
+
+    /// 设置信号
+    ///
+    /// 作为`hevent`传递给请求时取消请求
+    #[method(name = "Set")]
+    fn set(&mut self) -> RetCode {
+        let Some(event) = self.event.as_ref() else {
+            return RetCode::E_INVALID_HANDLE;
+        };
+        match event.set() {
+            Ok(()) => RetCode::OK,
+            Err(e) => self.error.set(RetCode::E_WIN32_ERROR, e)
+        }
+    }
+
+    /// 重置信号
+    #[method(name = "Reset")]
+    fn reset(&mut self) -> RetCode {
+        let Some(event) = self.event.as_ref() else {
+            return RetCode::E_INVALID_HANDLE;
+        };
+        match event.reset() {
+            Ok(()) => RetCode::OK,
+            Err(e) => self.error.set(RetCode::E_WIN32_ERROR, e)
+        }
+    }
+
+    /// 阻塞等待信号
+    ///
+    /// # Parameters
+    ///
+    /// - `timeout` 超时(秒)，默认无限等待
+    ///
+    /// # Returns
+    ///
+    /// 收到信号返回`OK`，超时返回`E_TIME_OUT`
+    #[method(name = "Wait", overload = 1)]
+    fn wait(&mut self, timeout: Option<pbdouble>) -> RetCode {
+        let Some(event) = self.event.as_ref() else {
+            return RetCode::E_INVALID_HANDLE;
+        };
+        let rv = match timeout {
+            Some(secs) => {
+                match Duration::try_from_secs_f64(secs) {
+                    Ok(dur) => event.wait_timeout(dur),
+                    Err(_) => {
+                        return self
+                            .error
+                            .set(RetCode::E_INVALID_ARGUMENT, format!("invalid timeout: {secs}"))
+                    },
+                }
+            },
+            None => event.blocking_wait()
+        };
+        match rv {
+            Ok(true) => RetCode::OK,
+            Ok(false) => RetCode::E_TIME_OUT,
+            Err(e) => self.error.set(RetCode::E_WIN32_ERROR, e)
+        }
+    }
+
+    /// 关闭事件
+    #[method(name = "Close")]
+    fn close(&mut self) -> RetCode {
+        self.event = None;
+        RetCode::OK
+    }
+
+    /// 事件句柄
+    ///
+    /// 用于`nx_httprequest.Send`/`AsyncSend`等方法的`hevent`参数，未创建时返回`0`
+    #[method(name = "GetHandle")]
+    fn handle(&self) -> pbulong {
+        self.event.as_ref().map(|event| event.as_raw().0 as pbulong).unwrap_or_default()
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
@@ -0,0 +1,654 @@
+use super::wildcard_match;
+use crate::{
+    pbx::crypto::{hash_file, Hasher}, prelude::*
+};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap, env, fs, io, mem, path::{Component, Path, PathBuf}, sync::atomic::{AtomicBool, Ordering}, thread
+};
+
+/// 默认每批次的文件数
+const DEFAULT_BATCH_SIZE: usize = 500;
+/// 长路径前缀
+const VERBATIM_PREFIX: &str = r"\\?\";
+/// 长路径`UNC`前缀
+const VERBATIM_UNC_PREFIX: &str = r"\\?\UNC\";
+
+struct FileOps {
+    state: HandlerState,
+    batch_size: usize,
+    job: JobSlot,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_fileops")]
+impl FileOps {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        FileOps {
+            state: HandlerState::new(session),
+            batch_size: DEFAULT_BATCH_SIZE,
+            job: JobSlot::default(),
+            error: LastError::default()
+        }
+    }
+
+    /// 设置异步操作每批次通知的数量，默认500
+    #[method(name = "SetBatchSize")]
+    fn set_batch_size(&mut self, size: pbulong) -> RetCode {
+        if size == 0 {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "batch size must be greater than 0");
+        }
+        self.batch_size = size as usize;
+        RetCode::OK
+    }
+
+    /// 异步查找文件
+    ///
+    /// # Parameters
+    ///
+    /// - `dir` 起始目录，支持网络共享路径
+    /// - `pattern` 匹配模式，不区分大小写，`*`、`?`匹配文件名中的字符，`**`匹配任意层目录，
+    ///   如`*.csv`、`2024*/*.txt`、`**/backup/*.bak`，空字符串表示所有文件
+    /// - `recursive` 模式不含目录时是否查找子目录，默认`false`
+    /// - `include_dirs` 结果是否包含目录，默认`false`
+    ///
+    /// # Description
+    ///
+    /// 在后台线程遍历，每`SetBatchSize`个结果触发一次`OnFindResults`，完成后触发`OnFindComplete`，
+    /// 无法访问的子目录将被跳过，不进入目录符号链接和连接点
+    #[method(name = "Find", overload = 2)]
+    fn find(
+        &mut self,
+        dir: String,
+        pattern: String,
+        recursive: Option<bool>,
+        include_dirs: Option<bool>
+    ) -> RetCode {
+        if self.job.is_running() {
+            return RetCode::E_BUSY;
+        }
+        if !Path::new(&dir).is_dir() {
+            return self.error.set(RetCode::E_FILE_NOT_FOUND, format!("directory not found: {dir}"));
+        }
+        let glob = Glob::new(&pattern, recursive.unwrap_or_default());
+        let include_dirs = include_dirs.unwrap_or_default();
+        let batch_size = self.batch_size;
+        spawn_file_job(
+            self,
+            |this| &mut this.job,
+            move |ctx| {
+                let mut count = 0u64;
+                find(Path::new(&dir), &glob, include_dirs, batch_size, &mut |batch: Vec<String>| {
+                    let first_index = count + 1;
+                    count += batch.len() as u64;
+                    let files = Value::from(batch).to_string();
+                    ctx.invoke((files, first_index), |this, (files, first_index)| {
+                        this.on_find_results(files, first_index as pblong)
+                    })
+                })
+            },
+            |this, rv| {
+                match rv {
+                    Ok(count) => this.on_find_complete("".to_owned(), count as pblong),
+                    Err(e) => {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_find_complete(error, 0);
+                    }
+                }
+            }
+        )
+    }
+
+    /// 异步比较两个目录
+    ///
+    /// # Parameters
+    ///
+    /// - `src` 源目录
+    /// - `dst` 目标目录
+    /// - `algorithm` 摘要算法：`MD5`、`SHA1`、`SHA256`、`SHA384`、`SHA512`、`CRC32`
+    /// - `recursive` 是否包含子目录，默认`true`
+    ///
+    /// # Description
+    ///
+    /// 按相对路径(不区分大小写)配对文件，大小不同时直接视为不同，否则同时计算两侧的摘要进行比较，
+    /// 差异通过`OnCompareResults`分批通知，完成后触发`OnCompareComplete`
+    #[method(name = "CompareDirs", overload = 1)]
+    fn compare_dirs(
+        &mut self,
+        src: String,
+        dst: String,
+        algorithm: String,
+        recursive: Option<bool>
+    ) -> RetCode {
+        if self.job.is_running() {
+            return RetCode::E_BUSY;
+        }
+        if Hasher::new(&algorithm).is_none() {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid algorithm: {algorithm}"));
+        }
+        for dir in [&src, &dst] {
+            if !Path::new(dir).is_dir() {
+                return self.error.set(RetCode::E_FILE_NOT_FOUND, format!("directory not found: {dir}"));
+            }
+        }
+        let recursive = recursive.unwrap_or(true);
+        let batch_size = self.batch_size;
+        spawn_file_job(
+            self,
+            |this| &mut this.job,
+            move |ctx| {
+                let cancelled = ctx.cancel_flag();
+                let mut count = 0u64;
+                let mut on_event = |event: CompareEvent| {
+                    match event {
+                        CompareEvent::Progress(total, processed) => {
+                            ctx.progress(total, processed, |this, (total, processed)| {
+                                this.on_compare_progress(total as pbulong, processed as pbulong)
+                            })
+                        },
+                        CompareEvent::Results(batch) => {
+                            let first_index = count + 1;
+                            count += batch.len() as u64;
+                            let results = Value::from(batch).to_string();
+                            ctx.invoke((results, first_index), |this, (results, first_index)| {
+                                this.on_compare_results(results, first_index as pblong)
+                            })
+                        }
+                    }
+                };
+                compare_dirs(
+                    Path::new(&src),
+                    Path::new(&dst),
+                    &algorithm,
+                    recursive,
+                    batch_size,
+                    &cancelled,
+                    &mut on_event
+                )
+            },
+            |this, rv| {
+                match rv {
+                    Ok((compared, differences)) => {
+                        this.on_compare_complete("".to_owned(), compared as pblong, differences as pblong)
+                    },
+                    Err(e) => {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_compare_complete(error, 0, 0);
+                    }
+                }
+            }
+        )
+    }
+
+    /// 取消正在执行的异步操作
+    #[method(name = "Cancel")]
+    fn cancel(&mut self) -> RetCode {
+        if self.job.cancel() {
+            RetCode::OK
+        } else {
+            RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.job.is_running() }
+
+    /// 规范化路径
+    ///
+    /// # Description
+    ///
+    /// 统一使用`\`分隔，合并重复的分隔符并解析`.`和`..`，不访问文件系统
+    #[method(name = "NormalizePath")]
+    fn normalize_path(&self, path: String) -> String { normalize(Path::new(&path)) }
+
+    /// 获取完整路径
+    ///
+    /// # Description
+    ///
+    /// 相对路径基于当前目录，结果已规范化
+    #[method(name = "GetFullPath")]
+    fn get_full_path(&self, path: String) -> String { full_path(Path::new(&path)) }
+
+    /// 转换为长路径(`\\?\`前缀)格式，用于访问超过260个字符的路径
+    #[method(name = "ToLongPath")]
+    fn to_long_path(&self, path: String) -> String { to_long_path(Path::new(&path)) }
+
+    /// 去除长路径(`\\?\`前缀)格式
+    #[method(name = "FromLongPath")]
+    fn from_long_path(&self, path: String) -> String { from_long_path(&path) }
+
+    /// 拼接路径，`path`为绝对路径时直接返回`path`
+    #[method(name = "JoinPath")]
+    fn join_path(&self, base: String, path: String) -> String { normalize(&Path::new(&base).join(path)) }
+
+    /// 获取上级目录，没有时返回空字符串
+    #[method(name = "GetParentPath")]
+    fn get_parent_path(&self, path: String) -> String {
+        Path::new(&path).parent().map(|parent| parent.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    /// 获取文件名(含扩展名)
+    #[method(name = "GetFileName")]
+    fn get_file_name(&self, path: String) -> String {
+        Path::new(&path).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    /// 获取扩展名(不含`.`)
+    #[method(name = "GetExtension")]
+    fn get_extension(&self, path: String) -> String {
+        Path::new(&path).extension().map(|ext| ext.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    /// 查找结果
+    ///
+    /// # Parameters
+    ///
+    /// - `files` 完整路径的`JSON`数组
+    /// - `first_index` 本批第一个结果的序号(从1开始)
+    ///
+    /// 返回`PREVENT`停止查找
+    #[event(name = "OnFindResults")]
+    fn on_find_results(&mut self, files: String, first_index: pblong) -> RetCode {}
+
+    /// 查找完成
+    ///
+    /// 成功时`error`为空字符串，`count`为结果总数
+    #[event(name = "OnFindComplete")]
+    fn on_find_complete(&mut self, error: String, count: pblong) {}
+
+    /// 比较进度
+    ///
+    /// # Parameters
+    ///
+    /// - `total` 需要比较的文件总数
+    /// - `processed` 已比较的文件数
+    ///
+    /// 返回`PREVENT`停止比较
+    #[event(name = "OnCompareProgress")]
+    fn on_compare_progress(&mut self, total: pbulong, processed: pbulong) -> RetCode {}
+
+    /// 比较结果
+    ///
+    /// # Parameters
+    ///
+    /// - `results` 差异的`JSON`数组，元素包含`path`(相对路径)、`status`(`missing`目标缺少、`extra`目标多出、
+    ///   `different`内容不同)、`src_size`、`dst_size`，内容不同且大小相同时还包含`src_hash`和`dst_hash`
+    /// - `first_index` 本批第一个结果的序号(从1开始)
+    ///
+    /// 返回`PREVENT`停止比较
+    #[event(name = "OnCompareResults")]
+    fn on_compare_results(&mut self, results: String, first_index: pblong) -> RetCode {}
+
+    /// 比较完成
+    ///
+    /// 成功时`error`为空字符串，`compared`为两侧都存在的文件数，`differences`为差异总数
+    #[event(name = "OnCompareComplete")]
+    fn on_compare_complete(&mut self, error: String, compared: pblong, differences: pblong) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Handler for FileOps {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+/// 查找模式
+struct Glob {
+    /// 按目录分隔的模式(小写)
+    segments: Vec<String>,
+    /// 只匹配文件名
+    name_only: bool,
+    recursive: bool
+}
+
+impl Glob {
+    fn new(pattern: &str, recursive: bool) -> Self {
+        let mut segments: Vec<String> = pattern
+            .split(['/', '\\'])
+            .filter(|segment| !segment.is_empty() && *segment != ".")
+            .map(|segment| segment.to_lowercase())
+            .collect();
+        if segments.is_empty() {
+            segments.push("*".to_owned());
+        }
+        let name_only = segments.len() == 1 && segments[0] != "**";
+        Glob {
+            segments,
+            name_only,
+            recursive
+        }
+    }
+
+    /// 相对路径是否匹配
+    fn is_match(&self, rel_path: &[String]) -> bool {
+        if self.name_only {
+            rel_path.last().map_or(false, |name| wildcard_match(&self.segments[0], name))
+        } else {
+            match_segments(&self.segments, rel_path)
+        }
+    }
+
+    /// 是否需要进入指定深度(从0开始)的子目录
+    fn can_descend(&self, depth: usize) -> bool {
+        if self.name_only {
+            self.recursive
+        } else {
+            self.segments.iter().any(|segment| segment == "**") || depth + 1 < self.segments.len()
+        }
+    }
+}
+
+fn match_segments(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|idx| match_segments(rest, &path[idx..]))
+        },
+        Some((first, rest)) => {
+            match path.split_first() {
+                Some((name, path)) => wildcard_match(first, name) && match_segments(rest, path),
+                None => false
+            }
+        },
+    }
+}
+
+/// 遍历目录
+///
+/// # Returns
+///
+/// 结果总数
+fn find(
+    root: &Path,
+    glob: &Glob,
+    include_dirs: bool,
+    batch_size: usize,
+    on_batch: &mut dyn FnMut(Vec<String>) -> bool
+) -> io::Result<u64> {
+    //使用长路径格式遍历，避免深层目录超过`MAX_PATH`
+    let root = PathBuf::from(to_long_path(root));
+    let mut count = 0u64;
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut stack = vec![(root.clone(), Vec::<String>::new())];
+    while let Some((dir, rel_path)) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if dir == root => return Err(e),
+            Err(_) => continue
+        };
+        for entry in entries {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let mut entry_rel_path = rel_path.clone();
+            entry_rel_path.push(entry.file_name().to_string_lossy().to_lowercase());
+            let is_dir = file_type.is_dir();
+            if (include_dirs || !is_dir) && glob.is_match(&entry_rel_path) {
+                batch.push(from_long_path(&entry.path().to_string_lossy()));
+                if batch.len() >= batch_size {
+                    count += batch.len() as u64;
+                    if !on_batch(mem::replace(&mut batch, Vec::with_capacity(batch_size))) {
+                        return Ok(count);
+                    }
+                }
+            }
+            //符号链接和连接点的`file_type`不是目录，不会进入
+            if is_dir && glob.can_descend(rel_path.len()) {
+                stack.push((entry.path(), entry_rel_path));
+            }
+        }
+    }
+    if !batch.is_empty() {
+        count += batch.len() as u64;
+        on_batch(batch);
+    }
+    Ok(count)
+}
+
+/// 目录比较的通知
+enum CompareEvent {
+    /// 总数和已处理数
+    Progress(u64, u64),
+    Results(Vec<Value>)
+}
+
+/// 目录中的文件
+struct FileEntry {
+    /// 相对路径
+    rel_path: String,
+    path: PathBuf,
+    size: u64
+}
+
+/// 列出目录中的文件，以小写的相对路径为键
+fn list_files(root: &Path, recursive: bool) -> io::Result<BTreeMap<String, FileEntry>> {
+    let root = PathBuf::from(to_long_path(root));
+    let mut files = BTreeMap::new();
+    let mut stack = vec![(root.clone(), String::new())];
+    while let Some((dir, rel_dir)) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if dir == root => return Err(e),
+            Err(_) => continue
+        };
+        for entry in entries {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let rel_path = if rel_dir.is_empty() {
+                name
+            } else {
+                format!("{rel_dir}\\{name}")
+            };
+            if file_type.is_dir() {
+                if recursive {
+                    stack.push((entry.path(), rel_path));
+                }
+            } else if file_type.is_file() {
+                let size = entry.metadata().map(|meta| meta.len()).unwrap_or_default();
+                files.insert(rel_path.to_lowercase(), FileEntry {
+                    rel_path,
+                    path: entry.path(),
+                    size
+                });
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// 比较两个目录
+///
+/// # Returns
+///
+/// 两侧都存在的文件数和差异数
+fn compare_dirs(
+    src: &Path,
+    dst: &Path,
+    algorithm: &str,
+    recursive: bool,
+    batch_size: usize,
+    cancelled: &AtomicBool,
+    on_event: &mut dyn FnMut(CompareEvent) -> bool
+) -> io::Result<(u64, u64)> {
+    let src_files = list_files(src, recursive)?;
+    let mut dst_files = list_files(dst, recursive)?;
+    let total = src_files.len() as u64;
+    let (mut compared, mut differences) = (0u64, 0u64);
+    let mut processed = 0u64;
+    let mut batch = Vec::with_capacity(batch_size);
+    let hash = |path: &Path| {
+        let hasher = Hasher::new(algorithm).expect("checked algorithm");
+        hash_file(hasher, &path.to_string_lossy(), |_, _| !cancelled.load(Ordering::Relaxed))
+    };
+    //先报告源目录的文件，再报告目标目录多出的文件
+    let mut results = src_files
+        .into_iter()
+        .map(|(key, src_file)| (Some(src_file), dst_files.remove(&key)))
+        .collect::<Vec<_>>();
+    results.extend(dst_files.into_values().map(|dst_file| (None, Some(dst_file))));
+    for (src_file, dst_file) in results {
+        let in_src = src_file.is_some();
+        let result = match (src_file, dst_file) {
+            (Some(src_file), Some(dst_file)) => {
+                compared += 1;
+                processed += 1;
+                if src_file.size != dst_file.size {
+                    Some(json!({
+                        "path": src_file.rel_path,
+                        "status": "different",
+                        "src_size": src_file.size,
+                        "dst_size": dst_file.size
+                    }))
+                } else {
+                    //两侧同时计算
+                    let (src_hash, dst_hash) = thread::scope(|scope| {
+                        let dst_hash = scope.spawn(|| hash(&dst_file.path));
+                        let src_hash = hash(&src_file.path);
+                        (
+                            src_hash,
+                            dst_hash
+                                .join()
+                                .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "panic")))
+                        )
+                    });
+                    if cancelled.load(Ordering::Relaxed) {
+                        return Ok((compared, differences));
+                    }
+                    let (src_hash, dst_hash) = (src_hash?, dst_hash?);
+                    if src_hash != dst_hash {
+                        Some(json!({
+                            "path": src_file.rel_path,
+                            "status": "different",
+                            "src_size": src_file.size,
+                            "dst_size": dst_file.size,
+                            "src_hash": src_hash,
+                            "dst_hash": dst_hash
+                        }))
+                    } else {
+                        None
+                    }
+                }
+            },
+            (Some(src_file), None) => {
+                processed += 1;
+                Some(json!({
+                    "path": src_file.rel_path,
+                    "status": "missing",
+                    "src_size": src_file.size
+                }))
+            },
+            (None, Some(dst_file)) => {
+                Some(json!({
+                    "path": dst_file.rel_path,
+                    "status": "extra",
+                    "dst_size": dst_file.size
+                }))
+            },
+            (None, None) => None
+        };
+        if let Some(result) = result {
+            differences += 1;
+            batch.push(result);
+            if batch.len() >= batch_size &&
+                !on_event(CompareEvent::Results(mem::replace(&mut batch, Vec::with_capacity(batch_size))))
+            {
+                return Ok((compared, differences));
+            }
+        }
+        if in_src && !on_event(CompareEvent::Progress(total, processed)) {
+            return Ok((compared, differences));
+        }
+    }
+    if !batch.is_empty() {
+        on_event(CompareEvent::Results(batch));
+    }
+    Ok((compared, differences))
+}
+
+/// 规范化路径
+pub(crate) fn normalize(path: &Path) -> String {
+    let mut prefix = String::new();
+    let mut has_root = false;
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(p) => prefix = p.as_os_str().to_string_lossy().into_owned(),
+            Component::RootDir => has_root = true,
+            Component::CurDir => {},
+            Component::ParentDir => {
+                match parts.last() {
+                    Some(last) if last != ".." => {
+                        parts.pop();
+                    },
+                    //根目录的上级仍是根目录
+                    _ if has_root => {},
+                    _ => parts.push("..".to_owned())
+                }
+            },
+            Component::Normal(name) => parts.push(name.to_string_lossy().into_owned())
+        }
+    }
+    let mut rv = prefix;
+    if has_root {
+        rv.push('\\');
+    }
+    rv.push_str(&parts.join("\\"));
+    if rv.is_empty() {
+        rv.push('.');
+    }
+    rv
+}
+
+/// 获取规范化的完整路径
+pub(crate) fn full_path(path: &Path) -> String {
+    if path.is_absolute() {
+        normalize(path)
+    } else {
+        match env::current_dir() {
+            Ok(cwd) => normalize(&cwd.join(path)),
+            Err(_) => normalize(path)
+        }
+    }
+}
+
+/// 转换为长路径格式
+pub(crate) fn to_long_path(path: &Path) -> String {
+    let path_str = path.to_string_lossy();
+    if path_str.starts_with(VERBATIM_PREFIX) {
+        return path_str.into_owned();
+    }
+    let full = full_path(path);
+    match full.strip_prefix(r"\\") {
+        Some(unc) => format!("{VERBATIM_UNC_PREFIX}{unc}"),
+        None => format!("{VERBATIM_PREFIX}{full}")
+    }
+}
+
+/// 去除长路径格式
+pub(crate) fn from_long_path(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(VERBATIM_UNC_PREFIX) {
+        format!(r"\\{unc}")
+    } else {
+        path.strip_prefix(VERBATIM_PREFIX).unwrap_or(path).to_owned()
+    }
+}
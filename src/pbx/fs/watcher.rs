@@ -0,0 +1,614 @@
+use super::wildcard_match;
+use crate::{base::locale::tr, prelude::*};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::{
+    ffi::OsString, io, os::windows::ffi::OsStringExt, path::{Path, PathBuf}, slice, sync::{
+        atomic::{AtomicBool, Ordering}, Arc
+    }, time::{Duration, Instant}
+};
+use windows::{
+    core::HSTRING, Win32::{
+        Foundation::{CloseHandle, ERROR_NOTIFY_ENUM_DIR, HANDLE, WAIT_OBJECT_0, WAIT_TIMEOUT}, Storage::FileSystem::{
+            CreateFileW, ReadDirectoryChangesW, FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OVERLAPPED, FILE_LIST_DIRECTORY, FILE_NOTIFY_CHANGE_DIR_NAME, FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE, FILE_NOTIFY_CHANGE_SIZE, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING
+        }, System::{
+            Threading::{CreateEventW, WaitForSingleObject}, IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED}
+        }
+    }
+};
+
+/// 通知缓冲区大小(网络共享目录的上限为64KB)
+const NOTIFY_BUF_SIZE: usize = 64 * 1024;
+/// 检查取消标志的最大间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// 默认的防抖间隔
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+// `FILE_NOTIFY_INFORMATION.Action`
+const FILE_ACTION_ADDED: u32 = 1;
+const FILE_ACTION_REMOVED: u32 = 2;
+const FILE_ACTION_MODIFIED: u32 = 3;
+const FILE_ACTION_RENAMED_OLD_NAME: u32 = 4;
+const FILE_ACTION_RENAMED_NEW_NAME: u32 = 5;
+
+struct FileWatcher {
+    state: HandlerState,
+    /// 监视的目录
+    path: String,
+    /// 文件名匹配模式
+    patterns: Vec<String>,
+    debounce: Duration,
+    running: Option<Running>,
+    watch_id: u64,
+    error: LastError
+}
+
+/// 执行中的监视任务
+struct Running {
+    cancelled: Arc<AtomicBool>,
+    cancel_hdl: CancelHandle
+}
+
+#[nonvisualobject(name = "nx_filewatcher")]
+impl FileWatcher {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        FileWatcher {
+            state: HandlerState::new(session),
+            path: String::new(),
+            patterns: Vec::new(),
+            debounce: DEFAULT_DEBOUNCE,
+            running: None,
+            watch_id: 0,
+            error: LastError::default()
+        }
+    }
+
+    /// 设置文件名过滤
+    ///
+    /// # Parameters
+    ///
+    /// - `patterns` 通配符(`*`、`?`)，多个以`;`分隔，如`*.csv;*.txt`，空字符串表示不过滤
+    ///
+    /// # Description
+    ///
+    /// 只匹配文件名(不含目录)且不区分大小写，重命名时新旧名称任一匹配即触发
+    #[method(name = "SetFilter")]
+    fn set_filter(&mut self, patterns: String) -> RetCode {
+        self.patterns = patterns
+            .split(';')
+            .map(|pattern| pattern.trim().to_lowercase())
+            .filter(|pattern| !pattern.is_empty())
+            .collect();
+        RetCode::OK
+    }
+
+    /// 设置防抖间隔
+    ///
+    /// # Parameters
+    ///
+    /// - `interval` 间隔(毫秒)，默认`500`，`0`表示不等待
+    ///
+    /// # Description
+    ///
+    /// 同一文件在间隔内的连续变化合并为一次事件，如复制大文件时的多次写入只触发一次`OnCreated`，
+    /// 下次`Start`时生效
+    #[method(name = "SetDebounce")]
+    fn set_debounce(&mut self, interval: pbulong) -> RetCode {
+        self.debounce = Duration::from_millis(interval as u64);
+        RetCode::OK
+    }
+
+    /// 开始监视目录
+    ///
+    /// # Parameters
+    ///
+    /// - `path` 目录，支持网络共享路径
+    /// - `recursive` 是否包含子目录，默认`false`
+    ///
+    /// # Description
+    ///
+    /// 正在监视时先停止再重新开始，事件参数均为完整路径
+    #[method(name = "Start", overload = 1)]
+    fn start(&mut self, path: String, recursive: Option<bool>) -> RetCode {
+        if !Path::new(&path).is_dir() {
+            return self.error.set(RetCode::E_FILE_NOT_FOUND, format!("directory not found: {path}"));
+        }
+        self.stop();
+        let mut watch = match Watch::open(Path::new(&path)) {
+            Ok(watch) => watch,
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                return self.error.set(RetCode::E_ACCESS_DENIED, e);
+            },
+            Err(e) => return self.error.set(RetCode::E_IO_ERROR, e)
+        };
+        //先提交一次读取，保证`Start`返回后的变化都能收到
+        let recursive = recursive.unwrap_or_default();
+        if let Err(e) = watch.read(recursive) {
+            return self.error.set(RetCode::E_IO_ERROR, e);
+        }
+        self.error.clear();
+        self.watch_id += 1;
+        self.path = path.clone();
+        let watch_id = self.watch_id;
+        let debounce = self.debounce;
+        let patterns = self.patterns.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let invoker = self.invoker();
+        let cancel_hdl = self.spawn(
+            {
+                let cancelled = cancelled.clone();
+                async move {
+                    let task = tokio::task::spawn_blocking(move || {
+                        let mut notify = |notify: Notify| {
+                            if cancelled.load(Ordering::Relaxed) {
+                                return false;
+                            }
+                            let rv = invoker
+                                .invoke_blocking(notify, move |this, notify| this.dispatch(watch_id, notify))
+                                .join();
+                            matches!(rv, Ok(true) | Err(InvokeError::Dropped))
+                        };
+                        watch.run(Path::new(&path), recursive, debounce, &patterns, &cancelled, &mut notify)
+                    });
+                    match task.await {
+                        Ok(rv) => rv,
+                        Err(e) => Err(io::Error::new(io::ErrorKind::Other, e))
+                    }
+                }
+            },
+            move |this, rv| {
+                if this.watch_id == watch_id {
+                    this.running = None;
+                    if let Err(e) = rv {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_error(error);
+                    }
+                }
+            }
+        );
+        self.running = Some(Running {
+            cancelled,
+            cancel_hdl
+        });
+        RetCode::OK
+    }
+
+    /// 停止监视
+    ///
+    /// # Description
+    ///
+    /// 防抖等待中的变化将被丢弃
+    #[method(name = "Stop")]
+    fn stop(&mut self) -> RetCode {
+        if let Some(running) = self.running.take() {
+            running.cancelled.store(true, Ordering::Relaxed);
+            running.cancel_hdl.cancel();
+        }
+        RetCode::OK
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.running.is_some() }
+
+    /// 最近一次`Start`的目录
+    #[method(name = "GetPath")]
+    fn get_path(&self) -> String { self.path.clone() }
+
+    /// 分发通知
+    ///
+    /// # Returns
+    ///
+    /// 是否继续监视
+    fn dispatch(&mut self, watch_id: u64, notify: Notify) -> bool {
+        if self.watch_id != watch_id || self.running.is_none() {
+            return false;
+        }
+        match notify {
+            Notify::Changes(changes) => {
+                for change in changes {
+                    let path = change.path.to_string_lossy().into_owned();
+                    match change.kind {
+                        ChangeKind::Created => self.on_created(path),
+                        ChangeKind::Modified => self.on_modified(path),
+                        ChangeKind::Deleted => self.on_deleted(path),
+                        ChangeKind::Renamed(old_path) => {
+                            self.on_renamed(old_path.to_string_lossy().into_owned(), path)
+                        },
+                    }
+                    //事件中可能调用了`Stop`或`Start`
+                    if self.watch_id != watch_id || self.running.is_none() {
+                        return false;
+                    }
+                }
+            },
+            Notify::Overflow => {
+                self.on_error(
+                    tr("too many changes, some notifications were lost", "变化过多，部分通知已丢失")
+                        .to_owned()
+                );
+            }
+        }
+        self.watch_id == watch_id && self.running.is_some()
+    }
+
+    /// 文件或目录创建
+    #[event(name = "OnCreated")]
+    fn on_created(&mut self, path: String) {}
+
+    /// 文件内容或大小变化
+    #[event(name = "OnModified")]
+    fn on_modified(&mut self, path: String) {}
+
+    /// 文件或目录删除(包括移出监视目录)
+    #[event(name = "OnDeleted")]
+    fn on_deleted(&mut self, path: String) {}
+
+    /// 文件或目录重命名
+    #[event(name = "OnRenamed")]
+    fn on_renamed(&mut self, old_path: String, new_path: String) {}
+
+    /// 监视出错
+    ///
+    /// # Description
+    ///
+    /// 变化过多导致通知丢失时继续监视，目录被删除或无法访问时停止监视
+    #[event(name = "OnError")]
+    fn on_error(&mut self, error: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Handler for FileWatcher {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+/// 变化类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+    /// 重命名，参数为原路径
+    Renamed(PathBuf)
+}
+
+#[derive(Debug, Clone)]
+struct Change {
+    kind: ChangeKind,
+    path: PathBuf
+}
+
+/// 后台线程发往UI线程的通知
+enum Notify {
+    Changes(Vec<Change>),
+    /// 通知缓冲区溢出，部分变化已丢失
+    Overflow
+}
+
+/// 防抖队列
+///
+/// 按首次出现的顺序保存每个路径合并后的变化，路径在防抖间隔内没有新变化时才输出
+#[derive(Default)]
+struct Debouncer {
+    pending: Vec<(Change, Instant)>
+}
+
+impl Debouncer {
+    fn find(&self, path: &Path) -> Option<usize> {
+        self.pending.iter().position(|(change, _)| change.path == path)
+    }
+
+    fn push(&mut self, kind: ChangeKind, path: PathBuf, now: Instant) {
+        let Some(idx) = self.find(&path) else {
+            self.pending.push((
+                Change {
+                    kind,
+                    path
+                },
+                now
+            ));
+            return;
+        };
+        let merged = match (self.pending[idx].0.kind.clone(), kind) {
+            //删除后重新创建(如编辑器保存)视为修改
+            (ChangeKind::Deleted, ChangeKind::Created) => Some((ChangeKind::Modified, None)),
+            //创建后删除的临时文件不输出
+            (ChangeKind::Created, ChangeKind::Deleted) => None,
+            //重命名后删除视为删除原文件
+            (ChangeKind::Renamed(old_path), ChangeKind::Deleted) => {
+                Some((ChangeKind::Deleted, Some(old_path)))
+            },
+            (_, ChangeKind::Deleted) => Some((ChangeKind::Deleted, None)),
+            //其它情况保留首次变化
+            (kind, _) => Some((kind, None))
+        };
+        match merged {
+            Some((kind, path)) => {
+                let (change, time) = &mut self.pending[idx];
+                change.kind = kind;
+                if let Some(path) = path {
+                    change.path = path;
+                }
+                *time = now;
+            },
+            None => {
+                self.pending.remove(idx);
+            }
+        }
+    }
+
+    fn rename(&mut self, old_path: PathBuf, new_path: PathBuf, now: Instant) {
+        //覆盖目标文件时以重命名为准
+        if let Some(idx) = self.find(&new_path) {
+            self.pending.remove(idx);
+        }
+        let kind = match self.find(&old_path).map(|idx| self.pending.remove(idx).0.kind) {
+            //创建后重命名视为直接创建新文件
+            Some(ChangeKind::Created) => ChangeKind::Created,
+            //连续重命名合并为一次
+            Some(ChangeKind::Renamed(orig_path)) if orig_path == new_path => ChangeKind::Modified,
+            Some(ChangeKind::Renamed(orig_path)) => ChangeKind::Renamed(orig_path),
+            _ => ChangeKind::Renamed(old_path)
+        };
+        self.pending.push((
+            Change {
+                kind,
+                path: new_path
+            },
+            now
+        ));
+    }
+
+    /// 下一个到期的时间
+    fn next_due(&self, debounce: Duration) -> Option<Instant> {
+        self.pending.iter().map(|(_, time)| *time + debounce).min()
+    }
+
+    /// 取出已到期的变化
+    fn take_due(&mut self, debounce: Duration, now: Instant) -> Vec<Change> {
+        let mut due = Vec::new();
+        self.pending.retain(|(change, time)| {
+            if now.saturating_duration_since(*time) >= debounce {
+                due.push(change.clone());
+                false
+            } else {
+                true
+            }
+        });
+        due
+    }
+}
+
+/// 目录监视句柄
+///
+/// 异步读取进行中时`OVERLAPPED`和缓冲区不能移动，因此总是在堆上分配
+struct Watch {
+    dir: HANDLE,
+    overlapped: OVERLAPPED,
+    buf: Vec<u32>,
+    pending: bool
+}
+
+unsafe impl Send for Watch {}
+
+impl Watch {
+    fn open(path: &Path) -> io::Result<Box<Watch>> {
+        unsafe {
+            let dir = CreateFileW(
+                &HSTRING::from(path),
+                FILE_LIST_DIRECTORY.0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                None,
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OVERLAPPED,
+                None
+            )
+            .map_err(|_| io::Error::last_os_error())?;
+            let event = match CreateEventW(None, true, false, None) {
+                Ok(event) => event,
+                Err(_) => {
+                    let err = io::Error::last_os_error();
+                    CloseHandle(dir);
+                    return Err(err);
+                }
+            };
+            let mut watch = Box::new(Watch {
+                dir,
+                overlapped: OVERLAPPED::default(),
+                buf: vec![0u32; NOTIFY_BUF_SIZE / 4],
+                pending: false
+            });
+            watch.overlapped.hEvent = event;
+            Ok(watch)
+        }
+    }
+
+    /// 提交异步读取
+    fn read(&mut self, recursive: bool) -> io::Result<()> {
+        let filter = FILE_NOTIFY_CHANGE_FILE_NAME |
+            FILE_NOTIFY_CHANGE_DIR_NAME |
+            FILE_NOTIFY_CHANGE_LAST_WRITE |
+            FILE_NOTIFY_CHANGE_SIZE;
+        let rv = unsafe {
+            ReadDirectoryChangesW(
+                self.dir,
+                self.buf.as_mut_ptr() as _,
+                (self.buf.len() * 4) as u32,
+                recursive,
+                filter,
+                None,
+                Some(&mut self.overlapped),
+                None
+            )
+        };
+        if rv == false {
+            return Err(io::Error::last_os_error());
+        }
+        self.pending = true;
+        Ok(())
+    }
+
+    /// 等待读取完成
+    ///
+    /// # Returns
+    ///
+    /// 超时返回`None`，否则返回读取的字节数(`0`表示缓冲区溢出)
+    fn wait(&mut self, timeout: Duration) -> io::Result<Option<usize>> {
+        let rc = unsafe { WaitForSingleObject(self.overlapped.hEvent, timeout.as_millis() as u32) };
+        match rc {
+            WAIT_OBJECT_0 => {
+                self.pending = false;
+                let mut len = 0u32;
+                if unsafe { GetOverlappedResult(self.dir, &self.overlapped, &mut len, false) } == false {
+                    let err = io::Error::last_os_error();
+                    if err.raw_os_error() == Some(ERROR_NOTIFY_ENUM_DIR.0 as i32) {
+                        return Ok(Some(0));
+                    }
+                    return Err(err);
+                }
+                Ok(Some(len as usize))
+            },
+            WAIT_TIMEOUT => Ok(None),
+            _ => Err(io::Error::last_os_error())
+        }
+    }
+
+    /// 解析`FILE_NOTIFY_INFORMATION`记录
+    fn records(&self, len: usize) -> Vec<(u32, PathBuf)> {
+        let buf =
+            unsafe { slice::from_raw_parts(self.buf.as_ptr() as *const u8, len.min(self.buf.len() * 4)) };
+        let field = |offset: usize| {
+            buf.get(offset..offset + 4)
+                .map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while let (Some(next), Some(action), Some(name_len)) =
+            (field(offset), field(offset + 4), field(offset + 8))
+        {
+            let Some(name) = buf.get(offset + 12..offset + 12 + name_len as usize) else {
+                break;
+            };
+            let name: Vec<u16> = name.chunks_exact(2).map(|c| u16::from_ne_bytes([c[0], c[1]])).collect();
+            records.push((action, PathBuf::from(OsString::from_wide(&name))));
+            if next == 0 {
+                break;
+            }
+            offset += next as usize;
+        }
+        records
+    }
+
+    /// 监视循环
+    fn run(
+        &mut self,
+        root: &Path,
+        recursive: bool,
+        debounce: Duration,
+        patterns: &[String],
+        cancelled: &AtomicBool,
+        notify: &mut dyn FnMut(Notify) -> bool
+    ) -> io::Result<()> {
+        let mut debouncer = Debouncer::default();
+        //重命名的原名称(与新名称成对出现)
+        let mut rename_from: Option<PathBuf> = None;
+        loop {
+            if cancelled.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            if !self.pending {
+                self.read(recursive)?;
+            }
+            let timeout = match debouncer.next_due(debounce) {
+                Some(due) => due.saturating_duration_since(Instant::now()).min(POLL_INTERVAL),
+                None => POLL_INTERVAL
+            };
+            match self.wait(timeout)? {
+                Some(0) => {
+                    if !notify(Notify::Overflow) {
+                        return Ok(());
+                    }
+                },
+                Some(len) => {
+                    let now = Instant::now();
+                    for (action, name) in self.records(len) {
+                        let path = root.join(name);
+                        if let Some(old_path) = rename_from.take() {
+                            if action == FILE_ACTION_RENAMED_NEW_NAME {
+                                debouncer.rename(old_path, path, now);
+                                continue;
+                            }
+                            //没有新名称说明移出了监视目录
+                            debouncer.push(ChangeKind::Deleted, old_path, now);
+                        }
+                        match action {
+                            FILE_ACTION_ADDED => debouncer.push(ChangeKind::Created, path, now),
+                            FILE_ACTION_REMOVED => debouncer.push(ChangeKind::Deleted, path, now),
+                            FILE_ACTION_MODIFIED => debouncer.push(ChangeKind::Modified, path, now),
+                            FILE_ACTION_RENAMED_OLD_NAME => rename_from = Some(path),
+                            //没有原名称说明从外部移入
+                            FILE_ACTION_RENAMED_NEW_NAME => debouncer.push(ChangeKind::Created, path, now),
+                            _ => {}
+                        }
+                    }
+                },
+                None => {}
+            }
+            let changes: Vec<Change> = debouncer
+                .take_due(debounce, Instant::now())
+                .into_iter()
+                .filter(|change| {
+                    //子目录内的变化会触发目录本身的修改通知，忽略
+                    if change.kind == ChangeKind::Modified && change.path.is_dir() {
+                        return false;
+                    }
+                    match &change.kind {
+                        ChangeKind::Renamed(old_path) => {
+                            is_match(patterns, old_path) || is_match(patterns, &change.path)
+                        },
+                        _ => is_match(patterns, &change.path)
+                    }
+                })
+                .collect();
+            if !changes.is_empty() && !notify(Notify::Changes(changes)) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        unsafe {
+            //取消未完成的读取并等待结束，之后才能释放缓冲区
+            if self.pending {
+                let mut len = 0u32;
+                CancelIoEx(self.dir, Some(&self.overlapped));
+                GetOverlappedResult(self.dir, &self.overlapped, &mut len, true);
+            }
+            CloseHandle(self.overlapped.hEvent);
+            CloseHandle(self.dir);
+        }
+    }
+}
+
+/// 文件名是否匹配任一模式，没有模式时总是匹配
+fn is_match(patterns: &[String], path: &Path) -> bool {
+    if patterns.is_empty() {
+        return true;
+    }
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+    let name = name.to_string_lossy().to_lowercase();
+    patterns.iter().any(|pattern| wildcard_match(pattern, &name))
+}
@@ -1,9 +1,348 @@
 use crate::prelude::*;
 use pbni::pbx::*;
 
-#[global_function(name = "pfwxFinalize")]
-fn finalize() {
+/// 销毁运行时
+///
+/// # Parameters
+///
+/// - `timeout` 等待期限(毫秒)，指定时先取消所有异步任务并处理积压的回调消息
+///
+/// # Returns
+///
+/// 在期限内完成返回`true`
+#[global_function(name = "pfwxFinalize", overload = 1)]
+fn finalize(timeout: Option<pbulong>) -> bool {
+    let mut rv = true;
     //销毁运行时
     #[cfg(feature = "reactor")]
-    reactor::runtime::shutdown();
+    {
+        if let Some(timeout) = timeout {
+            rv = reactor::shutdown_timeout(std::time::Duration::from_millis(timeout as u64));
+        } else {
+            reactor::runtime::shutdown();
+        }
+    }
+    //写入缓冲的日志
+    #[cfg(feature = "log")]
+    crate::base::logger::close_log();
+    rv
 }
+
+/// 设置UI线程回调消息队列
+///
+/// # Parameters
+///
+/// - `capacity` 队列容量
+/// - `policy` 队列已满时的策略：`0`等待，`1`合并进度通知，`2`丢弃最早的进度通知
+#[cfg(feature = "reactor")]
+#[global_function(name = "pfwxSetDispatchQueue")]
+fn set_dispatch_queue(capacity: pbulong, policy: pblong) -> RetCode {
+    let policy = match policy {
+        0 => reactor::OverflowPolicy::Block,
+        1 => reactor::OverflowPolicy::Coalesce,
+        2 => reactor::OverflowPolicy::DropOldest,
+        _ => return RetCode::E_INVALID_ARGUMENT
+    };
+    reactor::set_queue_config(capacity as usize, policy);
+    RetCode::OK
+}
+
+/// 设置UI线程阻塞监视
+///
+/// # Parameters
+///
+/// - `threshold` 回调消息等待处理的时长超过此阈值(毫秒)时记录警告日志，`0`表示停止监视
+#[cfg(feature = "reactor")]
+#[global_function(name = "pfwxSetWatchdog")]
+fn set_watchdog(threshold: pbulong) -> RetCode {
+    reactor::set_watchdog((threshold != 0).then(|| std::time::Duration::from_millis(threshold as u64)));
+    RetCode::OK
+}
+
+/// 在当前线程运行事件循环
+///
+/// # Parameters
+///
+/// - `timeout` 超时(毫秒)，默认不超时
+///
+/// # Description
+///
+/// 没有消息循环的后台会话(如PowerServer、批处理程序)使用异步对象时，调用此函数处理回调事件
+///
+/// # Returns
+///
+/// - `OK` 所有异步任务已完成
+/// - `CANCELLED` 回调事件中调用了`pfwxQuitEventLoop`
+/// - `E_TIME_OUT` 超时
+#[cfg(feature = "reactor")]
+#[global_function(name = "pfwxRunEventLoop", overload = 1)]
+fn run_event_loop(timeout: Option<pbulong>) -> RetCode {
+    match reactor::run_event_loop(timeout.map(|timeout| std::time::Duration::from_millis(timeout as u64))) {
+        reactor::EventLoopExit::Idle => RetCode::OK,
+        reactor::EventLoopExit::Quit => RetCode::CANCELLED,
+        reactor::EventLoopExit::Timeout => RetCode::E_TIME_OUT
+    }
+}
+
+/// 退出当前线程的事件循环
+#[cfg(feature = "reactor")]
+#[global_function(name = "pfwxQuitEventLoop")]
+fn quit_event_loop() { reactor::quit_event_loop(); }
+
+/// 延迟触发对象的`OnDelayed`事件
+///
+/// # Parameters
+///
+/// - `target` 接收事件的对象，需要定义`OnDelayed(string tag)`事件
+/// - `delay` 延迟(毫秒)
+/// - `tag` 事件参数
+#[cfg(feature = "task")]
+#[global_function(name = "pfwxPostDelayed")]
+fn post_delayed(target: Object, delay: pbulong, tag: String) -> RetCode {
+    super::task::post_delayed(&target, delay, tag);
+    RetCode::OK
+}
+
+/// 设置错误信息的语言
+///
+/// # Parameters
+///
+/// - `locale` `en`(默认)或`zh-CN`，也接受`en-US`、`zh`等写法
+///
+/// # Description
+///
+/// 影响之后触发的事件参数(如`OnError`、`OnClose`)、错误信息与异常文本，第三方库的原始错误信息保持不变
+#[global_function(name = "pfwxSetLocale")]
+fn set_locale(locale: String) -> RetCode {
+    match crate::base::locale::Locale::parse(&locale) {
+        Some(locale) => {
+            crate::base::locale::set(locale);
+            RetCode::OK
+        },
+        None => RetCode::E_INVALID_ARGUMENT
+    }
+}
+
+/// 获取错误信息的语言(`en`或`zh-CN`)
+#[global_function(name = "pfwxGetLocale")]
+fn get_locale() -> String { crate::base::locale::current().name().to_owned() }
+
+/// 获取诊断信息
+///
+/// # Returns
+///
+/// `JSON`对象字符串：
+///
+/// - `memory` 各子系统缓存数据的估算内存(字节)：`http.response`(响应数据)、`mqtt.offline_queue`(离线消息)、
+///   `dispatcher.pending`(当前线程积压的回调消息)
+/// - `objects` 各类对象的存活数量，如`nx_httpclient`、`nx_httpresponse`
+/// - `runtime` 运行时状态：`running`、`contexts`，以及当前线程的`pending_messages`、`pending_tasks`、`handlers`(异步对象)
+///
+/// # Description
+///
+/// 用于排查长时间运行后的内存增长，内存为估算值，不包含第三方库内部的缓存
+#[global_function(name = "pfwxGetDiagnostics")]
+fn get_diagnostics() -> String {
+    use crate::base::diag;
+    let join = |items: &[(&str, usize)]| {
+        items.iter().map(|(name, value)| format!("\"{name}\":{value}")).collect::<Vec<_>>().join(",")
+    };
+    #[allow(unused_mut)]
+    let mut memory = diag::memory();
+    #[allow(unused_mut)]
+    let mut runtime = String::new();
+    #[cfg(feature = "reactor")]
+    {
+        let stats = reactor::stats();
+        memory.push(("dispatcher.pending", stats.pending_bytes.unwrap_or_default()));
+        runtime = format!(
+            ",\"runtime\":{{\"running\":{},{}}}",
+            stats.running,
+            join(&[
+                ("contexts", stats.contexts),
+                ("pending_messages", stats.pending_messages.unwrap_or_default()),
+                ("pending_tasks", stats.pending_tasks.unwrap_or_default()),
+                ("handlers", stats.handlers.unwrap_or_default())
+            ])
+        );
+    }
+    format!("{{\"memory\":{{{}}},\"objects\":{{{}}}{runtime}}}", join(&memory), join(&diag::objects()))
+}
+
+/// 设置异常报告
+///
+/// # Parameters
+///
+/// - `dir` 报告文件的存放目录，空字符串表示不再生成报告
+/// - `minidump` 是否同时生成`minidump`文件，默认`false`
+///
+/// # Description
+///
+/// 后台运行时或回调过程中的`panic`在抛出PB异常前生成报告文件(异常信息、调用栈、运行时状态)
+#[cfg(feature = "reactor")]
+#[global_function(name = "pfwxSetCrashReport", overload = 1)]
+fn set_crash_report(dir: String, minidump: Option<bool>) -> RetCode {
+    let cfg = (!dir.is_empty()).then(|| {
+        reactor::CrashConfig {
+            dir: dir.into(),
+            minidump: minidump.unwrap_or_default()
+        }
+    });
+    reactor::set_crash_report(cfg);
+    RetCode::OK
+}
+
+/// 设置运行时日志
+///
+/// # Parameters
+///
+/// - `path` 日志文件路径
+/// - `level` 日志级别：`off`、`error`、`warn`、`info`、`debug`、`trace`
+/// - `rolling` 日志文件大小上限(MB)，超过后滚动到新文件，`0`表示不滚动
+#[cfg(feature = "log")]
+#[global_function(name = "pfwxSetLog")]
+fn set_log(path: String, level: String, rolling: pbulong) -> RetCode {
+    use crate::base::logger::{self, LogConfig};
+
+    let level = match level.trim().parse() {
+        Ok(level) => level,
+        Err(_) => return RetCode::E_INVALID_ARGUMENT
+    };
+    match logger::set_log(LogConfig {
+        path: path.into(),
+        level,
+        rolling: rolling as u64 * 1024 * 1024
+    }) {
+        Ok(true) => RetCode::OK,
+        Ok(false) => RetCode::E_BUSY,
+        Err(_) => RetCode::E_IO_ERROR
+    }
+}
+
+/// 加载全局默认配置
+///
+/// # Parameters
+///
+/// - `path` 配置文件路径，`JSON`或`INI`格式
+///
+/// # Description
+///
+/// 作为之后新建的`nx_httpconfig`、`nx_mqttconfig`的初始配置，未使用配置对象的`nx_httpclient`和`nx_mqttclient`同样生效，
+/// 对象的方法可以继续覆盖。支持的配置项(`INI`中为同名的节和键)：
+///
+/// - `http` `timeout`、`connect_timeout`、`proxy`、`proxy_user`、`proxy_password`、`user_agent`、`https_only`、
+///   `accept_invalid_cert`、`accept_invalid_host`、`sys_root_certificate`、`concurrency`、`retry`、`retry_delay`
+/// - `mqtt` `auto_reconnect`、`reconnect_min`、`reconnect_max`、`timeout`、`keep_alive`、`clean_session`
+/// - `log` `path`、`level`、`rolling`，设置了`path`时等同于调用`pfwxSetLog`
+///
+/// 时间的单位为秒
+///
+/// # Returns
+///
+/// 文件格式或配置值无效时返回`E_INVALID_DATA`，此时保留原有的配置
+#[cfg(feature = "config")]
+#[global_function(name = "pfwxLoadConfig")]
+fn load_config(path: String) -> RetCode {
+    use crate::base::config;
+    use std::io;
+
+    let cfg = match config::load(&path) {
+        Ok(cfg) => cfg,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return RetCode::E_FILE_NOT_FOUND,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => return RetCode::E_INVALID_DATA,
+        Err(_) => return RetCode::E_IO_ERROR
+    };
+    #[cfg(feature = "log")]
+    if let Some(log_path) = cfg.log.path.as_deref() {
+        use crate::base::logger::{self, LogConfig};
+
+        let level = match cfg.log.level.as_deref().unwrap_or("info").trim().parse() {
+            Ok(level) => level,
+            Err(_) => return RetCode::E_INVALID_DATA
+        };
+        if logger::set_log(LogConfig {
+            path: log_path.into(),
+            level,
+            rolling: cfg.log.rolling.unwrap_or_default() as u64 * 1024 * 1024
+        })
+        .is_err()
+        {
+            return RetCode::E_IO_ERROR;
+        }
+    }
+    config::set(cfg);
+    RetCode::OK
+}
+
+/// 生成`UUID`
+///
+/// # Parameters
+///
+/// - `version` 版本：`4`随机(默认)，`7`时间有序(适合作为数据库主键)
+/// - `simple` 是否省略连字符，默认`false`
+///
+/// # Returns
+///
+/// 小写格式的`UUID`，版本无效时返回空字符串
+#[global_function(name = "pfwxNewUUID", overload = 2)]
+fn new_uuid(version: Option<pblong>, simple: Option<bool>) -> String {
+    let uuid = match version.unwrap_or(4) {
+        4 => uuid::Uuid::new_v4(),
+        7 => uuid::Uuid::now_v7(),
+        _ => return "".to_owned()
+    };
+    if simple.unwrap_or_default() {
+        uuid.simple().to_string()
+    } else {
+        uuid.hyphenated().to_string()
+    }
+}
+
+/// 配置雪花算法ID生成器
+///
+/// # Parameters
+///
+/// - `node_id` 节点ID(`0`-`1023`)，多个进程同时生成ID时需要使用不同的节点ID
+/// - `epoch` 纪元(`UNIX`时间戳，毫秒)，默认`2020-01-01 00:00:00 UTC`
+#[global_function(name = "pfwxSetIdGenerator", overload = 1)]
+fn set_id_generator(node_id: pbulong, epoch: Option<pblonglong>) -> RetCode {
+    let epoch = match epoch {
+        Some(epoch) if epoch < 0 => return RetCode::E_INVALID_ARGUMENT,
+        Some(epoch) => epoch as u64,
+        None => crate::base::id::DEFAULT_EPOCH
+    };
+    if crate::base::id::configure(node_id as u64, epoch) {
+        RetCode::OK
+    } else {
+        RetCode::E_INVALID_ARGUMENT
+    }
+}
+
+/// 生成雪花算法ID
+///
+/// # Description
+///
+/// 64位整数，同一节点内单调递增，可用作关联ID或业务主键
+#[global_function(name = "pfwxNextId")]
+fn next_id() -> pblonglong { crate::base::id::next_id() as pblonglong }
+
+/// 生成进程内递增的序号
+///
+/// # Description
+///
+/// 从1开始，溢出后回绕并跳过0，适合作为`AsyncSend`等异步请求的ID
+#[global_function(name = "pfwxNextSequence")]
+fn next_sequence() -> pbulong { crate::base::id::next_sequence() as pbulong }
+
+/// 检测数据的编码
+///
+/// # Returns
+///
+/// 编码常量(`ENCODING_*`)，无法识别时返回`ENCODING_ANSI`
+///
+/// # Description
+///
+/// 优先根据`BOM`判断，其次根据内容推测`UTF-32`/`UTF-16`/`UTF-8`以及`GBK`/`Shift-JIS`/`EUC-KR`/`BIG5`
+#[cfg(feature = "encoding")]
+#[global_function(name = "pfwxDetectEncoding")]
+fn detect_encoding(data: &[u8]) -> pblong { crate::base::conv::detect_encoding(data) }
@@ -0,0 +1,405 @@
+use crate::prelude::*;
+use futures_util::future::join_all;
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use reqwest::{Client, Url};
+use std::{
+    collections::HashMap, io, process, sync::{Arc, Mutex}, time::Duration
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream, sync::Notify, time::{self, Instant, MissedTickBehavior}
+};
+
+/// 默认检测间隔
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+/// 默认检测超时
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// `MQTT`默认端口
+const MQTT_PORT: u16 = 1883;
+/// `MQTT`默认`TLS`端口
+const MQTT_TLS_PORT: u16 = 8883;
+
+/// 检测目标
+#[derive(Clone)]
+enum Target {
+    /// `HTTP`地址，`expected_status`为`0`时`2xx`和`3xx`视为正常
+    Http {
+        url: String,
+        expected_status: u16
+    },
+    /// `MQTT`服务器，`tls`为`true`时仅检测端口
+    Mqtt {
+        host: String,
+        port: u16,
+        tls: bool
+    },
+    /// `TCP`端口
+    Tcp {
+        host: String,
+        port: u16
+    }
+}
+
+/// 服务状态
+#[derive(Clone, Copy)]
+struct Status {
+    up: bool,
+    /// 检测耗时(毫秒)
+    latency: u64
+}
+
+struct HealthCheck {
+    state: HandlerState,
+    /// 与后台任务共享的检测目标
+    services: Arc<Mutex<Vec<(String, Target)>>>,
+    status: HashMap<String, Status>,
+    interval: Duration,
+    timeout: Duration,
+    /// 立即检测通知
+    notify: Arc<Notify>,
+    cancel_hdl: Option<CancelHandle>,
+    job_id: u64,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_healthcheck")]
+impl HealthCheck {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        HealthCheck {
+            state: HandlerState::new(session),
+            services: Arc::new(Mutex::new(Vec::new())),
+            status: HashMap::new(),
+            interval: DEFAULT_INTERVAL,
+            timeout: DEFAULT_TIMEOUT,
+            notify: Arc::new(Notify::new()),
+            cancel_hdl: None,
+            job_id: 0,
+            error: LastError::default()
+        }
+    }
+
+    /// 添加`HTTP`服务，名称已存在时替换
+    ///
+    /// # Parameters
+    ///
+    /// - `name` 服务名称，作为`OnStatusChanged`的参数
+    /// - `url` 检测地址，使用`GET`请求
+    /// - `expected_status` 期望的状态码，默认`0`表示`2xx`和`3xx`都视为正常
+    #[method(name = "AddHttp", overload = 1)]
+    fn add_http(&mut self, name: String, url: String, expected_status: Option<pbulong>) -> RetCode {
+        if let Err(e) = Url::parse(&url) {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid url: {e}"));
+        }
+        self.add(name, Target::Http {
+            url,
+            expected_status: expected_status.unwrap_or_default() as u16
+        })
+    }
+
+    /// 添加`MQTT`服务器，名称已存在时替换
+    ///
+    /// # Parameters
+    ///
+    /// - `name` 服务名称
+    /// - `url` 服务器地址，如`tcp://host:1883`、`host:1883`，`ssl://`和`mqtts://`仅检测端口是否可连接
+    ///
+    /// # Description
+    ///
+    /// 发送`CONNECT`并等待`CONNACK`，服务器拒绝连接(如认证失败)时同样视为正常
+    #[method(name = "AddMqtt")]
+    fn add_mqtt(&mut self, name: String, url: String) -> RetCode {
+        let url = if url.contains("://") {
+            url
+        } else {
+            format!("tcp://{url}")
+        };
+        let url = match Url::parse(&url) {
+            Ok(url) => url,
+            Err(e) => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid url: {e}"))
+        };
+        let tls = matches!(url.scheme(), "ssl" | "mqtts" | "wss");
+        let Some(host) = url.host_str() else {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("missing host: {url}"));
+        };
+        self.add(name, Target::Mqtt {
+            host: host.to_owned(),
+            port: url.port().unwrap_or(if tls {
+                MQTT_TLS_PORT
+            } else {
+                MQTT_PORT
+            }),
+            tls
+        })
+    }
+
+    /// 添加`TCP`端口，名称已存在时替换
+    #[method(name = "AddTcp")]
+    fn add_tcp(&mut self, name: String, host: String, port: pbulong) -> RetCode {
+        if host.is_empty() || port == 0 || port > u16::MAX as pbulong {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid address: {host}:{port}"));
+        }
+        self.add(name, Target::Tcp {
+            host,
+            port: port as u16
+        })
+    }
+
+    /// 删除服务
+    #[method(name = "Remove")]
+    fn remove(&mut self, name: String) -> RetCode {
+        let mut services = self.services.lock().unwrap();
+        let len = services.len();
+        services.retain(|(item, _)| *item != name);
+        if services.len() == len {
+            return self.error.set(RetCode::E_DATA_NOT_FOUND, format!("service not found: {name}"));
+        }
+        drop(services);
+        self.status.remove(&name);
+        RetCode::OK
+    }
+
+    /// 删除所有服务
+    #[method(name = "Clear")]
+    fn clear(&mut self) -> RetCode {
+        self.services.lock().unwrap().clear();
+        self.status.clear();
+        RetCode::OK
+    }
+
+    /// 设置检测间隔(毫秒)，默认`30000`，下次`Start`时生效
+    #[method(name = "SetInterval")]
+    fn set_interval(&mut self, interval: pbulong) -> RetCode {
+        if interval == 0 {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "interval must be greater than 0");
+        }
+        self.interval = Duration::from_millis(interval as u64);
+        RetCode::OK
+    }
+
+    /// 设置单次检测的超时(毫秒)，默认`5000`，下次`Start`时生效
+    #[method(name = "SetTimeout")]
+    fn set_timeout(&mut self, timeout: pbulong) -> RetCode {
+        if timeout == 0 {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "timeout must be greater than 0");
+        }
+        self.timeout = Duration::from_millis(timeout as u64);
+        RetCode::OK
+    }
+
+    /// 开始定期检测
+    ///
+    /// # Description
+    ///
+    /// 启动后立即检测一次，之后按`SetInterval`的间隔在后台并发检测所有服务，状态变化(包括首次检测)时触发`OnStatusChanged`
+    #[method(name = "Start")]
+    fn start(&mut self) -> RetCode {
+        self.stop();
+        let client = match Client::builder().timeout(self.timeout).build() {
+            Ok(client) => client,
+            Err(e) => return self.error.set(RetCode::FAILED, e)
+        };
+        self.job_id += 1;
+        let job_id = self.job_id;
+        let services = self.services.clone();
+        let notify = self.notify.clone();
+        let (interval, timeout) = (self.interval, self.timeout);
+        let invoker = self.invoker();
+        let cancel_hdl = self.spawn(
+            async move {
+                let mut ticker = time::interval(interval);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    tokio::select! {
+                        _ = ticker.tick() => {},
+                        _ = notify.notified() => {}
+                    }
+                    let targets = services.lock().unwrap().clone();
+                    let results = join_all(targets.into_iter().map(|(name, target)| {
+                        let client = client.clone();
+                        async move { (name, probe(&client, &target, timeout).await) }
+                    }))
+                    .await;
+                    let rv = invoker.invoke(results, |this, results| this.update(results)).await.await;
+                    if let Err(InvokeError::TargetIsDead) = rv {
+                        break;
+                    }
+                }
+            },
+            move |this, ()| {
+                if this.job_id == job_id {
+                    this.cancel_hdl = None;
+                }
+            }
+        );
+        self.cancel_hdl = Some(cancel_hdl);
+        RetCode::OK
+    }
+
+    /// 停止检测
+    #[method(name = "Stop")]
+    fn stop(&mut self) -> RetCode {
+        if let Some(cancel_hdl) = self.cancel_hdl.take() {
+            cancel_hdl.cancel();
+        }
+        RetCode::OK
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.cancel_hdl.is_some() }
+
+    /// 立即检测一次，需要先调用`Start`
+    #[method(name = "CheckNow")]
+    fn check_now(&mut self) -> RetCode {
+        if self.cancel_hdl.is_none() {
+            return self.error.set(RetCode::E_INVALID_OBJECT, "not started");
+        }
+        self.notify.notify_one();
+        RetCode::OK
+    }
+
+    /// 获取服务状态
+    ///
+    /// # Returns
+    ///
+    /// `1`正常，`0`异常，`-1`尚未检测或服务不存在
+    #[method(name = "GetStatus")]
+    fn get_status(&self, name: String) -> pblong {
+        match self.status.get(&name) {
+            Some(status) if status.up => 1,
+            Some(_) => 0,
+            None => -1
+        }
+    }
+
+    /// 获取最近一次检测的耗时(毫秒)，尚未检测时返回`-1`
+    #[method(name = "GetLatency")]
+    fn get_latency(&self, name: String) -> pblong {
+        self.status.get(&name).map(|status| status.latency as pblong).unwrap_or(-1)
+    }
+
+    /// 服务状态变化
+    ///
+    /// # Parameters
+    ///
+    /// - `service` 服务名称
+    /// - `up` 是否正常
+    /// - `latency` 检测耗时(毫秒)
+    #[event(name = "OnStatusChanged")]
+    fn on_status_changed(&mut self, service: String, up: bool, latency: pblong) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl HealthCheck {
+    fn add(&mut self, name: String, target: Target) -> RetCode {
+        if name.is_empty() {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "name is empty");
+        }
+        let mut services = self.services.lock().unwrap();
+        match services.iter_mut().find(|(item, _)| *item == name) {
+            Some((_, item)) => *item = target,
+            None => services.push((name.clone(), target))
+        }
+        drop(services);
+        //目标变化后重新触发状态通知
+        self.status.remove(&name);
+        RetCode::OK
+    }
+
+    /// 更新检测结果
+    fn update(&mut self, results: Vec<(String, Status)>) {
+        let alive = self.get_alive_state();
+        for (name, status) in results {
+            //检测期间被删除
+            if !self.services.lock().unwrap().iter().any(|(item, _)| *item == name) {
+                continue;
+            }
+            let changed = self.status.get(&name).map_or(true, |old| old.up != status.up);
+            self.status.insert(name.clone(), status);
+            if changed {
+                self.on_status_changed(name, status.up, status.latency as pblong);
+                //NOTE 对象可能在事件中被销毁
+                if alive.is_dead() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Handler for HealthCheck {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+/// 检测一个服务
+async fn probe(client: &Client, target: &Target, timeout: Duration) -> Status {
+    let inst = Instant::now();
+    let up = match target {
+        Target::Http {
+            url,
+            expected_status
+        } => {
+            match client.get(url).send().await {
+                Ok(resp) if *expected_status != 0 => resp.status().as_u16() == *expected_status,
+                Ok(resp) => resp.status().is_success() || resp.status().is_redirection(),
+                Err(_) => false
+            }
+        },
+        Target::Mqtt {
+            host,
+            port,
+            tls
+        } => {
+            time::timeout(timeout, async {
+                let mut stream = TcpStream::connect((host.as_str(), *port)).await?;
+                if !tls {
+                    mqtt_handshake(&mut stream).await?;
+                }
+                io::Result::Ok(())
+            })
+            .await
+            .map_or(false, |rv| rv.is_ok())
+        },
+        Target::Tcp {
+            host,
+            port
+        } => {
+            time::timeout(timeout, TcpStream::connect((host.as_str(), *port)))
+                .await
+                .map_or(false, |rv| rv.is_ok())
+        },
+    };
+    Status {
+        up,
+        latency: inst.elapsed().as_millis() as u64
+    }
+}
+
+/// 发送`MQTT 3.1.1`的`CONNECT`并等待`CONNACK`
+async fn mqtt_handshake(stream: &mut TcpStream) -> io::Result<()> {
+    let client_id = format!("pfwx-health-{}", process::id());
+    let mut packet = vec![0x10, (12 + client_id.len()) as u8];
+    //协议名、协议级别(4)、连接标志(清除会话)、保活时间(60秒)
+    packet.extend_from_slice(&[0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04, 0x02, 0x00, 0x3c]);
+    packet.extend_from_slice(&(client_id.len() as u16).to_be_bytes());
+    packet.extend_from_slice(client_id.as_bytes());
+    stream.write_all(&packet).await?;
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack).await?;
+    if connack[0] != 0x20 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid CONNACK"));
+    }
+    //连接被接受时断开
+    if connack[3] == 0 {
+        let _ = stream.write_all(&[0xe0, 0x00]).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use super::*;
+use crate::reactor::HandlerInvoker;
+use std::sync::RwLock;
+use tokio::sync::Mutex;
+
+/// 客户端的认证令牌
+///
+/// 由所有请求共享，令牌失效时串行化刷新，并发的请求只触发一次`OnAuthRequired`
+#[derive(Default)]
+pub struct AuthToken {
+    /// (版本, 令牌)
+    token: RwLock<(u64, Option<String>)>,
+    refresh: Mutex<()>
+}
+
+impl AuthToken {
+    /// 当前的版本与令牌
+    pub fn get(&self) -> (u64, Option<String>) { self.token.read().unwrap().clone() }
+
+    /// 设置令牌，`None`表示清除
+    pub fn set(&self, token: Option<String>) {
+        let mut guard = self.token.write().unwrap();
+        guard.0 += 1;
+        guard.1 = token;
+    }
+
+    pub fn is_set(&self) -> bool { self.token.read().unwrap().1.is_some() }
+
+    /// 认证失败后刷新令牌
+    ///
+    /// # Parameters
+    ///
+    /// - `version` 发送请求时使用的令牌版本
+    ///
+    /// # Returns
+    ///
+    /// 刷新后的令牌，未刷新时返回`None`
+    ///
+    /// # Description
+    ///
+    /// 令牌已被其它请求刷新时直接返回新令牌，否则触发`OnAuthRequired`，处理程序返回`1`且设置了新令牌时视为刷新成功
+    pub async fn refresh(
+        &self,
+        version: u64,
+        id: pbulong,
+        invoker: &HandlerInvoker<HttpClient>
+    ) -> Option<String> {
+        let _guard = self.refresh.lock().await;
+        let (current, token) = self.get();
+        if current != version {
+            return token;
+        }
+        match invoker.invoke(id, |this, id| this.on_auth_required(id)).await.await {
+            Ok(RetCode::PREVENT) => {
+                let (current, token) = self.get();
+                if current != version {
+                    token
+                } else {
+                    None
+                }
+            },
+            _ => None
+        }
+    }
+}
+
+/// 客户端的代理凭据
+///
+/// 由代理在每次连接时读取，凭据失效时串行化询问，并发的请求只触发一次`OnProxyAuthRequired`
+#[derive(Default)]
+pub struct ProxyCredential {
+    /// (版本, (用户名, 密码))
+    cred: RwLock<(u64, Option<(String, String)>)>,
+    prompt: Mutex<()>
+}
+
+impl ProxyCredential {
+    /// 当前的版本与凭据
+    pub fn get(&self) -> (u64, Option<(String, String)>) { self.cred.read().unwrap().clone() }
+
+    /// 设置凭据，优先于`nx_httpconfig`配置的代理认证
+    pub fn set(&self, user: String, psw: String) {
+        let mut guard = self.cred.write().unwrap();
+        guard.0 += 1;
+        guard.1 = Some((user, psw));
+    }
+
+    /// 代理认证失败后询问凭据
+    ///
+    /// # Parameters
+    ///
+    /// - `version` 发送请求时使用的凭据版本
+    /// - `realm` 代理返回的认证域
+    ///
+    /// # Returns
+    ///
+    /// 凭据是否已更新
+    ///
+    /// # Description
+    ///
+    /// 凭据已被其它请求更新时直接返回，否则触发`OnProxyAuthRequired`，处理程序返回`1`且设置了新凭据时视为更新成功
+    pub async fn prompt(
+        &self,
+        version: u64,
+        id: pbulong,
+        realm: String,
+        invoker: &HandlerInvoker<HttpClient>
+    ) -> bool {
+        let _guard = self.prompt.lock().await;
+        if self.get().0 != version {
+            return true;
+        }
+        match invoker.invoke(realm, move |this, realm| this.on_proxy_auth_required(id, realm)).await.await {
+            Ok(RetCode::PREVENT) => self.get().0 != version,
+            _ => false
+        }
+    }
+}
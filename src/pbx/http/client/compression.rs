@@ -0,0 +1,266 @@
+//! 请求体压缩与响应数据透明解压
+//!
+//! 客户端关闭了`reqwest`内置的解压，由此处按`nx_httpconfig.SetCompression`声明`Accept-Encoding`并解压，
+//! 以便响应记录原始的`Content-Encoding`
+
+use bytes::Bytes;
+use futures_util::Stream;
+use http_body::Body as HttpBody;
+use reqwest::{
+    header::{self, HeaderValue}, Body, Response, StatusCode
+};
+use std::{
+    io::{self, Write}, mem, pin::Pin, task::{ready, Context, Poll}
+};
+
+/// 压缩格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    Brotli,
+    /// 带`zlib`头的`DEFLATE`流(`RFC 1950`)
+    Deflate
+}
+
+impl ContentEncoding {
+    /// 解析压缩格式名称(不区分大小写)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(ContentEncoding::Gzip),
+            "br" | "brotli" => Some(ContentEncoding::Brotli),
+            "deflate" => Some(ContentEncoding::Deflate),
+            _ => None
+        }
+    }
+
+    /// `Content-Encoding`的取值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Deflate => "deflate"
+        }
+    }
+
+    /// 压缩数据
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            ContentEncoding::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            },
+            ContentEncoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 9, 22);
+                encoder.write_all(data)?;
+                Ok(encoder.into_inner())
+            },
+            ContentEncoding::Deflate => {
+                let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// 透明解压的压缩格式
+#[derive(Debug, Clone, Copy)]
+pub struct Decompression {
+    pub gzip: bool,
+    pub brotli: bool,
+    pub deflate: bool
+}
+
+impl Default for Decompression {
+    fn default() -> Self {
+        Decompression {
+            gzip: true,
+            brotli: true,
+            deflate: true
+        }
+    }
+}
+
+impl Decompression {
+    /// 设置是否解压指定格式
+    pub fn set(&mut self, encoding: ContentEncoding, enabled: bool) {
+        match encoding {
+            ContentEncoding::Gzip => self.gzip = enabled,
+            ContentEncoding::Brotli => self.brotli = enabled,
+            ContentEncoding::Deflate => self.deflate = enabled
+        }
+    }
+
+    /// 是否解压指定格式
+    pub fn accepts(&self, encoding: ContentEncoding) -> bool {
+        match encoding {
+            ContentEncoding::Gzip => self.gzip,
+            ContentEncoding::Brotli => self.brotli,
+            ContentEncoding::Deflate => self.deflate
+        }
+    }
+
+    /// `Accept-Encoding`请求头，均未启用时返回`None`
+    pub fn accept_encoding(&self) -> Option<HeaderValue> {
+        let value = [ContentEncoding::Gzip, ContentEncoding::Brotli, ContentEncoding::Deflate]
+            .into_iter()
+            .filter(|encoding| self.accepts(*encoding))
+            .map(|encoding| encoding.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if value.is_empty() {
+            None
+        } else {
+            Some(HeaderValue::from_str(&value).expect("invalid accept encoding"))
+        }
+    }
+
+    /// 按响应的`Content-Encoding`解压
+    ///
+    /// # Returns
+    ///
+    /// 解压后的响应(移除`Content-Encoding`与`Content-Length`)以及原始的压缩格式，无需解压时原样返回
+    ///
+    /// # Notice
+    ///
+    /// 没有响应体(`HEAD`请求、`204`/`304`或`Content-Length: 0`)时不解压，`HEAD`请求的响应体长度为0
+    pub fn decompress(&self, resp: Response) -> (Response, Option<ContentEncoding>) {
+        let status = resp.status();
+        if status.is_informational() ||
+            status == StatusCode::NO_CONTENT ||
+            status == StatusCode::NOT_MODIFIED ||
+            resp.content_length() == Some(0)
+        {
+            return (resp, None);
+        }
+        let encoding = resp
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .and_then(ContentEncoding::parse)
+            .filter(|encoding| self.accepts(*encoding));
+        let Some(encoding) = encoding else {
+            return (resp, None);
+        };
+        let mut resp = http::Response::from(resp);
+        resp.headers_mut().remove(header::CONTENT_ENCODING);
+        resp.headers_mut().remove(header::CONTENT_LENGTH);
+        let resp = resp.map(|body| Body::wrap_stream(DecodeStream::new(body, encoding)));
+        (Response::from(resp), Some(encoding))
+    }
+}
+
+/// 增量解压器
+enum Decoder {
+    Gzip(flate2::write::GzDecoder<Vec<u8>>),
+    Brotli(Box<brotli::DecompressorWriter<Vec<u8>>>),
+    Deflate(flate2::write::ZlibDecoder<Vec<u8>>)
+}
+
+impl Decoder {
+    fn new(encoding: ContentEncoding) -> Self {
+        match encoding {
+            ContentEncoding::Gzip => Decoder::Gzip(flate2::write::GzDecoder::new(Vec::new())),
+            ContentEncoding::Brotli => {
+                Decoder::Brotli(Box::new(brotli::DecompressorWriter::new(Vec::new(), 4096)))
+            },
+            ContentEncoding::Deflate => Decoder::Deflate(flate2::write::ZlibDecoder::new(Vec::new()))
+        }
+    }
+
+    /// 写入压缩数据，返回已解压的数据
+    fn write(&mut self, data: &[u8]) -> io::Result<Bytes> {
+        let out = match self {
+            Decoder::Gzip(decoder) => {
+                decoder.write_all(data)?;
+                decoder.get_mut()
+            },
+            Decoder::Brotli(decoder) => {
+                decoder.write_all(data)?;
+                decoder.get_mut()
+            },
+            Decoder::Deflate(decoder) => {
+                decoder.write_all(data)?;
+                decoder.get_mut()
+            }
+        };
+        Ok(mem::take(out).into())
+    }
+
+    /// 结束解压，返回剩余的数据
+    fn finish(self) -> io::Result<Bytes> {
+        let out = match self {
+            Decoder::Gzip(decoder) => decoder.finish()?,
+            Decoder::Brotli(decoder) => {
+                decoder
+                    .into_inner()
+                    .map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "incomplete brotli stream"))?
+            },
+            Decoder::Deflate(decoder) => decoder.finish()?
+        };
+        Ok(out.into())
+    }
+}
+
+/// 解压响应数据流
+struct DecodeStream {
+    body: Body,
+    /// 结束或失败后为`None`
+    decoder: Option<Decoder>,
+    /// 是否收到过数据
+    received: bool
+}
+
+impl DecodeStream {
+    fn new(body: Body, encoding: ContentEncoding) -> Self {
+        DecodeStream {
+            body,
+            decoder: Some(Decoder::new(encoding)),
+            received: false
+        }
+    }
+}
+
+impl Stream for DecodeStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            let Some(decoder) = this.decoder.as_mut() else {
+                return Poll::Ready(None);
+            };
+            let rv = match ready!(HttpBody::poll_frame(Pin::new(&mut this.body), cx)) {
+                Some(Ok(frame)) => {
+                    //忽略`trailers`
+                    let Ok(data) = frame.into_data() else {
+                        continue;
+                    };
+                    if data.is_empty() {
+                        continue;
+                    }
+                    this.received = true;
+                    decoder.write(&data)
+                },
+                Some(Err(e)) => Err(io::Error::new(io::ErrorKind::Other, e)),
+                //空响应体
+                None if !this.received => {
+                    this.decoder = None;
+                    return Poll::Ready(None);
+                },
+                None => this.decoder.take().unwrap().finish()
+            };
+            match rv {
+                //数据不足以解压出内容
+                Ok(data) if data.is_empty() && this.decoder.is_some() => continue,
+                Ok(data) if data.is_empty() => return Poll::Ready(None),
+                Ok(data) => return Poll::Ready(Some(Ok(data))),
+                Err(e) => {
+                    this.decoder = None;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+    }
+}
@@ -1,68 +1,362 @@
-use super::{cookie::HttpCookie, *};
+use super::{
+    auth::ProxyCredential, compression::{ContentEncoding, Decompression}, cookie::HttpCookie, signer::HmacSigning, sspi::SspiPackage, *
+};
+use crate::base::{cert::StoreCert, config, secret};
 use reqwest::{
-    header::{HeaderMap, HeaderName, HeaderValue}, Certificate, ClientBuilder, Identity, Proxy
+    header::{self, HeaderMap, HeaderName, HeaderValue}, Certificate, ClientBuilder, Identity, Proxy, Url
 };
-use std::time::Duration;
+use std::{error::Error, io, mem, time::Duration};
 
 pub struct HttpClientConfigEx {
     /// 异步请求-最大并发数
-    pub max_concurrency: usize
+    pub max_concurrency: usize,
+    /// 请求时从凭据管理器读取`Basic`认证的凭据名称
+    pub credential_target: Option<String>,
+    /// 失败重试策略
+    pub retry: RetryPolicy,
+    /// 接收缓冲策略
+    pub receive: ReceivePolicy,
+    /// 响应信封
+    pub envelope: Option<Arc<EnvelopePolicy>>,
+    /// 默认请求头，请求可通过`ClearDefaultHeaders`忽略
+    pub default_headers: HeaderMap,
+    /// 代理凭据，未设置代理时为`None`
+    pub proxy_credential: Option<Arc<ProxyCredential>>,
+    /// 系统代理，未调用`UseSystemProxy`时为`None`
+    pub system_proxy: Option<Arc<SystemProxy>>,
+    /// 透明解压的压缩格式
+    pub decompression: Decompression,
+    /// `Windows`集成认证的认证包
+    pub windows_auth: Option<SspiPackage>,
+    /// `HMAC`签名
+    pub hmac_signing: Option<Arc<HmacSigning>>
 }
 
 impl Default for HttpClientConfigEx {
     fn default() -> Self {
         HttpClientConfigEx {
-            max_concurrency: default::MAX_CONCURRENCY
+            max_concurrency: default::MAX_CONCURRENCY,
+            credential_target: None,
+            retry: RetryPolicy::default(),
+            receive: ReceivePolicy::default(),
+            envelope: None,
+            proxy_credential: None,
+            system_proxy: None,
+            default_headers: HeaderMap::new(),
+            decompression: Decompression::default(),
+            windows_auth: None,
+            hmac_signing: None
+        }
+    }
+}
+
+/// 失败重试策略
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// 最大重试次数，`0`表示不重试
+    pub max_retries: u32,
+    /// 首次重试的等待时间，之后每次加倍
+    pub delay: Duration
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            delay: default::RETRY_DELAY
+        }
+    }
+}
+
+/// 接收缓冲策略
+#[derive(Debug, Clone, Copy)]
+pub struct ReceivePolicy {
+    /// 按`Content-Length`预分配的最大字节数
+    pub max_prealloc: usize,
+    /// 缓冲区不足时的扩容倍数
+    pub growth: f64,
+    /// 接收的数据超过此字节数时转存到临时文件，`None`表示不转存
+    pub spill_threshold: Option<u64>
+}
+
+impl Default for ReceivePolicy {
+    fn default() -> Self {
+        ReceivePolicy {
+            max_prealloc: default::RECEIVE_MAX_PREALLOC,
+            growth: default::RECEIVE_GROWTH,
+            spill_threshold: None
         }
     }
 }
 
+/// 响应信封(如`{code,msg,data}`)
+///
+/// 路径以`.`分隔，如`result.data`
+#[derive(Debug, Clone)]
+pub struct EnvelopePolicy {
+    /// 数据节点路径，空表示整个响应
+    pub data_path: Vec<String>,
+    /// 状态码节点路径
+    pub code_path: Vec<String>,
+    /// 错误信息节点路径，空表示不读取
+    pub message_path: Vec<String>
+}
+
+impl EnvelopePolicy {
+    fn new(data_path: &str, code_path: &str, message_path: &str) -> Self {
+        let split = |path: &str| -> Vec<String> {
+            path.split('.').filter(|key| !key.is_empty()).map(str::to_owned).collect()
+        };
+        EnvelopePolicy {
+            data_path: split(data_path),
+            code_path: split(code_path),
+            message_path: split(message_path)
+        }
+    }
+}
+
+/// 代理配置
+struct ProxyConfig {
+    target: ProxyTarget,
+    auth: ProxyAuth
+}
+
+/// 代理地址
+enum ProxyTarget {
+    Url(Url),
+    /// 系统代理设置(`UseSystemProxy`)
+    System
+}
+
+/// 代理认证方式
+enum ProxyAuth {
+    None,
+    Basic(String, String),
+    /// 请求时从凭据管理器读取
+    Vault(String)
+}
+
+impl ProxyConfig {
+    /// 创建代理
+    ///
+    /// 每次连接时读取凭据，`credential`设置了凭据时优先使用
+    ///
+    /// # Returns
+    ///
+    /// 代理以及使用系统代理设置时的`SystemProxy`(发送前预先计算代理)
+    fn build(self, credential: Arc<ProxyCredential>) -> (Proxy, Option<Arc<SystemProxy>>) {
+        let ProxyConfig {
+            target,
+            auth
+        } = self;
+        let system = match target {
+            ProxyTarget::Url(_) => None,
+            ProxyTarget::System => Some(Arc::new(SystemProxy::new()))
+        };
+        let resolver = system.clone();
+        let proxy = Proxy::custom(move |dest| {
+            let mut url = match (&target, &resolver) {
+                (ProxyTarget::Url(url), _) => url.clone(),
+                //忽略无效的代理地址
+                (ProxyTarget::System, Some(system)) => {
+                    system.resolve(dest).as_deref().and_then(parse_proxy_url)?
+                },
+                (ProxyTarget::System, None) => return None
+            };
+            let cred = match (credential.get().1, &auth) {
+                (Some(cred), _) => Some(cred),
+                (None, ProxyAuth::None) => None,
+                (None, ProxyAuth::Basic(user, psw)) => Some((user.clone(), psw.clone())),
+                //凭据不存在时不认证
+                (None, ProxyAuth::Vault(target)) => {
+                    match secret::read_credential(target) {
+                        Ok(Some(cred)) => Some((cred.user_name.clone(), cred.secret_string())),
+                        _ => None
+                    }
+                },
+            };
+            if let Some((user, psw)) = cred {
+                let _ = url.set_username(&user);
+                let _ = url.set_password(Some(&psw));
+            }
+            Some(url)
+        });
+        (proxy, system)
+    }
+}
+
+/// 解析代理地址，没有协议时视为`http`
+fn parse_proxy_url(url: &str) -> Option<Url> {
+    match Url::parse(url) {
+        Ok(url) if url.has_host() => Some(url),
+        _ => Url::parse(&format!("http://{url}")).ok()
+    }
+}
+
 pub struct HttpClientConfig {
     builder: Option<ClientBuilder>,
-    cfg: Option<HttpClientConfigEx>
+    cfg: Option<HttpClientConfigEx>,
+    proxy: Option<ProxyConfig>,
+    tls: TlsOptions,
+    error: LastError
+}
+
+/// `TLS`选项
+///
+/// # Description
+///
+/// 使用系统证书存储中的客户端证书时需要自行创建`TLS`连接器(`reqwest`不支持该证书类型)，此时`ClientBuilder`中的`TLS`选项不生效，
+/// 因此同时记录一份
+#[derive(Default)]
+struct TlsOptions {
+    root_certs: Vec<native_tls::Certificate>,
+    disable_built_in_roots: bool,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+    /// 系统证书存储中的客户端证书
+    store_identity: Option<native_tls::Identity>
+}
+
+impl TlsOptions {
+    /// 创建`TLS`连接器
+    fn build_connector(self, identity: native_tls::Identity) -> native_tls::Result<native_tls::TlsConnector> {
+        let mut builder = native_tls::TlsConnector::builder();
+        for cert in self.root_certs {
+            builder.add_root_certificate(cert);
+        }
+        builder
+            .disable_built_in_roots(self.disable_built_in_roots)
+            .danger_accept_invalid_certs(self.accept_invalid_certs)
+            .danger_accept_invalid_hostnames(self.accept_invalid_hostnames)
+            .identity(identity)
+            .build()
+    }
 }
 
 impl Default for HttpClientConfig {
     fn default() -> Self {
-        HttpClientConfig {
+        let mut cfg = HttpClientConfig {
             builder: Some(HttpClientConfig::default_builder()),
-            cfg: Some(HttpClientConfigEx::default())
+            cfg: Some(HttpClientConfigEx::default()),
+            proxy: None,
+            tls: TlsOptions::default(),
+            error: LastError::default()
+        };
+        cfg.apply_defaults(&config::current().http);
+        cfg
+    }
+}
+
+impl HttpClientConfig {
+    /// 应用全局默认配置(`pfwxLoadConfig`)
+    fn apply_defaults(&mut self, defaults: &config::HttpDefaults) {
+        let mut builder = self.builder.take().unwrap();
+        if let Some(timeout) = defaults.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = defaults.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(agent) =
+            defaults.user_agent.as_deref().and_then(|agent| HeaderValue::from_str(agent).ok())
+        {
+            self.cfg.as_mut().unwrap().default_headers.insert(header::USER_AGENT, agent);
+        }
+        if let Some(enabled) = defaults.https_only {
+            builder = builder.https_only(enabled);
+        }
+        if let Some(enabled) = defaults.accept_invalid_cert {
+            builder = builder.danger_accept_invalid_certs(enabled);
+            self.tls.accept_invalid_certs = enabled;
+        }
+        if let Some(enabled) = defaults.accept_invalid_host {
+            builder = builder.danger_accept_invalid_hostnames(enabled);
+            self.tls.accept_invalid_hostnames = enabled;
+        }
+        if let Some(enabled) = defaults.sys_root_certificate {
+            builder = builder.tls_built_in_root_certs(enabled);
+            self.tls.disable_built_in_roots = !enabled;
+        }
+        self.builder.replace(builder);
+        //忽略无效的代理地址
+        if let Some(url) = defaults.proxy.as_deref().and_then(parse_proxy_url) {
+            self.proxy = Some(ProxyConfig {
+                target: ProxyTarget::Url(url),
+                auth: match defaults.proxy_user.clone() {
+                    Some(user) => ProxyAuth::Basic(user, defaults.proxy_password.clone().unwrap_or_default()),
+                    None => ProxyAuth::None
+                }
+            });
+        }
+        let rt_cfg = self.cfg.as_mut().unwrap();
+        if let Some(max_concurrency) = defaults.concurrency {
+            rt_cfg.max_concurrency = max_concurrency.max(1) as usize;
+        }
+        if let Some(max_retries) = defaults.retry {
+            rt_cfg.retry.max_retries = max_retries;
+        }
+        if let Some(delay) = defaults.retry_delay {
+            rt_cfg.retry.delay = delay;
         }
     }
 }
 
 #[nonvisualobject(name = "nx_httpconfig")]
 impl HttpClientConfig {
-    fn default_builder() -> ClientBuilder { ClientBuilder::default().use_native_tls() }
+    fn default_builder() -> ClientBuilder {
+        //由`Decompression`解压
+        ClientBuilder::default().use_native_tls().tls_info(true).gzip(false).brotli(false).deflate(false)
+    }
 
     /// 创建`reqwest::Client`
     ///
     /// # Notice
     ///
     /// 仅能调用一次
-    pub fn build(&mut self) -> reqwest::Result<(Client, HttpClientConfigEx)> {
-        let builder = self.builder.replace(Self::default_builder()).unwrap();
-        let rt_cfg = self.cfg.replace(HttpClientConfigEx::default()).unwrap();
+    pub fn build(&mut self) -> Result<(Client, HttpClientConfigEx), Box<dyn Error + Send + Sync>> {
+        let mut builder = self.builder.replace(Self::default_builder()).unwrap();
+        let mut rt_cfg = self.cfg.replace(HttpClientConfigEx::default()).unwrap();
+        let mut tls = mem::take(&mut self.tls);
+        if let Some(identity) = tls.store_identity.take() {
+            builder = builder.use_preconfigured_tls(tls.build_connector(identity)?);
+        }
+        if let Some(proxy) = self.proxy.take() {
+            let credential = Arc::new(ProxyCredential::default());
+            let (proxy, system) = proxy.build(credential.clone());
+            builder = builder.proxy(proxy);
+            rt_cfg.proxy_credential = Some(credential);
+            rt_cfg.system_proxy = system;
+        }
         let client = builder.build()?;
         Ok((client, rt_cfg))
     }
 
     #[method(name = "SetAgent")]
     fn agent(&mut self, val: String) -> &mut Self {
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(builder.user_agent(val));
+        match HeaderValue::from_str(&val) {
+            Ok(value) => {
+                self.cfg.as_mut().unwrap().default_headers.insert(header::USER_AGENT, value);
+            },
+            Err(_) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid agent: {val}"));
+            }
+        }
         self
     }
 
+    /// 设置默认请求头
+    ///
+    /// 请求没有设置同名请求头时附加，请求调用`ClearDefaultHeaders`时不附加(包括`SetAgent`)
     #[method(name = "SetDefaultHeader")]
     fn default_header(&mut self, key: String, val: String) -> &mut Self {
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            HeaderName::from_str(&key).expect("invalid header key"),
-            HeaderValue::from_str(&val).expect("invalid header value")
-        );
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(builder.default_headers(headers));
+        let (name, value) = match (HeaderName::from_str(&key), HeaderValue::from_str(&val)) {
+            (Ok(name), Ok(value)) => (name, value),
+            _ => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid header: {key}"));
+                return self;
+            }
+        };
+        self.cfg.as_mut().unwrap().default_headers.insert(name, value);
         self
     }
 
@@ -82,27 +376,83 @@ impl HttpClientConfig {
 
     #[method(name = "SetProxy")]
     fn proxy(&mut self, url: String) -> &mut Self {
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(builder.proxy(Proxy::all(url).expect("invalid proxy url")));
+        self.set_proxy(&url, ProxyAuth::None);
         self
     }
 
     #[method(name = "SetProxy")]
     fn proxy_with_cred(&mut self, url: String, user: String, psw: String) -> &mut Self {
-        let builder = self.builder.take().unwrap();
-        self.builder
-            .replace(builder.proxy(Proxy::all(url).expect("invalid proxy url").basic_auth(&user, &psw)));
+        self.set_proxy(&url, ProxyAuth::Basic(user, psw));
+        self
+    }
+
+    /// 使用系统代理设置
+    ///
+    /// # Description
+    ///
+    /// 读取当前用户的`Internet`选项代理设置，启用自动检测设置或自动配置脚本时通过`WinHTTP`计算每个地址的代理，
+    /// 失败时使用固定代理，系统设置变化后自动重新读取，覆盖`SetProxy`
+    #[method(name = "UseSystemProxy")]
+    fn use_system_proxy(&mut self) -> &mut Self {
+        self.proxy = Some(ProxyConfig {
+            target: ProxyTarget::System,
+            auth: ProxyAuth::None
+        });
+        self
+    }
+
+    /// 代理认证使用`Windows`凭据管理器中的普通凭据
+    ///
+    /// # Parameters
+    ///
+    /// - `target_name` 凭据名称(`Internet地址或网络地址`)
+    ///
+    /// # Description
+    ///
+    /// 需要先调用`SetProxy`或`UseSystemProxy`设置代理，每次请求时读取凭据，修改凭据后无需重新配置
+    #[method(name = "SetProxyCredentialFromVault")]
+    fn proxy_credential_from_vault(&mut self, target_name: String) -> &mut Self {
+        if let Some(proxy) = self.proxy.as_mut() {
+            proxy.auth = ProxyAuth::Vault(target_name);
+        }
+        self
+    }
+
+    /// 请求的`Basic`认证使用`Windows`凭据管理器中的普通凭据
+    ///
+    /// # Parameters
+    ///
+    /// - `target_name` 凭据名称(`Internet地址或网络地址`)，空字符串表示取消
+    ///
+    /// # Description
+    ///
+    /// 每次发送请求时读取凭据，请求已调用`SetBasicAuth`、`SetBearerAuth`或设置了`Authorization`头时不生效
+    #[method(name = "SetCredentialFromVault")]
+    fn credential_from_vault(&mut self, target_name: String) -> &mut Self {
+        let mut rt_cfg = self.cfg.take().unwrap();
+        rt_cfg.credential_target = if target_name.is_empty() {
+            None
+        } else {
+            Some(target_name)
+        };
+        self.cfg.replace(rt_cfg);
         self
     }
 
     #[method(name = "AddRootCertificate")]
     fn add_root_certificate(&mut self, pem: String) -> &mut Self {
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(
-            builder.add_root_certificate(
-                Certificate::from_pem(pem.as_bytes()).expect("invalid root certificate")
-            )
-        );
+        match Certificate::from_pem(pem.as_bytes()) {
+            Ok(cert) => {
+                let builder = self.builder.take().unwrap();
+                self.builder.replace(builder.add_root_certificate(cert));
+                if let Ok(cert) = native_tls::Certificate::from_pem(pem.as_bytes()) {
+                    self.tls.root_certs.push(cert);
+                }
+            },
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_DATA, e);
+            }
+        }
         self
     }
 
@@ -110,26 +460,45 @@ impl HttpClientConfig {
     fn sys_root_certificate(&mut self, enabled: bool) -> &mut Self {
         let builder = self.builder.take().unwrap();
         self.builder.replace(builder.tls_built_in_root_certs(enabled));
+        self.tls.disable_built_in_roots = !enabled;
         self
     }
 
     #[method(name = "SetCertificate")]
     fn certificate_pkcs8(&mut self, pem: String, key: String) -> &mut Self {
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(builder.identity(
-            Identity::from_pkcs8_pem(pem.as_bytes(), key.as_bytes()).expect("invalid certificate (PKCS8)")
-        ));
+        self.set_identity(Identity::from_pkcs8_pem(pem.as_bytes(), key.as_bytes()));
         self
     }
 
     #[method(name = "SetCertificatePKCS12")]
     fn certificate_pkcs12(&mut self, der: &[u8], psw: String) -> &mut Self {
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(
-            builder.identity(
-                Identity::from_pkcs12_der(der, psw.as_str()).expect("invalid certificate (PKCS12)")
-            )
-        );
+        self.set_identity(Identity::from_pkcs12_der(der, psw.as_str()));
+        self
+    }
+
+    /// 使用`Windows`证书存储中的客户端证书
+    ///
+    /// # Parameters
+    ///
+    /// - `store_name` 存储名称，默认为当前用户的存储，`LocalMachine\`前缀表示本地计算机的存储，如`My`、`LocalMachine\My`
+    /// - `thumbprint` 证书的`SHA-1`指纹(十六进制)
+    ///
+    /// # Description
+    ///
+    /// 握手时直接使用证书关联的私钥，私钥不需要允许导出
+    #[method(name = "SetCertificateFromStore")]
+    fn certificate_from_store(&mut self, store_name: String, thumbprint: String) -> &mut Self {
+        match StoreCert::find(&store_name, &thumbprint) {
+            Ok(cert) => self.tls.store_identity = Some(cert.into_identity()),
+            Err(e) => {
+                let code = match e.kind() {
+                    io::ErrorKind::InvalidInput => RetCode::E_INVALID_ARGUMENT,
+                    io::ErrorKind::NotFound => RetCode::E_DATA_NOT_FOUND,
+                    _ => RetCode::E_WIN32_ERROR
+                };
+                self.error.set(code, e);
+            }
+        }
         self
     }
 
@@ -137,6 +506,7 @@ impl HttpClientConfig {
     fn accept_invalid_certs(&mut self, enabled: bool) -> &mut Self {
         let builder = self.builder.take().unwrap();
         self.builder.replace(builder.danger_accept_invalid_certs(enabled));
+        self.tls.accept_invalid_certs = enabled;
         self
     }
 
@@ -144,20 +514,35 @@ impl HttpClientConfig {
     fn accept_invalid_hostnames(&mut self, enabled: bool) -> &mut Self {
         let builder = self.builder.take().unwrap();
         self.builder.replace(builder.danger_accept_invalid_hostnames(enabled));
+        self.tls.accept_invalid_hostnames = enabled;
         self
     }
 
     #[method(name = "SetTimeout")]
     fn timeout(&mut self, secs: pbdouble) -> &mut Self {
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(builder.timeout(Duration::from_secs_f64(secs)));
+        match Duration::try_from_secs_f64(secs) {
+            Ok(timeout) => {
+                let builder = self.builder.take().unwrap();
+                self.builder.replace(builder.timeout(timeout));
+            },
+            Err(_) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid timeout: {secs}"));
+            }
+        }
         self
     }
 
     #[method(name = "SetConnectTimeout")]
     fn connect_timeout(&mut self, secs: pbdouble) -> &mut Self {
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(builder.connect_timeout(Duration::from_secs_f64(secs)));
+        match Duration::try_from_secs_f64(secs) {
+            Ok(timeout) => {
+                let builder = self.builder.take().unwrap();
+                self.builder.replace(builder.connect_timeout(timeout));
+            },
+            Err(_) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid timeout: {secs}"));
+            }
+        }
         self
     }
 
@@ -168,6 +553,289 @@ impl HttpClientConfig {
         self
     }
 
+    /// 启用`Windows`集成认证
+    ///
+    /// # Parameters
+    ///
+    /// - `package` 认证包：`Negotiate`(默认，优先`Kerberos`)、`NTLM`，空字符串表示禁用
+    ///
+    /// # Description
+    ///
+    /// 服务器返回`401`并要求此认证方案时，通过`SSPI`使用当前登录用户的凭据完成质询握手后重发请求，
+    /// 请求可通过`nx_httprequest.SetWindowsAuth`单独设置，请求体为流时不认证
+    #[method(name = "SetWindowsAuth", overload = 1)]
+    fn windows_auth(&mut self, package: Option<String>) -> &mut Self {
+        let package = match package.as_deref().map(str::trim) {
+            Some("") => None,
+            Some(name) => {
+                match SspiPackage::parse(name) {
+                    Some(package) => Some(package),
+                    None => {
+                        self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid auth package: {name}"));
+                        return self;
+                    }
+                }
+            },
+            None => Some(SspiPackage::Negotiate)
+        };
+        self.cfg.as_mut().unwrap().windows_auth = package;
+        self
+    }
+
+    /// 设置`HMAC`签名
+    ///
+    /// # Parameters
+    ///
+    /// - `algorithm` `SHA256`或`SHA512`
+    /// - `key` 密钥
+    /// - `fields` 待签名的字段，以`,`分隔：`method`、`path`(路径与查询参数)、`url`、`timestamp`、`nonce`、`body`以及`header:<名称>`
+    /// - `header` 签名的请求头名称，默认`X-Signature`
+    /// - `template` 请求头的值模板，默认`{signature}`
+    /// - `separator` 字段之间的分隔符，默认为空
+    ///
+    /// # Description
+    ///
+    /// 发送前按`fields`的顺序拼接字段计算`HMAC`，按`template`生成`header`的值：
+    /// `{signature}`替换为`Base64`编码的签名，`{signature_hex}`替换为十六进制编码的签名，
+    /// `{timestamp}`与`{nonce}`替换为签名使用的时间戳与随机串，
+    /// 字段包含`timestamp`或`nonce`时同时附加`X-Timestamp`或`X-Nonce`，
+    /// 请求可通过`nx_httprequest.SetHmacSigning`与`ClearHmacSigning`单独设置，请求体为流时`body`为空
+    #[method(name = "SetHmacSigning", overload = 3)]
+    fn hmac_signing(
+        &mut self,
+        algorithm: String,
+        key: String,
+        fields: String,
+        header: Option<String>,
+        template: Option<String>,
+        separator: Option<String>
+    ) -> &mut Self {
+        match HmacSigning::new(
+            &algorithm,
+            key,
+            &fields,
+            header.as_deref().unwrap_or("X-Signature"),
+            template.unwrap_or_else(|| "{signature}".to_owned()),
+            separator.unwrap_or_default()
+        ) {
+            Ok(signing) => self.cfg.as_mut().unwrap().hmac_signing = Some(Arc::new(signing)),
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+            }
+        }
+        self
+    }
+
+    /// 清除`HMAC`签名
+    #[method(name = "ClearHmacSigning")]
+    fn clear_hmac_signing(&mut self) -> &mut Self {
+        self.cfg.as_mut().unwrap().hmac_signing = None;
+        self
+    }
+
+    /// 直接使用`HTTP/2`(`prior knowledge`)
+    ///
+    /// 不经过`ALPN`或`Upgrade`协商，服务器必须支持`HTTP/2`
+    #[method(name = "SetHttp2PriorKnowledge")]
+    fn http2_prior_knowledge(&mut self) -> &mut Self {
+        let builder = self.builder.take().unwrap();
+        self.builder.replace(builder.http2_prior_knowledge());
+        self
+    }
+
+    /// 设置`HTTP/2`的初始流控窗口
+    ///
+    /// # Parameters
+    ///
+    /// - `stream_window` 每个流的初始窗口字节数，`0`表示默认
+    /// - `connection_window` 连接的初始窗口字节数，`0`表示默认
+    ///
+    /// # Description
+    ///
+    /// 同一连接上并发大量请求时增大窗口可减少流控等待，启用`SetHttp2AdaptiveWindow`时忽略
+    #[method(name = "SetHttp2Window", overload = 1)]
+    fn http2_window(&mut self, stream_window: pbulong, connection_window: Option<pbulong>) -> &mut Self {
+        let builder = self.builder.take().unwrap();
+        let mut builder =
+            builder.http2_initial_stream_window_size(Some(stream_window).filter(|size| *size > 0));
+        if let Some(connection_window) = connection_window {
+            builder = builder
+                .http2_initial_connection_window_size(Some(connection_window).filter(|size| *size > 0));
+        }
+        self.builder.replace(builder);
+        self
+    }
+
+    /// 设置`HTTP/2`的自适应流控窗口
+    ///
+    /// 根据带宽时延积动态调整窗口，覆盖`SetHttp2Window`
+    #[method(name = "SetHttp2AdaptiveWindow")]
+    fn http2_adaptive_window(&mut self, enabled: bool) -> &mut Self {
+        let builder = self.builder.take().unwrap();
+        self.builder.replace(builder.http2_adaptive_window(enabled));
+        self
+    }
+
+    /// 设置`HTTP/2`的最大帧字节数
+    ///
+    /// # Parameters
+    ///
+    /// - `size` `16384`~`16777215`，`0`表示默认
+    #[method(name = "SetHttp2MaxFrameSize")]
+    fn http2_max_frame_size(&mut self, size: pbulong) -> &mut Self {
+        if size != 0 && !(16_384..=16_777_215).contains(&size) {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid max frame size: {size}"));
+            return self;
+        }
+        let builder = self.builder.take().unwrap();
+        self.builder.replace(builder.http2_max_frame_size(Some(size).filter(|size| *size > 0)));
+        self
+    }
+
+    /// 设置`HTTP/2`的保活`PING`
+    ///
+    /// # Parameters
+    ///
+    /// - `interval` 发送`PING`的间隔(秒)，`0`表示不发送(默认)
+    /// - `timeout` 等待`PING`应答的超时(秒)，超时后关闭连接，默认`20`
+    /// - `while_idle` 没有活动的流时是否发送，默认`false`
+    #[method(name = "SetHttp2KeepAlive", overload = 2)]
+    fn http2_keep_alive(
+        &mut self,
+        interval: pbdouble,
+        timeout: Option<pbdouble>,
+        while_idle: Option<bool>
+    ) -> &mut Self {
+        let interval = match Duration::try_from_secs_f64(interval) {
+            Ok(interval) => interval,
+            Err(_) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid interval: {interval}"));
+                return self;
+            }
+        };
+        let timeout = match timeout.map(Duration::try_from_secs_f64).transpose() {
+            Ok(timeout) => timeout,
+            Err(_) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid timeout: {}", timeout.unwrap()));
+                return self;
+            }
+        };
+        let builder = self.builder.take().unwrap();
+        let mut builder =
+            builder.http2_keep_alive_interval(Some(interval).filter(|interval| !interval.is_zero()));
+        if let Some(timeout) = timeout {
+            builder = builder.http2_keep_alive_timeout(timeout);
+        }
+        if let Some(while_idle) = while_idle {
+            builder = builder.http2_keep_alive_while_idle(while_idle);
+        }
+        self.builder.replace(builder);
+        self
+    }
+
+    /// 设置响应数据的透明解压
+    ///
+    /// # Parameters
+    ///
+    /// - `algorithm` 压缩格式：`gzip`、`br`(或`brotli`)、`deflate`
+    /// - `enabled` 是否启用，默认全部启用
+    ///
+    /// # Description
+    ///
+    /// 请求未指定`Accept-Encoding`时声明启用的格式，响应按`Content-Encoding`解压，
+    /// 通过`nx_httpresponse.IsDecompressed`判断响应是否被解压
+    #[method(name = "SetCompression")]
+    fn compression(&mut self, algorithm: String, enabled: bool) -> &mut Self {
+        match ContentEncoding::parse(&algorithm) {
+            Some(encoding) => self.cfg.as_mut().unwrap().decompression.set(encoding, enabled),
+            None => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid compression: {algorithm}"));
+            }
+        }
+        self
+    }
+
+    /// 设置失败重试
+    ///
+    /// # Parameters
+    ///
+    /// - `max_retries` 最大重试次数，`0`表示不重试(默认)
+    /// - `delay` 首次重试的等待时间(秒)，之后每次加倍，默认`1`
+    ///
+    /// # Description
+    ///
+    /// 仅在连接失败、超时以及服务器返回`502`、`503`、`504`时重试，请求体为流(如`multipart`文件)时不重试
+    #[method(name = "SetRetry", overload = 1)]
+    fn retry(&mut self, max_retries: pbulong, delay: Option<pbdouble>) -> &mut Self {
+        let mut rt_cfg = self.cfg.take().unwrap();
+        rt_cfg.retry.max_retries = max_retries;
+        if let Some(delay) = delay {
+            rt_cfg.retry.delay = Duration::from_secs_f64(delay.max(0.0));
+        }
+        self.cfg.replace(rt_cfg);
+        self
+    }
+
+    /// 设置接收缓冲
+    ///
+    /// # Parameters
+    ///
+    /// - `max_prealloc` 按`Content-Length`预分配的最大字节数，默认`1MB`，`0`表示不预分配
+    /// - `growth` 缓冲区不足时的扩容倍数，`1.1`~`4`，默认`2`
+    /// - `spill_threshold` 接收的数据超过此字节数时转存到临时文件，默认`0`表示不转存
+    ///
+    /// # Description
+    ///
+    /// 避免服务器返回错误的`Content-Length`时预分配过大的内存，转存后响应的`GetData`返回空`Blob`，
+    /// 通过`GetReceiveFile`获取临时文件路径，临时文件在响应对象销毁时删除，指定了`SetReceiveFile`的请求不受影响
+    #[method(name = "SetReceiveBuffer", overload = 2)]
+    fn receive_buffer(
+        &mut self,
+        max_prealloc: pbulong,
+        growth: Option<pbdouble>,
+        spill_threshold: Option<pbulong>
+    ) -> &mut Self {
+        let growth = growth.unwrap_or(default::RECEIVE_GROWTH);
+        if !(1.1..=4.0).contains(&growth) {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid growth: {growth}"));
+            return self;
+        }
+        let mut rt_cfg = self.cfg.take().unwrap();
+        rt_cfg.receive = ReceivePolicy {
+            max_prealloc: max_prealloc as usize,
+            growth,
+            spill_threshold: spill_threshold.filter(|threshold| *threshold > 0).map(u64::from)
+        };
+        self.cfg.replace(rt_cfg);
+        self
+    }
+
+    /// 设置响应信封
+    ///
+    /// # Parameters
+    ///
+    /// - `data_path` 数据节点路径(以`.`分隔，如`result.data`)，空字符串表示整个响应
+    /// - `code_path` 状态码节点路径
+    /// - `message_path` 错误信息节点路径，空字符串表示不读取
+    ///
+    /// # Description
+    ///
+    /// 成功接收的`JSON`响应按信封分类：状态码为`0`、`"0"`或`true`时成功，`GetData*`仅返回数据节点(字符串节点返回其内容)，
+    /// 其它状态码视为失败，触发`OnError`并通过`GetErrorInfo`返回错误信息，
+    /// 不是`JSON`或不包含状态码节点的响应不受影响，空字符串的`code_path`表示清除
+    #[method(name = "SetEnvelope")]
+    fn envelope(&mut self, data_path: String, code_path: String, message_path: String) -> &mut Self {
+        let mut rt_cfg = self.cfg.take().unwrap();
+        let policy = EnvelopePolicy::new(&data_path, &code_path, &message_path);
+        rt_cfg.envelope = if policy.code_path.is_empty() {
+            None
+        } else {
+            Some(Arc::new(policy))
+        };
+        self.cfg.replace(rt_cfg);
+        self
+    }
+
     #[method(name = "SetConcurrency")]
     fn concurrency(&mut self, max_concurrency: u32) -> &mut Self {
         let mut rt_cfg = self.cfg.take().unwrap();
@@ -175,10 +843,58 @@ impl HttpClientConfig {
         self.cfg.replace(rt_cfg);
         self
     }
+
+    /// 最近一次失败的错误码
+    ///
+    /// 配置方法返回对象自身以便链式调用，参数无效时不生效并记录错误
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl HttpClientConfig {
+    fn set_proxy(&mut self, url: &str, auth: ProxyAuth) {
+        match parse_proxy_url(url) {
+            Some(url) => {
+                self.proxy = Some(ProxyConfig {
+                    target: ProxyTarget::Url(url),
+                    auth
+                })
+            },
+            None => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid proxy url: {url}"));
+            }
+        }
+    }
+
+    fn set_identity(&mut self, identity: reqwest::Result<Identity>) {
+        match identity {
+            Ok(identity) => {
+                let builder = self.builder.take().unwrap();
+                self.builder.replace(builder.identity(identity));
+                //替换系统证书存储中的证书
+                self.tls.store_identity = None;
+            },
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_DATA, e);
+            }
+        }
+    }
 }
 
 /// 默认配置
 pub mod default {
+    use std::time::Duration;
+
     /// 异步请求-最大并发数
     pub const MAX_CONCURRENCY: usize = 16;
+    /// 首次重试的等待时间
+    pub const RETRY_DELAY: Duration = Duration::from_secs(1);
+    /// 接收缓冲-最大预分配字节数
+    pub const RECEIVE_MAX_PREALLOC: usize = 1024 * 1024;
+    /// 接收缓冲-扩容倍数
+    pub const RECEIVE_GROWTH: f64 = 2.0;
 }
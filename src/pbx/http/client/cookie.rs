@@ -1,20 +1,32 @@
 use super::*;
-use reqwest::cookie::{CookieStore, Jar};
+use ::cookie::Cookie as RawCookie;
+use bytes::Bytes;
+use chrono::{DateTime, Local};
+use cookie_store::{Cookie, CookieExpiration, CookieStore as Store};
+use reqwest::{cookie::CookieStore, Url};
+use std::sync::RwLock;
 
 #[derive(Default)]
 pub struct HttpCookie {
-    jar: Arc<Jar>
+    jar: Arc<CookieJar>,
+    error: LastError
 }
 
 #[nonvisualobject(name = "nx_httpcookie")]
 impl HttpCookie {
     /// 获取`Cookie-Jar`
-    pub fn get(&self) -> Arc<Jar> { self.jar.clone() }
+    pub fn get(&self) -> Arc<CookieJar> { self.jar.clone() }
 
+    /// 添加`Cookie`
+    ///
+    /// `url`无效时不添加，通过`GetLastError`获取错误信息
     #[method(name = "SetCookie")]
     fn set_cookie(&mut self, url: String, cookie: String) -> &mut Self {
-        if let Ok(url) = &url.parse() {
-            self.jar.add_cookie_str(&cookie, url);
+        match url.parse() {
+            Ok(url) => self.jar.add_cookie_str(&cookie, &url),
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid url: {e}"));
+            }
         }
         self
     }
@@ -31,4 +43,156 @@ impl HttpCookie {
             Default::default()
         }
     }
+
+    /// 未过期的`Cookie`数量
+    #[method(name = "GetCount")]
+    fn count(&self) -> pbint { self.jar.store.read().unwrap().iter_unexpired().count() as pbint }
+
+    /// `Cookie`名称
+    ///
+    /// # Parameters
+    ///
+    /// - `index` 序号(从`1`开始)，按域名、路径与名称排序
+    #[method(name = "GetCookieName")]
+    fn cookie_name(&self, index: pbint) -> String {
+        self.jar.with_cookie(index, |cookie| cookie.name().to_owned()).unwrap_or_default()
+    }
+
+    #[method(name = "GetCookieValue")]
+    fn cookie_value(&self, index: pbint) -> String {
+        self.jar.with_cookie(index, |cookie| cookie.value().to_owned()).unwrap_or_default()
+    }
+
+    /// `Cookie`所属的域名
+    #[method(name = "GetCookieDomain")]
+    fn cookie_domain(&self, index: pbint) -> String {
+        self.jar.with_cookie(index, cookie_domain).unwrap_or_default()
+    }
+
+    #[method(name = "GetCookiePath")]
+    fn cookie_path(&self, index: pbint) -> String {
+        self.jar.with_cookie(index, |cookie| String::from(&cookie.path)).unwrap_or_default()
+    }
+
+    /// `Cookie`的过期时间(本地时间，格式为`yyyy-mm-dd hh:mm:ss`)
+    ///
+    /// 会话`Cookie`返回空字符串
+    #[method(name = "GetCookieExpiry")]
+    fn cookie_expiry(&self, index: pbint) -> String {
+        self.jar
+            .with_cookie(index, |cookie| {
+                match &cookie.expires {
+                    CookieExpiration::AtUtc(at) => {
+                        DateTime::from_timestamp(at.unix_timestamp(), 0)
+                            .map(|at| at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                            .unwrap_or_default()
+                    },
+                    CookieExpiration::SessionEnd => String::new()
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// 删除`Cookie`
+    ///
+    /// # Parameters
+    ///
+    /// - `url` 请求地址，删除此地址会发送的同名`Cookie`
+    /// - `name` 名称
+    ///
+    /// # Returns
+    ///
+    /// 是否删除了`Cookie`
+    #[method(name = "Remove")]
+    fn remove(&mut self, url: String, name: String) -> bool {
+        let url = match url.parse::<Url>() {
+            Ok(url) => url,
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid url: {e}"));
+                return false;
+            }
+        };
+        let mut store = self.jar.store.write().unwrap();
+        let keys = store
+            .matches(&url)
+            .into_iter()
+            .filter(|cookie| cookie.name() == name)
+            .map(|cookie| (cookie_domain(cookie), String::from(&cookie.path)))
+            .collect::<Vec<_>>();
+        keys.iter()
+            .fold(false, |removed, (domain, path)| store.remove(domain, path, &name).is_some() || removed)
+    }
+
+    /// 删除所有`Cookie`
+    #[method(name = "Clear")]
+    fn clear(&mut self) -> &mut Self {
+        self.jar.store.write().unwrap().clear();
+        self
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+/// 可枚举的`Cookie-Jar`
+///
+/// 与`reqwest::cookie::Jar`的行为相同
+#[derive(Default)]
+pub struct CookieJar {
+    store: RwLock<Store>
+}
+
+impl CookieJar {
+    /// 按`Set-Cookie`的格式添加
+    fn add_cookie_str(&self, cookie: &str, url: &Url) {
+        let cookies = RawCookie::parse(cookie).ok().map(|cookie| cookie.into_owned());
+        self.store.write().unwrap().store_response_cookies(cookies.into_iter(), url);
+    }
+
+    /// 访问指定序号(从`1`开始)的未过期`Cookie`
+    fn with_cookie<R>(&self, index: pbint, f: impl FnOnce(&Cookie<'static>) -> R) -> Option<R> {
+        let store = self.store.read().unwrap();
+        let mut cookies = store.iter_unexpired().collect::<Vec<_>>();
+        //`CookieStore`按哈希表存储，排序使序号稳定
+        cookies.sort_by_cached_key(|cookie| {
+            (cookie_domain(cookie), String::from(&cookie.path), cookie.name().to_owned())
+        });
+        let cookie = cookies.get(usize::try_from(index).ok()?.checked_sub(1)?)?;
+        Some(f(cookie))
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies = cookie_headers.filter_map(|value| {
+            let value = value.to_str().ok()?;
+            RawCookie::parse(value).ok().map(|cookie| cookie.into_owned())
+        });
+        self.store.write().unwrap().store_response_cookies(cookies, url);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let value = self
+            .store
+            .read()
+            .unwrap()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if value.is_empty() {
+            return None;
+        }
+        HeaderValue::from_maybe_shared(Bytes::from(value)).ok()
+    }
+}
+
+/// `Cookie`所属的域名，即`CookieStore`中的键
+fn cookie_domain(cookie: &Cookie) -> String {
+    cookie.domain.as_cow().map(|domain| domain.into_owned()).unwrap_or_default()
 }
@@ -3,7 +3,8 @@ use std::mem::replace;
 
 #[derive(Default)]
 pub struct HttpForm {
-    form: HashMap<String, String>
+    form: HashMap<String, String>,
+    error: LastError
 }
 
 #[nonvisualobject(name = "nx_httpform")]
@@ -20,4 +21,12 @@ impl HttpForm {
         self.form.insert(name, val);
         self
     }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
 }
@@ -1,9 +1,13 @@
-use crate::prelude::*;
+use crate::{
+    base::{diag::LiveObject, id, secret}, prelude::*
+};
 use pbni::{pbx::*, prelude::*};
 use reactor::*;
-use reqwest::{Client, Method};
-use std::{cell::RefCell, collections::HashMap, fs, mem, rc::Rc, sync::Arc, thread};
-use tokio::sync::Semaphore;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue}, Client, Method, RequestBuilder
+};
+use std::{cell::RefCell, collections::HashMap, fs, mem, rc::Rc, result::Result as StdResult, sync::Arc};
+use tokio::{sync::Semaphore, time::Instant};
 
 mod config;
 mod response;
@@ -11,16 +15,64 @@ mod request;
 mod form;
 mod multipart;
 mod cookie;
+mod auth;
+mod pagination;
+mod stream;
+mod signer;
+mod compression;
+mod sspi;
+mod sysproxy;
 
-use config::HttpClientConfig;
+use auth::{AuthToken, ProxyCredential};
+use compression::{ContentEncoding, Decompression};
+use config::{EnvelopePolicy, HttpClientConfig, ReceivePolicy, RetryPolicy};
+use pagination::HttpPagination;
 use request::HttpRequest;
-use response::{HttpResponse, HttpResponseInner};
+use response::{CancelReason, HttpResponse, HttpResponseInner, PartialFilePolicy, SegmentedDownload};
+use signer::{CanonicalRequest, HmacSigning, HttpSigner, SignError};
+use sspi::{SspiContext, SspiPackage};
+use stream::{BodyStream, StreamRead};
+use sysproxy::SystemProxy;
 
 struct HttpClient {
     state: HandlerState,
     client: Client,
     semaphore: Arc<Semaphore>,
-    pending: Rc<RefCell<HashMap<pbulong, (CancelHandle, Option<String>)>>>
+    pending: Rc<RefCell<HashMap<pbulong, (CancelHandle, Option<String>)>>>,
+    /// 请求时从凭据管理器读取`Basic`认证的凭据名称
+    credential_target: Option<String>,
+    retry: RetryPolicy,
+    receive: ReceivePolicy,
+    /// 透明解压的压缩格式
+    decompression: Decompression,
+    /// 响应信封
+    envelope: Option<Arc<EnvelopePolicy>>,
+    /// 默认请求头
+    default_headers: Arc<HeaderMap>,
+    /// 异步请求发送前触发`OnBeforeSend`
+    intercept_send: bool,
+    /// 异步请求完成后触发`OnAfterReceive`
+    intercept_receive: bool,
+    /// 认证令牌
+    auth: Arc<AuthToken>,
+    /// 认证失败时触发`OnAuthRequired`刷新令牌
+    auth_refresh: bool,
+    /// 代理凭据，未设置代理时为`None`
+    proxy_credential: Option<Arc<ProxyCredential>>,
+    /// `Windows`集成认证的认证包
+    windows_auth: Option<SspiPackage>,
+    /// 系统代理，未调用`nx_httpconfig.UseSystemProxy`时为`None`
+    system_proxy: Option<Arc<SystemProxy>>,
+    /// `HMAC`签名
+    hmac_signing: Option<Arc<HmacSigning>>,
+    /// 代理认证失败时触发`OnProxyAuthRequired`询问凭据
+    proxy_auth_prompt: bool,
+    /// 拒绝ID重复的异步请求
+    reject_duplicate_id: bool,
+    /// 请求签名器(`nx_httpsigner`)
+    signer: Option<SharedObject>,
+    error: LastError,
+    _live: LiveObject
 }
 
 #[nonvisualobject(name = "nx_httpclient")]
@@ -28,26 +80,106 @@ impl HttpClient {
     #[constructor]
     fn new(session: Session, _object: Object) -> Self {
         let state = HandlerState::new(session);
-        let client = Client::new();
-        let semaphore = Arc::new(Semaphore::new(config::default::MAX_CONCURRENCY));
         let pending = Rc::new(RefCell::new(HashMap::new()));
+        //已加载全局配置时作为默认配置
+        let built = if crate::base::config::is_loaded() {
+            HttpClientConfig::default().build().ok()
+        } else {
+            None
+        };
+        let (client, cfg) = built.unwrap_or_else(|| {
+            let client = Client::builder().tls_info(true).gzip(false).brotli(false).deflate(false);
+            (client.build().expect("build http client"), Default::default())
+        });
         HttpClient {
             state,
             client,
-            semaphore,
-            pending
+            semaphore: Arc::new(Semaphore::new(cfg.max_concurrency)),
+            pending,
+            credential_target: cfg.credential_target,
+            retry: cfg.retry,
+            receive: cfg.receive,
+            decompression: cfg.decompression,
+            envelope: cfg.envelope,
+            default_headers: Arc::new(cfg.default_headers),
+            intercept_send: false,
+            intercept_receive: false,
+            auth: Default::default(),
+            auth_refresh: false,
+            proxy_credential: cfg.proxy_credential,
+            windows_auth: cfg.windows_auth,
+            system_proxy: cfg.system_proxy,
+            hmac_signing: cfg.hmac_signing,
+            proxy_auth_prompt: false,
+            reject_duplicate_id: false,
+            signer: None,
+            error: LastError::default(),
+            _live: LiveObject::new("nx_httpclient")
         }
     }
 
-    fn push_pending(&self, id: pbulong, cancel_hdl: CancelHandle, receive_file: Option<String>) {
+    /// 登记异步请求
+    ///
+    /// 存在相同ID的请求时取消并以`CancelReason::Superseded`结束
+    fn push_pending(&mut self, id: pbulong, cancel_hdl: CancelHandle, receive_file: Option<String>) {
         let mut pending = self.pending.borrow_mut();
-        let old = pending.insert(id, (cancel_hdl, receive_file));
+        let old = pending.remove(&id);
         drop(pending);
-        if let Some((hdl, _)) = old {
-            hdl.cancel();
+        if let Some((hdl, old_receive_file)) = old {
+            if hdl.cancel() {
+                let alive = self.get_alive_state();
+                self.on_superseded(id);
+                //NOTE 对象可能被销毁
+                if alive.is_dead() {
+                    cancel_hdl.cancel();
+                    return;
+                }
+                self.complete(
+                    id,
+                    HttpResponseInner::cancelled(CancelReason::Superseded),
+                    0,
+                    old_receive_file
+                );
+                //NOTE 对象可能被销毁
+                if alive.is_dead() {
+                    cancel_hdl.cancel();
+                    return;
+                }
+                //`OnComplete`中可能再次发送相同ID的请求
+                return self.push_pending(id, cancel_hdl, receive_file);
+            }
+        }
+        self.pending.borrow_mut().insert(id, (cancel_hdl, receive_file));
+    }
+
+    /// 在其它线程中调用时记录错误并返回`E_ACCESS_DENIED`
+    fn deny_foreign_thread(&mut self) -> Option<RetCode> {
+        if self.state.is_owner_thread() {
+            return None;
+        }
+        #[cfg(feature = "log")]
+        warn!("HTTP client accessed from a thread other than the owner thread");
+        Some(self.error.set(RetCode::E_ACCESS_DENIED, "called from a thread other than the owner thread"))
+    }
+
+    /// 使用签名器对请求签名
+    ///
+    /// 在UI线程中调用
+    fn sign(&self, req: &CanonicalRequest) -> StdResult<Vec<(HeaderName, HeaderValue)>, SignError> {
+        match self.signer.as_ref() {
+            Some(signer) => {
+                let mut signer = signer.get_native_mut::<HttpSigner>().expect("invalid httpsigner");
+                signer.sign(req)
+            },
+            None => Ok(Vec::new())
         }
     }
 
+    /// 是否拒绝使用该ID发送异步请求
+    fn rejects_id(&self, id: pbulong) -> bool {
+        self.reject_duplicate_id && self.pending.borrow().contains_key(&id)
+    }
+
     fn complete(
         &mut self,
         id: pbulong,
@@ -59,11 +191,21 @@ impl HttpClient {
         pending.remove(&id);
         drop(pending);
         let is_cancelled = resp.is_cancelled();
-        let is_succ = resp.is_succ();
+        let mut is_succ = resp.is_succ();
+        let status = resp.status().map(|status| status.as_u16() as pblong).unwrap_or_default();
         let resp = HttpResponse::new_object_modify(self.get_session(), |obj| {
             obj.init(resp, elapsed, Some(id), receive_file)
         });
         let alive = self.get_alive_state();
+        if !is_cancelled && self.intercept_receive {
+            if self.on_after_receive(id, &resp) == RetCode::PREVENT {
+                is_succ = false;
+            }
+            //NOTE 对象可能被销毁
+            if alive.is_dead() {
+                return;
+            }
+        }
         if !is_cancelled {
             if is_succ {
                 self.on_succ(id, &resp);
@@ -75,30 +217,325 @@ impl HttpClient {
         if alive.is_alive() {
             self.on_complete(id, &resp);
         }
+        if alive.is_alive() {
+            self.on_any_request_end(id, status, elapsed as pbulong);
+        }
+    }
+
+    /// 轮询的一次结果(`StartConditionalPoll`)
+    ///
+    /// 与`complete`相同但不结束请求，不触发`OnComplete`
+    fn poll_result(&mut self, id: pbulong, resp: HttpResponseInner, elapsed: u128) {
+        let mut is_succ = resp.is_succ();
+        let resp = HttpResponse::new_object_modify(self.get_session(), |obj| {
+            obj.init(resp, elapsed, Some(id), None)
+        });
+        if self.intercept_receive {
+            let alive = self.get_alive_state();
+            if self.on_after_receive(id, &resp) == RetCode::PREVENT {
+                is_succ = false;
+            }
+            //NOTE 对象可能被销毁
+            if alive.is_dead() {
+                return;
+            }
+        }
+        if is_succ {
+            self.on_succ(id, &resp);
+        } else {
+            self.on_error(id, &resp);
+        }
+    }
+
+    /// 执行发送前拦截
+    ///
+    /// # Returns
+    ///
+    /// 继续发送的请求，`OnBeforeSend`返回`1`时取消
+    fn apply_before_send(&mut self, id: pbulong, builder: RequestBuilder) -> Option<RequestBuilder> {
+        let mut req = HttpRequest::new_object_modify(self.get_session(), |obj| {
+            obj.init(self.get_object().share(), builder);
+        });
+        let alive = self.get_alive_state();
+        let rc = self.on_before_send(id, &req);
+        //NOTE 对象可能被销毁
+        if alive.is_dead() || rc == RetCode::PREVENT {
+            return None;
+        }
+        req.get_native_mut::<HttpRequest>().ok().and_then(|req| req.take_builder())
+    }
+
+    /// 触发`OnPage`
+    fn raise_page(&mut self, id: pbulong, page_no: u32, resp: HttpResponseInner) -> RetCode {
+        let resp =
+            HttpResponse::new_object_modify(self.get_session(), |obj| obj.init(resp, 0, Some(id), None));
+        self.on_page(id, page_no as pbulong, &resp)
+    }
+
+    /// 请求是否使用客户端的认证令牌
+    fn uses_auth_token(&self) -> bool { self.auth_refresh || self.auth.is_set() }
+
+    /// 应用凭据管理器中的`Basic`认证
+    fn apply_vault_credential(&self, builder: RequestBuilder) -> RequestBuilder {
+        let Some(target) = self.credential_target.as_deref() else {
+            return builder;
+        };
+        match secret::read_credential(target) {
+            Ok(Some(cred)) => {
+                let psw = cred.secret_string();
+                builder.basic_auth(
+                    cred.user_name,
+                    if psw.is_empty() {
+                        None
+                    } else {
+                        Some(psw)
+                    }
+                )
+            },
+            _ => {
+                #[cfg(feature = "log")]
+                warn!("Read credential '{}' failed", target);
+                builder
+            }
+        }
     }
 
     #[method(name = "Reconfig")]
     fn reconfig(&mut self, cfg: &mut HttpClientConfig) -> RetCode {
-        let (client, cfg) = cfg.build()?;
+        let (client, cfg) = match cfg.build() {
+            Ok(rv) => rv,
+            Err(e) => return self.error.set(RetCode::E_INVALID_ARGUMENT, e)
+        };
         self.client = client;
         self.semaphore = Arc::new(Semaphore::new(cfg.max_concurrency));
+        self.credential_target = cfg.credential_target;
+        self.retry = cfg.retry;
+        self.receive = cfg.receive;
+        self.decompression = cfg.decompression;
+        self.envelope = cfg.envelope;
+        self.default_headers = Arc::new(cfg.default_headers);
+        self.proxy_credential = cfg.proxy_credential;
+        self.windows_auth = cfg.windows_auth;
+        self.system_proxy = cfg.system_proxy;
+        self.hmac_signing = cfg.hmac_signing;
+        RetCode::OK
+    }
+
+    /// 设置拦截器
+    ///
+    /// # Parameters
+    ///
+    /// - `before_send` 异步请求发送前触发`OnBeforeSend`，可统一修改请求头、地址等
+    /// - `after_receive` 异步请求完成后先触发`OnAfterReceive`，可统一映射错误
+    ///
+    /// # Description
+    ///
+    /// 默认均不启用，仅对`AsyncSend`生效
+    #[method(name = "SetInterceptor")]
+    fn set_interceptor(&mut self, before_send: bool, after_receive: bool) -> RetCode {
+        self.intercept_send = before_send;
+        self.intercept_receive = after_receive;
+        RetCode::OK
+    }
+
+    /// 设置认证令牌
+    ///
+    /// # Parameters
+    ///
+    /// - `token` `Bearer`令牌，空字符串表示清除
+    ///
+    /// # Description
+    ///
+    /// 附加到没有调用`SetBasicAuth`、`SetBearerAuth`或设置`Authorization`头的请求，优先于`SetCredentialFromVault`，
+    /// 在`OnAuthRequired`中调用以更新令牌
+    #[method(name = "SetAuthToken")]
+    fn set_auth_token(&mut self, token: String) -> RetCode {
+        self.auth.set(if token.is_empty() {
+            None
+        } else {
+            Some(token)
+        });
+        RetCode::OK
+    }
+
+    /// 启用认证刷新
+    ///
+    /// # Description
+    ///
+    /// 异步请求响应`401`或`403`时触发`OnAuthRequired`，处理程序调用`SetAuthToken`设置新令牌并返回`1`后，
+    /// 使用新令牌重试原请求一次，并发的请求只触发一次`OnAuthRequired`并共享刷新结果，请求体为流时不重试
+    #[method(name = "SetAuthRefreshHandler")]
+    fn set_auth_refresh_handler(&mut self, enabled: bool) -> RetCode {
+        self.auth_refresh = enabled;
         RetCode::OK
     }
 
+    /// 启用代理认证询问
+    ///
+    /// # Description
+    ///
+    /// 异步请求被代理拒绝(`407`)时触发`OnProxyAuthRequired`，处理程序调用`SetProxyCredential`设置凭据并返回`1`后，
+    /// 使用新凭据重试原请求一次，并发的请求只触发一次`OnProxyAuthRequired`并共享询问结果，请求体为流时不重试，
+    /// 需要在`nx_httpconfig`中调用`SetProxy`设置代理
+    #[method(name = "SetProxyAuthHandler")]
+    fn set_proxy_auth_handler(&mut self, enabled: bool) -> RetCode {
+        self.proxy_auth_prompt = enabled;
+        RetCode::OK
+    }
+
+    /// 设置代理凭据
+    ///
+    /// # Description
+    ///
+    /// 优先于`nx_httpconfig`配置的代理认证，之后的连接均使用此凭据，在`OnProxyAuthRequired`中调用以更新凭据，
+    /// 未设置代理时返回`E_NO_SUPPORT`
+    #[method(name = "SetProxyCredential")]
+    fn set_proxy_credential(&mut self, user: String, psw: String) -> RetCode {
+        match self.proxy_credential.as_ref() {
+            Some(credential) => {
+                credential.set(user, psw);
+                RetCode::OK
+            },
+            None => self.error.set(RetCode::E_NO_SUPPORT, "proxy not configured")
+        }
+    }
+
+    /// 设置请求签名器
+    ///
+    /// # Parameters
+    ///
+    /// - `signer` 签名器，可使用内置的签名方式或继承后在`OnSign`中自定义签名
+    ///
+    /// # Description
+    ///
+    /// 每次发送请求前(包括分页的每一页与轮询的每一次)在UI线程中调用签名器并附加返回的请求头，
+    /// 失败重试与认证刷新后的重试沿用原签名
+    #[method(name = "SetSigner")]
+    fn set_signer(&mut self, signer: &mut HttpSigner) -> RetCode {
+        self.signer = Some(signer.get_object().share());
+        RetCode::OK
+    }
+
+    /// 取消请求签名器
+    #[method(name = "ClearSigner")]
+    fn clear_signer(&mut self) -> RetCode {
+        self.signer = None;
+        RetCode::OK
+    }
+
+    /// 设置ID重复的处理方式
+    ///
+    /// # Parameters
+    ///
+    /// - `reject` 拒绝使用未完成请求的ID发送异步请求(返回`E_BUSY`)
+    ///
+    /// # Description
+    ///
+    /// 默认不拒绝，此时取消原请求并触发`OnSuperseded`，原请求以`GetCancelReason`为`3`触发`OnComplete`
+    #[method(name = "SetRejectDuplicateId")]
+    fn set_reject_duplicate_id(&mut self, reject: bool) -> RetCode {
+        self.reject_duplicate_id = reject;
+        RetCode::OK
+    }
+
+    /// 创建对象的线程ID
+    #[method(name = "GetOwnerThreadId")]
+    fn get_owner_thread_id(&self) -> pbulong { self.state.owner_thread_id() as pbulong }
+
+    /// 调用线程是否为创建对象的线程
+    ///
+    /// # Description
+    ///
+    /// 对象只能在创建它的线程(会话)中使用，在其它线程中发送异步请求(`AsyncSend`、`FetchAllPages`等)时返回`E_ACCESS_DENIED`
+    #[method(name = "IsCallerOwnerThread")]
+    fn is_caller_owner_thread(&self) -> bool { self.state.is_owner_thread() }
+
     #[method(name = "HasAsyncRequest")]
     fn has_async_request(&self) -> bool { !self.pending.borrow().is_empty() }
 
+    /// 创建请求
+    ///
+    /// `method`无效时返回的请求对象发送时失败，通过`GetLastError`获取错误信息
     #[method(name = "Request")]
     fn request(&mut self, method: String, url: String) -> Object {
         let method = match Method::from_str(&method.to_ascii_uppercase()) {
             Ok(method) => method,
-            Err(_) => panic!("Unsupport method: {method}")
+            Err(_) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("unsupported method: {method}"));
+                return HttpRequest::new_object_modify(self.get_session(), |_| {});
+            }
         };
         HttpRequest::new_object_modify(self.get_session(), |obj| {
             obj.init(self.get_object().share(), self.client.request(method, url));
         })
     }
 
+    /// 分页获取
+    ///
+    /// # Parameters
+    ///
+    /// - `req` 第一页的请求，发送后失效
+    /// - `cfg` 分页配置
+    /// - `id` 请求ID，用于`Cancel`以及完成事件
+    ///
+    /// # Description
+    ///
+    /// 依次异步获取每一页并触发`OnPage`，处理程序返回`1`时停止，没有下一页或达到最大页数时完成，
+    /// 任一页失败时以该页的响应触发`OnError`，不触发进度事件，忽略`SetReceiveFile`
+    #[method(name = "FetchAllPages")]
+    fn fetch_all_pages(&mut self, req: &mut HttpRequest, cfg: &mut HttpPagination, id: pbulong) -> RetCode {
+        if let Some(rv) = self.deny_foreign_thread() {
+            return rv;
+        }
+        if self.rejects_id(id) {
+            return self.error.set(RetCode::E_BUSY, format!("duplicate request id: {id}"));
+        }
+        let Some((builder, opts)) = req.take_send(id, self) else {
+            return RetCode::E_INVALID_OBJECT;
+        };
+        let fut = pagination::fetch_all(builder, opts, cfg.build(), self.semaphore.clone(), self.invoker());
+        let fut = futures::correlate(id.to_string(), async move {
+            let inst = Instant::now();
+            let resp = fut.await;
+            #[cfg(feature = "log")]
+            debug!("Pagination completed in {}ms", inst.elapsed().as_millis());
+            (resp, inst.elapsed().as_millis())
+        });
+        let cancel_hdl = self.spawn(fut, move |this, (resp, elapsed)| this.complete(id, resp, elapsed, None));
+        self.push_pending(id, cancel_hdl, None);
+        RetCode::OK
+    }
+
+    /// 预连接
+    ///
+    /// # Parameters
+    ///
+    /// - `url` 服务地址
+    ///
+    /// # Returns
+    ///
+    /// 预连接ID，用于`OnPreconnect`，在其它线程中调用时返回`0`
+    ///
+    /// # Description
+    ///
+    /// 异步发送`HEAD`请求完成`DNS`解析、建立连接以及`TLS`握手，连接保留在连接池中供之后的请求复用，
+    /// 收到任意响应(包括错误状态码)即为成功，完成后触发`OnPreconnect`
+    #[method(name = "Preconnect")]
+    fn preconnect(&mut self, url: String) -> pbulong {
+        if self.deny_foreign_thread().is_some() {
+            return 0;
+        }
+        let id = id::next_sequence() as pbulong;
+        let req = self.client.head(url);
+        let fut = async move {
+            let inst = Instant::now();
+            let ok = req.send().await.is_ok();
+            (ok, inst.elapsed().as_millis())
+        };
+        self.spawn(fut, move |this, (ok, elapsed)| this.on_preconnect(id, ok, elapsed as pbulong));
+        id
+    }
+
     #[method(name = "Cancel")]
     fn cancel(&mut self, id: pbulong) -> RetCode {
         let mut pending = self.pending.borrow_mut();
@@ -106,11 +543,7 @@ impl HttpClient {
         drop(pending);
         if let Some((hdl, receive_file)) = removed {
             if hdl.cancel() {
-                self.complete(id, HttpResponseInner::cancelled(), 0, receive_file.clone());
-                if let Some(file_path) = receive_file {
-                    thread::yield_now();
-                    let _ = fs::remove_file(file_path);
-                }
+                self.complete(id, HttpResponseInner::cancelled(CancelReason::User), 0, receive_file);
             }
             RetCode::OK
         } else {
@@ -125,25 +558,181 @@ impl HttpClient {
         drop(pending);
         for (id, (hdl, receive_file)) in taked {
             if hdl.cancel() {
-                self.complete(id, HttpResponseInner::cancelled(), 0, receive_file.clone());
-                if let Some(file_path) = receive_file {
-                    thread::yield_now();
-                    let _ = fs::remove_file(file_path);
-                }
+                self.complete(id, HttpResponseInner::cancelled(CancelReason::User), 0, receive_file);
             }
         }
         RetCode::OK
     }
 
+    #[method(name = "CancelTag")]
+    fn cancel_tag(&mut self, tag: String) -> RetCode {
+        if self.state.cancel_tag(&tag) == 0 {
+            return RetCode::E_DATA_NOT_FOUND;
+        }
+        //清理被取消的请求
+        let mut pending = self.pending.borrow_mut();
+        let cancelled: Vec<pbulong> =
+            pending.iter().filter(|(_, (hdl, _))| !hdl.is_pending()).map(|(id, _)| *id).collect();
+        let removed: Vec<_> =
+            cancelled.into_iter().filter_map(|id| pending.remove(&id).map(|item| (id, item))).collect();
+        drop(pending);
+        for (id, (_, receive_file)) in removed {
+            self.complete(id, HttpResponseInner::cancelled(CancelReason::User), 0, receive_file);
+        }
+        RetCode::OK
+    }
+
+    /// 暂停事件
+    ///
+    /// # Description
+    ///
+    /// 暂停期间到达的事件被缓存，调用`ResumeEvents`或`FlushEvents`后按顺序触发
+    #[method(name = "SuspendEvents")]
+    fn suspend_events(&mut self) -> RetCode {
+        self.state.suspend_events();
+        RetCode::OK
+    }
+
+    /// 恢复事件并按顺序触发缓存的事件
+    ///
+    /// # Returns
+    ///
+    /// 触发的事件数量
+    #[method(name = "ResumeEvents")]
+    fn resume_events(&mut self) -> pbulong { self.state.resume_events() as pbulong }
+
+    /// 按顺序触发缓存的事件并保持暂停状态
+    ///
+    /// # Returns
+    ///
+    /// 触发的事件数量
+    #[method(name = "FlushEvents")]
+    fn flush_events(&mut self) -> pbulong { self.state.flush_events() as pbulong }
+
+    #[method(name = "IsEventsSuspended")]
+    fn is_events_suspended(&self) -> bool { self.state.is_events_suspended() }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+
+    /// 异步请求发送前(需要`SetInterceptor`启用)
+    ///
+    /// # Parameters
+    ///
+    /// - `id` 请求ID
+    /// - `req` 请求对象，可调用`SetHeader`、`SetUrl`、`Query`、`SetBody`等修改请求，不能再次发送
+    ///
+    /// # Returns
+    ///
+    /// 返回`1`取消请求，此时只触发`OnComplete`
+    #[event(name = "OnBeforeSend")]
+    fn on_before_send(&mut self, id: pbulong, req: &Object) -> RetCode {}
+
+    /// 异步请求完成后、`OnSuccess`/`OnError`前(需要`SetInterceptor`启用)，请求被取消时不触发
+    ///
+    /// # Returns
+    ///
+    /// 返回`1`时将成功的响应视为失败，改为触发`OnError`
+    #[event(name = "OnAfterReceive")]
+    fn on_after_receive(&mut self, id: pbulong, resp: &Object) -> RetCode {}
+
+    /// 认证失败，需要刷新令牌(需要`SetAuthRefreshHandler`启用)
+    ///
+    /// # Returns
+    ///
+    /// 调用`SetAuthToken`设置新令牌后返回`1`重试请求，否则按原响应完成
+    #[event(name = "OnAuthRequired")]
+    fn on_auth_required(&mut self, id: pbulong) -> RetCode {}
+
+    /// 代理认证失败，需要凭据(需要`SetProxyAuthHandler`启用)
+    ///
+    /// # Parameters
+    ///
+    /// - `id` 请求ID
+    /// - `realm` 代理返回的认证域，`HTTPS`请求无法获取时为空字符串
+    ///
+    /// # Returns
+    ///
+    /// 调用`SetProxyCredential`设置凭据后返回`1`重试请求，否则按原响应完成
+    #[event(name = "OnProxyAuthRequired")]
+    fn on_proxy_auth_required(&mut self, id: pbulong, realm: String) -> RetCode {}
+
+    /// 分页获取的一页(`FetchAllPages`)
+    ///
+    /// # Parameters
+    ///
+    /// - `id` 请求ID
+    /// - `page_no` 页序号，从`1`开始
+    /// - `resp` 当前页的响应
+    ///
+    /// # Returns
+    ///
+    /// 返回`1`停止获取之后的页
+    #[event(name = "OnPage")]
+    fn on_page(&mut self, id: pbulong, page_no: pbulong, resp: &Object) -> RetCode {}
+
     #[event(name = "OnSuccess")]
     fn on_succ(&mut self, id: pbulong, resp: &Object) {}
 
     #[event(name = "OnError")]
     fn on_error(&mut self, id: pbulong, resp: &Object) {}
 
+    /// 异步请求开始发送(`AsyncSend`、`FetchAllPages`、`StartConditionalPoll`)
+    ///
+    /// # Description
+    ///
+    /// 由所有请求触发，在`OnBeforeSend`之后、获得并发许可后触发，每个请求ID只触发一次
+    #[event(name = "OnAnyRequestStart")]
+    fn on_any_request_start(&mut self, id: pbulong, method: String, url: String) {}
+
+    /// 异步请求结束
+    ///
+    /// # Parameters
+    ///
+    /// - `status` `HTTP`状态码，发送失败或取消时为`0`
+    /// - `elapsed` 耗时(毫秒)
+    ///
+    /// # Description
+    ///
+    /// 在`OnComplete`之后触发，开始前被取消的请求不触发`OnAnyRequestStart`
+    #[event(name = "OnAnyRequestEnd")]
+    fn on_any_request_end(&mut self, id: pbulong, status: pblong, elapsed: pbulong) {}
+
+    /// 预连接完成
+    ///
+    /// # Parameters
+    ///
+    /// - `id` `Preconnect`返回的ID
+    /// - `ok` 是否成功建立连接
+    /// - `elapsed` 耗时(毫秒)
+    #[event(name = "OnPreconnect")]
+    fn on_preconnect(&mut self, id: pbulong, ok: bool, elapsed: pbulong) {}
+
+    /// 未完成的请求被相同ID的新请求取代
+    #[event(name = "OnSuperseded")]
+    fn on_superseded(&mut self, id: pbulong) {}
+
     #[event(name = "OnComplete")]
     fn on_complete(&mut self, id: pbulong, resp: &Object) {}
 
+    /// 收到响应数据块(`nx_httprequest.SetChunkEvent`)
+    ///
+    /// # Parameters
+    ///
+    /// - `id` 请求ID
+    /// - `data` 数据块
+    ///
+    /// # Returns
+    ///
+    /// 返回`1`取消请求
+    #[event(name = "OnDataChunk")]
+    fn on_data_chunk(&mut self, id: pbulong, data: &[u8]) -> RetCode {}
+
     #[event(name = "OnReceive")]
     fn on_recv(&mut self, id: pbulong, total: pbulong, received: pbulong, speed: pbulong) -> RetCode {}
 
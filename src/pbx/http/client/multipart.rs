@@ -4,13 +4,15 @@ use std::fs::File as StdFile;
 use tokio::fs::File;
 
 pub struct HttpMultipart {
-    builder: Option<Form>
+    builder: Option<Form>,
+    error: LastError
 }
 
 impl Default for HttpMultipart {
     fn default() -> Self {
         HttpMultipart {
-            builder: Some(Form::default())
+            builder: Some(Form::default()),
+            error: LastError::default()
         }
     }
 }
@@ -24,29 +26,30 @@ impl HttpMultipart {
     /// 仅能调用一次
     pub fn build(&mut self) -> Form { self.builder.replace(Form::default()).unwrap() }
 
+    /// 添加文本字段
+    ///
+    /// `mime`无效时不添加，通过`GetLastError`获取错误信息
     #[method(name = "AddField", overload = 1)]
     fn text(&mut self, name: String, val: String, mime: Option<String>) -> &mut Self {
-        let mut part = Part::text(val);
-        if let Some(mime) = mime {
-            part = part.mime_str(mime.as_str()).expect("invalid mime");
-        }
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(builder.part(name, part));
+        let part = Part::text(val);
+        self.add_part(name, part, mime);
         self
     }
 
+    /// 添加二进制字段
+    ///
+    /// `mime`无效时不添加，通过`GetLastError`获取错误信息
     #[method(name = "AddField", overload = 1)]
     fn binary(&mut self, name: String, val: &[u8], mime: Option<String>) -> &mut Self {
         let len = val.len();
-        let mut part = Part::stream_with_length(val.to_owned(), len as u64);
-        if let Some(mime) = mime {
-            part = part.mime_str(mime.as_str()).expect("invalid mime");
-        }
-        let builder = self.builder.take().unwrap();
-        self.builder.replace(builder.part(name, part));
+        let part = Part::stream_with_length(val.to_owned(), len as u64);
+        self.add_part(name, part, mime);
         self
     }
 
+    /// 添加文件
+    ///
+    /// 文件无法打开或`mime`无效时不添加，通过`GetLastError`获取错误信息
     #[method(name = "AddFile", overload = 2)]
     fn file(
         &mut self,
@@ -55,21 +58,49 @@ impl HttpMultipart {
         file_name: Option<String>,
         mime: Option<String>
     ) -> &mut Self {
-        if let Ok(file) = StdFile::open(file_path) {
-            let len = file.metadata().unwrap().len();
-            let mut part = Part::stream_with_length(File::from_std(file), len);
-            if let Some(file_name) = file_name {
-                part = part.file_name(file_name);
+        let mut part = match StdFile::open(&file_path).and_then(|file| Ok((file.metadata()?.len(), file))) {
+            Ok((len, file)) => Part::stream_with_length(File::from_std(file), len),
+            Err(e) => {
+                let code = if e.kind() == std::io::ErrorKind::NotFound {
+                    RetCode::E_FILE_NOT_FOUND
+                } else {
+                    RetCode::E_IO_ERROR
+                };
+                self.error.set(code, format!("{file_path}: {e}"));
+                return self;
             }
-            if let Some(mime) = mime {
-                part = part.mime_str(mime.as_str()).expect("invalid mime");
-            }
-            let builder = self.builder.take().unwrap();
-            self.builder.replace(builder.part(name, part));
+        };
+        if let Some(file_name) = file_name {
+            part = part.file_name(file_name);
         }
+        self.add_part(name, part, mime);
         self
     }
 
     #[method(name = "GetBoundary")]
     fn boundary(&mut self) -> &str { self.builder.as_ref().unwrap().boundary() }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl HttpMultipart {
+    fn add_part(&mut self, name: String, mut part: Part, mime: Option<String>) {
+        if let Some(mime) = mime {
+            part = match part.mime_str(mime.as_str()) {
+                Ok(part) => part,
+                Err(_) => {
+                    self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid mime: {mime}"));
+                    return;
+                }
+            };
+        }
+        let builder = self.builder.take().unwrap();
+        self.builder.replace(builder.part(name, part));
+    }
 }
@@ -0,0 +1,450 @@
+use super::{request::SendOptions, *};
+use crate::{base::locale::tr, reactor::HandlerInvoker};
+use bytes::Bytes;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// 分页方式
+#[derive(Debug, Clone)]
+enum PageStyle {
+    /// 页码
+    Page {
+        param: String,
+        start: u64,
+        size_param: Option<String>,
+        size: Option<u64>
+    },
+    /// 偏移量
+    Offset {
+        param: String,
+        limit_param: String,
+        limit: u64
+    },
+    /// 游标，从响应中读取下一页的游标
+    Cursor {
+        pointer: String,
+        param: String
+    },
+    /// 下一页地址，`None`表示读取`Link`头
+    NextLink {
+        pointer: Option<String>
+    }
+}
+
+/// 分页配置
+#[derive(Debug, Clone)]
+pub struct PaginationConfig {
+    style: PageStyle,
+    /// 数据数组的`JSON Pointer`，设置时合并所有页的数据
+    items_pointer: Option<String>,
+    max_pages: u32
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        PaginationConfig {
+            style: PageStyle::NextLink {
+                pointer: None
+            },
+            items_pointer: None,
+            max_pages: default::MAX_PAGES
+        }
+    }
+}
+
+pub struct HttpPagination {
+    cfg: PaginationConfig,
+    error: LastError
+}
+
+impl Default for HttpPagination {
+    fn default() -> Self {
+        HttpPagination {
+            cfg: PaginationConfig::default(),
+            error: LastError::default()
+        }
+    }
+}
+
+#[nonvisualobject(name = "nx_httppagination")]
+impl HttpPagination {
+    pub fn build(&self) -> PaginationConfig { self.cfg.clone() }
+
+    /// 按页码分页
+    ///
+    /// # Parameters
+    ///
+    /// - `param` 页码参数名
+    /// - `start` 起始页码，默认`1`
+    /// - `size_param` 每页数量参数名，默认不设置
+    /// - `size` 每页数量，设置时返回的数据少于此数量视为最后一页
+    #[method(name = "SetPageStyle", overload = 3)]
+    fn page_style(
+        &mut self,
+        param: String,
+        start: Option<pbulong>,
+        size_param: Option<String>,
+        size: Option<pbulong>
+    ) -> &mut Self {
+        if param.is_empty() {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, "empty page param");
+            return self;
+        }
+        self.cfg.style = PageStyle::Page {
+            param,
+            start: start.unwrap_or(1) as u64,
+            size_param: size_param.filter(|param| !param.is_empty()),
+            size: size.filter(|size| *size > 0).map(u64::from)
+        };
+        self
+    }
+
+    /// 按偏移量分页
+    ///
+    /// # Parameters
+    ///
+    /// - `param` 偏移量参数名，从`0`开始，每页增加返回的数据数量
+    /// - `limit_param` 每页数量参数名
+    /// - `limit` 每页数量，返回的数据少于此数量视为最后一页
+    ///
+    /// # Notice
+    ///
+    /// 需要`SetItemsPath`
+    #[method(name = "SetOffsetStyle")]
+    fn offset_style(&mut self, param: String, limit_param: String, limit: pbulong) -> &mut Self {
+        if param.is_empty() || limit_param.is_empty() || limit == 0 {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, "invalid offset style");
+            return self;
+        }
+        self.cfg.style = PageStyle::Offset {
+            param,
+            limit_param,
+            limit: limit as u64
+        };
+        self
+    }
+
+    /// 按游标分页
+    ///
+    /// # Parameters
+    ///
+    /// - `pointer` 响应中下一页游标的`JSON Pointer`(如`/paging/next_cursor`)，没有、为`null`或空字符串时结束
+    /// - `param` 游标参数名
+    #[method(name = "SetCursorStyle")]
+    fn cursor_style(&mut self, pointer: String, param: String) -> &mut Self {
+        if !is_pointer(&pointer) || param.is_empty() {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid cursor pointer: {pointer}"));
+            return self;
+        }
+        self.cfg.style = PageStyle::Cursor {
+            pointer,
+            param
+        };
+        self
+    }
+
+    /// 按下一页地址分页(默认)
+    ///
+    /// # Parameters
+    ///
+    /// - `pointer` 响应中下一页地址的`JSON Pointer`(如`/links/next`)，默认读取`Link`头中`rel="next"`的地址
+    ///
+    /// # Description
+    ///
+    /// 相对地址基于当前页的地址解析，没有下一页地址时结束
+    #[method(name = "SetNextLinkStyle", overload = 1)]
+    fn next_link_style(&mut self, pointer: Option<String>) -> &mut Self {
+        let pointer = pointer.filter(|pointer| !pointer.is_empty());
+        if let Some(pointer) = pointer.as_ref() {
+            if !is_pointer(pointer) {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid next link pointer: {pointer}"));
+                return self;
+            }
+        }
+        self.cfg.style = PageStyle::NextLink {
+            pointer
+        };
+        self
+    }
+
+    /// 设置数据数组
+    ///
+    /// # Parameters
+    ///
+    /// - `pointer` 响应中数据数组的`JSON Pointer`(如`/data/items`)，空字符串表示响应本身
+    ///
+    /// # Description
+    ///
+    /// 设置后完成事件的响应数据为合并所有页后的`JSON`数组，返回空数组的页视为最后一页
+    #[method(name = "SetItemsPath")]
+    fn items_path(&mut self, pointer: String) -> &mut Self {
+        if !pointer.is_empty() && !is_pointer(&pointer) {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid items pointer: {pointer}"));
+            return self;
+        }
+        self.cfg.items_pointer = Some(pointer);
+        self
+    }
+
+    /// 设置最大页数，默认`100`
+    #[method(name = "SetMaxPages")]
+    fn max_pages(&mut self, max_pages: pbulong) -> &mut Self {
+        self.cfg.max_pages = max_pages.max(1);
+        self
+    }
+
+    /// 最近一次失败的错误码
+    ///
+    /// 配置方法返回对象自身以便链式调用，参数无效时不生效并记录错误
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+/// 依次获取所有页
+///
+/// # Returns
+///
+/// 失败页的响应，或最后一页的响应(设置了数据数组时数据为合并后的数组)
+pub async fn fetch_all(
+    builder: RequestBuilder,
+    opts: SendOptions,
+    cfg: PaginationConfig,
+    semaphore: Arc<Semaphore>,
+    invoker: HandlerInvoker<HttpClient>
+) -> HttpResponseInner {
+    let id = opts.id;
+    let mut items = Vec::new();
+    let mut builder = match HttpRequest::notify_start(builder, id, &invoker).await {
+        Ok(builder) => builder,
+        Err(resp) => return resp
+    };
+    let mut offset = 0;
+    let mut page_no = 1;
+    let mut last;
+    loop {
+        //请求体为流时无法复制
+        let Some(page) = builder.try_clone() else {
+            return HttpResponseInner::send_error(tr("request body is not cloneable", "请求内容无法复制"));
+        };
+        let page = match &cfg.style {
+            PageStyle::Page {
+                param,
+                start,
+                size_param,
+                size
+            } => {
+                let mut pairs = vec![(param.as_str(), Cow::Owned((start + page_no as u64 - 1).to_string()))];
+                if let (Some(size_param), Some(size)) = (size_param, size) {
+                    pairs.push((size_param.as_str(), Cow::Owned(size.to_string())));
+                }
+                match set_query(page, &pairs) {
+                    Some(page) => page,
+                    None => return HttpResponseInner::send_error(tr("invalid request", "无效的请求"))
+                }
+            },
+            PageStyle::Offset {
+                param,
+                limit_param,
+                limit
+            } => {
+                match set_query(page, &[
+                    (param.as_str(), Cow::Owned(offset.to_string())),
+                    (limit_param.as_str(), Cow::Owned(limit.to_string()))
+                ]) {
+                    Some(page) => page,
+                    None => return HttpResponseInner::send_error(tr("invalid request", "无效的请求"))
+                }
+            },
+            _ => page
+        };
+        let resp = {
+            let _permit = semaphore.acquire().await;
+            #[cfg(feature = "log")]
+            debug!("Fetch page {}", page_no);
+            HttpRequest::send_with(page, opts.clone()).await
+        };
+        let HttpResponseInner::Received {
+            status,
+            headers,
+            data,
+            spill_file: None,
+            ..
+        } = &resp
+        else {
+            return resp;
+        };
+        let json = serde_json::from_slice::<Value>(data).ok();
+        //当前页的数据数量
+        let mut count = None;
+        if let Some(pointer) = cfg.items_pointer.as_deref() {
+            match json.as_ref().and_then(|json| json.pointer(pointer)).and_then(Value::as_array) {
+                Some(page_items) => {
+                    count = Some(page_items.len() as u64);
+                    items.extend(page_items.iter().cloned());
+                },
+                None => {
+                    return HttpResponseInner::receive_error(
+                        *status,
+                        headers.clone(),
+                        format!("{}: {pointer}", tr("items not found", "数据数组不存在"))
+                    );
+                }
+            }
+        }
+        let page_resp = HttpResponseInner::received(*status, headers.clone(), data.clone());
+        match invoker.invoke(page_resp, move |this, resp| this.raise_page(id, page_no, resp)).await.await {
+            Ok(RetCode::PREVENT) => {
+                last = resp;
+                break;
+            },
+            Ok(_) => {},
+            Err(InvokeError::TargetIsDead | InvokeError::Dropped) => {
+                return HttpResponseInner::cancelled(CancelReason::Shutdown)
+            },
+            Err(InvokeError::Panic) => panic!("Callback panic at OnPage")
+        }
+        //下一页
+        let next = if count == Some(0) {
+            None
+        } else {
+            match &cfg.style {
+                PageStyle::Page {
+                    size,
+                    ..
+                } => {
+                    match (size, count) {
+                        (Some(size), Some(count)) if count < *size => None,
+                        _ => Some(builder)
+                    }
+                },
+                PageStyle::Offset {
+                    limit,
+                    ..
+                } => {
+                    match count {
+                        Some(count) if count >= *limit => {
+                            offset += count;
+                            Some(builder)
+                        },
+                        _ => None
+                    }
+                },
+                PageStyle::Cursor {
+                    pointer,
+                    param
+                } => {
+                    let cursor = match json.as_ref().and_then(|json| json.pointer(pointer)) {
+                        Some(Value::String(cursor)) if !cursor.is_empty() => Some(Cow::Owned(cursor.clone())),
+                        Some(Value::Number(cursor)) => Some(Cow::Owned(cursor.to_string())),
+                        _ => None
+                    };
+                    cursor.and_then(|cursor| set_query(builder, &[(param.as_str(), cursor)]))
+                },
+                PageStyle::NextLink {
+                    pointer
+                } => {
+                    let link = match pointer {
+                        Some(pointer) => {
+                            json.as_ref()
+                                .and_then(|json| json.pointer(pointer))
+                                .and_then(Value::as_str)
+                                .filter(|link| !link.is_empty())
+                                .map(str::to_owned)
+                        },
+                        None => next_link(headers)
+                    };
+                    link.and_then(|link| set_url(builder, &link))
+                }
+            }
+        };
+        last = resp;
+        match next {
+            Some(next) if page_no < cfg.max_pages => {
+                builder = next;
+                page_no += 1;
+            },
+            Some(_) => {
+                #[cfg(feature = "log")]
+                warn!("Pagination stopped at max pages: {}", cfg.max_pages);
+                break;
+            },
+            None => break
+        }
+    }
+    match (cfg.items_pointer.is_some(), last) {
+        (
+            true,
+            HttpResponseInner::Received {
+                status,
+                headers,
+                peer_certificate,
+                ..
+            }
+        ) => {
+            let data = Value::Array(items).to_string();
+            let mut headers = headers;
+            headers.remove(header::CONTENT_LENGTH);
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            HttpResponseInner::received(status, headers, Bytes::from(data))
+                .with_peer_certificate(peer_certificate)
+        },
+        (_, last) => last
+    }
+}
+
+/// 设置查询参数，替换已有的同名参数
+fn set_query(builder: RequestBuilder, pairs: &[(&str, Cow<str>)]) -> Option<RequestBuilder> {
+    let (client, req) = builder.build_split();
+    let mut req = req.ok()?;
+    let url = req.url_mut();
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !pairs.iter().any(|(name, _)| name == key))
+        .map(|(key, val)| (key.into_owned(), val.into_owned()))
+        .collect();
+    {
+        let mut query = url.query_pairs_mut();
+        query.clear();
+        query.extend_pairs(kept);
+        query.extend_pairs(pairs.iter().map(|(key, val)| (*key, val.as_ref())));
+    }
+    Some(RequestBuilder::from_parts(client, req))
+}
+
+/// 替换请求地址，相对地址基于当前地址解析
+fn set_url(builder: RequestBuilder, link: &str) -> Option<RequestBuilder> {
+    let (client, req) = builder.build_split();
+    let mut req = req.ok()?;
+    let url = req.url().join(link).ok()?;
+    *req.url_mut() = url;
+    Some(RequestBuilder::from_parts(client, req))
+}
+
+/// 读取`Link`头中`rel="next"`的地址
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers.get_all(header::LINK).iter().filter_map(|value| value.to_str().ok()).find_map(|value| {
+        value.split(',').find_map(|link| {
+            let mut parts = link.split(';');
+            let url = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+            parts
+                .any(|param| {
+                    let param = param.trim();
+                    param == "rel=\"next\"" || param == "rel=next"
+                })
+                .then(|| url.to_owned())
+        })
+    })
+}
+
+fn is_pointer(pointer: &str) -> bool { pointer.starts_with('/') }
+
+/// 默认配置
+mod default {
+    /// 最大页数
+    pub const MAX_PAGES: u32 = 100;
+}
@@ -1,24 +1,72 @@
 use super::{form::HttpForm, multipart::HttpMultipart, *};
-use crate::base::pfw;
-use bytes::Bytes;
+use crate::{
+    base::{
+        diag::LiveObject, locale::{self, tr, Locale}, pfw
+    }, pbx::{json::Json, xml::Xml}
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bytes::{Bytes, BytesMut};
 use futures_util::{
-    future::{self, Either, FutureExt}, Stream
+    future::{self, Either, FutureExt}, stream, Stream
 };
 use http_body::Body as HttpBody;
 use reqwest::{
-    header::{self, HeaderValue, CONTENT_LENGTH}, Body, RequestBuilder, Response, Result as ReqwestResult
+    header::{self, HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH}, tls::TlsInfo, Body, Request, RequestBuilder, Response, Result as ReqwestResult, StatusCode, Url
 };
 use std::{
-    future::Future, pin::Pin, result::Result as StdResult, sync::atomic::{AtomicU64, Ordering}, task::{ready, Context as TaskContext, Poll}, time::Duration
+    collections::hash_map::DefaultHasher, future::Future, hash::{Hash, Hasher}, io, pin::Pin, result::Result as StdResult, sync::atomic::{AtomicU64, Ordering}, task::{ready, Context as TaskContext, Poll}, time::Duration
 };
 use tokio::{
-    task::yield_now, time::{self, Instant}
+    fs::File, io::AsyncReadExt, task::{spawn_blocking, yield_now}, time::{self, Instant}
 };
 
-#[derive(Default)]
 pub struct HttpRequest {
     inner: Option<HttpRequestInner>,
-    recv_file_path: Option<String>
+    recv_file_path: Option<String>,
+    /// 接收文件未完成时的处理策略
+    partial_file: PartialFilePolicy,
+    /// 断点续传的起始位置
+    resume_offset: Option<u64>,
+    /// 分段下载的最大分段数
+    segments: usize,
+    /// 流式读取的缓冲水位
+    stream_watermark: Option<usize>,
+    /// 通过`OnDataChunk`逐块接收
+    chunk_event: bool,
+    tag: Option<String>,
+    correlation_id: Option<String>,
+    /// 已设置认证信息
+    auth_set: bool,
+    /// `Windows`集成认证的认证包，`None`表示使用客户端的设置
+    windows_auth: Option<Option<SspiPackage>>,
+    /// `HMAC`签名，`None`表示使用客户端的设置
+    hmac_signing: Option<Option<Arc<HmacSigning>>>,
+    /// 不附加客户端的默认请求头
+    clear_default_headers: bool,
+    error: LastError,
+    _live: LiveObject
+}
+
+impl Default for HttpRequest {
+    fn default() -> Self {
+        HttpRequest {
+            inner: None,
+            recv_file_path: None,
+            partial_file: PartialFilePolicy::default(),
+            resume_offset: None,
+            segments: 0,
+            stream_watermark: None,
+            chunk_event: false,
+            tag: None,
+            correlation_id: None,
+            auth_set: false,
+            windows_auth: None,
+            hmac_signing: None,
+            clear_default_headers: false,
+            error: LastError::default(),
+            _live: LiveObject::new("nx_httprequest")
+        }
+    }
 }
 
 #[nonvisualobject(name = "nx_httprequest")]
@@ -30,8 +78,103 @@ impl HttpRequest {
         });
     }
 
+    /// 取出请求(用于拦截后继续发送)
+    pub(super) fn take_builder(&mut self) -> Option<RequestBuilder> {
+        self.inner.take().and_then(|inner| inner.builder)
+    }
+
+    /// 取出请求与发送参数(用于分页获取)
+    ///
+    /// 不接收到文件，不触发进度事件
+    pub(super) fn take_send(
+        &mut self,
+        id: pbulong,
+        client: &HttpClient
+    ) -> Option<(RequestBuilder, SendOptions)> {
+        let builder = self.take_builder()?;
+        //使用客户端的认证令牌时不再读取凭据管理器
+        let builder = if self.auth_set || client.uses_auth_token() {
+            builder
+        } else {
+            client.apply_vault_credential(builder)
+        };
+        let mut opts = self.send_options(id, client, false);
+        opts.recv_file_path = None;
+        opts.stream = None;
+        opts.chunk_event = false;
+        Some((builder, opts))
+    }
+
+    /// 客户端拒绝ID重复的请求或在其它线程中调用时检查
+    ///
+    /// 拒绝时记录错误，请求对象仍然有效
+    fn reject_duplicate(&mut self, id: pbulong) -> Option<RetCode> {
+        let client = self.inner.as_ref()?.client.get_native_ref::<HttpClient>().ok()?;
+        if !client.state.is_owner_thread() {
+            return Some(
+                self.error.set(RetCode::E_ACCESS_DENIED, "called from a thread other than the owner thread")
+            );
+        }
+        if client.rejects_id(id) {
+            Some(self.error.set(RetCode::E_BUSY, format!("duplicate request id: {id}")))
+        } else {
+            None
+        }
+    }
+
+    /// 修改请求
+    ///
+    /// 请求构造失败时记录错误并使请求对象失效
+    fn modify_request(&mut self, f: impl FnOnce(&mut Request)) -> bool {
+        let Some(inner) = self.inner.as_mut() else {
+            return false;
+        };
+        let (client, req) = inner.builder.take().unwrap().build_split();
+        match req {
+            Ok(mut req) => {
+                f(&mut req);
+                inner.builder.replace(RequestBuilder::from_parts(client, req));
+                true
+            },
+            Err(e) => {
+                self.inner = None;
+                self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+                false
+            }
+        }
+    }
+
+    /// 获取请求地址(包含查询参数)
+    #[method(name = "GetUrl")]
+    fn url(&mut self) -> String {
+        let mut url = String::new();
+        self.modify_request(|req| url = req.url().to_string());
+        url
+    }
+
+    /// 设置请求地址
+    ///
+    /// # Description
+    ///
+    /// 替换创建请求时的地址与已设置的查询参数，可在`nx_httpclient.OnBeforeSend`中修改请求的地址
+    #[method(name = "SetUrl")]
+    fn set_url(&mut self, url: String) -> &mut Self {
+        match Url::parse(&url) {
+            Ok(url) => {
+                self.modify_request(|req| *req.url_mut() = url);
+            },
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid url: {url}, {e}"));
+            }
+        }
+        self
+    }
+
     #[method(name = "SetHeader")]
     fn header(&mut self, key: String, val: String) -> &mut Self {
+        if key.eq_ignore_ascii_case("authorization") {
+            self.auth_set = true;
+        }
         if let Some(inner) = self.inner.as_mut() {
             let builder = inner.builder.take().unwrap();
             inner.builder.replace(builder.header(key, val));
@@ -39,8 +182,35 @@ impl HttpRequest {
         self
     }
 
+    /// 设置此请求的`User-Agent`
+    #[method(name = "SetUserAgent")]
+    fn user_agent(&mut self, val: String) -> &mut Self {
+        if let Some(inner) = self.inner.as_mut() {
+            match HeaderValue::from_str(&val) {
+                Ok(val) => {
+                    let builder = inner.builder.take().unwrap();
+                    let mut headers = HeaderMap::new();
+                    headers.insert(header::USER_AGENT, val);
+                    inner.builder.replace(builder.headers(headers));
+                },
+                Err(_) => {
+                    self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid user agent: {val}"));
+                }
+            }
+        }
+        self
+    }
+
+    /// 不附加客户端的默认请求头(`nx_httpconfig.SetDefaultHeader`、`SetAgent`)
+    #[method(name = "ClearDefaultHeaders")]
+    fn clear_default_headers(&mut self) -> &mut Self {
+        self.clear_default_headers = true;
+        self
+    }
+
     #[method(name = "SetBasicAuth")]
     fn basic_auth(&mut self, user: String, psw: String) -> &mut Self {
+        self.auth_set = true;
         if let Some(inner) = self.inner.as_mut() {
             let builder = inner.builder.take().unwrap();
             inner.builder.replace(builder.basic_auth(
@@ -57,6 +227,7 @@ impl HttpRequest {
 
     #[method(name = "SetBearerAuth")]
     fn bearer_auth(&mut self, token: String) -> &mut Self {
+        self.auth_set = true;
         if let Some(inner) = self.inner.as_mut() {
             let builder = inner.builder.take().unwrap();
             inner.builder.replace(builder.bearer_auth(token));
@@ -64,11 +235,90 @@ impl HttpRequest {
         self
     }
 
+    /// 设置`Windows`集成认证
+    ///
+    /// # Parameters
+    ///
+    /// - `package` 认证包：`Negotiate`(默认，优先`Kerberos`)、`NTLM`，空字符串表示禁用
+    ///
+    /// # Description
+    ///
+    /// 覆盖`nx_httpconfig.SetWindowsAuth`，启用时不附加客户端的认证令牌
+    #[method(name = "SetWindowsAuth", overload = 1)]
+    fn windows_auth(&mut self, package: Option<String>) -> &mut Self {
+        let package = match package.as_deref().map(str::trim) {
+            Some("") => None,
+            Some(name) => {
+                match SspiPackage::parse(name) {
+                    Some(package) => Some(package),
+                    None => {
+                        self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid auth package: {name}"));
+                        return self;
+                    }
+                }
+            },
+            None => Some(SspiPackage::Negotiate)
+        };
+        if package.is_some() {
+            self.auth_set = true;
+        }
+        self.windows_auth = Some(package);
+        self
+    }
+
+    /// 设置`HMAC`签名
+    ///
+    /// 覆盖`nx_httpconfig.SetHmacSigning`，参数相同
+    #[method(name = "SetHmacSigning", overload = 3)]
+    fn hmac_signing(
+        &mut self,
+        algorithm: String,
+        key: String,
+        fields: String,
+        header: Option<String>,
+        template: Option<String>,
+        separator: Option<String>
+    ) -> &mut Self {
+        match HmacSigning::new(
+            &algorithm,
+            key,
+            &fields,
+            header.as_deref().unwrap_or("X-Signature"),
+            template.unwrap_or_else(|| "{signature}".to_owned()),
+            separator.unwrap_or_default()
+        ) {
+            Ok(signing) => self.hmac_signing = Some(Some(Arc::new(signing))),
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+            }
+        }
+        self
+    }
+
+    /// 不使用客户端的`HMAC`签名
+    #[method(name = "ClearHmacSigning")]
+    fn clear_hmac_signing(&mut self) -> &mut Self {
+        self.hmac_signing = Some(None);
+        self
+    }
+
+    /// 使用`nx_jwt`生成的令牌设置`Bearer`认证
+    #[cfg(feature = "crypto")]
+    #[method(name = "SetBearerAuth")]
+    fn bearer_auth_jwt(&mut self, jwt: &mut crate::pbx::crypto::Jwt) -> &mut Self {
+        let token = jwt.encode().unwrap_or_default();
+        self.bearer_auth(token)
+    }
+
     #[method(name = "SetTimeout")]
     fn timeout(&mut self, secs: pbdouble) -> &mut Self {
+        let Ok(timeout) = Duration::try_from_secs_f64(secs) else {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid timeout: {secs}"));
+            return self;
+        };
         if let Some(inner) = self.inner.as_mut() {
             let builder = inner.builder.take().unwrap();
-            inner.builder.replace(builder.timeout(Duration::from_secs_f64(secs)));
+            inner.builder.replace(builder.timeout(timeout));
         }
         self
     }
@@ -101,13 +351,92 @@ impl HttpRequest {
         self
     }
 
+    /// 设置压缩的请求体
+    ///
+    /// # Parameters
+    ///
+    /// - `data` 未压缩的数据
+    /// - `algorithm` 压缩格式：`gzip`、`br`(或`brotli`)、`deflate`
+    /// - `content_type` 内容类型，默认为`application/octet-stream`
+    ///
+    /// # Description
+    ///
+    /// 发送时在后台线程中压缩并设置`Content-Encoding`，请求体为流时失败不重试，签名时请求体为空
+    #[method(name = "SetBodyCompressed", overload = 1)]
+    fn body_compressed(&mut self, data: &[u8], algorithm: String, content_type: Option<String>) -> &mut Self {
+        let Some(encoding) = ContentEncoding::parse(&algorithm) else {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid compression: {algorithm}"));
+            return self;
+        };
+        if let Some(inner) = self.inner.as_mut() {
+            let data = data.to_owned();
+            let body = stream::once(async move {
+                match spawn_blocking(move || encoding.compress(&data)).await {
+                    Ok(rv) => rv,
+                    Err(e) => Err(io::Error::new(io::ErrorKind::Other, e))
+                }
+            });
+            let builder = inner.builder.take().unwrap();
+            let mut builder = builder.body(Body::wrap_stream(body));
+            builder =
+                builder.header(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str())).header(
+                    header::CONTENT_TYPE,
+                    content_type.unwrap_or_else(|| mime::APPLICATION_OCTET_STREAM.to_string())
+                );
+            inner.builder.replace(builder);
+        }
+        self
+    }
+
+    /// 设置请求体为文件
+    ///
+    /// # Parameters
+    ///
+    /// - `file_path` 文件路径
+    /// - `content_type` 内容类型，默认为`application/octet-stream`
+    ///
+    /// # Description
+    ///
+    /// 发送时从文件流式读取，不加载到内存，`Content-Length`为调用时的文件大小，支持发送进度事件，
+    /// 请求体为流时失败不重试，签名时请求体为空
+    #[method(name = "SetBodyFile", overload = 1)]
+    fn body_file(&mut self, file_path: String, content_type: Option<String>) -> &mut Self {
+        if let Some(inner) = self.inner.as_mut() {
+            let (file, len) = match fs::File::open(&file_path).and_then(|file| {
+                let len = file.metadata()?.len();
+                Ok((file, len))
+            }) {
+                Ok(rv) => rv,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    self.error.set(RetCode::E_FILE_NOT_FOUND, e);
+                    return self;
+                },
+                Err(e) => {
+                    self.error.set(RetCode::E_IO_ERROR, e);
+                    return self;
+                }
+            };
+            let builder = inner.builder.take().unwrap();
+            let mut builder = builder.body(Body::wrap_stream(file_stream(File::from_std(file))));
+            builder = builder.header(CONTENT_LENGTH, len).header(
+                header::CONTENT_TYPE,
+                content_type.unwrap_or_else(|| mime::APPLICATION_OCTET_STREAM.to_string())
+            );
+            inner.builder.replace(builder);
+        }
+        self
+    }
+
     #[method(name = "SetBody")]
     fn json_or_xml(&mut self, obj: Object) -> &mut Self {
         if let Some(inner) = self.inner.as_mut() {
             let (data, content_type) = match obj.get_class_name().as_str() {
                 "n_json" => (pfw::json_serialize(&obj), "application/json; charset=utf-8"),
                 "n_xmldoc" => (pfw::xml_serialize(&obj), "text/xml; charset=utf-8"),
-                cls @ _ => panic!("unexpect class {cls}")
+                cls @ _ => {
+                    self.error.set(RetCode::E_INVALID_OBJECT, format!("unexpect class {cls}"));
+                    return self;
+                }
             };
             let builder = inner.builder.take().unwrap();
             let mut builder = builder.body(data);
@@ -117,6 +446,30 @@ impl HttpRequest {
         self
     }
 
+    #[method(name = "SetBody")]
+    fn json(&mut self, obj: &mut Json) -> &mut Self {
+        if let Some(inner) = self.inner.as_mut() {
+            let builder = inner.builder.take().unwrap();
+            let mut builder = builder.body(obj.to_json());
+            builder = builder
+                .header(header::CONTENT_TYPE, HeaderValue::from_static("application/json; charset=utf-8"));
+            inner.builder.replace(builder);
+        }
+        self
+    }
+
+    #[method(name = "SetBody")]
+    fn xml(&mut self, obj: &mut Xml) -> &mut Self {
+        if let Some(inner) = self.inner.as_mut() {
+            let builder = inner.builder.take().unwrap();
+            let mut builder = builder.body(obj.to_xml(false));
+            builder =
+                builder.header(header::CONTENT_TYPE, HeaderValue::from_static("text/xml; charset=utf-8"));
+            inner.builder.replace(builder);
+        }
+        self
+    }
+
     #[method(name = "SetBody")]
     fn multipart(&mut self, form: &mut HttpMultipart) -> &mut Self {
         if let Some(inner) = self.inner.as_mut() {
@@ -144,9 +497,133 @@ impl HttpRequest {
         self
     }
 
-    #[method(name = "SetReceiveFile")]
-    fn receive_file(&mut self, file_path: String) -> &mut Self {
+    /// 设置接收数据的文件
+    ///
+    /// # Parameters
+    ///
+    /// - `file_path` 文件路径，目录不存在时自动创建
+    /// - `partial` 接收失败或被取消时文件的处理策略：`DeletePartial`(默认)删除文件、`KeepPartial`保留文件、
+    ///   `RenameToTmp`重命名为`<file_path>.tmp`
+    ///
+    /// # Description
+    ///
+    /// 文件在写入端关闭后处理，文件被其它程序占用时在后台重试
+    #[method(name = "SetReceiveFile", overload = 1)]
+    fn receive_file(&mut self, file_path: String, partial: Option<String>) -> &mut Self {
+        if let Some(partial) = partial {
+            match PartialFilePolicy::parse(&partial) {
+                Some(partial) => self.partial_file = partial,
+                None => {
+                    self.error
+                        .set(RetCode::E_INVALID_ARGUMENT, format!("invalid partial file policy: {partial}"));
+                    return self;
+                }
+            }
+        }
+        self.recv_file_path = Some(file_path);
+        self.resume_offset = None;
+        self
+    }
+
+    /// 设置断点续传的接收文件
+    ///
+    /// # Parameters
+    ///
+    /// - `file_path` 文件路径，目录不存在时自动创建
+    ///
+    /// # Description
+    ///
+    /// 文件已存在且不为空时发送`Range`请求头，服务器返回`206`时追加到文件，否则重新写入文件，
+    /// 服务器返回错误状态时不修改文件，接收失败或被取消时保留文件以便再次续传，
+    /// 通过`nx_httpresponse.IsRangeHonored`判断服务器是否接受了范围请求
+    #[method(name = "SetResumeFile")]
+    fn resume_file(&mut self, file_path: String) -> &mut Self {
+        let offset = fs::metadata(&file_path).map(|meta| meta.len()).unwrap_or_default();
+        if offset > 0 {
+            if let Some(inner) = self.inner.as_mut() {
+                let builder = inner.builder.take().unwrap();
+                //续传的范围对应未压缩的内容
+                inner.builder.replace(
+                    builder
+                        .header(header::RANGE, format!("bytes={offset}-"))
+                        .header(header::ACCEPT_ENCODING, HeaderValue::from_static("identity"))
+                );
+            }
+        }
         self.recv_file_path = Some(file_path);
+        self.partial_file = PartialFilePolicy::Keep;
+        self.resume_offset = Some(offset).filter(|&offset| offset > 0);
+        self
+    }
+
+    /// 设置分段下载
+    ///
+    /// # Parameters
+    ///
+    /// - `count` 最大分段数，`0`或`1`表示不分段
+    ///
+    /// # Description
+    ///
+    /// 仅在设置了`SetReceiveFile`时生效，先请求第一个字节获取文件大小，然后按范围分段并行下载并写入文件的对应位置，
+    /// 每段不小于`1MB`，服务器不支持范围请求时按普通请求发送，`OnReceive`报告所有分段的合计进度，
+    /// 分段失败时不重试，不适用于`SetResumeFile`、`SetStreaming`与`SetChunkEvent`，所有分段只占用一个并发名额
+    #[method(name = "SetSegments")]
+    fn segments(&mut self, count: pbulong) -> &mut Self {
+        self.segments = count as usize;
+        self
+    }
+
+    /// 设置流式读取
+    ///
+    /// # Parameters
+    ///
+    /// - `watermark` 缓冲区的最大字节数，`0`表示不使用流式读取
+    ///
+    /// # Description
+    ///
+    /// 收到响应头后即完成请求，数据在后台接收到缓冲区，缓冲的数据达到`watermark`时暂停接收，
+    /// 通过`nx_httpresponse.ReadNext`按需读取，响应对象销毁时停止接收，
+    /// 优先于`SetReceiveFile`，不触发接收进度事件，不适用于`FetchAllPages`与`StartConditionalPoll`
+    #[method(name = "SetStreaming")]
+    fn streaming(&mut self, watermark: pbulong) -> &mut Self {
+        self.stream_watermark = if watermark == 0 {
+            None
+        } else {
+            Some(watermark as usize)
+        };
+        self
+    }
+
+    /// 设置逐块接收
+    ///
+    /// # Parameters
+    ///
+    /// - `enabled` 是否启用
+    ///
+    /// # Description
+    ///
+    /// 仅对`AsyncSend`生效，收到的每个数据块触发`nx_httpclient.OnDataChunk`，处理完成后才接收下一块，
+    /// 响应不保存数据，适合处理超大的响应，`SetStreaming`优先，忽略`SetReceiveFile`，不触发接收进度事件
+    #[method(name = "SetChunkEvent")]
+    fn chunk_event(&mut self, enabled: bool) -> &mut Self {
+        self.chunk_event = enabled;
+        self
+    }
+
+    #[method(name = "SetTag")]
+    fn tag(&mut self, tag: String) -> &mut Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    /// 设置关联ID
+    ///
+    /// # Description
+    ///
+    /// 关联ID会记录在请求过程的所有日志中，异步请求默认使用请求ID
+    #[method(name = "SetCorrelationId")]
+    fn correlation_id(&mut self, id: String) -> &mut Self {
+        self.correlation_id = Some(id);
         self
     }
 
@@ -158,80 +635,172 @@ impl HttpRequest {
         }) = self.inner.take()
         {
             let client = client.get_native_ref::<HttpClient>().expect("invalid httpclient");
-            let recv_file_path = self.recv_file_path.clone();
-            let fut = if progress.unwrap_or_default() {
-                Either::Left(self.send_with_progress_impl(
-                    0,
-                    &client,
-                    builder.unwrap(),
-                    recv_file_path.clone()
-                ))
+            //使用客户端的认证令牌时不再读取凭据管理器
+            let builder = if self.auth_set || client.uses_auth_token() {
+                builder
             } else {
-                Either::Right(self.send_impl(builder.unwrap(), recv_file_path.clone()))
+                builder.map(|builder| client.apply_vault_credential(builder))
+            };
+            let mut opts = self.send_options(0, &client, progress.unwrap_or_default());
+            //同步请求阻塞`UI`线程，无法触发`OnAuthRequired`、`OnProxyAuthRequired`和`OnDataChunk`
+            opts.auth_refresh = false;
+            opts.chunk_event = false;
+            opts.proxy_auth = None;
+            //在发送前直接签名
+            opts.sign = false;
+            let builder = match client.signer.as_ref() {
+                Some(_) => Self::sign_now(builder.unwrap(), &client),
+                None => Ok(builder.unwrap())
             };
+            let fut = match builder {
+                Ok(builder) => Either::Left(Self::send_with(builder, opts)),
+                Err(resp) => Either::Right(future::ready(resp))
+            };
+            let fut = match hevent.unwrap_or_default() {
+                0 => Either::Left(fut),
+                hevent => {
+                    match futures::cancel_by_event(fut, hevent) {
+                        Ok(fut) => {
+                            Either::Right(fut.map(|rv| {
+                                rv.unwrap_or_else(|| HttpResponseInner::cancelled(CancelReason::User))
+                            }))
+                        },
+                        Err(e) => {
+                            self.error.set(RetCode::E_INVALID_HANDLE, format!("invalid hevent: {e}"));
+                            let err_info = self.error.text().to_owned();
+                            return HttpResponse::new_object_modify(self.get_session(), |obj| {
+                                obj.init(
+                                    HttpResponseInner::send_error(err_info),
+                                    0,
+                                    None,
+                                    self.recv_file_path.take()
+                                )
+                            });
+                        }
+                    }
+                },
+            };
+            let correlation_id = self.correlation_id.take().unwrap_or_default();
             let (resp, elapsed) = client
-                .spawn_blocking(async move {
+                .spawn_blocking(futures::correlate(correlation_id, async move {
                     let inst = Instant::now();
-                    let hevent = hevent.unwrap_or_default();
-                    let resp = if hevent != 0 {
-                        if let Some(rv) = futures::cancel_by_event(fut, hevent).await {
-                            rv
-                        } else {
-                            HttpResponseInner::cancelled()
-                        }
-                    } else {
-                        fut.await
-                    };
+                    let resp = fut.await;
+                    #[cfg(feature = "log")]
+                    debug!("Request completed in {}ms", inst.elapsed().as_millis());
                     (resp, inst.elapsed().as_millis())
-                })
+                }))
                 .unwrap();
             HttpResponse::new_object_modify(self.get_session(), |obj| {
                 obj.init(resp, elapsed, None, self.recv_file_path.take())
             })
         } else {
+            let err_info = match self.error.text() {
+                "" => tr("invalid request object", "无效的请求对象").to_owned(),
+                text => text.to_owned()
+            };
             HttpResponse::new_object_modify(self.get_session(), |obj| {
-                obj.init(
-                    HttpResponseInner::send_error("invalid request object"),
-                    0,
-                    None,
-                    self.recv_file_path.take()
-                )
+                obj.init(HttpResponseInner::send_error(err_info), 0, None, self.recv_file_path.take())
             })
         }
     }
 
-    #[method(name = "AsyncSend", overload = 1)]
-    fn async_send(&mut self, id: pbulong, progress: Option<bool>) -> RetCode {
+    /// 异步发送请求
+    ///
+    /// # Parameters
+    ///
+    /// - `id` 请求ID，用于`Cancel`以及完成事件
+    /// - `progress` 是否触发进度事件，默认`false`
+    /// - `hevent` 用于取消请求的`Win32 Event`句柄，触发信号时取消(包括等待并发许可)，默认`0`表示不使用
+    ///
+    /// # Description
+    ///
+    /// 通过`hevent`取消时与`Cancel`相同，只触发`OnComplete`且响应的`IsCancelled`返回`true`，
+    /// 使用`hevent`的副本等待，发送后可以关闭原句柄，`hevent`无效时返回`E_INVALID_HANDLE`且请求对象失效
+    ///
+    /// 客户端调用`SetRejectDuplicateId(true)`且`id`的请求未完成时返回`E_BUSY`，请求对象仍然有效
+    #[method(name = "AsyncSend", overload = 2)]
+    fn async_send(&mut self, id: pbulong, progress: Option<bool>, hevent: Option<pbulong>) -> RetCode {
+        if let Some(rv) = self.reject_duplicate(id) {
+            return rv;
+        }
         if let Some(HttpRequestInner {
             client,
             builder
         }) = self.inner.take()
         {
-            let client = client.get_native_ref::<HttpClient>().expect("invalid httpclient");
+            let mut client = client.get_native_mut::<HttpClient>().expect("invalid httpclient");
+            //使用客户端的认证令牌时不再读取凭据管理器
+            let builder = if self.auth_set || client.uses_auth_token() {
+                builder
+            } else {
+                builder.map(|builder| client.apply_vault_credential(builder))
+            };
             let recv_file_path = self.recv_file_path.clone();
             //执行顺序锁
             let semaphore = client.semaphore.clone();
-            let fut = if progress.unwrap_or_default() {
-                Either::Left(self.send_with_progress_impl(
-                    id,
-                    &client,
-                    builder.unwrap(),
-                    recv_file_path.clone()
-                ))
-            } else {
-                Either::Right(self.send_impl(builder.unwrap(), recv_file_path.clone()))
+            let opts = self.send_options(id, &client, progress.unwrap_or_default());
+            let builder = builder.unwrap();
+            let intercept_send = client.intercept_send;
+            let invoker = client.invoker();
+            let fut = async move {
+                let builder = if intercept_send {
+                    //发送前在`UI`线程执行拦截
+                    match invoker
+                        .invoke(builder, move |this, builder| this.apply_before_send(id, builder))
+                        .await
+                        .await
+                    {
+                        Ok(Some(builder)) => builder,
+                        Ok(None) => return HttpResponseInner::cancelled(CancelReason::User),
+                        Err(InvokeError::TargetIsDead | InvokeError::Dropped) => {
+                            return HttpResponseInner::cancelled(CancelReason::Shutdown)
+                        },
+                        Err(InvokeError::Panic) => panic!("Callback panic at OnBeforeSend")
+                    }
+                } else {
+                    builder
+                };
+                match Self::notify_start(builder, id, &invoker).await {
+                    Ok(builder) => Self::send_with(builder, opts).await,
+                    Err(resp) => resp
+                }
             };
-            let cancel_hdl = client.spawn(
-                async move {
-                    let _permit = semaphore.acquire().await;
-                    let inst = Instant::now();
-                    let resp = fut.await;
-                    (id, resp, inst.elapsed().as_millis())
+            let correlation_id = self.correlation_id.take().unwrap_or_else(|| id.to_string());
+            let fut = async move {
+                let _permit = semaphore.acquire().await;
+                let inst = Instant::now();
+                #[cfg(feature = "log")]
+                debug!("Request started");
+                let resp = fut.await;
+                #[cfg(feature = "log")]
+                debug!("Request completed in {}ms", inst.elapsed().as_millis());
+                (id, resp, inst.elapsed().as_millis())
+            };
+            let fut = match hevent.unwrap_or_default() {
+                0 => Either::Left(fut),
+                hevent => {
+                    match futures::cancel_by_event(fut, hevent) {
+                        Ok(fut) => {
+                            Either::Right(fut.map(move |rv| {
+                                rv.unwrap_or_else(|| {
+                                    (id, HttpResponseInner::cancelled(CancelReason::User), 0)
+                                })
+                            }))
+                        },
+                        Err(e) => {
+                            return self.error.set(RetCode::E_INVALID_HANDLE, format!("invalid hevent: {e}"))
+                        },
+                    }
                 },
-                move |this, (id, resp, elapsed)| {
-                    this.complete(id, resp, elapsed, recv_file_path);
-                }
-            );
+            };
+            let fut = futures::correlate(correlation_id, fut);
+            let handler = move |this: &mut HttpClient, (id, resp, elapsed)| {
+                this.complete(id, resp, elapsed, recv_file_path);
+            };
+            let cancel_hdl = match self.tag.take() {
+                Some(tag) => client.spawn_tagged(tag, fut, handler),
+                None => client.spawn(fut, handler)
+            };
             client.push_pending(id, cancel_hdl, self.recv_file_path.take());
             RetCode::OK
         } else {
@@ -239,32 +808,607 @@ impl HttpRequest {
         }
     }
 
-    /// 请求实现
-    fn send_impl(
-        &mut self,
+    /// 开始条件轮询
+    ///
+    /// # Parameters
+    ///
+    /// - `id` 请求ID，用于`Cancel`停止轮询以及事件
+    /// - `interval` 轮询间隔(秒)，立即发送第一次请求
+    ///
+    /// # Description
+    ///
+    /// 每次请求附加上一次响应的`ETag`(`If-None-Match`)与`Last-Modified`(`If-Modified-Since`)，
+    /// 响应`304`或内容与上一次相同时不触发事件，内容变化时触发`OnSuccess`，失败时触发`OnError`并继续轮询，
+    /// 调用`Cancel`后触发`OnComplete`，不触发进度事件，忽略`SetReceiveFile`
+    #[method(name = "StartConditionalPoll")]
+    fn start_conditional_poll(&mut self, id: pbulong, interval: pbdouble) -> RetCode {
+        let interval = match Duration::try_from_secs_f64(interval) {
+            Ok(interval) if !interval.is_zero() => interval,
+            _ => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid interval: {interval}"))
+        };
+        if let Some(rv) = self.reject_duplicate(id) {
+            return rv;
+        }
+        let Some(HttpRequestInner {
+            client,
+            builder: Some(builder)
+        }) = self.inner.take()
+        else {
+            return RetCode::E_INVALID_OBJECT;
+        };
+        let mut client = client.get_native_mut::<HttpClient>().expect("invalid httpclient");
+        //使用客户端的认证令牌时不再读取凭据管理器
+        let builder = if self.auth_set || client.uses_auth_token() {
+            builder
+        } else {
+            client.apply_vault_credential(builder)
+        };
+        let mut opts = self.send_options(id, &client, false);
+        opts.recv_file_path = None;
+        opts.stream = None;
+        opts.chunk_event = false;
+        let fut = Self::poll(builder, opts, interval, client.semaphore.clone());
+        let correlation_id = self.correlation_id.take().unwrap_or_else(|| id.to_string());
+        let fut = futures::correlate(correlation_id, fut);
+        let handler = move |this: &mut HttpClient, resp| this.complete(id, resp, 0, None);
+        let cancel_hdl = match self.tag.take() {
+            Some(tag) => client.spawn_tagged(tag, fut, handler),
+            None => client.spawn(fut, handler)
+        };
+        client.push_pending(id, cancel_hdl, None);
+        RetCode::OK
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+
+    /// 发送参数
+    fn send_options(&self, id: pbulong, client: &HttpClient, progress: bool) -> SendOptions {
+        SendOptions {
+            id,
+            progress,
+            recv_file_path: self.recv_file_path.clone(),
+            partial: self.partial_file,
+            resume: self.resume_offset,
+            segments: self.segments,
+            stream: self.stream_watermark,
+            chunk_event: self.chunk_event,
+            retry: client.retry,
+            receive: client.receive,
+            decompression: client.decompression,
+            envelope: client.envelope.clone(),
+            default_headers: if self.clear_default_headers || client.default_headers.is_empty() {
+                None
+            } else {
+                Some(client.default_headers.clone())
+            },
+            auth: if !self.auth_set && client.uses_auth_token() {
+                Some(client.auth.clone())
+            } else {
+                None
+            },
+            auth_refresh: client.auth_refresh,
+            windows_auth: self.windows_auth.unwrap_or(client.windows_auth),
+            system_proxy: client.system_proxy.clone(),
+            hmac: self.hmac_signing.clone().unwrap_or_else(|| client.hmac_signing.clone()),
+            sign: client.signer.is_some(),
+            proxy_auth: if client.proxy_auth_prompt {
+                client.proxy_credential.clone()
+            } else {
+                None
+            },
+            invoker: client.invoker()
+        }
+    }
+
+    /// 发送请求
+    ///
+    /// 附加客户端的默认请求头，设置了签名器时签名，配置了响应信封时按信封分类响应
+    pub(super) fn send_with(
+        builder: RequestBuilder,
+        opts: SendOptions
+    ) -> impl Future<Output = HttpResponseInner> {
+        let envelope = opts.envelope.clone();
+        let builder = match opts.default_headers.as_deref() {
+            Some(headers) => with_default_headers(builder, headers),
+            None => Ok(builder)
+        };
+        //请求未指定时声明支持的压缩格式
+        let builder = match opts.decompression.accept_encoding() {
+            Some(value) => {
+                builder.and_then(|builder| {
+                    with_default_headers(builder, &HeaderMap::from_iter([(header::ACCEPT_ENCODING, value)]))
+                })
+            },
+            None => builder
+        };
+        async move {
+            //在阻塞线程中预先计算系统代理，连接时不阻塞运行时
+            let builder = match (builder, opts.system_proxy.as_ref()) {
+                (Ok(builder), Some(proxy)) => prefetch_proxy(builder, proxy).await,
+                (builder, _) => builder
+            };
+            let resp = match builder {
+                Ok(builder) => {
+                    match Self::sign_with(builder, &opts).await {
+                        Ok(builder) => Self::send_authorized(builder, opts).await,
+                        Err(resp) => resp
+                    }
+                },
+                Err(e) => HttpResponseInner::send_error(send_error_info(&e))
+            };
+            match envelope {
+                Some(envelope) => resp.unwrap_envelope(&envelope),
+                None => resp
+            }
+        }
+    }
+
+    /// 发送请求
+    ///
+    /// 使用客户端的认证令牌时附加令牌，响应`401`或`403`时刷新令牌并重试一次
+    fn send_authorized(
         builder: RequestBuilder,
-        recv_file_path: Option<String>
+        opts: SendOptions
     ) -> impl Future<Output = HttpResponseInner> {
         async move {
-            match builder.send().await {
-                Ok(resp) => HttpResponseInner::receive(resp, recv_file_path).await,
-                Err(e) => HttpResponseInner::send_error(e)
+            let Some(auth) = opts.auth.clone() else {
+                return Self::send_proxy_authorized(builder, opts).await;
+            };
+            let (version, token) = auth.get();
+            //请求体为流时无法复制，不重试
+            let retry_builder = if opts.auth_refresh {
+                builder.try_clone()
+            } else {
+                None
+            };
+            let resp = Self::send_proxy_authorized(with_token(builder, token), opts.clone()).await;
+            let Some(retry_builder) = retry_builder else {
+                return resp;
+            };
+            if !matches!(resp.status(), Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)) {
+                return resp;
+            }
+            match auth.refresh(version, opts.id, &opts.invoker).await {
+                Some(token) => {
+                    #[cfg(feature = "log")]
+                    debug!("Auth token refreshed, retry request");
+                    Self::send_proxy_authorized(with_token(retry_builder, Some(token)), opts).await
+                },
+                None => resp
+            }
+        }
+    }
+
+    /// 发送请求
+    ///
+    /// 代理认证失败(`407`)时询问凭据并重试一次
+    fn send_proxy_authorized(
+        builder: RequestBuilder,
+        opts: SendOptions
+    ) -> impl Future<Output = HttpResponseInner> {
+        async move {
+            let Some(credential) = opts.proxy_auth.clone() else {
+                return Self::send_negotiated(builder, opts).await;
+            };
+            let (version, _) = credential.get();
+            //请求体为流时无法复制，不重试
+            let Some(retry_builder) = builder.try_clone() else {
+                return Self::send_negotiated(builder, opts).await;
+            };
+            let resp = Self::send_negotiated(builder, opts.clone()).await;
+            if resp.status() != Some(StatusCode::PROXY_AUTHENTICATION_REQUIRED) {
+                return resp;
+            }
+            let realm = resp.headers().and_then(proxy_auth_realm).unwrap_or_default();
+            if credential.prompt(version, opts.id, realm, &opts.invoker).await {
+                #[cfg(feature = "log")]
+                debug!("Proxy credential updated, retry request");
+                Self::send_negotiated(retry_builder, opts).await
+            } else {
+                resp
+            }
+        }
+    }
+
+    /// 发送请求
+    ///
+    /// 启用了`Windows`集成认证且服务器要求认证(`401`)时通过`SSPI`完成质询握手，每一步重发一次请求
+    fn send_negotiated(
+        builder: RequestBuilder,
+        opts: SendOptions
+    ) -> impl Future<Output = HttpResponseInner> {
+        /// 最多握手的步数
+        const MAX_STEPS: usize = 3;
+        async move {
+            let Some(package) = opts.windows_auth else {
+                return Self::send_once(builder, opts).await;
+            };
+            let (client, req) = builder.build_split();
+            let req = match req {
+                Ok(req) => req,
+                Err(e) => return HttpResponseInner::send_error(send_error_info(&e))
+            };
+            //请求体为流时无法复制，不认证
+            let Some(template) = req.try_clone() else {
+                return Self::send_once(RequestBuilder::from_parts(client, req), opts).await;
+            };
+            let host = req.url().host_str().unwrap_or_default().to_owned();
+            let mut resp =
+                Self::send_once(RequestBuilder::from_parts(client.clone(), req), opts.clone()).await;
+            let mut context: Option<SspiContext> = None;
+            for _ in 0..MAX_STEPS {
+                if resp.status() != Some(StatusCode::UNAUTHORIZED) {
+                    break;
+                }
+                let challenge = match resp.headers().and_then(|headers| package.challenge(headers)) {
+                    Some(challenge) => challenge,
+                    None => break
+                };
+                //握手已开始时服务器不再返回令牌表示拒绝认证
+                if context.is_some() && challenge.is_none() {
+                    break;
+                }
+                let mut ctx = match context.take() {
+                    Some(ctx) => ctx,
+                    None => {
+                        match SspiContext::new(package, &host) {
+                            Ok(ctx) => ctx,
+                            Err(e) => return HttpResponseInner::send_error(format!("SSPI: {e}"))
+                        }
+                    },
+                };
+                //可能需要访问域控制器
+                let (ctx, rv) = match spawn_blocking(move || {
+                    let rv = ctx.step(challenge.as_deref());
+                    (ctx, rv)
+                })
+                .await
+                {
+                    Ok(rv) => rv,
+                    Err(e) => return HttpResponseInner::send_error(format!("SSPI: {e}"))
+                };
+                let token = match rv {
+                    Ok((token, _)) => token,
+                    Err(e) => return HttpResponseInner::send_error(format!("SSPI: {e}"))
+                };
+                context = Some(ctx);
+                let Some(mut req) = template.try_clone() else {
+                    break;
+                };
+                let value = format!("{} {}", package.scheme(), BASE64.encode(token));
+                match HeaderValue::try_from(value) {
+                    Ok(value) => req.headers_mut().insert(header::AUTHORIZATION, value),
+                    Err(e) => return HttpResponseInner::send_error(format!("SSPI: {e}"))
+                };
+                #[cfg(feature = "log")]
+                debug!("Windows auth step, retry request");
+                resp = Self::send_once(RequestBuilder::from_parts(client.clone(), req), opts.clone()).await;
+            }
+            resp
+        }
+    }
+
+    /// 附加`HMAC`签名后在UI线程中调用客户端的签名器
+    async fn sign_with(
+        builder: RequestBuilder,
+        opts: &SendOptions
+    ) -> StdResult<RequestBuilder, HttpResponseInner> {
+        let builder = match opts.hmac.as_deref() {
+            Some(signing) => with_hmac_signature(builder, signing)?,
+            None => builder
+        };
+        if !opts.sign {
+            return Ok(builder);
+        }
+        let (client, req) = builder.build_split();
+        let req = req.map_err(|e| HttpResponseInner::send_error(send_error_info(&e)))?;
+        let canonical = CanonicalRequest::new(&req);
+        let rv = match opts.invoker.invoke(canonical, |this, canonical| this.sign(&canonical)).await.await {
+            Ok(rv) => rv,
+            Err(InvokeError::TargetIsDead | InvokeError::Dropped) => {
+                return Err(HttpResponseInner::cancelled(CancelReason::Shutdown));
+            },
+            Err(InvokeError::Panic) => panic!("Callback panic at OnSign")
+        };
+        with_signature(RequestBuilder::from_parts(client, req), rv)
+    }
+
+    /// 在当前(UI)线程中调用客户端的签名器
+    fn sign_now(
+        builder: RequestBuilder,
+        client: &HttpClient
+    ) -> StdResult<RequestBuilder, HttpResponseInner> {
+        let (raw_client, req) = builder.build_split();
+        let req = req.map_err(|e| HttpResponseInner::send_error(send_error_info(&e)))?;
+        let rv = client.sign(&CanonicalRequest::new(&req));
+        with_signature(RequestBuilder::from_parts(raw_client, req), rv)
+    }
+
+    /// 触发`OnAnyRequestStart`
+    ///
+    /// # Returns
+    ///
+    /// 请求构造失败或对象已销毁时返回结束的响应
+    pub(super) async fn notify_start(
+        builder: RequestBuilder,
+        id: pbulong,
+        invoker: &HandlerInvoker<HttpClient>
+    ) -> StdResult<RequestBuilder, HttpResponseInner> {
+        let (client, req) = builder.build_split();
+        let req = req.map_err(|e| HttpResponseInner::send_error(send_error_info(&e)))?;
+        let target = (req.method().to_string(), req.url().to_string());
+        match invoker
+            .invoke(target, move |this, (method, url)| this.on_any_request_start(id, method, url))
+            .await
+            .await
+        {
+            Ok(()) => Ok(RequestBuilder::from_parts(client, req)),
+            Err(InvokeError::TargetIsDead | InvokeError::Dropped) => {
+                Err(HttpResponseInner::cancelled(CancelReason::Shutdown))
+            },
+            Err(InvokeError::Panic) => panic!("Callback panic at OnAnyRequestStart")
+        }
+    }
+
+    /// 条件轮询
+    ///
+    /// # Returns
+    ///
+    /// 请求无法复制或对象已销毁时结束
+    async fn poll(
+        builder: RequestBuilder,
+        opts: SendOptions,
+        interval: Duration,
+        semaphore: Arc<Semaphore>
+    ) -> HttpResponseInner {
+        let id = opts.id;
+        let builder = match Self::notify_start(builder, id, &opts.invoker).await {
+            Ok(builder) => builder,
+            Err(resp) => return resp
+        };
+        let mut etag: Option<HeaderValue> = None;
+        let mut last_modified: Option<HeaderValue> = None;
+        //服务器不支持条件请求时比较内容
+        let mut digest = None;
+        let mut ticker = time::interval(interval);
+        ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let Some(mut req) = builder.try_clone() else {
+                return HttpResponseInner::send_error(tr(
+                    "request body is not cloneable",
+                    "请求内容无法复制"
+                ));
+            };
+            if let Some(etag) = etag.as_ref() {
+                req = req.header(header::IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = last_modified.as_ref() {
+                req = req.header(header::IF_MODIFIED_SINCE, last_modified.clone());
+            }
+            let (resp, elapsed) = {
+                let _permit = semaphore.acquire().await;
+                let inst = Instant::now();
+                let resp = Self::send_with(req, opts.clone()).await;
+                (resp, inst.elapsed().as_millis())
+            };
+            if resp.status() == Some(StatusCode::NOT_MODIFIED) {
+                continue;
+            }
+            if let HttpResponseInner::Received {
+                status,
+                headers,
+                data,
+                ..
+            } = &resp
+            {
+                if status.is_success() {
+                    etag = headers.get(header::ETAG).cloned();
+                    last_modified = headers.get(header::LAST_MODIFIED).cloned();
+                    let mut hasher = DefaultHasher::new();
+                    data.hash(&mut hasher);
+                    let current = Some(hasher.finish());
+                    if current == digest {
+                        continue;
+                    }
+                    digest = current;
+                }
+            }
+            match opts
+                .invoker
+                .invoke((resp, elapsed), move |this, (resp, elapsed)| this.poll_result(id, resp, elapsed))
+                .await
+                .await
+            {
+                Ok(()) => {},
+                Err(InvokeError::TargetIsDead | InvokeError::Dropped) => {
+                    return HttpResponseInner::cancelled(CancelReason::Shutdown)
+                },
+                Err(InvokeError::Panic) => panic!("Callback panic at conditional poll")
+            }
+        }
+    }
+
+    /// 按参数选择请求实现
+    fn send_once(builder: RequestBuilder, opts: SendOptions) -> impl Future<Output = HttpResponseInner> {
+        if opts.segments > 1 &&
+            opts.recv_file_path.is_some() &&
+            opts.resume.is_none() &&
+            opts.stream.is_none() &&
+            !opts.chunk_event
+        {
+            Either::Left(Self::send_segmented_impl(builder, opts))
+        } else {
+            Either::Right(Self::send_plain(builder, opts))
+        }
+    }
+
+    /// 不分段发送请求
+    fn send_plain(builder: RequestBuilder, opts: SendOptions) -> impl Future<Output = HttpResponseInner> {
+        if opts.progress && opts.stream.is_none() && !opts.chunk_event {
+            Either::Left(Self::send_with_progress_impl(builder, opts))
+        } else {
+            Either::Right(Self::send_impl(builder, opts))
+        }
+    }
+
+    /// 分段下载实现
+    ///
+    /// 先请求第一个字节获取文件大小，服务器不支持范围请求时按普通请求重新发送
+    async fn send_segmented_impl(builder: RequestBuilder, opts: SendOptions) -> HttpResponseInner {
+        let (client, req) = builder.build_split();
+        let mut req = match req {
+            Ok(req) => req,
+            Err(e) => return HttpResponseInner::send_error(send_error_info(&e))
+        };
+        //分段的范围对应未压缩的内容
+        req.headers_mut().insert(header::ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+        //请求体为流时无法复制，不分段
+        let Some(mut probe) = req.try_clone() else {
+            return Self::send_plain(RequestBuilder::from_parts(client, req), opts).await;
+        };
+        probe.headers_mut().insert(header::RANGE, HeaderValue::from_static("bytes=0-0"));
+        let resp = match client.execute(probe).await {
+            Ok(resp) => resp,
+            Err(e) => return send_failure(&e)
+        };
+        let total_size = match resp.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                response::content_range_total(resp.headers()).filter(|&size| size > 0)
+            },
+            _ => None
+        };
+        let (Some(total_size), Some(file_path)) = (total_size, opts.recv_file_path.clone()) else {
+            drop(resp);
+            return Self::send_plain(RequestBuilder::from_parts(client, req), opts).await;
+        };
+        let cert = peer_certificate(&resp);
+        let download = SegmentedDownload {
+            client,
+            req,
+            file_path,
+            total_size,
+            segments: opts.segments as u64,
+            partial: opts.partial
+        };
+        HttpResponseInner::receive_segmented(opts.id, opts.invoker, opts.progress, download, resp)
+            .await
+            .with_peer_certificate(cert)
+    }
+
+    /// 请求实现
+    ///
+    /// 连接失败、超时或服务器暂时不可用时按`retry`策略重试
+    fn send_impl(builder: RequestBuilder, opts: SendOptions) -> impl Future<Output = HttpResponseInner> {
+        let SendOptions {
+            id,
+            recv_file_path,
+            partial,
+            resume,
+            stream,
+            chunk_event,
+            retry,
+            receive,
+            decompression,
+            invoker,
+            ..
+        } = opts;
+        async move {
+            let mut builder = builder;
+            let mut attempt = 0;
+            let rv = loop {
+                //请求体为流时无法复制，不重试
+                let next = if attempt < retry.max_retries {
+                    builder.try_clone()
+                } else {
+                    None
+                };
+                let rv = builder.send().await;
+                let retryable = match &rv {
+                    Ok(resp) => {
+                        matches!(
+                            resp.status(),
+                            StatusCode::BAD_GATEWAY |
+                                StatusCode::SERVICE_UNAVAILABLE |
+                                StatusCode::GATEWAY_TIMEOUT
+                        )
+                    },
+                    Err(e) => e.is_connect() || e.is_timeout()
+                };
+                match next {
+                    Some(next) if retryable => {
+                        let delay = retry.delay.saturating_mul(1 << attempt.min(16));
+                        #[cfg(feature = "log")]
+                        warn!("Request failed, retry {} in {}ms", attempt + 1, delay.as_millis());
+                        time::sleep(delay).await;
+                        builder = next;
+                        attempt += 1;
+                    },
+                    _ => break rv
+                }
+            };
+            match rv {
+                Ok(resp) => {
+                    let cert = peer_certificate(&resp);
+                    let (resp, encoding) = decompression.decompress(resp);
+                    match stream {
+                        Some(watermark) => HttpResponseInner::streaming(resp, watermark),
+                        None if chunk_event => HttpResponseInner::deliver_chunks(id, invoker, resp).await,
+                        None => {
+                            HttpResponseInner::receive(resp, recv_file_path, resume, receive, partial).await
+                        },
+                    }
+                    .with_peer_certificate(cert)
+                    .with_content_encoding(encoding)
+                },
+                Err(e) => {
+                    #[cfg(feature = "log")]
+                    warn!("Send request failed: {}", e);
+                    send_failure(&e)
+                }
             }
         }
     }
 
     /// 带进度回调的请求实现
     fn send_with_progress_impl(
-        &mut self,
-        id: pbulong,
-        client: &HttpClient,
         builder: RequestBuilder,
-        recv_file_path: Option<String>
+        opts: SendOptions
     ) -> impl Future<Output = HttpResponseInner> {
-        let invoker = client.invoker();
+        let SendOptions {
+            id,
+            recv_file_path,
+            partial,
+            resume,
+            receive,
+            decompression,
+            invoker,
+            ..
+        } = opts;
         async move {
             match Self::execute_request_with_progress(id, builder, invoker.clone()).await {
-                Ok(resp) => HttpResponseInner::receive_with_progress(id, invoker, resp, recv_file_path).await,
+                Ok(resp) => {
+                    let cert = peer_certificate(&resp);
+                    let (resp, encoding) = decompression.decompress(resp);
+                    HttpResponseInner::receive_with_progress(
+                        id,
+                        invoker,
+                        resp,
+                        recv_file_path,
+                        resume,
+                        receive,
+                        partial
+                    )
+                    .await
+                    .with_peer_certificate(cert)
+                    .with_content_encoding(encoding)
+                },
                 Err(e) => e
             }
         }
@@ -278,7 +1422,7 @@ impl HttpRequest {
     ) -> StdResult<Response, HttpResponseInner> {
         let (raw_client, mut req) = match builder.build_split() {
             (cli, Ok(req)) => (cli, req),
-            (_, Err(e)) => return Err(HttpResponseInner::send_error(e))
+            (_, Err(e)) => return Err(HttpResponseInner::send_error(send_error_info(&e)))
         };
         let mut total_size = 0;
         let sent_size = Arc::new(AtomicU64::new(0));
@@ -334,7 +1478,7 @@ impl HttpRequest {
                             continue;
                         },
                         Err(e) => {
-                            return Err(HttpResponseInner::send_error(e));
+                            return Err(send_failure(&e));
                         }
                     }
                 },
@@ -346,7 +1490,8 @@ impl HttpRequest {
                     //UI线程阻塞时截流，丢弃中间的速率
                     if matches!(tick_invoke, Either::Left(_)) {
                         tick_invoke = Either::Right(
-                            invoker.invoke(
+                            invoker.invoke_coalesced(
+                                        id as u64,
                                         (id, total_size, sent_size, speed),
                                         |this, (id, total_size, sent_size, speed)| {
                                             this.on_send(
@@ -375,10 +1520,14 @@ impl HttpRequest {
                         Ok(rv) => {
                             //取消
                             if rv == RetCode::PREVENT {
-                                return Err(HttpResponseInner::cancelled());
+                                return Err(HttpResponseInner::cancelled(CancelReason::User));
                             }
                         },
-                        Err(InvokeError::TargetIsDead) => return Err(HttpResponseInner::cancelled()),
+                        Err(InvokeError::TargetIsDead) => {
+                            return Err(HttpResponseInner::cancelled(CancelReason::Shutdown))
+                        },
+                        //被消息队列丢弃的进度通知
+                        Err(InvokeError::Dropped) => {},
                         Err(InvokeError::Panic) => panic!("Callback panic at OnSend")
                     }
                     #[allow(unused_assignments)]
@@ -397,6 +1546,122 @@ struct HttpRequestInner {
     builder: Option<RequestBuilder>
 }
 
+/// 发送参数
+#[derive(Clone)]
+pub(super) struct SendOptions {
+    id: pbulong,
+    /// 触发进度事件
+    progress: bool,
+    recv_file_path: Option<String>,
+    partial: PartialFilePolicy,
+    /// 断点续传的起始位置
+    resume: Option<u64>,
+    /// 分段下载的最大分段数
+    segments: usize,
+    /// 流式读取的缓冲水位
+    stream: Option<usize>,
+    /// 通过`OnDataChunk`逐块接收
+    chunk_event: bool,
+    retry: RetryPolicy,
+    receive: ReceivePolicy,
+    /// 透明解压的压缩格式
+    decompression: Decompression,
+    envelope: Option<Arc<EnvelopePolicy>>,
+    /// 客户端的默认请求头，请求调用`ClearDefaultHeaders`时为`None`
+    default_headers: Option<Arc<HeaderMap>>,
+    /// 客户端的认证令牌，请求已设置认证信息时为`None`
+    auth: Option<Arc<AuthToken>>,
+    /// 认证失败时刷新令牌并重试
+    auth_refresh: bool,
+    /// `Windows`集成认证的认证包
+    windows_auth: Option<SspiPackage>,
+    /// 客户端的系统代理
+    system_proxy: Option<Arc<SystemProxy>>,
+    /// `HMAC`签名
+    hmac: Option<Arc<HmacSigning>>,
+    /// 客户端的代理凭据，代理认证失败时询问凭据并重试
+    proxy_auth: Option<Arc<ProxyCredential>>,
+    /// 发送前调用客户端的签名器
+    sign: bool,
+    invoker: HandlerInvoker<HttpClient>
+}
+
+/// 附加默认请求头，请求已设置的同名请求头不变
+fn with_default_headers(builder: RequestBuilder, headers: &HeaderMap) -> ReqwestResult<RequestBuilder> {
+    let (client, req) = builder.build_split();
+    let mut req = req?;
+    for (name, value) in headers {
+        if !req.headers().contains_key(name) {
+            req.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    Ok(RequestBuilder::from_parts(client, req))
+}
+
+/// 附加签名请求头
+fn with_signature(
+    builder: RequestBuilder,
+    rv: StdResult<Vec<(HeaderName, HeaderValue)>, SignError>
+) -> StdResult<RequestBuilder, HttpResponseInner> {
+    match rv {
+        Ok(headers) => {
+            Ok(headers.into_iter().fold(builder, |builder, (name, value)| builder.header(name, value)))
+        },
+        Err(SignError::Cancelled) => Err(HttpResponseInner::cancelled(CancelReason::User)),
+        Err(SignError::Failed(e)) => {
+            Err(HttpResponseInner::send_error(match locale::current() {
+                Locale::En => format!("sign request failed: {e}"),
+                Locale::ZhCN => format!("请求签名失败: {e}")
+            }))
+        },
+    }
+}
+
+/// 预先计算请求地址使用的系统代理
+async fn prefetch_proxy(builder: RequestBuilder, proxy: &Arc<SystemProxy>) -> ReqwestResult<RequestBuilder> {
+    let (client, req) = builder.build_split();
+    let req = req?;
+    proxy.prefetch(req.url()).await;
+    Ok(RequestBuilder::from_parts(client, req))
+}
+
+/// 附加`HMAC`签名请求头
+fn with_hmac_signature(
+    builder: RequestBuilder,
+    signing: &HmacSigning
+) -> StdResult<RequestBuilder, HttpResponseInner> {
+    let (client, req) = builder.build_split();
+    let req = req.map_err(|e| HttpResponseInner::send_error(send_error_info(&e)))?;
+    let rv = signing.sign(&req);
+    with_signature(RequestBuilder::from_parts(client, req), rv)
+}
+
+/// 附加认证令牌
+fn with_token(builder: RequestBuilder, token: Option<String>) -> RequestBuilder {
+    match token {
+        Some(token) => builder.bearer_auth(token),
+        None => builder
+    }
+}
+
+/// 从文件流式读取请求体
+fn file_stream(file: File) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+    /// 每次读取的字节数
+    const CHUNK_SIZE: usize = 64 * 1024;
+    stream::unfold(Some(file), |file| {
+        async move {
+            let mut file = file?;
+            let mut buf = BytesMut::with_capacity(CHUNK_SIZE);
+            match file.read_buf(&mut buf).await {
+                Ok(0) => None,
+                Ok(_) => Some((Ok(buf.freeze()), Some(file))),
+                //读取失败后结束
+                Err(e) => Some((Err(e), None))
+            }
+        }
+    })
+}
+
 /// 封装HttpBody捕获发送字节数
 struct HttpBodyProgress {
     body: Body,
@@ -436,3 +1701,60 @@ impl Stream for HttpBodyProgress {
         (hint.lower() as usize, hint.upper().map(|v| v as usize))
     }
 }
+
+/// 服务器证书(`DER`)
+fn peer_certificate(resp: &Response) -> Option<Bytes> {
+    resp.extensions().get::<TlsInfo>().and_then(TlsInfo::peer_certificate).map(Bytes::copy_from_slice)
+}
+
+/// 发送失败的响应
+///
+/// `HTTPS`请求建立代理隧道时被拒绝(`407`)视为代理返回的响应，此时没有响应头
+fn send_failure(e: &reqwest::Error) -> HttpResponseInner {
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        if err.to_string() == "proxy authentication required" {
+            return HttpResponseInner::receive_error(
+                StatusCode::PROXY_AUTHENTICATION_REQUIRED,
+                HeaderMap::new(),
+                send_error_info(e)
+            );
+        }
+        source = err.source();
+    }
+    HttpResponseInner::send_error(send_error_info(e))
+}
+
+/// 解析`Proxy-Authenticate`头中的认证域
+fn proxy_auth_realm(headers: &HeaderMap) -> Option<String> {
+    headers.get_all(header::PROXY_AUTHENTICATE).iter().find_map(|value| {
+        let value = value.to_str().ok()?;
+        let (_, rest) = value.split_once("realm=")?;
+        let realm = match rest.strip_prefix('"') {
+            Some(rest) => rest.split('"').next()?,
+            None => rest.split(',').next()?.trim()
+        };
+        Some(realm.to_owned())
+    })
+}
+
+/// 发送失败的错误信息
+///
+/// 中文环境下在原始错误信息前附加失败类型
+fn send_error_info(e: &reqwest::Error) -> String {
+    if locale::current() == Locale::En {
+        return e.to_string();
+    }
+    let kind = if e.is_timeout() {
+        "请求超时"
+    } else if e.is_connect() {
+        "连接失败"
+    } else if e.is_builder() {
+        "无效的请求"
+    } else if e.is_redirect() {
+        "重定向失败"
+    } else {
+        "发送失败"
+    };
+    format!("{kind}: {e}")
+}
@@ -1,24 +1,52 @@
 use super::*;
 use crate::{
-    base::{conv, pfw}, reactor::HandlerInvoker
+    base::{
+        cert::CertInfo, conv, diag::{LiveObject, MemoryUsage}, id, locale::{self, tr, Locale}
+    }, pbx::{json, xml}, reactor::HandlerInvoker
 };
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use bytes::{Bytes, BytesMut};
 use futures_util::future::{self, Either, FutureExt};
 use mime::Mime;
 use reqwest::{
-    header::{self, HeaderMap}, Response, StatusCode
+    header::{self, HeaderMap, HeaderValue}, Client, Request, Response, StatusCode
+};
+use std::{
+    borrow::Cow, fmt::Display, io::{self, SeekFrom}, sync::atomic::{AtomicU64, Ordering}, thread, time::Duration
 };
-use std::{borrow::Cow, fmt::Display, time::Duration};
 use tokio::{
-    fs::File, io::AsyncWriteExt, task::yield_now, time::{self, Instant}
+    fs::{File, OpenOptions}, io::{AsyncSeekExt, AsyncWriteExt}, task::yield_now, time::{self, Instant}
 };
 
-#[derive(Default)]
 pub struct HttpResponse {
     inner: Option<HttpResponseInner>,
     elapsed: u128,
     async_id: Option<pbulong>,
-    receive_file: Option<String>
+    receive_file: Option<String>,
+    /// 数据超过转存阈值时写入的临时文件，销毁时删除
+    spill_file: Option<String>,
+    /// 缓存的响应数据
+    usage: MemoryUsage,
+    /// 流式读取时最近一次`ReadNext`读取的数据
+    chunk: Bytes,
+    _live: LiveObject,
+    error: LastError
+}
+
+impl Default for HttpResponse {
+    fn default() -> Self {
+        HttpResponse {
+            inner: None,
+            elapsed: 0,
+            async_id: None,
+            receive_file: None,
+            spill_file: None,
+            usage: MemoryUsage::new("http.response"),
+            chunk: Bytes::new(),
+            _live: LiveObject::new("nx_httpresponse"),
+            error: LastError::default()
+        }
+    }
 }
 
 #[nonvisualobject(name = "nx_httpresponse")]
@@ -30,6 +58,19 @@ impl HttpResponse {
         async_id: Option<pbulong>,
         receive_file: Option<String>
     ) {
+        let mut receive_file = receive_file;
+        if let HttpResponseInner::Received {
+            data,
+            spill_file,
+            ..
+        } = &kind
+        {
+            self.usage.set(data.len());
+            if spill_file.is_some() {
+                receive_file = spill_file.clone();
+                self.spill_file = spill_file.clone();
+            }
+        }
         self.inner = Some(kind);
         self.elapsed = elapsed;
         self.async_id = async_id;
@@ -72,6 +113,28 @@ impl HttpResponse {
         }
     }
 
+    fn content_encoding(&self) -> Option<&ContentEncoding> {
+        match self.inner.as_ref() {
+            Some(HttpResponseInner::Received {
+                content_encoding,
+                ..
+            }) => content_encoding.as_ref(),
+            _ => None
+        }
+    }
+
+    /// 服务器接受范围请求时的起始位置
+    fn range_start(&self) -> Option<u64> {
+        match self.inner.as_ref() {
+            Some(HttpResponseInner::Received {
+                status: StatusCode::PARTIAL_CONTENT,
+                headers,
+                ..
+            }) => content_range_start(headers),
+            _ => None
+        }
+    }
+
     fn content_type(&self) -> Option<&Mime> {
         if let Some(inner) = self.inner.as_ref() {
             match inner {
@@ -122,6 +185,22 @@ impl HttpResponse {
         }
     }
 
+    /// 解码数据
+    ///
+    /// 未指定编码时依次使用`Content-Type`中的`charset`参数、`BOM`以及内容检测的编码
+    fn decode_data(&self, encoding: Option<pblong>) -> Cow<'_, str> {
+        let Some(data) = self.data() else {
+            return "".into();
+        };
+        if let Some(encoding) = encoding {
+            return conv::decode(data, encoding);
+        }
+        match self.content_type().and_then(|content_type| content_type.get_param("charset")) {
+            Some(charset) => conv::decode_by_charset(data, charset.as_str()),
+            None => conv::decode_auto(data)
+        }
+    }
+
     #[method(name = "IsValid")]
     fn is_valid(&self) -> bool { self.inner.as_ref().map(HttpResponseInner::is_received).unwrap_or_default() }
 
@@ -135,22 +214,159 @@ impl HttpResponse {
         self.inner.as_ref().map(HttpResponseInner::is_cancelled).unwrap_or_default()
     }
 
+    /// 取消原因
+    ///
+    /// # Returns
+    ///
+    /// - `0` 未取消
+    /// - `1` 用户取消(`Cancel`/`CancelAll`/`CancelTag`、`hevent`或在事件中返回`1`)
+    /// - `2` 客户端对象已销毁
+    /// - `3` 被相同ID的新请求取代
+    #[method(name = "GetCancelReason")]
+    fn cancel_reason(&self) -> pblong {
+        match self.inner.as_ref() {
+            Some(HttpResponseInner::Cancelled {
+                reason
+            }) => *reason as pblong,
+            _ => 0
+        }
+    }
+
+    /// 是否为流式读取(`nx_httprequest.SetStreaming`)
+    #[method(name = "IsStreaming")]
+    fn is_streaming(&self) -> bool { self.stream().is_some() }
+
+    /// 流式读取下一块数据
+    ///
+    /// # Parameters
+    ///
+    /// - `max_bytes` 最多读取的字节数
+    ///
+    /// # Returns
+    ///
+    /// - `OK` 读取成功，通过`GetChunk`获取数据
+    /// - `E_BUSY` 已读完缓冲的数据，仍在接收，稍后再读取
+    /// - `E_DATA_NOT_FOUND` 已读完所有数据
+    /// - `E_IO_ERROR` 接收失败，通过`GetErrorInfo`获取错误信息
+    /// - `E_NO_SUPPORT` 不是流式读取的响应
+    #[method(name = "ReadNext")]
+    fn read_next(&mut self, max_bytes: pbulong) -> RetCode {
+        let Some(stream) = self.stream() else {
+            return self.error.set(RetCode::E_NO_SUPPORT, "not a streaming response");
+        };
+        let rv = stream.read(max_bytes as usize);
+        self.chunk = Bytes::new();
+        match rv {
+            StreamRead::Data(data) => {
+                self.chunk = data;
+                RetCode::OK
+            },
+            StreamRead::Pending => RetCode::E_BUSY,
+            StreamRead::End => RetCode::E_DATA_NOT_FOUND,
+            StreamRead::Error(e) => {
+                let (status, headers) = match self.inner.take() {
+                    Some(HttpResponseInner::Received {
+                        status,
+                        headers,
+                        ..
+                    }) => (status, headers),
+                    _ => unreachable!()
+                };
+                //转换为接收失败
+                self.error.set(RetCode::E_IO_ERROR, &e);
+                self.inner = Some(HttpResponseInner::receive_error(status, headers, e));
+                RetCode::E_IO_ERROR
+            }
+        }
+    }
+
+    /// 最近一次`ReadNext`读取的数据
+    #[method(name = "GetChunk")]
+    fn chunk(&self) -> &[u8] { &self.chunk }
+
+    fn stream(&self) -> Option<&BodyStream> {
+        match self.inner.as_ref() {
+            Some(HttpResponseInner::Received {
+                stream,
+                ..
+            }) => stream.as_ref(),
+            _ => None
+        }
+    }
+
+    /// 服务器证书
+    ///
+    /// # Returns
+    ///
+    /// `JSON`对象，包含`subject`(主题)、`issuer`(颁发者)、`not_before`(生效时间)、`not_after`(过期时间)
+    /// 以及`sha256`(`SHA-256`指纹)，时间为`UTC`的`yyyy-mm-dd hh:mm:ss`，不是`HTTPS`请求或发送失败时返回空字符串
+    #[method(name = "GetPeerCertificate")]
+    fn peer_certificate(&self) -> String {
+        let cert = match self.inner.as_ref() {
+            Some(
+                HttpResponseInner::ReceiveError {
+                    peer_certificate,
+                    ..
+                } |
+                HttpResponseInner::Received {
+                    peer_certificate,
+                    ..
+                }
+            ) => peer_certificate.as_deref(),
+            _ => None
+        };
+        cert.and_then(CertInfo::from_der).map(|info| info.to_json().to_string()).unwrap_or_default()
+    }
+
+    /// `Content-Type`缺失或为通用类型时需要检测内容
+    fn is_content_type_untrusted(&self) -> bool {
+        match self.content_type() {
+            Some(content_type) => {
+                matches!(
+                    content_type.essence_str(),
+                    "application/octet-stream" | "binary/octet-stream" | "text/plain"
+                )
+            },
+            None => true
+        }
+    }
+
+    /// 检测数据的内容类型
+    fn sniff(&self) -> Option<ContentKind> {
+        if self.is_content_type_untrusted() {
+            self.data().and_then(|data| sniff_content(data))
+        } else {
+            None
+        }
+    }
+
+    /// # Description
+    ///
+    /// `Content-Type`缺失或为`application/octet-stream`、`text/plain`时检测数据的开头
     #[method(name = "IsText")]
     fn is_text(&self) -> bool {
         self.content_type()
             .map(|content_type| {
                 content_type.type_() == "text" || content_type.subtype().as_str().ends_with("text")
             })
-            .unwrap_or_default()
+            .unwrap_or_default() ||
+            self.sniff() == Some(ContentKind::Text)
     }
 
+    /// # Description
+    ///
+    /// `Content-Type`缺失或为`application/octet-stream`、`text/plain`时检测数据的开头
     #[method(name = "IsJSON")]
     fn is_json(&self) -> bool {
         self.content_type()
             .map(|content_type| content_type.subtype().as_str().ends_with("json"))
-            .unwrap_or_default()
+            .unwrap_or_default() ||
+            self.sniff() == Some(ContentKind::Json)
     }
 
+    /// # Description
+    ///
+    /// `Content-Type`缺失或为`application/octet-stream`、`text/plain`时检测数据的开头
     #[method(name = "IsXML")]
     fn is_xml(&self) -> bool {
         self.content_type()
@@ -158,11 +374,18 @@ impl HttpResponse {
                 content_type.subtype().as_str().ends_with("xml") ||
                     content_type.suffix().map(|v| v == "xml").unwrap_or_default()
             })
-            .unwrap_or_default()
+            .unwrap_or_default() ||
+            self.sniff() == Some(ContentKind::Xml)
     }
 
+    /// # Description
+    ///
+    /// `Content-Type`缺失或为`application/octet-stream`、`text/plain`时以检测结果为准
     #[method(name = "IsBinary")]
     fn is_binary(&self) -> bool {
+        if self.is_content_type_untrusted() {
+            return self.sniff() == Some(ContentKind::Binary);
+        }
         self.content_type()
             .map(|content_type| content_type.subtype().as_str().ends_with("stream"))
             .unwrap_or_default()
@@ -180,6 +403,28 @@ impl HttpResponse {
     #[method(name = "GetReceiveFile")]
     fn receive_file(&self) -> &str { self.receive_file.as_ref().map(|v| v.as_str()).unwrap_or_default() }
 
+    /// 服务器是否接受了断点续传的范围请求(`nx_httprequest.SetResumeFile`)
+    ///
+    /// 接受时数据追加到接收文件，否则文件被重新写入
+    #[method(name = "IsRangeHonored")]
+    fn is_range_honored(&self) -> bool { self.range_start().is_some() }
+
+    /// 断点续传的起始位置，服务器未接受范围请求时返回`0`
+    #[method(name = "GetRangeStart")]
+    fn range_start_serialize(&self) -> pbulong { self.range_start().unwrap_or_default() as pbulong }
+
+    /// 响应数据是否已透明解压(`nx_httpconfig.SetCompression`)
+    #[method(name = "IsDecompressed")]
+    fn is_decompressed(&self) -> bool { self.content_encoding().is_some() }
+
+    /// 透明解压前的压缩格式(`gzip`、`br`或`deflate`)，未解压时返回空字符串
+    ///
+    /// 解压后响应头中不再包含`Content-Encoding`与`Content-Length`
+    #[method(name = "GetContentEncoding")]
+    fn content_encoding_serialize(&self) -> &str {
+        self.content_encoding().map(ContentEncoding::as_str).unwrap_or_default()
+    }
+
     #[method(name = "GetHeader")]
     fn header(&self, key: String) -> &str {
         self.headers().and_then(|headers| headers.get(key)).and_then(|v| v.to_str().ok()).unwrap_or_default()
@@ -228,6 +473,23 @@ impl HttpResponse {
             .unwrap_or_default()
     }
 
+    /// 根据`BOM`或内容检测数据的编码
+    ///
+    /// # Returns
+    ///
+    /// 编码常量(`ENCODING_*`)，无数据时返回`ENCODING_UNKNOWN`
+    ///
+    /// # Description
+    ///
+    /// 不考虑`Content-Type`中的`charset`参数
+    #[method(name = "DetectCharset")]
+    fn detect_charset(&self) -> pblong {
+        match self.data() {
+            Some(data) if !data.is_empty() => conv::detect_encoding(data),
+            _ => conv::ENCODING_UNKNOWN
+        }
+    }
+
     #[method(name = "GetHttpStatus")]
     fn http_status(&self) -> pbulong {
         self.status().map(|status| status.as_u16() as pbulong).unwrap_or_default()
@@ -239,64 +501,111 @@ impl HttpResponse {
     #[method(name = "GetData")]
     fn data_binay(&self) -> &[u8] { self.data().map(Bytes::as_ref).unwrap_or_default() }
 
-    #[method(name = "GetDataString", overload = 1)]
-    fn data_string(&self, encoding: Option<pblong>) -> Cow<'_, str> {
-        if let Some(data) = self.data() {
-            match encoding {
-                Some(encoding) => conv::decode(&data, encoding),
-                None => {
-                    let charset = self
-                        .content_type()
-                        .and_then(|content_type| content_type.get_param("charset"))
-                        .map(|charset| charset.as_str())
-                        .unwrap_or_default();
-                    conv::decode_by_charset(&data, charset)
-                }
-            }
-        } else {
-            "".into()
+    /// 数据大小(字节)
+    #[method(name = "GetDataSize")]
+    fn data_size(&self) -> pbulong { self.data().map(Bytes::len).unwrap_or_default() as pbulong }
+
+    /// 获取部分数据
+    ///
+    /// # Parameters
+    ///
+    /// - `start` 起始位置，从`1`开始
+    /// - `length` 长度，超出范围时截断
+    ///
+    /// # Description
+    ///
+    /// 分段读取大数据，避免一次性复制整个响应
+    #[method(name = "GetDataRange")]
+    fn data_range(&self, start: pbulong, length: pbulong) -> &[u8] {
+        let data = self.data().map(Bytes::as_ref).unwrap_or_default();
+        let start = (start.max(1) as usize - 1).min(data.len());
+        let end = start.saturating_add(length as usize).min(data.len());
+        &data[start..end]
+    }
+
+    /// 获取数据的内存地址
+    ///
+    /// # Description
+    ///
+    /// 用于将数据直接传递给外部函数(如`WriteFile`、`RtlMoveMemory`)而无需复制为`Blob`，
+    /// 地址在调用`ReleaseData`或对象销毁前有效，没有数据时返回`0`
+    #[method(name = "GetDataPtr")]
+    fn data_ptr(&self) -> pblonglong {
+        match self.data() {
+            Some(data) if !data.is_empty() => data.as_ptr() as usize as pblonglong,
+            _ => 0
         }
     }
 
+    /// 保存数据到文件，不经过`Blob`复制
+    #[method(name = "SaveData")]
+    fn save_data(&mut self, path: String) -> RetCode {
+        let Some(data) = self.data() else {
+            return self.error.set(RetCode::E_DATA_NOT_FOUND, "no response data");
+        };
+        match crate::base::fs::create_file_dir_all(&path).and_then(|_| std::fs::write(&path, data)) {
+            Ok(_) => RetCode::OK,
+            Err(e) => self.error.set(RetCode::E_IO_ERROR, e)
+        }
+    }
+
+    /// 释放缓存的数据
+    ///
+    /// # Description
+    ///
+    /// 通过`GetData`取得数据后调用，避免大数据在对象与`Blob`中各保留一份，释放后`GetData`返回空`Blob`，
+    /// `GetDataPtr`返回的地址失效
+    #[method(name = "ReleaseData")]
+    fn release_data(&mut self) -> RetCode {
+        match self.inner.as_mut() {
+            Some(HttpResponseInner::Received {
+                data,
+                ..
+            }) if !data.is_empty() => {
+                *data = Bytes::new();
+                self.usage.set(0);
+                RetCode::OK
+            },
+            _ => RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    /// 数据转换为`Data URI`(`data:<MIME类型>;base64,<数据>`)，可直接用于`HTML`的`<img src>`
+    ///
+    /// # Description
+    ///
+    /// `MIME`类型取自`Content-Type`(不含参数)，没有时为`application/octet-stream`，无数据时返回空字符串
+    #[method(name = "GetDataURI")]
+    fn data_uri(&self) -> String {
+        let Some(data) = self.data() else {
+            return "".to_owned();
+        };
+        let mime_type = self.content_type().map(Mime::essence_str).unwrap_or("application/octet-stream");
+        format!("data:{mime_type};base64,{}", BASE64.encode(data))
+    }
+
+    #[method(name = "GetDataString", overload = 1)]
+    fn data_string(&self, encoding: Option<pblong>) -> Cow<'_, str> { self.decode_data(encoding) }
+
     #[method(name = "GetDataJSON", overload = 1)]
     fn data_json(&self, encoding: Option<pblong>) -> Object {
-        let data = if let Some(data) = self.data() {
-            match encoding {
-                Some(encoding) => conv::decode(&data, encoding),
-                None => {
-                    let charset = self
-                        .content_type()
-                        .and_then(|content_type| content_type.get_param("charset"))
-                        .map(|charset| charset.as_str())
-                        .unwrap_or_default();
-                    conv::decode_by_charset(&data, charset)
-                }
-            }
-        } else {
-            "".into()
-        };
-        pfw::json_parse(self.get_session(), &data)
+        let data = self.decode_data(encoding);
+        json::parse(self.get_session(), &data)
     }
 
     #[method(name = "GetDataXML", overload = 1)]
     fn data_xml(&self, encoding: Option<pblong>) -> Object {
-        let data = if let Some(data) = self.data() {
-            match encoding {
-                Some(encoding) => conv::decode(&data, encoding),
-                None => {
-                    let charset = self
-                        .content_type()
-                        .and_then(|content_type| content_type.get_param("charset"))
-                        .map(|charset| charset.as_str())
-                        .unwrap_or_default();
-                    conv::decode_by_charset(&data, charset)
-                }
-            }
-        } else {
-            "".into()
-        };
-        pfw::xml_parse(self.get_session(), &data)
+        let data = self.decode_data(encoding);
+        xml::parse(self.get_session(), &data)
     }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
 }
 
 pub enum HttpResponseInner {
@@ -307,24 +616,75 @@ pub enum HttpResponseInner {
         status: StatusCode,
         headers: HeaderMap,
         content_type: Option<Mime>,
-        err_info: String
+        err_info: String,
+        /// 服务器证书(`DER`)
+        peer_certificate: Option<Bytes>
     },
     Received {
         status: StatusCode,
         headers: HeaderMap,
         content_type: Option<Mime>,
-        data: Bytes
+        data: Bytes,
+        /// 数据已转存的临时文件
+        spill_file: Option<String>,
+        /// 服务器证书(`DER`)
+        peer_certificate: Option<Bytes>,
+        /// 流式读取的数据
+        stream: Option<BodyStream>,
+        /// 已透明解压的原始压缩格式
+        content_encoding: Option<ContentEncoding>
     },
-    Cancelled
+    Cancelled {
+        reason: CancelReason
+    }
+}
+
+/// 取消原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    /// 调用`Cancel`/`CancelAll`/`CancelTag`、`hevent`触发或在事件中返回`1`
+    User = 1,
+    /// 客户端对象已销毁
+    Shutdown = 2,
+    /// 被相同ID的新请求取代
+    Superseded = 3
 }
 
 impl HttpResponseInner {
     pub fn is_send_error(&self) -> bool { matches!(self, HttpResponseInner::SendError { .. }) }
     pub fn is_receive_error(&self) -> bool { matches!(self, HttpResponseInner::ReceiveError { .. }) }
     pub fn is_received(&self) -> bool { matches!(self, HttpResponseInner::Received { .. }) }
-    pub fn is_cancelled(&self) -> bool { matches!(self, HttpResponseInner::Cancelled) }
+    pub fn is_cancelled(&self) -> bool { matches!(self, HttpResponseInner::Cancelled { .. }) }
     pub fn is_succ(&self) -> bool { self.is_received() }
 
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            HttpResponseInner::ReceiveError {
+                status,
+                ..
+            } |
+            HttpResponseInner::Received {
+                status,
+                ..
+            } => Some(*status),
+            _ => None
+        }
+    }
+
+    pub fn headers(&self) -> Option<&HeaderMap> {
+        match self {
+            HttpResponseInner::ReceiveError {
+                headers,
+                ..
+            } |
+            HttpResponseInner::Received {
+                headers,
+                ..
+            } => Some(headers),
+            _ => None
+        }
+    }
+
     pub fn send_error(err_info: impl Display) -> HttpResponseInner {
         HttpResponseInner::SendError {
             err_info: err_info.to_string()
@@ -343,7 +703,11 @@ impl HttpResponseInner {
             status,
             headers,
             content_type,
-            err_info: err_info.to_string()
+            err_info: match locale::current() {
+                Locale::En => err_info.to_string(),
+                Locale::ZhCN => format!("接收失败: {err_info}")
+            },
+            peer_certificate: None
         }
     }
     pub fn received(status: StatusCode, headers: HeaderMap, data: Bytes) -> HttpResponseInner {
@@ -355,43 +719,181 @@ impl HttpResponseInner {
             status,
             headers,
             content_type,
-            data
+            data,
+            spill_file: None,
+            peer_certificate: None,
+            stream: None,
+            content_encoding: None
         }
     }
+    pub fn spilled(status: StatusCode, headers: HeaderMap, file_path: String) -> HttpResponseInner {
+        let mut rv = HttpResponseInner::received(status, headers, Bytes::new());
+        if let HttpResponseInner::Received {
+            spill_file,
+            ..
+        } = &mut rv
+        {
+            *spill_file = Some(file_path);
+        }
+        rv
+    }
 
-    pub fn cancelled() -> HttpResponseInner { HttpResponseInner::Cancelled }
+    /// 附加服务器证书
+    pub fn with_peer_certificate(mut self, cert: Option<Bytes>) -> HttpResponseInner {
+        match &mut self {
+            HttpResponseInner::ReceiveError {
+                peer_certificate,
+                ..
+            } |
+            HttpResponseInner::Received {
+                peer_certificate,
+                ..
+            } => *peer_certificate = cert,
+            _ => {}
+        }
+        self
+    }
 
-    pub async fn receive(mut resp: Response, recv_file_path: Option<String>) -> HttpResponseInner {
+    /// 附加透明解压的原始压缩格式
+    pub fn with_content_encoding(mut self, encoding: Option<ContentEncoding>) -> HttpResponseInner {
+        if let HttpResponseInner::Received {
+            content_encoding,
+            ..
+        } = &mut self
+        {
+            *content_encoding = encoding;
+        }
+        self
+    }
+
+    pub fn cancelled(reason: CancelReason) -> HttpResponseInner {
+        HttpResponseInner::Cancelled {
+            reason
+        }
+    }
+
+    /// 按信封分类响应
+    ///
+    /// 成功时数据替换为数据节点，失败时转换为接收失败，不是`JSON`或不包含状态码节点时不变
+    pub fn unwrap_envelope(self, envelope: &EnvelopePolicy) -> HttpResponseInner {
+        match self {
+            HttpResponseInner::Received {
+                status,
+                headers,
+                content_type,
+                data,
+                spill_file: None,
+                peer_certificate,
+                stream: None,
+                content_encoding
+            } => {
+                match classify_envelope(&data, envelope) {
+                    Some(Ok(data)) => {
+                        HttpResponseInner::Received {
+                            status,
+                            headers,
+                            content_type,
+                            data,
+                            spill_file: None,
+                            peer_certificate,
+                            stream: None,
+                            content_encoding
+                        }
+                    },
+                    Some(Err(err_info)) => {
+                        HttpResponseInner::ReceiveError {
+                            status,
+                            headers,
+                            content_type,
+                            err_info,
+                            peer_certificate
+                        }
+                    },
+                    None => {
+                        HttpResponseInner::Received {
+                            status,
+                            headers,
+                            content_type,
+                            data,
+                            spill_file: None,
+                            peer_certificate,
+                            stream: None,
+                            content_encoding
+                        }
+                    },
+                }
+            },
+            other => other
+        }
+    }
+
+    /// 流式读取
+    ///
+    /// 不等待接收数据，通过`ReadNext`读取
+    pub fn streaming(resp: Response, watermark: usize) -> HttpResponseInner {
+        let mut rv = HttpResponseInner::received(resp.status(), resp.headers().clone(), Bytes::new());
+        if let HttpResponseInner::Received {
+            stream,
+            ..
+        } = &mut rv
+        {
+            *stream = Some(BodyStream::start(resp, watermark));
+        }
+        rv
+    }
+
+    pub async fn receive(
+        mut resp: Response,
+        recv_file_path: Option<String>,
+        resume: Option<u64>,
+        policy: ReceivePolicy,
+        partial: PartialFilePolicy
+    ) -> HttpResponseInner {
         let status = resp.status();
         let headers = resp.headers().clone();
+        //断点续传失败时不覆盖已接收的文件
+        let recv_file_path = recv_file_path.filter(|_| resume.is_none() || status.is_success());
         if let Some(file_path) = recv_file_path {
-            if let Err(e) = crate::base::fs::create_file_dir_all(&file_path) {
-                HttpResponseInner::receive_error(status, headers, e)
-            } else {
-                match File::create(file_path).await {
-                    Ok(mut file) => {
-                        while let Some(chunk) = resp.chunk().await.transpose() {
-                            match chunk {
-                                Ok(chunk) => {
-                                    if let Err(e) = file.write_all(&chunk).await {
-                                        return HttpResponseInner::receive_error(status, headers, e);
-                                    }
-                                },
-                                Err(e) => {
+            match open_receive_file(&file_path, status, &headers, resume).await {
+                Ok((file, _)) => {
+                    let mut partial_file = PartialFile::new(file_path, partial);
+                    //文件先于`partial_file`释放
+                    let mut file = file;
+                    while let Some(chunk) = resp.chunk().await.transpose() {
+                        match chunk {
+                            Ok(chunk) => {
+                                if let Err(e) = file.write_all(&chunk).await {
                                     return HttpResponseInner::receive_error(status, headers, e);
                                 }
+                            },
+                            Err(e) => {
+                                return HttpResponseInner::receive_error(status, headers, e);
                             }
                         }
-                        HttpResponseInner::received(status, headers, Default::default())
-                    },
-                    Err(e) => HttpResponseInner::receive_error(status, headers, e)
-                }
+                    }
+                    if let Err(e) = file.flush().await {
+                        return HttpResponseInner::receive_error(status, headers, e);
+                    }
+                    partial_file.complete();
+                    HttpResponseInner::received(status, headers, Default::default())
+                },
+                Err(e) => HttpResponseInner::receive_error(status, headers, e)
             }
         } else {
-            match resp.bytes().await {
-                Ok(data) => HttpResponseInner::received(status, headers, data),
-                Err(e) => HttpResponseInner::receive_error(status, headers, e)
+            let mut recv_data = ReceiveBuffer::new(policy, resp.content_length());
+            while let Some(chunk) = resp.chunk().await.transpose() {
+                match chunk {
+                    Ok(chunk) => {
+                        if let Err(e) = recv_data.write(&chunk).await {
+                            return HttpResponseInner::receive_error(status, headers, e);
+                        }
+                    },
+                    Err(e) => {
+                        return HttpResponseInner::receive_error(status, headers, e);
+                    }
+                }
             }
+            recv_data.finish(status, headers).await
         }
     }
 
@@ -399,31 +901,43 @@ impl HttpResponseInner {
         id: pbulong,
         invoker: HandlerInvoker<HttpClient>,
         mut resp: Response,
-        recv_file_path: Option<String>
+        recv_file_path: Option<String>,
+        resume: Option<u64>,
+        policy: ReceivePolicy,
+        partial: PartialFilePolicy
     ) -> HttpResponseInner {
         let status = resp.status();
         let headers = resp.headers().clone();
 
+        //断点续传失败时不覆盖已接收的文件
+        let recv_file_path = recv_file_path.filter(|_| resume.is_none() || status.is_success());
+        //先于文件声明，文件释放后再处理
+        let mut partial_file = None;
+        //追加写入的起始位置，计入进度
+        let mut offset = 0;
         let mut file = if let Some(file_path) = recv_file_path {
-            if let Err(e) = crate::base::fs::create_file_dir_all(&file_path) {
-                return HttpResponseInner::receive_error(status, headers, e);
-            } else {
-                match File::create(file_path).await {
-                    Ok(file) => Some(file),
-                    Err(e) => return HttpResponseInner::receive_error(status, headers, e)
-                }
+            match open_receive_file(&file_path, status, &headers, resume).await {
+                Ok((file, start)) => {
+                    partial_file = Some(PartialFile::new(file_path, partial));
+                    offset = start;
+                    Some(file)
+                },
+                Err(e) => return HttpResponseInner::receive_error(status, headers, e)
             }
         } else {
             None
         };
 
-        let total_size = resp.content_length().unwrap_or_default();
-        let mut recv_size: u64 = 0;
-        let mut recv_data = if file.is_some() {
-            BytesMut::new()
-        } else {
-            BytesMut::with_capacity(total_size.max(1024 * 1024) as usize)
-        };
+        let total_size = resp.content_length().map(|len| len + offset).unwrap_or_default();
+        let mut recv_size: u64 = offset;
+        let mut recv_data = ReceiveBuffer::new(
+            policy,
+            if file.is_some() {
+                None
+            } else {
+                resp.content_length()
+            }
+        );
 
         //定时器（每秒计算一次速率并回调通知对象）
         let mut tick_start = Instant::now();
@@ -452,8 +966,8 @@ impl HttpResponseInner {
                                 if let Err(e) = file.write_all(&chunk).await {
                                     return HttpResponseInner::receive_error(status, headers, e);
                                 }
-                            } else {
-                                recv_data.extend_from_slice(&chunk);
+                            } else if let Err(e) = recv_data.write(&chunk).await {
+                                return HttpResponseInner::receive_error(status, headers, e);
                             }
                         },
                         Ok(None) => {
@@ -464,7 +978,15 @@ impl HttpResponseInner {
                                 yield_now().await;
                                 continue;
                             }
-                            return HttpResponseInner::received(status, headers, recv_data.freeze());
+                            if let Some(file) = file.as_mut() {
+                                if let Err(e) = file.flush().await {
+                                    return HttpResponseInner::receive_error(status, headers, e);
+                                }
+                            }
+                            if let Some(partial_file) = partial_file.as_mut() {
+                                partial_file.complete();
+                            }
+                            return recv_data.finish(status, headers).await;
                         },
                         Err(e) => {
                             return HttpResponseInner::receive_error(status, headers, e);
@@ -478,7 +1000,8 @@ impl HttpResponseInner {
                     //UI线程阻塞时截流，丢弃中间的速率
                     if matches!(tick_invoke, Either::Left(_)) {
                         tick_invoke = Either::Right(
-                            invoker.invoke(
+                            invoker.invoke_coalesced(
+                                        id as u64,
                                         (id, total_size, recv_size, speed),
                                         |this, (id, total_size, recv_size, speed)| {
                                             this.on_recv(
@@ -507,10 +1030,14 @@ impl HttpResponseInner {
                         Ok(rv) => {
                             //取消
                             if rv == RetCode::PREVENT {
-                                return HttpResponseInner::cancelled();
+                                return HttpResponseInner::cancelled(CancelReason::User);
                             }
                         },
-                        Err(InvokeError::TargetIsDead) => return HttpResponseInner::cancelled(),
+                        Err(InvokeError::TargetIsDead) => {
+                            return HttpResponseInner::cancelled(CancelReason::Shutdown)
+                        },
+                        //被消息队列丢弃的进度通知
+                        Err(InvokeError::Dropped) => {},
                         Err(InvokeError::Panic) => panic!("Callback panic at OnRecv")
                     }
                     if done_flag == DoneFlag::Invoking {
@@ -521,3 +1048,494 @@ impl HttpResponseInner {
         }
     }
 }
+
+impl HttpResponseInner {
+    /// 分段并行接收到文件
+    ///
+    /// 每段单独发送范围请求并写入文件的对应位置，任意一段失败时整体失败，接收进度为所有分段的合计
+    ///
+    /// # Parameters
+    ///
+    /// - `probe` 获取文件大小的范围请求的响应，提供最终响应的响应头
+    pub async fn receive_segmented(
+        id: pbulong,
+        invoker: HandlerInvoker<HttpClient>,
+        progress: bool,
+        download: SegmentedDownload,
+        probe: Response
+    ) -> HttpResponseInner {
+        let SegmentedDownload {
+            client,
+            req,
+            file_path,
+            total_size,
+            segments,
+            partial
+        } = download;
+        let status = StatusCode::OK;
+        let mut headers = probe.headers().clone();
+        drop(probe);
+        headers.remove(header::CONTENT_RANGE);
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(total_size));
+
+        if let Err(e) = crate::base::fs::create_file_dir_all(&file_path) {
+            return HttpResponseInner::receive_error(status, headers, e);
+        }
+        match File::create(&file_path).await {
+            Ok(file) => {
+                if let Err(e) = file.set_len(total_size).await {
+                    return HttpResponseInner::receive_error(status, headers, e);
+                }
+            },
+            Err(e) => return HttpResponseInner::receive_error(status, headers, e)
+        }
+        let mut partial_file = PartialFile::new(file_path.clone(), partial);
+
+        let segments = segments.min(total_size.div_ceil(SegmentedDownload::MIN_SEGMENT_SIZE)).max(1);
+        let segment_size = total_size.div_ceil(segments).max(1);
+        let recv_size = Arc::new(AtomicU64::new(0));
+        let mut tasks = Vec::new();
+        for start in (0..total_size).step_by(segment_size as usize) {
+            let end = (start + segment_size).min(total_size) - 1;
+            let Some(mut req) = req.try_clone() else {
+                return HttpResponseInner::receive_error(status, headers, "request body can not be cloned");
+            };
+            req.headers_mut().insert(
+                header::RANGE,
+                HeaderValue::try_from(format!("bytes={start}-{end}")).expect("invalid range")
+            );
+            tasks.push(receive_segment(client.clone(), req, file_path.clone(), start, recv_size.clone()));
+        }
+        let mut download = future::try_join_all(tasks);
+
+        //定时器（每秒计算一次速率并回调通知对象）
+        let mut tick_start = Instant::now();
+        let mut tick_interval =
+            time::interval_at(tick_start + Duration::from_secs(1), Duration::from_secs(1));
+        let mut tick_size: u64 = 0; //基准
+        let mut tick_invoke = Either::Left(future::pending());
+        let on_recv = move |this: &mut HttpClient, (recv_size, speed): (u64, f32)| {
+            this.on_recv(id, total_size as pbulong, recv_size as pbulong, speed as pbulong)
+        };
+
+        loop {
+            tokio::select! {
+                rv = &mut download => {
+                    if let Err(e) = rv {
+                        return HttpResponseInner::receive_error(status, headers, e);
+                    }
+                    //通知接收完成
+                    if progress {
+                        let speed = total_size.saturating_sub(tick_size) as f32 / tick_start.elapsed().as_secs_f32();
+                        match invoker.invoke_coalesced(id as u64, (total_size, speed), on_recv).await.await {
+                            Ok(RetCode::PREVENT) => return HttpResponseInner::cancelled(CancelReason::User),
+                            Err(InvokeError::TargetIsDead) => return HttpResponseInner::cancelled(CancelReason::Shutdown),
+                            Err(InvokeError::Panic) => panic!("Callback panic at OnRecv"),
+                            _ => {}
+                        }
+                    }
+                    partial_file.complete();
+                    return HttpResponseInner::received(status, headers, Bytes::new());
+                },
+                _ = tick_interval.tick(), if progress => {
+                    let recv_size = recv_size.load(Ordering::Relaxed);
+                    let speed = (recv_size - tick_size) as f32 / tick_start.elapsed().as_secs_f32();
+                    tick_size = recv_size;
+                    tick_start = Instant::now();
+                    //UI线程阻塞时截流，丢弃中间的速率
+                    if matches!(tick_invoke, Either::Left(_)) {
+                        tick_invoke = Either::Right(
+                            invoker.invoke_coalesced(id as u64, (recv_size, speed), on_recv)
+                                .then(|rv| async { rv.await })
+                                .boxed()
+                        );
+                    }
+                },
+                rv = &mut tick_invoke => {
+                    tick_invoke = Either::Left(future::pending());
+                    match rv {
+                        Ok(RetCode::PREVENT) => return HttpResponseInner::cancelled(CancelReason::User),
+                        Ok(_) => {},
+                        Err(InvokeError::TargetIsDead) => {
+                            return HttpResponseInner::cancelled(CancelReason::Shutdown)
+                        },
+                        //被消息队列丢弃的进度通知
+                        Err(InvokeError::Dropped) => {},
+                        Err(InvokeError::Panic) => panic!("Callback panic at OnRecv")
+                    }
+                }
+            }
+        }
+    }
+
+    /// 逐块触发`OnDataChunk`
+    ///
+    /// 每块等待事件处理完成后再接收下一块，数据不保存在响应中
+    pub async fn deliver_chunks(
+        id: pbulong,
+        invoker: HandlerInvoker<HttpClient>,
+        mut resp: Response
+    ) -> HttpResponseInner {
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        loop {
+            let chunk = match resp.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => return HttpResponseInner::received(status, headers, Bytes::new()),
+                Err(e) => return HttpResponseInner::receive_error(status, headers, e)
+            };
+            match invoker.invoke(chunk, move |this, chunk| this.on_data_chunk(id, &chunk)).await.await {
+                Ok(RetCode::PREVENT) => return HttpResponseInner::cancelled(CancelReason::User),
+                Ok(_) => {},
+                Err(InvokeError::TargetIsDead) => {
+                    return HttpResponseInner::cancelled(CancelReason::Shutdown)
+                },
+                //数据块不能丢弃
+                Err(InvokeError::Dropped) => {
+                    return HttpResponseInner::receive_error(
+                        status,
+                        headers,
+                        "data chunk dropped by dispatch queue"
+                    )
+                },
+                Err(InvokeError::Panic) => panic!("Callback panic at OnDataChunk")
+            }
+        }
+    }
+}
+
+impl Drop for HttpResponse {
+    fn drop(&mut self) {
+        if let Some(spill_file) = self.spill_file.take() {
+            let _ = fs::remove_file(spill_file);
+        }
+    }
+}
+
+/// 接收缓冲
+///
+/// 按`ReceivePolicy`限制预分配的大小并扩容，超过转存阈值后将数据写入临时文件
+struct ReceiveBuffer {
+    policy: ReceivePolicy,
+    data: BytesMut,
+    spill: Option<(String, File)>
+}
+
+impl ReceiveBuffer {
+    fn new(policy: ReceivePolicy, content_length: Option<u64>) -> Self {
+        //`Content-Length`不可信，预分配不超过上限
+        let prealloc = content_length.unwrap_or_default().min(policy.max_prealloc as u64) as usize;
+        ReceiveBuffer {
+            policy,
+            data: BytesMut::with_capacity(prealloc),
+            spill: None
+        }
+    }
+
+    async fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+        if let Some((_, file)) = self.spill.as_mut() {
+            return file.write_all(chunk).await;
+        }
+        let required = self.data.len() + chunk.len();
+        if self.policy.spill_threshold.is_some_and(|threshold| required as u64 > threshold) {
+            let path = std::env::temp_dir()
+                .join(format!("pfwx-http-{}-{}.tmp", std::process::id(), id::next_sequence()))
+                .to_string_lossy()
+                .into_owned();
+            let file = File::create(&path).await?;
+            //先记录文件，写入失败时由`Drop`删除
+            let (_, file) = self.spill.insert((path, file));
+            file.write_all(&self.data).await?;
+            file.write_all(chunk).await?;
+            self.data = BytesMut::new();
+            return Ok(());
+        }
+        if required > self.data.capacity() {
+            let mut capacity = ((self.data.capacity() as f64 * self.policy.growth) as usize).max(required);
+            if let Some(threshold) = self.policy.spill_threshold {
+                capacity = capacity.min(threshold as usize);
+            }
+            let mut data = BytesMut::with_capacity(capacity);
+            data.extend_from_slice(&self.data);
+            self.data = data;
+        }
+        self.data.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    async fn finish(mut self, status: StatusCode, headers: HeaderMap) -> HttpResponseInner {
+        match self.spill.take() {
+            Some((path, mut file)) => {
+                let rv = file.flush().await;
+                drop(file);
+                match rv {
+                    Ok(_) => HttpResponseInner::spilled(status, headers, path),
+                    Err(e) => {
+                        let _ = fs::remove_file(path);
+                        HttpResponseInner::receive_error(status, headers, e)
+                    }
+                }
+            },
+            None => HttpResponseInner::received(status, headers, mem::take(&mut self.data).freeze())
+        }
+    }
+}
+
+impl Drop for ReceiveBuffer {
+    fn drop(&mut self) {
+        //接收失败或被取消
+        if let Some((path, file)) = self.spill.take() {
+            drop(file);
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// 分段下载
+pub struct SegmentedDownload {
+    pub client: Client,
+    /// 原始请求，每段复制后附加`Range`请求头
+    pub req: Request,
+    pub file_path: String,
+    pub total_size: u64,
+    /// 最大分段数
+    pub segments: u64,
+    pub partial: PartialFilePolicy
+}
+
+impl SegmentedDownload {
+    /// 每段的最小字节数
+    const MIN_SEGMENT_SIZE: u64 = 1024 * 1024;
+}
+
+/// 接收一段数据并写入文件的对应位置
+async fn receive_segment(
+    client: Client,
+    req: Request,
+    file_path: String,
+    start: u64,
+    recv_size: Arc<AtomicU64>
+) -> StdResult<(), String> {
+    let mut resp = client.execute(req).await.map_err(|e| e.to_string())?;
+    if resp.status() != StatusCode::PARTIAL_CONTENT || content_range_start(resp.headers()) != Some(start) {
+        return Err(format!("segment at {start} rejected: {}", resp.status()));
+    }
+    let mut file = OpenOptions::new().write(true).open(&file_path).await.map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(start)).await.map_err(|e| e.to_string())?;
+    while let Some(chunk) = resp.chunk().await.map_err(|e| e.to_string())? {
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        recv_size.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    }
+    file.flush().await.map_err(|e| e.to_string())
+}
+
+/// 打开接收数据的文件
+///
+/// 断点续传且服务器返回`206`时从已接收的位置继续写入，否则截断文件
+///
+/// # Returns
+///
+/// 文件以及写入的起始位置
+async fn open_receive_file(
+    file_path: &str,
+    status: StatusCode,
+    headers: &HeaderMap,
+    resume: Option<u64>
+) -> io::Result<(File, u64)> {
+    crate::base::fs::create_file_dir_all(file_path)?;
+    match resume {
+        Some(offset) if status == StatusCode::PARTIAL_CONTENT => {
+            if content_range_start(headers) != Some(offset) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("content range does not start at {offset}")
+                ));
+            }
+            let mut file = OpenOptions::new().write(true).open(file_path).await?;
+            //文件在发送后被修改时丢弃多余的部分
+            file.set_len(offset).await?;
+            file.seek(SeekFrom::Start(offset)).await?;
+            Ok((file, offset))
+        },
+        _ => Ok((File::create(file_path).await?, 0))
+    }
+}
+
+/// 解析`Content-Range: bytes <start>-<end>/<size>`的起始位置
+pub fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.trim().strip_prefix("bytes")?.trim_start();
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// 解析`Content-Range: bytes <start>-<end>/<size>`的总大小，未知(`*`)时返回`None`
+pub fn content_range_total(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+/// 接收文件未完成(失败或被取消)时的处理策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PartialFilePolicy {
+    /// 保留文件
+    Keep,
+    /// 删除文件
+    #[default]
+    Delete,
+    /// 重命名为`<文件名>.tmp`
+    RenameToTmp
+}
+
+impl PartialFilePolicy {
+    /// 解析策略名称(不区分大小写)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "keeppartial" => Some(PartialFilePolicy::Keep),
+            "deletepartial" => Some(PartialFilePolicy::Delete),
+            "renametotmp" => Some(PartialFilePolicy::RenameToTmp),
+            _ => None
+        }
+    }
+}
+
+/// 正在接收的文件
+///
+/// 未调用`complete`就释放时按策略处理文件，写入端可能尚未关闭文件，因此在后台线程中重试
+struct PartialFile {
+    path: String,
+    policy: PartialFilePolicy,
+    completed: bool
+}
+
+impl PartialFile {
+    /// 处理文件的重试次数
+    const RETRIES: u32 = 20;
+    /// 处理文件的重试间隔
+    const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+    fn new(path: String, policy: PartialFilePolicy) -> Self {
+        PartialFile {
+            path,
+            policy,
+            completed: false
+        }
+    }
+
+    /// 接收完成
+    fn complete(&mut self) { self.completed = true; }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if self.completed || self.policy == PartialFilePolicy::Keep {
+            return;
+        }
+        let path = mem::take(&mut self.path);
+        let policy = self.policy;
+        thread::spawn(move || {
+            for _ in 0..Self::RETRIES {
+                let rv = match policy {
+                    PartialFilePolicy::RenameToTmp => fs::rename(&path, format!("{path}.tmp")),
+                    _ => fs::remove_file(&path)
+                };
+                match rv {
+                    Ok(_) => return,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+                    Err(_) => thread::sleep(Self::RETRY_INTERVAL)
+                }
+            }
+            #[cfg(feature = "log")]
+            warn!("Clean up partial file '{}' failed", path);
+        });
+    }
+}
+
+/// 按信封解析响应数据
+///
+/// # Returns
+///
+/// 成功时返回数据节点，失败时返回错误信息，不是`JSON`或不包含状态码节点时返回`None`
+fn classify_envelope(data: &[u8], envelope: &EnvelopePolicy) -> Option<Result<Bytes, String>> {
+    use serde_json::Value;
+    let root = serde_json::from_slice::<Value>(data).ok()?;
+    let lookup = |path: &[String]| path.iter().try_fold(&root, |node, key| node.get(key.as_str()));
+    let code = lookup(&envelope.code_path)?;
+    let is_succ = match code {
+        Value::Number(code) => code.as_f64() == Some(0.0),
+        Value::String(code) => code == "0",
+        Value::Bool(code) => *code,
+        _ => false
+    };
+    if !is_succ {
+        let message = if envelope.message_path.is_empty() {
+            None
+        } else {
+            lookup(&envelope.message_path)
+        };
+        return Some(Err(match message {
+            Some(Value::String(msg)) if !msg.is_empty() => msg.clone(),
+            Some(msg) if !msg.is_null() && !msg.is_string() => msg.to_string(),
+            _ => format!("{}: {code}", tr("envelope error", "业务处理失败"))
+        }));
+    }
+    Some(Ok(match lookup(&envelope.data_path) {
+        Some(Value::String(text)) => Bytes::from(text.clone()),
+        Some(Value::Null) | None => Bytes::new(),
+        Some(node) => Bytes::from(node.to_string())
+    }))
+}
+
+/// 检测的内容类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContentKind {
+    Json,
+    Xml,
+    Text,
+    Binary
+}
+
+/// 根据数据的开头检测内容类型
+///
+/// 仅检测前`512`字节，无数据时返回`None`
+fn sniff_content(data: &[u8]) -> Option<ContentKind> {
+    const SAMPLE_SIZE: usize = 512;
+    /// 常见二进制格式的文件头
+    const MAGIC: &[&[u8]] =
+        &[b"\x89PNG", b"GIF8", b"\xFF\xD8\xFF", b"%PDF-", b"PK\x03\x04", b"\x1F\x8B", b"RIFF"];
+    if data.is_empty() {
+        return None;
+    }
+    let sample = &data[..data.len().min(SAMPLE_SIZE)];
+    //`UTF-16`/`UTF-32`先解码再检测
+    let decoded;
+    let sample = match conv::detect_bom(sample) {
+        Some((conv::ENCODING_UTF8, len)) => &sample[len..],
+        Some((encoding, _)) => {
+            decoded = conv::decode(sample, encoding).into_owned();
+            decoded.as_bytes()
+        },
+        None => {
+            if MAGIC.iter().any(|magic| sample.starts_with(magic)) {
+                return Some(ContentKind::Binary);
+            }
+            sample
+        }
+    };
+    if sample.iter().any(|b| matches!(b, 0x00..=0x08 | 0x0E..=0x1A | 0x1C..=0x1F)) {
+        return Some(ContentKind::Binary);
+    }
+    let text = sample.trim_ascii_start();
+    Some(match text.first() {
+        Some(b'{' | b'[') => ContentKind::Json,
+        Some(b'<') => {
+            let head: Vec<u8> = text.iter().take(16).map(u8::to_ascii_lowercase).collect();
+            let is_html = head.starts_with(b"<html") || head.starts_with(b"<!doctype html");
+            if is_html {
+                ContentKind::Text
+            } else {
+                ContentKind::Xml
+            }
+        },
+        _ => ContentKind::Text
+    })
+}
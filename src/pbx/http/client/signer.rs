@@ -0,0 +1,454 @@
+use super::*;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use reqwest::{
+    header::{HeaderName, HeaderValue, AUTHORIZATION}, Request
+};
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey, pkcs1v15, pkcs8::DecodePrivateKey, signature::{SignatureEncoding, Signer}, RsaPrivateKey
+};
+use sha2::{Sha256, Sha512};
+use std::{
+    result::Result as StdResult, time::{SystemTime, UNIX_EPOCH}
+};
+
+/// 请求签名器
+///
+/// 通过`nx_httpclient.SetSigner`设置，每次发送请求前在UI线程中对规范请求签名并附加返回的请求头
+pub struct HttpSigner {
+    scheme: SignScheme,
+    /// `OnSign`中通过`AddHeader`添加的请求头
+    headers: Vec<(HeaderName, HeaderValue)>,
+    error: LastError
+}
+
+/// 签名方式
+enum SignScheme {
+    /// 由`OnSign`事件签名
+    Custom,
+    /// `HMAC`签名头
+    Hmac {
+        algorithm: HmacAlgorithm,
+        key: Vec<u8>,
+        header: HeaderName
+    },
+    /// 微信支付`APIv3`
+    WechatPay {
+        mchid: String,
+        serial_no: String,
+        key: RsaPrivateKey
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum HmacAlgorithm {
+    Sha256,
+    Sha512
+}
+
+impl HmacAlgorithm {
+    /// 解析算法名称(`SHA256`或`SHA512`，不区分大小写)
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().replace('-', "").as_str() {
+            "SHA256" => Some(HmacAlgorithm::Sha256),
+            "SHA512" => Some(HmacAlgorithm::Sha512),
+            _ => None
+        }
+    }
+
+    fn mac(&self, key: &[u8], msg: &[u8]) -> Vec<u8> {
+        match self {
+            HmacAlgorithm::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts any key length");
+                mac.update(msg);
+                mac.finalize().into_bytes().to_vec()
+            },
+            HmacAlgorithm::Sha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("hmac accepts any key length");
+                mac.update(msg);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+}
+
+/// 配置的`HMAC`签名(`SetHmacSigning`)
+///
+/// 不经过UI线程，在发送前对选定的字段签名
+pub struct HmacSigning {
+    algorithm: HmacAlgorithm,
+    key: Vec<u8>,
+    /// 签名的请求头名称
+    header: HeaderName,
+    /// 请求头的值模板
+    template: String,
+    /// 待签名的字段
+    fields: Vec<SignField>,
+    /// 字段之间的分隔符
+    separator: String
+}
+
+/// 待签名的字段
+enum SignField {
+    Method,
+    /// 路径与查询参数
+    Path,
+    Url,
+    Timestamp,
+    Nonce,
+    Body,
+    /// 请求头的值
+    Header(HeaderName)
+}
+
+impl SignField {
+    fn parse(name: &str) -> Option<Self> {
+        let name = name.trim();
+        if let Some((prefix, header)) = name.split_once(':') {
+            return if prefix.trim().eq_ignore_ascii_case("header") {
+                HeaderName::from_str(header.trim()).ok().map(SignField::Header)
+            } else {
+                None
+            };
+        }
+        match name.to_ascii_lowercase().as_str() {
+            "method" => Some(SignField::Method),
+            "path" => Some(SignField::Path),
+            "url" => Some(SignField::Url),
+            "timestamp" => Some(SignField::Timestamp),
+            "nonce" => Some(SignField::Nonce),
+            "body" => Some(SignField::Body),
+            _ => None
+        }
+    }
+}
+
+impl HmacSigning {
+    /// # Parameters
+    ///
+    /// - `fields` 以`,`分隔的字段：`method`、`path`、`url`、`timestamp`、`nonce`、`body`以及`header:<名称>`
+    /// - `template` 请求头的值模板，`{signature}`替换为`Base64`编码的签名，`{signature_hex}`替换为十六进制编码的签名，
+    ///   `{timestamp}`与`{nonce}`替换为签名使用的时间戳与随机串
+    pub fn new(
+        algorithm: &str,
+        key: String,
+        fields: &str,
+        header: &str,
+        template: String,
+        separator: String
+    ) -> StdResult<Self, String> {
+        let algorithm =
+            HmacAlgorithm::parse(algorithm).ok_or_else(|| format!("unsupported algorithm: {algorithm}"))?;
+        let header = HeaderName::from_str(header).map_err(|_| format!("invalid header: {header}"))?;
+        let fields = fields
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .map(|name| SignField::parse(name).ok_or_else(|| format!("invalid field: {name}")))
+            .collect::<StdResult<Vec<_>, _>>()?;
+        if fields.is_empty() {
+            return Err("no fields to sign".to_owned());
+        }
+        Ok(HmacSigning {
+            algorithm,
+            key: key.into_bytes(),
+            header,
+            template,
+            fields,
+            separator
+        })
+    }
+
+    /// 对请求签名
+    ///
+    /// # Returns
+    ///
+    /// 需要附加的请求头，字段包含`timestamp`或`nonce`时同时附加`X-Timestamp`或`X-Nonce`
+    pub fn sign(&self, req: &Request) -> StdResult<Vec<(HeaderName, HeaderValue)>, SignError> {
+        let canonical = CanonicalRequest::new(req);
+        let mut msg = Vec::new();
+        let mut headers = Vec::new();
+        for (idx, field) in self.fields.iter().enumerate() {
+            if idx > 0 {
+                msg.extend_from_slice(self.separator.as_bytes());
+            }
+            match field {
+                SignField::Method => msg.extend_from_slice(canonical.method.as_bytes()),
+                SignField::Path => msg.extend_from_slice(canonical.path.as_bytes()),
+                SignField::Url => msg.extend_from_slice(canonical.url.as_bytes()),
+                SignField::Timestamp => {
+                    let timestamp = canonical.timestamp.to_string();
+                    msg.extend_from_slice(timestamp.as_bytes());
+                    headers.push((HeaderName::from_static("x-timestamp"), timestamp));
+                },
+                SignField::Nonce => {
+                    msg.extend_from_slice(canonical.nonce.as_bytes());
+                    headers.push((HeaderName::from_static("x-nonce"), canonical.nonce.clone()));
+                },
+                SignField::Body => msg.extend_from_slice(&canonical.body),
+                SignField::Header(name) => {
+                    if let Some(value) = req.headers().get(name) {
+                        msg.extend_from_slice(value.as_bytes());
+                    }
+                },
+            }
+        }
+        let mac = self.algorithm.mac(&self.key, &msg);
+        let value = self
+            .template
+            .replace("{signature}", &BASE64.encode(&mac))
+            .replace("{signature_hex}", &mac.iter().map(|b| format!("{b:02x}")).collect::<String>())
+            .replace("{timestamp}", &canonical.timestamp.to_string())
+            .replace("{nonce}", &canonical.nonce);
+        headers.insert(0, (self.header.clone(), value));
+        headers
+            .into_iter()
+            .map(|(name, value)| {
+                HeaderValue::from_str(&value)
+                    .map(|value| (name, value))
+                    .map_err(|_| SignError::Failed(format!("invalid header value: {value}")))
+            })
+            .collect()
+    }
+}
+
+/// 签名失败
+pub enum SignError {
+    /// 在`OnSign`中返回`1`取消请求
+    Cancelled,
+    Failed(String)
+}
+
+/// 规范请求
+pub struct CanonicalRequest {
+    pub method: String,
+    /// 完整地址
+    pub url: String,
+    /// 路径与查询参数
+    pub path: String,
+    /// `Unix`时间戳(秒)
+    pub timestamp: u64,
+    /// 随机串
+    pub nonce: String,
+    /// 请求体，为流时为空
+    pub body: Bytes
+}
+
+impl CanonicalRequest {
+    pub fn new(req: &Request) -> CanonicalRequest {
+        let url = req.url();
+        let path = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_owned()
+        };
+        CanonicalRequest {
+            method: req.method().to_string(),
+            url: url.to_string(),
+            path,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|dur| dur.as_secs())
+                .unwrap_or_default(),
+            nonce: uuid::Uuid::new_v4().simple().to_string(),
+            body: req.body().and_then(|body| body.as_bytes()).map(Bytes::copy_from_slice).unwrap_or_default()
+        }
+    }
+
+    /// 待签名串
+    ///
+    /// 依次为请求方法、路径与查询参数、时间戳、随机串以及请求体，每项以`\n`结尾
+    pub fn message(&self) -> Vec<u8> {
+        let mut msg =
+            format!("{}\n{}\n{}\n{}\n", self.method, self.path, self.timestamp, self.nonce).into_bytes();
+        msg.extend_from_slice(&self.body);
+        msg.push(b'\n');
+        msg
+    }
+}
+
+impl Default for HttpSigner {
+    fn default() -> Self {
+        HttpSigner {
+            scheme: SignScheme::Custom,
+            headers: Vec::new(),
+            error: LastError::default()
+        }
+    }
+}
+
+#[nonvisualobject(name = "nx_httpsigner")]
+impl HttpSigner {
+    /// 对规范请求签名
+    ///
+    /// # Returns
+    ///
+    /// 需要附加的请求头
+    pub fn sign(&mut self, req: &CanonicalRequest) -> StdResult<Vec<(HeaderName, HeaderValue)>, SignError> {
+        let header = |name: HeaderName, value: String| {
+            HeaderValue::from_str(&value)
+                .map(|value| (name, value))
+                .map_err(|_| SignError::Failed(format!("invalid header value: {value}")))
+        };
+        match &self.scheme {
+            SignScheme::Custom => {
+                self.headers.clear();
+                let rv = self.on_sign(
+                    req.method.clone(),
+                    req.url.clone(),
+                    req.timestamp as pbulong,
+                    req.nonce.clone(),
+                    String::from_utf8_lossy(&req.body).into_owned()
+                );
+                let headers = mem::take(&mut self.headers);
+                if rv == RetCode::PREVENT {
+                    Err(SignError::Cancelled)
+                } else {
+                    Ok(headers)
+                }
+            },
+            SignScheme::Hmac {
+                algorithm,
+                key,
+                header: name
+            } => {
+                let mac = algorithm.mac(key, &req.message());
+                Ok(vec![
+                    header(name.clone(), BASE64.encode(mac))?,
+                    header(HeaderName::from_static("x-timestamp"), req.timestamp.to_string())?,
+                    header(HeaderName::from_static("x-nonce"), req.nonce.clone())?,
+                ])
+            },
+            SignScheme::WechatPay {
+                mchid,
+                serial_no,
+                key
+            } => {
+                let signature = pkcs1v15::SigningKey::<Sha256>::new(key.clone())
+                    .try_sign(&req.message())
+                    .map_err(|e| SignError::Failed(e.to_string()))?;
+                let value = format!(
+                    "WECHATPAY2-SHA256-RSA2048 mchid=\"{mchid}\",nonce_str=\"{}\",signature=\"{}\",timestamp=\"{}\",serial_no=\"{serial_no}\"",
+                    req.nonce,
+                    BASE64.encode(signature.to_vec()),
+                    req.timestamp
+                );
+                Ok(vec![header(AUTHORIZATION, value)?])
+            }
+        }
+    }
+
+    /// 使用`OnSign`事件签名(默认)
+    #[method(name = "SetCustom")]
+    fn set_custom(&mut self) -> &mut Self {
+        self.scheme = SignScheme::Custom;
+        self
+    }
+
+    /// 使用`HMAC`签名
+    ///
+    /// # Parameters
+    ///
+    /// - `algorithm` `SHA256`或`SHA512`
+    /// - `key` 密钥
+    /// - `header` 签名的请求头名称，默认`X-Signature`
+    ///
+    /// # Description
+    ///
+    /// 对待签名串(请求方法、路径与查询参数、时间戳、随机串以及请求体，每项以`\n`结尾)计算`HMAC`，
+    /// 以`Base64`编码附加到`header`，同时附加`X-Timestamp`与`X-Nonce`
+    #[method(name = "SetHmac", overload = 1)]
+    fn set_hmac(&mut self, algorithm: String, key: String, header: Option<String>) -> &mut Self {
+        let Some(algorithm) = HmacAlgorithm::parse(&algorithm) else {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("unsupported algorithm: {algorithm}"));
+            return self;
+        };
+        let header = header.unwrap_or_else(|| "X-Signature".to_owned());
+        let Ok(header) = HeaderName::from_str(&header) else {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid header: {header}"));
+            return self;
+        };
+        self.scheme = SignScheme::Hmac {
+            algorithm,
+            key: key.into_bytes(),
+            header
+        };
+        self
+    }
+
+    /// 使用微信支付`APIv3`签名
+    ///
+    /// # Parameters
+    ///
+    /// - `mchid` 商户号
+    /// - `serial_no` 商户证书序列号
+    /// - `private_key` 商户私钥(`PEM`，`PKCS#8`或`PKCS#1`)
+    ///
+    /// # Description
+    ///
+    /// 按`SHA256-RSA2048`对待签名串签名并设置`Authorization`头
+    #[method(name = "SetWechatPay")]
+    fn set_wechat_pay(&mut self, mchid: String, serial_no: String, private_key: String) -> &mut Self {
+        let key = RsaPrivateKey::from_pkcs8_pem(&private_key)
+            .ok()
+            .or_else(|| RsaPrivateKey::from_pkcs1_pem(&private_key).ok());
+        match key {
+            Some(key) => {
+                self.scheme = SignScheme::WechatPay {
+                    mchid,
+                    serial_no,
+                    key
+                }
+            },
+            None => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, "invalid private key");
+            }
+        }
+        self
+    }
+
+    /// 添加签名请求头
+    ///
+    /// 只能在`OnSign`中调用
+    #[method(name = "AddHeader")]
+    fn add_header(&mut self, name: String, value: String) -> RetCode {
+        match (HeaderName::from_str(&name), HeaderValue::from_str(&value)) {
+            (Ok(name), Ok(value)) => {
+                self.headers.push((name, value));
+                RetCode::OK
+            },
+            _ => self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid header: {name}"))
+        }
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+
+    /// 请求签名(`SetCustom`)
+    ///
+    /// # Parameters
+    ///
+    /// - `method` 请求方法
+    /// - `url` 完整地址
+    /// - `timestamp` `Unix`时间戳(秒)
+    /// - `nonce` 随机串
+    /// - `body` 请求体，为流时为空字符串
+    ///
+    /// # Returns
+    ///
+    /// 调用`AddHeader`添加签名请求头，返回`1`取消请求
+    #[event(name = "OnSign")]
+    fn on_sign(
+        &mut self,
+        method: String,
+        url: String,
+        timestamp: pbulong,
+        nonce: String,
+        body: String
+    ) -> RetCode {
+    }
+}
@@ -0,0 +1,187 @@
+//! `Windows`集成认证
+//!
+//! 通过`SSPI`使用当前登录用户的凭据完成`Negotiate`(`Kerberos`/`NTLM`)或`NTLM`质询握手
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use reqwest::header::{self, HeaderMap};
+use std::{io, ptr, slice};
+use windows::{
+    core::PCWSTR, Win32::{
+        Foundation::{SEC_I_COMPLETE_AND_CONTINUE, SEC_I_COMPLETE_NEEDED, SEC_I_CONTINUE_NEEDED}, Security::{
+            Authentication::Identity::{
+                AcquireCredentialsHandleW, CompleteAuthToken, DeleteSecurityContext, FreeContextBuffer, FreeCredentialsHandle, InitializeSecurityContextW, SecBuffer, SecBufferDesc, ISC_REQ_ALLOCATE_MEMORY, ISC_REQ_CONNECTION, SECBUFFER_TOKEN, SECBUFFER_VERSION, SECPKG_CRED_OUTBOUND, SECURITY_NATIVE_DREP
+            }, Credentials::SecHandle
+        }
+    }
+};
+
+/// 认证包
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SspiPackage {
+    /// 优先`Kerberos`，不可用时回退到`NTLM`
+    Negotiate,
+    Ntlm
+}
+
+impl SspiPackage {
+    /// 解析认证包名称(不区分大小写)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "negotiate" | "kerberos" => Some(SspiPackage::Negotiate),
+            "ntlm" => Some(SspiPackage::Ntlm),
+            _ => None
+        }
+    }
+
+    /// `WWW-Authenticate`与`Authorization`中的认证方案
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            SspiPackage::Negotiate => "Negotiate",
+            SspiPackage::Ntlm => "NTLM"
+        }
+    }
+
+    /// 服务器的质询
+    ///
+    /// # Returns
+    ///
+    /// 服务器不支持此认证方案时返回`None`，质询不包含令牌(首次质询)时返回`Some(None)`
+    pub fn challenge(&self, headers: &HeaderMap) -> Option<Option<Vec<u8>>> {
+        let scheme = self.scheme();
+        headers.get_all(header::WWW_AUTHENTICATE).iter().find_map(|value| {
+            let value = value.to_str().ok()?.trim();
+            let rest = match value.get(..scheme.len()) {
+                Some(prefix) if prefix.eq_ignore_ascii_case(scheme) => &value[scheme.len()..],
+                _ => return None
+            };
+            if rest.is_empty() {
+                Some(None)
+            } else if rest.starts_with(' ') {
+                Some(BASE64.decode(rest.trim()).ok())
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// 客户端的安全上下文
+///
+/// 一次握手使用一个上下文，释放时删除上下文与凭据句柄
+pub struct SspiContext {
+    cred: SecHandle,
+    ctx: Option<SecHandle>,
+    /// 服务主体名称(`HTTP/<host>`)，以`\0`结尾
+    target: Vec<u16>
+}
+
+//句柄只在握手过程中按顺序使用
+unsafe impl Send for SspiContext {}
+
+impl SspiContext {
+    /// 获取当前登录用户的凭据
+    pub fn new(package: SspiPackage, host: &str) -> io::Result<Self> {
+        let package = to_wide(package.scheme());
+        let mut cred = SecHandle::default();
+        unsafe {
+            AcquireCredentialsHandleW(
+                PCWSTR::null(),
+                PCWSTR(package.as_ptr()),
+                SECPKG_CRED_OUTBOUND,
+                None,
+                None,
+                None,
+                None,
+                &mut cred,
+                None
+            )
+            .map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, e))?;
+        }
+        Ok(SspiContext {
+            cred,
+            ctx: None,
+            target: to_wide(&format!("HTTP/{host}"))
+        })
+    }
+
+    /// 根据服务器的质询生成下一个令牌
+    ///
+    /// # Returns
+    ///
+    /// 令牌以及握手是否已在客户端完成
+    pub fn step(&mut self, input: Option<&[u8]>) -> io::Result<(Vec<u8>, bool)> {
+        unsafe {
+            let mut in_buf = SecBuffer {
+                cbBuffer: input.map(|input| input.len()).unwrap_or_default() as u32,
+                BufferType: SECBUFFER_TOKEN,
+                pvBuffer: input.map(|input| input.as_ptr() as *mut _).unwrap_or(ptr::null_mut())
+            };
+            let in_desc = SecBufferDesc {
+                ulVersion: SECBUFFER_VERSION,
+                cBuffers: 1,
+                pBuffers: &mut in_buf
+            };
+            let mut out_buf = SecBuffer {
+                cbBuffer: 0,
+                BufferType: SECBUFFER_TOKEN,
+                pvBuffer: ptr::null_mut()
+            };
+            let mut out_desc = SecBufferDesc {
+                ulVersion: SECBUFFER_VERSION,
+                cBuffers: 1,
+                pBuffers: &mut out_buf
+            };
+            let mut new_ctx = self.ctx.unwrap_or_default();
+            let mut attrs = 0;
+            let rv = InitializeSecurityContextW(
+                Some(&self.cred),
+                self.ctx.as_ref().map(|ctx| ctx as *const _),
+                Some(self.target.as_ptr()),
+                ISC_REQ_ALLOCATE_MEMORY | ISC_REQ_CONNECTION,
+                0,
+                SECURITY_NATIVE_DREP,
+                input.map(|_| &in_desc as *const _),
+                0,
+                Some(&mut new_ctx),
+                Some(&mut out_desc),
+                &mut attrs,
+                None
+            );
+            let completed = if rv.is_err() {
+                Err(io::Error::new(io::ErrorKind::PermissionDenied, windows::core::Error::from(rv)))
+            } else {
+                self.ctx = Some(new_ctx);
+                if rv == SEC_I_COMPLETE_NEEDED || rv == SEC_I_COMPLETE_AND_CONTINUE {
+                    CompleteAuthToken(&new_ctx, &out_desc)
+                        .map(|_| rv == SEC_I_COMPLETE_NEEDED)
+                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+                } else {
+                    Ok(rv != SEC_I_CONTINUE_NEEDED)
+                }
+            };
+            //令牌由`SSPI`分配
+            let token = if out_buf.pvBuffer.is_null() {
+                Vec::new()
+            } else {
+                let token =
+                    slice::from_raw_parts(out_buf.pvBuffer as *const u8, out_buf.cbBuffer as usize).to_vec();
+                let _ = FreeContextBuffer(out_buf.pvBuffer);
+                token
+            };
+            completed.map(|completed| (token, completed))
+        }
+    }
+}
+
+impl Drop for SspiContext {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(ctx) = self.ctx.take() {
+                let _ = DeleteSecurityContext(&ctx);
+            }
+            let _ = FreeCredentialsHandle(&self.cred);
+        }
+    }
+}
+
+fn to_wide(s: &str) -> Vec<u16> { s.encode_utf16().chain(Some(0)).collect() }
@@ -0,0 +1,117 @@
+//! 响应数据流式读取
+//!
+//! 后台任务接收数据到缓冲区，缓冲的数据达到水位时暂停接收，由`ReadNext`取出数据后继续
+
+use crate::reactor::runtime;
+use bytes::{Bytes, BytesMut};
+use reqwest::Response;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// 读取结果
+pub enum StreamRead {
+    /// 读取到的数据
+    Data(Bytes),
+    /// 缓冲区已读完，仍在接收
+    Pending,
+    /// 已读完所有数据
+    End,
+    /// 接收失败
+    Error(String)
+}
+
+/// 流式读取的响应数据
+///
+/// 释放时停止接收
+pub struct BodyStream {
+    shared: Arc<Shared>
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// 缓冲区被读取或读取端已释放
+    notify: Notify,
+    /// 暂停接收的缓冲字节数
+    watermark: usize
+}
+
+#[derive(Default)]
+struct State {
+    buf: BytesMut,
+    finished: bool,
+    error: Option<String>,
+    /// 读取端已释放
+    closed: bool
+}
+
+impl BodyStream {
+    /// 开始在后台接收
+    pub fn start(resp: Response, watermark: usize) -> BodyStream {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            notify: Notify::new(),
+            watermark: watermark.max(1)
+        });
+        runtime::spawn(receive(resp, shared.clone()));
+        BodyStream {
+            shared
+        }
+    }
+
+    /// 读取最多`max_bytes`字节
+    pub fn read(&self, max_bytes: usize) -> StreamRead {
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.buf.is_empty() {
+            let len = state.buf.len().min(max_bytes.max(1));
+            let data = state.buf.split_to(len).freeze();
+            drop(state);
+            self.shared.notify.notify_one();
+            return StreamRead::Data(data);
+        }
+        if let Some(e) = state.error.as_ref() {
+            StreamRead::Error(e.clone())
+        } else if state.finished {
+            StreamRead::End
+        } else {
+            StreamRead::Pending
+        }
+    }
+}
+
+impl Drop for BodyStream {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.notify.notify_one();
+    }
+}
+
+async fn receive(mut resp: Response, shared: Arc<Shared>) {
+    loop {
+        //等待读取端取出数据
+        loop {
+            {
+                let state = shared.state.lock().unwrap();
+                if state.closed {
+                    return;
+                }
+                if state.buf.len() < shared.watermark {
+                    break;
+                }
+            }
+            shared.notify.notified().await;
+        }
+        let chunk = resp.chunk().await;
+        let mut state = shared.state.lock().unwrap();
+        match chunk {
+            Ok(Some(chunk)) => state.buf.extend_from_slice(&chunk),
+            Ok(None) => {
+                state.finished = true;
+                return;
+            },
+            Err(e) => {
+                state.error = Some(e.to_string());
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,414 @@
+//! 系统代理
+//!
+//! 读取当前用户的`IE`代理设置，启用自动检测(`WPAD`)或自动配置脚本(`PAC`)时通过`WinHTTP`计算代理，
+//! 监视注册表中的代理设置，变化后重新读取
+//!
+//! `WPAD`与`PAC`可能耗时数秒，在阻塞线程中计算并缓存结果，连接时只读取缓存，不阻塞运行时
+
+use reqwest::Url;
+use std::{
+    collections::{HashMap, HashSet}, ffi::c_void, sync::{Arc, Mutex}, time::{Duration, Instant}
+};
+use tokio::{runtime::Handle, task::spawn_blocking};
+use windows::{
+    core::{PCWSTR, PWSTR}, Win32::{
+        Foundation::{CloseHandle, BOOL, ERROR_SUCCESS, HANDLE, HGLOBAL, WAIT_OBJECT_0}, Networking::WinHttp::{
+            WinHttpCloseHandle, WinHttpGetIEProxyConfigForCurrentUser, WinHttpGetProxyForUrl, WinHttpOpen, WINHTTP_ACCESS_TYPE_NAMED_PROXY, WINHTTP_ACCESS_TYPE_NO_PROXY, WINHTTP_AUTOPROXY_AUTO_DETECT, WINHTTP_AUTOPROXY_CONFIG_URL, WINHTTP_AUTOPROXY_OPTIONS, WINHTTP_AUTO_DETECT_TYPE_DHCP, WINHTTP_AUTO_DETECT_TYPE_DNS_A, WINHTTP_CURRENT_USER_IE_PROXY_CONFIG, WINHTTP_PROXY_INFO
+        }, System::{
+            Memory::GlobalFree, Registry::{
+                RegCloseKey, RegNotifyChangeKeyValue, RegOpenKeyExW, HKEY, HKEY_CURRENT_USER, KEY_NOTIFY, REG_NOTIFY_CHANGE_LAST_SET, REG_NOTIFY_CHANGE_NAME, REG_NOTIFY_THREAD_AGNOSTIC
+            }, Threading::{CreateEventW, WaitForSingleObject}
+        }
+    }
+};
+
+/// 代理设置所在的注册表项
+const SETTINGS_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings";
+/// 无法监视注册表时重新读取设置的间隔
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 系统代理
+pub struct SystemProxy {
+    /// 执行`PAC`脚本的会话，创建失败时只使用固定代理
+    session: Option<Session>,
+    /// 代理设置的变化通知
+    watcher: Option<Watcher>,
+    state: Mutex<State>
+}
+
+//`WinHTTP`会话与注册表句柄可以在任意线程中使用，状态由`Mutex`保护
+unsafe impl Send for SystemProxy {}
+unsafe impl Sync for SystemProxy {}
+
+#[derive(Default)]
+struct State {
+    /// 代理设置与读取时间，`None`表示需要重新读取
+    settings: Option<(Arc<Settings>, Instant)>,
+    /// 设置的版本，重新读取后递增，丢弃按旧设置计算的结果
+    version: u64,
+    /// 目标地址(`scheme://host:port`)使用的代理，`None`表示直连
+    resolved: HashMap<String, Option<String>>,
+    /// 正在后台计算的目标地址
+    pending: HashSet<String>
+}
+
+/// 当前用户的代理设置
+#[derive(Default)]
+struct Settings {
+    /// 自动检测设置(`WPAD`)
+    auto_detect: bool,
+    /// 自动配置脚本地址
+    auto_config_url: Option<String>,
+    /// 固定代理
+    proxy: Option<String>,
+    /// 不使用固定代理的地址
+    bypass: Vec<String>
+}
+
+impl SystemProxy {
+    pub fn new() -> SystemProxy {
+        SystemProxy {
+            session: Session::open(),
+            watcher: Watcher::new(),
+            state: Mutex::new(State::default())
+        }
+    }
+
+    /// 在阻塞线程中预先计算访问`url`使用的代理
+    ///
+    /// 发送请求前调用，使连接时可以直接读取缓存
+    pub async fn prefetch(self: &Arc<Self>, url: &Url) {
+        let Some(key) = cache_key(url) else {
+            return;
+        };
+        let (settings, version) = self.settings();
+        if !settings.is_auto() || self.state.lock().unwrap().resolved.contains_key(&key) {
+            return;
+        }
+        let this = self.clone();
+        let url = url.clone();
+        let _ = spawn_blocking(move || this.resolve_auto(key, &url, &settings, version)).await;
+    }
+
+    /// 访问`url`使用的代理地址
+    ///
+    /// # Returns
+    ///
+    /// `host:port`或`scheme://host:port`，直连时返回`None`
+    ///
+    /// # Description
+    ///
+    /// 不阻塞：需要`PAC`而未缓存时(如重定向到其它主机)在后台计算，本次使用固定代理
+    pub fn resolve(self: &Arc<Self>, url: &Url) -> Option<String> {
+        let key = cache_key(url)?;
+        let (settings, version) = self.settings();
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(rv) = state.resolved.get(&key) {
+                return rv.clone();
+            }
+            if settings.is_auto() && state.pending.insert(key.clone()) {
+                if let Ok(handle) = Handle::try_current() {
+                    let this = self.clone();
+                    let key = key.clone();
+                    let url = url.clone();
+                    let settings = settings.clone();
+                    handle.spawn_blocking(move || this.resolve_auto(key, &url, &settings, version));
+                } else {
+                    state.pending.remove(&key);
+                }
+            }
+        }
+        let rv = settings.fixed_proxy(url);
+        if !settings.is_auto() {
+            self.cache(key, rv.clone(), version);
+        }
+        rv
+    }
+
+    /// 当前的代理设置，已变化时重新读取
+    fn settings(&self) -> (Arc<Settings>, u64) {
+        {
+            let state = self.state.lock().unwrap();
+            let stale = match (&self.watcher, &state.settings) {
+                (_, None) => true,
+                (Some(watcher), Some(_)) => watcher.changed(),
+                (None, Some((_, loaded))) => loaded.elapsed() >= REFRESH_INTERVAL
+            };
+            if let (false, Some((settings, _))) = (stale, &state.settings) {
+                return (settings.clone(), state.version);
+            }
+        }
+        //在锁外读取
+        let settings = Arc::new(Settings::load());
+        let mut state = self.state.lock().unwrap();
+        state.settings = Some((settings.clone(), Instant::now()));
+        state.version += 1;
+        state.resolved.clear();
+        state.pending.clear();
+        (settings, state.version)
+    }
+
+    /// 执行自动检测或自动配置脚本并缓存结果，在阻塞线程中调用
+    fn resolve_auto(&self, key: String, url: &Url, settings: &Settings, version: u64) {
+        //`PAC`失败时回退到固定代理
+        let rv = match self.session.as_ref().and_then(|session| session.proxy_for_url(url, settings)) {
+            Some(proxy) => proxy.as_deref().and_then(|list| select_proxy(list, url.scheme())),
+            None => settings.fixed_proxy(url)
+        };
+        self.cache(key, rv, version);
+    }
+
+    /// 缓存结果，设置已变化时丢弃
+    fn cache(&self, key: String, rv: Option<String>, version: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.version == version {
+            state.pending.remove(&key);
+            state.resolved.insert(key, rv);
+        }
+    }
+}
+
+impl Settings {
+    /// 是否启用了自动检测或自动配置脚本
+    fn is_auto(&self) -> bool { self.auto_detect || self.auto_config_url.is_some() }
+
+    /// 按固定代理与例外列表选择代理
+    fn fixed_proxy(&self, url: &Url) -> Option<String> {
+        if self.bypassed(url) {
+            None
+        } else {
+            self.proxy.as_deref().and_then(|list| select_proxy(list, url.scheme()))
+        }
+    }
+
+    /// 读取当前用户的代理设置，失败时视为直连
+    fn load() -> Settings {
+        let mut cfg = WINHTTP_CURRENT_USER_IE_PROXY_CONFIG::default();
+        unsafe {
+            if WinHttpGetIEProxyConfigForCurrentUser(&mut cfg) == false {
+                return Settings::default();
+            }
+            Settings {
+                auto_detect: cfg.fAutoDetect.as_bool(),
+                auto_config_url: take_string(cfg.lpszAutoConfigUrl),
+                proxy: take_string(cfg.lpszProxy),
+                bypass: take_string(cfg.lpszProxyBypass)
+                    .map(|list| split_list(&list).map(str::to_owned).collect())
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    /// 是否不使用固定代理
+    ///
+    /// `<local>`匹配不包含`.`的主机名，其它条目支持`*`通配符
+    fn bypassed(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        self.bypass.iter().any(|pattern| {
+            if pattern.eq_ignore_ascii_case("<local>") {
+                !host.contains('.')
+            } else {
+                let pattern = pattern.split_once("://").map(|(_, rest)| rest).unwrap_or(pattern);
+                wildcard_match(pattern, host)
+            }
+        })
+    }
+}
+
+/// `WinHTTP`会话
+struct Session(*mut c_void);
+
+impl Session {
+    fn open() -> Option<Session> {
+        let agent = to_wide("pfwx");
+        let handle = unsafe {
+            WinHttpOpen(
+                PCWSTR(agent.as_ptr()),
+                WINHTTP_ACCESS_TYPE_NO_PROXY,
+                PCWSTR::null(),
+                PCWSTR::null(),
+                0
+            )
+        };
+        if handle.is_null() {
+            None
+        } else {
+            Some(Session(handle))
+        }
+    }
+
+    /// 执行自动检测或自动配置脚本
+    ///
+    /// # Returns
+    ///
+    /// 代理列表，直连时返回`Some(None)`，失败时返回`None`
+    fn proxy_for_url(&self, url: &Url, settings: &Settings) -> Option<Option<String>> {
+        let config_url = settings.auto_config_url.as_deref().map(to_wide);
+        let mut opts = WINHTTP_AUTOPROXY_OPTIONS::default();
+        if settings.auto_detect {
+            opts.dwFlags |= WINHTTP_AUTOPROXY_AUTO_DETECT;
+            opts.dwAutoDetectFlags = WINHTTP_AUTO_DETECT_TYPE_DHCP | WINHTTP_AUTO_DETECT_TYPE_DNS_A;
+        }
+        if let Some(config_url) = config_url.as_ref() {
+            opts.dwFlags |= WINHTTP_AUTOPROXY_CONFIG_URL;
+            opts.lpszAutoConfigUrl = PCWSTR(config_url.as_ptr());
+        }
+        //下载脚本时使用当前登录用户的凭据
+        opts.fAutoLogonIfChallenged = BOOL::from(true);
+        let target = to_wide(url.as_str());
+        let mut info = WINHTTP_PROXY_INFO::default();
+        unsafe {
+            if WinHttpGetProxyForUrl(self.0, PCWSTR(target.as_ptr()), &mut opts, &mut info) == false {
+                return None;
+            }
+            let proxy = take_string(info.lpszProxy);
+            let _ = take_string(info.lpszProxyBypass);
+            if info.dwAccessType == WINHTTP_ACCESS_TYPE_NAMED_PROXY {
+                Some(proxy)
+            } else {
+                Some(None)
+            }
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = WinHttpCloseHandle(self.0);
+        }
+    }
+}
+
+/// 监视代理设置的注册表项
+struct Watcher {
+    key: HKEY,
+    event: HANDLE
+}
+
+impl Watcher {
+    fn new() -> Option<Watcher> {
+        let path = to_wide(SETTINGS_KEY);
+        let mut key = HKEY::default();
+        unsafe {
+            if RegOpenKeyExW(HKEY_CURRENT_USER, PCWSTR(path.as_ptr()), 0, KEY_NOTIFY, &mut key) !=
+                ERROR_SUCCESS
+            {
+                return None;
+            }
+            let event = match CreateEventW(None, false, false, PCWSTR::null()) {
+                Ok(event) => event,
+                Err(_) => {
+                    let _ = RegCloseKey(key);
+                    return None;
+                }
+            };
+            let watcher = Watcher {
+                key,
+                event
+            };
+            watcher.register().then_some(watcher)
+        }
+    }
+
+    /// 注册变化通知，通知只触发一次
+    fn register(&self) -> bool {
+        unsafe {
+            RegNotifyChangeKeyValue(
+                self.key,
+                BOOL::from(true),
+                REG_NOTIFY_CHANGE_NAME | REG_NOTIFY_CHANGE_LAST_SET | REG_NOTIFY_THREAD_AGNOSTIC,
+                self.event,
+                BOOL::from(true)
+            ) == ERROR_SUCCESS
+        }
+    }
+
+    /// 设置是否已变化，变化后重新注册通知
+    fn changed(&self) -> bool {
+        unsafe {
+            if WaitForSingleObject(self.event, 0) == WAIT_OBJECT_0 {
+                self.register();
+                true
+            } else {
+                false
+            }
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = RegCloseKey(self.key);
+            let _ = CloseHandle(self.event);
+        }
+    }
+}
+
+/// 缓存键(`scheme://host:port`)
+fn cache_key(url: &Url) -> Option<String> {
+    Some(format!(
+        "{}://{}:{}",
+        url.scheme(),
+        url.host_str()?,
+        url.port_or_known_default().unwrap_or_default()
+    ))
+}
+
+/// 从代理列表中选择
+///
+/// 列表以`;`或空白分隔，条目为`host:port`或`<scheme>=host:port`，优先使用与`scheme`匹配的条目
+fn select_proxy(list: &str, scheme: &str) -> Option<String> {
+    let mut fallback = None;
+    for entry in split_list(list) {
+        match entry.split_once('=') {
+            Some((name, addr)) if name.eq_ignore_ascii_case(scheme) => return Some(addr.to_owned()),
+            Some(_) => {},
+            None => {
+                fallback.get_or_insert(entry);
+            }
+        }
+    }
+    fallback.map(str::to_owned)
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(|c: char| c == ';' || c.is_whitespace()).filter(|entry| !entry.is_empty())
+}
+
+/// 不区分大小写的`*`通配符匹配
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let text = text.to_ascii_lowercase();
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = text.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// 读取并释放`WinHTTP`分配的字符串
+unsafe fn take_string(s: PWSTR) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    let rv = s.to_string().ok().filter(|s| !s.is_empty());
+    let _ = GlobalFree(HGLOBAL(s.0 as _));
+    rv
+}
+
+fn to_wide(s: &str) -> Vec<u16> { s.encode_utf16().chain(Some(0)).collect() }
@@ -1 +1,5 @@
 mod client;
+mod upload;
+
+#[cfg(feature = "netstatus")]
+pub use upload::UploadQueue;
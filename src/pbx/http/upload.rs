@@ -0,0 +1,580 @@
+use crate::{
+    base::{diag::LiveObject, fs::create_file_dir_all, id, locale::tr}, prelude::*
+};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue}, multipart::{Form, Part}, Client, StatusCode
+};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{HashMap, VecDeque}, fs, io, path::{Path, PathBuf}, time::Duration
+};
+use tokio::{fs::File, time};
+
+/// 上传队列
+///
+/// 按添加顺序在后台上传文件，失败时按退避策略重试，设置持久化文件后未完成的任务在重启后继续上传
+pub struct UploadQueue {
+    state: HandlerState,
+    client: Client,
+    headers: HeaderMap,
+    jobs: HashMap<String, UploadJob>,
+    /// 任务顺序
+    order: VecDeque<String>,
+    concurrency: usize,
+    max_retries: u32,
+    retry_delay: Duration,
+    paused: bool,
+    /// 由`nx_netstatus`在离线时暂停
+    offline_paused: bool,
+    persist_file: Option<PathBuf>,
+    error: LastError,
+    _live: LiveObject
+}
+
+/// 上传任务
+struct UploadJob {
+    url: String,
+    file_path: String,
+    file_field: String,
+    fields: Vec<(String, String)>,
+    /// 已尝试次数
+    attempts: u32,
+    status: JobStatus
+}
+
+/// 任务状态
+enum JobStatus {
+    Pending,
+    Uploading(CancelHandle),
+    /// 等待重试
+    Waiting(CancelHandle)
+}
+
+impl JobStatus {
+    fn name(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Uploading(_) => "uploading",
+            JobStatus::Waiting(_) => "waiting"
+        }
+    }
+
+    fn cancel(self) {
+        match self {
+            JobStatus::Uploading(hdl) | JobStatus::Waiting(hdl) => {
+                hdl.cancel();
+            },
+            JobStatus::Pending => {}
+        }
+    }
+}
+
+/// 上传失败
+struct UploadError {
+    info: String,
+    /// 是否可以重试
+    retryable: bool
+}
+
+#[nonvisualobject(name = "nx_uploadqueue")]
+impl UploadQueue {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        UploadQueue {
+            state: HandlerState::new(session),
+            client: Client::new(),
+            headers: HeaderMap::new(),
+            jobs: HashMap::new(),
+            order: VecDeque::new(),
+            concurrency: 1,
+            max_retries: default::MAX_RETRIES,
+            retry_delay: default::RETRY_DELAY,
+            paused: false,
+            offline_paused: false,
+            persist_file: None,
+            error: LastError::default(),
+            _live: LiveObject::new("nx_uploadqueue")
+        }
+    }
+
+    /// 设置持久化文件
+    ///
+    /// # Description
+    ///
+    /// 加载文件中未完成的任务并继续上传，之后队列的变化都会保存到文件，
+    /// 上传完成前程序退出的任务在下次加载后重新上传
+    #[method(name = "SetPersistFile")]
+    fn set_persist_file(&mut self, path: String) -> RetCode {
+        let path = PathBuf::from(path);
+        let loaded = match load_jobs(&path) {
+            Ok(loaded) => loaded,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                return self.error.set(RetCode::E_INVALID_DATA, format!("{}: {e}", path.display()))
+            },
+            Err(e) => return self.error.set(RetCode::E_IO_ERROR, format!("{}: {e}", path.display()))
+        };
+        for (job_id, job) in loaded {
+            if !self.jobs.contains_key(&job_id) {
+                self.order.push_back(job_id.clone());
+                self.jobs.insert(job_id, job);
+            }
+        }
+        self.persist_file = Some(path);
+        self.save();
+        self.pump();
+        RetCode::OK
+    }
+
+    /// 设置同时上传的任务数量，默认`1`(按顺序上传)
+    #[method(name = "SetConcurrency")]
+    fn set_concurrency(&mut self, concurrency: pbulong) -> RetCode {
+        self.concurrency = concurrency.max(1) as usize;
+        self.pump();
+        RetCode::OK
+    }
+
+    /// 设置重试策略
+    ///
+    /// # Parameters
+    ///
+    /// - `max_retries` 最大重试次数，默认`5`
+    /// - `delay` 首次重试的等待时间(秒)，之后每次加倍，最长`5`分钟，默认`2`
+    ///
+    /// # Description
+    ///
+    /// 连接失败、超时以及`408`、`429`、`5xx`状态码时重试，其它失败不重试
+    #[method(name = "SetRetry", overload = 1)]
+    fn set_retry(&mut self, max_retries: pbulong, delay: Option<pbdouble>) -> RetCode {
+        self.max_retries = max_retries;
+        if let Some(delay) = delay {
+            self.retry_delay = Duration::from_secs_f64(delay.max(0.0));
+        }
+        RetCode::OK
+    }
+
+    /// 设置所有上传请求的请求头(如`Authorization`)
+    ///
+    /// `val`为空字符串时删除
+    #[method(name = "SetHeader")]
+    fn set_header(&mut self, key: String, val: String) -> RetCode {
+        let Ok(name) = HeaderName::try_from(key.as_str()) else {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid header name: {key}"));
+        };
+        if val.is_empty() {
+            self.headers.remove(name);
+            return RetCode::OK;
+        }
+        match HeaderValue::try_from(val) {
+            Ok(val) => {
+                self.headers.insert(name, val);
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid header value: {key}, {e}"))
+        }
+    }
+
+    /// 添加上传任务
+    ///
+    /// # Parameters
+    ///
+    /// - `url` 上传地址，以`multipart/form-data`格式`POST`
+    /// - `file_path` 文件路径
+    /// - `fields` 附加的表单字段(`JSON`对象)，默认没有
+    /// - `file_field` 文件的字段名，默认`file`
+    ///
+    /// # Returns
+    ///
+    /// 任务ID，参数无效时返回空字符串并记录错误
+    #[method(name = "Add", overload = 2)]
+    fn add(
+        &mut self,
+        url: String,
+        file_path: String,
+        fields: Option<String>,
+        file_field: Option<String>
+    ) -> String {
+        if reqwest::Url::parse(&url).is_err() {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid url: {url}"));
+            return String::new();
+        }
+        let fields = match fields.filter(|fields| !fields.is_empty()) {
+            Some(fields) => {
+                match serde_json::from_str::<Value>(&fields) {
+                    Ok(Value::Object(map)) => fields_from_json(&map),
+                    _ => {
+                        self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid fields: {fields}"));
+                        return String::new();
+                    }
+                }
+            },
+            None => Vec::new()
+        };
+        let job_id = id::next_id().to_string();
+        self.jobs.insert(job_id.clone(), UploadJob {
+            url,
+            file_path,
+            file_field: file_field.filter(|field| !field.is_empty()).unwrap_or_else(|| "file".to_owned()),
+            fields,
+            attempts: 0,
+            status: JobStatus::Pending
+        });
+        self.order.push_back(job_id.clone());
+        self.save();
+        self.pump();
+        job_id
+    }
+
+    /// 取消并删除任务
+    #[method(name = "Remove")]
+    fn remove(&mut self, job_id: String) -> RetCode {
+        match self.take_job(&job_id) {
+            Some(job) => {
+                job.status.cancel();
+                self.save();
+                self.pump();
+                RetCode::OK
+            },
+            None => RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    /// 取消并删除所有任务
+    #[method(name = "Clear")]
+    fn clear(&mut self) -> RetCode {
+        self.order.clear();
+        for (_, job) in self.jobs.drain() {
+            job.status.cancel();
+        }
+        self.save();
+        RetCode::OK
+    }
+
+    /// 暂停上传
+    ///
+    /// 正在上传的任务继续完成，之后的任务等待`Resume`
+    #[method(name = "Pause")]
+    fn pause(&mut self) -> RetCode {
+        self.paused = true;
+        self.offline_paused = false;
+        RetCode::OK
+    }
+
+    #[method(name = "Resume")]
+    fn resume(&mut self) -> RetCode {
+        self.paused = false;
+        self.offline_paused = false;
+        self.pump();
+        RetCode::OK
+    }
+
+    #[method(name = "IsPaused")]
+    fn is_paused(&self) -> bool { self.paused }
+
+    /// 未完成的任务数量
+    #[method(name = "GetCount")]
+    fn count(&self) -> pbulong { self.jobs.len() as pbulong }
+
+    /// 按添加顺序返回未完成的任务ID
+    #[method(name = "GetJobs")]
+    fn jobs(&self) -> Vec<String> { self.order.iter().cloned().collect() }
+
+    /// 任务状态
+    ///
+    /// # Returns
+    ///
+    /// `pending`(排队)、`uploading`(上传中)或`waiting`(等待重试)，任务不存在时返回空字符串
+    #[method(name = "GetStatus")]
+    fn status(&self, job_id: String) -> &str {
+        self.jobs.get(&job_id).map(|job| job.status.name()).unwrap_or_default()
+    }
+
+    /// 任务的文件路径
+    #[method(name = "GetFile")]
+    fn file(&self, job_id: String) -> &str {
+        self.jobs.get(&job_id).map(|job| job.file_path.as_str()).unwrap_or_default()
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+
+    /// 网络状态变化(`nx_netstatus.AttachUploadQueue`)
+    ///
+    /// 离线时暂停，恢复在线时只继续由离线暂停的队列
+    #[cfg(feature = "netstatus")]
+    pub(crate) fn set_offline(&mut self, offline: bool) {
+        if offline {
+            if !self.paused {
+                self.paused = true;
+                self.offline_paused = true;
+            }
+        } else if self.offline_paused {
+            self.resume();
+        }
+    }
+
+    /// 开始排队的任务
+    fn pump(&mut self) {
+        if self.paused {
+            return;
+        }
+        let mut running =
+            self.jobs.values().filter(|job| matches!(job.status, JobStatus::Uploading(_))).count();
+        let pending: Vec<String> = self
+            .order
+            .iter()
+            .filter(|job_id| matches!(self.jobs[job_id.as_str()].status, JobStatus::Pending))
+            .cloned()
+            .collect();
+        let alive = self.get_alive_state();
+        for job_id in pending {
+            if running >= self.concurrency {
+                break;
+            }
+            self.start(job_id);
+            //NOTE 对象可能被销毁
+            if alive.is_dead() {
+                return;
+            }
+            running += 1;
+        }
+    }
+
+    /// 开始上传
+    fn start(&mut self, job_id: String) {
+        let Some(job) = self.jobs.get_mut(&job_id) else {
+            return;
+        };
+        job.attempts += 1;
+        let attempt = job.attempts;
+        let fut = upload(
+            self.client.post(job.url.as_str()).headers(self.headers.clone()),
+            job.file_path.clone(),
+            job.file_field.clone(),
+            job.fields.clone()
+        );
+        let id = job_id.clone();
+        let cancel_hdl = self.spawn(fut, move |this, rv| this.job_done(id, rv));
+        if let Some(job) = self.jobs.get_mut(&job_id) {
+            job.status = JobStatus::Uploading(cancel_hdl);
+        }
+        self.save();
+        self.on_job_start(job_id, attempt as pbulong);
+    }
+
+    /// 任务完成
+    fn job_done(&mut self, job_id: String, rv: Result<(StatusCode, String), UploadError>) {
+        let Some(job) = self.jobs.get_mut(&job_id) else {
+            return;
+        };
+        let alive = self.get_alive_state();
+        match rv {
+            Ok((status, data)) => {
+                self.take_job(&job_id);
+                self.save();
+                self.on_job_success(job_id, status.as_u16() as pbulong, data);
+            },
+            Err(e) if e.retryable && job.attempts <= self.max_retries => {
+                let attempt = job.attempts;
+                let delay =
+                    self.retry_delay.saturating_mul(1 << (attempt - 1).min(16)).min(default::MAX_RETRY_DELAY);
+                let id = job_id.clone();
+                let cancel_hdl = self.spawn(time::sleep(delay), move |this, ()| {
+                    if let Some(job) = this.jobs.get_mut(&id) {
+                        job.status = JobStatus::Pending;
+                        this.pump();
+                    }
+                });
+                if let Some(job) = self.jobs.get_mut(&job_id) {
+                    job.status = JobStatus::Waiting(cancel_hdl);
+                }
+                self.on_job_retry(job_id, attempt as pbulong, delay.as_millis() as pbulong, e.info);
+            },
+            Err(e) => {
+                self.take_job(&job_id);
+                self.save();
+                self.on_job_failed(job_id, e.info);
+            }
+        }
+        //NOTE 对象可能被销毁
+        if alive.is_alive() {
+            self.pump();
+        }
+    }
+
+    fn take_job(&mut self, job_id: &str) -> Option<UploadJob> {
+        let job = self.jobs.remove(job_id)?;
+        self.order.retain(|id| id != job_id);
+        Some(job)
+    }
+
+    /// 保存未完成的任务
+    fn save(&mut self) {
+        let Some(path) = self.persist_file.as_ref() else {
+            return;
+        };
+        let jobs: Vec<Value> = self
+            .order
+            .iter()
+            .filter_map(|job_id| self.jobs.get(job_id).map(|job| (job_id, job)))
+            .map(|(job_id, job)| {
+                json!({
+                    "id": job_id,
+                    "url": job.url,
+                    "file": job.file_path,
+                    "file_field": job.file_field,
+                    "fields": job.fields.iter().map(|(name, val)| (name.clone(), Value::String(val.clone()))).collect::<Map<String, Value>>(),
+                    "attempts": job.attempts
+                })
+            })
+            .collect();
+        //先写入临时文件再替换，避免写入中断时丢失队列
+        let tmp_path = path.with_extension("tmp");
+        let rv = create_file_dir_all(path)
+            .and_then(|_| fs::write(&tmp_path, Value::Array(jobs).to_string()))
+            .and_then(|_| fs::rename(&tmp_path, path));
+        if let Err(e) = rv {
+            #[cfg(feature = "log")]
+            warn!("Save upload queue failed: {}", e);
+            self.error.set(RetCode::E_IO_ERROR, format!("{}: {e}", path.display()));
+        }
+    }
+
+    /// 开始上传任务
+    ///
+    /// # Parameters
+    ///
+    /// - `attempt` 第几次尝试，从`1`开始
+    #[event(name = "OnJobStart")]
+    fn on_job_start(&mut self, job_id: String, attempt: pbulong) {}
+
+    /// 上传失败，等待重试
+    ///
+    /// # Parameters
+    ///
+    /// - `delay` 重试前的等待时间(毫秒)
+    #[event(name = "OnJobRetry")]
+    fn on_job_retry(&mut self, job_id: String, attempt: pbulong, delay: pbulong, info: String) {}
+
+    /// 上传成功，任务从队列中删除
+    ///
+    /// # Parameters
+    ///
+    /// - `status` `HTTP`状态码
+    /// - `data` 响应内容
+    #[event(name = "OnJobSuccess")]
+    fn on_job_success(&mut self, job_id: String, status: pbulong, data: String) {}
+
+    /// 上传失败且不再重试，任务从队列中删除
+    #[event(name = "OnJobFailed")]
+    fn on_job_failed(&mut self, job_id: String, info: String) {}
+}
+
+impl Handler for UploadQueue {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+/// 上传文件
+async fn upload(
+    builder: reqwest::RequestBuilder,
+    file_path: String,
+    file_field: String,
+    fields: Vec<(String, String)>
+) -> Result<(StatusCode, String), UploadError> {
+    let file = File::open(&file_path).await.map_err(|e| {
+        UploadError {
+            info: format!("{file_path}: {e}"),
+            retryable: false
+        }
+    })?;
+    let len = file.metadata().await.map(|meta| meta.len()).unwrap_or_default();
+    let file_name =
+        Path::new(&file_path).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut form = Form::new();
+    for (name, val) in fields {
+        form = form.text(name, val);
+    }
+    form = form.part(file_field, Part::stream_with_length(file, len).file_name(file_name));
+    let resp = builder.multipart(form).send().await.map_err(|e| {
+        UploadError {
+            info: format!("{}: {e}", tr("send error", "发送失败")),
+            retryable: e.is_connect() || e.is_timeout()
+        }
+    })?;
+    let status = resp.status();
+    let data = resp.text().await.unwrap_or_default();
+    if status.is_success() {
+        Ok((status, data))
+    } else {
+        Err(UploadError {
+            info: format!("{status}: {data}"),
+            retryable: status.is_server_error() ||
+                matches!(status, StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS)
+        })
+    }
+}
+
+/// 读取持久化的任务
+fn load_jobs(path: &Path) -> io::Result<Vec<(String, UploadJob)>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e)
+    };
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let Ok(Value::Array(items)) = serde_json::from_str::<Value>(&content) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid upload queue file"));
+    };
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let text = |key: &str| item.get(key).and_then(Value::as_str).map(str::to_owned);
+            Some((text("id")?, UploadJob {
+                url: text("url")?,
+                file_path: text("file")?,
+                file_field: text("file_field").unwrap_or_else(|| "file".to_owned()),
+                fields: item
+                    .get("fields")
+                    .and_then(Value::as_object)
+                    .map(fields_from_json)
+                    .unwrap_or_default(),
+                attempts: item.get("attempts").and_then(Value::as_u64).unwrap_or_default() as u32,
+                status: JobStatus::Pending
+            }))
+        })
+        .collect())
+}
+
+/// 表单字段，非字符串的值按`JSON`序列化
+fn fields_from_json(map: &Map<String, Value>) -> Vec<(String, String)> {
+    map.iter()
+        .map(|(name, val)| {
+            let val = match val {
+                Value::String(val) => val.clone(),
+                val => val.to_string()
+            };
+            (name.clone(), val)
+        })
+        .collect()
+}
+
+/// 默认配置
+mod default {
+    use std::time::Duration;
+
+    /// 最大重试次数
+    pub const MAX_RETRIES: u32 = 5;
+    /// 首次重试的等待时间
+    pub const RETRY_DELAY: Duration = Duration::from_secs(2);
+    /// 重试的最长等待时间
+    pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+}
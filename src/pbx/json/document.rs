@@ -0,0 +1,466 @@
+use crate::prelude::*;
+use pbni::pbx::*;
+use serde_json::{Map, Value};
+
+/// 值类型：路径不存在
+const TYPE_NONE: pblong = -1;
+const TYPE_NULL: pblong = 0;
+const TYPE_BOOLEAN: pblong = 1;
+const TYPE_NUMBER: pblong = 2;
+const TYPE_STRING: pblong = 3;
+const TYPE_ARRAY: pblong = 4;
+const TYPE_OBJECT: pblong = 5;
+
+/// 内置的`JSON`文档对象(不依赖`pfw.dll`)
+///
+/// # Path
+///
+/// - `a.b[0].c` 点号分隔的键名，方括号中为数组下标，键名包含特殊字符时使用`["a.b"]`
+/// - `/a/b/0/c` 以`/`开头时按`JSON Pointer`(RFC 6901)解析
+/// - 空字符串表示根节点
+#[derive(Default)]
+pub struct Json {
+    root: Value,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_json")]
+impl Json {
+    /// 解析`JSON`字符串(替换原有数据)
+    ///
+    /// # Returns
+    ///
+    /// 格式无效时返回`E_INVALID_DATA`并保留原有数据，通过`GetLastErrorText`获取错误信息
+    #[method(name = "Parse")]
+    fn parse(&mut self, data: String) -> RetCode {
+        match serde_json::from_str(&data) {
+            Ok(root) => {
+                self.root = root;
+                self.error.clear();
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_INVALID_DATA, e)
+        }
+    }
+
+    /// 序列化
+    ///
+    /// # Parameters
+    ///
+    /// - `pretty` 是否格式化输出，默认`false`
+    #[method(name = "ToString", overload = 1)]
+    fn serialize(&self, pretty: Option<bool>) -> String {
+        if pretty.unwrap_or_default() {
+            serde_json::to_string_pretty(&self.root).unwrap_or_default()
+        } else {
+            self.root.to_string()
+        }
+    }
+
+    /// 清除所有数据
+    #[method(name = "Clear")]
+    fn clear(&mut self) -> RetCode {
+        self.root = Value::Null;
+        RetCode::OK
+    }
+
+    /// 路径是否存在
+    #[method(name = "Exists")]
+    fn exists(&self, path: String) -> bool { self.get(&path).is_some() }
+
+    /// 值类型
+    ///
+    /// # Returns
+    ///
+    /// `-1`路径不存在，`0`空值，`1`布尔，`2`数值，`3`字符串，`4`数组，`5`对象
+    #[method(name = "GetType")]
+    fn value_type(&self, path: String) -> pblong {
+        match self.get(&path) {
+            None => TYPE_NONE,
+            Some(Value::Null) => TYPE_NULL,
+            Some(Value::Bool(_)) => TYPE_BOOLEAN,
+            Some(Value::Number(_)) => TYPE_NUMBER,
+            Some(Value::String(_)) => TYPE_STRING,
+            Some(Value::Array(_)) => TYPE_ARRAY,
+            Some(Value::Object(_)) => TYPE_OBJECT
+        }
+    }
+
+    /// 获取字符串
+    ///
+    /// 非字符串类型返回其`JSON`表示，路径不存在或为空值时返回`default`
+    #[method(name = "GetString", overload = 1)]
+    fn get_string(&self, path: String, default: Option<String>) -> String {
+        match self.get(&path) {
+            Some(Value::String(val)) => val.clone(),
+            Some(Value::Null) | None => default.unwrap_or_default(),
+            Some(val) => val.to_string()
+        }
+    }
+
+    /// 获取数值
+    ///
+    /// 字符串尝试按数值解析，失败时返回`default`
+    #[method(name = "GetNumber", overload = 1)]
+    fn get_number(&self, path: String, default: Option<pbdouble>) -> pbdouble {
+        let val = match self.get(&path) {
+            Some(Value::Number(val)) => val.as_f64(),
+            Some(Value::String(val)) => val.trim().parse().ok(),
+            Some(Value::Bool(val)) => Some(*val as u8 as f64),
+            _ => None
+        };
+        val.unwrap_or_else(|| default.unwrap_or_default())
+    }
+
+    /// 获取整数
+    ///
+    /// 小数部分被截断，失败时返回`default`
+    #[method(name = "GetLong", overload = 1)]
+    fn get_long(&self, path: String, default: Option<pblong>) -> pblong {
+        let val = match self.get(&path) {
+            Some(Value::Number(val)) => val.as_i64().or_else(|| val.as_f64().map(|val| val as i64)),
+            Some(Value::String(val)) => val.trim().parse().ok(),
+            Some(Value::Bool(val)) => Some(*val as i64),
+            _ => None
+        };
+        val.map(|val| val as pblong).unwrap_or_else(|| default.unwrap_or_default())
+    }
+
+    /// 获取布尔值
+    ///
+    /// 数值非零为`true`，字符串支持`true`/`false`，失败时返回`default`
+    #[method(name = "GetBoolean", overload = 1)]
+    fn get_boolean(&self, path: String, default: Option<bool>) -> bool {
+        let val = match self.get(&path) {
+            Some(Value::Bool(val)) => Some(*val),
+            Some(Value::Number(val)) => val.as_f64().map(|val| val != 0.0),
+            Some(Value::String(val)) => val.trim().parse().ok(),
+            _ => None
+        };
+        val.unwrap_or_else(|| default.unwrap_or_default())
+    }
+
+    /// 获取子节点的`JSON`字符串
+    ///
+    /// 路径不存在时返回空字符串
+    #[method(name = "GetJSON")]
+    fn get_json(&self, path: String) -> String { self.get(&path).map(Value::to_string).unwrap_or_default() }
+
+    /// 数组的元素数量或对象的成员数量
+    #[method(name = "GetCount")]
+    fn count(&self, path: String) -> pblong {
+        match self.get(&path) {
+            Some(Value::Array(val)) => val.len() as pblong,
+            Some(Value::Object(val)) => val.len() as pblong,
+            _ => 0
+        }
+    }
+
+    /// 对象的所有键名
+    #[method(name = "GetKeys")]
+    fn keys(&self, path: String) -> Vec<String> {
+        match self.get(&path) {
+            Some(Value::Object(val)) => val.keys().cloned().collect(),
+            _ => Vec::new()
+        }
+    }
+
+    /// 设置字符串
+    ///
+    /// # Description
+    ///
+    /// 自动创建不存在的中间节点，数组下标等于数组长度时追加元素
+    #[method(name = "SetString")]
+    fn set_string(&mut self, path: String, value: String) -> RetCode { self.set(&path, Value::String(value)) }
+
+    /// 设置数值
+    #[method(name = "SetNumber")]
+    fn set_number(&mut self, path: String, value: pbdouble) -> RetCode {
+        match serde_json::Number::from_f64(value) {
+            Some(value) => self.set(&path, Value::Number(value)),
+            None => self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid number: {value}"))
+        }
+    }
+
+    /// 设置整数
+    #[method(name = "SetLong")]
+    fn set_long(&mut self, path: String, value: pblong) -> RetCode { self.set(&path, Value::from(value)) }
+
+    /// 设置布尔值
+    #[method(name = "SetBoolean")]
+    fn set_boolean(&mut self, path: String, value: bool) -> RetCode { self.set(&path, Value::Bool(value)) }
+
+    /// 设置空值
+    #[method(name = "SetNull")]
+    fn set_null(&mut self, path: String) -> RetCode { self.set(&path, Value::Null) }
+
+    /// 使用`JSON`字符串设置子节点
+    #[method(name = "SetJSON")]
+    fn set_json(&mut self, path: String, data: String) -> RetCode {
+        match serde_json::from_str(&data) {
+            Ok(value) => self.set(&path, value),
+            Err(e) => self.error.set(RetCode::E_INVALID_DATA, e)
+        }
+    }
+
+    /// 使用另一个`nx_json`对象设置子节点(复制)
+    #[method(name = "SetJSON")]
+    fn set_json_object(&mut self, path: String, obj: &mut Json) -> RetCode {
+        let value = obj.root.clone();
+        self.set(&path, value)
+    }
+
+    /// 向数组追加字符串
+    ///
+    /// 路径不存在时创建数组
+    #[method(name = "AppendString")]
+    fn append_string(&mut self, path: String, value: String) -> RetCode {
+        self.append(&path, Value::String(value))
+    }
+
+    /// 向数组追加数值
+    #[method(name = "AppendNumber")]
+    fn append_number(&mut self, path: String, value: pbdouble) -> RetCode {
+        match serde_json::Number::from_f64(value) {
+            Some(value) => self.append(&path, Value::Number(value)),
+            None => self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid number: {value}"))
+        }
+    }
+
+    /// 向数组追加`JSON`字符串表示的节点
+    #[method(name = "AppendJSON")]
+    fn append_json(&mut self, path: String, data: String) -> RetCode {
+        match serde_json::from_str(&data) {
+            Ok(value) => self.append(&path, value),
+            Err(e) => self.error.set(RetCode::E_INVALID_DATA, e)
+        }
+    }
+
+    /// 删除节点
+    #[method(name = "Remove")]
+    fn remove(&mut self, path: String) -> RetCode {
+        let mut segs = match parse_path(&path) {
+            Some(segs) => segs,
+            None => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid path: {path}"))
+        };
+        let Some(last) = segs.pop() else {
+            self.root = Value::Null;
+            return RetCode::OK;
+        };
+        let removed = match (lookup_mut(&mut self.root, &segs), last) {
+            (Some(Value::Object(map)), seg) => map.remove(&seg.to_key()).is_some(),
+            (Some(Value::Array(arr)), seg) => {
+                match seg.to_index() {
+                    Some(idx) if idx < arr.len() => {
+                        arr.remove(idx);
+                        true
+                    },
+                    _ => false
+                }
+            },
+            _ => false
+        };
+        if removed {
+            RetCode::OK
+        } else {
+            self.error.set(RetCode::E_DATA_NOT_FOUND, format!("path not found: {path}"))
+        }
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Json {
+    /// 解析`JSON`字符串，失败时保留原有数据
+    pub fn load(&mut self, data: &str) -> bool {
+        match serde_json::from_str(data) {
+            Ok(root) => {
+                self.root = root;
+                true
+            },
+            Err(_) => false
+        }
+    }
+
+    /// 序列化为紧凑格式的`JSON`字符串
+    pub fn to_json(&self) -> String { self.root.to_string() }
+
+    /// 根节点
+    pub fn root(&self) -> &Value { &self.root }
+
+    fn get(&self, path: &str) -> Option<&Value> { lookup(&self.root, &parse_path(path)?) }
+
+    fn set(&mut self, path: &str, value: Value) -> RetCode {
+        let segs = match parse_path(path) {
+            Some(segs) => segs,
+            None => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid path: {path}"))
+        };
+        match lookup_or_insert(&mut self.root, &segs) {
+            Ok(slot) => {
+                *slot = value;
+                RetCode::OK
+            },
+            Err(rv) => self.error.set(rv, format!("path conflicts with existing value: {path}"))
+        }
+    }
+
+    fn append(&mut self, path: &str, value: Value) -> RetCode {
+        let segs = match parse_path(path) {
+            Some(segs) => segs,
+            None => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid path: {path}"))
+        };
+        match lookup_or_insert(&mut self.root, &segs) {
+            Ok(slot) => {
+                if slot.is_null() {
+                    *slot = Value::Array(Vec::new());
+                }
+                match slot {
+                    Value::Array(arr) => {
+                        arr.push(value);
+                        RetCode::OK
+                    },
+                    _ => self.error.set(RetCode::E_INVALID_TYPE, format!("not an array: {path}"))
+                }
+            },
+            Err(rv) => self.error.set(rv, format!("path conflicts with existing value: {path}"))
+        }
+    }
+}
+
+/// 路径片段
+enum Segment {
+    Key(String),
+    Index(usize)
+}
+
+impl Segment {
+    fn to_key(&self) -> String {
+        match self {
+            Segment::Key(key) => key.clone(),
+            Segment::Index(idx) => idx.to_string()
+        }
+    }
+
+    fn to_index(&self) -> Option<usize> {
+        match self {
+            Segment::Key(key) => key.parse().ok(),
+            Segment::Index(idx) => Some(*idx)
+        }
+    }
+}
+
+/// 解析路径，格式无效时返回`None`
+fn parse_path(path: &str) -> Option<Vec<Segment>> {
+    let path = path.trim();
+    //JSON Pointer
+    if let Some(pointer) = path.strip_prefix('/') {
+        return Some(
+            pointer
+                .split('/')
+                .map(|token| {
+                    let token = token.replace("~1", "/").replace("~0", "~");
+                    match token.parse() {
+                        Ok(idx) => Segment::Index(idx),
+                        Err(_) => Segment::Key(token)
+                    }
+                })
+                .collect()
+        );
+    }
+    let mut segs = Vec::new();
+    let mut key = String::new();
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                if !key.is_empty() {
+                    segs.push(Segment::Key(std::mem::take(&mut key)));
+                }
+            },
+            '[' => {
+                if !key.is_empty() {
+                    segs.push(Segment::Key(std::mem::take(&mut key)));
+                }
+                let mut inner = String::new();
+                let mut quoted = false;
+                loop {
+                    match chars.next()? {
+                        ']' if !quoted => break,
+                        c => {
+                            if c == '"' {
+                                quoted = !quoted;
+                            }
+                            inner.push(c);
+                        }
+                    }
+                }
+                let inner = inner.trim();
+                match inner.strip_prefix('"').and_then(|inner| inner.strip_suffix('"')) {
+                    Some(quoted) => segs.push(Segment::Key(quoted.to_owned())),
+                    None => segs.push(Segment::Index(inner.parse().ok()?))
+                }
+            },
+            c => key.push(c)
+        }
+    }
+    if !key.is_empty() {
+        segs.push(Segment::Key(key));
+    }
+    Some(segs)
+}
+
+fn lookup<'a>(root: &'a Value, segs: &[Segment]) -> Option<&'a Value> {
+    segs.iter().try_fold(root, |cur, seg| {
+        match cur {
+            Value::Object(map) => map.get(&seg.to_key()),
+            Value::Array(arr) => arr.get(seg.to_index()?),
+            _ => None
+        }
+    })
+}
+
+fn lookup_mut<'a>(root: &'a mut Value, segs: &[Segment]) -> Option<&'a mut Value> {
+    segs.iter().try_fold(root, |cur, seg| {
+        match cur {
+            Value::Object(map) => map.get_mut(&seg.to_key()),
+            Value::Array(arr) => arr.get_mut(seg.to_index()?),
+            _ => None
+        }
+    })
+}
+
+/// 查找节点，自动创建不存在的中间节点
+///
+/// # Returns
+///
+/// - `E_INVALID_TYPE` 中间节点不是对象或数组
+/// - `E_OUT_OF_RANGE` 数组下标大于数组长度
+fn lookup_or_insert<'a>(root: &'a mut Value, segs: &[Segment]) -> Result<&'a mut Value, RetCode> {
+    let mut cur = root;
+    for seg in segs {
+        if cur.is_null() {
+            *cur = match seg {
+                Segment::Key(_) => Value::Object(Map::new()),
+                Segment::Index(_) => Value::Array(Vec::new())
+            };
+        }
+        cur = match cur {
+            Value::Object(map) => map.entry(seg.to_key()).or_insert(Value::Null),
+            Value::Array(arr) => {
+                let idx = seg.to_index().ok_or(RetCode::E_INVALID_TYPE)?;
+                if idx == arr.len() {
+                    arr.push(Value::Null);
+                }
+                arr.get_mut(idx).ok_or(RetCode::E_OUT_OF_RANGE)?
+            },
+            _ => return Err(RetCode::E_INVALID_TYPE)
+        };
+    }
+    Ok(cur)
+}
@@ -0,0 +1,22 @@
+use crate::base::pfw;
+use pbni::{pbx::*, prelude::*};
+
+mod document;
+
+pub use document::Json;
+
+/// 解析`JSON`并创建对象
+///
+/// # Description
+///
+/// 优先创建`pfw::n_json`对象，`pfw.dll`不可用时创建内置的`nx_json`对象
+pub fn parse<'a>(session: Session, data: &str) -> Object<'a> {
+    match pfw::json_parse(session, data) {
+        Some(obj) => obj,
+        None => {
+            Json::new_object_modify(session, |obj| {
+                obj.load(data);
+            })
+        },
+    }
+}
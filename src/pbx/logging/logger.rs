@@ -0,0 +1,328 @@
+use crate::{
+    base::logger::{default, RollingFile, Rotation}, prelude::*
+};
+use pbni::pbx::*;
+use serde_json::{Map, Value};
+use std::io::Write;
+use tracing::{level_filters::LevelFilter, Level};
+use tracing_appender::non_blocking::{NonBlocking, NonBlockingBuilder, WorkerGuard};
+
+/// 默认的日志名称
+const DEFAULT_NAME: &str = "app";
+
+struct Logger {
+    name: String,
+    level: LevelFilter,
+    /// 输出`JSON`行
+    json: bool,
+    /// 同时转发到运行时日志(`pfwxSetLog`)
+    forward: bool,
+    /// 每条日志附带的上下文字段
+    fields: Vec<(String, Value)>,
+    writer: Option<(NonBlocking, WorkerGuard)>,
+    error: LastError
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger {
+            name: DEFAULT_NAME.to_owned(),
+            level: LevelFilter::INFO,
+            json: false,
+            forward: false,
+            fields: Vec::new(),
+            writer: None,
+            error: LastError::default()
+        }
+    }
+}
+
+#[nonvisualobject(name = "nx_logger")]
+impl Logger {
+    /// 打开日志文件，已打开时先关闭
+    ///
+    /// # Parameters
+    ///
+    /// - `path` 日志文件路径，目录不存在时自动创建
+    /// - `max_size` 文件大小上限(MB)，超过后滚动到新文件，默认`0`表示不按大小滚动
+    /// - `rotation` 按时间滚动：`none`(默认)、`hourly`、`daily`
+    /// - `max_files` 保留的旧文件数量，默认`5`
+    ///
+    /// # Description
+    ///
+    /// 日志在后台线程写入，调用方不会因磁盘`IO`阻塞，后台队列已满时丢弃新的日志
+    #[method(name = "Open", overload = 3)]
+    fn open(
+        &mut self,
+        path: String,
+        max_size: Option<pbulong>,
+        rotation: Option<String>,
+        max_files: Option<pbulong>
+    ) -> RetCode {
+        let rotation = match rotation.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            None | Some("" | "none") => Rotation::Never,
+            Some("hourly") => Rotation::Hourly,
+            Some("daily") => Rotation::Daily,
+            _ => {
+                return self.error.set(
+                    RetCode::E_INVALID_ARGUMENT,
+                    format!("invalid rotation: {}", rotation.unwrap_or_default())
+                );
+            }
+        };
+        //先关闭旧文件，确保缓冲的日志写入完成
+        self.writer = None;
+        let file = match RollingFile::open(
+            path.into(),
+            max_size.unwrap_or_default() as u64 * 1024 * 1024,
+            rotation,
+            max_files.map(|n| n as usize).unwrap_or(default::ROLLING_FILES)
+        ) {
+            Ok(file) => file,
+            Err(e) => return self.error.set(RetCode::E_IO_ERROR, e)
+        };
+        self.writer = Some(NonBlockingBuilder::default().lossy(true).thread_name("pfwx-logger").finish(file));
+        RetCode::OK
+    }
+
+    /// 关闭日志文件并等待缓冲的日志写入完成
+    #[method(name = "Close")]
+    fn close(&mut self) -> RetCode {
+        if self.writer.take().is_some() {
+            RetCode::OK
+        } else {
+            RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    #[method(name = "IsOpen")]
+    fn is_open(&self) -> bool { self.writer.is_some() }
+
+    /// 设置日志名称，默认`app`
+    #[method(name = "SetName")]
+    fn set_name(&mut self, name: String) -> RetCode {
+        self.name = name;
+        RetCode::OK
+    }
+
+    /// 设置日志级别
+    ///
+    /// # Parameters
+    ///
+    /// - `level` `off`、`error`、`warn`、`info`(默认)、`debug`、`trace`
+    #[method(name = "SetLevel")]
+    fn set_level(&mut self, level: String) -> RetCode {
+        match level.trim().parse() {
+            Ok(level) => {
+                self.level = level;
+                RetCode::OK
+            },
+            Err(e) => self.error.set(RetCode::E_INVALID_ARGUMENT, e)
+        }
+    }
+
+    /// 设置输出格式
+    ///
+    /// # Parameters
+    ///
+    /// - `format` `text`(默认)或`json`
+    ///
+    /// # Description
+    ///
+    /// `json`格式每行一个对象，包含`timestamp`、`level`、`logger`、`message`以及`fields`
+    #[method(name = "SetFormat")]
+    fn set_format(&mut self, format: String) -> RetCode {
+        self.json = match format.to_ascii_lowercase().as_str() {
+            "text" => false,
+            "json" => true,
+            _ => return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid format: {format}"))
+        };
+        RetCode::OK
+    }
+
+    /// 设置是否同时转发到运行时日志(`pfwxSetLog`)，默认`false`
+    ///
+    /// 转发的日志与本模块的日志写入同一文件，受`pfwxSetLog`的级别控制
+    #[method(name = "SetForward")]
+    fn set_forward(&mut self, forward: bool) -> RetCode {
+        self.forward = forward;
+        RetCode::OK
+    }
+
+    /// 设置每条日志附带的上下文字段(如用户、终端)，已存在时覆盖
+    #[method(name = "SetField")]
+    fn set_field(&mut self, key: String, value: String) -> RetCode {
+        match self.fields.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = Value::String(value),
+            None => self.fields.push((key, Value::String(value)))
+        }
+        RetCode::OK
+    }
+
+    /// 删除上下文字段
+    #[method(name = "RemoveField")]
+    fn remove_field(&mut self, key: String) -> RetCode {
+        let len = self.fields.len();
+        self.fields.retain(|(k, _)| *k != key);
+        if self.fields.len() != len {
+            RetCode::OK
+        } else {
+            RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    /// 清空上下文字段
+    #[method(name = "ClearFields")]
+    fn clear_fields(&mut self) -> RetCode {
+        self.fields.clear();
+        RetCode::OK
+    }
+
+    /// 指定级别是否会被记录，可避免构造不需要的日志内容
+    #[method(name = "IsEnabled")]
+    fn is_enabled(&self, level: String) -> bool {
+        match level.trim().parse::<Level>() {
+            Ok(level) => self.level >= level,
+            Err(_) => false
+        }
+    }
+
+    /// 记录日志
+    ///
+    /// # Parameters
+    ///
+    /// - `level` `error`、`warn`、`info`、`debug`、`trace`
+    /// - `message` 内容
+    /// - `fields` 附加字段，`JSON`对象(如`{"order":1001}`)或`key=value`以`;`分隔(如`order=1001;step=2`)
+    #[method(name = "Log", overload = 1)]
+    fn log(&mut self, level: String, message: String, fields: Option<String>) -> RetCode {
+        match level.trim().parse() {
+            Ok(level) => self.write(level, &message, fields.as_deref()),
+            Err(e) => self.error.set(RetCode::E_INVALID_ARGUMENT, e)
+        }
+    }
+
+    /// 记录`DEBUG`级别的日志，参数同`Log`
+    #[method(name = "Debug", overload = 1)]
+    fn debug(&mut self, message: String, fields: Option<String>) -> RetCode {
+        self.write(Level::DEBUG, &message, fields.as_deref())
+    }
+
+    /// 记录`INFO`级别的日志，参数同`Log`
+    #[method(name = "Info", overload = 1)]
+    fn info(&mut self, message: String, fields: Option<String>) -> RetCode {
+        self.write(Level::INFO, &message, fields.as_deref())
+    }
+
+    /// 记录`WARN`级别的日志，参数同`Log`
+    #[method(name = "Warn", overload = 1)]
+    fn warn(&mut self, message: String, fields: Option<String>) -> RetCode {
+        self.write(Level::WARN, &message, fields.as_deref())
+    }
+
+    /// 记录`ERROR`级别的日志，参数同`Log`
+    #[method(name = "Error", overload = 1)]
+    fn error(&mut self, message: String, fields: Option<String>) -> RetCode {
+        self.write(Level::ERROR, &message, fields.as_deref())
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Logger {
+    fn write(&mut self, level: Level, message: &str, fields: Option<&str>) -> RetCode {
+        if self.writer.is_none() && !self.forward {
+            return self.error.set(RetCode::E_INVALID_OBJECT, "log file not opened");
+        }
+        if self.level < level {
+            return RetCode::OK;
+        }
+        let mut all_fields = Map::new();
+        for (key, value) in &self.fields {
+            all_fields.insert(key.clone(), value.clone());
+        }
+        if let Some(fields) = fields {
+            match parse_fields(fields) {
+                Some(fields) => all_fields.extend(fields),
+                None => {
+                    return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid fields: {fields}"))
+                },
+            }
+        }
+        if self.forward {
+            forward(level, &self.name, message, &text_fields(&all_fields));
+        }
+        if let Some((writer, _)) = self.writer.as_mut() {
+            let timestamp = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string();
+            let mut line = if self.json {
+                let mut record = Map::new();
+                record.insert("timestamp".to_owned(), timestamp.into());
+                record.insert("level".to_owned(), level.to_string().into());
+                record.insert("logger".to_owned(), self.name.as_str().into());
+                record.insert("message".to_owned(), message.into());
+                if !all_fields.is_empty() {
+                    record.insert("fields".to_owned(), Value::Object(all_fields));
+                }
+                Value::Object(record).to_string()
+            } else {
+                //文本格式保持一行一条
+                let message = message.replace("\r\n", "\\n").replace('\n', "\\n");
+                format!("{timestamp} {level:>5} {}: {message}{}", self.name, text_fields(&all_fields))
+            };
+            line.push('\n');
+            if let Err(e) = writer.write_all(line.as_bytes()) {
+                return self.error.set(RetCode::E_IO_ERROR, e);
+            }
+        }
+        RetCode::OK
+    }
+}
+
+/// 解析附加字段
+fn parse_fields(fields: &str) -> Option<Map<String, Value>> {
+    let fields = fields.trim();
+    if fields.is_empty() {
+        return Some(Map::new());
+    }
+    if fields.starts_with('{') {
+        return serde_json::from_str(fields).ok();
+    }
+    let mut map = Map::new();
+    for pair in fields.split(';').filter(|pair| !pair.trim().is_empty()) {
+        let (key, value) = pair.split_once('=')?;
+        map.insert(key.trim().to_owned(), Value::String(value.trim().to_owned()));
+    }
+    Some(map)
+}
+
+/// 字段的文本格式(` key=value key2=value2`)
+fn text_fields(fields: &Map<String, Value>) -> String {
+    let mut rv = String::new();
+    for (key, value) in fields {
+        match value {
+            //不含空白的字符串直接输出，其它使用`JSON`格式
+            Value::String(value) if !value.is_empty() && !value.contains(char::is_whitespace) => {
+                rv.push_str(&format!(" {key}={value}"));
+            },
+            value => rv.push_str(&format!(" {key}={value}"))
+        }
+    }
+    rv
+}
+
+/// 转发到运行时日志
+fn forward(level: Level, name: &str, message: &str, fields: &str) {
+    match level {
+        Level::ERROR => tracing::error!(target: env!("CARGO_PKG_NAME"), logger = name, "{message}{fields}"),
+        Level::WARN => tracing::warn!(target: env!("CARGO_PKG_NAME"), logger = name, "{message}{fields}"),
+        Level::INFO => tracing::info!(target: env!("CARGO_PKG_NAME"), logger = name, "{message}{fields}"),
+        Level::DEBUG => tracing::debug!(target: env!("CARGO_PKG_NAME"), logger = name, "{message}{fields}"),
+        _ => tracing::trace!(target: env!("CARGO_PKG_NAME"), logger = name, "{message}{fields}")
+    }
+}
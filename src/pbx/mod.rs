@@ -2,9 +2,37 @@
 
 mod global_func;
 
+#[cfg(feature = "barcode")]
+mod barcode;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+#[cfg(feature = "codec")]
+mod codec;
+#[cfg(feature = "compress")]
+mod compress;
+#[cfg(feature = "crypto")]
+mod crypto;
+#[cfg(feature = "event")]
+mod event;
+#[cfg(feature = "fs")]
+mod fs;
+#[cfg(feature = "health")]
+mod health;
 #[cfg(feature = "http")]
 mod http;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "log")]
+mod logging;
 #[cfg(feature = "mqtt")]
 mod mqtt;
+#[cfg(feature = "netstatus")]
+mod netstatus;
 #[cfg(feature = "parser")]
 mod parser;
+#[cfg(feature = "secrets")]
+mod secrets;
+#[cfg(feature = "task")]
+mod task;
+#[cfg(feature = "xml")]
+mod xml;
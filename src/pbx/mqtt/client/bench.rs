@@ -0,0 +1,192 @@
+//! 吞吐量测试
+//!
+//! 向回显主题发布带时间戳的测试消息并订阅同一主题，根据收到的消息统计吞吐量与往返延迟
+
+use paho_mqtt::{async_client::AsyncClient, Message};
+use serde_json::json;
+use std::{
+    collections::VecDeque, future::Future, sync::{Arc, Mutex}, time::{Duration, Instant}
+};
+use tokio::{sync::Notify, time};
+
+/// 测试消息的标识
+const MAGIC: &[u8] = b"PFWB";
+/// 测试消息头的长度(标识 + 测试序号 + 消息序号 + 发送时间)
+pub const HEADER_LEN: usize = MAGIC.len() + 4 + 4 + 8;
+/// 同时等待发布完成的消息数量
+const PUBLISH_WINDOW: usize = 10;
+/// 发布完成后等待回显消息的时间
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(10);
+/// 预分配延迟记录的最大数量
+const MAX_PREALLOC: u32 = 65536;
+
+/// 进行中的测试
+///
+/// 由消息回调(非`UI`线程)记录收到的测试消息
+pub struct BenchRun {
+    run_id: u32,
+    topic: String,
+    count: u32,
+    start: Instant,
+    latencies: Vec<Duration>,
+    last_received: Option<Duration>,
+    done: Arc<Notify>
+}
+
+pub type SharedBench = Arc<Mutex<Option<BenchRun>>>;
+
+/// 测试参数
+pub struct BenchParams {
+    pub run_id: u32,
+    pub topic: String,
+    pub count: u32,
+    pub size: usize,
+    pub qos: i32
+}
+
+/// 开始测试
+///
+/// # Returns
+///
+/// 执行测试的任务，输出测试结果(`JSON`)
+///
+/// # Description
+///
+/// 调用时即标记为进行中，任务完成后清除
+pub fn run(client: AsyncClient, bench: SharedBench, params: BenchParams) -> impl Future<Output = String> {
+    let done = Arc::new(Notify::new());
+    let start = Instant::now();
+    *bench.lock().unwrap() = Some(BenchRun {
+        run_id: params.run_id,
+        topic: params.topic.clone(),
+        count: params.count,
+        start,
+        latencies: Vec::with_capacity(params.count.min(MAX_PREALLOC) as usize),
+        last_received: None,
+        done: done.clone()
+    });
+    async move { execute(client, bench, params, start, done).await }
+}
+
+async fn execute(
+    client: AsyncClient,
+    bench: SharedBench,
+    params: BenchParams,
+    start: Instant,
+    done: Arc<Notify>
+) -> String {
+    let mut error = None;
+    let mut sent: u32 = 0;
+    if let Err(e) = client.subscribe(params.topic.as_str(), params.qos).await {
+        error = Some(format!("subscribe failed: {e}"));
+    } else {
+        let size = params.size.max(HEADER_LEN);
+        let mut inflight = VecDeque::with_capacity(PUBLISH_WINDOW);
+        for seq in 0..params.count {
+            if inflight.len() >= PUBLISH_WINDOW {
+                match inflight.pop_front().unwrap().await {
+                    Ok(_) => sent += 1,
+                    Err(e) => error = Some(format!("publish failed: {e}"))
+                }
+            }
+            let payload = payload(params.run_id, seq, start.elapsed(), size);
+            inflight.push_back(client.publish(Message::new(params.topic.as_str(), payload, params.qos)));
+        }
+        for token in inflight {
+            match token.await {
+                Ok(_) => sent += 1,
+                Err(e) => error = Some(format!("publish failed: {e}"))
+            }
+        }
+        if sent > 0 {
+            let _ = time::timeout(RECEIVE_TIMEOUT, done.notified()).await;
+        }
+        let _ = client.unsubscribe(params.topic.as_str()).await;
+    }
+    let publish_elapsed = start.elapsed();
+
+    let run = bench.lock().unwrap().take().unwrap();
+    let mut latencies = run.latencies;
+    latencies.sort();
+    let received = latencies.len() as u32;
+    let elapsed = run.last_received.unwrap_or(publish_elapsed).as_secs_f64();
+    let ms = |dur: Duration| dur.as_secs_f64() * 1000.0;
+    let percentile = |p: f64| {
+        latencies.get(((latencies.len() as f64 * p).ceil() as usize).saturating_sub(1)).copied().map(ms)
+    };
+    let avg = if received > 0 {
+        Some(ms(latencies.iter().sum::<Duration>() / received))
+    } else {
+        None
+    };
+    let per_sec = |n: f64| {
+        if elapsed > 0.0 {
+            n / elapsed
+        } else {
+            0.0
+        }
+    };
+    json!({
+        "topic": params.topic,
+        "count": params.count,
+        "size": params.size.max(HEADER_LEN),
+        "qos": params.qos,
+        "sent": sent,
+        "received": received,
+        "lost": sent.saturating_sub(received),
+        "elapsed_ms": elapsed * 1000.0,
+        "msg_per_sec": per_sec(received as f64),
+        "bytes_per_sec": per_sec(received as f64 * params.size.max(HEADER_LEN) as f64),
+        "latency_ms": {
+            "min": latencies.first().copied().map(ms),
+            "avg": avg,
+            "p50": percentile(0.5),
+            "p95": percentile(0.95),
+            "max": latencies.last().copied().map(ms)
+        },
+        "error": error.unwrap_or_default()
+    })
+    .to_string()
+}
+
+/// 在消息回调中处理测试消息
+///
+/// # Returns
+///
+/// 是正在测试的主题上的测试消息时返回`true`，不再触发`OnMessage`
+pub fn on_message(bench: &SharedBench, msg: &Message) -> bool {
+    let payload = msg.payload();
+    if payload.len() < HEADER_LEN || !payload.starts_with(MAGIC) {
+        return false;
+    }
+    let field = |offset: usize, len: usize| &payload[MAGIC.len() + offset..MAGIC.len() + offset + len];
+    let run_id = u32::from_le_bytes(field(0, 4).try_into().unwrap());
+    let sent_at = Duration::from_nanos(u64::from_le_bytes(field(8, 8).try_into().unwrap()));
+    let mut bench = bench.lock().unwrap();
+    //只拦截正在测试的主题，其它消息照常触发事件
+    let run = match bench.as_mut() {
+        Some(run) if run.topic == msg.topic() => run,
+        _ => return false
+    };
+    //同一主题上之前测试迟到的消息丢弃
+    if run.run_id == run_id {
+        let now = run.start.elapsed();
+        run.latencies.push(now.saturating_sub(sent_at));
+        run.last_received = Some(now);
+        if run.latencies.len() as u32 >= run.count {
+            run.done.notify_one();
+        }
+    }
+    true
+}
+
+/// 生成测试消息
+fn payload(run_id: u32, seq: u32, sent_at: Duration, size: usize) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(size);
+    payload.extend_from_slice(MAGIC);
+    payload.extend_from_slice(&run_id.to_le_bytes());
+    payload.extend_from_slice(&seq.to_le_bytes());
+    payload.extend_from_slice(&(sent_at.as_nanos() as u64).to_le_bytes());
+    payload.resize(size, 0);
+    payload
+}
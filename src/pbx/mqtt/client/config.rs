@@ -1,31 +1,101 @@
 use super::*;
-use paho_mqtt::{ClientPersistence, ConnectOptions, CreateOptions, PersistenceType, SslOptionsBuilder};
-use std::{collections::HashMap, mem::replace};
+use crate::base::config;
+use paho_mqtt::{ConnectOptions, CreateOptions, PersistenceType, SslOptionsBuilder};
+use persist::{PersistStore, RuntimeStore};
+use pipeline::{CipherKey, Pipeline};
+use std::{collections::HashMap, mem::replace, path::PathBuf};
 
 pub struct MqttConfigEx {
-    pub offline_queue: bool
+    pub offline_queue: bool,
+    /// 主题前缀
+    pub topic_prefix: String,
+    /// 消息内容转换
+    pub pipeline: Pipeline,
+    /// 持久化存储
+    pub persistence: Option<PersistStore>
 }
 
 impl Default for MqttConfigEx {
     fn default() -> Self {
         MqttConfigEx {
-            offline_queue: false
+            offline_queue: false,
+            topic_prefix: String::new(),
+            pipeline: Pipeline::default(),
+            persistence: None
         }
     }
 }
 
+impl MqttConfigEx {
+    /// 添加主题前缀
+    ///
+    /// 共享订阅(`$share/<group>/<filter>`)在`<filter>`前添加，其它以`$`开头的系统主题不添加
+    pub fn add_prefix(&self, topic: &str) -> String {
+        if self.topic_prefix.is_empty() {
+            return topic.to_owned();
+        }
+        if let Some(rest) = topic.strip_prefix("$share/") {
+            if let Some((group, filter)) = rest.split_once('/') {
+                return format!("$share/{group}/{}{filter}", self.topic_prefix);
+            }
+        }
+        if topic.starts_with('$') {
+            return topic.to_owned();
+        }
+        format!("{}{topic}", self.topic_prefix)
+    }
+}
+
+/// 去除主题前缀，没有前缀时原样返回
+pub fn strip_topic_prefix<'a>(prefix: &str, topic: &'a str) -> &'a str {
+    if prefix.is_empty() {
+        return topic;
+    }
+    topic.strip_prefix(prefix).unwrap_or(topic)
+}
+
 pub struct MqttConfig {
     create_builder: Option<CreateOptionsBuilder>,
     conn_builder: ConnectOptionsBuilder,
-    cfg: MqttConfigEx
+    cfg: MqttConfigEx,
+    /// 遗嘱消息，创建时添加主题前缀
+    will: Option<Message>,
+    /// 客户端ID，用于定位文件存储的目录
+    client_id: String,
+    error: LastError
 }
 
 impl Default for MqttConfig {
     fn default() -> Self {
-        MqttConfig {
+        let mut cfg = MqttConfig {
             create_builder: Some(CreateOptionsBuilder::default()),
             conn_builder: ConnectOptionsBuilder::default(),
-            cfg: MqttConfigEx::default()
+            cfg: MqttConfigEx::default(),
+            will: None,
+            client_id: String::new(),
+            error: LastError::default()
+        };
+        cfg.apply_defaults(&config::current().mqtt);
+        cfg
+    }
+}
+
+impl MqttConfig {
+    /// 应用全局默认配置(`pfwxLoadConfig`)
+    fn apply_defaults(&mut self, defaults: &config::MqttDefaults) {
+        if defaults.auto_reconnect == Some(true) {
+            let min = defaults.reconnect_min.unwrap_or(default::RECONNECT_MIN);
+            let max = defaults.reconnect_max.unwrap_or(default::RECONNECT_MAX).max(min);
+            self.conn_builder.automatic_reconnect(min, max);
+        }
+        if let Some(timeout) = defaults.timeout {
+            self.conn_builder.connect_timeout(timeout);
+        }
+        if let Some(interval) = defaults.keep_alive {
+            self.conn_builder.keep_alive_interval(interval);
+        }
+        if let Some(clean) = defaults.clean_session {
+            self.conn_builder.clean_session(clean);
         }
     }
 }
@@ -37,18 +107,42 @@ impl MqttConfig {
     /// # Notice
     ///
     /// 仅能调用一次
-    pub fn build(&mut self, url: String) -> (CreateOptions, ConnectOptions, MqttConfigEx) {
+    ///
+    /// # Parameters
+    ///
+    /// - `will` 替换配置的遗嘱消息(`EnablePresence`)
+    pub fn build(
+        &mut self,
+        url: String,
+        will: Option<Message>
+    ) -> (CreateOptions, ConnectOptions, MqttConfigEx) {
         let create_builder = self.create_builder.replace(CreateOptionsBuilder::default()).unwrap();
         let cfg = replace(&mut self.cfg, MqttConfigEx::default());
         let mut conn_builder = replace(&mut self.conn_builder, ConnectOptionsBuilder::default());
         conn_builder.server_uris(&url.split(";").collect::<Vec<&str>>());
+        if let Some(will) = will.or_else(|| self.will.take()) {
+            let topic = cfg.add_prefix(will.topic());
+            conn_builder.will_message(message::with_topic(&will, topic));
+        }
         let ssl_opts = SslOptionsBuilder::new().enable_server_cert_auth(false).finalize();
         conn_builder.ssl_options(ssl_opts);
         (create_builder.finalize(), conn_builder.finalize(), cfg)
     }
 
+    /// 持久化存储
+    pub fn persistence(&self) -> Option<&PersistStore> { self.cfg.persistence.as_ref() }
+
+    /// 设置协议版本
+    ///
+    /// # Parameters
+    ///
+    /// - `ver` `0`(自动)、`3`(`3.1`)、`4`(`3.1.1`)或`5`
     #[method(name = "SetVersion")]
     fn version(&mut self, ver: pblong) -> &mut Self {
+        if !matches!(ver, 0 | 3 | 4 | 5) {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid mqtt version: {ver}"));
+            return self;
+        }
         let create_builder = self.create_builder.take().unwrap();
         self.create_builder.replace(create_builder.mqtt_version(ver as u32));
         self
@@ -57,6 +151,14 @@ impl MqttConfig {
     #[method(name = "SetClientId")]
     fn client_id(&mut self, id: String) -> &mut Self {
         let create_builder = self.create_builder.take().unwrap();
+        if let Some(PersistStore::File {
+            client_id,
+            ..
+        }) = self.cfg.persistence.as_mut()
+        {
+            *client_id = id.clone();
+        }
+        self.client_id = id.clone();
         self.create_builder.replace(create_builder.client_id(id));
         self
     }
@@ -77,9 +179,12 @@ impl MqttConfig {
     fn persistence_enabled(&mut self, enabled: bool) -> &mut Self {
         let create_builder = self.create_builder.take().unwrap();
         if enabled {
-            self.create_builder.replace(create_builder.user_persistence(RuntimeStore::default()));
+            let map = Arc::new(Mutex::new(HashMap::new()));
+            self.create_builder.replace(create_builder.user_persistence(RuntimeStore::new(map.clone())));
+            self.cfg.persistence = Some(PersistStore::Memory(map));
         } else {
             self.create_builder.replace(create_builder.persistence(PersistenceType::None));
+            self.cfg.persistence = None;
         }
         self
     }
@@ -87,6 +192,10 @@ impl MqttConfig {
     #[method(name = "SetPersistence")]
     fn persistence_file(&mut self, file_path: String) -> &mut Self {
         let create_builder = self.create_builder.take().unwrap();
+        self.cfg.persistence = Some(PersistStore::File {
+            root: PathBuf::from(&file_path),
+            client_id: self.client_id.clone()
+        });
         self.create_builder.replace(create_builder.persistence(file_path));
         self
     }
@@ -102,56 +211,101 @@ impl MqttConfig {
     #[method(name = "SetAutoReconnect")]
     fn automatic_reconnect(&mut self, enabled: bool) -> &mut Self {
         if enabled {
-            self.conn_builder.automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30));
+            self.conn_builder.automatic_reconnect(default::RECONNECT_MIN, default::RECONNECT_MAX);
         }
         self
     }
 
     #[method(name = "SetTimeout")]
     fn timeout(&mut self, secs: pbdouble) -> &mut Self {
-        self.conn_builder.connect_timeout(Duration::from_secs_f64(secs));
+        match Duration::try_from_secs_f64(secs) {
+            Ok(timeout) => {
+                self.conn_builder.connect_timeout(timeout);
+            },
+            Err(_) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid timeout: {secs}"));
+            }
+        }
         self
     }
 
     #[method(name = "SetWillMessage")]
     fn will_message(&mut self, msg: &mut MqttMessage) -> &mut Self {
         if let Some(msg) = msg.take() {
-            self.conn_builder.will_message(msg);
+            self.will = Some(msg);
         }
         self
     }
-}
 
-#[derive(Default)]
-struct RuntimeStore {
-    map: HashMap<String, Vec<u8>>
+    /// 设置主题前缀(如租户标识`tenant1/`)
+    ///
+    /// # Description
+    ///
+    /// 发布、订阅、取消订阅以及遗嘱消息的主题自动添加前缀，收到的消息的主题自动去除前缀，
+    /// 共享订阅(`$share/<group>/<filter>`)在`<filter>`前添加，其它以`$`开头的系统主题不添加
+    #[method(name = "SetTopicPrefix")]
+    fn topic_prefix(&mut self, prefix: String) -> &mut Self {
+        if prefix.contains(['+', '#']) {
+            self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid topic prefix: {prefix}"));
+            return self;
+        }
+        self.cfg.topic_prefix = prefix;
+        self
+    }
+
+    /// 设置消息内容转换
+    ///
+    /// # Parameters
+    ///
+    /// - `compress` 是否使用`zstd`压缩
+    /// - `aes_key` `AES-GCM`密钥(`16`或`32`字节)，空表示不加密
+    /// - `device_id` 设备标识，设置时添加包含发送时间与设备标识的`JSON`信封，默认不添加
+    ///
+    /// # Description
+    ///
+    /// 发布前依次压缩、加密并添加信封，收到消息后按相反顺序还原，还原失败时触发`OnError`并丢弃消息，
+    /// 通信双方需使用相同的配置
+    #[method(name = "SetPayloadPipeline", overload = 1)]
+    fn payload_pipeline(&mut self, compress: bool, aes_key: &[u8], device_id: Option<String>) -> &mut Self {
+        let cipher = if aes_key.is_empty() {
+            None
+        } else {
+            match CipherKey::new(aes_key) {
+                Some(cipher) => Some(cipher),
+                None => {
+                    self.error.set(
+                        RetCode::E_INVALID_ARGUMENT,
+                        format!("invalid aes key length: {}", aes_key.len())
+                    );
+                    return self;
+                }
+            }
+        };
+        self.cfg.pipeline = Pipeline {
+            compress,
+            cipher,
+            device_id: device_id.filter(|id| !id.is_empty())
+        };
+        self
+    }
+
+    /// 最近一次失败的错误码
+    ///
+    /// 配置方法返回对象自身以便链式调用，参数无效时不生效并记录错误
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
 }
 
-#[allow(unused_variables)]
-impl ClientPersistence for RuntimeStore {
-    fn open(&mut self, client_id: &str, server_uri: &str) -> paho_mqtt::Result<()> { Ok(()) }
-    fn close(&mut self) -> paho_mqtt::Result<()> { Ok(()) }
-    fn put(&mut self, key: &str, buffers: Vec<&[u8]>) -> paho_mqtt::Result<()> {
-        self.map.insert(
-            key.to_owned(),
-            buffers.into_iter().fold(Vec::new(), |mut buf, item| {
-                buf.extend_from_slice(item);
-                buf
-            })
-        );
-        Ok(())
-    }
-    fn get(&mut self, key: &str) -> paho_mqtt::Result<Vec<u8>> {
-        Ok(self.map.get(key).cloned().unwrap_or_default())
-    }
-    fn remove(&mut self, key: &str) -> paho_mqtt::Result<()> {
-        self.map.remove(key);
-        Ok(())
-    }
-    fn keys(&mut self) -> paho_mqtt::Result<Vec<String>> { Ok(self.map.keys().cloned().collect()) }
-    fn clear(&mut self) -> paho_mqtt::Result<()> {
-        self.map.clear();
-        Ok(())
-    }
-    fn contains_key(&mut self, key: &str) -> bool { self.map.contains_key(key) }
+/// 默认配置
+mod default {
+    use std::time::Duration;
+
+    /// 自动重连-最小间隔
+    pub const RECONNECT_MIN: Duration = Duration::from_secs(1);
+    /// 自动重连-最大间隔
+    pub const RECONNECT_MAX: Duration = Duration::from_secs(30);
 }
@@ -1,17 +1,30 @@
 use super::*;
-use crate::base::{conv, pfw};
-use paho_mqtt::MessageBuilder;
-use std::borrow::Cow;
+use crate::{
+    base::{conv, pfw}, pbx::{
+        json::{self, Json}, xml::{self, Xml}
+    }
+};
+use flate2::read::MultiGzDecoder;
+use paho_mqtt::{MessageBuilder, PropertyCode};
+use pipeline::Envelope;
+use std::{
+    borrow::Cow, io::{self, Read}
+};
 
 #[derive(Default)]
 pub struct MqttMessage {
-    inner: Option<Message>
+    inner: Option<Message>,
+    /// 收到的消息的信封信息
+    envelope: Option<Envelope>,
+    error: LastError
 }
 
 #[nonvisualobject(name = "nx_mqttmessage")]
 impl MqttMessage {
     pub fn init(&mut self, msg: Message) { self.inner = Some(msg); }
 
+    pub fn set_envelope(&mut self, envelope: Envelope) { self.envelope = Some(envelope); }
+
     /// 获取`paho_mqtt::Message`
     ///
     /// # Notice
@@ -112,7 +125,7 @@ impl MqttMessage {
         let data = match obj.get_class_name().as_str() {
             "n_json" => pfw::json_serialize(&obj),
             "n_xmldoc" => pfw::xml_serialize(&obj),
-            cls @ _ => panic!("unexpect class {cls}")
+            cls @ _ => return self.error.set(RetCode::E_INVALID_OBJECT, format!("unexpect class {cls}"))
         };
         self.inner = match self.inner.take() {
             Some(msg) => {
@@ -127,6 +140,38 @@ impl MqttMessage {
         RetCode::OK
     }
 
+    #[method(name = "SetData")]
+    fn set_payload_json(&mut self, obj: &mut Json) -> RetCode {
+        let data = obj.to_json();
+        self.inner = match self.inner.take() {
+            Some(msg) => {
+                Some(if msg.retained() {
+                    Message::new_retained(msg.topic(), data, msg.qos())
+                } else {
+                    Message::new(msg.topic(), data, msg.qos())
+                })
+            },
+            None => Some(MessageBuilder::new().payload(data).finalize())
+        };
+        RetCode::OK
+    }
+
+    #[method(name = "SetData")]
+    fn set_payload_xml(&mut self, obj: &mut Xml) -> RetCode {
+        let data = obj.to_xml(false);
+        self.inner = match self.inner.take() {
+            Some(msg) => {
+                Some(if msg.retained() {
+                    Message::new_retained(msg.topic(), data, msg.qos())
+                } else {
+                    Message::new(msg.topic(), data, msg.qos())
+                })
+            },
+            None => Some(MessageBuilder::new().payload(data).finalize())
+        };
+        RetCode::OK
+    }
+
     #[method(name = "GetData")]
     fn payload_binary(&self) -> &[u8] { self.inner.as_ref().map(|msg| msg.payload()).unwrap_or_default() }
 
@@ -139,6 +184,38 @@ impl MqttMessage {
         }
     }
 
+    /// 解压后按字符串获取内容
+    ///
+    /// # Parameters
+    ///
+    /// - `encoding` 字符编码，默认`UTF-8`
+    /// - `property` 指示压缩格式的用户属性名称(`MQTT 5`)，默认`Content-Encoding`
+    ///
+    /// # Description
+    ///
+    /// 依次根据用户属性(`gzip`或`zstd`)、`Content-Type`属性(包含`gzip`或`zstd`)以及内容的格式标识检测压缩格式，
+    /// 未压缩时与`GetDataString`相同，解压失败时返回空字符串并记录错误
+    #[method(name = "GetDataStringAuto", overload = 2)]
+    fn payload_string_auto(&mut self, encoding: Option<pblong>, property: Option<String>) -> String {
+        let Some(msg) = self.inner.as_ref() else {
+            return String::new();
+        };
+        let property = property.as_deref().unwrap_or("Content-Encoding");
+        let data = match Compression::detect(msg, property) {
+            Some(compression) => {
+                match compression.decompress(msg.payload()) {
+                    Ok(data) => Cow::Owned(data),
+                    Err(e) => {
+                        self.error.set(RetCode::E_INVALID_DATA, format!("decompress failed: {e}"));
+                        return String::new();
+                    }
+                }
+            },
+            None => Cow::Borrowed(msg.payload())
+        };
+        conv::decode(&data, encoding.unwrap_or(conv::ENCODING_UTF8)).into_owned()
+    }
+
     #[method(name = "GetDataJSON", overload = 1)]
     fn payload_json(&self, encoding: Option<pblong>) -> Object {
         let data = if let Some(data) = self.inner.as_ref().map(|msg| msg.payload()) {
@@ -146,7 +223,7 @@ impl MqttMessage {
         } else {
             "".into()
         };
-        pfw::json_parse(self.get_session(), &data)
+        json::parse(self.get_session(), &data)
     }
 
     #[method(name = "GetDataXML", overload = 1)]
@@ -156,6 +233,106 @@ impl MqttMessage {
         } else {
             "".into()
         };
-        pfw::xml_parse(self.get_session(), &data)
+        xml::parse(self.get_session(), &data)
+    }
+
+    /// 信封中的发送时间(`UNIX`毫秒)
+    ///
+    /// 未启用信封(`SetPayloadPipeline`)时返回`0`
+    #[method(name = "GetEnvelopeTime")]
+    fn envelope_time(&self) -> pblonglong {
+        self.envelope.as_ref().map(|envelope| envelope.timestamp).unwrap_or_default()
     }
+
+    /// 信封中的设备标识
+    ///
+    /// 未启用信封(`SetPayloadPipeline`)时返回空字符串
+    #[method(name = "GetEnvelopeDevice")]
+    fn envelope_device(&self) -> &str {
+        self.envelope.as_ref().map(|envelope| envelope.device_id.as_str()).unwrap_or_default()
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+/// 内容的压缩格式
+enum Compression {
+    Gzip,
+    Zstd
+}
+
+impl Compression {
+    fn from_name(name: &str) -> Option<Compression> {
+        let name = name.to_ascii_lowercase();
+        if name.contains("gzip") {
+            Some(Compression::Gzip)
+        } else if name.contains("zstd") {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// 检测压缩格式
+    ///
+    /// 优先使用属性，其次使用格式标识
+    fn detect(msg: &Message, property: &str) -> Option<Compression> {
+        let props = msg.properties();
+        props
+            .user_iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(property))
+            .and_then(|(_, val)| Compression::from_name(&val))
+            .or_else(|| {
+                props.get_string(PropertyCode::ContentType).and_then(|val| Compression::from_name(&val))
+            })
+            .or_else(|| {
+                let data = msg.payload();
+                if data.starts_with(&[0x1f, 0x8b]) {
+                    Some(Compression::Gzip)
+                } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+                    Some(Compression::Zstd)
+                } else {
+                    None
+                }
+            })
+    }
+
+    fn decompress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut buf = Vec::new();
+                MultiGzDecoder::new(data).read_to_end(&mut buf)?;
+                Ok(buf)
+            },
+            Compression::Zstd => zstd::stream::decode_all(data)
+        }
+    }
+}
+
+/// 复制消息并替换主题
+pub fn with_topic(msg: &Message, topic: String) -> Message {
+    MessageBuilder::new()
+        .topic(topic)
+        .payload(msg.payload())
+        .qos(msg.qos())
+        .retained(msg.retained())
+        .properties(msg.properties().clone())
+        .finalize()
+}
+
+/// 复制消息并替换内容
+pub fn with_payload(msg: &Message, payload: Vec<u8>) -> Message {
+    MessageBuilder::new()
+        .topic(msg.topic())
+        .payload(payload)
+        .qos(msg.qos())
+        .retained(msg.retained())
+        .properties(msg.properties().clone())
+        .finalize()
 }
@@ -1,23 +1,57 @@
-use crate::prelude::*;
+use crate::{
+    base::{
+        cert::CertInfo, diag::{LiveObject, MemoryUsage}, locale::tr
+    }, prelude::*
+};
+use native_tls::TlsConnector;
 use paho_mqtt::{
-    async_client::AsyncClient, ConnectOptionsBuilder, ConnectToken, CreateOptionsBuilder, DeliveryToken, Message, SubscribeToken
+    async_client::AsyncClient, ConnectOptionsBuilder, ConnectToken, CreateOptionsBuilder, DeliveryToken, Message, RequestResponse, SubscribeToken
 };
 use pbni::{pbx::*, prelude::*};
 use reactor::*;
-use std::{mem::take, time::Duration};
+use std::{
+    future::Future, mem::take, net::{TcpStream, ToSocketAddrs}, sync::{Arc, Mutex}, time::Duration
+};
 use tokio::time;
 
 mod config;
 mod message;
+mod bench;
+mod pipeline;
+mod persist;
 
+use bench::{BenchParams, SharedBench};
 use config::{MqttConfig, MqttConfigEx};
 use message::MqttMessage;
+use persist::{PersistStore, PersistedMessage};
+
+/// `SUBACK`中表示订阅失败的最小返回码
+const SUBACK_FAILURE: i32 = 0x80;
 
 struct Subscribe {
     topic_filter: String,
     qos: i32
 }
 
+/// 在线状态
+struct Presence {
+    topic: String,
+    online: Vec<u8>,
+    offline: Vec<u8>,
+    qos: i32,
+    retain: bool
+}
+
+impl Presence {
+    fn message(&self, topic: String, payload: &[u8]) -> Message {
+        if self.retain {
+            Message::new_retained(topic, payload, self.qos)
+        } else {
+            Message::new(topic, payload, self.qos)
+        }
+    }
+}
+
 struct MqttClient {
     state: HandlerState,
     client: Option<AsyncClient>,
@@ -25,7 +59,20 @@ struct MqttClient {
     has_connected: bool,
     has_closed: bool,
     conn_id: u64,
-    offline_publish: Vec<Message>
+    offline_publish: Vec<Message>,
+    /// 离线消息队列
+    offline_usage: MemoryUsage,
+    correlation_id: Option<String>,
+    /// 进行中的吞吐量测试
+    bench: SharedBench,
+    bench_id: u32,
+    presence: Option<Presence>,
+    /// 当前连接的服务器地址
+    server_uri: String,
+    /// 当前连接的服务器证书信息
+    peer_cert: Option<String>,
+    _live: LiveObject,
+    error: LastError
 }
 
 #[nonvisualobject(name = "nx_mqttclient")]
@@ -39,7 +86,16 @@ impl MqttClient {
             has_connected: false,
             has_closed: false,
             conn_id: 0,
-            offline_publish: Default::default()
+            offline_publish: Default::default(),
+            offline_usage: MemoryUsage::new("mqtt.offline_queue"),
+            correlation_id: None,
+            bench: Arc::new(Mutex::new(None)),
+            bench_id: 0,
+            presence: None,
+            server_uri: String::new(),
+            peer_cert: None,
+            _live: LiveObject::new("nx_mqttclient"),
+            error: LastError::default()
         }
     }
 
@@ -53,18 +109,34 @@ impl MqttClient {
 
     #[method(name = "Open", overload = 1)]
     fn open(&mut self, url: String, cfg: Option<&mut MqttConfig>) -> RetCode {
+        if let Some(rv) = self.deny_foreign_thread() {
+            return rv;
+        }
         if self.client.is_some() {
             return RetCode::E_BUSY;
         }
+        //遗嘱消息在创建时添加主题前缀
+        let will = self
+            .presence
+            .as_ref()
+            .map(|presence| presence.message(presence.topic.clone(), &presence.offline));
         let (create_cfg, conn_cfg, cfg) = match cfg {
-            Some(cfg) => cfg.build(url),
+            Some(cfg) => cfg.build(url, will),
+            //已加载全局配置时作为默认配置
+            None if crate::base::config::is_loaded() => MqttConfig::default().build(url, will),
             None => {
                 let mut conn_builder = ConnectOptionsBuilder::default();
                 conn_builder.server_uris(&url.split(";").collect::<Vec<&str>>());
+                if let Some(will) = will {
+                    conn_builder.will_message(will);
+                }
                 (CreateOptionsBuilder::default().finalize(), conn_builder.finalize(), Default::default())
             }
         };
-        let client = AsyncClient::new(create_cfg)?;
+        let client = match AsyncClient::new(create_cfg) {
+            Ok(client) => client,
+            Err(e) => return self.error.set(RetCode::FAILED, e)
+        };
         let invoker = self.invoker();
         client.set_connected_callback({
             let invoker = invoker.clone();
@@ -83,12 +155,31 @@ impl MqttClient {
                             } else {
                                 true
                             };
+                            //每次连接后发布在线状态
+                            if let Some(presence) = this.presence.as_ref() {
+                                let msg =
+                                    presence.message(this.cfg.add_prefix(&presence.topic), &presence.online);
+                                let client = this.client.as_ref().unwrap(); //SAFETY
+                                this.watch_publish(msg.topic().to_owned(), client.publish(msg));
+                            }
+                            //首次连接在连接完成后获取，此时才能确定连接的服务器
+                            if is_reconnect {
+                                this.watch_peer_certificate();
+                            }
+                            let alive = this.get_alive_state();
                             //TODO - 支持`session present`检测
                             this.on_open(is_reconnect, false);
+                            //NOTE 对象可能被销毁
+                            if alive.is_dead() {
+                                return;
+                            }
                             //处理离线消息
-                            let client = this.client.as_ref().unwrap(); //SAFETY
+                            let Some(client) = this.client.as_ref() else {
+                                return;
+                            };
                             if !this.offline_publish.is_empty() {
                                 let offline_publish = take(&mut this.offline_publish);
+                                this.offline_usage.set(0);
                                 for msg in offline_publish {
                                     this.watch_publish(msg.topic().to_owned(), client.publish(msg));
                                 }
@@ -122,7 +213,7 @@ impl MqttClient {
                     let _ = invoker
                         .invoke((), |this, ()| {
                             this.has_closed = true;
-                            this.on_close(-1, "lost".to_owned());
+                            this.on_close(-1, tr("lost", "连接丢失").to_owned());
                         })
                         .await;
                 });
@@ -130,14 +221,51 @@ impl MqttClient {
         });
         client.set_message_callback({
             let invoker = invoker.clone();
+            let bench = self.bench.clone();
+            let topic_prefix = cfg.topic_prefix.clone();
+            let pipeline = cfg.pipeline.clone();
             move |_, msg| {
                 if let Some(msg) = msg {
+                    if bench::on_message(&bench, &msg) {
+                        return;
+                    }
+                    let topic = config::strip_topic_prefix(&topic_prefix, msg.topic());
+                    let msg = if topic.len() != msg.topic().len() {
+                        message::with_topic(&msg, topic.to_owned())
+                    } else {
+                        msg
+                    };
+                    //在回调线程中还原消息内容，不占用`UI`线程
+                    let (msg, envelope) = if pipeline.is_empty() {
+                        (msg, None)
+                    } else {
+                        match pipeline.decode(msg.payload()) {
+                            Ok((payload, envelope)) => (message::with_payload(&msg, payload), envelope),
+                            Err(e) => {
+                                let info =
+                                    format!("{}: {}, {e}", tr("payload error", "消息还原失败"), msg.topic());
+                                let invoker = invoker.clone();
+                                runtime::spawn(async move {
+                                    let _ = invoker
+                                        .invoke(info, |this, info| {
+                                            this.on_error(error_code::ERROR_PAYLOAD, info)
+                                        })
+                                        .await;
+                                });
+                                return;
+                            }
+                        }
+                    };
                     let invoker = invoker.clone();
                     runtime::spawn(async move {
                         let _ = invoker
-                            .invoke(msg, |this, msg| {
-                                let obj =
-                                    MqttMessage::new_object_modify(this.get_session(), |obj| obj.init(msg));
+                            .invoke((msg, envelope), |this, (msg, envelope)| {
+                                let obj = MqttMessage::new_object_modify(this.get_session(), |obj| {
+                                    obj.init(msg);
+                                    if let Some(envelope) = envelope {
+                                        obj.set_envelope(envelope);
+                                    }
+                                });
                                 this.on_message(obj);
                             })
                             .await;
@@ -146,10 +274,15 @@ impl MqttClient {
             }
         });
         let token = client.connect(conn_cfg);
+        self.server_uri = url.split(';').next().unwrap_or_default().to_owned();
+        self.peer_cert = None;
         self.client = Some(client);
         self.cfg = cfg;
         self.conn_id += 1;
         self.watch_connect(token);
+        //关闭运行时前投递离线消息
+        self.on_shutdown(|this| this.flush_offline());
+        self.error.clear();
 
         RetCode::OK
     }
@@ -157,54 +290,251 @@ impl MqttClient {
     #[method(name = "Close")]
     fn close(&mut self) -> RetCode {
         self.offline_publish.clear();
+        self.offline_usage.set(0);
         let has_connected = self.has_connected;
         let has_closed = self.has_closed;
         self.has_connected = false;
         self.has_closed = false;
         if let Some(client) = self.client.take() {
+            //正常断开时服务器不发布遗嘱消息，先发布离线状态
+            let offline = match self.presence.as_ref() {
+                Some(presence) if has_connected && !has_closed => {
+                    Some(presence.message(self.cfg.add_prefix(&presence.topic), &presence.offline))
+                },
+                _ => None
+            };
             runtime::spawn(async move {
-                let _ = time::timeout(Duration::from_secs(3), client.disconnect(None)).await;
+                let _ = time::timeout(Duration::from_secs(3), async {
+                    if let Some(offline) = offline {
+                        let _ = client.publish(offline).await;
+                    }
+                    client.disconnect(None).await
+                })
+                .await;
             });
             if has_connected && !has_closed {
-                self.on_close(0, "close".to_owned());
+                self.on_close(0, tr("close", "连接已关闭").to_owned());
             }
         }
         RetCode::OK
     }
 
+    /// 启用在线状态
+    ///
+    /// # Parameters
+    ///
+    /// - `topic` 状态主题
+    /// - `online_payload` 在线状态，每次连接(包括重连)后发布
+    /// - `offline_payload` 离线状态，作为遗嘱消息在连接异常断开时由服务器发布，调用`Close`时主动发布
+    /// - `qos` 服务质量
+    /// - `retain` 是否保留消息，通常为`true`
+    ///
+    /// # Description
+    ///
+    /// 需要在`Open`前调用，替换`nx_mqttconfig.SetWillMessage`设置的遗嘱消息
+    #[method(name = "EnablePresence")]
+    fn enable_presence(
+        &mut self,
+        topic: String,
+        online_payload: String,
+        offline_payload: String,
+        qos: pblong,
+        retain: bool
+    ) -> RetCode {
+        if self.client.is_some() {
+            return RetCode::E_BUSY;
+        }
+        if topic.is_empty() || topic.contains(['+', '#']) || !(0..=2).contains(&qos) {
+            return self
+                .error
+                .set(RetCode::E_INVALID_ARGUMENT, format!("invalid presence topic or qos: {topic}"));
+        }
+        self.presence = Some(Presence {
+            topic,
+            online: online_payload.into_bytes(),
+            offline: offline_payload.into_bytes(),
+            qos,
+            retain
+        });
+        RetCode::OK
+    }
+
+    /// 服务器证书
+    ///
+    /// # Returns
+    ///
+    /// 同`nx_httpresponse.GetPeerCertificate`，未连接或不是`TLS`连接(`ssl://`、`mqtts://`、`wss://`)时返回空字符串
+    ///
+    /// # Description
+    ///
+    /// `paho`不提供已建立连接的证书，每次连接成功后在后台与连接的服务器单独握手获取，
+    /// 完成前返回空字符串，完成后触发`OnPeerCertificate`
+    ///
+    /// NOTE 单独握手可能被负载均衡分配到其它节点，证书不一定与当前连接相同
+    #[method(name = "GetPeerCertificate")]
+    fn peer_certificate(&mut self) -> String {
+        if !self.is_open() {
+            return String::new();
+        }
+        self.peer_cert.clone().unwrap_or_default()
+    }
+
+    /// 设置关联ID
+    ///
+    /// # Description
+    ///
+    /// 关联ID会记录在之后发起的发布、订阅等操作的日志中，空字符串恢复默认(连接序号)
+    #[method(name = "SetCorrelationId")]
+    fn set_correlation_id(&mut self, id: String) -> RetCode {
+        self.correlation_id = if id.is_empty() {
+            None
+        } else {
+            Some(id)
+        };
+        RetCode::OK
+    }
+
     #[method(name = "Publish")]
     fn publish(&mut self, msg: &mut MqttMessage) -> RetCode {
+        if let Some(rv) = self.deny_foreign_thread() {
+            return rv;
+        }
         if let Some(client) = self.client.as_ref() {
             let msg = match msg.take() {
+                Some(msg) if !self.cfg.topic_prefix.is_empty() => {
+                    message::with_topic(&msg, self.cfg.add_prefix(msg.topic()))
+                },
                 Some(msg) => msg,
-                None => return RetCode::E_INVALID_OBJECT
+                None => return self.error.set(RetCode::E_INVALID_OBJECT, "message already published")
+            };
+            let msg = if self.cfg.pipeline.is_empty() {
+                msg
+            } else {
+                match self.cfg.pipeline.encode(msg.payload()) {
+                    Ok(payload) => message::with_payload(&msg, payload),
+                    Err(e) => return self.error.set(RetCode::E_INVALID_DATA, e)
+                }
             };
             if (self.has_connected || !self.cfg.offline_queue) && client.is_connected() {
                 self.watch_publish(msg.topic().to_owned(), client.publish(msg));
             } else if self.cfg.offline_queue {
+                self.offline_usage.set(self.offline_usage.bytes() + msg.topic().len() + msg.payload().len());
                 self.offline_publish.push(msg);
             } else {
-                return RetCode::E_IO_ERROR;
+                return self.error.set(RetCode::E_IO_ERROR, "not connected");
             }
             RetCode::OK
         } else {
-            RetCode::E_INVALID_HANDLE
+            self.error.set(RetCode::E_INVALID_HANDLE, "client not opened")
+        }
+    }
+
+    /// 列出持久化存储中的消息
+    ///
+    /// # Parameters
+    ///
+    /// - `cfg` 读取该配置(`nx_mqttconfig.SetPersistence`)的存储，用于`Open`前查看，默认为最近一次`Open`的存储
+    ///
+    /// # Returns
+    ///
+    /// `JSON`数组，每项包含`key`(键)与`size`(字节数)，可识别的发布消息还包含`topic`、`qos`、`retain`以及`payload`(`Base64`编码)，
+    /// 没有设置持久化或读取失败时返回空数组
+    ///
+    /// # Description
+    ///
+    /// 包括已发送未确认的消息与离线时缓存的命令，主题包含前缀，内容为转换(`SetPayloadPipeline`)后的数据
+    #[method(name = "ListPersisted", overload = 1)]
+    fn list_persisted(&mut self, cfg: Option<&mut MqttConfig>) -> String {
+        let store = match cfg {
+            Some(cfg) => cfg.persistence(),
+            None => self.cfg.persistence.as_ref()
+        };
+        let entries = store.and_then(|store| store.entries().ok()).unwrap_or_default();
+        serde_json::Value::Array(entries.iter().map(|(key, data)| persist::describe(key, data)).collect())
+            .to_string()
+    }
+
+    /// 重新发布持久化存储中的消息
+    ///
+    /// # Description
+    ///
+    /// 需要已连接，按原主题、`QoS`与保留标志发布可识别的消息，不会从存储中删除，
+    /// 客户端重连时也会自动重发未确认的消息，接收方可能收到重复的消息
+    #[method(name = "ReplayPersisted")]
+    fn replay_persisted(&mut self) -> RetCode {
+        if let Some(rv) = self.deny_foreign_thread() {
+            return rv;
+        }
+        let Some(client) = self.client.as_ref() else {
+            return self.error.set(RetCode::E_INVALID_HANDLE, "client not opened");
+        };
+        if !self.has_connected || !client.is_connected() {
+            return self.error.set(RetCode::E_IO_ERROR, "not connected");
+        }
+        let Some(store) = self.cfg.persistence.as_ref() else {
+            return RetCode::OK;
+        };
+        let entries = match store.entries() {
+            Ok(entries) => entries,
+            Err(e) => return self.error.set(RetCode::E_IO_ERROR, e)
+        };
+        for (key, data) in entries {
+            if let Some(msg) = PersistedMessage::decode(&key, &data) {
+                let msg = if msg.retain {
+                    Message::new_retained(msg.topic, msg.payload, msg.qos)
+                } else {
+                    Message::new(msg.topic, msg.payload, msg.qos)
+                };
+                self.watch_publish(msg.topic().to_owned(), client.publish(msg));
+            }
+        }
+        RetCode::OK
+    }
+
+    /// 清空持久化存储
+    ///
+    /// # Description
+    ///
+    /// 需要在`Open`前或`Close`后调用，清空后未确认的消息与离线命令不再在连接时重发
+    ///
+    /// # Parameters
+    ///
+    /// - `cfg` 清空该配置的存储，默认为最近一次`Open`的存储
+    #[method(name = "ClearPersisted", overload = 1)]
+    fn clear_persisted(&mut self, cfg: Option<&mut MqttConfig>) -> RetCode {
+        if self.client.is_some() {
+            return RetCode::E_BUSY;
+        }
+        let store = match cfg {
+            Some(cfg) => cfg.persistence(),
+            None => self.cfg.persistence.as_ref()
+        };
+        match store.map(PersistStore::clear) {
+            Some(Err(e)) => self.error.set(RetCode::E_IO_ERROR, e),
+            _ => RetCode::OK
         }
     }
 
     #[method(name = "Subscribe", overload = 1)]
     fn subscribe(&mut self, topic_filter: String, qos: Option<pblong>) -> RetCode {
+        if let Some(rv) = self.deny_foreign_thread() {
+            return rv;
+        }
         if let Some(client) = self.client.as_ref() {
             let qos = qos.unwrap_or_default();
-            self.watch_subscribe(topic_filter.clone(), client.subscribe(topic_filter, qos));
+            let token = client.subscribe(self.cfg.add_prefix(&topic_filter), qos);
+            self.watch_subscribe(vec![topic_filter], vec![qos], token);
             RetCode::OK
         } else {
-            RetCode::E_INVALID_HANDLE
+            self.error.set(RetCode::E_INVALID_HANDLE, "client not opened")
         }
     }
 
     #[method(name = "Subscribe", overload = 1)]
     fn subscribe_many(&mut self, topic_filters: Vec<String>, qos: Option<Vec<pblong>>) -> RetCode {
+        if let Some(rv) = self.deny_foreign_thread() {
+            return rv;
+        }
         if let Some(client) = self.client.as_ref() {
             let qos = qos.unwrap_or_else(|| {
                 let mut qos = Vec::with_capacity(topic_filters.len());
@@ -212,65 +542,263 @@ impl MqttClient {
                 qos
             });
             assert_eq!(topic_filters.len(), qos.len());
-            self.watch_subscribe(topic_filters.join(";"), client.subscribe_many(&topic_filters, &qos));
+            let prefixed: Vec<String> =
+                topic_filters.iter().map(|topic_filter| self.cfg.add_prefix(topic_filter)).collect();
+            let token = client.subscribe_many(&prefixed, &qos);
+            self.watch_subscribe(topic_filters, qos, token);
             RetCode::OK
         } else {
-            RetCode::E_INVALID_HANDLE
+            self.error.set(RetCode::E_INVALID_HANDLE, "client not opened")
         }
     }
 
     #[method(name = "Unsubscribe")]
     fn unsubscribe(&mut self, topic_filter: String) -> RetCode {
+        if let Some(rv) = self.deny_foreign_thread() {
+            return rv;
+        }
         if let Some(client) = self.client.as_ref() {
+            let topic_filter = self.cfg.add_prefix(&topic_filter);
             self.watch_unsubscribe(topic_filter.clone(), client.unsubscribe(topic_filter));
             RetCode::OK
         } else {
-            RetCode::E_INVALID_HANDLE
+            self.error.set(RetCode::E_INVALID_HANDLE, "client not opened")
         }
     }
 
     #[method(name = "Unsubscribe")]
     fn unsubscribe_many(&mut self, topic_filters: Vec<String>) -> RetCode {
+        if let Some(rv) = self.deny_foreign_thread() {
+            return rv;
+        }
         if let Some(client) = self.client.as_ref() {
+            let topic_filters: Vec<String> =
+                topic_filters.iter().map(|topic_filter| self.cfg.add_prefix(topic_filter)).collect();
             self.watch_unsubscribe(topic_filters.join(";"), client.unsubscribe_many(&topic_filters));
             RetCode::OK
         } else {
-            RetCode::E_INVALID_HANDLE
+            self.error.set(RetCode::E_INVALID_HANDLE, "client not opened")
+        }
+    }
+
+    /// 吞吐量测试
+    ///
+    /// # Parameters
+    ///
+    /// - `topic` 回显主题，测试期间订阅此主题，发布的测试消息经服务器回显后统计
+    /// - `count` 消息数量
+    /// - `size` 消息大小(字节)，不小于`20`
+    /// - `qos` 服务质量，默认`0`
+    ///
+    /// # Description
+    ///
+    /// 需要已连接，测试完成后触发`OnBenchmark`，测试消息不触发`OnMessage`，
+    /// 所有消息发布后最多等待`10`秒接收回显，未收到的消息计为丢失
+    #[method(name = "Benchmark", overload = 1)]
+    fn benchmark(&mut self, topic: String, count: pbulong, size: pbulong, qos: Option<pblong>) -> RetCode {
+        if let Some(rv) = self.deny_foreign_thread() {
+            return rv;
+        }
+        let qos = qos.unwrap_or_default();
+        if topic.is_empty() || count == 0 || !(0..=2).contains(&qos) {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "invalid benchmark topic, count or qos");
+        }
+        let Some(client) = self.client.as_ref() else {
+            return self.error.set(RetCode::E_INVALID_HANDLE, "client not opened");
+        };
+        if !client.is_connected() {
+            return self.error.set(RetCode::E_IO_ERROR, "not connected");
+        }
+        if self.bench.lock().unwrap().is_some() {
+            return RetCode::E_BUSY;
+        }
+        self.bench_id = self.bench_id.wrapping_add(1);
+        let fut = bench::run(client.clone(), self.bench.clone(), BenchParams {
+            run_id: self.bench_id,
+            topic: self.cfg.add_prefix(&topic),
+            count,
+            size: size as usize,
+            qos
+        });
+        self.spawn(fut, |this, summary| this.on_benchmark(summary));
+        RetCode::OK
+    }
+
+    /// 创建对象的线程ID
+    #[method(name = "GetOwnerThreadId")]
+    fn get_owner_thread_id(&self) -> pbulong { self.state.owner_thread_id() as pbulong }
+
+    /// 调用线程是否为创建对象的线程
+    ///
+    /// # Description
+    ///
+    /// 对象只能在创建它的线程(会话)中使用，在其它线程中调用`Open`、`Publish`、`Subscribe`等方法时返回`E_ACCESS_DENIED`
+    #[method(name = "IsCallerOwnerThread")]
+    fn is_caller_owner_thread(&self) -> bool { self.state.is_owner_thread() }
+
+    /// 暂停事件
+    ///
+    /// # Description
+    ///
+    /// 暂停期间到达的事件被缓存，调用`ResumeEvents`或`FlushEvents`后按顺序触发
+    #[method(name = "SuspendEvents")]
+    fn suspend_events(&mut self) -> RetCode {
+        self.state.suspend_events();
+        RetCode::OK
+    }
+
+    /// 恢复事件并按顺序触发缓存的事件
+    ///
+    /// # Returns
+    ///
+    /// 触发的事件数量
+    #[method(name = "ResumeEvents")]
+    fn resume_events(&mut self) -> pbulong { self.state.resume_events() as pbulong }
+
+    /// 按顺序触发缓存的事件并保持暂停状态
+    ///
+    /// # Returns
+    ///
+    /// 触发的事件数量
+    #[method(name = "FlushEvents")]
+    fn flush_events(&mut self) -> pbulong { self.state.flush_events() as pbulong }
+
+    #[method(name = "IsEventsSuspended")]
+    fn is_events_suspended(&self) -> bool { self.state.is_events_suspended() }
+
+    /// 投递离线队列中的消息
+    ///
+    /// # Description
+    ///
+    /// 仅等待投递完成，不再通知发送结果
+    fn flush_offline(&mut self) {
+        if let Some(client) = self.client.as_ref() {
+            if !self.offline_publish.is_empty() {
+                let offline_publish = take(&mut self.offline_publish);
+                self.offline_usage.set(0);
+                let tokens: Vec<DeliveryToken> =
+                    offline_publish.into_iter().map(|msg| client.publish(msg)).collect();
+                runtime::spawn(async move {
+                    for token in tokens {
+                        let _ = token.await;
+                    }
+                });
+            }
         }
     }
 
     fn watch_connect(&self, token: ConnectToken) {
         let conn_id = self.conn_id;
-        self.spawn(async move { token.await }, move |this, rv| {
+        self.spawn(self.correlate("connect", token), move |this, rv| {
             if this.client.is_some() && conn_id == this.conn_id {
-                if let Err(e) = rv {
-                    this.client = None;
-                    this.on_error(error_code::ERROR_CONNECT, format!("connect error: {e}"));
+                match rv {
+                    Ok(rsp) => {
+                        if let Some(conn_rsp) = rsp.connect_response() {
+                            this.server_uri = conn_rsp.server_uri;
+                        }
+                        this.watch_peer_certificate();
+                    },
+                    Err(e) => {
+                        this.client = None;
+                        this.on_error(
+                            error_code::ERROR_CONNECT,
+                            format!("{}: {e}", tr("connect error", "连接失败"))
+                        );
+                    }
                 }
             }
         });
     }
 
+    /// 在后台获取当前连接的服务器证书，完成后触发`OnPeerCertificate`
+    fn watch_peer_certificate(&mut self) {
+        self.peer_cert = None;
+        let Some((host, port)) = tls_endpoint(&self.server_uri) else {
+            return;
+        };
+        let conn_id = self.conn_id;
+        let server_uri = self.server_uri.clone();
+        self.spawn(
+            async move {
+                tokio::task::spawn_blocking(move || probe_certificate(&host, port)).await.ok().flatten()
+            },
+            move |this, der| {
+                //期间重新连接到其它服务器
+                if this.client.is_none() || conn_id != this.conn_id || this.server_uri != server_uri {
+                    return;
+                }
+                let info = der
+                    .and_then(|der| CertInfo::from_der(&der))
+                    .map(|info| info.to_json().to_string())
+                    .unwrap_or_default();
+                this.peer_cert = Some(info.clone());
+                this.on_peer_certificate(info);
+            }
+        );
+    }
+
+    /// 在其它线程中调用时返回`E_ACCESS_DENIED`
+    fn deny_foreign_thread(&self) -> Option<RetCode> {
+        if self.state.is_owner_thread() {
+            return None;
+        }
+        #[cfg(feature = "log")]
+        warn!("MQTT client accessed from a thread other than the owner thread");
+        Some(RetCode::E_ACCESS_DENIED)
+    }
+
     fn watch_publish(&self, topic: String, token: DeliveryToken) {
         let conn_id = self.conn_id;
-        self.spawn(async move { token.await }, move |this, rv| {
+        self.spawn(self.correlate("publish", token), move |this, rv| {
             if this.client.is_some() && conn_id == this.conn_id {
                 if let Err(e) = rv {
-                    this.on_error(error_code::ERROR_PUBLISH, format!("publish error: {topic}, {e}"));
+                    this.on_error(
+                        error_code::ERROR_PUBLISH,
+                        format!("{}: {topic}, {e}", tr("publish error", "发布失败"))
+                    );
                 }
             }
         });
     }
 
-    fn watch_subscribe(&self, topic_filters: String, token: SubscribeToken) {
+    /// 等待订阅结果
+    ///
+    /// 服务器授予的`QoS`低于请求或拒绝订阅时触发`OnSubscribeDowngraded`
+    fn watch_subscribe(&self, topic_filters: Vec<String>, qos: Vec<i32>, token: SubscribeToken) {
         let conn_id = self.conn_id;
-        self.spawn(async move { token.await }, move |this, rv| {
-            if this.client.is_some() && conn_id == this.conn_id {
-                if let Err(e) = rv {
+        self.spawn(self.correlate("subscribe", token), move |this, rv| {
+            if this.client.is_none() || conn_id != this.conn_id {
+                return;
+            }
+            let rsp = match rv {
+                Ok(rsp) => rsp,
+                Err(e) => {
                     this.on_error(
                         error_code::ERROR_SUBSCRIBE,
-                        format!("subscribe error: {topic_filters}, {e}")
+                        format!("{}: {}, {e}", tr("subscribe error", "订阅失败"), topic_filters.join(";"))
                     );
+                    return;
+                }
+            };
+            let granted: Vec<i32> = match rsp.request_response() {
+                RequestResponse::Subscribe(code) => vec![*code as i32],
+                RequestResponse::SubscribeMany(codes) => codes.iter().map(|code| *code as i32).collect(),
+                _ => return
+            };
+            let alive = this.get_alive_state();
+            for ((topic_filter, requested), granted) in topic_filters.into_iter().zip(qos).zip(granted) {
+                if granted >= requested && granted < SUBACK_FAILURE {
+                    continue;
+                }
+                #[cfg(feature = "log")]
+                warn!(
+                    "MQTT subscription downgraded: {}, requested {}, granted {}",
+                    topic_filter, requested, granted
+                );
+                this.on_subscribe_downgraded(topic_filter, requested, granted);
+                //NOTE 对象可能被销毁或在事件中关闭
+                if alive.is_dead() || this.client.is_none() || conn_id != this.conn_id {
+                    break;
                 }
             }
         });
@@ -278,18 +806,40 @@ impl MqttClient {
 
     fn watch_unsubscribe(&self, topic_filters: String, token: SubscribeToken) {
         let conn_id = self.conn_id;
-        self.spawn(async move { token.await }, move |this, rv| {
+        self.spawn(self.correlate("unsubscribe", token), move |this, rv| {
             if this.client.is_some() && conn_id == this.conn_id {
                 if let Err(e) = rv {
                     this.on_error(
                         error_code::ERROR_UNSUBSCRIBE,
-                        format!("unsubscribe error: {topic_filters}, {e}")
+                        format!("{}: {topic_filters}, {e}", tr("unsubscribe error", "取消订阅失败"))
                     );
                 }
             }
         });
     }
 
+    /// 为操作附加关联ID
+    fn correlate<F, T>(&self, op: &'static str, token: F) -> impl Future<Output = F::Output>
+    where
+        F: Future<Output = Result<T, paho_mqtt::Error>> + Send + 'static
+    {
+        let correlation_id = match self.correlation_id.as_ref() {
+            Some(id) => id.clone(),
+            None => format!("mqtt-{}", self.conn_id)
+        };
+        futures::correlate(correlation_id, async move {
+            let rv = token.await;
+            #[cfg(feature = "log")]
+            match &rv {
+                Ok(_) => trace!("MQTT {} completed", op),
+                Err(e) => warn!("MQTT {} failed: {}", op, e)
+            }
+            #[cfg(not(feature = "log"))]
+            let _ = op;
+            rv
+        })
+    }
+
     #[event(name = "OnOpen")]
     fn on_open(&mut self, reconnect: bool, session_present: bool) {}
 
@@ -301,6 +851,43 @@ impl MqttClient {
 
     #[event(name = "OnMessage")]
     fn on_message(&mut self, msg: Object) {}
+
+    /// 订阅被降级或拒绝
+    ///
+    /// # Parameters
+    ///
+    /// - `topic_filter` 订阅的主题过滤器(不含前缀)
+    /// - `requested` 请求的`QoS`
+    /// - `granted` 服务器授予的`QoS`，拒绝时为`SUBACK`的失败码(不小于`128`)
+    ///
+    /// # Description
+    ///
+    /// 授予的`QoS`低于请求时，服务器按较低的`QoS`投递消息，`QoS 0`的消息在断线时可能丢失
+    /// 获取到服务器证书
+    ///
+    /// `info`同`GetPeerCertificate`，获取失败时为空字符串
+    #[event(name = "OnPeerCertificate")]
+    fn on_peer_certificate(&mut self, info: String) {}
+
+    #[event(name = "OnSubscribeDowngraded")]
+    fn on_subscribe_downgraded(&mut self, topic_filter: String, requested: pblong, granted: pblong) {}
+
+    /// 吞吐量测试完成
+    ///
+    /// # Parameters
+    ///
+    /// - `summary` 测试结果(`JSON`)：`sent`、`received`、`lost`、`elapsed_ms`、`msg_per_sec`、`bytes_per_sec`、
+    ///   `latency_ms`(`min`、`avg`、`p50`、`p95`、`max`)以及`error`
+    #[event(name = "OnBenchmark")]
+    fn on_benchmark(&mut self, summary: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
 }
 
 impl Handler for MqttClient {
@@ -308,6 +895,45 @@ impl Handler for MqttClient {
     fn alive_state(&self) -> AliveState { self.get_alive_state() }
 }
 
+/// 解析`TLS`连接的服务器地址
+///
+/// # Returns
+///
+/// 不是`TLS`连接时返回`None`
+fn tls_endpoint(server_uri: &str) -> Option<(String, u16)> {
+    let (scheme, rest) = server_uri.split_once("://")?;
+    let default_port = match scheme.to_ascii_lowercase().as_str() {
+        "ssl" | "mqtts" => 8883,
+        "wss" => 443,
+        _ => return None
+    };
+    let authority = rest.split('/').next()?;
+    //`IPv6`地址为`[addr]:port`
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+        _ => (authority, default_port)
+    };
+    Some((host.trim_start_matches('[').trim_end_matches(']').to_owned(), port))
+}
+
+/// 与服务器单独握手获取证书(`DER`)
+///
+/// NOTE 阻塞执行
+fn probe_certificate(host: &str, port: u16) -> Option<Vec<u8>> {
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    let addr = (host, port).to_socket_addrs().ok()?.next()?;
+    let stream = TcpStream::connect_timeout(&addr, TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(TIMEOUT)).ok()?;
+    let connector = TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .ok()?;
+    let stream = connector.connect(host, stream).ok()?;
+    stream.peer_certificate().ok()??.to_der().ok()
+}
+
 mod error_code {
     use super::*;
 
@@ -315,4 +941,5 @@ mod error_code {
     pub const ERROR_PUBLISH: pblong = -2;
     pub const ERROR_SUBSCRIBE: pblong = -3;
     pub const ERROR_UNSUBSCRIBE: pblong = -4;
+    pub const ERROR_PAYLOAD: pblong = -5;
 }
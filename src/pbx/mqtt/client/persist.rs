@@ -0,0 +1,281 @@
+//! 持久化存储
+//!
+//! 读取`paho`持久化的发送中消息(`s-`/`s5-`)与离线命令(`c-`/`c5-`)
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use paho_mqtt::ClientPersistence;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap, fs, io, path::{Path, PathBuf}, sync::{Arc, Mutex}
+};
+
+/// 文件存储的扩展名
+const FILE_EXTENSION: &str = "msg";
+/// `PUBLISH`报文类型
+const PUBLISH: u8 = 3;
+
+/// 持久化存储的位置
+#[derive(Clone)]
+pub enum PersistStore {
+    /// 内存存储
+    Memory(Arc<Mutex<HashMap<String, Vec<u8>>>>),
+    /// 文件存储
+    File {
+        /// 存储目录
+        root: PathBuf,
+        /// 客户端ID
+        client_id: String
+    }
+}
+
+impl PersistStore {
+    /// 文件存储中当前客户端的目录
+    ///
+    /// `paho`按`<存储目录>/<客户端ID>-<服务器地址>`保存，客户端创建时未指定服务器地址(连接时指定)，
+    /// 因此为`<存储目录>/<客户端ID>-`
+    fn client_dir(root: &Path, client_id: &str) -> PathBuf {
+        root.join(format!("{client_id}-").replace(':', "-"))
+    }
+
+    /// 所有条目
+    pub fn entries(&self) -> io::Result<Vec<(String, Vec<u8>)>> {
+        match self {
+            PersistStore::Memory(map) => {
+                let map = map.lock().unwrap();
+                Ok(map.iter().map(|(key, data)| (key.clone(), data.clone())).collect())
+            },
+            PersistStore::File {
+                root,
+                client_id
+            } => {
+                let mut files = Vec::new();
+                list_files(&Self::client_dir(root, client_id), &mut files)?;
+                files
+                    .into_iter()
+                    .map(|path| {
+                        let key = path.strip_prefix(root).unwrap_or(&path).with_extension("");
+                        Ok((key.to_string_lossy().replace('\\', "/"), fs::read(&path)?))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// 清空当前客户端的条目
+    pub fn clear(&self) -> io::Result<()> {
+        match self {
+            PersistStore::Memory(map) => {
+                map.lock().unwrap().clear();
+                Ok(())
+            },
+            PersistStore::File {
+                root,
+                client_id
+            } => {
+                let mut files = Vec::new();
+                list_files(&Self::client_dir(root, client_id), &mut files)?;
+                files.into_iter().try_for_each(fs::remove_file)
+            }
+        }
+    }
+}
+
+/// 递归查找文件存储的条目
+///
+/// `paho`按`<客户端目录>/<键>.msg`保存
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(FILE_EXTENSION)) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// 持久化的消息
+pub struct PersistedMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: i32,
+    pub retain: bool
+}
+
+impl PersistedMessage {
+    /// 解析条目
+    ///
+    /// 不是发布消息或无法识别时返回`None`
+    pub fn decode(key: &str, data: &[u8]) -> Option<PersistedMessage> {
+        //文件存储的键包含目录
+        let key = key.rsplit('/').next().unwrap_or(key);
+        if let Some(v5) = prefix_kind(key, "s") {
+            decode_packet(data, v5)
+        } else if prefix_kind(key, "c").is_some() {
+            decode_command(data)
+        } else {
+            None
+        }
+    }
+}
+
+/// 转换为`ListPersisted`的条目
+pub fn describe(key: &str, data: &[u8]) -> Value {
+    match PersistedMessage::decode(key, data) {
+        Some(msg) => {
+            json!({
+                "key": key,
+                "size": data.len(),
+                "topic": msg.topic,
+                "qos": msg.qos,
+                "retain": msg.retain,
+                "payload": BASE64.encode(&msg.payload)
+            })
+        },
+        None => {
+            json!({
+                "key": key,
+                "size": data.len()
+            })
+        }
+    }
+}
+
+/// 匹配`<prefix>-`或`<prefix>5-`开头的键
+///
+/// # Returns
+///
+/// 是否为`MQTT 5`
+fn prefix_kind(key: &str, prefix: &str) -> Option<bool> {
+    let rest = key.strip_prefix(prefix)?;
+    if rest.starts_with('-') {
+        Some(false)
+    } else if rest.starts_with("5-") {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// 解析发送中的`PUBLISH`报文
+fn decode_packet(data: &[u8], v5: bool) -> Option<PersistedMessage> {
+    let (&header, rest) = data.split_first()?;
+    if header >> 4 != PUBLISH {
+        return None;
+    }
+    let qos = ((header >> 1) & 0x03) as i32;
+    let retain = header & 0x01 != 0;
+    let (_, rest) = read_varint(rest)?;
+    let (len, rest) = split(rest, 2)?;
+    let (topic, mut rest) = split(rest, u16::from_be_bytes([len[0], len[1]]) as usize)?;
+    if qos > 0 {
+        //报文ID
+        rest = rest.get(2..)?;
+    }
+    if v5 {
+        let (len, props) = read_varint(rest)?;
+        rest = props.get(len..)?;
+    }
+    Some(PersistedMessage {
+        topic: String::from_utf8(topic.to_vec()).ok()?,
+        payload: rest.to_vec(),
+        qos,
+        retain
+    })
+}
+
+/// 解析离线命令
+///
+/// 依次为类型、令牌、主题(以`\0`结尾)、内容长度、内容、`QoS`以及是否保留
+fn decode_command(data: &[u8]) -> Option<PersistedMessage> {
+    let (kind, rest) = read_i32(data)?;
+    if kind != PUBLISH as i32 {
+        return None;
+    }
+    let (_token, rest) = read_i32(rest)?;
+    let end = rest.iter().position(|&b| b == 0)?;
+    let topic = String::from_utf8(rest[..end].to_vec()).ok()?;
+    let (len, rest) = read_i32(&rest[end + 1..])?;
+    let (payload, rest) = split(rest, usize::try_from(len).ok()?)?;
+    let (qos, rest) = read_i32(rest)?;
+    let (retain, _) = read_i32(rest)?;
+    Some(PersistedMessage {
+        topic,
+        payload: payload.to_vec(),
+        qos,
+        retain: retain != 0
+    })
+}
+
+fn split(data: &[u8], mid: usize) -> Option<(&[u8], &[u8])> {
+    if data.len() >= mid {
+        Some(data.split_at(mid))
+    } else {
+        None
+    }
+}
+
+fn read_i32(data: &[u8]) -> Option<(i32, &[u8])> {
+    let (val, rest) = split(data, 4)?;
+    Some((i32::from_ne_bytes(val.try_into().ok()?), rest))
+}
+
+/// 读取可变长度整数
+fn read_varint(data: &[u8]) -> Option<(usize, &[u8])> {
+    let mut val = 0;
+    for (idx, &b) in data.iter().take(4).enumerate() {
+        val |= ((b & 0x7f) as usize) << (7 * idx);
+        if b & 0x80 == 0 {
+            return Some((val, &data[idx + 1..]));
+        }
+    }
+    None
+}
+
+/// 内存存储
+pub struct RuntimeStore {
+    map: Arc<Mutex<HashMap<String, Vec<u8>>>>
+}
+
+impl RuntimeStore {
+    pub fn new(map: Arc<Mutex<HashMap<String, Vec<u8>>>>) -> Self {
+        RuntimeStore {
+            map
+        }
+    }
+}
+
+#[allow(unused_variables)]
+impl ClientPersistence for RuntimeStore {
+    fn open(&mut self, client_id: &str, server_uri: &str) -> paho_mqtt::Result<()> { Ok(()) }
+    fn close(&mut self) -> paho_mqtt::Result<()> { Ok(()) }
+    fn put(&mut self, key: &str, buffers: Vec<&[u8]>) -> paho_mqtt::Result<()> {
+        self.map.lock().unwrap().insert(
+            key.to_owned(),
+            buffers.into_iter().fold(Vec::new(), |mut buf, item| {
+                buf.extend_from_slice(item);
+                buf
+            })
+        );
+        Ok(())
+    }
+    fn get(&mut self, key: &str) -> paho_mqtt::Result<Vec<u8>> {
+        Ok(self.map.lock().unwrap().get(key).cloned().unwrap_or_default())
+    }
+    fn remove(&mut self, key: &str) -> paho_mqtt::Result<()> {
+        self.map.lock().unwrap().remove(key);
+        Ok(())
+    }
+    fn keys(&mut self) -> paho_mqtt::Result<Vec<String>> {
+        Ok(self.map.lock().unwrap().keys().cloned().collect())
+    }
+    fn clear(&mut self) -> paho_mqtt::Result<()> {
+        self.map.lock().unwrap().clear();
+        Ok(())
+    }
+    fn contains_key(&mut self, key: &str) -> bool { self.map.lock().unwrap().contains_key(key) }
+}
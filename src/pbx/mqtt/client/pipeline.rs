@@ -0,0 +1,149 @@
+//! 消息内容转换
+//!
+//! 发布前依次压缩(`zstd`)、加密(`AES-GCM`)并添加信封，收到消息后按相反顺序还原
+
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, KeyInit, OsRng}, Aes128Gcm, Aes256Gcm, Nonce
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::{json, Value};
+use std::{
+    io, time::{SystemTime, UNIX_EPOCH}
+};
+
+/// 随机数长度
+const NONCE_SIZE: usize = 12;
+/// 认证标签长度
+const TAG_SIZE: usize = 16;
+/// 压缩级别
+const COMPRESS_LEVEL: i32 = 3;
+
+/// 加密密钥
+#[derive(Clone)]
+pub enum CipherKey {
+    Aes128(Aes128Gcm),
+    Aes256(Aes256Gcm)
+}
+
+impl CipherKey {
+    /// 根据密钥长度(`16`或`32`字节)创建
+    pub fn new(key: &[u8]) -> Option<Self> {
+        match key.len() {
+            16 => Aes128Gcm::new_from_slice(key).ok().map(CipherKey::Aes128),
+            32 => Aes256Gcm::new_from_slice(key).ok().map(CipherKey::Aes256),
+            _ => None
+        }
+    }
+
+    fn encrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let mut nonce = [0; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+        let encrypted = match self {
+            CipherKey::Aes128(cipher) => cipher.encrypt(Nonce::from_slice(&nonce), data),
+            CipherKey::Aes256(cipher) => cipher.encrypt(Nonce::from_slice(&nonce), data)
+        }
+        .ok()?;
+        let mut out = Vec::with_capacity(NONCE_SIZE + encrypted.len());
+        out.extend_from_slice(&nonce);
+        out.extend(encrypted);
+        Some(out)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if data.len() < NONCE_SIZE + TAG_SIZE {
+            return None;
+        }
+        let (nonce, data) = data.split_at(NONCE_SIZE);
+        match self {
+            CipherKey::Aes128(cipher) => cipher.decrypt(Nonce::from_slice(nonce), data),
+            CipherKey::Aes256(cipher) => cipher.decrypt(Nonce::from_slice(nonce), data)
+        }
+        .ok()
+    }
+}
+
+/// 信封信息
+#[derive(Clone, Default)]
+pub struct Envelope {
+    /// 发送时间(`UNIX`毫秒)
+    pub timestamp: i64,
+    /// 设备标识
+    pub device_id: String
+}
+
+/// 转换配置
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    pub compress: bool,
+    pub cipher: Option<CipherKey>,
+    /// 设备标识，设置时添加信封
+    pub device_id: Option<String>
+}
+
+impl Pipeline {
+    pub fn is_empty(&self) -> bool { !self.compress && self.cipher.is_none() && self.device_id.is_none() }
+
+    /// 发布前转换
+    ///
+    /// # Description
+    ///
+    /// 信封格式为`{"ts":<发送时间>,"device":<设备标识>,"data":<Base64编码的内容>}`
+    pub fn encode(&self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        let mut data = if self.compress {
+            zstd::bulk::compress(payload, COMPRESS_LEVEL)?
+        } else {
+            payload.to_vec()
+        };
+        if let Some(cipher) = self.cipher.as_ref() {
+            data = cipher.encrypt(&data).ok_or_else(|| invalid_data("encrypt failed"))?;
+        }
+        if let Some(device_id) = self.device_id.as_ref() {
+            data = json!({
+                "ts": now(),
+                "device": device_id,
+                "data": BASE64.encode(&data)
+            })
+            .to_string()
+            .into_bytes();
+        }
+        Ok(data)
+    }
+
+    /// 收到消息后还原
+    ///
+    /// # Returns
+    ///
+    /// 原始内容以及信封信息(启用信封时)
+    pub fn decode(&self, payload: &[u8]) -> io::Result<(Vec<u8>, Option<Envelope>)> {
+        let mut envelope = None;
+        let mut data = if self.device_id.is_some() {
+            let value: Value =
+                serde_json::from_slice(payload).map_err(|_| invalid_data("invalid envelope"))?;
+            let data = value
+                .get("data")
+                .and_then(Value::as_str)
+                .and_then(|data| BASE64.decode(data).ok())
+                .ok_or_else(|| invalid_data("invalid envelope data"))?;
+            envelope = Some(Envelope {
+                timestamp: value.get("ts").and_then(Value::as_i64).unwrap_or_default(),
+                device_id: value.get("device").and_then(Value::as_str).unwrap_or_default().to_owned()
+            });
+            data
+        } else {
+            payload.to_vec()
+        };
+        if let Some(cipher) = self.cipher.as_ref() {
+            data = cipher.decrypt(&data).ok_or_else(|| invalid_data("decrypt failed"))?;
+        }
+        if self.compress {
+            data = zstd::stream::decode_all(data.as_slice())?;
+        }
+        Ok((data, envelope))
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|dur| dur.as_millis() as i64).unwrap_or_default()
+}
+
+fn invalid_data(msg: &str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg) }
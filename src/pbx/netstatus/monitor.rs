@@ -0,0 +1,364 @@
+use crate::prelude::*;
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::{
+    io, sync::{
+        atomic::{AtomicBool, Ordering}, Arc
+    }, time::{Duration, Instant}
+};
+use windows::Win32::{
+    Foundation::{CloseHandle, ERROR_IO_PENDING, HANDLE, WAIT_OBJECT_0, WAIT_TIMEOUT}, NetworkManagement::IpHelper::{CancelIPChangeNotify, NotifyAddrChange}, Networking::NetworkListManager::{
+        INetworkListManager, NetworkListManager, NLM_CONNECTIVITY_IPV4_INTERNET, NLM_CONNECTIVITY_IPV6_INTERNET, NLM_ENUM_NETWORK_CONNECTED
+    }, System::{
+        Com::{CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED}, Threading::{CreateEventW, WaitForSingleObject}, IO::OVERLAPPED
+    }
+};
+
+/// 检查取消标志的最大间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// 地址没有变化时重新检查连接状态的间隔(如认证门户登录后可访问互联网)
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// 地址变化后等待连接状态更新的时间
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+
+/// 网络状态监视
+///
+/// 通过网络列表管理器(`NLM`)检测是否可以访问互联网，地址变化时立即检查，否则定期检查
+struct NetStatus {
+    state: HandlerState,
+    running: Option<Running>,
+    watch_id: u64,
+    /// 最近一次检测的状态
+    current: Option<NetState>,
+    /// 离线时暂停的上传队列
+    #[cfg(feature = "http")]
+    upload_queues: Vec<SharedObject>,
+    error: LastError
+}
+
+/// 执行中的监视任务
+struct Running {
+    cancelled: Arc<AtomicBool>,
+    cancel_hdl: CancelHandle
+}
+
+/// 网络状态
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct NetState {
+    /// 是否可以访问互联网
+    online: bool,
+    /// 当前网络的名称
+    interface: String
+}
+
+#[nonvisualobject(name = "nx_netstatus")]
+impl NetStatus {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        NetStatus {
+            state: HandlerState::new(session),
+            running: None,
+            watch_id: 0,
+            current: None,
+            #[cfg(feature = "http")]
+            upload_queues: Vec::new(),
+            error: LastError::default()
+        }
+    }
+
+    /// 开始监视
+    ///
+    /// # Description
+    ///
+    /// 先检测一次当前状态(不触发事件)，之后状态变化时触发`OnNetworkChanged`，正在监视时先停止再重新开始
+    #[method(name = "Start")]
+    fn start(&mut self) -> RetCode {
+        self.stop();
+        self.error.clear();
+        let initial = query();
+        self.update(initial.clone());
+        self.watch_id += 1;
+        let watch_id = self.watch_id;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let invoker = self.invoker();
+        let cancel_hdl = self.spawn(
+            {
+                let cancelled = cancelled.clone();
+                async move {
+                    let task = tokio::task::spawn_blocking(move || {
+                        let mut notify = |state: NetState| {
+                            if cancelled.load(Ordering::Relaxed) {
+                                return false;
+                            }
+                            let rv = invoker
+                                .invoke_blocking(state, move |this, state| this.dispatch(watch_id, state))
+                                .join();
+                            matches!(rv, Ok(true) | Err(InvokeError::Dropped))
+                        };
+                        run(initial, &cancelled, &mut notify)
+                    });
+                    match task.await {
+                        Ok(rv) => rv,
+                        Err(e) => Err(io::Error::new(io::ErrorKind::Other, e))
+                    }
+                }
+            },
+            move |this, rv| {
+                if this.watch_id == watch_id {
+                    this.running = None;
+                    if let Err(e) = rv {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_error(error);
+                    }
+                }
+            }
+        );
+        self.running = Some(Running {
+            cancelled,
+            cancel_hdl
+        });
+        RetCode::OK
+    }
+
+    /// 停止监视
+    #[method(name = "Stop")]
+    fn stop(&mut self) -> RetCode {
+        if let Some(running) = self.running.take() {
+            running.cancelled.store(true, Ordering::Relaxed);
+            running.cancel_hdl.cancel();
+        }
+        RetCode::OK
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.running.is_some() }
+
+    /// 是否可以访问互联网
+    ///
+    /// 监视中返回最近一次检测的状态，否则立即检测
+    #[method(name = "IsOnline")]
+    fn is_online(&self) -> bool { self.snapshot().online }
+
+    /// 当前网络的名称
+    ///
+    /// 优先返回可以访问互联网的网络，没有连接任何网络时返回空字符串
+    #[method(name = "GetInterface")]
+    fn get_interface(&self) -> String { self.snapshot().interface }
+
+    /// 关联上传队列
+    ///
+    /// # Description
+    ///
+    /// 离线时暂停队列，恢复在线时继续由离线暂停的上传，调用`Pause`/`Resume`后以手动设置为准，需要先调用`Start`
+    #[cfg(feature = "http")]
+    #[method(name = "AttachUploadQueue")]
+    fn attach_upload_queue(&mut self, queue: &mut crate::pbx::http::UploadQueue) -> RetCode {
+        if let Some(current) = self.current.as_ref() {
+            queue.set_offline(!current.online);
+        }
+        self.upload_queues.push(queue.get_object().share());
+        RetCode::OK
+    }
+
+    /// 当前状态
+    fn snapshot(&self) -> NetState {
+        match (self.running.as_ref(), self.current.as_ref()) {
+            (Some(_), Some(current)) => current.clone(),
+            _ => query()
+        }
+    }
+
+    /// 记录状态并通知关联的对象
+    fn update(&mut self, state: NetState) {
+        #[cfg(feature = "http")]
+        self.upload_queues.retain(|queue| {
+            match queue.get_native_mut::<crate::pbx::http::UploadQueue>() {
+                Ok(mut queue) => {
+                    queue.set_offline(!state.online);
+                    true
+                },
+                Err(_) => false
+            }
+        });
+        self.current = Some(state);
+    }
+
+    /// 分发通知
+    ///
+    /// # Returns
+    ///
+    /// 是否继续监视
+    fn dispatch(&mut self, watch_id: u64, state: NetState) -> bool {
+        if self.watch_id != watch_id || self.running.is_none() {
+            return false;
+        }
+        self.update(state.clone());
+        self.on_network_changed(state.online, state.interface);
+        //事件中可能调用了`Stop`或`Start`
+        self.watch_id == watch_id && self.running.is_some()
+    }
+
+    /// 网络状态变化
+    ///
+    /// # Parameters
+    ///
+    /// - `online` 是否可以访问互联网
+    /// - `interface` 当前网络的名称，没有连接任何网络时为空字符串
+    #[event(name = "OnNetworkChanged")]
+    fn on_network_changed(&mut self, online: bool, interface: String) {}
+
+    /// 监视出错并停止
+    #[event(name = "OnError")]
+    fn on_error(&mut self, error: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Handler for NetStatus {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+/// 检测网络状态
+///
+/// 检测失败时视为离线
+fn query() -> NetState {
+    unsafe {
+        //线程已按其它模式初始化时直接使用
+        let com_init = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+        let rv = (|| -> windows::core::Result<NetState> {
+            let nlm: INetworkListManager = CoCreateInstance(&NetworkListManager, None, CLSCTX_ALL)?;
+            let networks = nlm.GetNetworks(NLM_ENUM_NETWORK_CONNECTED)?;
+            let mut state = NetState::default();
+            loop {
+                let mut item = [None];
+                let mut fetched = 0u32;
+                if networks.Next(&mut item, Some(&mut fetched)).is_err() || fetched == 0 {
+                    break;
+                }
+                let Some(network) = item[0].take() else {
+                    break;
+                };
+                let connectivity = network.GetConnectivity()?;
+                let online = connectivity.0 &
+                    (NLM_CONNECTIVITY_IPV4_INTERNET.0 | NLM_CONNECTIVITY_IPV6_INTERNET.0) !=
+                    0;
+                //优先使用可以访问互联网的网络
+                if online || state.interface.is_empty() {
+                    state = NetState {
+                        online,
+                        interface: network.GetName()?.to_string()
+                    };
+                }
+                if online {
+                    break;
+                }
+            }
+            Ok(state)
+        })();
+        if com_init {
+            CoUninitialize();
+        }
+        rv.unwrap_or_default()
+    }
+}
+
+/// 监视循环
+fn run(
+    mut last: NetState,
+    cancelled: &AtomicBool,
+    notify: &mut dyn FnMut(NetState) -> bool
+) -> io::Result<()> {
+    let mut change = AddrChange::new()?;
+    let mut next_check = Instant::now() + CHECK_INTERVAL;
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if !change.pending {
+            change.arm()?;
+        }
+        if change.wait(POLL_INTERVAL)? {
+            next_check = next_check.min(Instant::now() + SETTLE_DELAY);
+        }
+        let now = Instant::now();
+        if now < next_check {
+            continue;
+        }
+        next_check = now + CHECK_INTERVAL;
+        let state = query();
+        if state != last {
+            last = state.clone();
+            if !notify(state) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// 地址变化通知
+///
+/// 异步通知进行中时`OVERLAPPED`不能移动，因此总是在堆上分配
+struct AddrChange {
+    overlapped: OVERLAPPED,
+    pending: bool
+}
+
+impl AddrChange {
+    fn new() -> io::Result<Box<AddrChange>> {
+        let event =
+            unsafe { CreateEventW(None, true, false, None) }.map_err(|_| io::Error::last_os_error())?;
+        let mut change = Box::new(AddrChange {
+            overlapped: OVERLAPPED::default(),
+            pending: false
+        });
+        change.overlapped.hEvent = event;
+        Ok(change)
+    }
+
+    /// 提交异步通知
+    fn arm(&mut self) -> io::Result<()> {
+        let mut handle = HANDLE::default();
+        let rc = unsafe { NotifyAddrChange(&mut handle, &self.overlapped) };
+        if rc != ERROR_IO_PENDING.0 {
+            return Err(io::Error::from_raw_os_error(rc as i32));
+        }
+        self.pending = true;
+        Ok(())
+    }
+
+    /// 等待地址变化
+    ///
+    /// # Returns
+    ///
+    /// 超时返回`false`
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        let rc = unsafe { WaitForSingleObject(self.overlapped.hEvent, timeout.as_millis() as u32) };
+        match rc {
+            WAIT_OBJECT_0 => {
+                self.pending = false;
+                Ok(true)
+            },
+            WAIT_TIMEOUT => Ok(false),
+            _ => Err(io::Error::last_os_error())
+        }
+    }
+}
+
+impl Drop for AddrChange {
+    fn drop(&mut self) {
+        unsafe {
+            if self.pending {
+                CancelIPChangeNotify(&self.overlapped);
+            }
+            CloseHandle(self.overlapped.hEvent);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use regex::Regex;
+use serde_json::{json, Value};
+
+/// 查找匹配的属性
+///
+/// # Description
+///
+/// 遍历所有叶子节点，使用`路径=值`格式的文本进行匹配，对象数组中的元素路径优先使用`name`字段
+///
+/// # Returns
+///
+/// 匹配列表，每一项为`{"path": "...", "value": ...}`
+pub fn find(root: &Value, re: &Regex) -> Vec<Value> {
+    let mut found = Vec::new();
+    walk("", root, &mut |path, val| {
+        if re.is_match(&format!("{path}={}", text_of(val))) {
+            found.push(json!({
+                "path": path,
+                "value": val
+            }));
+        }
+    });
+    found
+}
+
+/// 替换匹配的属性值
+///
+/// # Parameters
+///
+/// - `selector` 匹配属性路径
+/// - `pattern` 匹配属性值
+/// - `replacement` 替换文本，支持`$1`/`${name}`引用捕获组
+///
+/// # Returns
+///
+/// 修改列表，每一项为`{"path": "...", "old": ..., "new": ...}`
+pub fn replace(root: &mut Value, selector: &Regex, pattern: &Regex, replacement: &str) -> Vec<Value> {
+    let mut changes = Vec::new();
+    walk_mut("", root, &mut |path, val| {
+        if !selector.is_match(path) {
+            return;
+        }
+        let old_text = text_of(val);
+        if !pattern.is_match(&old_text) {
+            return;
+        }
+        let new_text = pattern.replace_all(&old_text, replacement);
+        if new_text == old_text {
+            return;
+        }
+        //保持原数据类型
+        let new_val = match val {
+            Value::Number(_) => {
+                match new_text.parse::<serde_json::Number>() {
+                    Ok(num) => Value::Number(num),
+                    Err(_) => Value::String(new_text.into_owned())
+                }
+            },
+            Value::Bool(_) if new_text == "true" || new_text == "false" => Value::Bool(new_text == "true"),
+            _ => Value::String(new_text.into_owned())
+        };
+        changes.push(json!({
+            "path": path,
+            "old": val,
+            "new": new_val
+        }));
+        *val = new_val;
+    });
+    changes
+}
+
+fn text_of(val: &Value) -> String {
+    match val {
+        Value::String(val) => val.clone(),
+        val => val.to_string()
+    }
+}
+
+/// 数组元素的路径名
+fn element_name(idx: usize, val: &Value) -> String {
+    match val.get("name").and_then(Value::as_str) {
+        Some(name) => name.to_owned(),
+        None => (idx + 1).to_string()
+    }
+}
+
+/// 遍历所有非空的叶子节点
+pub fn walk(path: &str, val: &Value, f: &mut impl FnMut(&str, &Value)) {
+    match val {
+        Value::Object(map) => {
+            for (key, val) in map {
+                walk(&format!("{path}/{key}"), val, f);
+            }
+        },
+        Value::Array(items) => {
+            for (idx, val) in items.iter().enumerate() {
+                walk(&format!("{path}/{}", element_name(idx, val)), val, f);
+            }
+        },
+        Value::Null => {},
+        val => f(path, val)
+    }
+}
+
+fn walk_mut(path: &str, val: &mut Value, f: &mut impl FnMut(&str, &mut Value)) {
+    match val {
+        Value::Object(map) => {
+            for (key, val) in map.iter_mut() {
+                walk_mut(&format!("{path}/{key}"), val, f);
+            }
+        },
+        Value::Array(items) => {
+            for (idx, val) in items.iter_mut().enumerate() {
+                let path = format!("{path}/{}", element_name(idx, val));
+                walk_mut(&path, val, f);
+            }
+        },
+        Value::Null => {},
+        val => f(path, val)
+    }
+}
@@ -0,0 +1,503 @@
+use crate::{
+    base::{conv, locale::tr}, prelude::*
+};
+use csv::{QuoteStyle, ReaderBuilder, StringRecord, Trim, WriterBuilder};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashSet, fs::{self, File}, io::{self, BufRead, BufReader, Read, Write}, mem
+};
+
+/// 默认每批次的行数
+const DEFAULT_BATCH_SIZE: usize = 1000;
+/// 编码检测的读取缓冲区大小
+const DETECT_BUF_SIZE: usize = 64 * 1024;
+
+/// 读写选项
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    delimiter: u8,
+    quote: u8,
+    has_headers: bool,
+    trim: bool,
+    quote_all: bool,
+    /// 文件编码，`ENCODING_UNKNOWN`表示读取时自动检测、写入时使用`UTF-8`
+    encoding: pblong
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            delimiter: b',',
+            quote: b'"',
+            has_headers: true,
+            trim: false,
+            quote_all: false,
+            encoding: conv::ENCODING_UNKNOWN
+        }
+    }
+}
+
+struct Csv {
+    state: HandlerState,
+    opts: Options,
+    batch_size: usize,
+    job: JobSlot,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_csv")]
+impl Csv {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Csv {
+            state: HandlerState::new(session),
+            opts: Options::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            job: JobSlot::default(),
+            error: LastError::default()
+        }
+    }
+
+    /// 设置分隔符
+    ///
+    /// # Parameters
+    ///
+    /// - `delimiter` 单个ASCII字符，默认`,`，`\t`或`tab`表示制表符(`TSV`)
+    #[method(name = "SetDelimiter")]
+    fn set_delimiter(&mut self, delimiter: String) -> RetCode {
+        let delimiter = match delimiter.as_str() {
+            "\\t" => b'\t',
+            delimiter if delimiter.eq_ignore_ascii_case("tab") => b'\t',
+            delimiter => {
+                match ascii_char(delimiter) {
+                    Some(c) => c,
+                    None => {
+                        return self
+                            .error
+                            .set(RetCode::E_INVALID_ARGUMENT, format!("invalid delimiter: {delimiter}"));
+                    }
+                }
+            },
+        };
+        self.opts.delimiter = delimiter;
+        RetCode::OK
+    }
+
+    /// 设置引号字符，默认`"`
+    #[method(name = "SetQuote")]
+    fn set_quote(&mut self, quote: String) -> RetCode {
+        match ascii_char(&quote) {
+            Some(c) => {
+                self.opts.quote = c;
+                RetCode::OK
+            },
+            None => self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid quote: {quote}"))
+        }
+    }
+
+    /// 设置首行是否为标题行，默认`true`
+    ///
+    /// # Description
+    ///
+    /// 有标题行时每行解析为对象，否则解析为数组
+    #[method(name = "SetHasHeaders")]
+    fn set_has_headers(&mut self, has_headers: bool) -> RetCode {
+        self.opts.has_headers = has_headers;
+        RetCode::OK
+    }
+
+    /// 设置解析时是否去除字段两端的空白，默认`false`
+    #[method(name = "SetTrim")]
+    fn set_trim(&mut self, trim: bool) -> RetCode {
+        self.opts.trim = trim;
+        RetCode::OK
+    }
+
+    /// 设置生成时是否为所有字段添加引号，默认`false`(仅在需要时添加)
+    #[method(name = "SetQuoteAll")]
+    fn set_quote_all(&mut self, quote_all: bool) -> RetCode {
+        self.opts.quote_all = quote_all;
+        RetCode::OK
+    }
+
+    /// 设置文件编码
+    ///
+    /// 默认`ENCODING_UNKNOWN`，读取时自动检测，写入时使用`UTF-8`
+    #[method(name = "SetEncoding")]
+    fn set_encoding(&mut self, encoding: pblong) -> RetCode {
+        self.opts.encoding = encoding;
+        RetCode::OK
+    }
+
+    /// 设置`ParseFileAsync`每批次通知的行数，默认1000
+    #[method(name = "SetBatchSize")]
+    fn set_batch_size(&mut self, size: pbulong) -> RetCode {
+        if size == 0 {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "batch size must be greater than 0");
+        }
+        self.batch_size = size as usize;
+        RetCode::OK
+    }
+
+    /// 解析`CSV`字符串
+    ///
+    /// # Returns
+    ///
+    /// `JSON`数组，格式无效时返回空字符串，通过`GetLastErrorText`获取错误信息
+    #[method(name = "Parse")]
+    fn parse(&mut self, data: String) -> String {
+        let mut rows = Vec::new();
+        match read_records(data.as_bytes(), &self.opts, usize::MAX, &mut |batch: Vec<Value>| {
+            rows.extend(batch);
+            true
+        }) {
+            Ok(_) => {
+                self.error.clear();
+                Value::Array(rows).to_string()
+            },
+            Err(e) => {
+                self.error.set_io(e);
+                "".to_owned()
+            }
+        }
+    }
+
+    /// 解析`CSV`文件
+    ///
+    /// # Returns
+    ///
+    /// `JSON`数组，失败时返回空字符串，通过`GetLastErrorText`获取错误信息
+    ///
+    /// # Description
+    ///
+    /// 同步执行并一次性返回所有行，大文件使用`ParseFileAsync`
+    #[method(name = "ParseFile")]
+    fn parse_file(&mut self, path: String) -> String {
+        let mut rows = Vec::new();
+        match read_file(&path, &self.opts, usize::MAX, &mut |batch: Vec<Value>| {
+            rows.extend(batch);
+            true
+        }) {
+            Ok(_) => {
+                self.error.clear();
+                Value::Array(rows).to_string()
+            },
+            Err(e) => {
+                self.error.set_io(e);
+                "".to_owned()
+            }
+        }
+    }
+
+    /// 异步解析`CSV`文件
+    ///
+    /// # Description
+    ///
+    /// 在后台线程流式读取，每`SetBatchSize`行触发一次`OnRows`，完成后触发`OnComplete`
+    #[method(name = "ParseFileAsync")]
+    fn parse_file_async(&mut self, path: String) -> RetCode {
+        let opts = self.opts;
+        let batch_size = self.batch_size;
+        spawn_file_job(
+            self,
+            |this| &mut this.job,
+            move |ctx| {
+                let mut count = 0u64;
+                read_file(&path, &opts, batch_size, &mut |batch: Vec<Value>| {
+                    let first_row = count + 1;
+                    count += batch.len() as u64;
+                    ctx.invoke((Value::Array(batch).to_string(), first_row), |this, (rows, first_row)| {
+                        this.on_rows(rows, first_row as pblong)
+                    })
+                })
+            },
+            |this, rv| {
+                match rv {
+                    Ok(count) => this.on_complete("".to_owned(), count as pblong),
+                    Err(e) => {
+                        let error = e.to_string();
+                        this.error.set_io(e);
+                        this.on_complete(error, 0);
+                    }
+                }
+            }
+        )
+    }
+
+    /// 生成`CSV`字符串
+    ///
+    /// # Parameters
+    ///
+    /// - `rows` `JSON`数组，元素为对象或数组
+    ///
+    /// # Description
+    ///
+    /// 有标题行时以所有对象键名的并集作为标题行
+    #[method(name = "Generate")]
+    fn generate(&self, rows: String) -> String {
+        match serde_json::from_str(&rows) {
+            Ok(Value::Array(rows)) => write_rows(&rows, &self.opts).unwrap_or_default(),
+            _ => "".to_owned()
+        }
+    }
+
+    /// 生成`CSV`文件
+    ///
+    /// # Parameters
+    ///
+    /// - `rows` `JSON`数组，元素为对象或数组
+    /// - `bom` 是否写入`BOM`(`UTF-8`/`UTF-16`)，默认`false`
+    #[method(name = "WriteFile", overload = 1)]
+    fn write_file(&mut self, path: String, rows: String, bom: Option<bool>) -> RetCode {
+        let rows = match serde_json::from_str(&rows) {
+            Ok(Value::Array(rows)) => rows,
+            Ok(_) => return self.error.set(RetCode::E_INVALID_DATA, "rows must be a json array"),
+            Err(e) => return self.error.set(RetCode::E_INVALID_DATA, e)
+        };
+        let rv = write_rows(&rows, &self.opts)
+            .and_then(|text| save_file(&path, &text, self.opts.encoding, bom.unwrap_or_default()));
+        self.error.set_io_result(rv)
+    }
+
+    /// 转换`DataWindow`导出的文本(`SaveAs(Text!)`或`Describe("DataWindow.Data")`)
+    ///
+    /// # Description
+    ///
+    /// 文本以制表符分隔字段，按当前分隔符和引号设置重新生成，不处理标题行
+    #[method(name = "FromDWText")]
+    fn from_dw_text(&self, text: String) -> String { write_dw_text(&text, &self.opts).unwrap_or_default() }
+
+    /// 将`DataWindow`导出的文本转换后写入文件
+    ///
+    /// # Parameters
+    ///
+    /// - `bom` 是否写入`BOM`(`UTF-8`/`UTF-16`)，默认`false`
+    #[method(name = "SaveDWText", overload = 1)]
+    fn save_dw_text(&mut self, path: String, text: String, bom: Option<bool>) -> RetCode {
+        let rv = write_dw_text(&text, &self.opts)
+            .and_then(|text| save_file(&path, &text, self.opts.encoding, bom.unwrap_or_default()));
+        self.error.set_io_result(rv)
+    }
+
+    /// 取消异步任务
+    #[method(name = "Cancel")]
+    fn cancel(&mut self) -> RetCode {
+        if self.job.cancel() {
+            RetCode::OK
+        } else {
+            RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.job.is_running() }
+
+    /// 异步解析的一批数据
+    ///
+    /// `rows`为`JSON`数组，`first_row`为该批次首行的行号(从1开始，不含标题行)，返回`PREVENT`取消任务
+    #[event(name = "OnRows")]
+    fn on_rows(&mut self, rows: String, first_row: pblong) -> RetCode {}
+
+    /// 异步解析完成
+    ///
+    /// 成功时`error`为空字符串，`rows`为总行数
+    #[event(name = "OnComplete")]
+    fn on_complete(&mut self, error: String, rows: pblong) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Handler for Csv {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+fn ascii_char(s: &str) -> Option<u8> {
+    match s.as_bytes() {
+        [c] if c.is_ascii() => Some(*c),
+        _ => None
+    }
+}
+
+/// 读取所有行，每`batch_size`行回调一次
+///
+/// # Returns
+///
+/// 总行数，回调返回`false`时中止并返回`Interrupted`错误
+fn read_records(
+    reader: impl Read,
+    opts: &Options,
+    batch_size: usize,
+    on_batch: &mut dyn FnMut(Vec<Value>) -> bool
+) -> io::Result<u64> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(opts.delimiter)
+        .quote(opts.quote)
+        .has_headers(opts.has_headers)
+        .trim(if opts.trim {
+            Trim::All
+        } else {
+            Trim::None
+        })
+        .flexible(true)
+        .from_reader(reader);
+    let headers = if opts.has_headers {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+    let mut record = StringRecord::new();
+    let mut batch = Vec::new();
+    let mut count = 0;
+    let cancelled = || io::Error::new(io::ErrorKind::Interrupted, tr("cancelled", "已取消"));
+    while reader.read_record(&mut record)? {
+        batch.push(record_to_json(headers.as_ref(), &record));
+        count += 1;
+        if batch.len() >= batch_size && !on_batch(mem::take(&mut batch)) {
+            return Err(cancelled());
+        }
+    }
+    if !batch.is_empty() && !on_batch(batch) {
+        return Err(cancelled());
+    }
+    Ok(count)
+}
+
+/// 读取文件，自动检测编码并去除`BOM`
+fn read_file(
+    path: &str,
+    opts: &Options,
+    batch_size: usize,
+    on_batch: &mut dyn FnMut(Vec<Value>) -> bool
+) -> io::Result<u64> {
+    let mut reader = BufReader::with_capacity(DETECT_BUF_SIZE, File::open(path)?);
+    let head = reader.fill_buf()?;
+    let encoding = match opts.encoding {
+        conv::ENCODING_UNKNOWN => conv::detect_encoding(head),
+        encoding => encoding
+    };
+    if let Some((bom_encoding, len)) = conv::detect_bom(head) {
+        if bom_encoding == encoding {
+            reader.consume(len);
+        }
+    }
+    read_records(conv::decode_reader(reader, encoding)?, opts, batch_size, on_batch)
+}
+
+/// 有标题行时转换为对象，否则转换为数组
+fn record_to_json(headers: Option<&StringRecord>, record: &StringRecord) -> Value {
+    match headers {
+        Some(headers) => {
+            let mut obj = Map::new();
+            for (idx, field) in record.iter().enumerate() {
+                let key = headers.get(idx).map(str::to_owned).unwrap_or_else(|| format!("column{}", idx + 1));
+                obj.insert(key, Value::String(field.to_owned()));
+            }
+            Value::Object(obj)
+        },
+        None => Value::Array(record.iter().map(|field| Value::String(field.to_owned())).collect())
+    }
+}
+
+fn writer_builder(opts: &Options) -> WriterBuilder {
+    let mut builder = WriterBuilder::new();
+    builder
+        .delimiter(opts.delimiter)
+        .quote(opts.quote)
+        .quote_style(if opts.quote_all {
+            QuoteStyle::Always
+        } else {
+            QuoteStyle::Necessary
+        })
+        .flexible(true);
+    builder
+}
+
+/// 生成`CSV`字符串
+fn write_rows(rows: &[Value], opts: &Options) -> io::Result<String> {
+    let mut writer = writer_builder(opts).from_writer(Vec::new());
+    //所有对象键名的并集
+    let mut headers = Vec::new();
+    if opts.has_headers {
+        let mut seen = HashSet::new();
+        for row in rows {
+            if let Value::Object(obj) = row {
+                for key in obj.keys() {
+                    if seen.insert(key.as_str()) {
+                        headers.push(key.as_str());
+                    }
+                }
+            }
+        }
+        if !headers.is_empty() {
+            writer.write_record(&headers)?;
+        }
+    }
+    for row in rows {
+        match row {
+            Value::Object(obj) => {
+                writer.write_record(headers.iter().map(|key| field_text(obj.get(*key))))?;
+            },
+            Value::Array(arr) => writer.write_record(arr.iter().map(|val| field_text(Some(val))))?,
+            val => writer.write_record([field_text(Some(val))])?
+        }
+    }
+    let data = writer.into_inner().map_err(|e| e.into_error())?;
+    String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// 转换`DataWindow`导出的制表符分隔文本
+fn write_dw_text(text: &str, opts: &Options) -> io::Result<String> {
+    let mut writer = writer_builder(opts).from_writer(Vec::new());
+    for line in text.lines().filter(|line| !line.is_empty()) {
+        writer.write_record(line.split('\t'))?;
+    }
+    let data = writer.into_inner().map_err(|e| e.into_error())?;
+    String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn field_text(val: Option<&Value>) -> String {
+    match val {
+        Some(Value::String(val)) => val.clone(),
+        Some(Value::Null) | None => "".to_owned(),
+        Some(val) => val.to_string()
+    }
+}
+
+/// 按指定编码写入文件
+fn save_file(path: &str, text: &str, encoding: pblong, bom: bool) -> io::Result<()> {
+    let encoding = match encoding {
+        conv::ENCODING_UNKNOWN => conv::ENCODING_UTF8,
+        encoding => encoding
+    };
+    let mut file = File::create(path)?;
+    let mut write = || -> io::Result<()> {
+        if bom {
+            let bom: &[u8] = match encoding {
+                conv::ENCODING_UTF8 => b"\xEF\xBB\xBF",
+                conv::ENCODING_UTF16LE => b"\xFF\xFE",
+                conv::ENCODING_UTF16BE => b"\xFE\xFF",
+                conv::ENCODING_UTF32LE => b"\xFF\xFE\x00\x00",
+                conv::ENCODING_UTF32BE => b"\x00\x00\xFE\xFF",
+                _ => b""
+            };
+            file.write_all(bom)?;
+        }
+        file.write_all(&conv::encode(text, encoding))?;
+        file.flush()
+    };
+    let rv = write();
+    if rv.is_err() {
+        drop(file);
+        let _ = fs::remove_file(path);
+    }
+    rv
+}
@@ -0,0 +1,99 @@
+use serde_json::{json, Map, Value};
+
+/// 比较两个`JSON`值的差异
+///
+/// # Description
+///
+/// 对象数组中的元素优先按`name`字段匹配，避免插入或调整顺序后产生连锁差异
+///
+/// # Returns
+///
+/// 差异列表，每一项为`{"op": "added|removed|changed", "path": "...", "old": ..., "new": ...}`
+pub fn diff(old: &Value, new: &Value) -> Vec<Value> {
+    let mut changes = Vec::new();
+    diff_value("", old, new, &mut changes);
+    changes
+}
+
+fn diff_value(path: &str, old: &Value, new: &Value, changes: &mut Vec<Value>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => diff_object(path, old, new, changes),
+        (Value::Array(old), Value::Array(new)) => diff_array(path, old, new, changes),
+        (old, new) if old != new => {
+            changes.push(json!({
+                "op": "changed",
+                "path": path,
+                "old": old,
+                "new": new
+            }))
+        },
+        _ => {}
+    }
+}
+
+fn diff_object(path: &str, old: &Map<String, Value>, new: &Map<String, Value>, changes: &mut Vec<Value>) {
+    for (key, old_val) in old {
+        let path = format!("{path}/{key}");
+        match new.get(key) {
+            Some(new_val) => diff_value(&path, old_val, new_val, changes),
+            None => removed(&path, old_val, changes)
+        }
+    }
+    for (key, new_val) in new {
+        if !old.contains_key(key) {
+            added(&format!("{path}/{key}"), new_val, changes);
+        }
+    }
+}
+
+fn diff_array(path: &str, old: &[Value], new: &[Value], changes: &mut Vec<Value>) {
+    let name_of = |val: &Value| val.get("name").and_then(Value::as_str).map(ToOwned::to_owned);
+    //所有元素都有名称时按名称匹配
+    if !old.is_empty() && !new.is_empty() && old.iter().chain(new.iter()).all(|val| name_of(val).is_some()) {
+        for old_val in old {
+            let name = name_of(old_val).unwrap();
+            let path = format!("{path}/{name}");
+            match new.iter().find(|new_val| name_of(new_val).as_deref() == Some(&name)) {
+                Some(new_val) => diff_value(&path, old_val, new_val, changes),
+                None => removed(&path, old_val, changes)
+            }
+        }
+        for new_val in new {
+            let name = name_of(new_val).unwrap();
+            if !old.iter().any(|old_val| name_of(old_val).as_deref() == Some(&name)) {
+                added(&format!("{path}/{name}"), new_val, changes);
+            }
+        }
+    } else {
+        for (idx, old_val) in old.iter().enumerate() {
+            let path = format!("{path}/{}", idx + 1);
+            match new.get(idx) {
+                Some(new_val) => diff_value(&path, old_val, new_val, changes),
+                None => removed(&path, old_val, changes)
+            }
+        }
+        for (idx, new_val) in new.iter().enumerate().skip(old.len()) {
+            added(&format!("{path}/{}", idx + 1), new_val, changes);
+        }
+    }
+}
+
+fn added(path: &str, new: &Value, changes: &mut Vec<Value>) {
+    if !new.is_null() {
+        changes.push(json!({
+            "op": "added",
+            "path": path,
+            "new": new
+        }));
+    }
+}
+
+fn removed(path: &str, old: &Value, changes: &mut Vec<Value>) {
+    if !old.is_null() {
+        changes.push(json!({
+            "op": "removed",
+            "path": path,
+            "old": old
+        }));
+    }
+}
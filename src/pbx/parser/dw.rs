@@ -1,11 +1,17 @@
-use crate::prelude::*;
+use super::{attr, diff, expr, gen, lint, merge, srd};
+use crate::{
+    base::pfw, pbx::json::{self, Json}, prelude::*
+};
 use dwparser::DWSyntax;
 use pbni::pbx::*;
-use std::mem::transmute;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::{cell::RefCell, collections::HashMap, mem::transmute, rc::Rc};
 
 #[derive(Default)]
 struct DWParser {
-    inner: Option<DWParserInner>
+    inner: Option<DWParserInner>,
+    error: LastError
 }
 
 #[nonvisualobject(name = "nx_dwparser")]
@@ -19,14 +25,60 @@ impl DWParser {
             //SAFETY
             transmute(syn.as_str())
         };
-        let ast = DWSyntax::parse(syn_ref)?;
-        self.inner = Some(DWParserInner {
-            syn,
-            ast
-        });
+        let ast = match DWSyntax::parse(syn_ref) {
+            Ok(ast) => ast,
+            Err(_) => return self.error.set(RetCode::E_INVALID_DATA, "invalid datawindow syntax")
+        };
+        self.inner = Some(DWParserInner::new(syn, ast));
         RetCode::OK
     }
 
+    /// 从`.srd`文件解析
+    ///
+    /// # Description
+    ///
+    /// 自动检测`ANSI`/`UTF-8`/`UTF-16`编码，支持`HexASCII`格式与导出头
+    #[method(name = "ParseFile")]
+    fn parse_file(&mut self, path: String) -> RetCode {
+        let syn = match srd::read_file(&path) {
+            Ok(syn) => syn,
+            Err(e) => return self.error.set_io(e)
+        };
+        self.parse(syn)
+    }
+
+    /// 根据列定义生成DW语法
+    ///
+    /// # Parameters
+    ///
+    /// - `style` 显示风格：`grid`或`freeform`
+    /// - `columns` `JSON`数组，每一项为`{"name": "id", "type": "long", "width": 300, "label": "编号"}`
+    ///   - `type` 支持DW列类型(如`char(20)`、`decimal(2)`、`datetime`)和简写`string`/`int`/`decimal`，默认`string`
+    ///   - `length` 简写`string`的长度，默认`255`
+    ///   - `scale` 简写`decimal`的小数位数，默认`2`
+    ///
+    /// # Returns
+    ///
+    /// 生成的DW语法并同时解析，失败时返回空字符串
+    #[method(name = "Generate")]
+    fn generate(&mut self, style: String, columns: String) -> String {
+        let columns: serde_json::Value = match serde_json::from_str(&columns) {
+            Ok(columns) => columns,
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+                return "".to_owned();
+            }
+        };
+        match gen::generate(&style, &columns) {
+            Ok(syn) if self.parse(syn.clone()) == RetCode::OK => syn,
+            Ok(_) => "".to_owned(),
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+                "".to_owned()
+            }
+        }
+    }
+
     /// 获取指定语法项的参数值
     ///
     /// 兼容`DataWindow::Describe`参数和返回值
@@ -45,12 +97,462 @@ impl DWParser {
     #[method(name = "Modify")]
     fn modify(&mut self, modifier: String) -> String {
         if let Some(inner) = &mut self.inner {
-            inner.ast.modify(&modifier)
+            inner.modify(&modifier)
         } else {
             "!".to_owned()
         }
     }
 
+    /// 列数量
+    #[method(name = "GetColumnCount")]
+    fn column_count(&self) -> pblong {
+        self.describe_value("DataWindow.Column.Count").parse().unwrap_or_default()
+    }
+
+    /// 列名
+    ///
+    /// # Parameters
+    ///
+    /// - `index` 列序号(从`1`开始)
+    #[method(name = "GetColumnName")]
+    fn column_name(&self, index: pblong) -> String { self.describe_value(&format!("#{index}.Name")) }
+
+    /// 列数据类型
+    ///
+    /// # Parameters
+    ///
+    /// - `index` 列序号(从`1`开始)
+    ///
+    /// # Returns
+    ///
+    /// 如`char(10)`、`decimal(2)`、`datetime`
+    #[method(name = "GetColumnType")]
+    fn column_type(&self, index: pblong) -> String { self.describe_value(&format!("#{index}.ColType")) }
+
+    /// 指定区域的对象数量
+    ///
+    /// # Parameters
+    ///
+    /// - `band` 区域名(如`header`、`detail`)，空字符串或`*`表示所有区域
+    #[method(name = "GetObjectCount")]
+    fn object_count(&self, band: String) -> pblong { self.objects(&band).len() as pblong }
+
+    /// 指定区域的对象名
+    ///
+    /// # Parameters
+    ///
+    /// - `band` 区域名(如`header`、`detail`)，空字符串或`*`表示所有区域
+    /// - `index` 对象序号(从`1`开始)
+    #[method(name = "GetObjectName")]
+    fn object_name(&self, band: String, index: pblong) -> String {
+        if index < 1 {
+            return "".to_owned();
+        }
+        self.objects(&band).get(index as usize - 1).cloned().unwrap_or_default()
+    }
+
+    /// 批量修改
+    ///
+    /// # Parameters
+    ///
+    /// - `patch` `JSON`数组，每一项为以下格式之一：
+    ///   - `{"op": "modify", "target": "t_1.text", "value": "abc"}` 字符串值自动加引号，数值原样使用
+    ///   - `{"op": "modify", "modifier": "t_1.text='abc' t_1.x=10"}` 原始`Modify`参数
+    ///   - `{"op": "create", "syntax": "text(name=t_2 ...)"}` 插入对象
+    ///   - `{"op": "destroy", "name": "t_1"}` 删除对象，`"column": true`时删除列
+    ///
+    /// # Returns
+    ///
+    /// `JSON`数组，按顺序对应每一项的结果：`{"ok": true}`或`{"ok": false, "error": "..."}`，
+    /// `patch`格式错误时返回空字符串
+    #[method(name = "ApplyPatch")]
+    fn apply_patch(&mut self, patch: String) -> String {
+        let entries: Vec<serde_json::Value> = match serde_json::from_str(&patch) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+                return "".to_owned();
+            }
+        };
+        let inner = match &mut self.inner {
+            Some(inner) => inner,
+            None => {
+                self.error.set(RetCode::E_INVALID_HANDLE, "syntax not parsed");
+                return "".to_owned();
+            }
+        };
+        let results: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                let rv = match patch_modifier(entry) {
+                    Ok(modifier) => inner.modify(&modifier),
+                    Err(e) => e
+                };
+                if rv.is_empty() {
+                    json!({ "ok": true })
+                } else {
+                    json!({ "ok": false, "error": rv })
+                }
+            })
+            .collect();
+        serde_json::Value::from(results).to_string()
+    }
+
+    /// 从另一个DW语法合并区域、对象和属性
+    ///
+    /// # Parameters
+    ///
+    /// - `other` 模板DW语法
+    /// - `parts` `JSON`格式的合并内容：
+    ///   - `bands` 区域名数组，复制区域的高度、颜色以及区域内的所有对象
+    ///   - `objects` 对象名数组
+    ///   - `attributes` 属性数组，如`["DataWindow.Color", "t_title.font.face"]`
+    ///
+    /// # Returns
+    ///
+    /// `JSON`格式的结果：
+    ///
+    /// ```json
+    /// {
+    ///     "objects": [{ "name": "t_1", "as": "t_1_2" }, { "name": "t_2", "error": "..." }],
+    ///     "attributes": [{ "name": "DataWindow.Color", "error": "..." }]
+    /// }
+    /// ```
+    ///
+    /// 名称冲突的对象自动重命名，未解析或参数格式错误时返回空字符串
+    #[method(name = "MergeFrom")]
+    fn merge_from(&mut self, other: String, parts: String) -> String {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => {
+                self.error.set(RetCode::E_INVALID_HANDLE, "syntax not parsed");
+                return "".to_owned();
+            }
+        };
+        let other = match DWSyntax::parse(&other) {
+            Ok(ast) => ast,
+            Err(_) => {
+                self.error.set(RetCode::E_INVALID_DATA, "invalid datawindow syntax");
+                return "".to_owned();
+            }
+        };
+        let parts: serde_json::Value = match serde_json::from_str(&parts) {
+            Ok(parts) => parts,
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+                return "".to_owned();
+            }
+        };
+        let list = |key: &str| -> Vec<String> {
+            parts
+                .get(key)
+                .and_then(serde_json::Value::as_array)
+                .map(|items| items.iter().filter_map(|item| item.as_str().map(ToOwned::to_owned)).collect())
+                .unwrap_or_default()
+        };
+        let bands = list("bands");
+        let mut names = list("objects");
+        let mut attributes = list("attributes");
+        for band in &bands {
+            attributes.push(format!("DataWindow.{band}.Height"));
+            attributes.push(format!("DataWindow.{band}.Color"));
+            for name in objects(&other, band) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+
+        //合并对象
+        let (mut root, other_root) = match (serde_json::to_value(&inner.ast), serde_json::to_value(&other)) {
+            (Ok(root), Ok(other_root)) => (root, other_root),
+            (Err(e), _) | (_, Err(e)) => {
+                self.error.set(RetCode::E_INTERNAL_ERROR, e);
+                return "".to_owned();
+            }
+        };
+        let merged = merge::merge_objects(&mut root, &other_root, &names);
+        if merged.iter().any(|item| item.get("as").is_some()) &&
+            self.from_json_ast(root.to_string()) != RetCode::OK
+        {
+            return "".to_owned();
+        }
+
+        //复制属性
+        let inner = self.inner.as_mut().unwrap();
+        let attributes: Vec<_> = attributes
+            .iter()
+            .map(|name| {
+                let value = describe_value(&other, name);
+                let rv = if value.is_empty() {
+                    "attribute not found".to_owned()
+                } else {
+                    inner.modify(&format!("{name}=\"{}\"", value.replace('~', "~~").replace('"', "~\"")))
+                };
+                if rv.is_empty() {
+                    json!({ "name": name })
+                } else {
+                    json!({ "name": name, "error": rv })
+                }
+            })
+            .collect();
+        json!({
+            "objects": merged,
+            "attributes": attributes
+        })
+        .to_string()
+    }
+
+    /// 比较与另一个DW语法的差异
+    ///
+    /// # Returns
+    ///
+    /// `JSON`格式的差异：
+    ///
+    /// ```json
+    /// {
+    ///     "columns": { "added": [], "removed": [] },
+    ///     "objects": { "added": [], "removed": [] },
+    ///     "changes": [{ "op": "added|removed|changed", "path": "...", "old": ..., "new": ... }]
+    /// }
+    /// ```
+    ///
+    /// 未解析或`other`语法错误时返回空字符串
+    #[method(name = "Diff")]
+    fn diff(&mut self, other: String) -> String {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => {
+                self.error.set(RetCode::E_INVALID_HANDLE, "syntax not parsed");
+                return "".to_owned();
+            }
+        };
+        let other = match DWSyntax::parse(&other) {
+            Ok(ast) => ast,
+            Err(_) => {
+                self.error.set(RetCode::E_INVALID_DATA, "invalid datawindow syntax");
+                return "".to_owned();
+            }
+        };
+        let (old_val, new_val) = match (serde_json::to_value(&inner.ast), serde_json::to_value(&other)) {
+            (Ok(old_val), Ok(new_val)) => (old_val, new_val),
+            (Err(e), _) | (_, Err(e)) => {
+                self.error.set(RetCode::E_INTERNAL_ERROR, e);
+                return "".to_owned();
+            }
+        };
+        json!({
+            "columns": diff_names(&columns(&inner.ast), &columns(&other)),
+            "objects": diff_names(&objects(&inner.ast, ""), &objects(&other, "")),
+            "changes": diff::diff(&old_val, &new_val)
+        })
+        .to_string()
+    }
+
+    /// 列的代码表
+    ///
+    /// # Parameters
+    ///
+    /// - `column` 列名
+    ///
+    /// # Returns
+    ///
+    /// `JSON`格式：
+    ///
+    /// ```json
+    /// {
+    ///     "style": "ddlb",
+    ///     "values": [{ "value": "M", "display": "Male" }],
+    ///     "dddw": { "name": "d_dddw", "display_column": "name", "data_column": "id" }
+    /// }
+    /// ```
+    ///
+    /// `dddw`仅在编辑风格为`dddw`时返回，`checkbox`的`values`为选中和未选中的值，列不存在时返回空字符串
+    #[method(name = "GetCodeTable")]
+    fn code_table(&self, column: String) -> String {
+        let style = self.describe_value(&format!("{column}.Edit.Style")).to_ascii_lowercase();
+        if style.is_empty() {
+            return "".to_owned();
+        }
+        let mut rv = json!({ "style": style });
+        match style.as_str() {
+            "dddw" => {
+                rv["values"] = json!([]);
+                rv["dddw"] = json!({
+                    "name": self.describe_value(&format!("{column}.DDDW.Name")),
+                    "display_column": self.describe_value(&format!("{column}.DDDW.DisplayColumn")),
+                    "data_column": self.describe_value(&format!("{column}.DDDW.DataColumn"))
+                });
+            },
+            "checkbox" => {
+                let text = self.describe_value(&format!("{column}.CheckBox.Text"));
+                rv["values"] = json!([
+                    { "value": self.describe_value(&format!("{column}.CheckBox.On")), "display": text },
+                    { "value": self.describe_value(&format!("{column}.CheckBox.Off")), "display": "" }
+                ]);
+            },
+            _ => rv["values"] = code_values(&self.describe_value(&format!("{column}.Values")))
+        }
+        rv.to_string()
+    }
+
+    /// 校验语法
+    ///
+    /// # Parameters
+    ///
+    /// - `rules` `JSON`格式的规则配置，如`{"tab_order": {"step": 10}, "update": false}`，
+    ///   未指定的规则默认启用，空字符串表示启用所有规则：
+    ///   - `tab_order` Tab顺序存在间隔或重复
+    ///   - `update` 未设置更新表、关键列或可更新列
+    ///   - `edit_style` 可编辑的列没有编辑风格
+    ///   - `expressions` 表达式引用了不存在的列或对象
+    ///
+    /// # Returns
+    ///
+    /// `JSON`数组：`[{"rule": "...", "target": "...", "message": "..."}]`，未解析或`rules`格式错误时返回空字符串
+    #[method(name = "Validate")]
+    fn validate(&mut self, rules: String) -> String {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => {
+                self.error.set(RetCode::E_INVALID_HANDLE, "syntax not parsed");
+                return "".to_owned();
+            }
+        };
+        let rules = if rules.trim().is_empty() {
+            json!({})
+        } else {
+            match serde_json::from_str(&rules) {
+                Ok(rules) => rules,
+                Err(e) => {
+                    self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+                    return "".to_owned();
+                }
+            }
+        };
+        serde_json::Value::from(lint::validate(&inner.ast, &rules)).to_string()
+    }
+
+    /// 使用正则表达式查找属性
+    ///
+    /// # Parameters
+    ///
+    /// - `pattern` 正则表达式，匹配`路径=值`格式的文本，如`/font/face=Arial`
+    ///
+    /// # Returns
+    ///
+    /// `JSON`数组：`[{"path": "...", "value": ...}]`，未解析或正则表达式错误时返回空字符串
+    #[method(name = "FindAttribute")]
+    fn find_attribute(&mut self, pattern: String) -> String {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => {
+                self.error.set(RetCode::E_INVALID_HANDLE, "syntax not parsed");
+                return "".to_owned();
+            }
+        };
+        let re = match Regex::new(&pattern) {
+            Ok(re) => re,
+            Err(e) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+                return "".to_owned();
+            }
+        };
+        let root = match serde_json::to_value(&inner.ast) {
+            Ok(root) => root,
+            Err(e) => {
+                self.error.set(RetCode::E_INTERNAL_ERROR, e);
+                return "".to_owned();
+            }
+        };
+        serde_json::Value::from(attr::find(&root, &re)).to_string()
+    }
+
+    /// 使用正则表达式替换属性值
+    ///
+    /// # Parameters
+    ///
+    /// - `selector` 匹配属性路径的正则表达式，如`/font/face$`
+    /// - `pattern` 匹配属性值的正则表达式
+    /// - `replacement` 替换文本，支持`$1`引用捕获组
+    ///
+    /// # Returns
+    ///
+    /// `JSON`数组：`[{"path": "...", "old": ..., "new": ...}]`，未解析、正则表达式错误或替换后语法无效时返回空字符串
+    #[method(name = "ReplaceAttribute")]
+    fn replace_attribute(&mut self, selector: String, pattern: String, replacement: String) -> String {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => {
+                self.error.set(RetCode::E_INVALID_HANDLE, "syntax not parsed");
+                return "".to_owned();
+            }
+        };
+        let (selector, pattern) = match (Regex::new(&selector), Regex::new(&pattern)) {
+            (Ok(selector), Ok(pattern)) => (selector, pattern),
+            (Err(e), _) | (_, Err(e)) => {
+                self.error.set(RetCode::E_INVALID_ARGUMENT, e);
+                return "".to_owned();
+            }
+        };
+        let mut root = match serde_json::to_value(&inner.ast) {
+            Ok(root) => root,
+            Err(e) => {
+                self.error.set(RetCode::E_INTERNAL_ERROR, e);
+                return "".to_owned();
+            }
+        };
+        let changes = attr::replace(&mut root, &selector, &pattern, &replacement);
+        //重新生成AST，失败时`from_json_ast`已记录错误信息
+        if !changes.is_empty() && self.from_json_ast(root.to_string()) != RetCode::OK {
+            return "".to_owned();
+        }
+        serde_json::Value::from(changes).to_string()
+    }
+
+    /// 计算DW表达式
+    ///
+    /// # Parameters
+    ///
+    /// - `expr` 表达式，如`if(status = 1, rgb(255,0,0), 0)`
+    /// - `row_values` `JSON`对象，表达式中引用的列名与值，日期时间使用`yyyy-mm-dd hh:mm:ss`格式的字符串
+    ///
+    /// # Returns
+    ///
+    /// `JSON`格式的结果：`{"type": "number|string|boolean|date|time|datetime|null", "value": ...}`，
+    /// 失败时返回`{"error": "..."}`
+    ///
+    /// # Description
+    ///
+    /// 不依赖已解析的语法，不支持聚合函数(如`sum`)和依赖行上下文的函数(如`getrow`)
+    #[method(name = "EvaluateExpression")]
+    fn evaluate_expression(&self, expr: String, row_values: String) -> String {
+        let row = if row_values.trim().is_empty() {
+            serde_json::Map::new()
+        } else {
+            match serde_json::from_str(&row_values) {
+                Ok(row) => row,
+                Err(e) => return json!({ "error": format!("invalid row values: {e}") }).to_string()
+            }
+        };
+        match expr::evaluate(&expr, &row) {
+            Ok(val) => json!({ "type": val.type_name(), "value": val.to_json() }).to_string(),
+            Err(e) => json!({ "error": e }).to_string()
+        }
+    }
+
+    /// 序列化为DW语法
+    ///
+    /// 包含`Modify`等修改后的结果，可以通过`DataWindow::Create`或`Parse`重新导入
+    #[method(name = "ToSyntax")]
+    fn to_syntax(&self) -> String {
+        if let Some(inner) = &self.inner {
+            inner.ast.to_string()
+        } else {
+            "".to_owned()
+        }
+    }
+
     /// 反序列化`JSON-AST`字符串
     #[method(name = "FromJson")]
     fn from_json_ast(&mut self, syn: String) -> RetCode {
@@ -58,11 +560,11 @@ impl DWParser {
             //SAFETY
             transmute(syn.as_str())
         };
-        let ast = serde_json::from_str::<DWSyntax>(syn_ref)?;
-        self.inner = Some(DWParserInner {
-            syn,
-            ast
-        });
+        let ast = match serde_json::from_str::<DWSyntax>(syn_ref) {
+            Ok(ast) => ast,
+            Err(e) => return self.error.set(RetCode::E_INVALID_DATA, e)
+        };
+        self.inner = Some(DWParserInner::new(syn, ast));
         RetCode::OK
     }
 
@@ -75,10 +577,258 @@ impl DWParser {
             "".to_owned()
         }
     }
+
+    /// 反序列化`JSON-AST`对象
+    ///
+    /// # Parameters
+    ///
+    /// - `obj` `pfw::n_json`对象
+    #[method(name = "FromJsonObject")]
+    fn from_json_object(&mut self, obj: Object) -> RetCode {
+        let cls = obj.get_class_name();
+        if cls != "n_json" {
+            return self.error.set(RetCode::E_INVALID_OBJECT, format!("unexpect class {cls}"));
+        }
+        self.from_json_ast(pfw::json_serialize(&obj))
+    }
+
+    /// 反序列化`JSON-AST`对象
+    ///
+    /// # Parameters
+    ///
+    /// - `obj` `nx_json`对象
+    #[method(name = "FromJsonObject")]
+    fn from_json(&mut self, obj: &mut Json) -> RetCode { self.from_json_ast(obj.to_json()) }
+
+    /// 序列化为`JSON-AST`对象
+    ///
+    /// # Returns
+    ///
+    /// `pfw::n_json`对象(`pfw.dll`不可用时为`nx_json`对象)，未解析时为空对象
+    #[method(name = "ToJsonObject")]
+    fn to_json_object(&self) -> Object {
+        let mut data = self.to_json_ast();
+        if data.is_empty() {
+            data = "{}".to_owned();
+        }
+        json::parse(self.get_session(), &data)
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl DWParser {
+    /// 获取语法项的参数值，无效时返回空字符串
+    fn describe_value(&self, selector: &str) -> String {
+        match &self.inner {
+            Some(inner) => describe_value(&inner.ast, selector),
+            None => "".to_owned()
+        }
+    }
+
+    /// 指定区域的对象名列表
+    ///
+    /// # Description
+    ///
+    /// 按区域缓存，修改语法后重新生成
+    fn objects(&self, band: &str) -> Rc<Vec<String>> {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return Rc::default()
+        };
+        let key = band_key(band);
+        let mut cache = inner.objects.borrow_mut();
+        if cache.is_empty() {
+            let mut all = Vec::new();
+            let mut bands: HashMap<String, Vec<String>> = HashMap::new();
+            for (name, band) in object_bands(&inner.ast) {
+                bands.entry(band).or_default().push(name.clone());
+                all.push(name);
+            }
+            cache.extend(bands.into_iter().map(|(band, names)| (band, Rc::new(names))));
+            cache.insert("*".to_owned(), Rc::new(all));
+        }
+        cache.get(&key).cloned().unwrap_or_default()
+    }
+}
+
+/// 获取语法项的参数值，无效时返回空字符串
+pub(super) fn describe_value(ast: &DWSyntax, selector: &str) -> String {
+    let rv = ast.describe(selector);
+    if rv == "!" || rv == "?" {
+        "".to_owned()
+    } else {
+        rv
+    }
+}
+
+/// 列名列表
+pub(super) fn columns(ast: &DWSyntax) -> Vec<String> {
+    let count: usize = describe_value(ast, "DataWindow.Column.Count").parse().unwrap_or_default();
+    (1..=count).map(|idx| describe_value(ast, &format!("#{idx}.Name"))).collect()
+}
+
+/// 指定区域的对象名列表
+pub(super) fn objects(ast: &DWSyntax, band: &str) -> Vec<String> {
+    let key = band_key(band);
+    object_bands(ast)
+        .into_iter()
+        .filter(|(_, band)| key == "*" || *band == key)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// 区域名的缓存键，空字符串或`*`表示所有区域
+fn band_key(band: &str) -> String {
+    match band.trim() {
+        "" => "*".to_owned(),
+        band => band.to_ascii_lowercase()
+    }
+}
+
+/// 所有对象的名称和所在区域(小写)
+///
+/// # Description
+///
+/// 遍历一次语法树，按定义顺序收集带有`band`属性的命名项，缺少属性时才通过`Describe`查询
+fn object_bands(ast: &DWSyntax) -> Vec<(String, String)> {
+    fn collect(val: &Value, ast: &DWSyntax, found: &mut Vec<(String, String)>) {
+        match val {
+            Value::Object(map) => {
+                for val in map.values() {
+                    collect(val, ast, found);
+                }
+            },
+            Value::Array(items) => {
+                for item in items {
+                    match item.get("name").and_then(Value::as_str).filter(|name| !name.is_empty()) {
+                        Some(name) => {
+                            if let Some(band) = item_band(item, ast, name) {
+                                found.push((name.to_owned(), band));
+                            }
+                        },
+                        None => collect(item, ast, found)
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    let root = match serde_json::to_value(ast) {
+        Ok(root) => root,
+        Err(_) => return Vec::new()
+    };
+    let mut found = Vec::new();
+    collect(&root, ast, &mut found);
+    found
+}
+
+/// 语法项所在的区域(小写)，不是对象(如`table`中的列定义)时返回`None`
+fn item_band(item: &Value, ast: &DWSyntax, name: &str) -> Option<String> {
+    let map = item.as_object()?;
+    //属性可能直接位于语法项或其属性表中
+    let band = map
+        .iter()
+        .chain(map.values().filter_map(Value::as_object).flatten())
+        .find(|(key, _)| key.eq_ignore_ascii_case("band"))
+        .map(|(_, val)| val.as_str().map(ToOwned::to_owned).unwrap_or_else(|| val.to_string()));
+    match band {
+        Some(band) => Some(band.trim_matches('"').to_ascii_lowercase()),
+        None if map.keys().any(|key| key.eq_ignore_ascii_case("x")) => {
+            Some(describe_value(ast, &format!("{name}.Band")).to_ascii_lowercase())
+                .filter(|band| !band.is_empty())
+        },
+        None => None
+    }
+}
+
+/// 解析`显示值\t数据值/...`格式的代码表
+fn code_values(values: &str) -> serde_json::Value {
+    let values: Vec<_> = values
+        .split('/')
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let item = item.replace("~t", "\t");
+            let (display, value) = item.split_once('\t').unwrap_or((item.as_str(), ""));
+            json!({
+                "value": value,
+                "display": display
+            })
+        })
+        .collect();
+    values.into()
+}
+
+/// 转换批量修改项为`Modify`参数
+fn patch_modifier(entry: &serde_json::Value) -> Result<String, String> {
+    let field = |key: &str| entry.get(key).and_then(serde_json::Value::as_str);
+    match field("op").unwrap_or_default() {
+        "modify" => {
+            if let Some(modifier) = field("modifier") {
+                return Ok(modifier.to_owned());
+            }
+            let target = field("target").ok_or("missing target")?;
+            let value = match entry.get("value") {
+                Some(serde_json::Value::String(value)) => {
+                    format!("\"{}\"", value.replace('~', "~~").replace('"', "~\""))
+                },
+                Some(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => {
+                    value.to_string()
+                },
+                _ => return Err("missing value".to_owned())
+            };
+            Ok(format!("{target}={value}"))
+        },
+        "create" => Ok(format!("create {}", field("syntax").ok_or("missing syntax")?)),
+        "destroy" => {
+            let name = field("name").ok_or("missing name")?;
+            if entry.get("column").and_then(serde_json::Value::as_bool).unwrap_or_default() {
+                Ok(format!("destroy column {name}"))
+            } else {
+                Ok(format!("destroy {name}"))
+            }
+        },
+        op => Err(format!("unsupported op: {op}"))
+    }
+}
+
+/// 比较两个名称列表
+fn diff_names(old: &[String], new: &[String]) -> serde_json::Value {
+    let added: Vec<_> = new.iter().filter(|name| !old.contains(name)).collect();
+    let removed: Vec<_> = old.iter().filter(|name| !new.contains(name)).collect();
+    json!({
+        "added": added,
+        "removed": removed
+    })
 }
 
 #[allow(dead_code)]
 struct DWParserInner {
     syn: String, //NOTE 不能修改
-    ast: DWSyntax<'static>
+    ast: DWSyntax<'static>,
+    //按区域缓存的对象名列表，`*`表示所有区域
+    objects: RefCell<HashMap<String, Rc<Vec<String>>>>
+}
+
+impl DWParserInner {
+    fn new(syn: String, ast: DWSyntax<'static>) -> Self {
+        DWParserInner {
+            syn,
+            ast,
+            objects: RefCell::default()
+        }
+    }
+
+    /// 修改语法项的参数值并清除缓存
+    fn modify(&mut self, modifier: &str) -> String {
+        self.objects.get_mut().clear();
+        self.ast.modify(modifier)
+    }
 }
@@ -0,0 +1,1110 @@
+//! DataWindow表达式求值
+//!
+//! 支持常用的运算符、`if`/`case`、字符串/数值/日期函数以及列引用，不支持聚合函数和依赖行上下文的函数
+
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike, Weekday};
+use serde_json::{json, Map, Value as JsonValue};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// 表达式的值
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    DateTime(NaiveDateTime)
+}
+
+impl Value {
+    /// 类型名
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Date(_) => "date",
+            Value::Time(_) => "time",
+            Value::DateTime(_) => "datetime"
+        }
+    }
+
+    /// 转换为`JSON`值，日期时间使用`ISO-8601`格式
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            Value::Null => JsonValue::Null,
+            Value::Number(val) => json!(val),
+            Value::String(val) => json!(val),
+            Value::Boolean(val) => json!(val),
+            Value::Date(val) => json!(val.format("%Y-%m-%d").to_string()),
+            Value::Time(val) => json!(val.format("%H:%M:%S%.f").to_string()),
+            Value::DateTime(val) => json!(val.format("%Y-%m-%d %H:%M:%S%.f").to_string())
+        }
+    }
+
+    fn from_json(val: &JsonValue) -> Value {
+        match val {
+            JsonValue::Null => Value::Null,
+            JsonValue::Bool(val) => Value::Boolean(*val),
+            JsonValue::Number(val) => Value::Number(val.as_f64().unwrap_or_default()),
+            JsonValue::String(val) => Value::String(val.clone()),
+            val => Value::String(val.to_string())
+        }
+    }
+
+    fn is_true(&self) -> bool { matches!(self, Value::Boolean(true)) }
+}
+
+/// 解析并计算表达式
+///
+/// # Parameters
+///
+/// - `expr` 表达式
+/// - `row` 列名与值，列名不区分大小写
+pub fn evaluate(expr: &str, row: &Map<String, JsonValue>) -> Result<Value> {
+    let tokens = tokenize(expr)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0
+    };
+    let expr = parser.parse_expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected token: {token:?}"));
+    }
+    Evaluator {
+        row
+    }
+    .eval(&expr)
+}
+
+/// 表达式中引用的列或对象名
+///
+/// # Description
+///
+/// 仅做词法分析，不要求表达式可以被求值(如聚合函数`sum(amount for all)`)
+pub fn references(expr: &str) -> Result<Vec<String>> {
+    //非函数调用的关键字
+    const KEYWORDS: &[&str] = &[
+        "and", "or", "not", "true", "false", "for", "all", "group", "page", "crosstab", "graph", "distinct",
+        "when", "then", "else", "to", "is", "null"
+    ];
+    let tokens = tokenize(expr)?;
+    let mut refs: Vec<String> = Vec::new();
+    for (idx, token) in tokens.iter().enumerate() {
+        if let Token::Ident(ident) = token {
+            if tokens.get(idx + 1) == Some(&Token::LParen) ||
+                KEYWORDS.iter().any(|keyword| ident.eq_ignore_ascii_case(keyword)) ||
+                refs.iter().any(|name| name.eq_ignore_ascii_case(ident))
+            {
+                continue;
+            }
+            refs.push(ident.clone());
+        }
+    }
+    Ok(refs)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    String(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+        let ch = chars[idx];
+        if ch.is_whitespace() {
+            idx += 1;
+        } else if ch.is_ascii_digit() || (ch == '.' && chars.get(idx + 1).map_or(false, char::is_ascii_digit))
+        {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_ascii_digit() || chars[idx] == '.') {
+                idx += 1;
+            }
+            //科学计数法
+            if idx < chars.len() && (chars[idx] == 'e' || chars[idx] == 'E') {
+                let mut end = idx + 1;
+                if end < chars.len() && (chars[end] == '+' || chars[end] == '-') {
+                    end += 1;
+                }
+                if end < chars.len() && chars[end].is_ascii_digit() {
+                    idx = end;
+                    while idx < chars.len() && chars[idx].is_ascii_digit() {
+                        idx += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..idx].iter().collect();
+            tokens.push(Token::Number(text.parse().map_err(|_| format!("invalid number: {text}"))?));
+        } else if ch == '"' || ch == '\'' {
+            //`~`转义
+            let mut text = String::new();
+            idx += 1;
+            loop {
+                match chars.get(idx) {
+                    Some(&c) if c == ch => break,
+                    Some('~') => {
+                        idx += 1;
+                        match chars.get(idx) {
+                            Some('n') => text.push('\n'),
+                            Some('r') => text.push('\r'),
+                            Some('t') => text.push('\t'),
+                            Some(&c) => text.push(c),
+                            None => return Err("unterminated string".to_owned())
+                        }
+                    },
+                    Some(&c) => text.push(c),
+                    None => return Err("unterminated string".to_owned())
+                }
+                idx += 1;
+            }
+            idx += 1;
+            tokens.push(Token::String(text));
+        } else if ch.is_alphabetic() || ch == '_' || ch == '#' || ch == '$' {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_alphanumeric() || matches!(chars[idx], '_' | '#' | '$'))
+            {
+                idx += 1;
+            }
+            tokens.push(Token::Ident(chars[start..idx].iter().collect()));
+        } else {
+            let next = chars.get(idx + 1).copied();
+            let (token, len) = match (ch, next) {
+                ('<', Some('=')) => (Token::Op("<="), 2),
+                ('>', Some('=')) => (Token::Op(">="), 2),
+                ('<', Some('>')) => (Token::Op("<>"), 2),
+                ('<', _) => (Token::Op("<"), 1),
+                ('>', _) => (Token::Op(">"), 1),
+                ('=', _) => (Token::Op("="), 1),
+                ('+', _) => (Token::Op("+"), 1),
+                ('-', _) => (Token::Op("-"), 1),
+                ('*', _) => (Token::Op("*"), 1),
+                ('/', _) => (Token::Op("/"), 1),
+                ('^', _) => (Token::Op("^"), 1),
+                ('(', _) => (Token::LParen, 1),
+                (')', _) => (Token::RParen, 1),
+                (',', _) => (Token::Comma, 1),
+                _ => return Err(format!("unexpected character: {ch}"))
+            };
+            tokens.push(token);
+            idx += len;
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Literal(Value),
+    Column(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Case {
+        value: Box<Expr>,
+        whens: Vec<(Vec<CaseMatch>, Expr)>,
+        other: Option<Box<Expr>>
+    }
+}
+
+#[derive(Debug)]
+enum CaseMatch {
+    Value(Expr),
+    Range(Expr, Expr)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.pos) }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.advance() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {expected:?}, found {token:?}")),
+            None => Err(format!("expected {expected:?}, found end of expression"))
+        }
+    }
+
+    /// 下一个标识符是否为指定关键字(不区分大小写)
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            },
+            _ => None
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr> {
+        let mut lhs = self.parse_and()?;
+        while self.eat_keyword("or") {
+            lhs = Expr::Binary("or", Box::new(lhs), Box::new(self.parse_and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut lhs = self.parse_not()?;
+        while self.eat_keyword("and") {
+            lhs = Expr::Binary("and", Box::new(lhs), Box::new(self.parse_not()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") {
+            Ok(Expr::Unary("not", Box::new(self.parse_not()?)))
+        } else {
+            self.parse_cmp()
+        }
+    }
+
+    fn parse_cmp(&mut self) -> Result<Expr> {
+        let mut lhs = self.parse_add()?;
+        while let Some(op) = self.eat_op(&["=", "<>", "<", ">", "<=", ">="]) {
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.parse_add()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_add(&mut self) -> Result<Expr> {
+        let mut lhs = self.parse_mul()?;
+        while let Some(op) = self.eat_op(&["+", "-"]) {
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.parse_mul()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_mul(&mut self) -> Result<Expr> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(op) = self.eat_op(&["*", "/"]) {
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.parse_unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        match self.eat_op(&["+", "-"]) {
+            Some(op) => Ok(Expr::Unary(op, Box::new(self.parse_unary()?))),
+            None => self.parse_pow()
+        }
+    }
+
+    fn parse_pow(&mut self) -> Result<Expr> {
+        let mut lhs = self.parse_primary()?;
+        while self.eat_op(&["^"]).is_some() {
+            lhs = Expr::Binary("^", Box::new(lhs), Box::new(self.parse_primary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        match self.advance() {
+            Some(Token::Number(val)) => Ok(Expr::Literal(Value::Number(val))),
+            Some(Token::String(val)) => Ok(Expr::Literal(Value::String(val))),
+            Some(Token::LParen) => {
+                let expr = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            },
+            Some(Token::Ident(ident)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.pos += 1;
+                    if ident.eq_ignore_ascii_case("case") {
+                        return self.parse_case();
+                    }
+                    let mut args = Vec::new();
+                    if self.peek() != Some(&Token::RParen) {
+                        loop {
+                            args.push(self.parse_expr()?);
+                            if self.peek() != Some(&Token::Comma) {
+                                break;
+                            }
+                            self.pos += 1;
+                        }
+                    }
+                    self.expect(Token::RParen)?;
+                    Ok(Expr::Call(ident.to_ascii_lowercase(), args))
+                } else if ident.eq_ignore_ascii_case("true") {
+                    Ok(Expr::Literal(Value::Boolean(true)))
+                } else if ident.eq_ignore_ascii_case("false") {
+                    Ok(Expr::Literal(Value::Boolean(false)))
+                } else {
+                    Ok(Expr::Column(ident))
+                }
+            },
+            Some(token) => Err(format!("unexpected token: {token:?}")),
+            None => Err("unexpected end of expression".to_owned())
+        }
+    }
+
+    /// `case(value when a then x when b, c then y when d to e then z else w)`
+    fn parse_case(&mut self) -> Result<Expr> {
+        let value = self.parse_expr()?;
+        let mut whens = Vec::new();
+        while self.eat_keyword("when") {
+            let mut matches = Vec::new();
+            loop {
+                let start = self.parse_expr()?;
+                if self.eat_keyword("to") {
+                    matches.push(CaseMatch::Range(start, self.parse_expr()?));
+                } else {
+                    matches.push(CaseMatch::Value(start));
+                }
+                if self.peek() != Some(&Token::Comma) {
+                    break;
+                }
+                self.pos += 1;
+            }
+            if !self.eat_keyword("then") {
+                return Err("expected then".to_owned());
+            }
+            whens.push((matches, self.parse_expr()?));
+        }
+        let other = if self.eat_keyword("else") {
+            Some(Box::new(self.parse_expr()?))
+        } else {
+            None
+        };
+        self.expect(Token::RParen)?;
+        if whens.is_empty() {
+            return Err("case requires at least one when".to_owned());
+        }
+        Ok(Expr::Case {
+            value: Box::new(value),
+            whens,
+            other
+        })
+    }
+}
+
+struct Evaluator<'a> {
+    row: &'a Map<String, JsonValue>
+}
+
+impl Evaluator<'_> {
+    fn eval(&self, expr: &Expr) -> Result<Value> {
+        match expr {
+            Expr::Literal(val) => Ok(val.clone()),
+            Expr::Column(name) => {
+                let val = self.row.get(name).or_else(|| {
+                    self.row.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, val)| val)
+                });
+                match val {
+                    Some(val) => Ok(Value::from_json(val)),
+                    None => Err(format!("unknown column: {name}"))
+                }
+            },
+            Expr::Unary(op, operand) => {
+                let val = self.eval(operand)?;
+                match (*op, val) {
+                    (_, Value::Null) => Ok(Value::Null),
+                    ("not", Value::Boolean(val)) => Ok(Value::Boolean(!val)),
+                    ("-", Value::Number(val)) => Ok(Value::Number(-val)),
+                    ("+", Value::Number(val)) => Ok(Value::Number(val)),
+                    (op, val) => Err(format!("operator {op} not applicable to {}", val.type_name()))
+                }
+            },
+            Expr::Binary(op @ ("and" | "or"), lhs, rhs) => {
+                let lhs = self.eval(lhs)?.is_true();
+                //短路求值
+                if (*op == "and" && !lhs) || (*op == "or" && lhs) {
+                    return Ok(Value::Boolean(lhs));
+                }
+                Ok(Value::Boolean(self.eval(rhs)?.is_true()))
+            },
+            Expr::Binary(op, lhs, rhs) => binary(op, self.eval(lhs)?, self.eval(rhs)?),
+            Expr::Call(name, args) => self.call(name, args),
+            Expr::Case {
+                value,
+                whens,
+                other
+            } => {
+                let value = self.eval(value)?;
+                for (matches, then) in whens {
+                    for item in matches {
+                        let hit = match item {
+                            CaseMatch::Value(expr) => compare(&value, &self.eval(expr)?)? == Some(0),
+                            CaseMatch::Range(start, end) => {
+                                matches!(compare(&value, &self.eval(start)?)?, Some(0 | 1)) &&
+                                    matches!(compare(&value, &self.eval(end)?)?, Some(-1 | 0))
+                            },
+                        };
+                        if hit {
+                            return self.eval(then);
+                        }
+                    }
+                }
+                match other {
+                    Some(other) => self.eval(other),
+                    None => Ok(Value::Null)
+                }
+            }
+        }
+    }
+
+    fn call(&self, name: &str, args: &[Expr]) -> Result<Value> {
+        //需要惰性求值或处理NULL参数的函数
+        match name {
+            "if" => {
+                check_args(name, args.len(), 3, 3)?;
+                return if self.eval(&args[0])?.is_true() {
+                    self.eval(&args[1])
+                } else {
+                    self.eval(&args[2])
+                };
+            },
+            "isnull" => {
+                check_args(name, args.len(), 1, 1)?;
+                return Ok(Value::Boolean(self.eval(&args[0])? == Value::Null));
+            },
+            _ => {}
+        }
+        let args = args.iter().map(|arg| self.eval(arg)).collect::<Result<Vec<_>>>()?;
+        //参数为NULL时结果为NULL
+        if args.contains(&Value::Null) {
+            return Ok(Value::Null);
+        }
+        function(name, &args)
+    }
+}
+
+fn check_args(name: &str, count: usize, min: usize, max: usize) -> Result<()> {
+    if count < min || count > max {
+        Err(format!("{name}: expected {min}..{max} arguments, found {count}"))
+    } else {
+        Ok(())
+    }
+}
+
+fn binary(op: &str, lhs: Value, rhs: Value) -> Result<Value> {
+    if lhs == Value::Null || rhs == Value::Null {
+        return Ok(Value::Null);
+    }
+    let ord = match op {
+        "=" | "<>" | "<" | ">" | "<=" | ">=" => compare(&lhs, &rhs)?,
+        _ => None
+    };
+    Ok(match (op, lhs, rhs) {
+        ("=", ..) => Value::Boolean(ord == Some(0)),
+        ("<>", ..) => Value::Boolean(ord != Some(0)),
+        ("<", ..) => Value::Boolean(ord == Some(-1)),
+        (">", ..) => Value::Boolean(ord == Some(1)),
+        ("<=", ..) => Value::Boolean(matches!(ord, Some(-1 | 0))),
+        (">=", ..) => Value::Boolean(matches!(ord, Some(0 | 1))),
+        ("+", Value::String(lhs), Value::String(rhs)) => Value::String(lhs + &rhs),
+        ("+", Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs + rhs),
+        ("-", Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs - rhs),
+        ("*", Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs * rhs),
+        ("/", Value::Number(_), Value::Number(rhs)) if rhs == 0.0 => {
+            return Err("division by zero".to_owned())
+        },
+        ("/", Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs / rhs),
+        ("^", Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs.powf(rhs)),
+        (op, lhs, rhs) => {
+            return Err(format!(
+                "operator {op} not applicable to {} and {}",
+                lhs.type_name(),
+                rhs.type_name()
+            ))
+        },
+    })
+}
+
+/// 比较两个值
+///
+/// # Returns
+///
+/// `-1`/`0`/`1`，`NULL`参与比较时返回`None`
+fn compare(lhs: &Value, rhs: &Value) -> Result<Option<i8>> {
+    let ord = match (lhs, rhs) {
+        (Value::Null, _) | (_, Value::Null) => return Ok(None),
+        (Value::Number(lhs), Value::Number(rhs)) => lhs.partial_cmp(rhs),
+        (Value::String(lhs), Value::String(rhs)) => Some(lhs.cmp(rhs)),
+        (Value::Boolean(lhs), Value::Boolean(rhs)) => Some(lhs.cmp(rhs)),
+        (Value::Time(lhs), Value::Time(rhs)) => Some(lhs.cmp(rhs)),
+        (Value::Date(_) | Value::DateTime(_), Value::Date(_) | Value::DateTime(_)) => {
+            Some(to_datetime(lhs)?.cmp(&to_datetime(rhs)?))
+        },
+        //与字符串形式的日期比较
+        (Value::Date(_) | Value::DateTime(_), Value::String(_)) |
+        (Value::String(_), Value::Date(_) | Value::DateTime(_)) => {
+            Some(to_datetime(lhs)?.cmp(&to_datetime(rhs)?))
+        },
+        (lhs, rhs) => return Err(format!("cannot compare {} with {}", lhs.type_name(), rhs.type_name()))
+    };
+    Ok(ord.map(|ord| ord as i8))
+}
+
+fn function(name: &str, args: &[Value]) -> Result<Value> {
+    let arity = |min: usize, max: usize| check_args(name, args.len(), min, max);
+    let num = |idx: usize| to_number(&args[idx]);
+    let text = |idx: usize| to_string(&args[idx], None);
+    Ok(match name {
+        //字符串
+        "string" => {
+            arity(1, 2)?;
+            let format = if args.len() > 1 {
+                Some(text(1)?)
+            } else {
+                None
+            };
+            Value::String(to_string(&args[0], format.as_deref())?)
+        },
+        "len" => {
+            arity(1, 1)?;
+            Value::Number(text(0)?.chars().count() as f64)
+        },
+        "upper" => {
+            arity(1, 1)?;
+            Value::String(text(0)?.to_uppercase())
+        },
+        "lower" => {
+            arity(1, 1)?;
+            Value::String(text(0)?.to_lowercase())
+        },
+        "trim" => {
+            arity(1, 1)?;
+            Value::String(text(0)?.trim_matches(' ').to_owned())
+        },
+        "lefttrim" => {
+            arity(1, 1)?;
+            Value::String(text(0)?.trim_start_matches(' ').to_owned())
+        },
+        "righttrim" => {
+            arity(1, 1)?;
+            Value::String(text(0)?.trim_end_matches(' ').to_owned())
+        },
+        "left" => {
+            arity(2, 2)?;
+            Value::String(text(0)?.chars().take(num(1)?.max(0.0) as usize).collect())
+        },
+        "right" => {
+            arity(2, 2)?;
+            let chars: Vec<char> = text(0)?.chars().collect();
+            let count = (num(1)?.max(0.0) as usize).min(chars.len());
+            Value::String(chars[chars.len() - count..].iter().collect())
+        },
+        "mid" => {
+            arity(2, 3)?;
+            let start = (num(1)?.max(1.0) as usize) - 1;
+            let count = if args.len() > 2 {
+                num(2)?.max(0.0) as usize
+            } else {
+                usize::MAX
+            };
+            Value::String(text(0)?.chars().skip(start).take(count).collect())
+        },
+        "pos" => {
+            arity(2, 3)?;
+            let chars: Vec<char> = text(0)?.chars().collect();
+            let pattern: Vec<char> = text(1)?.chars().collect();
+            let start = if args.len() > 2 {
+                (num(2)?.max(1.0) as usize) - 1
+            } else {
+                0
+            };
+            let pos = if pattern.is_empty() || start >= chars.len() {
+                None
+            } else {
+                chars[start..].windows(pattern.len()).position(|window| window == pattern.as_slice())
+            };
+            Value::Number(pos.map_or(0, |pos| pos + start + 1) as f64)
+        },
+        "replace" => {
+            arity(4, 4)?;
+            let chars: Vec<char> = text(0)?.chars().collect();
+            let start = ((num(1)?.max(1.0) as usize) - 1).min(chars.len());
+            //长度可能很大(转换时饱和为`usize::MAX`)
+            let end = start.saturating_add(num(2)?.max(0.0) as usize).min(chars.len());
+            let mut rv: String = chars[..start].iter().collect();
+            rv.push_str(&text(3)?);
+            rv.extend(&chars[end..]);
+            Value::String(rv)
+        },
+        "fill" => {
+            arity(2, 2)?;
+            let pattern = text(0)?;
+            Value::String(pattern.chars().cycle().take(num(1)?.max(0.0) as usize).collect())
+        },
+        "space" => {
+            arity(1, 1)?;
+            Value::String(" ".repeat(num(0)?.max(0.0) as usize))
+        },
+        "asc" => {
+            arity(1, 1)?;
+            Value::Number(text(0)?.chars().next().map_or(0, |c| c as u32) as f64)
+        },
+        "char" => {
+            arity(1, 1)?;
+            Value::String(char::from_u32(num(0)? as u32).map(String::from).unwrap_or_default())
+        },
+        "isnumber" => {
+            arity(1, 1)?;
+            Value::Boolean(text(0)?.trim().parse::<f64>().is_ok())
+        },
+        "isdate" => {
+            arity(1, 1)?;
+            Value::Boolean(parse_date(&text(0)?).is_some())
+        },
+        "istime" => {
+            arity(1, 1)?;
+            Value::Boolean(parse_time(&text(0)?).is_some())
+        },
+        //数值
+        "number" | "real" | "double" | "dec" => {
+            arity(1, 1)?;
+            Value::Number(num(0)?)
+        },
+        "long" | "integer" => {
+            arity(1, 1)?;
+            Value::Number(num(0)?.trunc())
+        },
+        "abs" => {
+            arity(1, 1)?;
+            Value::Number(num(0)?.abs())
+        },
+        "sign" => {
+            arity(1, 1)?;
+            let val = num(0)?;
+            Value::Number(if val > 0.0 {
+                1.0
+            } else if val < 0.0 {
+                -1.0
+            } else {
+                0.0
+            })
+        },
+        "sqrt" => {
+            arity(1, 1)?;
+            Value::Number(num(0)?.sqrt())
+        },
+        "int" => {
+            arity(1, 1)?;
+            Value::Number(num(0)?.floor())
+        },
+        "ceiling" => {
+            arity(1, 1)?;
+            Value::Number(num(0)?.ceil())
+        },
+        "round" | "truncate" => {
+            arity(2, 2)?;
+            let factor = 10f64.powi(num(1)? as i32);
+            let val = num(0)? * factor;
+            //四舍五入远离零
+            let val = if name == "round" {
+                val.round()
+            } else {
+                val.trunc()
+            };
+            Value::Number(val / factor)
+        },
+        "mod" => {
+            arity(2, 2)?;
+            let rhs = num(1)?;
+            if rhs == 0.0 {
+                return Err("division by zero".to_owned());
+            }
+            Value::Number(num(0)? % rhs)
+        },
+        "max" | "min" => {
+            arity(2, 2)?;
+            let ord = compare(&args[0], &args[1])?;
+            let pick_first = if name == "max" {
+                ord != Some(-1)
+            } else {
+                ord != Some(1)
+            };
+            if pick_first {
+                args[0].clone()
+            } else {
+                args[1].clone()
+            }
+        },
+        "rgb" => {
+            arity(3, 3)?;
+            let channel = |idx: usize| num(idx).map(|val| val.clamp(0.0, 255.0) as u32);
+            Value::Number((channel(0)? | channel(1)? << 8 | channel(2)? << 16) as f64)
+        },
+        //日期时间
+        "today" => {
+            arity(0, 0)?;
+            Value::Date(Local::now().date_naive())
+        },
+        "now" => {
+            arity(0, 0)?;
+            Value::Time(Local::now().time())
+        },
+        "date" => {
+            arity(1, 3)?;
+            if args.len() == 3 {
+                let date = NaiveDate::from_ymd_opt(num(0)? as i32, num(1)? as u32, num(2)? as u32);
+                Value::Date(date.ok_or("invalid date")?)
+            } else {
+                Value::Date(to_datetime(&args[0])?.date())
+            }
+        },
+        "time" => {
+            arity(1, 3)?;
+            if args.len() == 3 {
+                let time = NaiveTime::from_hms_opt(num(0)? as u32, num(1)? as u32, num(2)? as u32);
+                Value::Time(time.ok_or("invalid time")?)
+            } else {
+                Value::Time(to_time(&args[0])?)
+            }
+        },
+        "datetime" => {
+            arity(1, 2)?;
+            let date = to_datetime(&args[0])?;
+            if args.len() == 2 {
+                Value::DateTime(date.date().and_time(to_time(&args[1])?))
+            } else {
+                Value::DateTime(date)
+            }
+        },
+        "year" => {
+            arity(1, 1)?;
+            Value::Number(to_datetime(&args[0])?.year() as f64)
+        },
+        "month" => {
+            arity(1, 1)?;
+            Value::Number(to_datetime(&args[0])?.month() as f64)
+        },
+        "day" => {
+            arity(1, 1)?;
+            Value::Number(to_datetime(&args[0])?.day() as f64)
+        },
+        "hour" => {
+            arity(1, 1)?;
+            Value::Number(to_time(&args[0])?.hour() as f64)
+        },
+        "minute" => {
+            arity(1, 1)?;
+            Value::Number(to_time(&args[0])?.minute() as f64)
+        },
+        "second" => {
+            arity(1, 1)?;
+            Value::Number(to_time(&args[0])?.second() as f64)
+        },
+        "daynumber" => {
+            arity(1, 1)?;
+            Value::Number((to_datetime(&args[0])?.weekday().num_days_from_sunday() + 1) as f64)
+        },
+        "dayname" => {
+            arity(1, 1)?;
+            Value::String(day_name(to_datetime(&args[0])?.weekday()).to_owned())
+        },
+        "daysafter" => {
+            arity(2, 2)?;
+            let days = to_datetime(&args[1])?.date().signed_duration_since(to_datetime(&args[0])?.date());
+            Value::Number(days.num_days() as f64)
+        },
+        "secondsafter" => {
+            arity(2, 2)?;
+            let secs = to_time(&args[1])?.signed_duration_since(to_time(&args[0])?);
+            Value::Number(secs.num_seconds() as f64)
+        },
+        "relativedate" => {
+            arity(2, 2)?;
+            let date = to_datetime(&args[0])?.date();
+            let date = TimeDelta::try_days(num(1)? as i64).and_then(|days| date.checked_add_signed(days));
+            Value::Date(date.ok_or("date out of range")?)
+        },
+        "relativetime" => {
+            arity(2, 2)?;
+            let secs = TimeDelta::try_seconds(num(1)? as i64).ok_or("time out of range")?;
+            Value::Time(to_time(&args[0])?.overflowing_add_signed(secs).0)
+        },
+        _ => return Err(format!("unsupported function: {name}"))
+    })
+}
+
+fn to_number(val: &Value) -> Result<f64> {
+    match val {
+        Value::Number(val) => Ok(*val),
+        Value::String(val) => val.trim().parse().map_err(|_| format!("not a number: {val}")),
+        Value::Boolean(val) => {
+            Ok(if *val {
+                1.0
+            } else {
+                0.0
+            })
+        },
+        val => Err(format!("{} is not a number", val.type_name()))
+    }
+}
+
+fn to_datetime(val: &Value) -> Result<NaiveDateTime> {
+    match val {
+        Value::Date(val) => Ok(val.and_time(NaiveTime::MIN)),
+        Value::DateTime(val) => Ok(*val),
+        Value::String(text) => parse_date(text).ok_or_else(|| format!("not a date: {text}")),
+        val => Err(format!("{} is not a date", val.type_name()))
+    }
+}
+
+fn to_time(val: &Value) -> Result<NaiveTime> {
+    match val {
+        Value::Time(val) => Ok(*val),
+        Value::DateTime(val) => Ok(val.time()),
+        Value::Date(_) => Ok(NaiveTime::MIN),
+        Value::String(text) => {
+            parse_time(text)
+                .or_else(|| parse_date(text).map(|val| val.time()))
+                .ok_or_else(|| format!("not a time: {text}"))
+        },
+        val => Err(format!("{} is not a time", val.type_name()))
+    }
+}
+
+/// 解析日期或日期时间字符串
+fn parse_date(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    const DATETIME_FORMATS: &[&str] = &[
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y/%m/%d %H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+        "%Y/%m/%d %H:%M"
+    ];
+    const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%Y%m%d"];
+    DATETIME_FORMATS.iter().find_map(|fmt| NaiveDateTime::parse_from_str(text, fmt).ok()).or_else(|| {
+        DATE_FORMATS
+            .iter()
+            .find_map(|fmt| NaiveDate::parse_from_str(text, fmt).ok())
+            .map(|date| date.and_time(NaiveTime::MIN))
+    })
+}
+
+/// 解析时间字符串
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    let text = text.trim();
+    ["%H:%M:%S%.f", "%H:%M"].iter().find_map(|fmt| NaiveTime::parse_from_str(text, fmt).ok())
+}
+
+fn day_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday"
+    }
+}
+
+/// 转换为字符串
+///
+/// # Parameters
+///
+/// - `format` 格式，数值如`#,##0.00`，日期时间如`yyyy-mm-dd hh:mm:ss`
+fn to_string(val: &Value, format: Option<&str>) -> Result<String> {
+    Ok(match (val, format) {
+        (Value::Null, _) => String::new(),
+        (Value::String(val), _) => val.clone(),
+        (Value::Boolean(val), _) => val.to_string(),
+        (Value::Number(val), Some(format)) if !format.is_empty() => format_number(*val, format),
+        (Value::Number(val), _) => {
+            if val.fract() == 0.0 && val.abs() < 1e15 {
+                format!("{}", *val as i64)
+            } else {
+                val.to_string()
+            }
+        },
+        (Value::Date(val), Some(format)) if !format.is_empty() => {
+            format_datetime(&val.and_time(NaiveTime::MIN), format)
+        },
+        (Value::Time(val), Some(format)) if !format.is_empty() => {
+            format_datetime(&NaiveDate::default().and_time(*val), format)
+        },
+        (Value::DateTime(val), Some(format)) if !format.is_empty() => format_datetime(val, format),
+        (Value::Date(val), _) => val.format("%Y-%m-%d").to_string(),
+        (Value::Time(val), _) => val.format("%H:%M:%S").to_string(),
+        (Value::DateTime(val), _) => val.format("%Y-%m-%d %H:%M:%S").to_string()
+    })
+}
+
+/// 按`#,##0.00`形式的格式输出数值
+fn format_number(val: f64, format: &str) -> String {
+    //`正数;负数`
+    let (format, negative) = match format.split_once(';') {
+        Some((_, neg)) if val < 0.0 => (neg, true),
+        Some((pos, _)) => (pos, false),
+        None => (format, false)
+    };
+    let percent = format.contains('%');
+    let val = if percent {
+        val * 100.0
+    } else {
+        val
+    };
+    let val = if negative {
+        val.abs()
+    } else {
+        val
+    };
+    let decimals =
+        format.split_once('.').map_or(0, |(_, frac)| frac.chars().filter(|c| matches!(c, '0' | '#')).count());
+    let text = format!("{:.*}", decimals, val.abs());
+    let (int_part, frac_part) = text.split_once('.').unwrap_or((text.as_str(), ""));
+    let int_part = if format.contains(',') {
+        let digits: Vec<char> = int_part.chars().collect();
+        let mut rv = String::new();
+        for (idx, digit) in digits.iter().enumerate() {
+            if idx > 0 && (digits.len() - idx) % 3 == 0 {
+                rv.push(',');
+            }
+            rv.push(*digit);
+        }
+        rv
+    } else {
+        int_part.to_owned()
+    };
+    //`#`开头的格式省略整数部分的零
+    let int_part = if int_part == "0" && !format.split('.').next().unwrap_or_default().contains('0') {
+        String::new()
+    } else {
+        int_part
+    };
+    let mut rv = String::new();
+    if val < 0.0 {
+        rv.push('-');
+    }
+    //保留格式中的前缀(如货币符号)
+    rv.extend(format.chars().take_while(|c| !matches!(c, '0' | '#' | ',' | '.')));
+    rv.push_str(&int_part);
+    if !frac_part.is_empty() {
+        rv.push('.');
+        rv.push_str(frac_part);
+    }
+    if percent {
+        rv.push('%');
+    }
+    rv
+}
+
+/// 按`yyyy-mm-dd hh:mm:ss`形式的格式输出日期时间
+///
+/// `mm`紧跟在`h`之后时表示分钟，否则表示月份
+fn format_datetime(val: &NaiveDateTime, format: &str) -> String {
+    let chars: Vec<char> = format.chars().collect();
+    let mut rv = String::new();
+    let mut idx = 0;
+    let mut after_hour = false;
+    while idx < chars.len() {
+        let ch = chars[idx].to_ascii_lowercase();
+        let run = chars[idx..].iter().take_while(|c| c.to_ascii_lowercase() == ch).count();
+        match ch {
+            'y' if run >= 4 => rv.push_str(&format!("{:04}", val.year())),
+            'y' => rv.push_str(&format!("{:02}", val.year() % 100)),
+            'm' if after_hour => {
+                rv.push_str(&if run >= 2 {
+                    format!("{:02}", val.minute())
+                } else {
+                    val.minute().to_string()
+                });
+                after_hour = false;
+            },
+            'm' if run >= 4 => rv.push_str(month_name(val.month())),
+            'm' if run == 3 => rv.push_str(&month_name(val.month())[..3]),
+            'm' => {
+                rv.push_str(&if run >= 2 {
+                    format!("{:02}", val.month())
+                } else {
+                    val.month().to_string()
+                })
+            },
+            'd' if run >= 4 => rv.push_str(day_name(val.weekday())),
+            'd' if run == 3 => rv.push_str(&day_name(val.weekday())[..3]),
+            'd' => {
+                rv.push_str(&if run >= 2 {
+                    format!("{:02}", val.day())
+                } else {
+                    val.day().to_string()
+                })
+            },
+            'h' => {
+                rv.push_str(&if run >= 2 {
+                    format!("{:02}", val.hour())
+                } else {
+                    val.hour().to_string()
+                });
+                after_hour = true;
+            },
+            's' => {
+                rv.push_str(&if run >= 2 {
+                    format!("{:02}", val.second())
+                } else {
+                    val.second().to_string()
+                })
+            },
+            'f' => {
+                let frac = format!("{:06}", val.nanosecond() / 1000);
+                rv.push_str(&frac[..run.min(6)]);
+            },
+            _ => {
+                rv.extend(&chars[idx..idx + run]);
+                idx += run;
+                continue;
+            }
+        }
+        idx += run;
+    }
+    rv
+}
+
+fn month_name(month: u32) -> &'static str {
+    const NAMES: [&str; 12] = [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December"
+    ];
+    NAMES[(month as usize).clamp(1, 12) - 1]
+}
@@ -0,0 +1,160 @@
+use serde_json::Value;
+use std::fmt::Write;
+
+/// 行高
+const ROW_HEIGHT: i64 = 76;
+/// 行间距
+const ROW_SPACING: i64 = 16;
+/// 默认列宽
+const DEFAULT_WIDTH: i64 = 320;
+/// 自由格式的标签宽度
+const LABEL_WIDTH: i64 = 400;
+/// 边距
+const MARGIN: i64 = 9;
+/// 公共字体属性
+const FONT: &str = "font.face=\"Tahoma\" font.height=\"-10\" font.weight=\"400\" font.family=\"2\" \
+                    font.pitch=\"2\" font.charset=\"0\" background.mode=\"1\" background.color=\"536870912\"";
+
+struct ColumnSpec {
+    name: String,
+    label: String,
+    coltype: String,
+    width: i64,
+    numeric: bool,
+    limit: i64,
+    format: &'static str
+}
+
+impl ColumnSpec {
+    fn from_json(val: &Value) -> Result<ColumnSpec, String> {
+        let name =
+            val.get("name").and_then(Value::as_str).filter(|name| !name.is_empty()).ok_or("missing name")?;
+        if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("invalid column name: {name}"));
+        }
+        let label = val.get("label").and_then(Value::as_str).unwrap_or(name);
+        let int = |key: &str| val.get(key).and_then(Value::as_i64);
+        let coltype = val.get("type").and_then(Value::as_str).unwrap_or("string").trim().to_ascii_lowercase();
+        //简写类型
+        let coltype = match coltype.as_str() {
+            "string" | "char" => format!("char({})", int("length").unwrap_or(255)),
+            "int" | "integer" | "long" => "long".to_owned(),
+            "decimal" => format!("decimal({})", int("scale").unwrap_or(2)),
+            _ => coltype
+        };
+        let kind = coltype.split('(').next().unwrap_or_default();
+        let (numeric, format) = match kind {
+            "char" => (false, "[general]"),
+            "long" | "ulong" | "number" | "real" | "decimal" => (true, "[general]"),
+            "date" => (false, "[shortdate]"),
+            "datetime" => (false, "[shortdate] [time]"),
+            "time" => (false, "[time]"),
+            _ => return Err(format!("unsupported type: {coltype}"))
+        };
+        let limit = if kind == "char" {
+            coltype.trim_start_matches("char(").trim_end_matches(')').parse().unwrap_or_default()
+        } else {
+            0
+        };
+        Ok(ColumnSpec {
+            name: name.to_owned(),
+            label: label.to_owned(),
+            width: int("width").filter(|width| *width > 0).unwrap_or(DEFAULT_WIDTH),
+            coltype,
+            numeric,
+            limit,
+            format
+        })
+    }
+}
+
+/// 根据列定义生成DW语法
+///
+/// # Parameters
+///
+/// - `style` `grid`或`freeform`
+/// - `columns` 列定义`JSON`数组，每一项为`{"name": "id", "type": "long", "width": 300, "label": "编号"}`
+pub fn generate(style: &str, columns: &Value) -> Result<String, String> {
+    let grid = match style.trim().to_ascii_lowercase().as_str() {
+        "grid" => true,
+        "freeform" => false,
+        style => return Err(format!("unsupported style: {style}"))
+    };
+    let columns = columns
+        .as_array()
+        .ok_or("columns must be an array")?
+        .iter()
+        .map(ColumnSpec::from_json)
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.is_empty() {
+        return Err("no columns".to_owned());
+    }
+
+    let row_pitch = ROW_HEIGHT + ROW_SPACING;
+    let (header_height, detail_height) = if grid {
+        (row_pitch, row_pitch)
+    } else {
+        (0, row_pitch * columns.len() as i64 + ROW_SPACING)
+    };
+
+    let mut syn = String::new();
+    let _ = writeln!(syn, "release 10;");
+    let _ = writeln!(
+        syn,
+        "datawindow(units=0 timer_interval=0 color=1073741824 processing={} print.margin.bottom=96 \
+         print.margin.left=110 print.margin.right=110 print.margin.top=96 print.paper.size=0 print.paper.source=0 \
+         selected.mouse=no)",
+        if grid { 1 } else { 0 }
+    );
+    let _ = writeln!(syn, "header(height={header_height} color=\"536870912\")");
+    let _ = writeln!(syn, "summary(height=0 color=\"536870912\")");
+    let _ = writeln!(syn, "footer(height=0 color=\"536870912\")");
+    let _ = writeln!(syn, "detail(height={detail_height} color=\"536870912\")");
+    let _ = write!(syn, "table(");
+    for col in &columns {
+        let _ = write!(
+            syn,
+            "column=(type={} updatewhereclause=yes name={} dbname=\"{}\")\n ",
+            col.coltype, col.name, col.name
+        );
+    }
+    let _ = writeln!(syn, ")");
+
+    let mut x = MARGIN;
+    for (idx, col) in columns.iter().enumerate() {
+        let (label_band, label_x, label_y, label_width, col_x, col_y) = if grid {
+            ("header", x, 8, col.width, x, 8)
+        } else {
+            let y = ROW_SPACING + row_pitch * idx as i64;
+            ("detail", MARGIN, y, LABEL_WIDTH, MARGIN * 2 + LABEL_WIDTH, y)
+        };
+        let _ = writeln!(
+            syn,
+            "text(band={label_band} alignment=\"{}\" text=\"{}\" border=\"0\" color=\"33554432\" x=\"{label_x}\" \
+             y=\"{label_y}\" height=\"{ROW_HEIGHT}\" width=\"{label_width}\" html.valueishtml=\"0\" name={}_t \
+             visible=\"1\" {FONT})",
+            if grid { 2 } else { 1 },
+            escape(&col.label),
+            col.name
+        );
+        let _ = writeln!(
+            syn,
+            "column(band=detail id={} alignment=\"{}\" tabsequence={} border=\"0\" color=\"33554432\" x=\"{col_x}\" \
+             y=\"{col_y}\" height=\"{ROW_HEIGHT}\" width=\"{}\" format=\"{}\" html.valueishtml=\"0\" name={} \
+             visible=\"1\" edit.limit={} edit.case=any edit.autoselect=yes edit.autohscroll=yes {FONT})",
+            idx + 1,
+            if col.numeric { 1 } else { 0 },
+            (idx + 1) * 10,
+            col.width,
+            col.format,
+            col.name,
+            col.limit
+        );
+        x += col.width + MARGIN;
+    }
+    let _ = writeln!(syn, "htmltable(border=\"1\")");
+    Ok(syn)
+}
+
+/// 转义DW语法中的字符串
+fn escape(text: &str) -> String { text.replace('~', "~~").replace('"', "~\"") }
@@ -0,0 +1,141 @@
+use super::{
+    attr, dw::{columns, describe_value, objects}, expr
+};
+use dwparser::DWSyntax;
+use serde_json::{json, Value};
+
+/// 默认的Tab顺序步长
+const DEFAULT_TAB_STEP: i64 = 10;
+
+/// 校验DW语法
+///
+/// # Parameters
+///
+/// - `rules` 规则配置，未指定的规则默认启用，值为`false`时禁用：
+///   - `tab_order` Tab顺序存在间隔或重复，可指定`{"step": 10}`
+///   - `update` 未设置更新表或关键列
+///   - `edit_style` 可编辑的列没有编辑风格
+///   - `expressions` 表达式引用了不存在的列或对象
+///
+/// # Returns
+///
+/// 违规列表，每一项为`{"rule": "...", "target": "...", "message": "..."}`
+pub fn validate(ast: &DWSyntax, rules: &Value) -> Vec<Value> {
+    let enabled = |rule: &str| !matches!(rules.get(rule), Some(Value::Bool(false)));
+    let mut violations = Vec::new();
+    let columns = columns(ast);
+    if enabled("tab_order") {
+        let step = rules.pointer("/tab_order/step").and_then(Value::as_i64).unwrap_or(DEFAULT_TAB_STEP);
+        check_tab_order(ast, &columns, step, &mut violations);
+    }
+    if enabled("update") {
+        check_update(ast, &columns, &mut violations);
+    }
+    if enabled("edit_style") {
+        check_edit_style(ast, &mut violations);
+    }
+    if enabled("expressions") {
+        check_expressions(ast, &columns, &mut violations);
+    }
+    violations
+}
+
+fn violation(rule: &str, target: &str, message: String) -> Value {
+    json!({
+        "rule": rule,
+        "target": target,
+        "message": message
+    })
+}
+
+/// 列对象(非`compute`等)
+fn column_objects(ast: &DWSyntax) -> Vec<String> {
+    objects(ast, "")
+        .into_iter()
+        .filter(|name| describe_value(ast, &format!("{name}.Type")).eq_ignore_ascii_case("column"))
+        .collect()
+}
+
+fn check_tab_order(ast: &DWSyntax, columns: &[String], step: i64, violations: &mut Vec<Value>) {
+    let mut tabs: Vec<(i64, &String)> = columns
+        .iter()
+        .filter_map(|name| {
+            let tab: i64 = describe_value(ast, &format!("{name}.TabSequence")).parse().unwrap_or_default();
+            (tab > 0).then_some((tab, name))
+        })
+        .collect();
+    tabs.sort();
+    let mut expected = step;
+    for (idx, (tab, name)) in tabs.iter().enumerate() {
+        if idx > 0 && tabs[idx - 1].0 == *tab {
+            violations.push(violation(
+                "tab_order",
+                name,
+                format!("duplicate tab sequence {tab} with {}", tabs[idx - 1].1)
+            ));
+            continue;
+        }
+        if step > 0 && *tab != expected {
+            violations.push(violation("tab_order", name, format!("tab sequence {tab}, expected {expected}")));
+        }
+        expected = tab + step;
+    }
+}
+
+fn check_update(ast: &DWSyntax, columns: &[String], violations: &mut Vec<Value>) {
+    if describe_value(ast, "DataWindow.Table.UpdateTable").is_empty() {
+        violations.push(violation("update", "DataWindow", "update table is not set".to_owned()));
+        return;
+    }
+    let is_yes = |selector: String| describe_value(ast, &selector).eq_ignore_ascii_case("yes");
+    if !columns.iter().any(|name| is_yes(format!("{name}.Key"))) {
+        violations.push(violation("update", "DataWindow", "no key column".to_owned()));
+    }
+    if !columns.iter().any(|name| is_yes(format!("{name}.Update"))) {
+        violations.push(violation("update", "DataWindow", "no updatable column".to_owned()));
+    }
+}
+
+fn check_edit_style(ast: &DWSyntax, violations: &mut Vec<Value>) {
+    for name in column_objects(ast) {
+        let tab: i64 = describe_value(ast, &format!("{name}.TabSequence")).parse().unwrap_or_default();
+        if tab > 0 && describe_value(ast, &format!("{name}.Edit.Style")).is_empty() {
+            violations.push(violation("edit_style", &name, "editable column without edit style".to_owned()));
+        }
+    }
+}
+
+fn check_expressions(ast: &DWSyntax, columns: &[String], violations: &mut Vec<Value>) {
+    let root = match serde_json::to_value(ast) {
+        Ok(root) => root,
+        Err(_) => return
+    };
+    let mut names: Vec<String> = columns.to_vec();
+    names.extend(objects(ast, ""));
+    attr::walk("", &root, &mut |path, val| {
+        let text = match val.as_str() {
+            Some(text) => text,
+            None => return
+        };
+        //`compute`的表达式或`默认值~t表达式`格式的属性
+        let code = if path.to_ascii_lowercase().ends_with("/expression") {
+            text
+        } else if let Some((_, code)) = text.split_once("~t").or_else(|| text.split_once('\t')) {
+            code
+        } else {
+            return;
+        };
+        match expr::references(code) {
+            Ok(refs) => {
+                for name in refs {
+                    let known =
+                        name.starts_with('#') || names.iter().any(|known| known.eq_ignore_ascii_case(&name));
+                    if !known {
+                        violations.push(violation("expressions", path, format!("unknown reference: {name}")));
+                    }
+                }
+            },
+            Err(e) => violations.push(violation("expressions", path, format!("invalid expression: {e}")))
+        }
+    });
+}
@@ -0,0 +1,72 @@
+use serde_json::{json, Value};
+
+/// 合并对象
+///
+/// # Description
+///
+/// 在`source`中按名称查找对象(带有`name`字段的数组元素)，复制到`target`相同路径的数组中，
+/// 名称冲突时自动添加`_2`、`_3`等后缀
+///
+/// # Returns
+///
+/// 结果列表，成功时为`{"name": "t_1", "as": "t_1_2"}`，失败时为`{"name": "t_1", "error": "..."}`
+pub fn merge_objects(target: &mut Value, source: &Value, names: &[String]) -> Vec<Value> {
+    names
+        .iter()
+        .map(|name| {
+            match merge_object(target, source, name) {
+                Ok(new_name) => json!({ "name": name, "as": new_name }),
+                Err(e) => json!({ "name": name, "error": e })
+            }
+        })
+        .collect()
+}
+
+fn merge_object(target: &mut Value, source: &Value, name: &str) -> Result<String, String> {
+    let (pointer, item) = find_named(source, "", name).ok_or("object not found")?;
+    let mut item = item.clone();
+    //解决名称冲突
+    let mut new_name = name.to_owned();
+    let mut suffix = 2;
+    while find_named(target, "", &new_name).is_some() {
+        new_name = format!("{name}_{suffix}");
+        suffix += 1;
+    }
+    item["name"] = Value::String(new_name.clone());
+    match target.pointer_mut(&pointer) {
+        Some(Value::Array(items)) => {
+            items.push(item);
+            Ok(new_name)
+        },
+        _ => Err(format!("container not found: {pointer}"))
+    }
+}
+
+/// 查找指定名称的数组元素
+///
+/// # Returns
+///
+/// 所在数组的`JSON Pointer`和元素
+fn find_named<'a>(val: &'a Value, pointer: &str, name: &str) -> Option<(String, &'a Value)> {
+    match val {
+        Value::Object(map) => {
+            map.iter().find_map(|(key, val)| {
+                find_named(val, &format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1")), name)
+            })
+        },
+        Value::Array(items) => {
+            if let Some(item) = items.iter().find(|item| {
+                item.get("name")
+                    .and_then(Value::as_str)
+                    .map_or(false, |item_name| item_name.eq_ignore_ascii_case(name))
+            }) {
+                return Some((pointer.to_owned(), item));
+            }
+            items
+                .iter()
+                .enumerate()
+                .find_map(|(idx, item)| find_named(item, &format!("{pointer}/{idx}"), name))
+        },
+        _ => None
+    }
+}
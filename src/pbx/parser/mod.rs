@@ -1 +1,9 @@
 mod dw;
+mod diff;
+mod expr;
+mod attr;
+mod srd;
+mod gen;
+mod lint;
+mod merge;
+mod csv;
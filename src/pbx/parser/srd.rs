@@ -0,0 +1,75 @@
+use crate::base::conv;
+use std::{fs, io, path::Path};
+
+/// `HexASCII`编码起始标记，`$$HEX{n}$$`后跟随`n`个`UTF-16LE`字符的十六进制编码
+const HEX_BEGIN: &str = "$$HEX";
+/// `HexASCII`编码结束标记
+const HEX_END: &str = "$$ENDHEX$$";
+
+/// 读取`.srd`导出文件
+///
+/// # Description
+///
+/// - 根据`BOM`或内容检测编码
+/// - 解码`HexASCII`格式(`HA`开头的导出文件)
+/// - 去除`$PBExportHeader$`和`$PBExportComments$`导出头
+pub fn read_file(path: impl AsRef<Path>) -> io::Result<String> {
+    let data = fs::read(path)?;
+    let text = conv::decode_auto(&data);
+    Ok(strip_header(&decode_hex_ascii(&text)?).to_owned())
+}
+
+/// 解码`$$HEX{n}$$...$$ENDHEX$$`片段
+///
+/// # Errors
+///
+/// 片段格式无效时返回`InvalidData`
+fn decode_hex_ascii(text: &str) -> io::Result<String> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+    let mut rv = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(HEX_BEGIN) {
+        rv.push_str(&rest[..start]);
+        let seg = &rest[start + HEX_BEGIN.len()..];
+        let count_end = seg.find("$$").ok_or_else(|| invalid("unterminated hex length"))?;
+        let count: usize = seg[..count_end].parse().map_err(|_| invalid("invalid hex length"))?;
+        let hex_len = count.checked_mul(4).ok_or_else(|| invalid("hex length overflow"))?;
+        let hex = seg
+            .as_bytes()
+            .get(count_end + 2..)
+            .and_then(|hex| hex.get(..hex_len))
+            .ok_or_else(|| invalid("truncated hex data"))?;
+        //编码中包含非ASCII字符时结束位置可能不是字符边界
+        let tail = seg
+            .get(count_end + 2 + hex_len..)
+            .and_then(|tail| tail.strip_prefix(HEX_END))
+            .ok_or_else(|| invalid("missing $$ENDHEX$$"))?;
+        let bytes = std::str::from_utf8(hex)
+            .ok()
+            .and_then(conv::hex_decode)
+            .filter(|bytes| bytes.len() == count * 2)
+            .ok_or_else(|| invalid("invalid hex digit"))?;
+        //低字节在前
+        let units: Vec<u16> =
+            bytes.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+        rv.push_str(&String::from_utf16_lossy(&units));
+        rest = tail;
+    }
+    rv.push_str(rest);
+    Ok(rv)
+}
+
+/// 去除导出头
+fn strip_header(text: &str) -> &str {
+    let mut rest = text;
+    loop {
+        let line_end = rest.find('\n').map_or(rest.len(), |idx| idx + 1);
+        let line = rest[..line_end].trim_start();
+        let line = line.strip_prefix("HA").unwrap_or(line);
+        if line.starts_with("$PBExportHeader$") || line.starts_with("$PBExportComments$") {
+            rest = &rest[line_end..];
+        } else {
+            break rest;
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::{
+    base::{fs, secret}, prelude::*
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use pbni::pbx::*;
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, io, path::Path};
+
+/// 凭据管理器中的默认名称前缀
+const DEFAULT_VAULT_PREFIX: &str = "pfwx:";
+
+/// 存储位置
+#[derive(Default)]
+enum Store {
+    #[default]
+    None,
+    /// `DPAPI`加密后保存到`JSON`文件
+    File {
+        path: String,
+        entries: BTreeMap<String, String>
+    },
+    /// `Windows`凭据管理器
+    Vault {
+        prefix: String
+    }
+}
+
+#[derive(Default)]
+struct Secrets {
+    store: Store,
+    /// 使用计算机范围的`DPAPI`
+    machine: bool,
+    entropy: Vec<u8>,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_secrets")]
+impl Secrets {
+    /// 打开文件存储
+    ///
+    /// # Parameters
+    ///
+    /// - `path` 文件路径，不存在时在第一次`Set`时创建
+    ///
+    /// # Description
+    ///
+    /// 每个机密使用`DPAPI`单独加密后以`Base64`保存，文件本身可以随意复制但只能在加密时的用户(或计算机)下解密
+    #[method(name = "Open")]
+    fn open(&mut self, path: String) -> RetCode {
+        let entries = match std::fs::read(&path) {
+            Ok(data) => {
+                match serde_json::from_slice::<Map<String, Value>>(&data) {
+                    Ok(map) => {
+                        map.into_iter()
+                            .filter_map(|(name, value)| value.as_str().map(|value| (name, value.to_owned())))
+                            .collect()
+                    },
+                    Err(e) => return self.error.set(RetCode::E_INVALID_DATA, e)
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                return self.error.set(RetCode::E_ACCESS_DENIED, e);
+            },
+            Err(e) => return self.error.set(RetCode::E_IO_ERROR, e)
+        };
+        self.store = Store::File {
+            path,
+            entries
+        };
+        self.error.clear();
+        RetCode::OK
+    }
+
+    /// 使用`Windows`凭据管理器存储
+    ///
+    /// # Parameters
+    ///
+    /// - `prefix` 凭据名称前缀，默认`pfwx:`，实际名称为`前缀 + 机密名称`
+    ///
+    /// # Description
+    ///
+    /// 凭据保存在当前用户的普通凭据中，可在控制面板中查看和管理，不受`SetScope`影响
+    #[method(name = "UseCredentialManager", overload = 1)]
+    fn use_credential_manager(&mut self, prefix: Option<String>) -> RetCode {
+        self.store = Store::Vault {
+            prefix: prefix.unwrap_or_else(|| DEFAULT_VAULT_PREFIX.to_owned())
+        };
+        RetCode::OK
+    }
+
+    /// 设置`DPAPI`加密范围
+    ///
+    /// # Parameters
+    ///
+    /// - `machine` `true`本机所有用户都可以解密(适用于服务)，`false`只有当前用户可以解密(默认)
+    #[method(name = "SetScope")]
+    fn set_scope(&mut self, machine: bool) -> RetCode {
+        self.machine = machine;
+        RetCode::OK
+    }
+
+    /// 设置`DPAPI`附加熵
+    ///
+    /// # Description
+    ///
+    /// 设置后解密时必须使用相同的值，可避免同一用户下的其它程序直接解密
+    #[method(name = "SetEntropy")]
+    fn set_entropy(&mut self, entropy: String) -> RetCode {
+        self.entropy = entropy.into_bytes();
+        RetCode::OK
+    }
+
+    /// 保存机密，已存在时覆盖
+    #[method(name = "Set")]
+    fn set(&mut self, name: String, value: String) -> RetCode {
+        if name.is_empty() {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "name is empty");
+        }
+        match &mut self.store {
+            Store::None => self.error.set(RetCode::E_INVALID_OBJECT, "store not opened"),
+            Store::File {
+                path,
+                entries
+            } => {
+                let data = match secret::protect(value.as_bytes(), &self.entropy, self.machine) {
+                    Ok(data) => data,
+                    Err(e) => return self.error.set(RetCode::E_WIN32_ERROR, e)
+                };
+                let old = entries.insert(name.clone(), BASE64.encode(data));
+                let rv = save_file(path, entries, &mut self.error);
+                //保存失败时恢复
+                if rv != RetCode::OK {
+                    match old {
+                        Some(old) => entries.insert(name, old),
+                        None => entries.remove(&name)
+                    };
+                }
+                rv
+            },
+            Store::Vault {
+                prefix
+            } => {
+                match secret::write_credential(&format!("{prefix}{name}"), &name, value.as_bytes()) {
+                    Ok(_) => RetCode::OK,
+                    Err(e) => self.error.set(RetCode::E_WIN32_ERROR, e)
+                }
+            },
+        }
+    }
+
+    /// 读取机密
+    ///
+    /// # Parameters
+    ///
+    /// - `default` 不存在或无法解密时的返回值，默认空字符串
+    #[method(name = "Get", overload = 1)]
+    fn get(&self, name: String, default: Option<String>) -> String {
+        self.read(&name).unwrap_or_else(|| default.unwrap_or_default())
+    }
+
+    /// 是否存在指定的机密
+    #[method(name = "Exists")]
+    fn exists(&self, name: String) -> bool {
+        match &self.store {
+            Store::None => false,
+            Store::File {
+                entries,
+                ..
+            } => entries.contains_key(&name),
+            Store::Vault {
+                prefix
+            } => matches!(secret::read_credential(&format!("{prefix}{name}")), Ok(Some(_)))
+        }
+    }
+
+    /// 删除机密
+    #[method(name = "Remove")]
+    fn remove(&mut self, name: String) -> RetCode {
+        match &mut self.store {
+            Store::None => self.error.set(RetCode::E_INVALID_OBJECT, "store not opened"),
+            Store::File {
+                path,
+                entries
+            } => {
+                let Some(old) = entries.remove(&name) else {
+                    return self.error.set(RetCode::E_DATA_NOT_FOUND, format!("secret not found: {name}"));
+                };
+                let rv = save_file(path, entries, &mut self.error);
+                if rv != RetCode::OK {
+                    entries.insert(name, old);
+                }
+                rv
+            },
+            Store::Vault {
+                prefix
+            } => {
+                match secret::delete_credential(&format!("{prefix}{name}")) {
+                    Ok(true) => RetCode::OK,
+                    Ok(false) => {
+                        self.error.set(RetCode::E_DATA_NOT_FOUND, format!("secret not found: {name}"))
+                    },
+                    Err(e) => self.error.set(RetCode::E_WIN32_ERROR, e)
+                }
+            },
+        }
+    }
+
+    /// 所有机密的名称
+    #[method(name = "GetNames")]
+    fn get_names(&self) -> Vec<String> {
+        match &self.store {
+            Store::None => Vec::new(),
+            Store::File {
+                entries,
+                ..
+            } => entries.keys().cloned().collect(),
+            Store::Vault {
+                prefix
+            } => {
+                secret::enum_credentials(&format!("{prefix}*"))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|target| target.strip_prefix(prefix.as_str()).map(ToOwned::to_owned))
+                    .collect()
+            },
+        }
+    }
+
+    /// 使用`DPAPI`加密字符串
+    ///
+    /// # Returns
+    ///
+    /// `Base64`格式的密文，失败时返回空字符串
+    #[method(name = "Protect")]
+    fn protect(&self, data: String) -> String {
+        secret::protect(data.as_bytes(), &self.entropy, self.machine)
+            .map(|data| BASE64.encode(data))
+            .unwrap_or_default()
+    }
+
+    /// 使用`DPAPI`解密`Protect`的结果
+    ///
+    /// # Returns
+    ///
+    /// 失败时返回空字符串
+    #[method(name = "Unprotect")]
+    fn unprotect(&self, data: String) -> String { self.decrypt(&data).unwrap_or_default() }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Secrets {
+    fn read(&self, name: &str) -> Option<String> {
+        match &self.store {
+            Store::None => None,
+            Store::File {
+                entries,
+                ..
+            } => self.decrypt(entries.get(name)?),
+            Store::Vault {
+                prefix
+            } => {
+                secret::read_credential(&format!("{prefix}{name}"))
+                    .ok()
+                    .flatten()
+                    .map(|cred| cred.secret_string())
+            },
+        }
+    }
+
+    fn decrypt(&self, data: &str) -> Option<String> {
+        let data = BASE64.decode(data.trim()).ok()?;
+        let data = secret::unprotect(&data, &self.entropy).ok()?;
+        String::from_utf8(data).ok()
+    }
+}
+
+/// 保存文件存储
+///
+/// 先写入临时文件再替换，避免写入中断导致已有的机密丢失
+fn save_file(path: &str, entries: &BTreeMap<String, String>, error: &mut LastError) -> RetCode {
+    let map: Map<String, Value> =
+        entries.iter().map(|(name, value)| (name.clone(), Value::from(value.as_str()))).collect();
+    let data = match serde_json::to_vec_pretty(&Value::Object(map)) {
+        Ok(data) => data,
+        Err(e) => return error.set(RetCode::FAILED, e)
+    };
+    let tmp_path = format!("{path}.tmp");
+    let rv = fs::create_file_dir_all(path)
+        .and_then(|_| std::fs::write(&tmp_path, data))
+        .and_then(|_| std::fs::rename(&tmp_path, Path::new(path)));
+    match rv {
+        Ok(_) => RetCode::OK,
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            let code = if e.kind() == io::ErrorKind::PermissionDenied {
+                RetCode::E_ACCESS_DENIED
+            } else {
+                RetCode::E_IO_ERROR
+            };
+            error.set(code, e)
+        }
+    }
+}
@@ -0,0 +1,6 @@
+mod timer;
+mod process;
+mod watchdog;
+mod scheduler;
+
+pub(crate) use timer::post_delayed;
@@ -0,0 +1,283 @@
+use crate::{base::conv, prelude::*};
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::{process::Stdio, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader}, process::{Child, ChildStdin, Command}, sync::{mpsc, oneshot}, time
+};
+
+/// 不创建控制台窗口
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+struct Process {
+    state: HandlerState,
+    working_dir: Option<String>,
+    envs: Vec<(String, String)>,
+    encoding: pblong,
+    running: Option<Running>,
+    error: LastError
+}
+
+/// 运行中的进程
+struct Running {
+    pid: u32,
+    input_tx: Option<mpsc::UnboundedSender<Vec<u8>>>,
+    kill_tx: Option<oneshot::Sender<()>>,
+    cancel_hdl: CancelHandle
+}
+
+#[nonvisualobject(name = "nx_process")]
+impl Process {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Process {
+            state: HandlerState::new(session),
+            working_dir: None,
+            envs: Vec::new(),
+            encoding: conv::ENCODING_UTF8,
+            running: None,
+            error: LastError::default()
+        }
+    }
+
+    #[method(name = "SetWorkingDir")]
+    fn working_dir(&mut self, dir: String) -> &mut Self {
+        self.working_dir = Some(dir);
+        self
+    }
+
+    #[method(name = "SetEnv")]
+    fn env(&mut self, key: String, val: String) -> &mut Self {
+        self.envs.push((key, val));
+        self
+    }
+
+    /// 设置标准输入输出的文本编码，默认`utf-8`
+    #[method(name = "SetEncoding")]
+    fn encoding(&mut self, encoding: pblong) -> &mut Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// 启动进程
+    ///
+    /// # Parameters
+    ///
+    /// - `program` 程序路径
+    /// - `args` 命令行参数(原样传递)
+    /// - `timeout` 超时(秒)，超时后结束进程
+    #[method(name = "Start", overload = 2)]
+    fn start(&mut self, program: String, args: Option<String>, timeout: Option<pbdouble>) -> RetCode {
+        if self.running.is_some() {
+            return RetCode::E_BUSY;
+        }
+        let mut cmd = Command::new(program);
+        if let Some(args) = args {
+            cmd.raw_arg(args);
+        }
+        if let Some(dir) = self.working_dir.as_ref() {
+            cmd.current_dir(dir);
+        }
+        cmd.envs(self.envs.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .creation_flags(CREATE_NO_WINDOW)
+            .kill_on_drop(true);
+        //NOTE 管道需要在运行时中创建
+        let mut child = match self.spawn_blocking(async move { cmd.spawn() }).unwrap() {
+            Ok(child) => child,
+            Err(e) => {
+                let code = match e.kind() {
+                    std::io::ErrorKind::NotFound => RetCode::E_FILE_NOT_FOUND,
+                    std::io::ErrorKind::PermissionDenied => RetCode::E_ACCESS_DENIED,
+                    _ => RetCode::E_IO_ERROR
+                };
+                return self.error.set(code, e);
+            }
+        };
+        self.error.clear();
+        let pid = child.id().unwrap_or_default();
+        let encoding = self.encoding;
+        let timeout = timeout.filter(|secs| *secs > 0.0).map(Duration::from_secs_f64);
+
+        //标准输入
+        let (input_tx, input_rx) = mpsc::unbounded_channel();
+        runtime::spawn(Self::write_input(child.stdin.take().unwrap(), input_rx));
+
+        //标准输出
+        let invoker = self.invoker();
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        let (kill_tx, kill_rx) = oneshot::channel();
+        let cancel_hdl = self.spawn(
+            async move {
+                let (exit, ..) = tokio::join!(
+                    Self::wait(child, kill_rx, timeout),
+                    Self::read_output(stdout, false, encoding, invoker.clone()),
+                    Self::read_output(stderr, true, encoding, invoker)
+                );
+                exit
+            },
+            |this, (code, timed_out)| {
+                this.running = None;
+                this.on_exit(code, timed_out);
+            }
+        );
+
+        self.running = Some(Running {
+            pid,
+            input_tx: Some(input_tx),
+            kill_tx: Some(kill_tx),
+            cancel_hdl
+        });
+        RetCode::OK
+    }
+
+    /// 写入标准输入
+    #[method(name = "Write")]
+    fn write(&mut self, data: String) -> RetCode {
+        let data = conv::encode(&data, self.encoding).into_owned();
+        self.write_binary(&data)
+    }
+
+    /// 写入一行到标准输入
+    #[method(name = "WriteLine")]
+    fn write_line(&mut self, data: String) -> RetCode { self.write(data + "\r\n") }
+
+    /// 写入二进制数据到标准输入
+    #[method(name = "Write")]
+    fn write_binary(&mut self, data: &[u8]) -> RetCode {
+        if let Some(input_tx) = self.running.as_ref().and_then(|running| running.input_tx.as_ref()) {
+            if input_tx.send(data.to_owned()).is_ok() {
+                RetCode::OK
+            } else {
+                self.error.set(RetCode::E_IO_ERROR, "standard input closed")
+            }
+        } else {
+            self.error.set(RetCode::E_INVALID_HANDLE, "process not running")
+        }
+    }
+
+    /// 关闭标准输入
+    #[method(name = "CloseInput")]
+    fn close_input(&mut self) -> RetCode {
+        if let Some(running) = self.running.as_mut() {
+            running.input_tx = None;
+            RetCode::OK
+        } else {
+            self.error.set(RetCode::E_INVALID_HANDLE, "process not running")
+        }
+    }
+
+    /// 结束进程
+    ///
+    /// # Description
+    ///
+    /// 进程结束后触发`OnExit`事件
+    #[method(name = "Kill")]
+    fn kill(&mut self) -> RetCode {
+        if let Some(kill_tx) = self.running.as_mut().and_then(|running| running.kill_tx.take()) {
+            let _ = kill_tx.send(());
+            RetCode::OK
+        } else {
+            self.error.set(RetCode::E_INVALID_HANDLE, "process not running")
+        }
+    }
+
+    /// 结束进程并且不再触发事件
+    #[method(name = "Abort")]
+    fn abort(&mut self) -> RetCode {
+        if let Some(running) = self.running.take() {
+            //取消任务时销毁`Child`对象会结束进程
+            running.cancel_hdl.cancel();
+            RetCode::OK
+        } else {
+            self.error.set(RetCode::E_INVALID_HANDLE, "process not running")
+        }
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.running.is_some() }
+
+    #[method(name = "GetPid")]
+    fn pid(&self) -> pbulong { self.running.as_ref().map(|running| running.pid).unwrap_or_default() }
+
+    /// 等待进程结束
+    ///
+    /// # Returns
+    ///
+    /// `(退出码, 是否超时)`
+    async fn wait(
+        mut child: Child,
+        kill_rx: oneshot::Receiver<()>,
+        timeout: Option<Duration>
+    ) -> (pblong, bool) {
+        let timeout = async move {
+            match timeout {
+                Some(dur) => time::sleep(dur).await,
+                None => futures_util::future::pending().await
+            }
+        };
+        let timed_out = tokio::select! {
+            rv = child.wait() => {
+                return (rv.ok().and_then(|status| status.code()).unwrap_or(-1), false);
+            },
+            _ = kill_rx => false,
+            _ = timeout => true
+        };
+        let _ = child.kill().await;
+        (child.wait().await.ok().and_then(|status| status.code()).unwrap_or(-1), timed_out)
+    }
+
+    /// 逐行读取输出并通知对象
+    async fn read_output(
+        reader: impl AsyncRead + Unpin,
+        is_stderr: bool,
+        encoding: pblong,
+        invoker: HandlerInvoker<Process>
+    ) {
+        let mut lines = BufReader::new(reader).split(b'\n');
+        while let Ok(Some(mut line)) = lines.next_segment().await {
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            let line = conv::decode(&line, encoding).into_owned();
+            let rv = invoker
+                .invoke((line, is_stderr), |this, (line, is_stderr)| this.on_output(line, is_stderr))
+                .await
+                .await;
+            if let Err(InvokeError::TargetIsDead) = rv {
+                break;
+            }
+        }
+    }
+
+    /// 写入标准输入
+    async fn write_input(mut stdin: ChildStdin, mut input_rx: mpsc::UnboundedReceiver<Vec<u8>>) {
+        while let Some(data) = input_rx.recv().await {
+            if stdin.write_all(&data).await.is_err() || stdin.flush().await.is_err() {
+                break;
+            }
+        }
+    }
+
+    #[event(name = "OnOutput")]
+    fn on_output(&mut self, line: String, is_stderr: bool) {}
+
+    #[event(name = "OnExit")]
+    fn on_exit(&mut self, code: pblong, timed_out: bool) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Handler for Process {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
@@ -0,0 +1,280 @@
+use crate::{base::fs::create_file_dir_all, prelude::*};
+use chrono::{DateTime, Local, TimeZone};
+use cron::Schedule;
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::{collections::HashMap, fs, path::PathBuf, str::FromStr};
+use tokio::time;
+
+struct Scheduler {
+    state: HandlerState,
+    jobs: HashMap<String, Job>,
+    persist_file: Option<PathBuf>,
+    //持久化的下次执行时间(UNIX时间戳)
+    persisted: HashMap<String, i64>,
+    error: LastError
+}
+
+/// 计划任务
+struct Job {
+    schedule: JobSchedule,
+    next_run: DateTime<Local>,
+    cancel_hdl: Option<CancelHandle>
+}
+
+/// 执行计划
+enum JobSchedule {
+    Cron(Box<Schedule>),
+    Interval(chrono::Duration)
+}
+
+impl JobSchedule {
+    /// 计算指定时间之后的下次执行时间
+    fn next_after(&self, after: &DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            JobSchedule::Cron(schedule) => schedule.after(after).next(),
+            JobSchedule::Interval(interval) => Some(*after + *interval)
+        }
+    }
+}
+
+#[nonvisualobject(name = "nx_scheduler")]
+impl Scheduler {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Scheduler {
+            state: HandlerState::new(session),
+            jobs: HashMap::new(),
+            persist_file: None,
+            persisted: HashMap::new(),
+            error: LastError::default()
+        }
+    }
+
+    /// 设置持久化文件
+    ///
+    /// # Description
+    ///
+    /// 保存每个任务的下次执行时间，之后添加的同名任务从保存的时间继续执行，错过的执行会立即补发一次
+    #[method(name = "SetPersistFile")]
+    fn set_persist_file(&mut self, path: String) -> RetCode {
+        let path = PathBuf::from(path);
+        self.persisted.clear();
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                for line in content.lines() {
+                    if let Some((job_id, ts)) = line.rsplit_once('\t') {
+                        if let Ok(ts) = ts.trim().parse() {
+                            self.persisted.insert(job_id.to_owned(), ts);
+                        }
+                    }
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+            Err(e) => return self.error.set(RetCode::E_IO_ERROR, e)
+        }
+        self.persist_file = Some(path);
+        RetCode::OK
+    }
+
+    /// 添加`cron`表达式计划的任务
+    ///
+    /// # Parameters
+    ///
+    /// - `job_id` 任务ID，已存在时替换
+    /// - `expr` `cron`表达式，支持`分 时 日 月 周`5段格式和包含秒的6/7段格式
+    #[method(name = "AddCron")]
+    fn add_cron(&mut self, job_id: String, expr: String) -> RetCode {
+        let expr = expr.trim();
+        //兼容不含秒的5段格式
+        let expr = if expr.split_whitespace().count() == 5 {
+            format!("0 {expr}")
+        } else {
+            expr.to_owned()
+        };
+        match Schedule::from_str(&expr) {
+            Ok(schedule) => self.add_job(job_id, JobSchedule::Cron(Box::new(schedule))),
+            Err(e) => self.error.set(RetCode::E_INVALID_ARGUMENT, e)
+        }
+    }
+
+    /// 添加固定间隔的任务
+    ///
+    /// # Parameters
+    ///
+    /// - `job_id` 任务ID，已存在时替换
+    /// - `interval` 间隔(秒)
+    #[method(name = "AddInterval")]
+    fn add_interval(&mut self, job_id: String, interval: pbulong) -> RetCode {
+        if interval == 0 {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "interval must be greater than 0");
+        }
+        self.add_job(job_id, JobSchedule::Interval(chrono::Duration::seconds(interval as i64)))
+    }
+
+    #[method(name = "Remove")]
+    fn remove(&mut self, job_id: String) -> RetCode {
+        if let Some(job) = self.jobs.remove(&job_id) {
+            if let Some(cancel_hdl) = job.cancel_hdl {
+                cancel_hdl.cancel();
+            }
+            self.persisted.remove(&job_id);
+            self.save();
+            RetCode::OK
+        } else {
+            RetCode::E_DATA_NOT_FOUND
+        }
+    }
+
+    #[method(name = "RemoveAll")]
+    fn remove_all(&mut self) -> RetCode {
+        for (job_id, job) in self.jobs.drain() {
+            if let Some(cancel_hdl) = job.cancel_hdl {
+                cancel_hdl.cancel();
+            }
+            self.persisted.remove(&job_id);
+        }
+        self.save();
+        RetCode::OK
+    }
+
+    #[method(name = "Exists")]
+    fn exists(&self, job_id: String) -> bool { self.jobs.contains_key(&job_id) }
+
+    #[method(name = "GetJobs")]
+    fn jobs(&self) -> Vec<String> { self.jobs.keys().cloned().collect() }
+
+    /// 下次执行时间
+    ///
+    /// # Returns
+    ///
+    /// `yyyy-mm-dd hh:mm:ss`格式，任务不存在时返回空字符串
+    #[method(name = "GetNextRun")]
+    fn next_run(&self, job_id: String) -> String {
+        self.jobs
+            .get(&job_id)
+            .map(|job| job.next_run.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default()
+    }
+
+    /// 添加任务
+    fn add_job(&mut self, job_id: String, schedule: JobSchedule) -> RetCode {
+        if let Some(job) = self.jobs.remove(&job_id) {
+            if let Some(cancel_hdl) = job.cancel_hdl {
+                cancel_hdl.cancel();
+            }
+        }
+        //优先使用持久化的下次执行时间
+        let next_run = match self.persisted.get(&job_id).and_then(|ts| Local.timestamp_opt(*ts, 0).single()) {
+            Some(next_run) => next_run,
+            None => {
+                match schedule.next_after(&Local::now()) {
+                    Some(next_run) => next_run,
+                    None => {
+                        return self.error.set(RetCode::E_INVALID_ARGUMENT, "schedule has no upcoming time")
+                    },
+                }
+            },
+        };
+        self.jobs.insert(job_id.clone(), Job {
+            schedule,
+            next_run,
+            cancel_hdl: None
+        });
+        self.arm(&job_id);
+        self.save();
+        RetCode::OK
+    }
+
+    /// 等待任务的下次执行时间
+    fn arm(&mut self, job_id: &str) {
+        let next_run = match self.jobs.get(job_id) {
+            Some(job) => job.next_run,
+            None => return
+        };
+        let id = job_id.to_owned();
+        let cancel_hdl = self.spawn(
+            async move {
+                //分段等待，系统时间调整后仍能按时触发
+                loop {
+                    let remaining = (next_run - Local::now()).to_std().unwrap_or_default();
+                    if remaining.is_zero() {
+                        break;
+                    }
+                    time::sleep(remaining.min(default::MAX_SLEEP)).await;
+                }
+            },
+            move |this, ()| this.job_due(id)
+        );
+        if let Some(job) = self.jobs.get_mut(job_id) {
+            job.cancel_hdl = Some(cancel_hdl);
+        }
+    }
+
+    /// 任务到期
+    fn job_due(&mut self, job_id: String) {
+        let job = match self.jobs.get_mut(&job_id) {
+            Some(job) => job,
+            None => return
+        };
+        job.cancel_hdl = None;
+        //先安排下次执行，事件中可能删除或替换任务
+        let after = job.next_run.max(Local::now());
+        match job.schedule.next_after(&after) {
+            Some(next_run) => {
+                job.next_run = next_run;
+                self.arm(&job_id);
+            },
+            None => {
+                self.jobs.remove(&job_id);
+                self.persisted.remove(&job_id);
+            }
+        }
+        self.save();
+        self.on_job_due(job_id);
+    }
+
+    /// 保存下次执行时间
+    fn save(&mut self) {
+        let path = match self.persist_file.as_ref() {
+            Some(path) => path,
+            None => return
+        };
+        for (job_id, job) in self.jobs.iter() {
+            self.persisted.insert(job_id.clone(), job.next_run.timestamp());
+        }
+        let mut content = String::new();
+        for (job_id, ts) in self.persisted.iter() {
+            content.push_str(&format!("{job_id}\t{ts}\r\n"));
+        }
+        let _ = create_file_dir_all(path);
+        if let Err(e) = fs::write(path, content) {
+            self.error.set(RetCode::E_IO_ERROR, e);
+        }
+    }
+
+    /// 任务到期事件
+    #[event(name = "OnJobDue")]
+    fn on_job_due(&mut self, job_id: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Handler for Scheduler {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
+
+/// 默认配置
+mod default {
+    use std::time::Duration;
+
+    /// 单次等待的最长时间
+    pub const MAX_SLEEP: Duration = Duration::from_secs(30);
+}
@@ -0,0 +1,183 @@
+use crate::prelude::*;
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::time::Duration;
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+/// 延迟触发的任务标签前缀，与用户标签区分
+const AFTER_TAG_PREFIX: &str = "after:";
+
+struct Timer {
+    state: HandlerState,
+    cancel_hdl: Option<CancelHandle>,
+    timer_id: u64,
+    count: pbulong,
+    error: LastError
+}
+
+/// 延迟触发指定对象的`OnDelayed`事件
+///
+/// # Description
+///
+/// 创建一个临时的定时器对象执行等待，触发事件后释放
+pub(crate) fn post_delayed(target: &Object, delay: pbulong, tag: String) {
+    let target = target.share();
+    Timer::new_object_modify(target.get_session(), move |timer| {
+        //等待期间保持对象存活
+        let this = timer.get_object().share();
+        timer.spawn(time::sleep(Duration::from_millis(delay as u64)), move |_, ()| {
+            let mut target = target;
+            let _ = target.trigger_event("OnDelayed", pbargs![tag.as_str()]);
+            drop(this);
+        });
+    });
+}
+
+#[nonvisualobject(name = "nx_timer")]
+impl Timer {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Timer {
+            state: HandlerState::new(session),
+            cancel_hdl: None,
+            timer_id: 0,
+            count: 0,
+            error: LastError::default()
+        }
+    }
+
+    /// 启动定时器
+    ///
+    /// # Parameters
+    ///
+    /// - `interval` 间隔(毫秒)
+    /// - `repeat` 是否重复触发，默认`true`
+    ///
+    /// # Description
+    ///
+    /// 按启动时刻对齐的固定节拍触发，事件处理耗时不会累计漂移，错过的节拍直接跳过
+    #[method(name = "Start", overload = 1)]
+    fn start(&mut self, interval: pbulong, repeat: Option<bool>) -> RetCode {
+        if interval == 0 {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "interval must be greater than 0");
+        }
+        self.stop();
+        self.timer_id += 1;
+        self.count = 0;
+        let timer_id = self.timer_id;
+        let repeat = repeat.unwrap_or(true);
+        let interval = Duration::from_millis(interval as u64);
+        let invoker = self.invoker();
+        let cancel_hdl = self.spawn(
+            async move {
+                let mut ticker = time::interval_at(Instant::now() + interval, interval);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                loop {
+                    ticker.tick().await;
+                    match invoker.invoke(timer_id, |this, timer_id| this.tick(timer_id)).await.await {
+                        Ok(true) | Err(InvokeError::Dropped) if repeat => {},
+                        _ => break
+                    }
+                }
+            },
+            move |this, ()| {
+                if this.timer_id == timer_id {
+                    this.cancel_hdl = None;
+                }
+            }
+        );
+        self.cancel_hdl = Some(cancel_hdl);
+        RetCode::OK
+    }
+
+    /// 停止定时器
+    #[method(name = "Stop")]
+    fn stop(&mut self) -> RetCode {
+        if let Some(cancel_hdl) = self.cancel_hdl.take() {
+            cancel_hdl.cancel();
+        }
+        RetCode::OK
+    }
+
+    /// 延迟触发一次`OnDelayed`事件
+    ///
+    /// # Parameters
+    ///
+    /// - `delay` 延迟(毫秒)
+    /// - `tag` 事件参数，可通过`CancelAfter`取消相同标签的等待
+    ///
+    /// # Description
+    ///
+    /// 与`Start`启动的定时器互不影响，可以同时等待多个
+    #[method(name = "After", overload = 1)]
+    fn after(&mut self, delay: pbulong, tag: Option<String>) -> RetCode {
+        let tag = tag.unwrap_or_default();
+        self.spawn_tagged(
+            format!("{AFTER_TAG_PREFIX}{tag}"),
+            time::sleep(Duration::from_millis(delay as u64)),
+            move |this, ()| this.on_delayed(tag)
+        );
+        RetCode::OK
+    }
+
+    /// 取消`After`的等待
+    ///
+    /// # Parameters
+    ///
+    /// - `tag` 标签，默认为空标签
+    #[method(name = "CancelAfter", overload = 1)]
+    fn cancel_after(&mut self, tag: Option<String>) -> RetCode {
+        let tag = tag.unwrap_or_default();
+        if self.state.cancel_tag(&format!("{AFTER_TAG_PREFIX}{tag}")) == 0 {
+            RetCode::E_DATA_NOT_FOUND
+        } else {
+            RetCode::OK
+        }
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.cancel_hdl.is_some() }
+
+    #[method(name = "GetCount")]
+    fn count(&self) -> pbulong { self.count }
+
+    /// 触发事件
+    ///
+    /// # Returns
+    ///
+    /// 定时器是否继续运行
+    fn tick(&mut self, timer_id: u64) -> bool {
+        if self.timer_id != timer_id {
+            return false;
+        }
+        self.count += 1;
+        if self.on_tick(self.count) == RetCode::PREVENT {
+            self.stop();
+        }
+        //事件中可能调用了`Stop`或`Start`
+        self.timer_id == timer_id && self.cancel_hdl.is_some()
+    }
+
+    /// 定时触发事件
+    ///
+    /// 返回`PREVENT`停止定时器
+    #[event(name = "OnTick")]
+    fn on_tick(&mut self, count: pbulong) -> RetCode {}
+
+    /// 延迟触发事件
+    #[event(name = "OnDelayed")]
+    fn on_delayed(&mut self, tag: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Handler for Timer {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
@@ -0,0 +1,87 @@
+use crate::prelude::*;
+use pbni::{pbx::*, prelude::*};
+use reactor::*;
+use std::time::Duration;
+
+struct Watchdog {
+    state: HandlerState,
+    //已注册的阻塞报告处理过程
+    stall_id: Option<u64>,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_watchdog")]
+impl Watchdog {
+    #[constructor]
+    fn new(session: Session, _object: Object) -> Self {
+        Watchdog {
+            state: HandlerState::new(session),
+            stall_id: None,
+            error: LastError::default()
+        }
+    }
+
+    /// 启动UI线程阻塞监视
+    ///
+    /// # Parameters
+    ///
+    /// - `threshold` 回调消息等待处理的时长超过此阈值(毫秒)时触发`OnStall`事件
+    ///
+    /// # Description
+    ///
+    /// 阻塞阈值对所有线程生效，同一线程只有最后启动的对象接收事件
+    #[method(name = "Start")]
+    fn start(&mut self, threshold: pbulong) -> RetCode {
+        if threshold == 0 {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, "threshold must be greater than 0");
+        }
+        reactor::set_watchdog(Some(Duration::from_millis(threshold as u64)));
+        let stall_id = self.on_stall(|this, report| {
+            this.on_stall_event(
+                report.waited.as_millis() as pbulong,
+                report.pending as pbulong,
+                report.backtrace.clone()
+            );
+        });
+        self.stall_id = Some(stall_id);
+        RetCode::OK
+    }
+
+    /// 停止UI线程阻塞监视
+    #[method(name = "Stop")]
+    fn stop(&mut self) -> RetCode {
+        //同一线程的其它对象已接管时保留其监视
+        if let Some(stall_id) = self.stall_id.take() {
+            if reactor::clear_stall_handler(stall_id) {
+                reactor::set_watchdog(None);
+            }
+        }
+        RetCode::OK
+    }
+
+    #[method(name = "IsRunning")]
+    fn is_running(&self) -> bool { self.stall_id.is_some() }
+
+    /// UI线程阻塞事件
+    ///
+    /// # Parameters
+    ///
+    /// - `waited` 回调消息等待处理的时长(毫秒)
+    /// - `pending` 积压的回调消息数量
+    /// - `backtrace` UI线程恢复处理消息时的调用栈
+    #[event(name = "OnStall")]
+    fn on_stall_event(&mut self, waited: pbulong, pending: pbulong, backtrace: String) {}
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Handler for Watchdog {
+    fn state(&self) -> &HandlerState { &self.state }
+    fn alive_state(&self) -> AliveState { self.get_alive_state() }
+}
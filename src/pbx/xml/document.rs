@@ -0,0 +1,525 @@
+use crate::prelude::*;
+use pbni::pbx::*;
+use quick_xml::{
+    escape::escape, events::{BytesStart, Event}, Reader
+};
+
+/// `XML`声明
+const DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+
+/// 内置的`XML`文档对象(不依赖`pfw.dll`)
+///
+/// # Path
+///
+/// - `a/b[2]/c` 相对于根元素，`/`分隔的元素名，方括号中为同名元素的序号(从1开始，默认1)
+/// - `*`匹配任意元素名
+/// - 空字符串表示根元素
+#[derive(Default)]
+pub struct Xml {
+    root: Option<Element>,
+    error: LastError
+}
+
+#[nonvisualobject(name = "nx_xml")]
+impl Xml {
+    /// 解析`XML`字符串(替换原有数据)
+    ///
+    /// # Returns
+    ///
+    /// 格式无效时返回`E_INVALID_DATA`并保留原有数据
+    #[method(name = "Parse")]
+    fn parse(&mut self, data: String) -> RetCode {
+        if self.load(&data) {
+            self.error.clear();
+            RetCode::OK
+        } else {
+            self.error.set(RetCode::E_INVALID_DATA, "invalid xml document")
+        }
+    }
+
+    /// 创建仅包含根元素的新文档(替换原有数据)
+    #[method(name = "Create")]
+    fn create(&mut self, root_name: String) -> RetCode {
+        if !is_valid_name(&root_name) {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid element name: {root_name}"));
+        }
+        self.root = Some(Element::new(root_name));
+        RetCode::OK
+    }
+
+    /// 序列化
+    ///
+    /// # Parameters
+    ///
+    /// - `pretty` 是否缩进输出，默认`false`
+    #[method(name = "ToString", overload = 1)]
+    fn serialize(&self, pretty: Option<bool>) -> String { self.to_xml(pretty.unwrap_or_default()) }
+
+    /// 是否已解析或创建文档
+    #[method(name = "IsValid")]
+    fn is_valid(&self) -> bool { self.root.is_some() }
+
+    /// 根元素名称
+    #[method(name = "GetRootName")]
+    fn root_name(&self) -> String { self.root.as_ref().map(|root| root.name.clone()).unwrap_or_default() }
+
+    /// 元素是否存在
+    #[method(name = "Exists")]
+    fn exists(&self, path: String) -> bool { self.get(&path).is_some() }
+
+    /// 元素的文本(包含`CDATA`)
+    ///
+    /// 元素不存在时返回空字符串
+    #[method(name = "GetText")]
+    fn text(&self, path: String) -> String { self.get(&path).map(Element::text).unwrap_or_default() }
+
+    /// 设置元素的文本(替换原有的文本和子元素)
+    ///
+    /// # Parameters
+    ///
+    /// - `cdata` 是否使用`CDATA`，默认`false`
+    ///
+    /// # Description
+    ///
+    /// 自动创建不存在的元素，序号等于同名元素数量加1时追加元素
+    #[method(name = "SetText", overload = 1)]
+    fn set_text(&mut self, path: String, text: String, cdata: Option<bool>) -> RetCode {
+        match self.get_or_insert(&path) {
+            Ok(elem) => {
+                elem.children.clear();
+                if !text.is_empty() {
+                    elem.children.push(if cdata.unwrap_or_default() {
+                        Node::CData(text)
+                    } else {
+                        Node::Text(text)
+                    });
+                }
+                RetCode::OK
+            },
+            Err(rv) => self.error.set(rv, format!("invalid path: {path}"))
+        }
+    }
+
+    /// 元素的属性值
+    ///
+    /// 属性不存在时返回空字符串
+    #[method(name = "GetAttribute")]
+    fn attribute(&self, path: String, name: String) -> String {
+        self.get(&path)
+            .and_then(|elem| elem.attrs.iter().find(|(key, _)| key == &name))
+            .map(|(_, val)| val.clone())
+            .unwrap_or_default()
+    }
+
+    /// 设置元素的属性值
+    ///
+    /// 自动创建不存在的元素
+    #[method(name = "SetAttribute")]
+    fn set_attribute(&mut self, path: String, name: String, value: String) -> RetCode {
+        if !is_valid_name(&name) {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid name: {name}"));
+        }
+        match self.get_or_insert(&path) {
+            Ok(elem) => {
+                match elem.attrs.iter_mut().find(|(key, _)| key == &name) {
+                    Some((_, val)) => *val = value,
+                    None => elem.attrs.push((name, value))
+                }
+                RetCode::OK
+            },
+            Err(rv) => self.error.set(rv, format!("invalid path: {path}"))
+        }
+    }
+
+    /// 删除元素的属性
+    #[method(name = "RemoveAttribute")]
+    fn remove_attribute(&mut self, path: String, name: String) -> RetCode {
+        let Some(elem) = self.get_mut(&path) else {
+            return self.error.set(RetCode::E_DATA_NOT_FOUND, format!("element not found: {path}"));
+        };
+        let len = elem.attrs.len();
+        elem.attrs.retain(|(key, _)| key != &name);
+        if elem.attrs.len() == len {
+            self.error.set(RetCode::E_DATA_NOT_FOUND, format!("attribute not found: {name}"))
+        } else {
+            RetCode::OK
+        }
+    }
+
+    /// 元素的所有属性名
+    #[method(name = "GetAttributeNames")]
+    fn attribute_names(&self, path: String) -> Vec<String> {
+        self.get(&path)
+            .map(|elem| elem.attrs.iter().map(|(key, _)| key.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// 匹配路径的同名元素数量(忽略最后一级的序号)
+    #[method(name = "GetCount")]
+    fn count(&self, path: String) -> pblong {
+        let (Some(mut segs), Some(root)) = (parse_path(&path), self.root.as_ref()) else {
+            return 0;
+        };
+        let Some(last) = segs.pop() else {
+            return 1;
+        };
+        lookup(root, &segs).map(|parent| parent.elements(&last.name).count() as pblong).unwrap_or_default()
+    }
+
+    /// 子元素的名称
+    #[method(name = "GetChildNames")]
+    fn child_names(&self, path: String) -> Vec<String> {
+        self.get(&path)
+            .map(|elem| elem.elements("*").map(|elem| elem.name.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// 追加子元素
+    ///
+    /// # Parameters
+    ///
+    /// - `path` 父元素，不存在时自动创建
+    /// - `name` 子元素名称
+    /// - `text` 子元素的文本，默认空
+    #[method(name = "AddElement", overload = 1)]
+    fn add_element(&mut self, path: String, name: String, text: Option<String>) -> RetCode {
+        if !is_valid_name(&name) {
+            return self.error.set(RetCode::E_INVALID_ARGUMENT, format!("invalid name: {name}"));
+        }
+        match self.get_or_insert(&path) {
+            Ok(parent) => {
+                let mut elem = Element::new(name);
+                if let Some(text) = text.filter(|text| !text.is_empty()) {
+                    elem.children.push(Node::Text(text));
+                }
+                parent.children.push(Node::Element(elem));
+                RetCode::OK
+            },
+            Err(rv) => self.error.set(rv, format!("invalid path: {path}"))
+        }
+    }
+
+    /// 删除元素
+    ///
+    /// 删除根元素时清空文档
+    #[method(name = "Remove")]
+    fn remove(&mut self, path: String) -> RetCode {
+        let (Some(mut segs), Some(root)) = (parse_path(&path), self.root.as_mut()) else {
+            return self.error.set(RetCode::E_DATA_NOT_FOUND, format!("element not found: {path}"));
+        };
+        let Some(last) = segs.pop() else {
+            self.root = None;
+            return RetCode::OK;
+        };
+        let Some(parent) = lookup_mut(root, &segs) else {
+            return self.error.set(RetCode::E_DATA_NOT_FOUND, format!("element not found: {path}"));
+        };
+        let pos = parent
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, Node::Element(elem) if elem.is_match(&last.name)))
+            .nth(last.index)
+            .map(|(pos, _)| pos);
+        match pos {
+            Some(pos) => {
+                parent.children.remove(pos);
+                RetCode::OK
+            },
+            None => self.error.set(RetCode::E_DATA_NOT_FOUND, format!("element not found: {path}"))
+        }
+    }
+
+    /// 最近一次失败的错误码
+    #[method(name = "GetLastError")]
+    fn get_last_error(&self) -> RetCode { self.error.code() }
+
+    /// 最近一次失败的错误信息
+    #[method(name = "GetLastErrorText")]
+    fn get_last_error_text(&self) -> String { self.error.text().to_owned() }
+}
+
+impl Xml {
+    /// 解析`XML`字符串，失败时保留原有数据
+    pub fn load(&mut self, data: &str) -> bool {
+        match parse_document(data) {
+            Some(root) => {
+                self.root = Some(root);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// 序列化为`XML`字符串(包含`XML`声明)，未解析时返回空字符串
+    pub fn to_xml(&self, pretty: bool) -> String {
+        let Some(root) = self.root.as_ref() else {
+            return "".to_owned();
+        };
+        let mut rv = String::from(DECLARATION);
+        if pretty {
+            rv.push('\n');
+        }
+        write_element(&mut rv, root, pretty, 0);
+        rv
+    }
+
+    fn get(&self, path: &str) -> Option<&Element> { lookup(self.root.as_ref()?, &parse_path(path)?) }
+
+    fn get_mut(&mut self, path: &str) -> Option<&mut Element> {
+        let segs = parse_path(path)?;
+        lookup_mut(self.root.as_mut()?, &segs)
+    }
+
+    fn get_or_insert(&mut self, path: &str) -> Result<&mut Element, RetCode> {
+        let segs = parse_path(path).ok_or(RetCode::E_INVALID_ARGUMENT)?;
+        let root = self.root.as_mut().ok_or(RetCode::E_INVALID_OBJECT)?;
+        lookup_or_insert(root, &segs)
+    }
+}
+
+#[derive(Clone)]
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Node>
+}
+
+#[derive(Clone)]
+enum Node {
+    Element(Element),
+    Text(String),
+    CData(String),
+    Comment(String)
+}
+
+impl Element {
+    fn new(name: String) -> Self {
+        Element {
+            name,
+            attrs: Vec::new(),
+            children: Vec::new()
+        }
+    }
+
+    fn is_match(&self, name: &str) -> bool { name == "*" || self.name == name }
+
+    /// 指定名称的子元素
+    fn elements<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter_map(move |node| {
+            match node {
+                Node::Element(elem) if elem.is_match(name) => Some(elem),
+                _ => None
+            }
+        })
+    }
+
+    /// 直接包含的文本
+    fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|node| {
+                match node {
+                    Node::Text(text) | Node::CData(text) => Some(text.as_str()),
+                    _ => None
+                }
+            })
+            .collect()
+    }
+}
+
+/// 路径片段
+struct Segment {
+    name: String,
+    /// 同名元素的序号(从0开始)
+    index: usize
+}
+
+/// 解析路径，格式无效时返回`None`
+fn parse_path(path: &str) -> Option<Vec<Segment>> {
+    path.split('/')
+        .map(str::trim)
+        .filter(|seg| !seg.is_empty())
+        .map(|seg| {
+            match seg.strip_suffix(']').and_then(|seg| seg.split_once('[')) {
+                Some((name, index)) => {
+                    let index: usize = index.trim().parse().ok()?;
+                    if index == 0 {
+                        return None;
+                    }
+                    Some(Segment {
+                        name: name.trim().to_owned(),
+                        index: index - 1
+                    })
+                },
+                None => {
+                    Some(Segment {
+                        name: seg.to_owned(),
+                        index: 0
+                    })
+                },
+            }
+        })
+        .collect()
+}
+
+fn lookup<'a>(root: &'a Element, segs: &[Segment]) -> Option<&'a Element> {
+    segs.iter().try_fold(root, |cur, seg| cur.elements(&seg.name).nth(seg.index))
+}
+
+fn lookup_mut<'a>(root: &'a mut Element, segs: &[Segment]) -> Option<&'a mut Element> {
+    segs.iter().try_fold(root, |cur, seg| {
+        cur.children
+            .iter_mut()
+            .filter_map(|node| {
+                match node {
+                    Node::Element(elem) if elem.is_match(&seg.name) => Some(elem),
+                    _ => None
+                }
+            })
+            .nth(seg.index)
+    })
+}
+
+/// 查找元素，自动创建不存在的元素
+///
+/// # Returns
+///
+/// - `E_INVALID_ARGUMENT` 需要创建的元素名称无效(如`*`)
+/// - `E_OUT_OF_RANGE` 序号大于同名元素数量加1
+fn lookup_or_insert<'a>(root: &'a mut Element, segs: &[Segment]) -> Result<&'a mut Element, RetCode> {
+    let mut cur = root;
+    for seg in segs {
+        let count = cur.elements(&seg.name).count();
+        if seg.index > count {
+            return Err(RetCode::E_OUT_OF_RANGE);
+        }
+        if seg.index == count {
+            if !is_valid_name(&seg.name) {
+                return Err(RetCode::E_INVALID_ARGUMENT);
+            }
+            cur.children.push(Node::Element(Element::new(seg.name.clone())));
+        }
+        cur = cur
+            .children
+            .iter_mut()
+            .filter_map(|node| {
+                match node {
+                    Node::Element(elem) if elem.is_match(&seg.name) => Some(elem),
+                    _ => None
+                }
+            })
+            .nth(seg.index)
+            .ok_or(RetCode::E_OUT_OF_RANGE)?;
+    }
+    Ok(cur)
+}
+
+/// 元素/属性名称是否有效
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == ':' => {},
+        _ => return false
+    }
+    chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
+/// 解析文档，返回根元素
+///
+/// NOTE 忽略`XML`声明、处理指令和根元素之外的注释
+fn parse_document(data: &str) -> Option<Element> {
+    let mut reader = Reader::from_str(data);
+    reader.config_mut().trim_text(true);
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    loop {
+        let node = match reader.read_event().ok()? {
+            Event::Start(e) => {
+                stack.push(new_element(&e)?);
+                continue;
+            },
+            Event::End(_) => Node::Element(stack.pop()?),
+            Event::Empty(e) => Node::Element(new_element(&e)?),
+            Event::Text(e) => Node::Text(e.unescape().ok()?.into_owned()),
+            Event::CData(e) => Node::CData(String::from_utf8_lossy(&e).into_owned()),
+            Event::Comment(e) => Node::Comment(String::from_utf8_lossy(&e).into_owned()),
+            Event::Eof => break,
+            _ => continue
+        };
+        match (stack.last_mut(), node) {
+            (Some(parent), node) => parent.children.push(node),
+            //只允许一个根元素
+            (None, Node::Element(elem)) if root.is_none() => root = Some(elem),
+            (None, Node::Element(_)) => return None,
+            (None, _) => {}
+        }
+    }
+    if !stack.is_empty() {
+        return None;
+    }
+    root
+}
+
+fn new_element(e: &BytesStart) -> Option<Element> {
+    let mut elem = Element::new(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+    for attr in e.attributes() {
+        let attr = attr.ok()?;
+        elem.attrs.push((
+            String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+            attr.unescape_value().ok()?.into_owned()
+        ));
+    }
+    Some(elem)
+}
+
+fn write_element(out: &mut String, elem: &Element, pretty: bool, depth: usize) {
+    out.push('<');
+    out.push_str(&elem.name);
+    for (key, val) in &elem.attrs {
+        out.push(' ');
+        out.push_str(key);
+        out.push_str("=\"");
+        out.push_str(&escape(val));
+        out.push('"');
+    }
+    if elem.children.is_empty() {
+        out.push_str("/>");
+        return;
+    }
+    out.push('>');
+    //包含文本时不缩进，避免改变文本内容
+    let indent = pretty && !elem.children.iter().any(|node| matches!(node, Node::Text(_) | Node::CData(_)));
+    for node in &elem.children {
+        if indent {
+            write_indent(out, depth + 1);
+        }
+        match node {
+            Node::Element(child) => write_element(out, child, pretty, depth + 1),
+            Node::Text(text) => out.push_str(&escape(text)),
+            Node::CData(text) => {
+                out.push_str("<![CDATA[");
+                out.push_str(&text.replace("]]>", "]]]]><![CDATA[>"));
+                out.push_str("]]>");
+            },
+            Node::Comment(text) => {
+                out.push_str("<!--");
+                out.push_str(text);
+                out.push_str("-->");
+            }
+        }
+    }
+    if indent {
+        write_indent(out, depth);
+    }
+    out.push_str("</");
+    out.push_str(&elem.name);
+    out.push('>');
+}
+
+fn write_indent(out: &mut String, depth: usize) {
+    out.push('\n');
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+}
@@ -0,0 +1,22 @@
+use crate::base::pfw;
+use pbni::{pbx::*, prelude::*};
+
+mod document;
+
+pub use document::Xml;
+
+/// 解析`XML`并创建对象
+///
+/// # Description
+///
+/// 优先创建`pfw::n_xmldoc`对象，`pfw.dll`不可用时创建内置的`nx_xml`对象
+pub fn parse<'a>(session: Session, data: &str) -> Object<'a> {
+    match pfw::xml_parse(session, data) {
+        Some(obj) => obj,
+        None => {
+            Xml::new_object_modify(session, |obj| {
+                obj.load(data);
+            })
+        },
+    }
+}
@@ -1,14 +1,15 @@
-use super::{mem::UnsafeBox, runtime};
+use super::{mem::UnsafeBox, watchdog};
+use crate::base::locale::tr;
 use pbni::{
     pbx::{AliveState, Session}, pbx_throw
 };
 use std::{
-    cell::RefCell, mem, panic::{self, AssertUnwindSafe}, rc::Rc, sync::{
-        atomic::{AtomicUsize, Ordering}, Arc, Mutex
-    }, thread
+    cell::RefCell, collections::VecDeque, mem, panic::{self, AssertUnwindSafe}, rc::Rc, sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex
+    }, thread, time::Instant
 };
 use tokio::{
-    sync::{oneshot, Mutex as AsyncMutex}, time
+    sync::{oneshot, Notify}, time
 };
 use windows::{
     core::{s, PCSTR}, Win32::{
@@ -21,13 +22,56 @@ static CURRENT_CONTEXT: RefCell<Option<SyncContext>> = RefCell::new(None);
 }
 static CONTEXT_COUNT: AtomicUsize = AtomicUsize::new(0);
 static WINDOW_CLASS_ATOM: Mutex<u16> = Mutex::new(0);
+static QUEUE_CONFIG: Mutex<QueueConfig> = Mutex::new(QueueConfig {
+    capacity: default::QUEUE_CAPACITY,
+    policy: OverflowPolicy::Block
+});
 const WM_SYNC_CONTEXT: u32 = WM_USER + 0xff00;
 
+/// 设置消息队列的容量和溢出策略
+///
+/// # Description
+///
+/// 对之后创建的同步上下文以及当前线程的同步上下文生效
+pub fn set_queue_config(capacity: usize, policy: OverflowPolicy) {
+    let cfg = QueueConfig {
+        capacity: capacity.max(1),
+        policy
+    };
+    *QUEUE_CONFIG.lock().unwrap() = cfg;
+    if let Some(ctx) = SyncContext::try_current() {
+        ctx.inner.queue.reconfig(cfg);
+    }
+}
+
+/// 同步上下文的统计信息
+///
+/// # Returns
+///
+/// `(同步上下文数量, 当前线程积压的回调消息数量)`
+///
+/// # Description
+///
+/// 不会阻塞，可在异常处理过程中使用
+pub fn stats() -> (usize, Option<usize>) {
+    let pending = CURRENT_CONTEXT
+        .try_with(|current| {
+            let current = current.try_borrow().ok()?;
+            let state = current.as_ref()?.inner.queue.state.try_lock().ok()?;
+            Some(state.items.len())
+        })
+        .ok()
+        .flatten();
+    (CONTEXT_COUNT.load(Ordering::Relaxed), pending)
+}
+
+/// 每条积压的回调消息的估算内存(字节)，不包含参数指向的数据
+pub fn message_size() -> usize { mem::size_of::<MessagePack>() }
+
 /// UI线程同步上下文
 #[derive(Clone)]
 pub struct SyncContext {
-    inner: Rc<SyncContextInner>,
-    hwnd: Arc<AsyncMutex<HWND>>
+    inner: Rc<SyncContextInner>
 }
 
 impl SyncContext {
@@ -42,17 +86,24 @@ impl SyncContext {
         })
     }
 
+    /// 获取当前线程已绑定的同步上下文
+    pub fn try_current() -> Option<SyncContext> { CURRENT_CONTEXT.with(|current| current.borrow().clone()) }
+
     //创建UI线程同步上下文
+    //
+    //没有消息循环的线程(如后台会话)同样由当前线程持有消息窗口，回调在`run_event_loop`中处理
     fn new(pbsession: Session) -> SyncContext {
         use windows::{
             core::Error as WinError, Win32::{
                 Foundation::*, UI::WindowsAndMessaging::{
-                    CreateWindowExA, RegisterClassA, SetWindowLongPtrA, GWL_USERDATA, HMENU, HWND_MESSAGE, WINDOW_EX_STYLE, WNDCLASSA, WS_POPUP
+                    CreateWindowExA, IsGUIThread, RegisterClassA, SetWindowLongPtrA, GWL_USERDATA, HMENU, HWND_MESSAGE, WINDOW_EX_STYLE, WNDCLASSA, WS_POPUP
                 }
             }
         };
 
         unsafe {
+            //创建窗口前检查，创建窗口后线程总会被转换为GUI线程
+            let background = !IsGUIThread(false).as_bool();
             let hinst = GetModuleHandleA(PCSTR::null()).unwrap_or_default();
             let mut atom = WINDOW_CLASS_ATOM.lock().unwrap();
             //注册窗口类
@@ -87,25 +138,29 @@ impl SyncContext {
             //计数
             CONTEXT_COUNT.fetch_add(1, Ordering::Relaxed);
 
+            let queue = Arc::new(MessageQueue::new(*QUEUE_CONFIG.lock().unwrap()));
+            //登记到阻塞监视，后台会话只在事件循环中处理消息，不视为阻塞
+            if !background {
+                watchdog::register(&queue);
+            }
             let inner = Rc::new(SyncContextInner {
                 hwnd,
-                pbsession
+                pbsession,
+                queue,
+                background
             });
 
             //绑定上下文
             SetWindowLongPtrA(hwnd, GWL_USERDATA, inner.as_ref() as *const SyncContextInner as _);
 
-            let hwnd = Arc::new(AsyncMutex::new(hwnd));
-
             SyncContext {
-                inner,
-                hwnd
+                inner
             }
         }
     }
 
     /// 消息派发器
-    pub fn dispatcher(&self) -> Dispatcher { Dispatcher::new(self.hwnd.clone()) }
+    pub fn dispatcher(&self) -> Dispatcher { Dispatcher::new(self.inner.hwnd, self.inner.queue.clone()) }
 
     /// 处理消息
     pub fn process_message(&self) {
@@ -128,43 +183,54 @@ impl SyncContext {
         }
     }
 
+    /// 等待并处理消息
+    ///
+    /// # Description
+    ///
+    /// 用于没有消息循环的线程(如后台会话)
+    ///
+    /// # Returns
+    ///
+    /// 超时前收到消息返回`true`
+    pub fn wait_message(&self, timeout: time::Duration) -> bool {
+        use windows::Win32::{
+            Foundation::WAIT_TIMEOUT, UI::WindowsAndMessaging::{MsgWaitForMultipleObjects, QS_POSTMESSAGE}
+        };
+
+        //只有新到达的消息能唤醒等待，需要先处理积压的消息
+        self.process_message();
+        let rc = unsafe {
+            MsgWaitForMultipleObjects(
+                None,
+                false,
+                timeout.as_millis().min(u32::MAX as u128) as u32,
+                QS_POSTMESSAGE
+            )
+        };
+        self.process_message();
+        rc != WAIT_TIMEOUT.0
+    }
+
     /// 窗口过程
     unsafe extern "system" fn wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
         use windows::Win32::UI::WindowsAndMessaging::{DefWindowProcA, GetWindowLongPtrA, GWL_USERDATA};
 
         if msg == WM_SYNC_CONTEXT {
             let ctx = &*(GetWindowLongPtrA(hwnd, GWL_USERDATA) as *const SyncContextInner);
-            let session = ctx.pbsession.clone();
-            let pack: MessagePack = UnsafeBox::from_raw(mem::transmute(lparam)).unpack();
-            let has_rx = pack.tx.send(()).is_ok(); //接收
-            match pack.payload {
-                MessagePayload::Invoke(payload) => {
-                    if let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| {
-                        (payload.handler)(payload.param, payload.alive.is_alive() && has_rx);
-                    })) {
-                        let panic_info = match e.downcast_ref::<String>() {
-                            Some(e) => &e,
-                            None => {
-                                match e.downcast_ref::<&'static str>() {
-                                    Some(e) => e,
-                                    None => "unknown"
-                                }
-                            },
-                        };
-                        if !session.has_exception() {
-                            pbx_throw!(
-                                session,
-                                "{}\r\nbacktrace:\r\n{:?}",
-                                panic_info,
-                                backtrace::Backtrace::new()
-                            );
-                        }
-                    }
-                },
-                MessagePayload::Panic(payload) => {
-                    pbx_throw!(session, "{}", payload.info);
+            //允许投递新的唤醒消息
+            ctx.queue.notified.store(false, Ordering::SeqCst);
+            //检查UI线程是否被阻塞
+            if !ctx.background {
+                if let Some((seq, waited, pending)) = ctx.queue.backlog() {
+                    watchdog::check(seq, waited, pending);
                 }
             }
+            for pack in ctx.queue.take_dropped() {
+                pack.discard(Delivery::Dropped);
+            }
+            while let Some(pack) = ctx.queue.pop() {
+                Self::handle_message(&ctx.pbsession, pack);
+            }
             return LRESULT(0);
         }
 
@@ -172,10 +238,54 @@ impl SyncContext {
     }
 }
 
+impl SyncContext {
+    /// 执行消息
+    fn handle_message(session: &Session, pack: MessagePack) {
+        let has_rx = pack.tx.send(()).is_ok(); //接收
+        match pack.payload {
+            MessagePayload::Invoke(payload) => {
+                let delivery = if payload.alive.is_alive() && has_rx {
+                    Delivery::Invoke
+                } else {
+                    Delivery::Discard
+                };
+                if let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| {
+                    (payload.handler)(payload.param, delivery);
+                })) {
+                    let panic_info = match e.downcast_ref::<String>() {
+                        Some(e) => &e,
+                        None => {
+                            match e.downcast_ref::<&'static str>() {
+                                Some(e) => e,
+                                None => "unknown"
+                            }
+                        },
+                    };
+                    if !session.has_exception() {
+                        pbx_throw!(
+                            session,
+                            "{}\r\n{}:\r\n{:?}",
+                            panic_info,
+                            tr("backtrace", "调用栈"),
+                            backtrace::Backtrace::new()
+                        );
+                    }
+                }
+            },
+            MessagePayload::Panic(payload) => {
+                pbx_throw!(session, "{}", payload.info);
+            }
+        }
+    }
+}
+
 //销毁时回收线程资源
 struct SyncContextInner {
     hwnd: HWND,
-    pbsession: Session
+    pbsession: Session,
+    queue: Arc<MessageQueue>,
+    //没有消息循环的线程(如后台会话)
+    background: bool
 }
 
 impl Drop for SyncContextInner {
@@ -185,6 +295,9 @@ impl Drop for SyncContextInner {
         unsafe {
             //销毁窗口
             DestroyWindow(self.hwnd);
+            //丢弃未处理的消息
+            self.queue.clear();
+            watchdog::unregister(&self.queue);
             if CONTEXT_COUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
                 //注销窗口类
                 let mut atom = WINDOW_CLASS_ATOM.lock().unwrap();
@@ -195,9 +308,6 @@ impl Drop for SyncContextInner {
                     );
                     *atom = 0;
                 }
-                //FIXME
-                //销毁运行时
-                runtime::shutdown();
             }
         }
     }
@@ -209,6 +319,15 @@ struct MessagePack {
     tx: oneshot::Sender<()>
 }
 
+impl MessagePack {
+    /// 丢弃消息并释放内存
+    fn discard(self, delivery: Delivery) {
+        if let MessagePayload::Invoke(payload) = self.payload {
+            (payload.handler)(payload.param, delivery);
+        }
+    }
+}
+
 /// 消息内容
 enum MessagePayload {
     Invoke(PayloadInvoke),
@@ -218,7 +337,7 @@ enum MessagePayload {
 /// 消息内容-回调过程
 struct PayloadInvoke {
     param: UnsafeBox<()>,
-    handler: Box<dyn FnOnce(UnsafeBox<()>, bool) + Send + 'static>,
+    handler: Box<dyn FnOnce(UnsafeBox<()>, Delivery) + Send + 'static>,
     alive: AliveState
 }
 
@@ -227,67 +346,256 @@ struct PayloadPanic {
     info: String
 }
 
+/// 回调过程的执行方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// 执行
+    Invoke,
+    /// 接收目标被销毁
+    Discard,
+    /// 队列溢出被丢弃
+    Dropped
+}
+
+/// 消息队列溢出策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// 等待队列出现空闲位置
+    Block,
+    /// 替换队列中相同合并键的消息，没有可合并的消息时等待
+    Coalesce,
+    /// 丢弃最早的可合并消息(`invoke_coalesced`)，没有可丢弃的消息时等待
+    ///
+    /// 普通回调(如异步任务的完成通知)不会被丢弃
+    DropOldest
+}
+
+/// 消息队列配置
+#[derive(Debug, Clone, Copy)]
+struct QueueConfig {
+    capacity: usize,
+    policy: OverflowPolicy
+}
+
+/// 消息队列
+///
+/// # Description
+///
+/// 所有消息通过一个唤醒消息(`WM_SYNC_CONTEXT`)统一处理，避免占用系统消息队列
+pub(super) struct MessageQueue {
+    state: Mutex<QueueState>,
+    //出现空闲位置的通知
+    space: Notify,
+    //是否已投递唤醒消息
+    notified: AtomicBool
+}
+
+struct QueueState {
+    items: VecDeque<QueuedMessage>,
+    //溢出被丢弃的消息，由UI线程释放
+    dropped: Vec<MessagePack>,
+    next_seq: u64,
+    cfg: QueueConfig
+}
+
+/// 队列中的消息
+struct QueuedMessage {
+    seq: u64,
+    key: Option<(usize, u64)>,
+    pack: MessagePack,
+    queued_at: Instant
+}
+
+impl MessageQueue {
+    fn new(cfg: QueueConfig) -> Self {
+        MessageQueue {
+            state: Mutex::new(QueueState {
+                items: VecDeque::new(),
+                dropped: Vec::new(),
+                next_seq: 0,
+                cfg
+            }),
+            space: Notify::new(),
+            notified: AtomicBool::new(false)
+        }
+    }
+
+    /// 修改配置
+    fn reconfig(&self, cfg: QueueConfig) {
+        let mut state = self.state.lock().unwrap();
+        state.cfg = cfg;
+        drop(state);
+        self.space.notify_waiters();
+    }
+
+    /// 消息入队
+    ///
+    /// # Returns
+    ///
+    /// 成功返回消息序号，队列已满时返还消息
+    fn try_push(&self, key: Option<(usize, u64)>, pack: MessagePack) -> Result<u64, MessagePack> {
+        let mut state = self.state.lock().unwrap();
+        let seq = state.next_seq;
+        if state.items.len() >= state.cfg.capacity {
+            let dropped = match state.cfg.policy {
+                OverflowPolicy::Block => return Err(pack),
+                OverflowPolicy::Coalesce => {
+                    match key.and_then(|key| state.items.iter().rposition(|item| item.key == Some(key))) {
+                        Some(idx) => state.items.remove(idx).unwrap(),
+                        None => return Err(pack)
+                    }
+                },
+                OverflowPolicy::DropOldest => {
+                    match state.items.iter().position(|item| item.key.is_some()) {
+                        Some(idx) => state.items.remove(idx).unwrap(),
+                        None => return Err(pack)
+                    }
+                },
+            };
+            #[cfg(feature = "log")]
+            warn!("Dispatch queue is full, message #{} was dropped", dropped.seq);
+            //回调过程需要在UI线程中执行
+            state.dropped.push(dropped.pack);
+            state.next_seq += 1;
+            state.items.push_back(QueuedMessage {
+                seq,
+                key,
+                pack,
+                queued_at: Instant::now()
+            });
+            return Ok(seq);
+        }
+        state.next_seq += 1;
+        state.items.push_back(QueuedMessage {
+            seq,
+            key,
+            pack,
+            queued_at: Instant::now()
+        });
+        Ok(seq)
+    }
+
+    /// 取出最早的消息
+    fn pop(&self) -> Option<MessagePack> {
+        let mut state = self.state.lock().unwrap();
+        let item = state.items.pop_front();
+        drop(state);
+        if item.is_some() {
+            self.space.notify_waiters();
+        }
+        item.map(|item| item.pack)
+    }
+
+    /// 取出溢出被丢弃的消息
+    fn take_dropped(&self) -> Vec<MessagePack> { mem::take(&mut self.state.lock().unwrap().dropped) }
+
+    /// 删除未被处理的消息
+    fn remove(&self, seq: u64) -> Option<MessagePack> {
+        let mut state = self.state.lock().unwrap();
+        let idx = state.items.iter().position(|item| item.seq == seq)?;
+        let item = state.items.remove(idx);
+        drop(state);
+        self.space.notify_waiters();
+        item.map(|item| item.pack)
+    }
+
+    /// 积压情况
+    ///
+    /// # Returns
+    ///
+    /// `(最早的消息序号, 最早的消息等待的时长, 消息数量)`，没有未处理的消息时返回`None`
+    pub(super) fn backlog(&self) -> Option<(u64, time::Duration, usize)> {
+        let state = self.state.lock().unwrap();
+        state.items.front().map(|item| (item.seq, item.queued_at.elapsed(), state.items.len()))
+    }
+
+    /// 是否有未处理的消息
+    fn is_empty(&self) -> bool { self.state.lock().unwrap().items.is_empty() }
+
+    /// 丢弃所有未处理的消息
+    fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        let items = mem::take(&mut state.items);
+        let dropped = mem::take(&mut state.dropped);
+        drop(state);
+        for pack in dropped {
+            pack.discard(Delivery::Dropped);
+        }
+        for item in items {
+            item.pack.discard(Delivery::Discard);
+        }
+        self.space.notify_waiters();
+    }
+}
+
 /// 消息派发器
 #[derive(Clone)]
 pub struct Dispatcher {
-    //加锁缓解UI线程出现消息积压，避免系统消息队列溢出，节省系统资源
-    hwnd: Arc<AsyncMutex<HWND>>
+    hwnd: HWND,
+    queue: Arc<MessageQueue>
 }
 
 impl Dispatcher {
-    fn new(hwnd: Arc<AsyncMutex<HWND>>) -> Dispatcher {
+    fn new(hwnd: HWND, queue: Arc<MessageQueue>) -> Dispatcher {
         Dispatcher {
-            hwnd
+            hwnd,
+            queue
         }
     }
 
     /// 派发回调请求给UI线程执行
+    ///
+    /// # Parameters
+    ///
+    /// - `key` 合并键，`OverflowPolicy::Coalesce`策略下替换队列中相同键的消息
     pub async fn dispatch_invoke(
         &self,
+        key: Option<(usize, u64)>,
         param: UnsafeBox<()>,
-        handler: Box<dyn FnOnce(UnsafeBox<()>, bool) + Send + 'static>,
+        handler: Box<dyn FnOnce(UnsafeBox<()>, Delivery) + Send + 'static>,
         alive: AliveState
     ) -> bool {
-        self.dispatch(MessagePayload::Invoke(PayloadInvoke {
-            param,
-            handler,
-            alive
-        }))
+        self.dispatch(
+            key,
+            MessagePayload::Invoke(PayloadInvoke {
+                param,
+                handler,
+                alive
+            })
+        )
         .await
     }
 
     /// 派发异常信息给UI线程
     pub async fn dispatch_panic(&self, info: String) -> bool {
-        self.dispatch(MessagePayload::Panic(PayloadPanic {
-            info
-        }))
+        self.dispatch(
+            None,
+            MessagePayload::Panic(PayloadPanic {
+                info
+            })
+        )
         .await
     }
 
     /// 派发消息给UI线程
-    async fn dispatch(&self, payload: MessagePayload) -> bool {
-        use windows::Win32::UI::WindowsAndMessaging::IsWindow;
-
-        let hwnd = self.hwnd.lock().await;
-
-        if let Some((mut rx, alive, msg_pack)) = self.post_message(*hwnd, payload) {
-            //等待消息被接收
-            loop {
-                tokio::select! {
-                    _ = &mut rx => return true,
-                    _ = time::sleep(time::Duration::from_millis(100)) => {
-                        unsafe {
-                            if alive.as_ref().map(|v|v.is_dead()).unwrap_or_default() || IsWindow(*hwnd) == false {
-                                //需要再次检查信号，避免目标销毁前接收了消息
-                                if rx.try_recv().is_ok() {
-                                    return true;
-                                }
+    async fn dispatch(&self, key: Option<(usize, u64)>, payload: MessagePayload) -> bool {
+        let (mut rx, alive, mut pack) = Self::pack(payload);
+
+        //入队
+        let seq = loop {
+            let space = self.queue.space.notified();
+            match self.queue.try_push(key, pack) {
+                Ok(seq) => break seq,
+                Err(rv) => {
+                    pack = rv;
+                    //队列已满，等待空闲位置
+                    tokio::select! {
+                        _ = space => {},
+                        _ = time::sleep(time::Duration::from_millis(100)) => {
+                            if self.is_target_dead(&alive) {
                                 //接收目标被销毁，需要释放内存
-                                let msg_pack = msg_pack.unpack();
-                                if let MessagePayload::Invoke(payload) = msg_pack.payload {
-                                    (payload.handler)(payload.param, false);
-                                }
-                                #[cfg(feature = "trace")]
+                                pack.discard(Delivery::Discard);
+                                #[cfg(feature = "log")]
                                 warn!("Context window was destroyed");
                                 return false;
                             }
@@ -295,8 +603,26 @@ impl Dispatcher {
                     }
                 }
             }
-        } else {
-            false
+        };
+
+        if !self.notify() {
+            return !self.revoke(seq);
+        }
+
+        //等待消息被接收
+        loop {
+            tokio::select! {
+                //消息被丢弃时发送端直接释放
+                rv = &mut rx => return rv.is_ok(),
+                _ = time::sleep(time::Duration::from_millis(100)) => {
+                    //消息已被取出时继续等待接收信号
+                    if self.is_target_dead(&alive) && self.revoke(seq) {
+                        return false;
+                    }
+                    //唤醒消息投递失败后重试
+                    self.notify();
+                }
+            }
         }
     }
 
@@ -308,7 +634,7 @@ impl Dispatcher {
     pub fn dispatch_invoke_blocking(
         &self,
         param: UnsafeBox<()>,
-        handler: Box<dyn FnOnce(UnsafeBox<()>, bool) + Send + 'static>,
+        handler: Box<dyn FnOnce(UnsafeBox<()>, Delivery) + Send + 'static>,
         alive: AliveState
     ) -> bool {
         self.dispatch_blocking(MessagePayload::Invoke(PayloadInvoke {
@@ -335,81 +661,121 @@ impl Dispatcher {
     ///
     /// 在非异步上下文中使用
     fn dispatch_blocking(&self, payload: MessagePayload) -> bool {
-        use windows::Win32::UI::WindowsAndMessaging::IsWindow;
-
-        let hwnd = self.hwnd.blocking_lock();
-
-        if let Some((mut rx, alive, msg_pack)) = self.post_message(*hwnd, payload) {
-            //等待消息被接收
-            loop {
-                if rx.try_recv().is_ok() {
-                    return true;
-                }
-                unsafe {
-                    if alive.as_ref().map(|v| v.is_dead()).unwrap_or_default() || IsWindow(*hwnd) == false {
+        let (mut rx, alive, mut pack) = Self::pack(payload);
+
+        //入队
+        let seq = loop {
+            match self.queue.try_push(None, pack) {
+                Ok(seq) => break seq,
+                Err(rv) => {
+                    pack = rv;
+                    if self.is_target_dead(&alive) {
                         //接收目标被销毁，需要释放内存
-                        let msg_pack = msg_pack.unpack();
-                        if let MessagePayload::Invoke(payload) = msg_pack.payload {
-                            (payload.handler)(payload.param, false);
-                        }
-                        #[cfg(feature = "trace")]
+                        pack.discard(Delivery::Discard);
+                        #[cfg(feature = "log")]
                         warn!("Context window was destroyed");
                         return false;
                     }
+                    //队列已满，等待空闲位置
+                    thread::sleep(time::Duration::from_millis(10));
                 }
-                thread::sleep(time::Duration::from_millis(100));
             }
-        } else {
-            false
+        };
+
+        if !self.notify() {
+            return !self.revoke(seq);
         }
-    }
 
-    /// 派发消息
-    fn post_message(
-        &self,
-        hwnd: HWND,
-        payload: MessagePayload
-    ) -> Option<(oneshot::Receiver<()>, Option<AliveState>, UnsafeBox<MessagePack>)> {
-        use windows::Win32::{Foundation::ERROR_NOT_ENOUGH_QUOTA, UI::WindowsAndMessaging::PostMessageA};
+        //等待消息被接收
+        loop {
+            match rx.try_recv() {
+                Ok(_) => return true,
+                //消息被丢弃
+                Err(oneshot::error::TryRecvError::Closed) => return false,
+                Err(oneshot::error::TryRecvError::Empty) => {}
+            }
+            //消息已被取出时继续等待接收信号
+            if self.is_target_dead(&alive) && self.revoke(seq) {
+                return false;
+            }
+            //唤醒消息投递失败后重试
+            self.notify();
+            thread::sleep(time::Duration::from_millis(100));
+        }
+    }
 
+    /// 消息打包
+    fn pack(payload: MessagePayload) -> (oneshot::Receiver<()>, Option<AliveState>, MessagePack) {
         let alive = if let MessagePayload::Invoke(payload) = &payload {
             Some(payload.alive.clone())
         } else {
             None
         };
-
-        //参数打包
         let (tx, rx) = oneshot::channel();
-        let msg_pack = UnsafeBox::pack(MessagePack {
+        (rx, alive, MessagePack {
             payload,
             tx
-        });
+        })
+    }
 
-        loop {
-            unsafe {
-                if PostMessageA(hwnd, WM_SYNC_CONTEXT, WPARAM(0), LPARAM(msg_pack.as_raw() as _)) == false {
-                    //消息队列满了
-                    if GetLastError() == ERROR_NOT_ENOUGH_QUOTA {
-                        #[cfg(feature = "trace")]
-                        warn!("Windows message queue is full");
-                        //等待后重试
-                        thread::sleep(time::Duration::from_millis(100));
-                        continue;
-                    }
-                    //窗口已经被销毁，说明此时目标线程已经不存在，需要释放内存
-                    let msg_pack = msg_pack.unpack();
-                    if let MessagePayload::Invoke(payload) = msg_pack.payload {
-                        (payload.handler)(payload.param, false);
-                    }
-                    #[cfg(feature = "trace")]
-                    warn!("PostMessage to the context window failed");
-                    return None;
-                } else {
-                    break;
+    /// 接收目标是否被销毁
+    fn is_target_dead(&self, alive: &Option<AliveState>) -> bool {
+        use windows::Win32::UI::WindowsAndMessaging::IsWindow;
+
+        alive.as_ref().map(|v| v.is_dead()).unwrap_or_default() || unsafe { IsWindow(self.hwnd) == false }
+    }
+
+    /// 撤回未被处理的消息并释放内存
+    ///
+    /// # Returns
+    ///
+    /// 撤回成功返回`true`，消息已被UI线程取出时返回`false`
+    fn revoke(&self, seq: u64) -> bool {
+        if let Some(pack) = self.queue.remove(seq) {
+            pack.discard(Delivery::Discard);
+            #[cfg(feature = "log")]
+            warn!("Context window was destroyed");
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 投递唤醒消息
+    ///
+    /// # Description
+    ///
+    /// 系统消息队列已满时不再等待，由后续的派发过程重试
+    ///
+    /// # Returns
+    ///
+    /// 窗口已经被销毁时返回`false`
+    fn notify(&self) -> bool {
+        use windows::Win32::{Foundation::ERROR_NOT_ENOUGH_QUOTA, UI::WindowsAndMessaging::PostMessageA};
+
+        if self.queue.is_empty() || self.queue.notified.swap(true, Ordering::SeqCst) {
+            return true;
+        }
+        unsafe {
+            if PostMessageA(self.hwnd, WM_SYNC_CONTEXT, WPARAM(0), LPARAM(0)) == false {
+                let err = GetLastError();
+                self.queue.notified.store(false, Ordering::SeqCst);
+                if err == ERROR_NOT_ENOUGH_QUOTA {
+                    #[cfg(feature = "log")]
+                    warn!("Windows message queue is full");
+                    return true;
                 }
+                #[cfg(feature = "log")]
+                warn!("PostMessage to the context window failed");
+                return false;
             }
         }
-
-        Some((rx, alive, msg_pack))
+        true
     }
 }
+
+/// 默认配置
+mod default {
+    /// 消息队列容量
+    pub const QUEUE_CAPACITY: usize = 256;
+}
@@ -0,0 +1,128 @@
+//! 异常报告
+//!
+use super::{context, handler, runtime};
+use std::{
+    fmt::Write as _, fs::{self, File}, io::Write, os::windows::io::AsRawHandle, panic::{self, PanicInfo}, path::{Path, PathBuf}, sync::{Mutex, Once}, thread
+};
+use windows::Win32::{
+    Foundation::HANDLE, System::{
+        Diagnostics::Debug::{MiniDumpWithThreadInfo, MiniDumpWriteDump}, SystemInformation::GetLocalTime, Threading::{GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId}
+    }
+};
+
+static CRASH_CONFIG: Mutex<Option<CrashConfig>> = Mutex::new(None);
+static INSTALL_HOOK: Once = Once::new();
+
+/// 异常报告配置
+#[derive(Debug, Clone)]
+pub struct CrashConfig {
+    /// 报告文件的存放目录
+    pub dir: PathBuf,
+    /// 是否同时生成`minidump`文件
+    pub minidump: bool
+}
+
+/// 设置异常报告
+///
+/// # Description
+///
+/// 首次调用时安装`panic`钩子，后台运行时或回调过程中的任何`panic`都会在抛出PB异常前生成报告文件，
+/// `None`表示不再生成报告
+pub fn set_crash_report(cfg: Option<CrashConfig>) {
+    INSTALL_HOOK.call_once(|| {
+        let prev_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            write_report(info);
+            prev_hook(info);
+        }));
+    });
+    *CRASH_CONFIG.lock().unwrap_or_else(|e| e.into_inner()) = cfg;
+}
+
+/// 生成报告文件
+fn write_report(info: &PanicInfo) {
+    let cfg = match CRASH_CONFIG.lock() {
+        Ok(cfg) => cfg.clone(),
+        Err(_) => return
+    };
+    let cfg = match cfg {
+        Some(cfg) => cfg,
+        None => return
+    };
+    if fs::create_dir_all(&cfg.dir).is_err() {
+        return;
+    }
+
+    let (pid, tid, now) = unsafe { (GetCurrentProcessId(), GetCurrentThreadId(), GetLocalTime()) };
+    let timestamp = format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        now.wYear, now.wMonth, now.wDay, now.wHour, now.wMinute, now.wSecond, now.wMilliseconds
+    );
+    let file_name = format!("{}-crash-{}-{}-{}", env!("CARGO_PKG_NAME"), timestamp, pid, tid);
+
+    let mut report = String::new();
+    let _ = writeln!(report, "version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "time: {}", timestamp);
+    let _ = writeln!(report, "process: {}", pid);
+    let _ = writeln!(report, "thread: {} ({})", tid, thread::current().name().unwrap_or("<unnamed>"));
+    let _ = writeln!(report);
+    let _ = writeln!(report, "panic: {}", panic_message(info));
+    if let Some(loc) = info.location() {
+        let _ = writeln!(report, "location: {}:{}:{}", loc.file(), loc.line(), loc.column());
+    }
+    let _ = writeln!(report);
+    let _ = writeln!(report, "[runtime]");
+    let (contexts, pending_messages) = context::stats();
+    let _ = writeln!(report, "running: {}", runtime::is_running());
+    let _ = writeln!(report, "sync contexts: {}", contexts);
+    if let Some(pending_messages) = pending_messages {
+        let _ = writeln!(report, "pending messages (current thread): {}", pending_messages);
+    }
+    if let Some(pending_tasks) = handler::pending_tasks() {
+        let _ = writeln!(report, "pending tasks (current thread): {}", pending_tasks);
+    }
+    let _ = writeln!(report);
+    let _ = writeln!(report, "[backtrace]");
+    let _ = writeln!(report, "{:?}", backtrace::Backtrace::new());
+
+    if let Ok(mut file) = File::create(cfg.dir.join(format!("{}.txt", file_name))) {
+        let _ = file.write_all(report.as_bytes());
+    }
+    if cfg.minidump {
+        write_minidump(&cfg.dir.join(format!("{}.dmp", file_name)));
+    }
+}
+
+/// 生成`minidump`文件
+fn write_minidump(path: &Path) {
+    if let Ok(file) = File::create(path) {
+        unsafe {
+            let rv = MiniDumpWriteDump(
+                GetCurrentProcess(),
+                GetCurrentProcessId(),
+                HANDLE(file.as_raw_handle() as _),
+                MiniDumpWithThreadInfo,
+                None,
+                None,
+                None
+            );
+            if rv == false {
+                drop(file);
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// 提取`panic`信息
+fn panic_message<'a>(info: &'a PanicInfo) -> &'a str {
+    match info.payload().downcast_ref::<String>() {
+        Some(e) => e,
+        None => {
+            match info.payload().downcast_ref::<&'static str>() {
+                Some(e) => e,
+                None => "unknown"
+            }
+        },
+    }
+}
@@ -61,6 +61,27 @@ impl Win32Event {
         }
     }
 
+    /// 复制`HANDLE`并拥有副本的所有权
+    ///
+    /// 原句柄被关闭不影响副本
+    pub fn duplicate(handle: HEVENT) -> Result<Self, WinError> {
+        let handle = unsafe {
+            let hprocess = GetCurrentProcess();
+            let mut dup_handle = HEVENT::default();
+            if DuplicateHandle(hprocess, handle, hprocess, &mut dup_handle, 0, false, DUPLICATE_SAME_ACCESS) ==
+                false
+            {
+                return Err(WinError::from_win32());
+            }
+            dup_handle
+        };
+        Ok(Win32Event {
+            handle,
+            owned: true,
+            waiting: None
+        })
+    }
+
     /// 转换为`HANDLE`
     pub fn into_raw(mut self) -> HEVENT {
         self.owned = false;
@@ -1,35 +1,70 @@
 use super::event::{Win32Event, HEVENT};
 use futures_util::future::{self, Either};
 use pbni::primitive::pbulong;
-use std::future::Future;
+use std::{fmt::Display, future::Future};
+use windows::core::Error as WinError;
 
 /// 执行`fut`任务并支持通过Win32 Event Handle信号进行取消
 ///
 /// # Returns
 ///
-/// 执行完成返回`Some(Output)`，被取消返回`None`
+/// 返回的任务执行完成时输出`Some(Output)`，被取消时输出`None`
 ///
-/// # Panics
+/// # Errors
 ///
-/// `hevent`为无效句柄时会触发Panic
+/// `hevent`为无效句柄时返回错误
 ///
-/// # Undefined behaviors
+/// # Description
 ///
-/// `hevent`在WAIT过程中被销毁将导致**未定义行为(UB)**
-pub async fn cancel_by_event<F>(fut: F, hevent: pbulong) -> Option<F::Output>
+/// 调用时复制`hevent`，等待过程中原句柄被销毁不影响取消，等待信号失败时不再支持取消并继续执行`fut`
+pub fn cancel_by_event<F>(
+    fut: F,
+    hevent: pbulong
+) -> Result<impl Future<Output = Option<F::Output>>, WinError>
 where
     F: Future
 {
-    let event = Win32Event::from_raw(HEVENT(hevent as _));
-    tokio::pin!(fut);
-    tokio::pin!(event);
-    match future::select(fut, event).await {
-        Either::Left((rv, _)) => Some(rv),
-        Either::Right((rv, _)) => {
-            match rv {
-                Ok(_) => None,
-                Err(e) => panic!("wait hevent failed: {e}")
+    let event = Win32Event::duplicate(HEVENT(hevent as _))?;
+    Ok(async move {
+        tokio::pin!(fut);
+        tokio::pin!(event);
+        match future::select(fut, event).await {
+            Either::Left((rv, _)) => Some(rv),
+            Either::Right((Ok(_), _)) => None,
+            Either::Right((Err(_e), fut)) => {
+                #[cfg(feature = "log")]
+                warn!("Wait hevent failed: {}", _e);
+                Some(fut.await)
             }
-        },
-    }
+        }
+    })
+}
+
+/// 为`fut`任务附加关联ID
+///
+/// # Description
+///
+/// 关联ID作为日志的`span`字段(`correlation_id`)，任务执行过程中产生的日志都包含此ID，便于还原多步骤的操作
+#[cfg(feature = "log")]
+pub fn correlate<F>(id: impl Display, fut: F) -> impl Future<Output = F::Output>
+where
+    F: Future
+{
+    use tracing::Instrument;
+
+    //使用`ERROR`级别使`span`在任何日志级别下都生效
+    fut.instrument(error_span!("task", correlation_id = %id))
+}
+
+/// 为`fut`任务附加关联ID
+///
+/// # Description
+///
+/// 未启用日志时不做处理
+#[cfg(not(feature = "log"))]
+pub fn correlate<F>(_id: impl Display, fut: F) -> F
+where
+    F: Future
+{
+    fut
 }
@@ -1,12 +1,71 @@
 use super::{
-    context::{Dispatcher, SyncContext}, mem::{UnsafeBox, UnsafePointer}, runtime
+    context::{Delivery, Dispatcher, SyncContext}, mem::{UnsafeBox, UnsafePointer}, runtime, watchdog::{self, StallReport}
 };
+use crate::base::locale::tr;
 use futures_util::FutureExt;
 use pbni::pbx::{AliveState, Session};
 use std::{
-    cell::RefCell, future::Future, marker::PhantomData, panic::AssertUnwindSafe, pin::Pin, rc::{Rc, Weak}, task::{ready, Context, Poll}, thread, thread::ThreadId, time::Duration
+    cell::RefCell, collections::{HashMap, VecDeque}, future::Future, marker::PhantomData, panic::AssertUnwindSafe, pin::Pin, rc::{Rc, Weak}, task::{ready, Context, Poll}, thread, thread::ThreadId, time::Duration
 };
 use tokio::sync::oneshot;
+use windows::Win32::System::Threading::GetCurrentThreadId;
+
+thread_local! {
+static HANDLER_STATES: RefCell<Vec<Weak<RefCell<HandlerStateManager>>>> = RefCell::new(Vec::new());
+}
+
+/// 关闭当前线程所有对象的异步任务
+///
+/// # Description
+///
+/// 先执行对象注册的关闭过程(`Handler::on_shutdown`)，再取消所有未完成的异步任务
+pub fn shutdown_all() {
+    let mgrs: Vec<_> =
+        HANDLER_STATES.with(|states| states.borrow().iter().filter_map(Weak::upgrade).collect());
+    for mgr in mgrs.iter() {
+        let hook = mgr.borrow_mut().shutdown_hook.take();
+        if let Some(hook) = hook {
+            hook();
+        }
+    }
+    for mgr in mgrs.iter() {
+        mgr.borrow_mut().cancel_all();
+    }
+}
+
+/// 当前线程未完成的异步任务数量
+///
+/// # Description
+///
+/// 不会阻塞，可在异常处理过程中使用
+pub fn pending_tasks() -> Option<usize> {
+    HANDLER_STATES
+        .try_with(|states| {
+            let states = states.try_borrow().ok()?;
+            let mut count = 0;
+            for mgr in states.iter().filter_map(Weak::upgrade) {
+                //任务结束时接收端会被释放
+                count += mgr.try_borrow().ok()?.pending.values().filter(|task| !task.tx.is_closed()).count();
+            }
+            Some(count)
+        })
+        .ok()
+        .flatten()
+}
+
+/// 当前线程存活的异步对象数量
+///
+/// # Description
+///
+/// 不会阻塞，可在异常处理过程中使用
+pub fn handler_count() -> Option<usize> {
+    HANDLER_STATES
+        .try_with(|states| {
+            Some(states.try_borrow().ok()?.iter().filter(|mgr| mgr.strong_count() > 0).count())
+        })
+        .ok()
+        .flatten()
+}
 
 /// 回调处理对象抽象
 pub trait Handler: Sized + 'static {
@@ -19,6 +78,46 @@ pub trait Handler: Sized + 'static {
     /// 对象回调派发器
     fn invoker(&self) -> HandlerInvoker<Self> { HandlerInvoker::bind(self) }
 
+    /// 注册后台运行时关闭前执行的清理过程
+    ///
+    /// # Description
+    ///
+    /// 在UI线程中执行，对象销毁后不再执行，重复注册会覆盖之前的过程
+    fn on_shutdown<H>(&self, hook: H)
+    where
+        H: FnOnce(&mut Self) + 'static
+    {
+        let this = unsafe { UnsafePointer::from_raw(self as *const Self as *mut Self) };
+        let alive = self.alive_state();
+        self.state().set_shutdown_hook(Box::new(move || {
+            if alive.is_alive() {
+                hook(unsafe { &mut *this.into_raw() });
+            }
+        }));
+    }
+
+    /// 接收当前线程的UI线程阻塞报告
+    ///
+    /// # Returns
+    ///
+    /// 处理过程的标识，用于`reactor::clear_stall_handler`
+    ///
+    /// # Description
+    ///
+    /// 在UI线程中执行，对象销毁后不再执行，同一线程只保留最后注册的对象
+    fn on_stall<H>(&self, handler: H) -> u64
+    where
+        H: Fn(&mut Self, &StallReport) + 'static
+    {
+        let this = unsafe { UnsafePointer::from_raw(self as *const Self as *mut Self) };
+        let alive = self.alive_state();
+        watchdog::set_stall_handler(Rc::new(move |report: &StallReport| {
+            if alive.is_alive() {
+                handler(unsafe { &mut *this.as_raw() }, report);
+            }
+        }))
+    }
+
     /// 启动一个异步任务
     ///
     /// # Parameters
@@ -41,78 +140,28 @@ pub trait Handler: Sized + 'static {
         F::Output: Send + 'static,
         H: FnOnce(&mut Self, F::Output) + Send + 'static
     {
-        let invoker = self.invoker();
-        let (cancel_hdl, mut cancel_rx) = self.state().new_cancel_handle();
-        let handler = {
-            let cancel_id = cancel_hdl.id();
-            move |this: &mut Self, param: F::Output| {
-                //删除取消ID成功说明任务没有被取消
-                if this.state().remove_cancel_id(cancel_id) {
-                    handler(this, param);
-                } else {
-                    #[cfg(feature = "trace")]
-                    {
-                        let loc = std::panic::Location::caller();
-                        trace!("Task was cancelled ({}:{})", loc.file(), loc.line());
-                    }
-                }
-            }
-        };
-
-        //封装异步任务
-        let fut = async move {
-            tokio::pin! {
-            let fut = AssertUnwindSafe(fut).catch_unwind();
-            }
-            loop {
-                tokio::select! {
-                    rv = &mut fut => {
-                        match rv {
-                            Ok(rv) => {
-                                //检查取消信号
-                                if cancel_rx.try_recv().is_ok() {
-                                    #[cfg(feature = "trace")]
-                                    {
-                                        let loc = std::panic::Location::caller();
-                                        trace!("Task was cancelled ({}:{})", loc.file(), loc.line());
-                                    }
-                                    break;
-                                }
-                                let _ = invoker.invoke(rv, handler).await;
-                            },
-                            Err(e) => {
-                                let panic_info = match e.downcast_ref::<String>() {
-                                    Some(e) => &e,
-                                    None => {
-                                        match e.downcast_ref::<&'static str>() {
-                                            Some(e) => e,
-                                            None => "unknown"
-                                        }
-                                    },
-                                };
-                                invoker
-                                    .panic(panic_info)
-                                    .await;
-                            }
-                        }
-                        break;
-                    },
-                    _ = &mut cancel_rx => {
-                        #[cfg(feature = "trace")]
-                        {
-                            let loc = std::panic::Location::caller();
-                            trace!("Task was cancelled ({}:{})", loc.file(), loc.line());
-                        }
-                        break
-                    },
-                }
-            }
-        };
-
-        //执行
-        runtime::spawn(fut);
+        spawn_task(self, None, fut, handler)
+    }
 
-        cancel_hdl
+    /// 启动一个带分组标签的异步任务
+    ///
+    /// # Parameters
+    ///
+    /// - `tag` 分组标签，通过`HandlerState::cancel_tag`取消同一分组的所有任务
+    /// - `fut` 异步任务
+    /// - `handler` 接收`fut`执行结果并在当前(UI)线程中执行
+    ///
+    /// # Returns
+    ///
+    /// `CancelHandle` 任务取消句柄
+    #[cfg_attr(feature = "trace", track_caller)]
+    fn spawn_tagged<F, H>(&self, tag: impl Into<String>, fut: F, handler: H) -> CancelHandle
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+        H: FnOnce(&mut Self, F::Output) + Send + 'static
+    {
+        spawn_task(self, Some(tag.into()), fut, handler)
     }
 
     /// 阻塞启动一个异步任务
@@ -167,6 +216,89 @@ pub trait Handler: Sized + 'static {
     }
 }
 
+/// 启动一个异步任务
+#[cfg_attr(feature = "trace", track_caller)]
+fn spawn_task<T, F, H>(this: &T, tag: Option<String>, fut: F, handler: H) -> CancelHandle
+where
+    T: Handler,
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+    H: FnOnce(&mut T, F::Output) + Send + 'static
+{
+    let invoker = this.invoker();
+    let (cancel_hdl, mut cancel_rx) = this.state().new_cancel_handle(tag);
+    let handler = {
+        let cancel_id = cancel_hdl.id();
+        move |this: &mut T, param: F::Output| {
+            //删除取消ID成功说明任务没有被取消
+            if this.state().remove_cancel_id(cancel_id) {
+                handler(this, param);
+            } else {
+                #[cfg(feature = "trace")]
+                {
+                    let loc = std::panic::Location::caller();
+                    trace!("Task was cancelled ({}:{})", loc.file(), loc.line());
+                }
+            }
+        }
+    };
+
+    //封装异步任务
+    let fut = async move {
+        tokio::pin! {
+        let fut = AssertUnwindSafe(fut).catch_unwind();
+        }
+        loop {
+            tokio::select! {
+                rv = &mut fut => {
+                    match rv {
+                        Ok(rv) => {
+                            //检查取消信号
+                            if cancel_rx.try_recv().is_ok() {
+                                #[cfg(feature = "trace")]
+                                {
+                                    let loc = std::panic::Location::caller();
+                                    trace!("Task was cancelled ({}:{})", loc.file(), loc.line());
+                                }
+                                break;
+                            }
+                            let _ = invoker.invoke(rv, handler).await;
+                        },
+                        Err(e) => {
+                            let panic_info = match e.downcast_ref::<String>() {
+                                Some(e) => &e,
+                                None => {
+                                    match e.downcast_ref::<&'static str>() {
+                                        Some(e) => e,
+                                        None => "unknown"
+                                    }
+                                },
+                            };
+                            invoker
+                                .panic(panic_info)
+                                .await;
+                        }
+                    }
+                    break;
+                },
+                _ = &mut cancel_rx => {
+                    #[cfg(feature = "trace")]
+                    {
+                        let loc = std::panic::Location::caller();
+                        trace!("Task was cancelled ({}:{})", loc.file(), loc.line());
+                    }
+                    break
+                },
+            }
+        }
+    };
+
+    //执行
+    runtime::spawn(fut);
+
+    cancel_hdl
+}
+
 /// 阻塞任务错误
 #[derive(Debug, thiserror::Error)]
 pub enum SpawnBlockingError {
@@ -177,24 +309,50 @@ pub enum SpawnBlockingError {
 /// 回调处理对象的状态
 pub struct HandlerState {
     session: Session,
-    mgr: Rc<RefCell<HandlerStateManager>>
+    mgr: Rc<RefCell<HandlerStateManager>>,
+    /// 创建对象的线程
+    owner_thread: ThreadId,
+    /// 创建对象的线程ID(`Win32`)
+    owner_thread_id: u32,
+    //所有对象销毁后释放运行时
+    _rt_ref: runtime::RuntimeRef
 }
 
 impl HandlerState {
     pub fn new(session: Session) -> Self {
+        let mgr: Rc<RefCell<HandlerStateManager>> = Default::default();
+        //登记到当前线程，关闭运行时前统一取消
+        HANDLER_STATES.with(|states| {
+            let mut states = states.borrow_mut();
+            states.retain(|state| state.strong_count() > 0);
+            states.push(Rc::downgrade(&mgr));
+        });
         HandlerState {
             session,
-            mgr: Default::default()
+            mgr,
+            owner_thread: thread::current().id(),
+            owner_thread_id: unsafe { GetCurrentThreadId() },
+            _rt_ref: runtime::RuntimeRef::acquire()
         }
     }
 
     /// PB会话
     fn session(&self) -> &Session { &self.session }
 
+    /// 创建对象的线程ID(`Win32`)
+    pub fn owner_thread_id(&self) -> u32 { self.owner_thread_id }
+
+    /// 当前线程是否为创建对象的线程
+    ///
+    /// # Description
+    ///
+    /// 对象只能在创建它的线程(PB会话)中使用，在其它线程中启动的异步任务会将回调派发到错误的同步上下文
+    pub fn is_owner_thread(&self) -> bool { self.owner_thread == thread::current().id() }
+
     /// 新建一个异步任务取消句柄
-    fn new_cancel_handle(&self) -> (CancelHandle, oneshot::Receiver<()>) {
+    fn new_cancel_handle(&self, tag: Option<String>) -> (CancelHandle, oneshot::Receiver<()>) {
         let mut mgr = self.mgr.borrow_mut();
-        let (id, rx) = mgr.new_cancel_id();
+        let (id, rx) = mgr.new_cancel_id(tag);
         drop(mgr);
         (
             CancelHandle {
@@ -211,54 +369,192 @@ impl HandlerState {
         let mut mgr = self.mgr.borrow_mut();
         mgr.remove_cancel(id)
     }
+
+    /// 设置运行时关闭前执行的清理过程
+    fn set_shutdown_hook(&self, hook: Box<dyn FnOnce()>) {
+        let mut mgr = self.mgr.borrow_mut();
+        mgr.shutdown_hook = Some(hook);
+    }
+
+    /// 取消指定分组标签的所有异步任务
+    ///
+    /// # Returns
+    ///
+    /// 被取消的任务数量
+    pub fn cancel_tag(&self, tag: &str) -> usize {
+        let mut mgr = self.mgr.borrow_mut();
+        mgr.cancel_tag(tag)
+    }
+
+    /// 取消所有未完成的异步任务
+    ///
+    /// # Returns
+    ///
+    /// 被取消的任务数量
+    pub fn cancel_all(&self) -> usize {
+        let mut mgr = self.mgr.borrow_mut();
+        mgr.cancel_all()
+    }
+
+    /// 暂停回调
+    ///
+    /// # Description
+    ///
+    /// 暂停期间到达的回调被缓存，异步任务等待回调执行完成
+    pub fn suspend_events(&self) {
+        let mut mgr = self.mgr.borrow_mut();
+        mgr.events_suspended = true;
+    }
+
+    /// 恢复回调并按顺序执行缓存的回调
+    ///
+    /// # Returns
+    ///
+    /// 执行的回调数量
+    pub fn resume_events(&self) -> usize {
+        let mut mgr = self.mgr.borrow_mut();
+        mgr.events_suspended = false;
+        drop(mgr);
+        self.flush_events()
+    }
+
+    /// 按顺序执行缓存的回调并保持暂停状态
+    ///
+    /// # Returns
+    ///
+    /// 执行的回调数量
+    pub fn flush_events(&self) -> usize {
+        let mut mgr = self.mgr.borrow_mut();
+        //回调中重入
+        if mgr.events_flushing {
+            return 0;
+        }
+        mgr.events_flushing = true;
+        drop(mgr);
+        let mut count = 0;
+        loop {
+            //回调过程中可能访问状态，不能持有借用
+            let event = self.mgr.borrow_mut().held_events.pop_front();
+            match event {
+                Some(event) => {
+                    event(true);
+                    count += 1;
+                },
+                None => break
+            }
+        }
+        self.mgr.borrow_mut().events_flushing = false;
+        count
+    }
+
+    /// 是否暂停了回调
+    pub fn is_events_suspended(&self) -> bool { self.mgr.borrow().events_suspended }
+
+    /// 缓存的回调数量
+    pub fn held_event_count(&self) -> usize { self.mgr.borrow().held_events.len() }
+
+    /// 是否需要缓存回调
+    ///
+    /// # Description
+    ///
+    /// 执行缓存的回调期间新到达的回调也需要排在后面
+    fn is_holding_events(&self) -> bool {
+        let mgr = self.mgr.borrow();
+        mgr.events_suspended || mgr.events_flushing
+    }
+
+    /// 缓存回调
+    fn hold_event(&self, event: Box<dyn FnOnce(bool)>) {
+        let mut mgr = self.mgr.borrow_mut();
+        mgr.held_events.push_back(event);
+    }
 }
 
 /// 异步任务状态管理器
 #[derive(Default)]
 struct HandlerStateManager {
     next_id: u64,
-    pending: Vec<(u64, oneshot::Sender<()>)>
+    pending: HashMap<u64, PendingTask>,
+    //下次清理失效元素的阈值
+    purge_threshold: usize,
+    shutdown_hook: Option<Box<dyn FnOnce()>>,
+    //暂停事件
+    events_suspended: bool,
+    //正在执行缓存的回调
+    events_flushing: bool,
+    //缓存的回调，参数为`false`时丢弃
+    held_events: VecDeque<Box<dyn FnOnce(bool)>>
+}
+
+/// 未完成的任务
+struct PendingTask {
+    tag: Option<String>,
+    tx: oneshot::Sender<()>
 }
 
 impl HandlerStateManager {
     /// 新建取消ID
-    fn new_cancel_id(&mut self) -> (u64, oneshot::Receiver<()>) {
+    fn new_cancel_id(&mut self, tag: Option<String>) -> (u64, oneshot::Receiver<()>) {
         let id = self.next_id;
         self.next_id += 1;
         let (tx, rx) = oneshot::channel();
-        //优先覆盖失效的元素(任务Panic后残留)
-        if let Some(idx) = self.pending.iter().position(|(_, tx)| tx.is_closed()) {
-            self.pending[idx] = (id, tx);
-        } else {
-            self.pending.push((id, tx));
+        //清理失效的元素(任务Panic后残留)，阈值随数量倍增以均摊开销
+        if self.pending.len() >= self.purge_threshold {
+            self.pending.retain(|_, task| !task.tx.is_closed());
+            self.purge_threshold = (self.pending.len() * 2).max(default::PURGE_THRESHOLD);
         }
+        self.pending.insert(id, PendingTask {
+            tag,
+            tx
+        });
         (id, rx)
     }
 
     /// 取消任务
     fn cancel(&mut self, id: u64) -> bool {
-        if let Some(idx) = self.pending.iter().position(|item| item.0 == id) {
-            let (_, tx) = self.pending.remove(idx);
-            let _ = tx.send(());
+        if let Some(task) = self.pending.remove(&id) {
+            let _ = task.tx.send(());
             true
         } else {
             false
         }
     }
 
+    /// 取消指定分组标签的任务
+    fn cancel_tag(&mut self, tag: &str) -> usize {
+        let ids: Vec<u64> = self
+            .pending
+            .iter()
+            .filter(|(_, task)| task.tag.as_deref() == Some(tag))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids.iter() {
+            self.cancel(*id);
+        }
+        ids.len()
+    }
+
     /// 删除取消通道
-    fn remove_cancel(&mut self, id: u64) -> bool {
-        let len = self.pending.len();
-        self.pending.retain(|item| item.0 != id);
-        len != self.pending.len()
+    fn remove_cancel(&mut self, id: u64) -> bool { self.pending.remove(&id).is_some() }
+
+    /// 是否存在未完成的任务
+    fn contains(&self, id: u64) -> bool { self.pending.contains_key(&id) }
+
+    /// 取消所有未完成的任务
+    fn cancel_all(&mut self) -> usize {
+        let count = self.pending.len();
+        for (_, task) in self.pending.drain() {
+            let _ = task.tx.send(());
+        }
+        count
     }
 }
 
 impl Drop for HandlerStateManager {
     fn drop(&mut self) {
-        //取消所有未完成的任务
-        while let Some((_, tx)) = self.pending.pop() {
-            let _ = tx.send(());
+        self.cancel_all();
+        for event in self.held_events.drain(..) {
+            event(false);
         }
     }
 }
@@ -283,6 +579,16 @@ impl CancelHandle {
         }
     }
 
+    /// 任务是否仍未完成(未被取消且结果未被处理)
+    pub fn is_pending(&self) -> bool {
+        if let Some(mgr) = self.mgr.upgrade() {
+            let mgr = mgr.borrow();
+            mgr.contains(self.id)
+        } else {
+            false
+        }
+    }
+
     /// 取消ID
     fn id(&self) -> u64 { self.id }
 }
@@ -321,6 +627,41 @@ impl<T: Handler> HandlerInvoker<T> {
     ///
     /// 通过`InvokeJoinHandle`获取`handler`返回值
     pub async fn invoke<P, H, R>(&self, param: P, handler: H) -> InvokeJoinHandle<R>
+    where
+        P: Send + 'static,
+        H: FnOnce(&mut T, P) -> R + Send + 'static,
+        R: Send + 'static
+    {
+        self.invoke_impl(None, param, handler).await
+    }
+
+    /// 发起可合并的回调请求给UI线程执行
+    ///
+    /// # Description
+    ///
+    /// 消息队列采用`OverflowPolicy::Coalesce`策略时，队列已满会替换此对象相同`key`的未处理请求，
+    /// 被替换的请求返回`InvokeError::Dropped`，适用于进度通知等只关心最新状态的回调
+    ///
+    /// # Parameters
+    ///
+    /// - `key` 合并键
+    /// - `param` 参数
+    /// - `handler` 接收`param`参数的回调过程并在UI线程中执行
+    ///
+    /// # Returns
+    ///
+    /// 通过`InvokeJoinHandle`获取`handler`返回值
+    pub async fn invoke_coalesced<P, H, R>(&self, key: u64, param: P, handler: H) -> InvokeJoinHandle<R>
+    where
+        P: Send + 'static,
+        H: FnOnce(&mut T, P) -> R + Send + 'static,
+        R: Send + 'static
+    {
+        self.invoke_impl(Some(key), param, handler).await
+    }
+
+    /// 发起回调请求给UI线程执行
+    async fn invoke_impl<P, H, R>(&self, key: Option<u64>, param: P, handler: H) -> InvokeJoinHandle<R>
     where
         P: Send + 'static,
         H: FnOnce(&mut T, P) -> R + Send + 'static,
@@ -328,27 +669,17 @@ impl<T: Handler> HandlerInvoker<T> {
     {
         assert_ne!(self.thread_id, thread::current().id());
         if self.alive.is_dead() {
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "log")]
             trace!("Object is dead");
             return InvokeJoinHandle(None);
         }
         let (tx, rx) = oneshot::channel();
-        let handler = unsafe {
-            let this = self.this.clone();
-            Box::new(move |param: UnsafeBox<()>, invoke: bool| {
-                let param = param.cast::<P>().unpack();
-                let rv = if invoke {
-                    let this = &mut *this.into_raw();
-                    Some(handler(this, param))
-                } else {
-                    None
-                };
-                let _ = tx.send(rv);
-            })
-        };
+        let handler = Self::wrap_handler(self.this.clone(), handler, tx);
         let param = UnsafeBox::pack(param).cast::<()>();
-        if !self.dsp.dispatch_invoke(param, handler, self.alive.clone()).await {
-            #[cfg(feature = "trace")]
+        //合并键限定在对象范围内
+        let key = key.map(|key| (self.this.as_raw() as usize, key));
+        if !self.dsp.dispatch_invoke(key, param, handler, self.alive.clone()).await {
+            #[cfg(feature = "log")]
             trace!("Dispatch invoke failed");
             return InvokeJoinHandle(None);
         }
@@ -377,37 +708,68 @@ impl<T: Handler> HandlerInvoker<T> {
     {
         assert_ne!(self.thread_id, thread::current().id());
         if self.alive.is_dead() {
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "log")]
             trace!("Object is dead");
             return InvokeJoinHandle(None);
         }
         let (tx, rx) = oneshot::channel();
-        let handler = unsafe {
-            let this = self.this.clone();
-            Box::new(move |param: UnsafeBox<()>, invoke: bool| {
-                let param = param.cast::<P>().unpack();
-                let rv = if invoke {
-                    let this = &mut *this.into_raw();
-                    Some(handler(this, param))
-                } else {
-                    None
-                };
-                let _ = tx.send(rv);
-            })
-        };
+        let handler = Self::wrap_handler(self.this.clone(), handler, tx);
         let param = UnsafeBox::pack(param).cast::<()>();
         if !self.dsp.dispatch_invoke_blocking(param, handler, self.alive.clone()) {
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "log")]
             trace!("Dispatch invoke failed");
             return InvokeJoinHandle(None);
         }
         InvokeJoinHandle(Some(rx))
     }
 
+    /// 封装回调过程
+    fn wrap_handler<P, H, R>(
+        this: UnsafePointer<T>,
+        handler: H,
+        tx: oneshot::Sender<Result<R, InvokeError>>
+    ) -> Box<dyn FnOnce(UnsafeBox<()>, Delivery) + Send + 'static>
+    where
+        P: Send + 'static,
+        H: FnOnce(&mut T, P) -> R + Send + 'static,
+        R: Send + 'static
+    {
+        Box::new(move |param: UnsafeBox<()>, delivery: Delivery| {
+            let param = unsafe { param.cast::<P>().unpack() };
+            let rv = match delivery {
+                Delivery::Invoke => {
+                    let state = unsafe { &*this.as_raw() }.state();
+                    if state.is_holding_events() {
+                        //暂停期间缓存回调，恢复后按顺序执行
+                        state.hold_event(Box::new(move |invoke: bool| {
+                            let rv = if invoke {
+                                Ok(handler(unsafe { &mut *this.into_raw() }, param))
+                            } else {
+                                Err(InvokeError::TargetIsDead)
+                            };
+                            let _ = tx.send(rv);
+                        }));
+                        return;
+                    }
+                    let this = unsafe { &mut *this.into_raw() };
+                    Ok(handler(this, param))
+                },
+                Delivery::Discard => Err(InvokeError::TargetIsDead),
+                Delivery::Dropped => Err(InvokeError::Dropped)
+            };
+            let _ = tx.send(rv);
+        })
+    }
+
     /// 派发执行异常信息给UI线程
     async fn panic(&self, panic_info: &str) -> bool {
         self.dsp
-            .dispatch_panic(format!("{}\r\nbacktrace:\r\n{:?}", panic_info, backtrace::Backtrace::new()))
+            .dispatch_panic(format!(
+                "{}\r\n{}:\r\n{:?}",
+                panic_info,
+                tr("backtrace", "调用栈"),
+                backtrace::Backtrace::new()
+            ))
             .await
     }
 
@@ -418,8 +780,9 @@ impl<T: Handler> HandlerInvoker<T> {
     /// 在非异步上下文中使用
     fn panic_blocking(&self, panic_info: &str) -> bool {
         self.dsp.dispatch_panic_blocking(format!(
-            "{}\r\nbacktrace:\r\n{:?}",
+            "{}\r\n{}:\r\n{:?}",
             panic_info,
+            tr("backtrace", "调用栈"),
             backtrace::Backtrace::new()
         ))
     }
@@ -437,15 +800,14 @@ impl<T> Clone for HandlerInvoker<T> {
 }
 
 /// UI线程调用返回值接收句柄
-pub struct InvokeJoinHandle<T>(Option<oneshot::Receiver<Option<T>>>);
+pub struct InvokeJoinHandle<T>(Option<oneshot::Receiver<Result<T, InvokeError>>>);
 
 impl<T> InvokeJoinHandle<T> {
     pub fn join(self) -> Result<T, InvokeError> {
         match self.0 {
             Some(rx) => {
                 match rx.blocking_recv() {
-                    Ok(Some(rv)) => Ok(rv),
-                    Ok(None) => Err(InvokeError::TargetIsDead),
+                    Ok(rv) => rv,
                     //NOTE 回调过程发生异常导致`tx`被提前销毁
                     Err(_) => Err(InvokeError::Panic)
                 }
@@ -462,8 +824,7 @@ impl<T> Future for InvokeJoinHandle<T> {
         match &mut this.0 {
             Some(rx) => {
                 match ready!(Pin::new(rx).poll(cx)) {
-                    Ok(Some(rv)) => Poll::Ready(Ok(rv)),
-                    Ok(None) => Poll::Ready(Err(InvokeError::TargetIsDead)),
+                    Ok(rv) => Poll::Ready(rv),
                     //NOTE 回调过程发生异常导致`tx`被提前销毁
                     Err(_) => Poll::Ready(Err(InvokeError::Panic))
                 }
@@ -478,6 +839,14 @@ impl<T> Future for InvokeJoinHandle<T> {
 pub enum InvokeError {
     #[error("target is dead")]
     TargetIsDead,
+    #[error("dropped by dispatch queue")]
+    Dropped,
     #[error("panic")]
     Panic
 }
+
+/// 默认配置
+mod default {
+    /// 清理失效任务的最小阈值
+    pub const PURGE_THRESHOLD: usize = 64;
+}
@@ -0,0 +1,186 @@
+//! 后台文件任务
+//!
+use super::handler::{CancelHandle, Handler, HandlerInvoker, InvokeError};
+use crate::prelude::RetCode;
+use std::{
+    io, sync::{
+        atomic::{AtomicBool, Ordering}, Arc
+    }, time::{Duration, Instant}
+};
+
+/// 进度事件的最小间隔
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 后台任务槽
+///
+/// # Description
+///
+/// 同一对象同时只执行一个后台任务，取消后重新启动的任务不受旧任务完成回调的影响
+#[derive(Default)]
+pub struct JobSlot {
+    running: Option<Running>,
+    job_id: u64
+}
+
+/// 执行中的后台任务
+struct Running {
+    cancelled: Arc<AtomicBool>,
+    cancel_hdl: CancelHandle
+}
+
+impl JobSlot {
+    /// 是否有执行中的任务
+    pub fn is_running(&self) -> bool { self.running.is_some() }
+
+    /// 取消执行中的任务
+    ///
+    /// # Returns
+    ///
+    /// 没有执行中的任务时返回`false`
+    pub fn cancel(&mut self) -> bool {
+        match self.running.take() {
+            Some(running) => {
+                running.cancelled.store(true, Ordering::Relaxed);
+                running.cancel_hdl.cancel();
+                true
+            },
+            None => false
+        }
+    }
+}
+
+/// 后台任务的执行上下文，在阻塞线程中使用
+pub struct JobContext<T> {
+    cancelled: Arc<AtomicBool>,
+    invoker: HandlerInvoker<T>,
+    last_progress: Instant
+}
+
+impl<T: Handler> JobContext<T> {
+    /// 是否已取消
+    pub fn is_cancelled(&self) -> bool { self.cancelled.load(Ordering::Relaxed) }
+
+    /// 取消标志，用于需要在多个线程中检查取消的任务
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> { self.cancelled.clone() }
+
+    /// 在UI线程中执行事件并等待返回
+    ///
+    /// # Returns
+    ///
+    /// 返回`false`时中止任务：已取消、事件返回`PREVENT`或对象已销毁
+    pub fn invoke<P, H>(&self, param: P, handler: H) -> bool
+    where
+        P: Send + 'static,
+        H: FnOnce(&mut T, P) -> RetCode + Send + 'static
+    {
+        if self.is_cancelled() {
+            return false;
+        }
+        let rv = self.invoker.invoke_blocking(param, handler).join();
+        !matches!(rv, Ok(RetCode::PREVENT) | Err(InvokeError::TargetIsDead))
+    }
+
+    /// 通知进度
+    ///
+    /// # Description
+    ///
+    /// 间隔小于`PROGRESS_INTERVAL`时跳过(完成时除外)，`handler`参数为`(total, processed)`
+    ///
+    /// # Returns
+    ///
+    /// 同`invoke`
+    pub fn progress<H>(&mut self, total: u64, processed: u64, handler: H) -> bool
+    where
+        H: FnOnce(&mut T, (u64, u64)) -> RetCode + Send + 'static
+    {
+        self.progress_with(total, processed, (total, processed), handler)
+    }
+
+    /// 通知进度并传递自定义参数
+    ///
+    /// # Description
+    ///
+    /// 同`progress`，`param`只在需要通知时传递给`handler`
+    pub fn progress_with<P, H>(&mut self, total: u64, processed: u64, param: P, handler: H) -> bool
+    where
+        P: Send + 'static,
+        H: FnOnce(&mut T, P) -> RetCode + Send + 'static
+    {
+        if self.is_cancelled() {
+            return false;
+        }
+        if processed < total && self.last_progress.elapsed() < PROGRESS_INTERVAL {
+            return true;
+        }
+        self.last_progress = Instant::now();
+        self.invoke(param, handler)
+    }
+}
+
+/// 启动后台文件任务
+///
+/// # Parameters
+///
+/// - `slot` 对象的任务槽
+/// - `job` 在阻塞线程中执行
+/// - `complete` 在UI线程中接收执行结果，执行前任务槽已释放
+///
+/// # Returns
+///
+/// 已有执行中的任务时返回`E_BUSY`
+///
+/// # Cancellation
+///
+/// - 通过`JobSlot::cancel`手动取消，`job`通过`JobContext`检查取消标志
+/// - 对象销毁时自动取消
+pub fn spawn_file_job<T, R, J, H>(
+    this: &mut T,
+    slot: fn(&mut T) -> &mut JobSlot,
+    job: J,
+    complete: H
+) -> RetCode
+where
+    T: Handler,
+    R: Send + 'static,
+    J: FnOnce(&mut JobContext<T>) -> io::Result<R> + Send + 'static,
+    H: FnOnce(&mut T, io::Result<R>) + Send + 'static
+{
+    if slot(this).is_running() {
+        return RetCode::E_BUSY;
+    }
+    let job_id = {
+        let slot = slot(this);
+        slot.job_id += 1;
+        slot.job_id
+    };
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let mut ctx = JobContext {
+        cancelled: cancelled.clone(),
+        invoker: this.invoker(),
+        last_progress: Instant::now()
+    };
+    let cancel_hdl = this.spawn(
+        async move {
+            let task = tokio::task::spawn_blocking(move || {
+                ctx.last_progress = Instant::now();
+                job(&mut ctx)
+            });
+            match task.await {
+                Ok(rv) => rv,
+                Err(e) => Err(io::Error::new(io::ErrorKind::Other, e))
+            }
+        },
+        move |this, rv| {
+            let slot = slot(this);
+            if slot.job_id == job_id {
+                slot.running = None;
+            }
+            complete(this, rv);
+        }
+    );
+    slot(this).running = Some(Running {
+        cancelled,
+        cancel_hdl
+    });
+    RetCode::OK
+}
@@ -3,10 +3,144 @@
 #![allow(dead_code)]
 
 mod context;
+mod crash;
 pub mod runtime;
 mod handler;
 mod event;
+mod job;
 mod mem;
+mod watchdog;
 pub mod futures;
 
+pub use context::{set_queue_config, OverflowPolicy};
+pub use crash::{set_crash_report, CrashConfig};
+pub use event::Win32Event;
 pub use handler::{CancelHandle, Handler, HandlerInvoker, HandlerState, InvokeError};
+pub use job::{spawn_file_job, JobContext, JobSlot, PROGRESS_INTERVAL};
+use std::{
+    cell::Cell, time::{Duration, Instant}
+};
+pub use watchdog::{clear_stall_handler, set_threshold as set_watchdog, StallReport};
+
+thread_local! {
+static QUIT_EVENT_LOOP: Cell<bool> = Cell::new(false);
+}
+
+/// 运行时统计
+#[derive(Debug, Clone)]
+pub struct RuntimeStats {
+    /// 后台运行时是否在运行
+    pub running: bool,
+    /// 同步上下文数量
+    pub contexts: usize,
+    /// 当前线程积压的回调消息数量
+    pub pending_messages: Option<usize>,
+    /// 当前线程积压的回调消息的估算内存(字节)
+    pub pending_bytes: Option<usize>,
+    /// 当前线程未完成的异步任务数量
+    pub pending_tasks: Option<usize>,
+    /// 当前线程存活的异步对象数量
+    pub handlers: Option<usize>
+}
+
+/// 获取运行时统计
+pub fn stats() -> RuntimeStats {
+    let (contexts, pending_messages) = context::stats();
+    RuntimeStats {
+        running: runtime::is_running(),
+        contexts,
+        pending_messages,
+        pending_bytes: pending_messages.map(|count| count * context::message_size()),
+        pending_tasks: handler::pending_tasks(),
+        handlers: handler::handler_count()
+    }
+}
+
+/// 在期限内优雅地销毁后台运行时
+///
+/// # Description
+///
+/// 1. 执行对象注册的关闭过程并取消当前线程所有未完成的异步任务
+/// 2. 处理UI线程积压的回调消息
+/// 3. 等待后台运行时退出
+///
+/// # Returns
+///
+/// 在期限内完成返回`true`
+pub fn shutdown_timeout(timeout: Duration) -> bool {
+    handler::shutdown_all();
+    let sync_ctx = context::SyncContext::try_current();
+    let mut process_message = || {
+        if let Some(sync_ctx) = &sync_ctx {
+            sync_ctx.process_message();
+        }
+    };
+    process_message();
+    runtime::shutdown_timeout(timeout, process_message)
+}
+
+/// 事件循环的退出原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventLoopExit {
+    /// 当前线程所有异步任务已完成
+    Idle,
+    /// 调用了`quit_event_loop`
+    Quit,
+    /// 超时
+    Timeout
+}
+
+/// 在当前线程运行事件循环
+///
+/// # Description
+///
+/// 用于没有消息循环的后台会话(如PowerServer、批处理程序)，处理异步任务的回调消息直到当前线程所有异步任务完成、
+/// 调用`quit_event_loop`或超时
+///
+/// **回调必须在对象所属的线程中执行，所以事件循环运行在调用线程中**
+pub fn run_event_loop(timeout: Option<Duration>) -> EventLoopExit {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    QUIT_EVENT_LOOP.with(|quit| quit.set(false));
+    loop {
+        if let Some(sync_ctx) = context::SyncContext::try_current() {
+            sync_ctx.process_message();
+        }
+        if QUIT_EVENT_LOOP.with(|quit| quit.replace(false)) {
+            return EventLoopExit::Quit;
+        }
+        if handler::pending_tasks().unwrap_or_default() == 0 {
+            return EventLoopExit::Idle;
+        }
+        let wait = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return EventLoopExit::Timeout;
+                }
+                (deadline - now).min(default::EVENT_LOOP_INTERVAL)
+            },
+            None => default::EVENT_LOOP_INTERVAL
+        };
+        match context::SyncContext::try_current() {
+            Some(sync_ctx) => {
+                sync_ctx.wait_message(wait);
+            },
+            None => std::thread::sleep(wait)
+        }
+    }
+}
+
+/// 退出当前线程的事件循环
+///
+/// # Description
+///
+/// 在回调过程中调用，`run_event_loop`处理完当前消息后返回
+pub fn quit_event_loop() { QUIT_EVENT_LOOP.with(|quit| quit.set(true)); }
+
+/// 默认配置
+mod default {
+    use std::time::Duration;
+
+    /// 事件循环检查任务状态的间隔
+    pub const EVENT_LOOP_INTERVAL: Duration = Duration::from_millis(100);
+}
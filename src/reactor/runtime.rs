@@ -1,11 +1,48 @@
 use std::{
-    future::Future, panic, pin::Pin, sync::Mutex, thread::{self, JoinHandle}, time::Duration
+    ffi::c_void, future::Future, panic, pin::Pin, sync::{
+        atomic::{AtomicUsize, Ordering}, Mutex
+    }, thread, time::{Duration, Instant}
 };
 use tokio::{
-    runtime, sync::{mpsc, mpsc::UnboundedReceiver, oneshot}, task
+    runtime, sync::{
+        mpsc, mpsc::UnboundedReceiver, oneshot::{self, error::TryRecvError}
+    }, task
+};
+use windows::Win32::{
+    Foundation::{CloseHandle, HANDLE, HMODULE, WAIT_TIMEOUT}, System::{
+        LibraryLoader::{FreeLibrary, FreeLibraryAndExitThread}, Threading::{CreateThread, WaitForSingleObject, THREAD_CREATION_FLAGS}
+    }
 };
 
 static GLOBAL_RUNTIME: Mutex<Option<Runtime>> = Mutex::new(None);
+static REF_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// 运行时引用
+///
+/// # Description
+///
+/// 所有引用释放后关闭消息通道，后台线程在完成剩余的异步任务后自行退出，不阻塞当前线程
+pub struct RuntimeRef(());
+
+impl RuntimeRef {
+    /// 增加运行时引用
+    pub fn acquire() -> Self {
+        let _runtime = GLOBAL_RUNTIME.lock().expect("Lock runtime failed");
+        REF_COUNT.fetch_add(1, Ordering::Relaxed);
+        RuntimeRef(())
+    }
+}
+
+impl Drop for RuntimeRef {
+    fn drop(&mut self) {
+        let mut runtime = GLOBAL_RUNTIME.lock().expect("Lock runtime failed");
+        if REF_COUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            if let Some(runtime) = runtime.take() {
+                runtime.detach();
+            }
+        }
+    }
+}
 
 /// 在后台执行一个异步任务
 #[cfg_attr(feature = "trace", track_caller)]
@@ -25,12 +62,65 @@ where
     runtime_tx.send(msg).expect("Send message to runtime failed");
 }
 
+/// 后台运行时是否在运行
+///
+/// # Description
+///
+/// 不会阻塞，运行时被锁定时视为在运行
+pub fn is_running() -> bool { GLOBAL_RUNTIME.try_lock().map(|runtime| runtime.is_some()).unwrap_or(true) }
+
 /// 销毁后台运行时
+///
+/// # Description
+///
+/// 等待所有异步任务结束
 pub fn shutdown() {
     let mut runtime = GLOBAL_RUNTIME.lock().expect("Lock runtime failed");
     *runtime = None;
 }
 
+/// 在期限内销毁后台运行时
+///
+/// # Parameters
+///
+/// - `timeout` 等待运行时退出的期限
+/// - `idle` 等待过程中反复执行(如处理UI线程积压的回调消息)
+///
+/// # Returns
+///
+/// 运行时在期限内退出返回`true`，超时则放弃等待并返回`false`
+pub fn shutdown_timeout(timeout: Duration, mut idle: impl FnMut()) -> bool {
+    let runtime = GLOBAL_RUNTIME.lock().expect("Lock runtime failed").take();
+    let mut runtime = match runtime {
+        Some(runtime) => runtime,
+        None => return true
+    };
+
+    //关闭消息通道
+    drop(runtime.msg_tx.take());
+
+    let deadline = Instant::now() + timeout;
+    let mut stop_rx = runtime.stop_rx.take().unwrap();
+    loop {
+        match stop_rx.try_recv() {
+            Ok(_) | Err(TryRecvError::Closed) => break,
+            Err(TryRecvError::Empty) => {
+                if Instant::now() >= deadline {
+                    //放弃等待，后台线程随进程退出
+                    drop(runtime.thrd_hdl.take());
+                    #[cfg(feature = "log")]
+                    warn!("Runtime shutdown timed out");
+                    return false;
+                }
+                idle();
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    true
+}
+
 /// 异步任务
 #[cfg(feature = "trace")]
 struct Task(Pin<Box<dyn Future<Output = ()> + Send + 'static>>, &'static panic::Location<'static>);
@@ -39,7 +129,7 @@ struct Task(Pin<Box<dyn Future<Output = ()> + Send + 'static>>);
 
 /// 运行时
 struct Runtime {
-    thrd_hdl: Option<JoinHandle<()>>,
+    thrd_hdl: Option<ThreadHandle>,
     msg_tx: Option<mpsc::UnboundedSender<Task>>,
     stop_rx: Option<oneshot::Receiver<()>>
 }
@@ -94,7 +184,8 @@ impl Runtime {
         //Console
         let (console, server) = console_subscriber::Builder::default().build();
 
-        tracing_subscriber::registry().with(file).with(dbg).with(console).init();
+        //可能已经通过`pfwxSetLog`安装了订阅器
+        let _ = tracing_subscriber::registry().with(file).with(dbg).with(console).try_init();
 
         Self::startup_with_trace(server)
     }
@@ -156,9 +247,8 @@ impl Runtime {
         let runloop = new_runloop(msg_rx);
 
         //创建后台线程
-        let thrd_hdl = thread::Builder::new()
-            .name("bkgnd-rt".to_owned())
-            .spawn(move || {
+        let thrd_hdl = ThreadHandle::spawn(move || {
+            {
                 //单线程运行时
                 let rt = runtime::Builder::new_current_thread().enable_all().build().unwrap();
                 let local = task::LocalSet::new();
@@ -168,10 +258,10 @@ impl Runtime {
                 //NOTE
                 //运行时可能创建了`blocking`后台线程，此处需要立即退出并且不等待线程结束信号
                 rt.shutdown_background();
-                //退出信号
-                let _ = stop_tx.send(());
-            })
-            .expect("Create runtime thread");
+            }
+            //退出信号
+            let _ = stop_tx.send(());
+        });
 
         Runtime {
             thrd_hdl: Some(thrd_hdl),
@@ -181,26 +271,125 @@ impl Runtime {
     }
 }
 
+impl Runtime {
+    /// 增加当前模块的引用计数
+    fn module_ref() -> Option<HMODULE> {
+        use windows::{core::PCWSTR, Win32::System::LibraryLoader::*};
+
+        let mut hmod = HMODULE::default();
+        unsafe {
+            if GetModuleHandleExW(
+                GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+                PCWSTR::from_raw(Self::module_ref as *const u16),
+                &mut hmod
+            ) == true
+            {
+                Some(hmod)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// 关闭消息通道并且不等待后台线程退出
+    fn detach(mut self) {
+        drop(self.msg_tx.take());
+        drop(self.stop_rx.take());
+        drop(self.thrd_hdl.take());
+    }
+}
+
 impl Drop for Runtime {
     fn drop(&mut self) {
-        use std::os::windows::prelude::*;
-        use windows::Win32::{
-            Foundation::{HANDLE, WAIT_TIMEOUT}, System::Threading::WaitForSingleObject
-        };
-
         //关闭消息通道
         drop(self.msg_tx.take());
 
         //检查线程是否存活，可能提前被`ExitProcess`销毁
-        let thrd_hdl = self.thrd_hdl.take().unwrap();
-        let rc = unsafe { WaitForSingleObject(HANDLE(thrd_hdl.as_raw_handle() as _), 0) };
-        if rc == WAIT_TIMEOUT {
+        let thrd_hdl = match self.thrd_hdl.take() {
+            Some(thrd_hdl) => thrd_hdl,
+            //已放弃等待
+            None => return
+        };
+        if thrd_hdl.is_alive() {
             //NOTE 不能直接WAIT线程对象，因为此时可能正处于TLS销毁流程中，OS加了保护锁防止不同线程同时进入`DllMain`
             //issue: https://github.com/rust-lang/rust/issues/74875
-            let _ = self.stop_rx.take().unwrap().blocking_recv();
-            //FIXME
-            //短暂挂起使线程调用栈完全退出
-            thread::sleep(Duration::from_millis(200));
+            //后台线程通过`FreeLibraryAndExitThread`退出，收到退出信号后不需要等待线程调用栈完全退出
+            if let Some(stop_rx) = self.stop_rx.take() {
+                let _ = stop_rx.blocking_recv();
+            }
+        }
+    }
+}
+
+/// 后台线程句柄
+///
+/// # Description
+///
+/// 后台线程退出时通过`FreeLibraryAndExitThread`释放模块引用，该函数不会返回，
+/// 因此不能用于`std::thread`创建的线程(跳过了`std`的线程退出流程)，需要使用`CreateThread`创建原生线程
+struct ThreadHandle(HANDLE);
+
+impl ThreadHandle {
+    /// 创建后台线程
+    fn spawn<F>(f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static
+    {
+        struct Payload {
+            f: Box<dyn FnOnce() + Send>,
+            hmod: Option<HMODULE>
+        }
+
+        unsafe extern "system" fn thread_main(param: *mut c_void) -> u32 {
+            let Payload {
+                f,
+                hmod
+            } = *Box::from_raw(param as *mut Payload);
+            //不能让`panic`越过线程入口
+            let _ = panic::catch_unwind(panic::AssertUnwindSafe(f));
+            //释放模块引用并退出线程，此后不再执行模块中的代码
+            if let Some(hmod) = hmod {
+                FreeLibraryAndExitThread(hmod, 0);
+            }
+            0
+        }
+
+        //创建线程前持有模块引用，线程退出前模块不会被卸载
+        let param = Box::into_raw(Box::new(Payload {
+            f: Box::new(f),
+            hmod: Runtime::module_ref()
+        }));
+        match unsafe {
+            CreateThread(
+                None,
+                0,
+                Some(thread_main),
+                Some(param as *const c_void),
+                THREAD_CREATION_FLAGS(0),
+                None
+            )
+        } {
+            Ok(hdl) => ThreadHandle(hdl),
+            Err(e) => {
+                let payload = unsafe { Box::from_raw(param) };
+                if let Some(hmod) = payload.hmod {
+                    unsafe {
+                        FreeLibrary(hmod);
+                    }
+                }
+                panic!("Create runtime thread failed: {e}");
+            }
+        }
+    }
+
+    /// 线程是否存活，可能提前被`ExitProcess`销毁
+    fn is_alive(&self) -> bool { unsafe { WaitForSingleObject(self.0, 0) == WAIT_TIMEOUT } }
+}
+
+impl Drop for ThreadHandle {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
         }
     }
 }
@@ -0,0 +1,508 @@
+//! UI线程阻塞监视
+//!
+use super::context::MessageQueue;
+use std::{
+    cell::{Cell, RefCell}, ffi::c_void, fmt::Write, mem, rc::Rc, sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering}, Arc, Mutex, Weak
+    }, thread, time::Duration
+};
+use windows::Win32::{
+    Foundation::{CloseHandle, HANDLE}, System::{
+        Diagnostics::Debug::{GetThreadContext, CONTEXT}, Threading::{
+            GetCurrentThreadId, OpenThread, ResumeThread, SuspendThread, THREAD_GET_CONTEXT, THREAD_QUERY_INFORMATION, THREAD_SUSPEND_RESUME
+        }
+    }
+};
+
+//阈值(毫秒)，`0`表示不启用
+static THRESHOLD: AtomicU64 = AtomicU64::new(0);
+//监视线程是否在运行
+static MONITOR_RUNNING: AtomicBool = AtomicBool::new(false);
+//被监视的UI线程
+static WATCHED: Mutex<Vec<Watched>> = Mutex::new(Vec::new());
+//调用栈最大采样深度
+const MAX_FRAMES: usize = 64;
+//栈数据最大复制长度
+const MAX_STACK_COPY: usize = 1024 * 1024;
+//栈数据未复制完整时保留的展开余量
+const UNWIND_MARGIN: usize = 64 * 1024;
+
+thread_local! {
+static STALL_HANDLER: RefCell<Option<(u64, Rc<dyn Fn(&StallReport)>)>> = RefCell::new(None);
+static NEXT_HANDLER_ID: Cell<u64> = Cell::new(1);
+}
+
+/// UI线程阻塞报告
+#[derive(Debug, Clone)]
+pub struct StallReport {
+    /// 回调消息等待处理的时长
+    pub waited: Duration,
+    /// 积压的回调消息数量
+    pub pending: usize,
+    /// 阻塞期间UI线程的调用栈，未能采样时为`no sample`
+    pub backtrace: String
+}
+
+/// 被监视的UI线程
+struct Watched {
+    thread_id: u32,
+    thread: HANDLE,
+    queue: Weak<MessageQueue>,
+    //阻塞期间采样的调用栈`(消息序号, 调用栈)`，调用栈为空表示正在解析符号
+    sample: Option<(u64, String)>
+}
+
+/// 设置UI线程阻塞阈值
+///
+/// # Description
+///
+/// 回调消息等待处理的时长超过阈值时发出警告，`None`表示停止监视
+pub fn set_threshold(threshold: Option<Duration>) {
+    let ms = threshold.map(|v| (v.as_millis() as u64).max(1)).unwrap_or_default();
+    THRESHOLD.store(ms, Ordering::Relaxed);
+    if ms != 0 {
+        start_monitor();
+    }
+}
+
+/// UI线程阻塞阈值
+pub fn threshold() -> Option<Duration> {
+    match THRESHOLD.load(Ordering::Relaxed) {
+        0 => None,
+        ms => Some(Duration::from_millis(ms))
+    }
+}
+
+/// 取消当前线程的阻塞报告处理过程
+///
+/// # Returns
+///
+/// `id`为当前线程正在使用的处理过程时返回`true`，否则不做修改
+pub fn clear_stall_handler(id: u64) -> bool {
+    STALL_HANDLER.with(|current| {
+        let mut current = current.borrow_mut();
+        if current.as_ref().map_or(false, |(current_id, _)| *current_id == id) {
+            *current = None;
+            true
+        } else {
+            false
+        }
+    })
+}
+
+/// 设置当前线程的阻塞报告处理过程
+///
+/// # Returns
+///
+/// 处理过程的标识，用于`clear_stall_handler`
+///
+/// # Description
+///
+/// 在UI线程中执行，重复设置会覆盖之前的过程
+pub(super) fn set_stall_handler(handler: Rc<dyn Fn(&StallReport)>) -> u64 {
+    let id = NEXT_HANDLER_ID.with(|next| next.replace(next.get() + 1));
+    STALL_HANDLER.with(|current| *current.borrow_mut() = Some((id, handler)));
+    id
+}
+
+/// 登记当前(UI)线程的消息队列
+pub(super) fn register(queue: &Arc<MessageQueue>) {
+    let thread_id = unsafe { GetCurrentThreadId() };
+    let thread = match unsafe {
+        OpenThread(THREAD_SUSPEND_RESUME | THREAD_GET_CONTEXT | THREAD_QUERY_INFORMATION, false, thread_id)
+    } {
+        Ok(thread) => thread,
+        Err(e) => {
+            #[cfg(feature = "log")]
+            warn!("Watchdog failed to open UI thread: {}", e);
+            return;
+        }
+    };
+    WATCHED.lock().unwrap().push(Watched {
+        thread_id,
+        thread,
+        queue: Arc::downgrade(queue),
+        sample: None
+    });
+}
+
+/// 注销消息队列
+pub(super) fn unregister(queue: &Arc<MessageQueue>) {
+    let mut watched = WATCHED.lock().unwrap();
+    if let Some(idx) = watched.iter().position(|item| item.queue.as_ptr() == Arc::as_ptr(queue)) {
+        let item = watched.swap_remove(idx);
+        unsafe {
+            CloseHandle(item.thread);
+        }
+    }
+}
+
+/// 检查回调消息的等待时长
+///
+/// # Parameters
+///
+/// - `seq` 最早的消息序号
+/// - `waited` 最早的消息等待处理的时长
+/// - `pending` 积压的消息数量
+pub(super) fn check(seq: u64, waited: Duration, pending: usize) {
+    let threshold = match threshold() {
+        Some(threshold) => threshold,
+        None => return
+    };
+    if waited < threshold {
+        return;
+    }
+    let report = StallReport {
+        waited,
+        pending,
+        backtrace: take_sample(seq).unwrap_or_else(|| "no sample".to_owned())
+    };
+    #[cfg(feature = "log")]
+    warn!(
+        "UI thread was blocked for {}ms with {} pending messages\r\nbacktrace:\r\n{}",
+        report.waited.as_millis(),
+        report.pending,
+        report.backtrace
+    );
+    //处理过程中可能重入消息循环，不能持有借用
+    let handler = STALL_HANDLER.with(|current| current.borrow().as_ref().map(|(_, handler)| handler.clone()));
+    if let Some(handler) = handler {
+        handler(&report);
+    }
+}
+
+/// 取出当前线程阻塞期间采样的调用栈
+fn take_sample(seq: u64) -> Option<String> {
+    let thread_id = unsafe { GetCurrentThreadId() };
+    let mut watched = WATCHED.lock().unwrap();
+    let item = watched.iter_mut().find(|item| item.thread_id == thread_id)?;
+    match item.sample.take() {
+        Some((sample_seq, backtrace)) if sample_seq == seq && !backtrace.is_empty() => Some(backtrace),
+        sample => {
+            item.sample = sample;
+            None
+        }
+    }
+}
+
+/// 启动监视线程
+fn start_monitor() {
+    if MONITOR_RUNNING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    if thread::Builder::new().name("pfwx-watchdog".to_owned()).spawn(monitor).is_err() {
+        MONITOR_RUNNING.store(false, Ordering::Release);
+    }
+}
+
+/// 监视线程
+///
+/// # Description
+///
+/// 在UI线程阻塞期间挂起并采样调用栈，每条阻塞的消息只采样一次
+fn monitor() {
+    loop {
+        let threshold = match threshold() {
+            Some(threshold) => threshold,
+            None => {
+                MONITOR_RUNNING.store(false, Ordering::Release);
+                //退出前被重新启用
+                if threshold().is_some() && !MONITOR_RUNNING.swap(true, Ordering::AcqRel) {
+                    continue;
+                }
+                return;
+            }
+        };
+        thread::sleep((threshold / 4).clamp(Duration::from_millis(10), Duration::from_millis(250)));
+
+        //挂起期间只收集地址，恢复后再解析符号
+        let mut samples = Vec::new();
+        {
+            let mut watched = WATCHED.lock().unwrap();
+            for item in watched.iter_mut() {
+                let (seq, waited, pending) = match item.queue.upgrade().and_then(|queue| queue.backlog()) {
+                    Some(backlog) => backlog,
+                    None => continue
+                };
+                if waited < threshold ||
+                    item.sample.as_ref().map_or(false, |(sample_seq, _)| *sample_seq == seq)
+                {
+                    continue;
+                }
+                item.sample = Some((seq, String::new()));
+                samples.push((item.thread_id, seq, waited, pending, unsafe { sample_thread(item.thread) }));
+            }
+        }
+
+        for (thread_id, seq, waited, pending, frames) in samples {
+            let backtrace = format_frames(&frames);
+            #[cfg(feature = "log")]
+            warn!(
+                "UI thread #{} is blocked for {}ms with {} pending messages\r\nbacktrace:\r\n{}",
+                thread_id,
+                waited.as_millis(),
+                pending,
+                backtrace
+            );
+            #[cfg(not(feature = "log"))]
+            let _ = (waited, pending);
+            let mut watched = WATCHED.lock().unwrap();
+            if let Some(item) = watched.iter_mut().find(|item| item.thread_id == thread_id) {
+                if item.sample.as_ref().map_or(false, |(sample_seq, _)| *sample_seq == seq) {
+                    item.sample = Some((seq, backtrace));
+                }
+            }
+        }
+    }
+}
+
+/// 挂起线程并采样调用栈
+///
+/// # Returns
+///
+/// 各个栈帧的指令地址
+///
+/// # Description
+///
+/// 挂起期间只复制线程上下文与栈数据，恢复后再回溯栈帧
+///
+/// # Safety
+///
+/// 挂起期间不能分配内存或调用需要加锁的系统函数(如`RtlLookupFunctionEntry`)，目标线程可能持有锁
+unsafe fn sample_thread(thread: HANDLE) -> Vec<usize> {
+    let mut frames = Vec::with_capacity(MAX_FRAMES);
+    let mut stack = StackCopy::new();
+    if SuspendThread(thread) == u32::MAX {
+        return frames;
+    }
+    let mut ctx: CONTEXT = mem::zeroed();
+    ctx.ContextFlags = CONTEXT_FLAGS;
+    let captured = GetThreadContext(thread, &mut ctx).as_bool() && stack.capture(stack_pointer(&ctx));
+    ResumeThread(thread);
+    if captured {
+        walk_stack(&mut ctx, &stack, &mut frames);
+    }
+    frames
+}
+
+#[cfg(target_arch = "x86_64")]
+const CONTEXT_FLAGS: windows::Win32::System::Diagnostics::Debug::CONTEXT_FLAGS =
+    windows::Win32::System::Diagnostics::Debug::CONTEXT_FULL_AMD64;
+
+#[cfg(target_arch = "x86")]
+const CONTEXT_FLAGS: windows::Win32::System::Diagnostics::Debug::CONTEXT_FLAGS =
+    windows::Win32::System::Diagnostics::Debug::CONTEXT_CONTROL_X86;
+
+#[cfg(target_arch = "x86_64")]
+fn stack_pointer(ctx: &CONTEXT) -> usize { ctx.Rsp as usize }
+
+#[cfg(target_arch = "x86")]
+fn stack_pointer(ctx: &CONTEXT) -> usize { ctx.Esp as usize }
+
+/// 挂起期间复制的栈数据
+struct StackCopy {
+    /// 复制时的栈顶地址
+    base: usize,
+    /// 复制的字节数
+    len: usize,
+    /// 是否复制到了栈底
+    complete: bool,
+    data: Vec<usize>
+}
+
+impl StackCopy {
+    /// 预先分配内存
+    fn new() -> Self {
+        StackCopy {
+            base: 0,
+            len: 0,
+            complete: false,
+            data: vec![0; MAX_STACK_COPY / mem::size_of::<usize>()]
+        }
+    }
+
+    /// 复制栈顶到栈底的数据
+    ///
+    /// # Safety
+    ///
+    /// 在目标线程挂起期间调用，不分配内存
+    unsafe fn capture(&mut self, sp: usize) -> bool {
+        use windows::Win32::System::{
+            Diagnostics::Debug::ReadProcessMemory, Memory::{VirtualQuery, MEMORY_BASIC_INFORMATION}, Threading::GetCurrentProcess
+        };
+
+        let mut info = MEMORY_BASIC_INFORMATION::default();
+        if VirtualQuery(Some(sp as *const c_void), &mut info, mem::size_of_val(&info)) == 0 {
+            return false;
+        }
+        let end = info.BaseAddress as usize + info.RegionSize;
+        let len = end.saturating_sub(sp).min(MAX_STACK_COPY);
+        if len == 0 ||
+            !ReadProcessMemory(
+                GetCurrentProcess(),
+                sp as *const c_void,
+                self.data.as_mut_ptr() as *mut c_void,
+                len,
+                None
+            )
+            .as_bool()
+        {
+            return false;
+        }
+        self.base = sp;
+        self.len = len;
+        self.complete = sp + len == end;
+        true
+    }
+
+    /// 可回溯的栈范围的结束地址
+    ///
+    /// 栈未复制完整时保留余量，避免展开时读取到副本之外
+    fn limit(&self) -> usize {
+        if self.complete {
+            self.base + self.len
+        } else {
+            (self.base + self.len).saturating_sub(UNWIND_MARGIN)
+        }
+    }
+
+    /// 读取原地址处的值
+    fn read(&self, addr: usize) -> Option<usize> {
+        if addr < self.base ||
+            addr % mem::size_of::<usize>() != 0 ||
+            addr + mem::size_of::<usize>() > self.limit()
+        {
+            return None;
+        }
+        Some(self.data[(addr - self.base) / mem::size_of::<usize>()])
+    }
+
+    /// 原地址与副本地址的偏移
+    #[cfg(target_arch = "x86_64")]
+    fn offset(&self) -> usize { (self.data.as_ptr() as usize).wrapping_sub(self.base) }
+
+    /// 是否为原栈中的地址
+    #[cfg(target_arch = "x86_64")]
+    fn contains(&self, addr: usize) -> bool { addr >= self.base && addr < self.base + self.len }
+}
+
+/// 通过展开信息回溯栈帧
+///
+/// 展开时将指向栈的寄存器映射到副本中
+#[cfg(target_arch = "x86_64")]
+unsafe fn walk_stack(ctx: &mut CONTEXT, stack: &StackCopy, frames: &mut Vec<usize>) {
+    use std::ptr;
+    use windows::Win32::System::Diagnostics::Debug::{
+        RtlLookupFunctionEntry, RtlVirtualUnwind, UNW_FLAG_NHANDLER
+    };
+
+    let offset = stack.offset() as u64;
+    while frames.len() < MAX_FRAMES && ctx.Rip != 0 {
+        frames.push(ctx.Rip as usize);
+        let sp = ctx.Rsp;
+        let mut image_base = 0;
+        let entry = RtlLookupFunctionEntry(ctx.Rip, &mut image_base, None);
+        if entry.is_null() {
+            //叶函数，返回地址位于栈顶
+            match stack.read(ctx.Rsp as usize) {
+                Some(rip) => ctx.Rip = rip as u64,
+                None => break
+            }
+            ctx.Rsp += 8;
+        } else {
+            if stack.read(ctx.Rsp as usize).is_none() {
+                break;
+            }
+            for reg in stack_registers(ctx) {
+                if stack.contains(*reg as usize) {
+                    *reg = reg.wrapping_add(offset);
+                }
+            }
+            let mut handler_data = ptr::null_mut();
+            let mut establisher_frame = 0;
+            RtlVirtualUnwind(
+                UNW_FLAG_NHANDLER,
+                image_base,
+                ctx.Rip,
+                entry,
+                ctx,
+                &mut handler_data,
+                &mut establisher_frame,
+                None
+            );
+            for reg in stack_registers(ctx) {
+                if stack.contains(reg.wrapping_sub(offset) as usize) {
+                    *reg = reg.wrapping_sub(offset);
+                }
+            }
+        }
+        //栈指针必须向栈底移动
+        if ctx.Rsp <= sp {
+            break;
+        }
+    }
+}
+
+/// 可能指向栈的寄存器
+#[cfg(target_arch = "x86_64")]
+fn stack_registers(ctx: &mut CONTEXT) -> [&mut u64; 9] {
+    [
+        &mut ctx.Rsp,
+        &mut ctx.Rbp,
+        &mut ctx.Rbx,
+        &mut ctx.Rsi,
+        &mut ctx.Rdi,
+        &mut ctx.R12,
+        &mut ctx.R13,
+        &mut ctx.R14,
+        &mut ctx.R15
+    ]
+}
+
+/// 通过帧指针回溯栈帧
+#[cfg(target_arch = "x86")]
+unsafe fn walk_stack(ctx: &mut CONTEXT, stack: &StackCopy, frames: &mut Vec<usize>) {
+    frames.push(ctx.Eip as usize);
+    let mut sp = ctx.Esp as usize;
+    let mut fp = ctx.Ebp as usize;
+    while frames.len() < MAX_FRAMES && fp >= sp {
+        //`[fp]`为上一帧的帧指针，`[fp + 4]`为返回地址
+        let (Some(next), Some(ret)) = (stack.read(fp), stack.read(fp + mem::size_of::<usize>())) else {
+            break;
+        };
+        if ret == 0 {
+            break;
+        }
+        frames.push(ret);
+        sp = fp + mem::size_of::<usize>() * 2;
+        fp = next;
+    }
+}
+
+/// 解析栈帧的符号
+fn format_frames(frames: &[usize]) -> String {
+    let mut text = String::new();
+    for (idx, &addr) in frames.iter().enumerate() {
+        //返回地址指向调用指令的下一条指令
+        let pc = if idx == 0 {
+            addr
+        } else {
+            addr - 1
+        };
+        let mut resolved = false;
+        backtrace::resolve(pc as *mut c_void, |symbol| {
+            resolved = true;
+            let _ = match symbol.name() {
+                Some(name) => writeln!(text, "{:4}: {}", idx, name),
+                None => writeln!(text, "{:4}: {:#x}", idx, addr)
+            };
+            if let (Some(file), Some(line)) = (symbol.filename(), symbol.lineno()) {
+                let _ = writeln!(text, "             at {}:{}", file.display(), line);
+            }
+        });
+        if !resolved {
+            let _ = writeln!(text, "{:4}: {:#x}", idx, addr);
+        }
+    }
+    text
+}